@@ -1,20 +1,181 @@
 use std::sync::Once;
 
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 use abstutil::Tags;
-use osm2lanes::{get_lane_specs_ltr, MapConfig};
+use osm2lanes::{
+    get_lane_specs_ltr, osm, Direction, DrivingSide, LaneSpec, LaneType, MapConfig, Placement,
+    RoadPosition,
+};
 
 static SETUP_LOGGER: Once = Once::new();
 
 #[wasm_bindgen(js_name = getLaneSpecs)]
 pub fn get_lane_specs(tags: JsValue, config: JsValue) -> Result<String, JsValue> {
+    setup();
+
+    let tags: Tags = serde_wasm_bindgen::from_value(tags)?;
+    let config: MapConfig = serde_wasm_bindgen::from_value(config)?;
+
+    Ok(serde_json::to_string(&get_lane_specs_ltr(&tags, &config)).unwrap())
+}
+
+/// Everything is optional.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct LaneOptions {
+    /// "Left" or "Right", defaulting to "Right"
+    driving_side: Option<DrivingSide>,
+    /// A two-letter ISO country code, used for legal defaults like speed limits
+    country_code: String,
+    inferred_sidewalks: Option<bool>,
+    inferred_kerbs: Option<bool>,
+    bikes_can_use_bus_lanes: Option<bool>,
+    include_railroads: Option<bool>,
+    /// Used to evaluate conditional restrictions, like `2024-06-01T08:00:00`
+    date_time: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LanesOutput {
+    lanes: Vec<LaneOutput>,
+    /// Meters
+    total_width: f64,
+    placement: Placement,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LaneOutput {
+    #[serde(rename = "type")]
+    lt: LaneType,
+    direction: Direction,
+    /// Meters
+    width: f64,
+    /// Like "Left" or "Through". Empty means no restrictions are tagged.
+    turns: Vec<String>,
+}
+
+/// Returns the lanes of a road, left-to-right, along with warnings about the tags. `options` is
+/// optional; see the TypeScript definitions.
+#[wasm_bindgen(js_name = getLanes)]
+pub fn get_lanes(tags: JsValue, options: JsValue) -> Result<JsValue, JsValue> {
+    setup();
+
+    let tags: Tags = serde_wasm_bindgen::from_value(tags)?;
+    let options: LaneOptions = if options.is_undefined() || options.is_null() {
+        LaneOptions::default()
+    } else {
+        serde_wasm_bindgen::from_value(options)?
+    };
+
+    let mut cfg = MapConfig::default();
+    cfg.driving_side = options.driving_side.unwrap_or(DrivingSide::Right);
+    cfg.country_code = options.country_code;
+    cfg.inferred_sidewalks = options.inferred_sidewalks.unwrap_or(cfg.inferred_sidewalks);
+    cfg.inferred_kerbs = options.inferred_kerbs.unwrap_or(cfg.inferred_kerbs);
+    cfg.bikes_can_use_bus_lanes = options
+        .bikes_can_use_bus_lanes
+        .unwrap_or(cfg.bikes_can_use_bus_lanes);
+    cfg.include_railroads = options.include_railroads.unwrap_or(cfg.include_railroads);
+    if let Some(date_time) = options.date_time {
+        cfg.date_time = Some(
+            NaiveDateTime::parse_from_str(&date_time, "%Y-%m-%dT%H:%M:%S")
+                .map_err(|err| JsValue::from_str(&format!("Bad date_time {date_time}: {err}")))?,
+        );
+    }
+
+    let specs = get_lane_specs_ltr(&tags, &cfg);
+    let mut warnings = Vec::new();
+    let placement = match Placement::parse(&tags) {
+        Ok(placement) => placement,
+        Err(err) => {
+            warnings.push(format!("Ignoring placement: {err}"));
+            Placement::Consistent(RoadPosition::Center)
+        }
+    };
+    warnings.extend(check_tags(&tags, &specs, &cfg));
+
+    let output = LanesOutput {
+        total_width: specs.iter().map(|spec| spec.width.inner_meters()).sum(),
+        lanes: specs
+            .into_iter()
+            .map(|spec| LaneOutput {
+                lt: spec.lt,
+                direction: spec.dir,
+                width: spec.width.inner_meters(),
+                turns: spec
+                    .allowed_turns
+                    .iter()
+                    .map(|turn| format!("{turn:?}"))
+                    .collect(),
+            })
+            .collect(),
+        placement,
+        warnings,
+    };
+    output
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|err| JsValue::from_str(&err.to_string()))
+}
+
+/// Points out tags that were likely misunderstood or guessed
+fn check_tags(tags: &Tags, specs: &[LaneSpec], cfg: &MapConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !tags.contains_key(osm::HIGHWAY) && !tags.contains_key("railway") {
+        warnings.push("There's no highway tag, so the lanes are a guess".to_string());
+    }
+    if let Some(lanes) = tags.get("lanes").and_then(|x| x.parse::<usize>().ok()) {
+        let counted = specs
+            .iter()
+            .filter(|spec| matches!(spec.lt, LaneType::Driving | LaneType::Bus))
+            .count();
+        if lanes != counted {
+            warnings.push(format!(
+                "lanes={lanes}, but {counted} driving and bus lanes were found"
+            ));
+        }
+    }
+    if cfg.inferred_sidewalks && !tags.contains_key("sidewalk") {
+        warnings.push("No sidewalk tag, so sidewalks were inferred".to_string());
+    }
+    if cfg.country_code.is_empty() {
+        warnings.push("No country_code, so legal defaults are a guess".to_string());
+    }
+    warnings
+}
+
+fn setup() {
     SETUP_LOGGER.call_once(|| console_log::init_with_level(log::Level::Info).unwrap());
     // Panics shouldn't happen, but if they do, console.log them.
     console_error_panic_hook::set_once();
+}
 
-    let tags: Tags = serde_wasm_bindgen::from_value(tags)?;
-    let config: MapConfig = serde_wasm_bindgen::from_value(config)?;
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export interface LaneOptions {
+  driving_side?: "Left" | "Right";
+  country_code?: string;
+  inferred_sidewalks?: boolean;
+  inferred_kerbs?: boolean;
+  bikes_can_use_bus_lanes?: boolean;
+  include_railroads?: boolean;
+  date_time?: string;
+}
+
+export interface Lane {
+  type: string | { [kind: string]: string };
+  direction: "Forward" | "Backward";
+  width: number;
+  turns: string[];
+}
 
-    Ok(serde_json::to_string_pretty(&get_lane_specs_ltr(&tags, &config)).unwrap())
+export interface Lanes {
+  lanes: Lane[];
+  total_width: number;
+  placement: any;
+  warnings: string[];
 }
+"#;
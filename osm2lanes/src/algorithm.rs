@@ -11,18 +11,33 @@ use muv_osm::{
         Lane, LaneIndex, LaneVariant,
     },
     units::{self, Quantity},
-    AccessLevel, Conditional, Lifecycle, Location, TMode, TModes, Tag, Taglike, Vehicle,
+    AccessLevel, Conditional, Location, TMode, TModes, Tag, Taglike, Vehicle,
 };
 
 use crate::{
     osm::{self, HIGHWAY},
-    BufferType, Direction, DrivingSide, LaneSpec, LaneType, MapConfig, ParkingType, TurnDirection,
+    BufferType, Direction, DrivingSide, LaneSpec, LaneType, MapConfig, ParkingType, ShoulderAccess,
+    TurnDirection, HARD_SHOULDER_THICKNESS,
 };
 
 /// Purely from OSM tags, determine the lanes that a road segment has.
 pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
     let mut tags = tags;
 
+    // Roads under construction or proposed get the lanes they'll have once they're built.
+    // osm2streets tracks their lifecycle on the road instead.
+    let mut planned_tags;
+    let planned = ["construction", "proposed"]
+        .into_iter()
+        .find(|value| tags.is(HIGHWAY, value))
+        .and_then(|value| tags.get(value))
+        .cloned();
+    if let Some(planned) = planned {
+        planned_tags = tags.clone();
+        planned_tags.insert(HIGHWAY, planned);
+        tags = &planned_tags;
+    }
+
     // This'll do weird things for the special cases of railways and cycleways/footways, but the
     // added tags will be ignored, so it doesn't matter too much.
     let mut cloned_tags;
@@ -42,6 +57,7 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         (country, _) => country,
     };
 
+    let osm_tags = tags;
     let tags: Tag = tags.inner().iter().collect();
     let lanes = lanes(&tags, &[&country]).unwrap();
 
@@ -69,6 +85,10 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
                 dir: direction,
                 width: LaneSpec::typical_lane_width(lt),
                 allowed_turns: EnumSet::new(),
+                is_virtual: false,
+                shoulder_access: None,
+                max_speed: None,
+                bus_hours: None,
                 lane: None,
             });
         }
@@ -76,15 +96,258 @@ pub fn get_lane_specs_ltr(tags: &Tags, cfg: &MapConfig) -> Vec<LaneSpec> {
         specs.push(from_lane(lane, highway_tag, direction, cfg.date_time));
     }
 
-    if lanes.lifecycle == Lifecycle::Construction {
-        for lane in &mut specs {
-            lane.lt = LaneType::Construction;
-        }
+    apply_lane_rules(&mut specs, osm_tags);
+    apply_center_turn_lanes(&mut specs, osm_tags);
+
+    if matches!(
+        highway_tag,
+        "motorway" | "motorway_link" | "trunk" | "trunk_link"
+    ) {
+        apply_emergency_lanes(&mut specs, osm_tags);
+        apply_hard_shoulders(&mut specs, osm_tags);
     }
 
     specs
 }
 
+/// Lanes marked with `emergency:lanes` and closed to general traffic become `Shoulder` lanes that
+/// emergency vehicles can use. Without this, they're treated as a shoulder anyone can use or as
+/// construction, depending on how access is tagged.
+fn apply_emergency_lanes(specs: &mut [LaneSpec], tags: &Tags) {
+    for (suffix, dir) in [
+        ("", None),
+        (":forward", Some(Direction::Forward)),
+        (":backward", Some(Direction::Backward)),
+    ] {
+        let Some(emergency) = tags.get(&format!("emergency:lanes{suffix}")) else {
+            continue;
+        };
+        let emergency: Vec<&str> = emergency.split('|').collect();
+        let closed: Vec<bool> = (0..emergency.len())
+            .map(|idx| {
+                ["access", "motor_vehicle", "motorcar"].iter().any(|key| {
+                    tags.get(&format!("{key}:lanes{suffix}"))
+                        .and_then(|x| x.split('|').nth(idx))
+                        == Some("no")
+                })
+            })
+            .collect();
+
+        let Some(candidates) = lanes_matching_tag(specs, dir, emergency.len()) else {
+            // The tagging is inconsistent, so don't guess
+            continue;
+        };
+
+        for ((idx, value), closed) in candidates.into_iter().zip(emergency).zip(closed) {
+            if !closed || !matches!(value, "yes" | "designated") {
+                continue;
+            }
+            let spec = &mut specs[idx];
+            spec.lt = LaneType::Shoulder;
+            spec.allowed_turns = EnumSet::new();
+            spec.shoulder_access = Some(ShoulderAccess {
+                vehicles: false,
+                emergency: true,
+            });
+            if spec.lane.as_ref().map_or(true, |lane| lane.width.is_none()) {
+                spec.width = HARD_SHOULDER_THICKNESS;
+            }
+        }
+    }
+}
+
+/// Finds the lanes that the values of a `:lanes` tag refer to, in order. Lanes in `:lanes` tags
+/// are counted from the left in their direction of travel, or across the whole road if `dir` is
+/// `None`. Muv may have already turned some tagged lanes into shoulders, so only skip shoulders if
+/// the count doesn't match otherwise. Returns `None` if the number of values doesn't match the
+/// lanes.
+fn lanes_matching_tag(
+    specs: &[LaneSpec],
+    dir: Option<Direction>,
+    count: usize,
+) -> Option<Vec<usize>> {
+    for include_shoulders in [true, false] {
+        let mut candidates: Vec<usize> = specs
+            .iter()
+            .enumerate()
+            .filter(|(_, spec)| {
+                (spec.lt.is_tagged_by_lanes_suffix()
+                    || (include_shoulders && spec.lt == LaneType::Shoulder))
+                    && dir.map(|dir| spec.dir == dir).unwrap_or(true)
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        if dir == Some(Direction::Backward) {
+            candidates.reverse();
+        }
+        if candidates.len() == count {
+            return Some(candidates);
+        }
+    }
+    None
+}
+
+/// Records per-lane rules that don't change the lane type: speed limits from `maxspeed:lanes`,
+/// and the hours that a bus lane applies from `bus:lanes:conditional` and `psv:lanes:conditional`.
+fn apply_lane_rules(specs: &mut [LaneSpec], tags: &Tags) {
+    for (suffix, dir) in [
+        ("", None),
+        (":forward", Some(Direction::Forward)),
+        (":backward", Some(Direction::Backward)),
+    ] {
+        if let Some(speeds) = tags.get(&format!("maxspeed:lanes{suffix}")) {
+            let speeds: Vec<&str> = speeds.split('|').collect();
+            if let Some(candidates) = lanes_matching_tag(specs, dir, speeds.len()) {
+                for (idx, value) in candidates.into_iter().zip(speeds) {
+                    if let Some(speed) = crate::units::parse_speed(value) {
+                        specs[idx].max_speed = Some(speed);
+                    }
+                }
+            }
+        }
+
+        for key in ["bus", "psv"] {
+            let Some(values) = tags.get(&format!("{key}:lanes{suffix}:conditional")) else {
+                continue;
+            };
+            let values: Vec<&str> = values.split('|').collect();
+            let Some(candidates) = lanes_matching_tag(specs, dir, values.len()) else {
+                continue;
+            };
+            for (idx, value) in candidates.into_iter().zip(values) {
+                if specs[idx].bus_hours.is_some() {
+                    continue;
+                }
+                specs[idx].bus_hours = split_conditional(value)
+                    .into_iter()
+                    .find(|(value, _)| matches!(*value, "designated" | "yes"))
+                    .map(|(_, condition)| condition.to_string());
+            }
+        }
+    }
+}
+
+/// A lane shared by turning traffic in both directions allows the turns from
+/// `turn:lanes:both_ways` or `turn:both_ways`, the same way from either end. When those aren't
+/// tagged, like with `centre_turn_lane=yes`, it's a left-turn lane, which is the only kind of
+/// center turn lane in practice.
+fn apply_center_turn_lanes(specs: &mut [LaneSpec], tags: &Tags) {
+    let tagged = tags
+        .get("turn:lanes:both_ways")
+        .or_else(|| tags.get("turn:both_ways"))
+        // Multiple lanes in the middle aren't supported, so use the first
+        .and_then(|x| TurnDirection::parse_set(x.split('|').next().unwrap_or_default()).ok())
+        .filter(|turns| !turns.is_empty());
+    for spec in specs {
+        if spec.lt == LaneType::SharedLeftTurn && spec.allowed_turns.is_empty() {
+            spec.allowed_turns = tagged.unwrap_or(EnumSet::only(TurnDirection::Left));
+        }
+    }
+}
+
+/// Splits a value like `designated @ (Mo-Fr 07:00-09:00; Sa 08:00-12:00); no @ wet` into each
+/// value and its condition, without parentheses.
+fn split_conditional(input: &str) -> Vec<(&str, &str)> {
+    let mut pieces = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth == 0 => {
+                pieces.push(&input[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push(&input[start..]);
+
+    pieces
+        .into_iter()
+        .filter_map(|piece| {
+            let (value, condition) = piece.split_once('@')?;
+            let condition = condition.trim();
+            let condition = condition
+                .strip_prefix('(')
+                .and_then(|x| x.strip_suffix(')'))
+                .unwrap_or(condition);
+            Some((value.trim(), condition.trim()))
+        })
+        .collect()
+}
+
+/// `shoulder=*` on motorways and trunk roads describes a hard shoulder, wide enough to stop a
+/// vehicle on. Only emergency vehicles may drive on it, unless `shoulder:access` says otherwise.
+fn apply_hard_shoulders(specs: &mut Vec<LaneSpec>, tags: &Tags) {
+    let shoulder = tags.get("shoulder").map(|x| x.as_str()).unwrap_or("no");
+    for left in [true, false] {
+        let side = if left { "left" } else { "right" };
+        let present = match tags
+            .get(&format!("shoulder:{side}"))
+            .or_else(|| tags.get("shoulder:both"))
+        {
+            Some(value) => value == "yes",
+            None => shoulder == "yes" || shoulder == "both" || shoulder == side,
+        };
+        if !present || specs.is_empty() {
+            continue;
+        }
+
+        // Any per-side tag wins over the tag for both sides
+        let get = |key: &str| {
+            tags.get(&format!("shoulder:{side}:{key}"))
+                .or_else(|| tags.get(&format!("shoulder:{key}")))
+        };
+        let access = ShoulderAccess {
+            vehicles: get("access").map_or(false, |x| {
+                matches!(x.as_str(), "yes" | "designated" | "permissive")
+            }),
+            emergency: get("access:emergency").map_or(true, |x| x != "no"),
+        };
+        let tagged_width = get("width").and_then(|x| crate::units::parse_distance(x));
+
+        // The shoulder belongs between the carriageway and anything on the roadside
+        let edge = if left {
+            specs.iter().position(|spec| spec.lt.is_roadway())
+        } else {
+            specs.iter().rposition(|spec| spec.lt.is_roadway())
+        };
+        let Some(edge) = edge else {
+            continue;
+        };
+        let idx = if specs[edge].lt == LaneType::Shoulder {
+            edge
+        } else {
+            let idx = if left { edge } else { edge + 1 };
+            specs.insert(
+                idx,
+                LaneSpec {
+                    lt: LaneType::Shoulder,
+                    dir: specs[edge].dir,
+                    width: HARD_SHOULDER_THICKNESS,
+                    allowed_turns: EnumSet::new(),
+                    is_virtual: false,
+                    shoulder_access: None,
+                    max_speed: None,
+                    bus_hours: None,
+                    lane: None,
+                },
+            );
+            idx
+        };
+
+        let spec = &mut specs[idx];
+        spec.shoulder_access = Some(access);
+        if let Some(width) = tagged_width {
+            spec.width = width;
+        } else if spec.lane.as_ref().map_or(true, |lane| lane.width.is_none()) {
+            spec.width = HARD_SHOULDER_THICKNESS;
+        }
+    }
+}
+
 /// Get the direction of traffic for the lane.
 /// This currently used for bidirectional lanes (such as sidewalks)
 /// as osm2lanes doesn't have a mapping for this yet.
@@ -132,6 +395,10 @@ fn from_lane(
         dir,
         width,
         allowed_turns: turns,
+        is_virtual: false,
+        shoulder_access: None,
+        max_speed: None,
+        bus_hours: None,
         lane: Some(lane),
     }
 }
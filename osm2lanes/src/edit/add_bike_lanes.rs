@@ -82,6 +82,10 @@ impl LaneSpec {
                     dir,
                     width: LaneSpec::typical_lane_width(LaneType::Biking),
                     allowed_turns: Default::default(),
+                    is_virtual: false,
+                    shoulder_access: None,
+                    max_speed: None,
+                    bus_hours: None,
                     lane: None,
                 };
                 if let Some(buffer) = buffer_type {
@@ -92,6 +96,10 @@ impl LaneSpec {
                             dir,
                             width: LaneSpec::typical_lane_width(LaneType::Buffer(buffer)),
                             allowed_turns: Default::default(),
+                            is_virtual: false,
+                            shoulder_access: None,
+                            max_speed: None,
+                            bus_hours: None,
                             lane: None,
                         },
                     );
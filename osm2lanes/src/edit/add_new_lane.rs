@@ -102,6 +102,10 @@ impl LaneSpec {
                 dir,
                 width: LaneSpec::typical_lane_widths(lt, highway_type)[0].0,
                 allowed_turns: Default::default(),
+                is_virtual: false,
+                shoulder_access: None,
+                max_speed: None,
+                bus_hours: None,
                 lane: None,
             },
         );
@@ -27,6 +27,10 @@ impl LaneSpec {
                 // Dummy
                 width: Distance::ZERO,
                 allowed_turns: Default::default(),
+                is_virtual: false,
+                shoulder_access: None,
+                max_speed: None,
+                bus_hours: None,
                 lane: None,
             })
             .collect()
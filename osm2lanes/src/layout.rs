@@ -0,0 +1,275 @@
+use abstutil::Tags;
+use geom::Distance;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    get_lane_specs_ltr, osm, BufferType, Direction, DrivingSide, LaneSpec, LaneType, MapConfig,
+    ParkingType, Placement, RoadPosition,
+};
+
+/// Regional context needed to interpret OSM tags.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Locale {
+    pub driving_side: DrivingSide,
+    /// The [two-letter ISO country code](https://en.wikipedia.org/wiki/ISO_3166-1_alpha-2), used
+    /// for legal defaults. If empty, a country driving on `driving_side` is assumed.
+    pub country_code: String,
+}
+
+impl Locale {
+    pub fn new(driving_side: DrivingSide, country_code: &str) -> Self {
+        Self {
+            driving_side,
+            country_code: country_code.to_string(),
+        }
+    }
+
+    fn to_config(&self) -> MapConfig {
+        let mut cfg = MapConfig::default();
+        cfg.driving_side = self.driving_side;
+        cfg.country_code = self.country_code.clone();
+        cfg
+    }
+}
+
+/// The cross-section of a road, parsed from the tags of one OSM way.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoadLayout {
+    /// The OSM `highway` tag, or `railway` for railways
+    pub highway: String,
+    /// Left-to-right, relative to the direction the OSM way points
+    pub lanes: Vec<LaneSpec>,
+    /// Where the OSM way is drawn along the road
+    pub placement: Placement,
+}
+
+/// Determines the cross-section of a road purely from OSM tags.
+pub fn parse(tags: &Tags, locale: &Locale) -> RoadLayout {
+    RoadLayout {
+        highway: tags
+            .get(osm::HIGHWAY)
+            .or_else(|| tags.get("railway"))
+            .cloned()
+            .unwrap_or_default(),
+        lanes: get_lane_specs_ltr(tags, &locale.to_config()),
+        placement: Placement::parse(tags).unwrap_or(Placement::Consistent(RoadPosition::Center)),
+    }
+}
+
+impl RoadLayout {
+    pub fn total_width(&self) -> Distance {
+        self.lanes.iter().map(|l| l.width).sum()
+    }
+
+    /// None if the road is bidirectional for driving, or if there are no driving lanes at all.
+    pub fn oneway_for_driving(&self) -> Option<Direction> {
+        LaneSpec::oneway_for_driving(&self.lanes)
+    }
+
+    /// Describes this layout as OSM tags. Passing the result back through `parse` reproduces the
+    /// type, direction, and turns of each lane, as long as the layout is something the tags can
+    /// express: driving lanes in the middle, optionally a shared center turn lane, and bus lanes,
+    /// bike lanes, parking, and sidewalks on the sides. Widths, placement, and buffers other than
+    /// kerbs aren't described.
+    pub fn to_tags(&self) -> Tags {
+        let mut tags = Tags::empty();
+        tags.insert(osm::HIGHWAY, self.highway.clone());
+
+        // Kerbs are inferred from the other tags
+        let lanes: Vec<&LaneSpec> = self
+            .lanes
+            .iter()
+            .filter(|l| l.lt != LaneType::Buffer(BufferType::Curb))
+            .collect();
+
+        let is_core = |l: &&LaneSpec| matches!(l.lt, LaneType::Driving | LaneType::SharedLeftTurn);
+        let (first_core, last_core) = match (
+            lanes.iter().position(is_core),
+            lanes.iter().rposition(is_core),
+        ) {
+            (Some(first), Some(last)) => (first, last),
+            // Just a path or a railway; the highway tag says almost enough
+            _ => {
+                if lanes
+                    .iter()
+                    .all(|l| !l.lt.is_for_moving_vehicles() || l.dir == Direction::Forward)
+                    && lanes.iter().any(|l| l.lt.is_for_moving_vehicles())
+                {
+                    tags.insert("oneway", "yes");
+                }
+                return tags;
+            }
+        };
+
+        let core = &lanes[first_core..=last_core];
+        let num_fwd = core
+            .iter()
+            .filter(|l| l.lt == LaneType::Driving && l.dir == Direction::Forward)
+            .count();
+        let num_back = core
+            .iter()
+            .filter(|l| l.lt == LaneType::Driving && l.dir == Direction::Backward)
+            .count();
+        let center_turn = core.iter().any(|l| l.lt == LaneType::SharedLeftTurn);
+
+        tags.insert("lanes", (num_fwd + num_back).to_string());
+        if num_back == 0 {
+            tags.insert("oneway", "yes");
+        } else if num_fwd == 0 {
+            tags.insert("oneway", "-1");
+        } else {
+            tags.insert("oneway", "no");
+            tags.insert("lanes:forward", num_fwd.to_string());
+            tags.insert("lanes:backward", num_back.to_string());
+        }
+        if center_turn {
+            tags.insert("centre_turn_lane", "yes");
+        }
+
+        // Turns are listed left-to-right from the perspective of traffic in each direction
+        let fwd_turns: Vec<&LaneSpec> = core
+            .iter()
+            .filter(|l| l.lt == LaneType::Driving && l.dir == Direction::Forward)
+            .cloned()
+            .collect();
+        let back_turns: Vec<&LaneSpec> = core
+            .iter()
+            .rev()
+            .filter(|l| l.lt == LaneType::Driving && l.dir == Direction::Backward)
+            .cloned()
+            .collect();
+        if num_back == 0 {
+            insert_turn_lanes(&mut tags, "turn:lanes", &fwd_turns);
+        } else if num_fwd == 0 {
+            insert_turn_lanes(&mut tags, "turn:lanes", &back_turns);
+        } else {
+            insert_turn_lanes(&mut tags, "turn:lanes:forward", &fwd_turns);
+            insert_turn_lanes(&mut tags, "turn:lanes:backward", &back_turns);
+        }
+
+        // Outermost lanes come last on both sides
+        let mut left_side: Vec<&LaneSpec> = lanes[0..first_core].to_vec();
+        left_side.reverse();
+        let right_side: Vec<&LaneSpec> = lanes[last_core + 1..].to_vec();
+
+        let mut sidewalks = (false, false);
+        for (side, lanes, has_sidewalk) in [
+            ("left", left_side, &mut sidewalks.0),
+            ("right", right_side, &mut sidewalks.1),
+        ] {
+            for lane in lanes {
+                match lane.lt {
+                    LaneType::Sidewalk => {
+                        *has_sidewalk = true;
+                    }
+                    LaneType::Biking => {
+                        tags.insert(format!("cycleway:{side}"), "lane");
+                    }
+                    LaneType::Bus => {
+                        tags.insert(format!("busway:{side}"), "lane");
+                    }
+                    LaneType::Parking(parking) => {
+                        tags.insert(
+                            format!("parking:lane:{side}"),
+                            match parking {
+                                ParkingType::Parallel => "parallel",
+                                ParkingType::Diagonal => "diagonal",
+                                ParkingType::Perpendicular => "perpendicular",
+                            },
+                        );
+                    }
+                    _ => {}
+                }
+            }
+        }
+        tags.insert(
+            "sidewalk",
+            match sidewalks {
+                (true, true) => "both",
+                (true, false) => "left",
+                (false, true) => "right",
+                (false, false) => "no",
+            },
+        );
+
+        tags
+    }
+}
+
+fn insert_turn_lanes(tags: &mut Tags, key: &str, lanes: &[&LaneSpec]) {
+    if lanes.iter().all(|l| l.allowed_turns.is_empty()) {
+        return;
+    }
+    let value = lanes
+        .iter()
+        .map(|l| {
+            if l.allowed_turns.is_empty() {
+                "none".to_string()
+            } else {
+                l.allowed_turns
+                    .iter()
+                    .map(|t| t.tag_value())
+                    .collect::<Vec<_>>()
+                    .join(";")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+    tags.insert(key, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::tags_from_pairs;
+
+    #[test]
+    fn test_tags_roundtrip() {
+        for (input, driving_side) in [
+            (
+                vec![
+                    "highway=residential",
+                    "lanes=2",
+                    "oneway=yes",
+                    "sidewalk=both",
+                    "cycleway:left=lane",
+                ],
+                DrivingSide::Right,
+            ),
+            (
+                vec!["highway=residential", "oneway=no", "sidewalk=both"],
+                DrivingSide::Left,
+            ),
+            (
+                vec![
+                    "highway=secondary_link",
+                    "lanes=2",
+                    "oneway=yes",
+                    "turn:lanes=left;through|right",
+                ],
+                DrivingSide::Right,
+            ),
+            (vec!["highway=cycleway", "oneway=yes"], DrivingSide::Left),
+        ] {
+            let tags = tags_from_pairs(&input);
+            let locale = Locale::new(driving_side, "");
+
+            let layout = parse(&tags, &locale);
+            let roundtrip = parse(&layout.to_tags(), &locale);
+            let describe = |layout: &RoadLayout| {
+                layout
+                    .lanes
+                    .iter()
+                    .map(|l| (l.lt, l.dir, l.allowed_turns))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                describe(&layout),
+                describe(&roundtrip),
+                "roundtrip of {:?} produced {:?}",
+                input,
+                layout.to_tags()
+            );
+        }
+    }
+}
@@ -1,29 +1,45 @@
+//! osm2lanes determines the cross-section of a road -- its lanes, their direction, width, and
+//! allowed turns -- purely from the tags of one OSM way. It doesn't need the rest of the street
+//! network; see osm2streets for that.
+//!
+//! The stable entry point is [`parse`], which returns a [`RoadLayout`]. [`RoadLayout::to_tags`]
+//! goes the other way. The types these use ([`RoadLayout`], [`Locale`], [`LaneSpec`],
+//! [`LaneType`], [`Direction`], [`DrivingSide`], [`Placement`], [`TurnDirection`]) follow semver:
+//! until 1.0, breaking changes to them bump the minor version. osm2streets re-exports these types
+//! rather than defining its own, so there's one source of truth. Everything else public, like
+//! [`get_lane_specs_ltr`] and [`MapConfig`], is used by osm2streets and may change more freely.
+
 #[macro_use]
 extern crate anyhow;
 
 mod algorithm;
 mod edit;
+mod layout;
 pub mod osm;
 mod placement;
 #[cfg(test)]
 mod tests;
 mod turns;
+pub mod units;
 
 use chrono::NaiveDateTime;
 use enumset::{EnumSet, EnumSetType};
 use muv_osm::lanes::Lane;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Speed};
 
 pub use algorithm::get_lane_specs_ltr;
+pub use layout::{parse, Locale, RoadLayout};
 
 pub const NORMAL_LANE_THICKNESS: Distance = Distance::const_meters(3.0);
 const SERVICE_ROAD_LANE_THICKNESS: Distance = Distance::const_meters(2.0);
 pub const SIDEWALK_THICKNESS: Distance = Distance::const_meters(1.5);
 const SHOULDER_THICKNESS: Distance = Distance::const_meters(0.5);
+const HARD_SHOULDER_THICKNESS: Distance = Distance::const_meters(2.5);
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum LaneType {
@@ -285,10 +301,35 @@ pub struct LaneSpec {
     /// (though local rules might still dictate restrictions).
     /// Turns for specific vehicle types (`turn:bus:lanes` and such) are not yet captured.
     pub allowed_turns: EnumSet<TurnDirection>,
+    /// A lane that only describes access, like contraflow cycling permission or an emergency
+    /// corridor, without taking up any space. Virtual lanes have zero width and aren't drawn, but
+    /// are used for routing and exports.
+    #[serde(default, rename = "virtual")]
+    pub is_virtual: bool,
+    /// Only set for `Shoulder` lanes on motorways and trunk roads that are tagged as hard
+    /// shoulders or emergency lanes. Other shoulders are just the unmarked edge of a road.
+    #[serde(default)]
+    pub shoulder_access: Option<ShoulderAccess>,
+    /// From `maxspeed:lanes`, when this lane's limit is tagged separately from the road's
+    #[serde(default)]
+    pub max_speed: Option<Speed>,
+    /// When a bus lane only applies at some times, from `bus:lanes:conditional` or
+    /// `psv:lanes:conditional`, in `opening_hours` syntax like `Mo-Fr 07:00-09:00`
+    #[serde(default)]
+    pub bus_hours: Option<String>,
 
     pub lane: Option<Lane>,
 }
 
+/// Who may drive on a hard shoulder or emergency lane
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShoulderAccess {
+    /// General traffic may drive here, like hard shoulder running during peak hours
+    pub vehicles: bool,
+    /// Emergency vehicles may drive here
+    pub emergency: bool,
+}
+
 impl LaneSpec {
     /// For a given lane type, returns some likely widths. This may depend on the OSM highway type
     /// of the road. The first value returned will be used as a default.
@@ -367,6 +408,31 @@ impl LaneSpec {
         Self::typical_lane_widths(lt, "road")[0].0
     }
 
+    /// Describes lanes compactly, left to right, for tests and logs. Each lane is its `to_char`,
+    /// then `^` or `v` for forward or backward lanes for moving vehicles, then the width in meters.
+    /// For example, `s1.5 dv3.0 d^3.0 b^1.5 s1.5`.
+    pub fn cross_section_sketch(lanes: &[LaneSpec]) -> String {
+        lanes
+            .iter()
+            .map(|lane| {
+                let dir = if !lane.lt.is_for_moving_vehicles() {
+                    ""
+                } else if lane.dir == Direction::Forward {
+                    "^"
+                } else {
+                    "v"
+                };
+                format!(
+                    "{}{}{:.1}",
+                    lane.lt.to_char(),
+                    dir,
+                    lane.width.inner_meters()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// None if bidirectional. If it's one-way, which direction is that relative to the road?
     /// (Usually forwards)
     pub fn oneway_for_driving(lanes: &[LaneSpec]) -> Option<Direction> {
@@ -543,7 +609,9 @@ pub enum DrivingSide {
     Left,
 }
 
+/// Any fields missing when deserializing take their default value.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MapConfig {
     /// If true, driving happens on the right side of the road (USA). If false, on the left
     /// (Australia).
@@ -575,10 +643,48 @@ pub struct MapConfig {
     pub include_railroads: bool,
     pub inferred_kerbs: bool,
     pub date_time: Option<NaiveDateTime>,
+    /// When two roads are joined at a degenerate intersection and meet at a sharp angle, the
+    /// kink is smoothed into a curve. The curve strays at most this far from the original
+    /// geometry. Zero, the default, disables smoothing.
+    pub degenerate_smoothing_max_deviation: Distance,
+    /// After splitting OSM ways into roads, simplify each road's geometry, removing points that
+    /// stray less than this from the result. Endpoints never move, and roads won't start crossing
+    /// themselves. Zero disables this.
+    pub reference_line_simplification: Distance,
+    /// Used by the `SmoothRoadGeometry` transformation. Sharp corners along roads are smoothed
+    /// into curves straying at most this far from the original corner.
+    pub road_smoothing_max_deviation: Distance,
+    /// Which `highway` values become roads
+    pub highway_filter: HighwayFilter,
+    /// The shape of the intersection at the end of dead-end roads
+    pub dead_end_cap: DeadEndCap,
+    /// Besides roads tagged `junction=intersection`, the `CollapseShortRoads` transformation
+    /// collapses roads shorter than this, after trimming. Zero disables this.
+    pub short_road_threshold: Distance,
+    /// Roads made from these OSM ways are never collapsed by `CollapseShortRoads`, even if
+    /// they're tagged `junction=intersection` or are short.
+    pub do_not_collapse: BTreeSet<osm::WayID>,
+    /// How to calculate the polygon of every intersection
+    pub intersection_geometry: IntersectionGeometry,
+    /// Use a different `IntersectionGeometry` for intersections containing these OSM nodes, to
+    /// work around problems with particular junctions
+    pub intersection_geometry_overrides: BTreeMap<osm::NodeID, IntersectionGeometry>,
+    /// What to do with paths inside buildings, like mall corridors and station concourses
+    pub indoor_paths: IndoorPaths,
+    /// Keep rivers, canals, and railways that aren't imported as roads, so blocks can be split
+    /// where they cross. See `StreetNetwork::block_barriers`.
+    pub block_barriers: bool,
+    /// Keep a copy of the network before any transformations, available through
+    /// `StreetNetwork::original`. This roughly doubles memory usage.
+    pub keep_original: bool,
+    /// Don't collapse short roads or degenerate intersections when a road name would be lost or
+    /// two differently named roads would be merged. Otherwise, merged roads keep the other names
+    /// in `alt_names`.
+    pub preserve_road_names: bool,
 }
 
-impl MapConfig {
-    pub fn default() -> Self {
+impl Default for MapConfig {
+    fn default() -> Self {
         Self {
             // Just a dummy value that'll be set later
             driving_side: DrivingSide::Right,
@@ -592,6 +698,117 @@ impl MapConfig {
             include_railroads: true,
             inferred_kerbs: true,
             date_time: None,
+            degenerate_smoothing_max_deviation: Distance::ZERO,
+            reference_line_simplification: Distance::ZERO,
+            road_smoothing_max_deviation: Distance::meters(2.0),
+            highway_filter: HighwayFilter::default(),
+            dead_end_cap: DeadEndCap::Square,
+            short_road_threshold: Distance::ZERO,
+            do_not_collapse: BTreeSet::new(),
+            intersection_geometry: IntersectionGeometry::CornerTrim,
+            intersection_geometry_overrides: BTreeMap::new(),
+            indoor_paths: IndoorPaths::Include,
+            block_barriers: false,
+            keep_original: false,
+            preserve_road_names: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeadEndCap {
+    /// A square piece of the end of the road
+    Square,
+    /// A semicircle past the end of the road
+    Rounded,
+    /// Like `Rounded`, but where there's a `highway=turning_circle` node, a circular bulb wide
+    /// enough to turn around in
+    TurningCircles,
+}
+
+/// The strategy for calculating intersection polygons and trimming roads back from them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntersectionGeometry {
+    /// Trim roads back to where their edges meet, and connect those corners. This handles most
+    /// intersections well.
+    #[default]
+    CornerTrim,
+    /// Like `CornerTrim`, but use the convex hull of the result. This hides jagged or
+    /// self-intersecting polygons at messy junctions, at the cost of covering some extra space.
+    /// Roads are trimmed back to the edge of the hull.
+    ConvexHull,
+    /// Use the smallest `area:highway` polygon mapped in OSM containing the point where all the
+    /// roads meet, trimming roads to where they leave it. Falls back to `CornerTrim` if there's no
+    /// such area.
+    MappedArea,
+    /// Like `CornerTrim`, but when that fails or produces a polygon crossing itself, buffer the
+    /// end of every road and use the union of those pieces, trimming roads to where they leave
+    /// it. This always produces a valid polygon, even on pathological input.
+    BufferUnionFallback,
+}
+
+/// Paths count as indoors if they're tagged `indoor` (with any value besides `no`),
+/// `highway=corridor`, or both `level` and `covered=yes`. A `level` alone isn't enough; it's also
+/// used for ramps and bridges outside.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IndoorPaths {
+    /// Import them like any other path
+    #[default]
+    Include,
+    /// Don't import them
+    Exclude,
+    /// Don't make them roads, but keep their geometry in `StreetNetwork::indoor_paths`
+    Separate,
+}
+
+/// Controls which OSM `highway` values are imported as roads. By default, the values in
+/// `HighwayFilter::DEFAULT` are imported.
+///
+/// Roads under construction (`highway=construction` with `construction=<value>`) and proposed
+/// roads (`highway=proposed` with `proposed=<value>`) are filtered by that value, and get the lanes
+/// they'll have once built; osm2streets records their lifecycle on the road. Construction is
+/// imported by default; exclude `construction` to drop it. Proposed roads are dropped unless
+/// `proposed` is included. Disused roads (`disused:highway=<value>`) are dropped unless `disused`
+/// is included.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HighwayFilter {
+    /// Values imported in addition to the defaults, like `bridleway`
+    pub include: BTreeSet<String>,
+    /// Values never imported, even if they're in the defaults
+    pub exclude: BTreeSet<String>,
+}
+
+impl HighwayFilter {
+    pub const DEFAULT: [&'static str; 22] = [
+        "busway",
+        "construction",
+        "cycleway",
+        "footway",
+        "living_street",
+        "motorway",
+        "motorway_link",
+        "path",
+        "pedestrian",
+        "primary",
+        "primary_link",
+        "residential",
+        "secondary",
+        "secondary_link",
+        "service",
+        "steps",
+        "tertiary",
+        "tertiary_link",
+        "track",
+        "trunk",
+        "trunk_link",
+        "unclassified",
+    ];
+
+    /// Should ways with this `highway` value become roads?
+    pub fn allows(&self, highway: &str) -> bool {
+        if self.exclude.contains(highway) {
+            return false;
         }
+        Self::DEFAULT.contains(&highway) || self.include.contains(highway)
     }
 }
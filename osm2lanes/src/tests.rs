@@ -1,12 +1,30 @@
 use std::sync::Once;
 
-use abstutil::Tags;
 use env_logger::{Builder, Env};
 
-use crate::{get_lane_specs_ltr, Direction, DrivingSide, MapConfig};
+use abstutil::Tags;
+use enumset::EnumSet;
+use geom::Distance;
+
+use crate::{
+    get_lane_specs_ltr, BufferType, Direction, DrivingSide, LaneSpec, LaneType, MapConfig,
+    TurnDirection,
+};
 
 static SETUP_LOGGER: Once = Once::new();
 
+/// Builds tags from `key=value` strings, like `["highway=residential", "lanes=2"]`
+pub(crate) fn tags_from_pairs(pairs: &[&str]) -> Tags {
+    let mut tags = Tags::empty();
+    for pair in pairs {
+        let (key, value) = pair
+            .split_once('=')
+            .unwrap_or_else(|| panic!("{pair} isn't key=value"));
+        tags.insert(key, value);
+    }
+    tags
+}
+
 #[test]
 fn test_osm_to_specs() {
     SETUP_LOGGER.call_once(|| Builder::from_env(Env::default().default_filter_or("info")).init());
@@ -289,6 +307,31 @@ fn test_osm_to_specs() {
             "ddddd",
             "^^^^^",
         ),
+        (
+            "https://wiki.openstreetmap.org/wiki/Key:shoulder",
+            vec![
+                "highway=motorway",
+                "lanes=2",
+                "oneway=yes",
+                "shoulder=right",
+            ],
+            DrivingSide::Right,
+            "ddS",
+            "^^^",
+        ),
+        (
+            "https://wiki.openstreetmap.org/wiki/Key:emergency",
+            vec![
+                "highway=motorway",
+                "lanes=3",
+                "oneway=yes",
+                "emergency:lanes=no|no|designated",
+                "motor_vehicle:lanes=yes|yes|no",
+            ],
+            DrivingSide::Right,
+            "ddS",
+            "^^^",
+        ),
     ];
     let cases_count = cases.len();
 
@@ -300,7 +343,7 @@ fn test_osm_to_specs() {
         if input.iter().all(|x| !x.starts_with("highway=")) {
             input.push("highway=residential");
         }
-        let actual = get_lane_specs_ltr(&tags(input.clone()), &cfg);
+        let actual = get_lane_specs_ltr(&tags_from_pairs(&input), &cfg);
         let actual_lt: String = actual.iter().map(|s| s.lt.to_char()).collect();
         let actual_dir: String = actual
             .iter()
@@ -335,11 +378,118 @@ fn test_osm_to_specs() {
     );
 }
 
-fn tags(kv: Vec<&str>) -> Tags {
-    let mut tags = Tags::empty();
-    for pair in kv {
-        let parts = pair.split('=').collect::<Vec<_>>();
-        tags.insert(parts[0], parts[1]);
+#[test]
+fn test_lane_rules() {
+    let mut cfg = MapConfig::default();
+    cfg.driving_side = DrivingSide::Right;
+    let specs = get_lane_specs_ltr(
+        &tags_from_pairs(&[
+            "highway=primary",
+            "oneway=yes",
+            "lanes=3",
+            "maxspeed:lanes=80|80|60",
+            "bus:lanes=yes|yes|designated",
+            "bus:lanes:conditional=||designated @ (Mo-Fr 07:00-09:00; Sa 08:00-12:00)",
+        ]),
+        &cfg,
+    );
+    let speeds: Vec<Option<f64>> = specs
+        .iter()
+        .map(|s| {
+            s.max_speed
+                .map(|x| (x.inner_meters_per_second() * 3.6).round())
+        })
+        .collect();
+    assert_eq!(vec![Some(80.0), Some(80.0), Some(60.0)], speeds);
+    assert_eq!(None, specs[0].bus_hours);
+    assert_eq!(
+        Some("Mo-Fr 07:00-09:00; Sa 08:00-12:00".to_string()),
+        specs[2].bus_hours
+    );
+}
+
+#[test]
+fn test_center_turn_lanes() {
+    let mut cfg = MapConfig::default();
+    cfg.driving_side = DrivingSide::Right;
+    for (input, expected) in [
+        (
+            vec![
+                "highway=primary",
+                "lanes=3",
+                "lanes:both_ways=1",
+                "turn:lanes:both_ways=left",
+            ],
+            EnumSet::only(TurnDirection::Left),
+        ),
+        (
+            vec![
+                "highway=primary",
+                "lanes=3",
+                "lanes:both_ways=1",
+                "turn:lanes:both_ways=left;reverse",
+            ],
+            TurnDirection::Left | TurnDirection::Reverse,
+        ),
+        (
+            vec!["highway=primary", "lanes=2", "centre_turn_lane=yes"],
+            EnumSet::only(TurnDirection::Left),
+        ),
+    ] {
+        let specs = get_lane_specs_ltr(&tags_from_pairs(&input), &cfg);
+        let center = specs
+            .iter()
+            .find(|spec| spec.lt == LaneType::SharedLeftTurn)
+            .unwrap_or_else(|| panic!("no center turn lane for {input:?}"));
+        assert_eq!(expected, center.allowed_turns, "for {input:?}");
     }
-    tags
+}
+
+#[test]
+fn test_unbuilt_roads_keep_lane_types() {
+    let mut cfg = MapConfig::default();
+    cfg.driving_side = DrivingSide::Right;
+    let built = get_lane_specs_ltr(
+        &tags_from_pairs(&["highway=primary", "lanes=3", "oneway=yes"]),
+        &cfg,
+    );
+    for lifecycle in ["construction", "proposed"] {
+        let planned = format!("{lifecycle}=primary");
+        let unbuilt = get_lane_specs_ltr(
+            &tags_from_pairs(&[
+                &format!("highway={lifecycle}"),
+                &planned,
+                "lanes=3",
+                "oneway=yes",
+            ]),
+            &cfg,
+        );
+        assert_eq!(built, unbuilt, "for highway={lifecycle}");
+    }
+}
+
+#[test]
+fn test_cross_section_sketch() {
+    let lane = |lt, dir, width| LaneSpec {
+        lt,
+        dir,
+        width: Distance::meters(width),
+        allowed_turns: EnumSet::new(),
+        is_virtual: false,
+        shoulder_access: None,
+        max_speed: None,
+        bus_hours: None,
+        lane: None,
+    };
+    let lanes = vec![
+        lane(LaneType::Sidewalk, Direction::Backward, 1.5),
+        lane(LaneType::Buffer(BufferType::Curb), Direction::Backward, 0.5),
+        lane(LaneType::Driving, Direction::Backward, 3.0),
+        lane(LaneType::Driving, Direction::Forward, 3.3),
+        lane(LaneType::Biking, Direction::Forward, 1.5),
+    ];
+    assert_eq!(
+        "s1.5 |0.5 dv3.0 d^3.3 b^1.5",
+        LaneSpec::cross_section_sketch(&lanes)
+    );
 }
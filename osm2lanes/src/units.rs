@@ -0,0 +1,139 @@
+//! Parsing quantities from OSM tag values, like `maxwidth="7'6\""` or `maxspeed="30 mph"`. Every
+//! value is converted to metric. See <https://wiki.openstreetmap.org/wiki/Map_features/Units>.
+
+use geom::{Distance, Speed};
+
+/// Parses a length. Plain numbers are meters. Also handles `km`, `mi`, `ft`, `in`, and feet and
+/// inches written like `7'6"`.
+pub fn parse_distance(value: &str) -> Option<Distance> {
+    let value = value.trim();
+
+    // Feet and inches, like 7'6", 7', or 6"
+    if value.ends_with('"') || value.ends_with('\'') {
+        let (feet, inches) = match value.split_once('\'') {
+            Some((feet, inches)) => (feet.trim(), inches.trim().trim_end_matches('"').trim()),
+            None => ("0", value.trim_end_matches('"').trim()),
+        };
+        let feet = feet.parse::<f64>().ok()?;
+        let inches = if inches.is_empty() {
+            0.0
+        } else {
+            inches.parse::<f64>().ok()?
+        };
+        return Some(Distance::feet(feet + inches / 12.0));
+    }
+
+    let (number, unit) = split_unit(value)?;
+    let meters = match unit {
+        "" | "m" => number,
+        "km" => number * 1000.0,
+        "mi" => number * 1609.344,
+        "ft" => number * 0.3048,
+        "in" => number * 0.0254,
+        "nmi" => number * 1852.0,
+        _ => return None,
+    };
+    Some(Distance::meters(meters))
+}
+
+/// Parses a speed. Plain numbers are km/h. Also handles `mph` and `knots`.
+pub fn parse_speed(value: &str) -> Option<Speed> {
+    let (number, unit) = split_unit(value.trim())?;
+    match unit {
+        "" | "km/h" | "kmh" | "kph" => Some(Speed::km_per_hour(number)),
+        "mph" => Some(Speed::miles_per_hour(number)),
+        "knots" => Some(Speed::km_per_hour(number * 1.852)),
+        _ => None,
+    }
+}
+
+/// Parses a weight, returning metric tonnes. Plain numbers are tonnes. Also handles `kg`, `lbs`,
+/// and `st` (short tons).
+pub fn parse_weight(value: &str) -> Option<f64> {
+    let (number, unit) = split_unit(value.trim())?;
+    match unit {
+        "" | "t" => Some(number),
+        "kg" => Some(number / 1000.0),
+        "lbs" => Some(number * 0.000_453_592_37),
+        "st" => Some(number * 0.907_184_74),
+        _ => None,
+    }
+}
+
+/// Parses a duration like `duration=*`, returning seconds. Handles `mm`, `hh:mm`, and
+/// `hh:mm:ss`.
+pub fn parse_duration(value: &str) -> Option<f64> {
+    let parts: Vec<f64> = value
+        .split(':')
+        .map(|x| x.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match parts[..] {
+        [minutes] => Some(minutes * 60.0),
+        [hours, minutes] => Some(hours * 3600.0 + minutes * 60.0),
+        [hours, minutes, seconds] => Some(hours * 3600.0 + minutes * 60.0 + seconds),
+        _ => None,
+    }
+}
+
+/// Parses a slope like `incline=8%`, returning the percent. `up` and `down` don't say how steep
+/// something is.
+pub fn parse_incline(value: &str) -> Option<f64> {
+    value.trim().strip_suffix('%')?.trim().parse::<f64>().ok()
+}
+
+// Splits something like "30 mph" or "3.5m" into the number and unit
+fn split_unit(value: &str) -> Option<(f64, &str)> {
+    let idx = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(value.len());
+    let number = value[..idx].parse::<f64>().ok()?;
+    Some((number, value[idx..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_distance() {
+        assert_eq!(Some(Distance::meters(3.5)), parse_distance("3.5"));
+        assert_eq!(Some(Distance::meters(3.5)), parse_distance("3.5 m"));
+        assert_eq!(Some(Distance::meters(1500.0)), parse_distance("1.5km"));
+        assert_eq!(Some(Distance::feet(7.5)), parse_distance("7'6\""));
+        assert_eq!(Some(Distance::feet(12.0)), parse_distance("12'"));
+        assert_eq!(Some(Distance::feet(0.5)), parse_distance("6\""));
+        assert_eq!(None, parse_distance("default"));
+        assert_eq!(None, parse_distance("3 furlongs"));
+    }
+
+    #[test]
+    fn test_parse_speed() {
+        assert_eq!(Some(Speed::km_per_hour(30.5)), parse_speed("30.5"));
+        assert_eq!(Some(Speed::km_per_hour(50.0)), parse_speed("50 km/h"));
+        assert_eq!(Some(Speed::miles_per_hour(30.0)), parse_speed("30 mph"));
+        assert_eq!(None, parse_speed("signals"));
+    }
+
+    #[test]
+    fn test_parse_weight() {
+        assert_eq!(Some(7.5), parse_weight("7.5"));
+        assert_eq!(Some(3.5), parse_weight("3500 kg"));
+        assert_eq!(None, parse_weight("heavy"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(900.0), parse_duration("15"));
+        assert_eq!(Some(5400.0), parse_duration("01:30"));
+        assert_eq!(Some(3661.0), parse_duration("1:01:01"));
+        assert_eq!(None, parse_duration("about an hour"));
+    }
+
+    #[test]
+    fn test_parse_incline() {
+        assert_eq!(Some(-10.0), parse_incline("-10%"));
+        assert_eq!(Some(8.0), parse_incline("8 %"));
+        assert_eq!(None, parse_incline("up"));
+    }
+}
@@ -42,10 +42,13 @@ public class StreetNetwork {
 
 	public native List<PaintArea> getPaintAreas();
 
+	public native String getSummaryJson();
+
 	public static void main(String[] args) throws Exception {
 		byte[] osmInput = Files.readAllBytes(Paths.get("../tests/src/aurora_sausage_link/input.osm"));
 		StreetNetwork network = create(osmInput);
 		System.out.println(network.getSurfaces());
 		System.out.println(network.getPaintAreas());
+		System.out.println(network.getSummaryJson());
 	}
 }
@@ -1,6 +1,6 @@
 use abstutil::Timer;
 use jni::objects::{JClass, JObject, JValue};
-use jni::sys::{jlong, jobject};
+use jni::sys::{jlong, jobject, jstring};
 use jni::JNIEnv;
 
 use osm2streets::{MapConfig, Transformation};
@@ -180,3 +180,15 @@ pub unsafe extern "system" fn Java_org_osm2streets_StreetNetwork_getPaintAreas(
     }
     j_paint_areas.into_raw()
 }
+
+#[no_mangle]
+#[allow(non_snake_case)]
+pub unsafe extern "system" fn Java_org_osm2streets_StreetNetwork_getSummaryJson(
+    env: JNIEnv,
+    j_self: JObject,
+) -> jstring {
+    let inner_pointer = env.get_field(j_self, "pointer", "J").unwrap();
+    let streets = &mut *(inner_pointer.j().unwrap() as *mut StreetNetwork);
+    let json = streets.inner.to_summary_json().unwrap();
+    env.new_string(json).unwrap().into_raw()
+}
@@ -31,6 +31,14 @@ JNIEXPORT jobject JNICALL Java_org_osm2streets_StreetNetwork_getSurfaces
 JNIEXPORT jobject JNICALL Java_org_osm2streets_StreetNetwork_getPaintAreas
   (JNIEnv *, jobject);
 
+/*
+ * Class:     org_osm2streets_StreetNetwork
+ * Method:    getSummaryJson
+ * Signature: ()Ljava/lang/String;
+ */
+JNIEXPORT jstring JNICALL Java_org_osm2streets_StreetNetwork_getSummaryJson
+  (JNIEnv *, jobject);
+
 #ifdef __cplusplus
 }
 #endif
@@ -1,15 +1,16 @@
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::sync::Once;
 
 use abstutil::{Tags, Timer};
 use chrono::NaiveDateTime;
-use geom::{Distance, LonLat, PolyLine, Polygon};
+use geom::{Distance, Duration, LonLat};
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
 use osm2streets::{
-    osm, DebugStreets, DrivingSide, Filter, IntersectionID, LaneID, MapConfig, Placement, RoadID,
-    RoadSideID, SideOfRoad, Sidepath, StreetNetwork, Transformation,
+    osm, Budget, CancellationToken, Crs, DeadEndCap, DebugStreets, DrivingSide, Filter,
+    IndoorPaths, IntersectionGeometry, IntersectionID, LaneID, MapConfig, Profile, Progress,
+    RoadID, RoadSideID, SideOfRoad, Sidepath, StreetNetwork, Transformation, Truck,
 };
 
 static SETUP_LOGGER: Once = Once::new();
@@ -23,102 +24,608 @@ pub struct ImportOptions {
     inferred_kerbs: bool,
     date_time: Option<NaiveDateTime>,
     override_driving_side: String,
+    /// After importing, only keep the OSM ways that became roads, the nodes that became
+    /// intersections, and relations referencing those ways
+    #[serde(default)]
+    drop_osm_document: bool,
+    /// What to do with frontage roads running alongside main roads. Empty does nothing, "detect"
+    /// records them in `parallel_to`, and "merge" zips them into the main road.
+    #[serde(default)]
+    frontage_roads: String,
+    /// Simplify road geometry, removing points that stray less than this many meters. Zero
+    /// disables this.
+    #[serde(default)]
+    reference_line_simplification: f64,
+    /// When collapsing a degenerate intersection joins two roads at a sharp angle, smooth the kink
+    /// into a curve straying at most this many meters from the original geometry. Zero disables
+    /// this.
+    #[serde(default)]
+    degenerate_smoothing_max_deviation: f64,
+    /// Smooth sharp corners along roads into curves
+    #[serde(default)]
+    smooth_road_geometry: bool,
+    /// Make lanes that end mid-block merge into the next lane over, by setting their allowed turns
+    #[serde(default)]
+    mark_lane_transitions: bool,
+    /// `highway` values to import besides the defaults, like "bridleway" or "proposed"
+    #[serde(default)]
+    include_highways: Vec<String>,
+    /// `highway` values not to import, like "construction" or "track"
+    #[serde(default)]
+    exclude_highways: Vec<String>,
+    /// Remove roads under construction, proposed, or disused after importing
+    #[serde(default)]
+    remove_unbuilt_roads: bool,
+    /// The shape of dead-ends: "square" (the default), "rounded", or "turning_circles"
+    #[serde(default)]
+    dead_end_cap: String,
+    /// Also collapse roads shorter than this many meters. Zero disables this.
+    #[serde(default)]
+    short_road_threshold: f64,
+    /// OSM way IDs that should never be collapsed
+    #[serde(default)]
+    do_not_collapse: Vec<i64>,
+    /// Where roads with a different `placement` meet, ease their center lines to meet smoothly
+    #[serde(default)]
+    blend_placement_offsets: bool,
+    /// How to calculate intersection polygons: "corner_trim" (the default), "convex_hull",
+    /// "mapped_area", or "buffer_union_fallback"
+    #[serde(default)]
+    intersection_geometry: String,
+    /// Per OSM node ID, a different `intersection_geometry` to use for that intersection
+    #[serde(default)]
+    intersection_geometry_overrides: BTreeMap<String, String>,
+    /// What to do with paths inside buildings: "" imports them like anything else, "exclude"
+    /// drops them, and "separate" keeps them apart from the network
+    #[serde(default)]
+    indoor_paths: String,
+    /// Split blocks where rivers, canals, and railways that aren't roads cross them
+    #[serde(default)]
+    block_barriers: bool,
+    /// Keep a copy of the network before any transformations
+    #[serde(default)]
+    keep_original: bool,
+    /// Don't collapse roads or intersections when a road name would be lost
+    #[serde(default)]
+    preserve_road_names: bool,
+    /// Link roads tagged with separately mapped sidewalks or cycleways to the ways running
+    /// alongside them
+    #[serde(default)]
+    link_separate_ways: bool,
+}
+
+// TypeScript definitions for the types passed to and from Rust as plain objects. Keep these in
+// sync with `ImportOptions` and `abstutil::Tags`.
+#[wasm_bindgen(typescript_custom_section)]
+const TS_TYPES: &str = r#"
+export interface ImportOptions {
+  debug_each_step: boolean;
+  dual_carriageway_experiment: boolean;
+  sidepath_zipping_experiment: boolean;
+  inferred_sidewalks: boolean;
+  inferred_kerbs: boolean;
+  date_time?: string | null;
+  override_driving_side: "" | "Left" | "Right";
+  drop_osm_document?: boolean;
+  frontage_roads?: "" | "detect" | "merge";
+  reference_line_simplification?: number;
+  degenerate_smoothing_max_deviation?: number;
+  smooth_road_geometry?: boolean;
+  mark_lane_transitions?: boolean;
+  include_highways?: string[];
+  exclude_highways?: string[];
+  remove_unbuilt_roads?: boolean;
+  dead_end_cap?: "" | "square" | "rounded" | "turning_circles";
+  short_road_threshold?: number;
+  do_not_collapse?: number[];
+  blend_placement_offsets?: boolean;
+  intersection_geometry?: "" | "corner_trim" | "convex_hull" | "mapped_area" | "buffer_union_fallback";
+  intersection_geometry_overrides?: Record<string, "corner_trim" | "convex_hull" | "mapped_area" | "buffer_union_fallback">;
+  indoor_paths?: "" | "exclude" | "separate";
+  block_barriers?: boolean;
+  keep_original?: boolean;
+  preserve_road_names?: boolean;
+  link_separate_ways?: boolean;
+}
+
+export type OsmTags = Record<string, string>;
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "ImportOptions")]
+    pub type JsImportOptions;
+
+    #[wasm_bindgen(typescript_type = "OsmTags")]
+    pub type OsmTags;
 }
 
 #[wasm_bindgen]
 pub struct JsStreetNetwork {
     inner: StreetNetwork,
-    ways: BTreeMap<osm::WayID, streets_reader::osm_reader::Way>,
+    skipped: streets_reader::SkipReport,
 }
 
 #[wasm_bindgen]
 impl JsStreetNetwork {
     // TODO clip_pts_geojson should be Option. Empty means None.
+    /// `progress` is an optional callback, called with the name of the current stage and the
+    /// fraction of it completed. If it returns `false`, the import is cancelled and an error is
+    /// thrown.
     #[wasm_bindgen(constructor)]
     pub fn new(
         osm_input: &[u8],
         clip_pts_geojson: &str,
-        input: JsValue,
+        input: JsImportOptions,
+        progress: Option<js_sys::Function>,
     ) -> Result<JsStreetNetwork, JsValue> {
         SETUP_LOGGER.call_once(|| console_log::init_with_level(log::Level::Info).unwrap());
         // Panics shouldn't happen, but if they do, console.log them.
         console_error_panic_hook::set_once();
 
-        let input: ImportOptions = serde_wasm_bindgen::from_value(input)?;
+        let input: ImportOptions = serde_wasm_bindgen::from_value(input.into())?;
+        let clip_pts = parse_clip_pts(clip_pts_geojson)?;
+        let settings = ImportSettings::new(input)?;
 
-        let clip_pts = if clip_pts_geojson.is_empty() {
-            None
-        } else {
-            let mut list =
-                LonLat::parse_geojson_polygons(clip_pts_geojson.to_string()).map_err(err_to_js)?;
-            if list.len() != 1 {
-                return Err(JsValue::from_str(&format!(
-                    "{clip_pts_geojson} doesn't contain exactly one polygon"
-                )));
-            }
-            Some(list.pop().unwrap().0)
-        };
-
-        let mut cfg = MapConfig::default();
-        cfg.inferred_sidewalks = input.inferred_sidewalks;
-        cfg.inferred_kerbs = input.inferred_kerbs;
-        cfg.date_time = input.date_time;
-        cfg.override_driving_side = match input.override_driving_side.as_str() {
-            "" => None,
-            "Left" => Some(DrivingSide::Left),
-            "Right" => Some(DrivingSide::Right),
-            x => {
-                return Err(JsValue::from_str(&format!(
-                    "Unknown override_driving_side = {x}"
-                )))
-            }
-        };
+        let mut progress = js_progress(progress);
 
         let mut timer = Timer::throwaway();
-        let (mut street_network, doc) =
-            streets_reader::osm_to_street_network(osm_input, clip_pts, cfg, &mut timer)
+        let (mut street_network, mut doc) = streets_reader::osm_to_street_network_with_progress(
+            osm_input,
+            clip_pts,
+            settings.cfg,
+            &mut timer,
+            &mut progress,
+        )
+        .map_err(err_to_js)?;
+        let skipped = std::mem::take(&mut doc.skipped);
+        let index = doc.into_osm_index(&street_network, settings.drop_osm_document);
+        street_network.set_osm_index(index);
+
+        if settings.debug_each_step {
+            street_network
+                .apply_transformations_stepwise_debugging_with_progress(
+                    settings.transformations,
+                    &mut timer,
+                    &mut progress,
+                )
                 .map_err(err_to_js)?;
-        let mut transformations = Transformation::standard_for_clipped_areas();
-        if input.dual_carriageway_experiment {
-            // Collapsing short roads tries to touch "bridges," making debugging harder
-            transformations.retain(|t| !matches!(t, Transformation::CollapseShortRoads));
-            transformations.push(Transformation::MergeDualCarriageways);
-        }
-        if input.sidepath_zipping_experiment {
-            transformations.push(Transformation::ZipSidepaths);
-            transformations.push(Transformation::CollapseDegenerateIntersections);
-        }
-        if input.debug_each_step {
-            street_network.apply_transformations_stepwise_debugging(transformations, &mut timer);
         } else {
-            street_network.apply_transformations(transformations, &mut timer);
+            street_network
+                .apply_transformations_with_progress(
+                    settings.transformations,
+                    &mut timer,
+                    &mut progress,
+                )
+                .map_err(err_to_js)?;
         }
 
         Ok(Self {
             inner: street_network,
-            ways: doc.ways,
+            skipped,
         })
     }
+    // The render calls below take an optional filter. See parse_filter.
     #[wasm_bindgen(js_name = toGeojsonPlain)]
-    pub fn to_geojson_plain(&self) -> String {
-        self.inner.to_geojson(&Filter::All).unwrap()
+    pub fn to_geojson_plain(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Like `toGeojsonPlain`, but for the network before any transformations. Only works if
+    /// `keep_original` was set.
+    #[wasm_bindgen(js_name = toOriginalGeojson)]
+    pub fn to_original_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        let Some(original) = self.inner.original() else {
+            return Err(JsValue::from_str("keep_original wasn't set"));
+        };
+        original
+            .to_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Transforms the output of any GeoJSON method into another coordinate system: "lonlat",
+    /// "web_mercator", "utm", "local_azimuthal", or "map" for internal coordinates.
+    #[wasm_bindgen(js_name = reprojectGeojson)]
+    pub fn reproject_geojson(&self, geojson: &str, crs: &str) -> Result<String, JsValue> {
+        let crs = Crs::parse(crs).map_err(err_to_js)?;
+        self.inner
+            .reproject_geojson(geojson, crs)
+            .map_err(err_to_js)
+    }
+
+    /// Adds a `hash` property to every feature in the output of any GeoJSON method, covering its
+    /// geometry and other properties, to skip re-uploading unchanged features after an edit.
+    #[wasm_bindgen(js_name = addFeatureHashes)]
+    pub fn add_feature_hashes(&self, geojson: &str) -> Result<String, JsValue> {
+        osm2streets::add_feature_hashes(geojson).map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = toLanePolygonsGeojson)]
-    pub fn to_lane_polygons_geojson(&self) -> String {
-        self.inner.to_lane_polygons_geojson(&Filter::All).unwrap()
+    pub fn to_lane_polygons_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_lane_polygons_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = toLaneMarkingsGeojson)]
-    pub fn to_lane_markings_geojson(&self) -> String {
-        self.inner.to_lane_markings_geojson(&Filter::All).unwrap()
+    pub fn to_lane_markings_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_lane_markings_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = toIntersectionMarkingsGeojson)]
-    pub fn to_intersection_markings_geojson(&self) -> String {
+    pub fn to_intersection_markings_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
         self.inner
-            .to_intersection_markings_geojson(&Filter::All)
-            .unwrap()
+            .to_intersection_markings_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toMovementsGeojson)]
+    pub fn to_movements_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_movements_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns a JSON array describing each allowed movement through an intersection, for routing
+    /// costs: the turn `angle` and `turn_direction`, the `approach_control`, the number of
+    /// `conflicting_movements`, and whether it `crosses_traffic`.
+    #[wasm_bindgen(js_name = toMovementAttributesJson)]
+    pub fn to_movement_attributes_json(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_movement_attributes_json(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toTrafficControlGeojson)]
+    pub fn to_traffic_control_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_traffic_control_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toGradeSeparationsGeojson)]
+    pub fn to_grade_separations_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_grade_separations_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toJunctionComplexesGeojson)]
+    pub fn to_junction_complexes_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_junction_complexes_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns a polygon per street covering its full right-of-way, including sidewalks, verges,
+    /// and intersections.
+    #[wasm_bindgen(js_name = toRightOfWayGeojson)]
+    pub fn to_right_of_way_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_right_of_way_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns a JSON object with `motor`, `cycling`, `walking`, and `rail` FeatureCollections, each
+    /// with the lanes and intersections usable by that mode, to toggle as separate map layers.
+    #[wasm_bindgen(js_name = toModeLayersGeojson)]
+    pub fn to_mode_layers_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_mode_layers_geojson(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns a JSON array with each pair of neighboring lanes, and whether vehicles may change
+    /// from one to the other.
+    #[wasm_bindgen(js_name = toLaneAdjacencyJson)]
+    pub fn to_lane_adjacency_json(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_lane_adjacency_json(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns a JSON array of the roads that collapsing short roads would affect, without
+    /// changing anything. Each has the `road` ID, `reasons` (`Length`, `JunctionTag`, or
+    /// `TrimOverlap`), `length` in meters, and `blocked` if it won't actually be collapsed.
+    #[wasm_bindgen(js_name = previewCollapseShortRoads)]
+    pub fn preview_collapse_short_roads(&self) -> Result<String, JsValue> {
+        self.inner
+            .preview_collapse_short_roads_json()
+            .map_err(err_to_js)
+    }
+
+    /// Takes a road ID and a lane edit as JSON, like `{"ChangeType": [2, "Biking"]}`. Returns a
+    /// JSON array of the OSM tags this edit would change, each with a `key`, and the `old` and
+    /// `new` value (null if the tag is added or removed). Nothing is changed.
+    #[wasm_bindgen(js_name = previewLaneEditTags)]
+    pub fn preview_lane_edit_tags(&self, road: usize, edit: &str) -> Result<String, JsValue> {
+        self.inner
+            .preview_lane_edit_tags_json(RoadID(road), edit)
+            .map_err(err_to_js)
+    }
+
+    /// Checks the network for internal problems, returning a JSON array of violations, each with a
+    /// `description`. These indicate bugs in osm2streets.
+    #[wasm_bindgen(js_name = findInvariantViolations)]
+    pub fn find_invariant_violations(&self) -> Result<String, JsValue> {
+        self.inner.to_invariant_violations_json().map_err(err_to_js)
+    }
+
+    /// Returns an SVG drawing of the lanes, intersections, markings, and road names touching the
+    /// filter.
+    #[wasm_bindgen(js_name = toSvg)]
+    pub fn to_svg(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_svg(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns the simplified network as synthetic OSM XML, with one way per road and one node per
+    /// intersection.
+    #[wasm_bindgen(js_name = toOsmXml)]
+    pub fn to_osm_xml(&self, filter: JsValue) -> Result<String, JsValue> {
+        Ok(self.inner.to_osm_xml(&self.parse_filter(filter)?))
+    }
+
+    /// Returns a JSON object with totals describing the whole network: lengths by highway type,
+    /// lane-km by lane type, intersection counts, and sidewalk and cycle coverage.
+    #[wasm_bindgen(js_name = toSummaryJson)]
+    pub fn to_summary_json(&self) -> Result<String, JsValue> {
+        self.inner.to_summary_json().map_err(err_to_js)
+    }
+
+    /// Returns a JSON array with the carriageway, curb-to-curb, and right-of-way widths in meters
+    /// and the number of general purpose lanes of each road.
+    #[wasm_bindgen(js_name = toRoadWidthsJson)]
+    pub fn to_road_widths_json(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_road_widths_json(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Returns a JSON array with rough estimates of the hourly capacity and free-flow speed of
+    /// each road, `forward` and `backward` relative to the road's direction.
+    #[wasm_bindgen(js_name = toRoadCapacityJson)]
+    pub fn to_road_capacity_json(&self, filter: JsValue) -> Result<String, JsValue> {
+        self.inner
+            .to_road_capacity_json(&self.parse_filter(filter)?)
+            .map_err(err_to_js)
+    }
+
+    /// Marks barriers for wheelchair users: kerbs without curb cuts, steps without ramps,
+    /// sidewalks narrower than `min_sidewalk_width` meters, and rough or steep paths.
+    #[wasm_bindgen(js_name = toAccessibilityGeojson)]
+    pub fn to_accessibility_geojson(
+        &self,
+        filter: JsValue,
+        min_sidewalk_width: f64,
+    ) -> Result<String, JsValue> {
+        self.inner
+            .to_accessibility_geojson(
+                &self.parse_filter(filter)?,
+                Distance::meters(min_sidewalk_width),
+            )
+            .map_err(err_to_js)
+    }
+
+    /// Finds roads that a truck of this height (in meters), weight, and axle load (in tonnes)
+    /// can't use, where no alternative connects the truck network.
+    #[wasm_bindgen(js_name = toFreightBarriersGeojson)]
+    pub fn to_freight_barriers_geojson(
+        &self,
+        height: f64,
+        weight: f64,
+        axle_load: f64,
+    ) -> Result<String, JsValue> {
+        self.inner
+            .to_freight_barriers_geojson(&Truck {
+                height: Distance::meters(height),
+                weight,
+                axle_load,
+            })
+            .map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toMapEdgesGeojson)]
+    pub fn to_map_edges_geojson(&self) -> Result<String, JsValue> {
+        self.inner.to_map_edges_geojson().map_err(err_to_js)
+    }
+
+    /// Returns GeoJSON lines for the paths inside buildings kept apart with
+    /// `indoor_paths: "separate"`.
+    #[wasm_bindgen(js_name = toIndoorPathsGeojson)]
+    pub fn to_indoor_paths_geojson(&self) -> Result<String, JsValue> {
+        self.inner.to_indoor_paths_geojson().map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toFerriesGeojson)]
+    pub fn to_ferries_geojson(&self) -> Result<String, JsValue> {
+        self.inner.to_ferries_geojson().map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toParkingGeojson)]
+    pub fn to_parking_geojson(&self) -> Result<String, JsValue> {
+        self.inner.to_parking_geojson().map_err(err_to_js)
+    }
+
+    /// Gzips the output of one of the calls above, to shrink big payloads. `call` is the name of
+    /// the call, like `toLanePolygonsGeojson`, and `filter` is used by the render calls.
+    #[wasm_bindgen(js_name = toGzippedOutput)]
+    pub fn to_gzipped_output(&self, call: &str, filter: JsValue) -> Result<Vec<u8>, JsValue> {
+        let filter = self.parse_filter(filter)?;
+        let output = match call {
+            "toGeojsonPlain" => self.inner.to_geojson(&filter),
+            "toLanePolygonsGeojson" => self.inner.to_lane_polygons_geojson(&filter),
+            "toLaneMarkingsGeojson" => self.inner.to_lane_markings_geojson(&filter),
+            "toIntersectionMarkingsGeojson" => self.inner.to_intersection_markings_geojson(&filter),
+            "toMovementsGeojson" => self.inner.to_movements_geojson(&filter),
+            "toTrafficControlGeojson" => self.inner.to_traffic_control_geojson(&filter),
+            "toGradeSeparationsGeojson" => self.inner.to_grade_separations_geojson(&filter),
+            "toJunctionComplexesGeojson" => self.inner.to_junction_complexes_geojson(&filter),
+            "toRightOfWayGeojson" => self.inner.to_right_of_way_geojson(&filter),
+            "toModeLayersGeojson" => self.inner.to_mode_layers_geojson(&filter),
+            "toMapEdgesGeojson" => self.inner.to_map_edges_geojson(),
+            "toIndoorPathsGeojson" => self.inner.to_indoor_paths_geojson(),
+            "toFerriesGeojson" => self.inner.to_ferries_geojson(),
+            "toParkingGeojson" => self.inner.to_parking_geojson(),
+            "toJson" => serde_json::to_string(&self.inner).map_err(anyhow::Error::from),
+            x => return Err(JsValue::from_str(&format!("Can't gzip unknown call {x}"))),
+        }
+        .map_err(err_to_js)?;
+        osm2streets::gzip(&output).map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = crossSectionAtPoint)]
+    pub fn cross_section_at_point(&self, lon: f64, lat: f64) -> Result<String, JsValue> {
+        self.inner
+            .cross_section_at_point_json(LonLat::new(lon, lat))
+            .map_err(err_to_js)
+    }
+
+    /// Takes a JSON list of events, each located by `road` and `start` (and optionally `end`) in
+    /// meters, or by `lon` and `lat`, with any `properties`. Returns GeoJSON placing each event
+    /// along its road.
+    #[wasm_bindgen(js_name = linearEventsToGeojson)]
+    pub fn linear_events_to_geojson(&self, events_json: &str) -> Result<String, JsValue> {
+        let events = self
+            .inner
+            .parse_linear_events(events_json)
+            .map_err(err_to_js)?;
+        self.inner
+            .linear_events_to_geojson(&events)
+            .map_err(err_to_js)
+    }
+
+    /// Takes the contents of a GTFS `stops.txt` file. Returns GeoJSON points, describing where
+    /// each stop is along the network.
+    #[wasm_bindgen(js_name = matchGtfsStopsGeojson)]
+    pub fn match_gtfs_stops_geojson(&self, stops_txt: &str) -> Result<String, JsValue> {
+        let stops = osm2streets::parse_gtfs_stops(stops_txt).map_err(err_to_js)?;
+        let matches = self.inner.match_gtfs_stops(stops);
+        self.inner
+            .gtfs_matches_to_geojson(&matches)
+            .map_err(err_to_js)
+    }
+
+    /// Like `matchGtfsStopsGeojson`, but returns CSV.
+    #[wasm_bindgen(js_name = matchGtfsStopsCsv)]
+    pub fn match_gtfs_stops_csv(&self, stops_txt: &str) -> Result<String, JsValue> {
+        let stops = osm2streets::parse_gtfs_stops(stops_txt).map_err(err_to_js)?;
+        let matches = self.inner.match_gtfs_stops(stops);
+        Ok(self.inner.gtfs_matches_to_csv(&matches))
+    }
+
+    /// Finds problems in the OSM tags, like impossible turn lanes and contradicting lane counts.
+    /// Returns a MapRoulette challenge as GeoJSON, with one task per problem.
+    #[wasm_bindgen(js_name = toMaprouletteGeojson)]
+    pub fn to_maproulette_geojson(&self) -> Result<String, JsValue> {
+        let mut issues = self.inner.find_impossible_turn_lanes();
+        issues.extend(
+            self.inner
+                .find_tagging_issues(self.osm_ways().map(|(id, way)| (id, &way.tags))),
+        );
+        issues.extend(self.inner.find_unmatched_separate_ways());
+        self.inner
+            .to_maproulette_geojson(&issues)
+            .map_err(err_to_js)
+    }
+
+    /// Summarizes the OSM ways and relations ignored while importing, grouped by reason, with
+    /// a count and a few sample IDs for each.
+    #[wasm_bindgen(js_name = getSkipReport)]
+    pub fn get_skip_report(&self) -> String {
+        self.skipped.to_json()
+    }
+
+    /// Returns a JSON array with the input to every intersection whose geometry couldn't be
+    /// calculated. Save one as a file in `tests/geometry_fixtures` to reproduce the failure.
+    #[wasm_bindgen(js_name = getGeometryFailures)]
+    pub fn get_geometry_failures(&self) -> Result<String, JsValue> {
+        let list: Vec<_> = self.inner.geometry_failures.values().collect();
+        serde_json::to_string(&list).map_err(err_to_js)
+    }
+
+    /// Returns a JSON array of turn restrictions dropped after transformations, because the roads
+    /// they refer to were removed or no longer connect.
+    #[wasm_bindgen(js_name = getStaleTurnRestrictions)]
+    pub fn get_stale_turn_restrictions(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.inner.stale_turn_restrictions).map_err(err_to_js)
+    }
+
+    /// Returns a JSON array explaining what each transformation changed. If a road or
+    /// intersection ID is given, only includes entries mentioning it.
+    #[wasm_bindgen(js_name = getAuditLog)]
+    pub fn get_audit_log(
+        &self,
+        road: Option<usize>,
+        intersection: Option<usize>,
+    ) -> Result<String, JsValue> {
+        self.inner
+            .audit_log_json(road.map(RoadID), intersection.map(IntersectionID))
+            .map_err(err_to_js)
+    }
+
+    /// Copies the roads matching the filter (see parse_filter) into a standalone network, useful
+    /// for sharing a small piece of a map in a bug report.
+    #[wasm_bindgen(js_name = subset)]
+    pub fn subset(&self, filter: JsValue) -> Result<JsStreetNetwork, JsValue> {
+        let inner = self
+            .inner
+            .subset(&self.parse_filter(filter)?)
+            .map_err(err_to_js)?;
+        Ok(self.with_network(inner))
+    }
+
+    /// Like `subset`, but copies every road with a name, plus the roads up to `hops`
+    /// intersections away from them.
+    #[wasm_bindgen(js_name = subsetAroundStreet)]
+    pub fn subset_around_street(
+        &self,
+        name: String,
+        hops: usize,
+    ) -> Result<JsStreetNetwork, JsValue> {
+        let filter = Filter::around_street(&self.inner, &name, hops).map_err(err_to_js)?;
+        let inner = self.inner.subset(&filter).map_err(err_to_js)?;
+        Ok(self.with_network(inner))
+    }
+
+    /// Returns GeoJSON with every road reachable from an intersection by `profile` traffic
+    /// (`pedestrian`, `wheelchair`, `bicycle`, `motor`, or `rail`), within a `budget` of `meters` or `seconds`.
+    /// Roads only partly reachable are cut off where the budget runs out.
+    #[wasm_bindgen(js_name = reachableWithinGeojson)]
+    pub fn reachable_within_geojson(
+        &self,
+        intersection: usize,
+        budget: f64,
+        unit: &str,
+        profile: &str,
+    ) -> Result<String, JsValue> {
+        let budget = match unit {
+            "meters" => Budget::Distance(Distance::meters(budget)),
+            "seconds" => Budget::Time(Duration::seconds(budget)),
+            x => return Err(JsValue::from_str(&format!("Unknown budget unit {x}"))),
+        };
+        self.inner
+            .reachable_within_geojson(
+                IntersectionID(intersection),
+                budget,
+                parse_profile(profile)?,
+            )
+            .map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toCorridorsJson)]
+    pub fn to_corridors_json(&self) -> Result<String, JsValue> {
+        self.inner.to_corridors_json().map_err(err_to_js)
+    }
+
+    #[wasm_bindgen(js_name = toCorridorsCsv)]
+    pub fn to_corridors_csv(&self) -> String {
+        self.inner.to_corridors_csv()
     }
 
     #[wasm_bindgen(js_name = getDebugSteps)]
@@ -131,30 +638,46 @@ impl JsStreetNetwork {
     }
 
     #[wasm_bindgen(js_name = debugClockwiseOrderingGeojson)]
-    pub fn debug_clockwise_ordering_geojson(&self) -> String {
+    pub fn debug_clockwise_ordering_geojson(&self) -> Result<String, JsValue> {
         self.inner
             .debug_clockwise_ordering_geojson(&Filter::All)
-            .unwrap()
+            .map_err(err_to_js)
+    }
+
+    /// Shows which algorithm trimmed each end of every road, and why
+    #[wasm_bindgen(js_name = debugTrimProvenanceGeojson)]
+    pub fn debug_trim_provenance_geojson(&self, filter: JsValue) -> Result<String, JsValue> {
+        let filter = self.parse_filter(filter)?;
+        self.inner
+            .debug_trim_provenance_geojson(&filter)
+            .map_err(err_to_js)
     }
 
     // TODO Can we take Filter as input here?
     #[wasm_bindgen(js_name = debugClockwiseOrderingForIntersectionGeojson)]
-    pub fn debug_clockwise_ordering_for_intersection_geojson(&self, intersection: usize) -> String {
+    pub fn debug_clockwise_ordering_for_intersection_geojson(
+        &self,
+        intersection: usize,
+    ) -> Result<String, JsValue> {
         let mut intersections = BTreeSet::new();
         intersections.insert(IntersectionID(intersection));
         self.inner
             .debug_clockwise_ordering_geojson(&Filter::Filtered(BTreeSet::new(), intersections))
-            .unwrap()
+            .map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = debugMovementsFromLaneGeojson)]
-    pub fn debug_movements_from_lane_geojson(&self, road: usize, index: usize) -> String {
+    pub fn debug_movements_from_lane_geojson(
+        &self,
+        road: usize,
+        index: usize,
+    ) -> Result<String, JsValue> {
         self.inner
             .debug_movements_from_lane_geojson(LaneID {
                 road: RoadID(road),
                 index,
             })
-            .unwrap()
+            .map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = debugRoadsConnectedToIntersectionGeojson)]
@@ -168,96 +691,111 @@ impl JsStreetNetwork {
                     .to_geojson(Some(&self.inner.gps_bounds)),
             );
         }
-        serde_json::to_string_pretty(&geom::geometries_to_geojson(polygons)).unwrap()
+        serde_json::to_string(&geom::geometries_to_geojson(polygons)).unwrap()
     }
 
-    // TODO I think https://github.com/cloudflare/serde-wasm-bindgen would let us just return a
-    // HashMap
+    /// Returns the way's OSM tags as an object
     #[wasm_bindgen(js_name = getOsmTagsForWay)]
-    pub fn get_osm_tags_for_way(&self, id: i64) -> Result<String, JsValue> {
-        if let Some(ref way) = self.ways.get(&osm::WayID(id)) {
-            Ok(serde_json::to_string_pretty(&way.tags).unwrap())
+    pub fn get_osm_tags_for_way(&self, id: i64) -> Result<OsmTags, JsValue> {
+        if let Some(ref way) = self
+            .inner
+            .osm_index()
+            .and_then(|index| index.way(osm::WayID(id)))
+        {
+            Ok(to_js(&way.tags)?.unchecked_into())
         } else {
             Err(JsValue::from_str(&format!("unknown way {id}")))
         }
     }
 
+    /// Returns the way's original points as a flat `[lon1, lat1, lon2, lat2, ...]` array
+    #[wasm_bindgen(js_name = getLonLatsForWay)]
+    pub fn get_lon_lats_for_way(&self, id: i64) -> Result<Vec<f64>, JsValue> {
+        let Some(way) = self
+            .inner
+            .osm_index()
+            .and_then(|index| index.way(osm::WayID(id)))
+        else {
+            return Err(JsValue::from_str(&format!("unknown way {id}")));
+        };
+        Ok(self
+            .inner
+            .gps_bounds
+            .convert_back(&way.pts)
+            .into_iter()
+            .flat_map(|pt| [pt.x(), pt.y()])
+            .collect())
+    }
+
     /// Returns the entire StreetNetwork as JSON. The API doesn't have guarantees about backwards
     /// compatibility.
     #[wasm_bindgen(js_name = toJson)]
     pub fn to_json(&self) -> String {
-        serde_json::to_string_pretty(&self.inner).unwrap()
+        serde_json::to_string(&self.inner).unwrap()
     }
 
-    /// Returns a GeoJSON Polygon showing a wide buffer around the way's original geometry
+    /// Returns a GeoJSON Polygon showing a wide buffer around the way's original geometry, with
+    /// chevrons cut out to show its direction
     #[wasm_bindgen(js_name = getGeometryForWay)]
-    pub fn get_geometry_for_way(&self, id: i64) -> String {
-        let id = osm::WayID(id);
-
-        // The lanes, and thus width, will be the same for every road belonging to the way
-        let width = self
-            .inner
-            .roads
-            .values()
-            .find(|r| r.from_osm_way(id))
-            .map(|r| r.total_width())
-            .unwrap();
-
-        let polyline = PolyLine::unchecked_new(self.ways[&id].pts.clone());
-
-        // Show a wide buffer around the way
-        let mut polygon = polyline.make_polygons(1.5 * width);
-
-        // "Cut out" one or more chevrons from the polygon to indicate the direction
-        let num_chevrons = std::cmp::max(
-            1,
-            (polyline.length() / Distance::meters(50.0)).floor() as i64,
-        );
-        let chevrons = (1..=num_chevrons)
-            .map(|i| {
-                let (top_pt, angle) = polyline
-                    // chevrons are uniformly spread along the polyline
-                    .dist_along((i as f64 / (num_chevrons as f64 + 1.0)) * polyline.length())
-                    .unwrap();
-                PolyLine::must_new(vec![
-                    top_pt.project_away(width / 2.0, angle.rotate_degs(135.0)),
-                    top_pt,
-                    top_pt.project_away(width / 2.0, angle.rotate_degs(-135.0)),
-                ])
-                .make_polygons(width * 0.2)
-            })
-            .collect::<Vec<Polygon>>();
-
-        chevrons
-            .iter()
-            .for_each(|c| polygon = polygon.difference(c).unwrap()[0].clone());
-
-        serde_json::to_string_pretty(&polygon.to_geojson(Some(&self.inner.gps_bounds))).unwrap()
+    pub fn get_geometry_for_way(&self, id: i64) -> Result<String, JsValue> {
+        let polygon = streets_reader::ways::geometry_for_way(&self.inner, osm::WayID(id))
+            .map_err(err_to_js)?;
+        serde_json::to_string(&polygon.to_geojson(Some(&self.inner.gps_bounds))).map_err(err_to_js)
     }
 
     /// Returns the XML string representing a way. Any OSM tags changed via
     /// `overwrite_osm_tags_for_way` are reflected.
     #[wasm_bindgen(js_name = wayToXml)]
     pub fn way_to_xml(&self, id: i64) -> Result<String, JsValue> {
-        let Some(ref way) = self.ways.get(&osm::WayID(id)) else {
+        let id = osm::WayID(id);
+        let Some(way) = self.inner.osm_index().and_then(|index| index.way(id)) else {
             return Err(JsValue::from_str(&format!("unknown way {id}")));
         };
+        Ok(streets_reader::ways::way_to_xml(id, way))
+    }
 
-        let mut out = format!(r#"<way id="{id}""#);
-        if let Some(version) = way.version {
-            out.push_str(&format!(r#" version="{version}""#));
-        }
-        out.push_str(">\n");
-        for node in &way.nodes {
-            out.push_str(&format!(r#"  <nd ref="{}"/>"#, node.0));
-            out.push('\n');
+    /// Returns the OSM tags of the nodes an intersection was built from, as a JSON array of
+    /// `{ node, tags }`
+    #[wasm_bindgen(js_name = getOsmTagsForIntersection)]
+    pub fn get_osm_tags_for_intersection(&self, intersection: usize) -> Result<String, JsValue> {
+        let i = IntersectionID(intersection);
+        if !self.inner.intersections.contains_key(&i) {
+            return Err(JsValue::from_str(&format!(
+                "unknown intersection {intersection}"
+            )));
         }
-        for (k, v) in way.tags.inner() {
-            out.push_str(&format!(r#"  <tag k="{k}" v="{v}"/>"#));
-            out.push('\n');
+        self.inner
+            .osm_tags_for_intersection_json(i)
+            .map_err(err_to_js)
+    }
+
+    /// Returns every OSM relation with the way as a member, as a JSON array of
+    /// `{ id, tags, members }`
+    #[wasm_bindgen(js_name = getRelationsForWay)]
+    pub fn get_relations_for_way(&self, id: i64) -> Result<String, JsValue> {
+        self.inner
+            .relations_for_way_json(osm::WayID(id))
+            .map_err(err_to_js)
+    }
+
+    /// Returns the roads of an intersection in clockwise order, as a JSON array of
+    /// `{ road, bearing, gap_to_next }` in degrees, with bearings starting from north
+    #[wasm_bindgen(js_name = getClockwiseRoads)]
+    pub fn get_clockwise_roads(&self, intersection: usize) -> Result<String, JsValue> {
+        let i = IntersectionID(intersection);
+        if !self.inner.intersections.contains_key(&i) {
+            return Err(JsValue::from_str(&format!(
+                "unknown intersection {intersection}"
+            )));
         }
-        out.push_str("</way>");
-        Ok(out)
+        serde_json::to_string(&self.inner.clockwise_road_angles(i)).map_err(err_to_js)
+    }
+
+    /// Returns a JSON array of everywhere the number of lanes in one direction changes between
+    /// two roads, as `{ intersection, from, to, dropped_lanes, added_lanes }`
+    #[wasm_bindgen(js_name = findLaneTransitions)]
+    pub fn find_lane_transitions(&self) -> Result<String, JsValue> {
+        serde_json::to_string(&self.inner.find_lane_transitions()).map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = findBlock)]
@@ -279,9 +817,18 @@ impl JsStreetNetwork {
             .map_err(err_to_js)
     }
 
+    /// Returns GeoJSON polygons of every block. Each has the `boundary` road sides, `perimeter`
+    /// in meters, `area` in square meters, and a `neighborhood` number shared with blocks only
+    /// separated by local streets. `progress` is an optional callback, like in the constructor.
     #[wasm_bindgen(js_name = findAllBlocks)]
-    pub fn find_all_blocks(&self, sidewalks: bool) -> String {
-        self.inner.find_all_blocks(sidewalks).unwrap()
+    pub fn find_all_blocks(
+        &self,
+        sidewalks: bool,
+        progress: Option<js_sys::Function>,
+    ) -> Result<String, JsValue> {
+        self.inner
+            .find_all_blocks_with_progress(sidewalks, &mut js_progress(progress))
+            .map_err(err_to_js)
     }
 }
 
@@ -290,32 +837,10 @@ impl JsStreetNetwork {
 impl JsStreetNetwork {
     /// Modifies all affected roads
     #[wasm_bindgen(js_name = overwriteOsmTagsForWay)]
-    pub fn overwrite_osm_tags_for_way(&mut self, id: i64, tags: String) {
-        let id = osm::WayID(id);
-        let tags: Tags = serde_json::from_slice(tags.as_bytes()).unwrap();
-
-        let mut intersections = BTreeSet::new();
-        for road in self.inner.roads.values_mut() {
-            if road.from_osm_way(id) {
-                // Repeat some of the work in Road::new
-
-                // TODO This could panic, for example if the user removes the highway tag
-                road.lane_specs_ltr = osm2streets::get_lane_specs_ltr(&tags, &self.inner.config);
-                intersections.extend(road.endpoints());
-
-                // Silently fail
-                if let Ok(p) = Placement::parse(&tags) {
-                    road.reference_line_placement = p;
-                }
-
-                road.update_center_line(self.inner.config.driving_side);
-            }
-        }
-        for i in intersections {
-            self.inner.update_i(i);
-        }
-
-        self.ways.get_mut(&id).unwrap().tags = tags;
+    pub fn overwrite_osm_tags_for_way(&mut self, id: i64, tags: OsmTags) -> Result<(), JsValue> {
+        let tags: Tags = serde_wasm_bindgen::from_value(tags.into())?;
+        streets_reader::ways::overwrite_osm_tags_for_way(&mut self.inner, osm::WayID(id), tags)
+            .map_err(err_to_js)
     }
 
     #[wasm_bindgen(js_name = collapseShortRoad)]
@@ -332,6 +857,20 @@ impl JsStreetNetwork {
         }
     }
 
+    /// Moves an intersection to a new WGS84 position, dragging the ends of connected roads along.
+    #[wasm_bindgen(js_name = moveIntersection)]
+    pub fn move_intersection(
+        &mut self,
+        intersection: usize,
+        lon: f64,
+        lat: f64,
+    ) -> Result<(), JsValue> {
+        let pt = LonLat::new(lon, lat).to_pt(&self.inner.gps_bounds);
+        self.inner
+            .move_intersection(IntersectionID(intersection), pt)
+            .map_err(err_to_js)
+    }
+
     #[wasm_bindgen(js_name = zipSidepath)]
     pub fn zip_sidepath(&mut self, road: usize) {
         if let Some(sidepath) = Sidepath::new(&self.inner, RoadID(road)) {
@@ -357,7 +896,7 @@ impl JsDebugStreets {
     pub fn get_network(&self) -> JsValue {
         JsValue::from(JsStreetNetwork {
             inner: self.inner.streets.clone(),
-            ways: BTreeMap::new(),
+            skipped: Default::default(),
         })
     }
 
@@ -365,8 +904,382 @@ impl JsDebugStreets {
     pub fn to_debug_geojson(&self) -> Option<String> {
         self.inner.to_debug_geojson()
     }
+
+    /// Roads and intersections added, modified, or removed since the previous step
+    #[wasm_bindgen(js_name = toDiffGeojson)]
+    pub fn to_diff_geojson(&self) -> Option<String> {
+        self.inner.to_diff_geojson()
+    }
+}
+
+impl JsStreetNetwork {
+    /// Wraps part of this network. Its OSM index only covers that part.
+    fn with_network(&self, inner: StreetNetwork) -> Self {
+        Self {
+            inner,
+            skipped: Default::default(),
+        }
+    }
+
+    fn osm_ways(&self) -> impl Iterator<Item = (osm::WayID, &streets_reader::osm_reader::Way)> {
+        self.inner
+            .osm_index()
+            .into_iter()
+            .flat_map(|index| index.ways())
+    }
+
+    /// Leaving out the filter (or passing `null`) renders everything. `[minLon, minLat, maxLon,
+    /// maxLat]` renders just a bounding box, like the viewport. A string is parsed as a GeoJSON
+    /// polygon.
+    fn parse_filter(&self, filter: JsValue) -> Result<Filter, JsValue> {
+        if filter.is_undefined() || filter.is_null() {
+            return Ok(Filter::All);
+        }
+        if let Some(geojson) = filter.as_string() {
+            let mut list = LonLat::parse_geojson_polygons(geojson).map_err(err_to_js)?;
+            if list.len() != 1 {
+                return Err(JsValue::from_str(
+                    "The filter doesn't contain exactly one polygon",
+                ));
+            }
+            return Filter::within_gps(&self.inner.gps_bounds, list.pop().unwrap().0)
+                .map_err(err_to_js);
+        }
+        let bbox: Vec<f64> = serde_wasm_bindgen::from_value(filter)?;
+        if bbox.len() != 4 {
+            return Err(JsValue::from_str(
+                "A bounding box filter needs [minLon, minLat, maxLon, maxLat]",
+            ));
+        }
+        Ok(Filter::bbox_from_gps(
+            &self.inner.gps_bounds,
+            LonLat::new(bbox[0], bbox[1]),
+            LonLat::new(bbox[2], bbox[3]),
+        ))
+    }
+}
+
+/// Imports a `JsStreetNetwork` in stages, returning to JS in between. Inside a web worker, this
+/// lets the event loop run, so messages like cancellation get through. Call `step` until it
+/// returns `false`, then `finish`.
+#[wasm_bindgen]
+pub struct JsImport {
+    stage: ImportStage,
+    progress: Option<js_sys::Function>,
+    drop_osm_document: bool,
+    debug_each_step: bool,
+}
+
+enum ImportStage {
+    Read {
+        osm_input: Vec<u8>,
+        clip_pts: Option<Vec<LonLat>>,
+        cfg: MapConfig,
+        transformations: Vec<Transformation>,
+    },
+    Extract(streets_reader::ChunkedImport, Vec<Transformation>),
+    Split(streets_reader::ChunkedImport, Vec<Transformation>),
+    Transform(JsStreetNetwork, VecDeque<Transformation>),
+    Done(JsStreetNetwork),
+    // A step failed, or is in progress
+    Failed,
+}
+
+#[wasm_bindgen]
+impl JsImport {
+    /// Takes the same arguments as the `JsStreetNetwork` constructor, but doesn't do any work yet.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        osm_input: &[u8],
+        clip_pts_geojson: &str,
+        input: JsImportOptions,
+        progress: Option<js_sys::Function>,
+    ) -> Result<JsImport, JsValue> {
+        SETUP_LOGGER.call_once(|| console_log::init_with_level(log::Level::Info).unwrap());
+        console_error_panic_hook::set_once();
+
+        let input: ImportOptions = serde_wasm_bindgen::from_value(input.into())?;
+        let settings = ImportSettings::new(input)?;
+        Ok(Self {
+            stage: ImportStage::Read {
+                osm_input: osm_input.to_vec(),
+                clip_pts: parse_clip_pts(clip_pts_geojson)?,
+                cfg: settings.cfg,
+                transformations: settings.transformations,
+            },
+            progress,
+            drop_osm_document: settings.drop_osm_document,
+            debug_each_step: settings.debug_each_step,
+        })
+    }
+
+    /// Describes the work the next call to `step` will do.
+    #[wasm_bindgen(js_name = nextStage)]
+    pub fn next_stage(&self) -> String {
+        match self.stage {
+            ImportStage::Read { .. } => "reading OSM".to_string(),
+            ImportStage::Extract(_, _) => "processing OSM".to_string(),
+            ImportStage::Split(_, _) => "splitting OSM ways".to_string(),
+            ImportStage::Transform(_, ref transformations) => {
+                if self.debug_each_step {
+                    "transforming".to_string()
+                } else {
+                    format!("transforming ({} steps left)", transformations.len())
+                }
+            }
+            ImportStage::Done(_) | ImportStage::Failed => "done".to_string(),
+        }
+    }
+
+    /// Does the next stage of importing. Returns `false` when everything is done. Each
+    /// transformation is a separate stage, unless `debug_each_step` is set.
+    pub fn step(&mut self) -> Result<bool, JsValue> {
+        let mut progress = js_progress(self.progress.clone());
+        let mut timer = Timer::throwaway();
+
+        self.stage = match std::mem::replace(&mut self.stage, ImportStage::Failed) {
+            ImportStage::Read {
+                osm_input,
+                clip_pts,
+                cfg,
+                transformations,
+            } => {
+                let import = streets_reader::ChunkedImport::read(
+                    &osm_input,
+                    clip_pts,
+                    cfg,
+                    &mut timer,
+                    &mut progress,
+                )
+                .map_err(err_to_js)?;
+                ImportStage::Extract(import, transformations)
+            }
+            ImportStage::Extract(mut import, transformations) => {
+                import
+                    .extract(&mut timer, &mut progress)
+                    .map_err(err_to_js)?;
+                ImportStage::Split(import, transformations)
+            }
+            ImportStage::Split(mut import, transformations) => {
+                import.split(&mut timer, &mut progress).map_err(err_to_js)?;
+                let (mut inner, mut doc) = import.finish().map_err(err_to_js)?;
+                let skipped = std::mem::take(&mut doc.skipped);
+                let index = doc.into_osm_index(&inner, self.drop_osm_document);
+                inner.set_osm_index(index);
+                ImportStage::Transform(JsStreetNetwork { inner, skipped }, transformations.into())
+            }
+            ImportStage::Transform(mut network, mut transformations) => {
+                if self.debug_each_step {
+                    network
+                        .inner
+                        .apply_transformations_stepwise_debugging_with_progress(
+                            transformations.drain(..).collect(),
+                            &mut timer,
+                            &mut progress,
+                        )
+                        .map_err(err_to_js)?;
+                } else if let Some(transformation) = transformations.pop_front() {
+                    network
+                        .inner
+                        .apply_transformations_with_progress(
+                            vec![transformation],
+                            &mut timer,
+                            &mut progress,
+                        )
+                        .map_err(err_to_js)?;
+                }
+                if transformations.is_empty() {
+                    ImportStage::Done(network)
+                } else {
+                    ImportStage::Transform(network, transformations)
+                }
+            }
+            ImportStage::Done(network) => ImportStage::Done(network),
+            ImportStage::Failed => {
+                return Err(JsValue::from_str("An earlier step failed"));
+            }
+        };
+        Ok(!matches!(self.stage, ImportStage::Done(_)))
+    }
+
+    /// Returns the imported network, after `step` has returned `false`.
+    pub fn finish(self) -> Result<JsStreetNetwork, JsValue> {
+        match self.stage {
+            ImportStage::Done(network) => Ok(network),
+            _ => Err(JsValue::from_str(
+                "Importing isn't done yet; keep calling step",
+            )),
+        }
+    }
+}
+
+/// Everything decided from `ImportOptions`
+struct ImportSettings {
+    cfg: MapConfig,
+    transformations: Vec<Transformation>,
+    drop_osm_document: bool,
+    debug_each_step: bool,
+}
+
+impl ImportSettings {
+    fn new(input: ImportOptions) -> Result<Self, JsValue> {
+        let mut cfg = MapConfig::default();
+        cfg.inferred_sidewalks = input.inferred_sidewalks;
+        cfg.inferred_kerbs = input.inferred_kerbs;
+        cfg.date_time = input.date_time;
+        cfg.reference_line_simplification = Distance::meters(input.reference_line_simplification);
+        cfg.degenerate_smoothing_max_deviation =
+            Distance::meters(input.degenerate_smoothing_max_deviation);
+        cfg.short_road_threshold = Distance::meters(input.short_road_threshold);
+        cfg.do_not_collapse = input.do_not_collapse.into_iter().map(osm::WayID).collect();
+        cfg.highway_filter.include = input.include_highways.into_iter().collect();
+        cfg.highway_filter.exclude = input.exclude_highways.into_iter().collect();
+        cfg.override_driving_side = match input.override_driving_side.as_str() {
+            "" => None,
+            "Left" => Some(DrivingSide::Left),
+            "Right" => Some(DrivingSide::Right),
+            x => {
+                return Err(JsValue::from_str(&format!(
+                    "Unknown override_driving_side = {x}"
+                )))
+            }
+        };
+        cfg.dead_end_cap = match input.dead_end_cap.as_str() {
+            "" | "square" => DeadEndCap::Square,
+            "rounded" => DeadEndCap::Rounded,
+            "turning_circles" => DeadEndCap::TurningCircles,
+            x => return Err(JsValue::from_str(&format!("Unknown dead_end_cap = {x}"))),
+        };
+        cfg.intersection_geometry = parse_intersection_geometry(&input.intersection_geometry)?;
+        for (node, value) in &input.intersection_geometry_overrides {
+            let Ok(node) = node.parse::<i64>() else {
+                return Err(JsValue::from_str(&format!("Unknown OSM node ID {node}")));
+            };
+            cfg.intersection_geometry_overrides
+                .insert(osm::NodeID(node), parse_intersection_geometry(value)?);
+        }
+        cfg.indoor_paths = match input.indoor_paths.as_str() {
+            "" => IndoorPaths::Include,
+            "exclude" => IndoorPaths::Exclude,
+            "separate" => IndoorPaths::Separate,
+            x => return Err(JsValue::from_str(&format!("Unknown indoor_paths = {x}"))),
+        };
+        cfg.block_barriers = input.block_barriers;
+        cfg.keep_original = input.keep_original;
+        cfg.preserve_road_names = input.preserve_road_names;
+        let frontage_roads = match input.frontage_roads.as_str() {
+            "" => None,
+            "detect" => Some(Transformation::DetectFrontageRoads),
+            "merge" => Some(Transformation::MergeFrontageRoads),
+            x => return Err(JsValue::from_str(&format!("Unknown frontage_roads = {x}"))),
+        };
+
+        let mut transformations = Transformation::standard_for_clipped_areas();
+        if input.dual_carriageway_experiment {
+            // Collapsing short roads tries to touch "bridges," making debugging harder
+            transformations.retain(|t| !matches!(t, Transformation::CollapseShortRoads));
+            transformations.push(Transformation::MergeDualCarriageways);
+        }
+        if input.sidepath_zipping_experiment {
+            transformations.push(Transformation::ZipSidepaths);
+            transformations.push(Transformation::CollapseDegenerateIntersections);
+        }
+        if let Some(transformation) = frontage_roads {
+            transformations.push(transformation);
+            transformations.push(Transformation::CollapseDegenerateIntersections);
+        }
+        if input.remove_unbuilt_roads {
+            transformations.insert(0, Transformation::RemoveUnbuiltRoads);
+        }
+        if input.smooth_road_geometry {
+            transformations.push(Transformation::SmoothRoadGeometry);
+        }
+        if input.blend_placement_offsets {
+            transformations.push(Transformation::BlendPlacementOffsets);
+        }
+        if input.mark_lane_transitions {
+            transformations.push(Transformation::MarkLaneTransitions);
+        }
+        if input.link_separate_ways {
+            transformations.push(Transformation::LinkSeparateWays);
+        }
+
+        Ok(Self {
+            cfg,
+            transformations,
+            drop_osm_document: input.drop_osm_document,
+            debug_each_step: input.debug_each_step,
+        })
+    }
+}
+
+fn parse_intersection_geometry(value: &str) -> Result<IntersectionGeometry, JsValue> {
+    match value {
+        "" | "corner_trim" => Ok(IntersectionGeometry::CornerTrim),
+        "convex_hull" => Ok(IntersectionGeometry::ConvexHull),
+        "mapped_area" => Ok(IntersectionGeometry::MappedArea),
+        "buffer_union_fallback" => Ok(IntersectionGeometry::BufferUnionFallback),
+        x => Err(JsValue::from_str(&format!(
+            "Unknown intersection_geometry = {x}"
+        ))),
+    }
+}
+
+/// Parses a routing profile: "pedestrian", "wheelchair", "bicycle", "motor", or "rail".
+fn parse_profile(value: &str) -> Result<Profile, JsValue> {
+    match value {
+        "pedestrian" => Ok(Profile::Pedestrian),
+        "wheelchair" => Ok(Profile::Wheelchair),
+        "bicycle" => Ok(Profile::Bicycle),
+        "motor" => Ok(Profile::Motor),
+        "rail" => Ok(Profile::Rail),
+        x => Err(JsValue::from_str(&format!("Unknown profile {x}"))),
+    }
+}
+
+/// Empty means no clipping
+fn parse_clip_pts(clip_pts_geojson: &str) -> Result<Option<Vec<LonLat>>, JsValue> {
+    if clip_pts_geojson.is_empty() {
+        return Ok(None);
+    }
+    let mut list =
+        LonLat::parse_geojson_polygons(clip_pts_geojson.to_string()).map_err(err_to_js)?;
+    if list.len() != 1 {
+        return Err(JsValue::from_str(&format!(
+            "{clip_pts_geojson} doesn't contain exactly one polygon"
+        )));
+    }
+    Ok(Some(list.pop().unwrap().0))
+}
+
+/// Converts to a plain JS value. Maps become objects, not `Map`s.
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsValue> {
+    value
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(err_to_js)
 }
 
 fn err_to_js<E: std::fmt::Display>(err: E) -> JsValue {
     JsValue::from_str(&err.to_string())
 }
+
+/// Calls a JS function with `(stage, fraction)`. If it returns `false`, cancels the operation.
+fn js_progress(callback: Option<js_sys::Function>) -> Progress<'static> {
+    let Some(f) = callback else {
+        return Progress::none();
+    };
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    Progress::new(move |stage, fraction| {
+        if let Ok(result) = f.call2(
+            &JsValue::NULL,
+            &JsValue::from_str(stage),
+            &JsValue::from_f64(fraction),
+        ) {
+            if result == JsValue::FALSE {
+                cancel.cancel();
+            }
+        }
+    })
+    .with_cancellation(token)
+}
@@ -1,12 +1,14 @@
 use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
 use std::sync::Once;
 
 use abstutil::{Tags, Timer};
 use chrono::NaiveDateTime;
-use geom::{Distance, LonLat, PolyLine, Polygon};
+use geom::{Distance, Duration, LonLat};
 use osm2streets::{
-    osm, DebugStreets, DrivingSide, Filter, IntersectionID, LaneID, MapConfig, Placement, RoadID,
-    RoadSideID, SideOfRoad, Sidepath, StreetNetwork, Transformation,
+    osm, Budget, CancellationToken, Crs, DeadEndCap, DebugStreets, DrivingSide, Filter,
+    IndoorPaths, IntersectionGeometry, IntersectionID, LaneID, MapConfig, Profile, Progress,
+    RoadID, RoadSideID, SideOfRoad, Sidepath, StreetNetwork, Transformation, Truck,
 };
 use pyo3::prelude::*;
 use serde::{Deserialize, Serialize};
@@ -23,12 +25,80 @@ pub struct ImportOptions {
     inferred_kerbs: bool,
     date_time: Option<NaiveDateTime>,
     override_driving_side: String,
+    /// After importing, only keep the OSM ways that became roads, the nodes that became
+    /// intersections, and relations referencing those ways
+    #[serde(default)]
+    drop_osm_document: bool,
+    /// What to do with frontage roads running alongside main roads. Empty does nothing, "detect"
+    /// records them in `parallel_to`, and "merge" zips them into the main road.
+    #[serde(default)]
+    frontage_roads: String,
+    /// Simplify road geometry, removing points that stray less than this many meters. Zero
+    /// disables this.
+    #[serde(default)]
+    reference_line_simplification: f64,
+    /// When collapsing a degenerate intersection joins two roads at a sharp angle, smooth the kink
+    /// into a curve straying at most this many meters from the original geometry. Zero disables
+    /// this.
+    #[serde(default)]
+    degenerate_smoothing_max_deviation: f64,
+    /// Smooth sharp corners along roads into curves
+    #[serde(default)]
+    smooth_road_geometry: bool,
+    /// Make lanes that end mid-block merge into the next lane over, by setting their allowed turns
+    #[serde(default)]
+    mark_lane_transitions: bool,
+    /// `highway` values to import besides the defaults, like "bridleway" or "proposed"
+    #[serde(default)]
+    include_highways: Vec<String>,
+    /// `highway` values not to import, like "construction" or "track"
+    #[serde(default)]
+    exclude_highways: Vec<String>,
+    /// Remove roads under construction, proposed, or disused after importing
+    #[serde(default)]
+    remove_unbuilt_roads: bool,
+    /// The shape of dead-ends: "square" (the default), "rounded", or "turning_circles"
+    #[serde(default)]
+    dead_end_cap: String,
+    /// Also collapse roads shorter than this many meters. Zero disables this.
+    #[serde(default)]
+    short_road_threshold: f64,
+    /// OSM way IDs that should never be collapsed
+    #[serde(default)]
+    do_not_collapse: Vec<i64>,
+    /// Where roads with a different `placement` meet, ease their center lines to meet smoothly
+    #[serde(default)]
+    blend_placement_offsets: bool,
+    /// How to calculate intersection polygons: "corner_trim" (the default), "convex_hull",
+    /// "mapped_area", or "buffer_union_fallback"
+    #[serde(default)]
+    intersection_geometry: String,
+    /// Per OSM node ID, a different `intersection_geometry` to use for that intersection
+    #[serde(default)]
+    intersection_geometry_overrides: BTreeMap<String, String>,
+    /// What to do with paths inside buildings: "" imports them like anything else, "exclude"
+    /// drops them, and "separate" keeps them apart from the network
+    #[serde(default)]
+    indoor_paths: String,
+    /// Split blocks where rivers, canals, and railways that aren't roads cross them
+    #[serde(default)]
+    block_barriers: bool,
+    /// Keep a copy of the network before any transformations
+    #[serde(default)]
+    keep_original: bool,
+    /// Don't collapse roads or intersections when a road name would be lost
+    #[serde(default)]
+    preserve_road_names: bool,
+    /// Link roads tagged with separately mapped sidewalks or cycleways to the ways running
+    /// alongside them
+    #[serde(default)]
+    link_separate_ways: bool,
 }
 
 #[pyclass]
 pub struct PyStreetNetwork {
     inner: StreetNetwork,
-    ways: BTreeMap<osm::WayID, streets_reader::osm_reader::Way>,
+    skipped: streets_reader::SkipReport,
 }
 
 #[pymethods]
@@ -39,109 +109,652 @@ impl PyStreetNetwork {
     /// - `clip_pts_geojson`: Optional GeoJSON string representing a polygon to clip the input data.
     /// - `input`: JSON string that sets configuration options for the import, including `debug_each_step`,
     ///   `dual_carriageway_experiment`, `sidepath_zipping_experiment`, `inferred_sidewalks`, `inferred_kerbs`,
-    ///   `date_time`, and `override_driving_side`.
+    ///   `date_time`, `override_driving_side`, and optionally `drop_osm_document` to save memory by
+    ///   only keeping the OSM ways that became roads, `frontage_roads` ("detect" or "merge"),
+    ///   `include_highways` / `exclude_highways` (lists of `highway` values),
+    ///   `remove_unbuilt_roads`, `dead_end_cap` ("square", "rounded", or "turning_circles"),
+    ///   `short_road_threshold` in meters, `do_not_collapse` (a list of OSM way IDs),
+    ///   `blend_placement_offsets`, `intersection_geometry` ("corner_trim", "convex_hull",
+    ///   "mapped_area", or "buffer_union_fallback"), `intersection_geometry_overrides` (a dict from OSM node ID to one of
+    ///   those), `indoor_paths` ("exclude" or "separate"), `block_barriers` to split blocks
+    ///   at waterways and railways, `keep_original` to keep a copy of the network before
+    ///   transformations, `preserve_road_names` to avoid collapsing roads when a name
+    ///   would be lost, and `link_separate_ways` to find the ways that `sidewalk=separate` and
+    ///   similar tags refer to.
+    /// - `progress`: Optional callable, called with the name of the current stage and the fraction
+    ///   of it completed. If it returns `False`, the import is cancelled and raises an error.
     #[new]
+    #[args(progress = "None")]
     pub fn new(
         py: Python, // Added `py: Python` here to get the Python context
         osm_input: &[u8],
         clip_pts_geojson: &str,
         input: PyObject,
+        progress: Option<PyObject>,
     ) -> PyResult<Self> {
-        SETUP_LOGGER.call_once(|| env_logger::init());
-
-        let input: ImportOptions = serde_json::from_str(input.extract::<&str>(py)?)
-            .map_err(|e| err_to_py_value(format!("Failed to parse input: {}", e)))?;
-
-        // Parse clip points if provided
-        let clip_pts = if clip_pts_geojson.is_empty() {
-            None
-        } else {
-            let mut list = LonLat::parse_geojson_polygons(clip_pts_geojson.to_string())
-                .map_err(|e| err_to_py_value(format!("{}", e)))?;
-            if list.len() != 1 {
-                return Err(err_to_py_value(
-                    "clip_pts_geojson must contain exactly one polygon",
-                ));
-            }
-            Some(list.pop().unwrap().0)
-        };
+        let input = input.extract::<&str>(py)?;
+        Self::import(py, osm_input, clip_pts_geojson, input, progress)
+    }
 
-        let mut cfg = MapConfig::default();
-        cfg.inferred_sidewalks = input.inferred_sidewalks;
-        cfg.inferred_kerbs = input.inferred_kerbs;
-        cfg.date_time = input.date_time;
-        cfg.override_driving_side = match input.override_driving_side.as_str() {
-            "" => None,
-            "Left" => Some(DrivingSide::Left),
-            "Right" => Some(DrivingSide::Right),
-            x => {
-                return Err(err_to_py_value(format!("Unknown driving side: {x}")));
-            }
+    /// Creates a new instance of `PyStreetNetwork`, reading the OSM input from somewhere besides
+    /// memory. Large inputs are read by Rust in chunks, without first loading all of them into a
+    /// Python `bytes` object.
+    ///
+    /// - `source`: A path to a `.osm` or `.pbf` file, an `http://` or `https://` URL, or a
+    ///   file-like object opened in binary mode, such as one from `fsspec.open`.
+    /// - `input`: The same JSON string of import options as the constructor.
+    /// - `clip_path`: Optional path to a GeoJSON file with a polygon to clip the input data.
+    /// - `progress`: The same optional callable as the constructor.
+    #[staticmethod]
+    #[args(clip_path = "None", progress = "None")]
+    pub fn from_source(
+        py: Python,
+        source: PyObject,
+        input: &str,
+        clip_path: Option<PathBuf>,
+        progress: Option<PyObject>,
+    ) -> PyResult<Self> {
+        let osm_input = read_source(py, source)?;
+        let clip_pts_geojson = match clip_path {
+            Some(path) => std::fs::read_to_string(&path)
+                .map_err(|err| err_to_py_value(format!("Can't read {}: {err}", path.display())))?,
+            None => String::new(),
         };
+        Self::import(py, &osm_input, &clip_pts_geojson, input, progress)
+    }
 
-        let mut timer = Timer::throwaway();
-        let (mut street_network, doc) =
-            streets_reader::osm_to_street_network(osm_input, clip_pts, cfg, &mut timer)
-                .map_err(err_to_py_runtime)?;
-
-        let mut transformations = Transformation::standard_for_clipped_areas();
-        if input.dual_carriageway_experiment {
-            transformations.retain(|t| !matches!(t, Transformation::CollapseShortRoads));
-            transformations.push(Transformation::MergeDualCarriageways);
-        }
-        if input.sidepath_zipping_experiment {
-            transformations.push(Transformation::ZipSidepaths);
-            transformations.push(Transformation::CollapseDegenerateIntersections);
-        }
-
-        if input.debug_each_step {
-            street_network.apply_transformations_stepwise_debugging(transformations, &mut timer);
-        } else {
-            street_network.apply_transformations(transformations, &mut timer);
-        }
+    /// Converts the entire `StreetNetwork` to a GeoJSON format.
+    ///
+    /// Returns a GeoJSON string representing all elements in the street network. To render only
+    /// part of the network, pass `bbox` as `[min_lon, min_lat, max_lon, max_lat]`, or a
+    /// `polygon_geojson`. The other rendering methods take the same arguments.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_geojson_plain(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
 
-        Ok(Self {
-            inner: street_network,
-            ways: doc.ways,
-        })
+    /// Like `to_geojson_plain`, but for the network before any transformations. Only works if
+    /// `keep_original` was set.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_original_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        let Some(original) = self.inner.original() else {
+            return Err(err_to_py_value("keep_original wasn't set"));
+        };
+        original
+            .to_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
     }
 
-    /// Converts the entire `StreetNetwork` to a GeoJSON format.
+    /// Transforms the output of any GeoJSON method into another coordinate system.
     ///
-    /// Returns a GeoJSON string representing all elements in the street network.
-    pub fn to_geojson_plain(&self) -> PyResult<String> {
+    /// `crs` is "lonlat", "web_mercator", "utm", "local_azimuthal", or "map" for internal
+    /// coordinates. The result names the EPSG code and proj4 string.
+    pub fn reproject_geojson(&self, geojson: &str, crs: &str) -> PyResult<String> {
+        let crs = Crs::parse(crs).map_err(err_to_py_value)?;
         self.inner
-            .to_geojson(&Filter::All)
+            .reproject_geojson(geojson, crs)
             .map_err(err_to_py_runtime)
     }
 
+    /// Adds a `hash` property to every feature in the output of any GeoJSON method.
+    ///
+    /// The hash covers the geometry and other properties, so unchanged features can be skipped
+    /// after an edit.
+    pub fn add_feature_hashes(&self, geojson: &str) -> PyResult<String> {
+        osm2streets::add_feature_hashes(geojson).map_err(err_to_py_runtime)
+    }
+
     /// Converts lane polygons in the `StreetNetwork` to a GeoJSON format.
     ///
     /// Returns a GeoJSON string representing the polygons of each lane.
-    pub fn to_lane_polygons_geojson(&self) -> PyResult<String> {
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_lane_polygons_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
         self.inner
-            .to_lane_polygons_geojson(&Filter::All)
+            .to_lane_polygons_geojson(&self.parse_filter(bbox, polygon_geojson)?)
             .map_err(err_to_py_runtime)
     }
 
     /// Converts lane markings in the `StreetNetwork` to a GeoJSON format.
     ///
     /// Returns a GeoJSON string representing the lane markings, such as dashed or solid lines.
-    pub fn to_lane_markings_geojson(&self) -> PyResult<String> {
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_lane_markings_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
         self.inner
-            .to_lane_markings_geojson(&Filter::All)
+            .to_lane_markings_geojson(&self.parse_filter(bbox, polygon_geojson)?)
             .map_err(err_to_py_runtime)
     }
 
     /// Converts intersection markings in the `StreetNetwork` to a GeoJSON format.
     ///
     /// Returns a GeoJSON string representing the markings at intersections.
-    pub fn to_intersection_markings_geojson(&self) -> PyResult<String> {
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_intersection_markings_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_intersection_markings_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Converts the movements through each intersection to a GeoJSON format.
+    ///
+    /// Returns a GeoJSON string with an arrow per movement, with properties describing the roads
+    /// involved, the turn direction, the classes of traffic that can make it, any turn restriction
+    /// relation, the control on the approach, and how many other movements conflict with it.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_movements_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_movements_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Describes movements through intersections for routing costs.
+    ///
+    /// Returns a JSON array describing each allowed movement through intersections touching `bbox`
+    /// or `polygon_geojson`: the turn `angle` and `turn_direction`, the `approach_control`, the
+    /// number of `conflicting_movements`, and whether it `crosses_traffic`.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_movement_attributes_json(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_movement_attributes_json(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Converts the signs, signals, and crossings controlling intersections to a GeoJSON format.
+    ///
+    /// Returns a GeoJSON string with a point per device, with its kind, intersection, and the
+    /// incoming roads it applies to (empty for every approach).
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_traffic_control_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_traffic_control_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Converts the places where roads cross without connecting to a GeoJSON format.
+    ///
+    /// Returns a GeoJSON string with a point per crossing, with the upper and lower road, the
+    /// difference in their layers, any bridge or tunnel, and whether a `layer` tag looks missing.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_grade_separations_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_grade_separations_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Converts groups of intersections connected by `junction=intersection` roads to a GeoJSON
+    /// format.
+    ///
+    /// Returns a GeoJSON string with a polygon per group, listing the member intersections,
+    /// internal and external roads, and the through-movements between external roads.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_junction_complexes_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_junction_complexes_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Shows the full right-of-way of each street.
+    ///
+    /// Returns a GeoJSON string with a polygon per street, covering its roads at full width,
+    /// including sidewalks, verges, and zipped sidepaths, and its intersections. Roads sharing a
+    /// name form one street.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_right_of_way_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_right_of_way_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Splits the network into a layer per mode of travel.
+    ///
+    /// Returns a JSON object with `motor`, `cycling`, `walking`, and `rail` GeoJSON
+    /// FeatureCollections. Each has the lanes usable by that mode, with `road` and `index`
+    /// properties, and the intersections connecting them, with an `intersection` property.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_mode_layers_geojson(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_mode_layers_geojson(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Describes which lanes vehicles may change between.
+    ///
+    /// Returns a JSON array with each pair of neighboring lanes on the roads touching `bbox` or
+    /// `polygon_geojson`, and whether changing from left to right and right to left is allowed.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_lane_adjacency_json(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_lane_adjacency_json(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Previews collapsing short roads.
+    ///
+    /// Returns a JSON array of the roads that would be collapsed, without changing anything. Each
+    /// has the `road` ID, `reasons` (`Length`, `JunctionTag`, or `TrimOverlap`), `length` in
+    /// meters, and `blocked` if it won't actually be collapsed.
+    pub fn preview_collapse_short_roads(&self) -> PyResult<String> {
+        self.inner
+            .preview_collapse_short_roads_json()
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Previews the OSM tags changed by a lane edit.
+    ///
+    /// Takes a road ID and a lane edit as JSON, like `{"ChangeType": [2, "Biking"]}`. Returns a
+    /// JSON array of the tags this edit would change, each with a `key`, and the `old` and `new`
+    /// value (null if the tag is added or removed). Nothing is changed.
+    pub fn preview_lane_edit_tags(&self, road: usize, edit: &str) -> PyResult<String> {
+        self.inner
+            .preview_lane_edit_tags_json(RoadID(road), edit)
+            .map_err(err_to_py_value)
+    }
+
+    /// Validates the network.
+    ///
+    /// Returns a JSON array of internal problems, like roads and intersections that don't point to
+    /// each other, each with a `description`. These indicate bugs in osm2streets.
+    pub fn find_invariant_violations(&self) -> PyResult<String> {
+        self.inner
+            .to_invariant_violations_json()
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Draws part of the network as an SVG.
+    ///
+    /// Lanes are colored by type, with markings, intersections, and road names. Pass `bbox` or
+    /// `polygon_geojson` to draw just one intersection or street.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_svg(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_svg(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Exports the simplified network as synthetic OSM XML.
+    ///
+    /// Each road becomes one way with tags describing its lanes, and each intersection becomes one
+    /// node. All IDs are negative; the original OSM IDs are in `osm2streets:*` tags.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_osm_xml(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        Ok(self
+            .inner
+            .to_osm_xml(&self.parse_filter(bbox, polygon_geojson)?))
+    }
+
+    /// Summarizes the whole network.
+    ///
+    /// Returns a JSON object with lengths by highway type, lane-km by lane type, intersection
+    /// counts by kind and control, and the percent of driveable roads with sidewalks and cycle
+    /// infrastructure.
+    pub fn to_summary_json(&self) -> PyResult<String> {
+        self.inner.to_summary_json().map_err(err_to_py_runtime)
+    }
+
+    /// Measures the width of roads.
+    ///
+    /// Returns a JSON array with the carriageway, curb-to-curb, and right-of-way widths in meters
+    /// and the number of general purpose lanes of each road touching `bbox` or `polygon_geojson`.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_road_widths_json(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_road_widths_json(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Estimates the capacity of roads.
+    ///
+    /// Returns a JSON array with rough estimates of the hourly capacity and free-flow speed of each
+    /// road touching `bbox` or `polygon_geojson`, `forward` and `backward` relative to the road's
+    /// direction.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_road_capacity_json(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_road_capacity_json(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Marks barriers for wheelchair users.
+    ///
+    /// Returns a GeoJSON string with kerbs without curb cuts, steps without ramps, sidewalks
+    /// narrower than `min_sidewalk_width` meters, and rough or steep paths touching `bbox` or
+    /// `polygon_geojson`. Each feature has a `barrier` and a `description`.
+    #[args(min_sidewalk_width = "0.9", bbox = "None", polygon_geojson = "None")]
+    pub fn to_accessibility_geojson(
+        &self,
+        min_sidewalk_width: f64,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<String> {
+        self.inner
+            .to_accessibility_geojson(
+                &self.parse_filter(bbox, polygon_geojson)?,
+                Distance::meters(min_sidewalk_width),
+            )
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Finds roads that split the network for a truck.
+    ///
+    /// Returns a GeoJSON string with every road that a truck of this `height` in meters,
+    /// `weight`, and `axle_load` in tonnes can't use, where no alternative connects the truck
+    /// network. Each feature has a `reason` and whether it's a bridge or tunnel.
+    #[args(height = "4.0", weight = "40.0", axle_load = "11.5")]
+    pub fn to_freight_barriers_geojson(
+        &self,
+        height: f64,
+        weight: f64,
+        axle_load: f64,
+    ) -> PyResult<String> {
+        self.inner
+            .to_freight_barriers_geojson(&Truck {
+                height: Distance::meters(height),
+                weight,
+                axle_load,
+            })
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Converts every road crossing the map boundary to a GeoJSON format.
+    ///
+    /// Returns a GeoJSON string with an arrow pointing off-map per road, and properties describing
+    /// whether traffic can enter or leave the map there.
+    pub fn to_map_edges_geojson(&self) -> PyResult<String> {
+        self.inner.to_map_edges_geojson().map_err(err_to_py_runtime)
+    }
+
+    /// Converts paths inside buildings to a GeoJSON format.
+    ///
+    /// Returns a GeoJSON string with a line per path kept apart from the network with
+    /// `indoor_paths` set to "separate".
+    pub fn to_indoor_paths_geojson(&self) -> PyResult<String> {
+        self.inner
+            .to_indoor_paths_geojson()
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Returns ferry routes as GeoJSON lines.
+    ///
+    /// Each feature has the intersections on either end, when both terminals are near the road
+    /// network.
+    pub fn to_ferries_geojson(&self) -> PyResult<String> {
+        self.inner.to_ferries_geojson().map_err(err_to_py_runtime)
+    }
+
+    /// Divides parking lanes into individual spots.
+    ///
+    /// Returns a GeoJSON string with a polygon per spot, including the capacity of its lane, and
+    /// a polygon per stretch of parking lane kept clear near intersections and driveways.
+    pub fn to_parking_geojson(&self) -> PyResult<String> {
+        self.inner.to_parking_geojson().map_err(err_to_py_runtime)
+    }
+
+    /// Gzips the output of another method, to shrink big payloads.
+    ///
+    /// - `call`: The name of the method, like `to_lane_polygons_geojson`.
+    /// - `bbox` and `polygon_geojson`: Used by the rendering methods.
+    ///
+    /// Returns the compressed output as bytes.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn to_gzipped_output(
+        &self,
+        py: Python,
+        call: &str,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<PyObject> {
+        let filter = self.parse_filter(bbox, polygon_geojson)?;
+        let output = match call {
+            "to_geojson_plain" => self.inner.to_geojson(&filter),
+            "to_lane_polygons_geojson" => self.inner.to_lane_polygons_geojson(&filter),
+            "to_lane_markings_geojson" => self.inner.to_lane_markings_geojson(&filter),
+            "to_intersection_markings_geojson" => {
+                self.inner.to_intersection_markings_geojson(&filter)
+            }
+            "to_movements_geojson" => self.inner.to_movements_geojson(&filter),
+            "to_traffic_control_geojson" => self.inner.to_traffic_control_geojson(&filter),
+            "to_grade_separations_geojson" => self.inner.to_grade_separations_geojson(&filter),
+            "to_junction_complexes_geojson" => self.inner.to_junction_complexes_geojson(&filter),
+            "to_right_of_way_geojson" => self.inner.to_right_of_way_geojson(&filter),
+            "to_mode_layers_geojson" => self.inner.to_mode_layers_geojson(&filter),
+            "to_map_edges_geojson" => self.inner.to_map_edges_geojson(),
+            "to_parking_geojson" => self.inner.to_parking_geojson(),
+            "to_json" => Ok(serde_json::to_string(&self.inner).map_err(err_to_py_runtime)?),
+            x => return Err(err_to_py_value(format!("Can't gzip unknown call {x}"))),
+        }
+        .map_err(err_to_py_runtime)?;
+        let bytes = osm2streets::gzip(&output).map_err(err_to_py_runtime)?;
+        Ok(pyo3::types::PyBytes::new(py, &bytes).into())
+    }
+
+    /// Describes the lanes of the road at a GPS position.
+    ///
+    /// Returns a JSON string with the road ID and each lane, with its offset in meters from the
+    /// OSM way, or `null` if no road covers the position.
+    pub fn cross_section_at_point(&self, lon: f64, lat: f64) -> PyResult<String> {
+        self.inner
+            .cross_section_at_point_json(LonLat::new(lon, lat))
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Places external point and interval data along roads.
+    ///
+    /// Takes a JSON list of events, each located by `road` and `start` (and optionally `end`) in
+    /// meters, or by `lon` and `lat`, with any `properties`. Returns a GeoJSON string with each
+    /// event's geometry.
+    pub fn linear_events_to_geojson(&self, events_json: &str) -> PyResult<String> {
+        let events = self
+            .inner
+            .parse_linear_events(events_json)
+            .map_err(err_to_py_runtime)?;
         self.inner
-            .to_intersection_markings_geojson(&Filter::All)
+            .linear_events_to_geojson(&events)
             .map_err(err_to_py_runtime)
     }
 
+    /// Snaps public transit stops to the network.
+    ///
+    /// - `stops_txt`: The contents of a GTFS `stops.txt` file.
+    ///
+    /// Returns a GeoJSON string of points, describing where each stop is along the network.
+    pub fn match_gtfs_stops_geojson(&self, stops_txt: &str) -> PyResult<String> {
+        let stops = osm2streets::parse_gtfs_stops(stops_txt).map_err(err_to_py_value)?;
+        let matches = self.inner.match_gtfs_stops(stops);
+        self.inner
+            .gtfs_matches_to_geojson(&matches)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Like `match_gtfs_stops_geojson`, but returns CSV.
+    pub fn match_gtfs_stops_csv(&self, stops_txt: &str) -> PyResult<String> {
+        let stops = osm2streets::parse_gtfs_stops(stops_txt).map_err(err_to_py_value)?;
+        let matches = self.inner.match_gtfs_stops(stops);
+        Ok(self.inner.gtfs_matches_to_csv(&matches))
+    }
+
+    /// Finds problems in the OSM tags, like impossible turn lanes and contradicting lane counts.
+    ///
+    /// Returns a GeoJSON string usable as a MapRoulette challenge, with one task per problem.
+    pub fn to_maproulette_geojson(&self) -> PyResult<String> {
+        let mut issues = self.inner.find_impossible_turn_lanes();
+        issues.extend(
+            self.inner
+                .find_tagging_issues(self.osm_ways().map(|(id, way)| (id, &way.tags))),
+        );
+        issues.extend(self.inner.find_unmatched_separate_ways());
+        self.inner
+            .to_maproulette_geojson(&issues)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Returns a JSON array with the input to every intersection whose geometry couldn't be
+    /// calculated. Save one as a file in `tests/geometry_fixtures` to reproduce the failure.
+    pub fn get_geometry_failures(&self) -> PyResult<String> {
+        let list: Vec<_> = self.inner.geometry_failures.values().collect();
+        serde_json::to_string(&list).map_err(err_to_py_runtime)
+    }
+
+    /// Returns a JSON array of turn restrictions dropped after transformations, because the roads
+    /// they refer to were removed or no longer connect.
+    pub fn get_stale_turn_restrictions(&self) -> PyResult<String> {
+        serde_json::to_string(&self.inner.stale_turn_restrictions).map_err(err_to_py_runtime)
+    }
+
+    /// Returns a JSON array explaining what each transformation changed.
+    ///
+    /// If `road` or `intersection` IDs are given, only includes entries mentioning them.
+    #[args(road = "None", intersection = "None")]
+    pub fn get_audit_log(
+        &self,
+        road: Option<usize>,
+        intersection: Option<usize>,
+    ) -> PyResult<String> {
+        self.inner
+            .audit_log_json(road.map(RoadID), intersection.map(IntersectionID))
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Copies the roads touching `bbox` or `polygon_geojson` into a standalone network, useful for
+    /// sharing a small piece of a map in a bug report.
+    #[args(bbox = "None", polygon_geojson = "None")]
+    pub fn subset(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<PyStreetNetwork> {
+        let inner = self
+            .inner
+            .subset(&self.parse_filter(bbox, polygon_geojson)?)
+            .map_err(err_to_py_value)?;
+        Ok(self.with_network(inner))
+    }
+
+    /// Like `subset`, but copies every road with a name, plus the roads up to `hops`
+    /// intersections away from them.
+    pub fn subset_around_street(&self, name: String, hops: usize) -> PyResult<PyStreetNetwork> {
+        let filter = Filter::around_street(&self.inner, &name, hops).map_err(err_to_py_value)?;
+        let inner = self.inner.subset(&filter).map_err(err_to_py_value)?;
+        Ok(self.with_network(inner))
+    }
+
+    /// Summarizes the OSM ways and relations ignored while importing as JSON.
+    ///
+    /// Keys are the reason something was skipped, with a count and a few sample IDs for each.
+    pub fn get_skip_report(&self) -> String {
+        self.skipped.to_json()
+    }
+
+    /// Finds every road reachable from an intersection.
+    ///
+    /// `profile` is `pedestrian`, `wheelchair`, `bicycle`, `motor`, or `rail`, and `budget` is in `unit`s of
+    /// `meters` or `seconds`. Returns a GeoJSON string with every road reachable within the budget,
+    /// cutting off roads only partly reachable where the budget runs out.
+    #[args(unit = "\"meters\"", profile = "\"pedestrian\"")]
+    pub fn reachable_within_geojson(
+        &self,
+        intersection: usize,
+        budget: f64,
+        unit: &str,
+        profile: &str,
+    ) -> PyResult<String> {
+        let budget = match unit {
+            "meters" => Budget::Distance(Distance::meters(budget)),
+            "seconds" => Budget::Time(Duration::seconds(budget)),
+            x => return Err(err_to_py_value(format!("Unknown budget unit: {x}"))),
+        };
+        self.inner
+            .reachable_within_geojson(
+                IntersectionID(intersection),
+                budget,
+                parse_profile(profile)?,
+            )
+            .map_err(err_to_py_value)
+    }
+
+    /// Summarizes every named corridor as JSON.
+    ///
+    /// Returns a JSON string listing each corridor's intersections in order, with their control
+    /// type, spacing, and approach lane counts.
+    pub fn to_corridors_json(&self) -> PyResult<String> {
+        self.inner.to_corridors_json().map_err(err_to_py_runtime)
+    }
+
+    /// Summarizes every named corridor as CSV.
+    ///
+    /// Returns a CSV string with one row per intersection along each corridor.
+    pub fn to_corridors_csv(&self) -> String {
+        self.inner.to_corridors_csv()
+    }
+
     /// Retrieves debugging steps for each modification applied to the `StreetNetwork`.
     ///
     /// Returns a vector of `PyDebugStreets` objects, which represent intermediate states or steps
@@ -171,6 +784,16 @@ impl PyStreetNetwork {
             .map_err(err_to_py_runtime)
     }
 
+    /// Shows how each end of every road was trimmed back from its intersection.
+    ///
+    /// Returns a GeoJSON string with a point at each road end, with the `algorithm` used, the
+    /// `reason` it was chosen, and the `trim` distance in meters.
+    pub fn debug_trim_provenance_geojson(&self) -> PyResult<String> {
+        self.inner
+            .debug_trim_provenance_geojson(&Filter::All)
+            .map_err(err_to_py_runtime)
+    }
+
     /// Converts clockwise ordering information for a specific intersection to GeoJSON format.
     ///
     /// - `intersection`: ID of the intersection to be debugged.
@@ -217,8 +840,7 @@ impl PyStreetNetwork {
                     .to_geojson(Some(&self.inner.gps_bounds)),
             );
         }
-        serde_json::to_string_pretty(&geom::geometries_to_geojson(polygons))
-            .map_err(err_to_py_runtime)
+        serde_json::to_string(&geom::geometries_to_geojson(polygons)).map_err(err_to_py_runtime)
     }
 
     /// Retrieves OSM tags for a given way (road or path).
@@ -227,8 +849,12 @@ impl PyStreetNetwork {
     ///
     /// Returns a JSON string with the OSM tags for the way, or an error if the way does not exist.
     pub fn get_osm_tags_for_way(&self, id: i64) -> PyResult<String> {
-        if let Some(ref way) = self.ways.get(&osm::WayID(id)) {
-            Ok(serde_json::to_string_pretty(&way.tags).unwrap())
+        if let Some(ref way) = self
+            .inner
+            .osm_index()
+            .and_then(|index| index.way(osm::WayID(id)))
+        {
+            Ok(serde_json::to_string(&way.tags).unwrap())
         } else {
             Err(err_to_py_value(format!("unknown way {}", id)))
         }
@@ -238,7 +864,7 @@ impl PyStreetNetwork {
     ///
     /// Returns a JSON string representing the full `StreetNetwork` data structure.
     pub fn to_json(&self) -> PyResult<String> {
-        serde_json::to_string_pretty(&self.inner).map_err(err_to_py_runtime)
+        serde_json::to_string(&self.inner).map_err(err_to_py_runtime)
     }
 
     /// Retrieves the geometry of a way (road or path) as a buffered polygon in GeoJSON format.
@@ -248,37 +874,9 @@ impl PyStreetNetwork {
     /// Returns a GeoJSON string of the way's geometry, including additional buffers and chevrons
     /// for indicating directionality.
     pub fn get_geometry_for_way(&self, id: i64) -> PyResult<String> {
-        let id = osm::WayID(id);
-        let width = self
-            .inner
-            .roads
-            .values()
-            .find(|r| r.from_osm_way(id))
-            .map(|r| r.total_width())
-            .unwrap();
-        let polyline = PolyLine::unchecked_new(self.ways[&id].pts.clone());
-        let mut polygon = polyline.make_polygons(1.5 * width);
-        let num_chevrons = std::cmp::max(
-            1,
-            (polyline.length() / Distance::meters(50.0)).floor() as i64,
-        );
-        let chevrons = (1..=num_chevrons)
-            .map(|i| {
-                let (top_pt, angle) = polyline
-                    .dist_along((i as f64 / (num_chevrons as f64 + 1.0)) * polyline.length())
-                    .unwrap();
-                PolyLine::must_new(vec![
-                    top_pt.project_away(width / 2.0, angle.rotate_degs(135.0)),
-                    top_pt,
-                    top_pt.project_away(width / 2.0, angle.rotate_degs(-135.0)),
-                ])
-                .make_polygons(width * 0.2)
-            })
-            .collect::<Vec<Polygon>>();
-        chevrons
-            .iter()
-            .for_each(|c| polygon = polygon.difference(c).unwrap()[0].clone());
-        serde_json::to_string_pretty(&polygon.to_geojson(Some(&self.inner.gps_bounds)))
+        let polygon = streets_reader::ways::geometry_for_way(&self.inner, osm::WayID(id))
+            .map_err(err_to_py_value)?;
+        serde_json::to_string(&polygon.to_geojson(Some(&self.inner.gps_bounds)))
             .map_err(err_to_py_runtime)
     }
 
@@ -288,22 +886,63 @@ impl PyStreetNetwork {
     ///
     /// Returns an XML string for the way, or an error if the way does not exist.
     pub fn way_to_xml(&self, id: i64) -> PyResult<String> {
-        let Some(ref way) = self.ways.get(&osm::WayID(id)) else {
+        let id = osm::WayID(id);
+        let Some(way) = self.inner.osm_index().and_then(|index| index.way(id)) else {
             return Err(err_to_py_value(format!("unknown way {}", id)));
         };
-        let mut out = format!(r#"<way id="{}""#, id);
-        if let Some(version) = way.version {
-            out.push_str(&format!(r#" version="{}""#, version));
-        }
-        out.push_str(">\n");
-        for node in &way.nodes {
-            out.push_str(&format!(r#"  <nd ref="{}"/>\n"#, node.0));
+        Ok(streets_reader::ways::way_to_xml(id, way))
+    }
+
+    /// Retrieves the OSM tags of the nodes an intersection was built from.
+    ///
+    /// - `intersection`: The ID of the intersection.
+    ///
+    /// Returns a JSON array of `{ node, tags }`.
+    pub fn get_osm_tags_for_intersection(&self, intersection: usize) -> PyResult<String> {
+        let i = IntersectionID(intersection);
+        if !self.inner.intersections.contains_key(&i) {
+            return Err(err_to_py_value(format!(
+                "unknown intersection {intersection}"
+            )));
         }
-        for (k, v) in way.tags.inner() {
-            out.push_str(&format!(r#"  <tag k="{}" v="{}"/>\n"#, k, v));
+        self.inner
+            .osm_tags_for_intersection_json(i)
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Retrieves every OSM relation with a way as a member, like turn restrictions and routes.
+    ///
+    /// - `id`: The OSM ID of the way.
+    ///
+    /// Returns a JSON array of `{ id, tags, members }`.
+    pub fn get_relations_for_way(&self, id: i64) -> PyResult<String> {
+        self.inner
+            .relations_for_way_json(osm::WayID(id))
+            .map_err(err_to_py_runtime)
+    }
+
+    /// Lists the roads of an intersection in clockwise order.
+    ///
+    /// - `intersection`: The ID of the intersection.
+    ///
+    /// Returns a JSON array of `{ road, bearing, gap_to_next }` in degrees. Bearings start from
+    /// north.
+    pub fn get_clockwise_roads(&self, intersection: usize) -> PyResult<String> {
+        let i = IntersectionID(intersection);
+        if !self.inner.intersections.contains_key(&i) {
+            return Err(err_to_py_value(format!(
+                "unknown intersection {intersection}"
+            )));
         }
-        out.push_str("</way>");
-        Ok(out)
+        serde_json::to_string(&self.inner.clockwise_road_angles(i)).map_err(err_to_py_runtime)
+    }
+
+    /// Finds everywhere the number of lanes in one direction changes between two roads.
+    ///
+    /// Returns a JSON array of `{ intersection, from, to, dropped_lanes, added_lanes }`. Lanes are
+    /// indices into the road's lanes, and dropped lanes include the direction they merge.
+    pub fn find_lane_transitions(&self) -> PyResult<String> {
+        serde_json::to_string(&self.inner.find_lane_transitions()).map_err(err_to_py_runtime)
     }
 
     /// Finds and returns a block for a specified road side as a polygon.
@@ -335,10 +974,19 @@ impl PyStreetNetwork {
     ///
     /// - `sidewalks`: Boolean indicating if sidewalks should be included in the blocks.
     ///
-    /// Returns a GeoJSON string representing all blocks.    
-    pub fn find_all_blocks(&self, sidewalks: bool) -> PyResult<String> {
+    /// Returns a GeoJSON string representing all blocks. Each has the `boundary` road sides,
+    /// `perimeter` in meters, `area` in square meters, and a `neighborhood` number shared with
+    /// blocks only separated by local streets.
+    /// `progress` is an optional callable, like in the constructor.
+    #[args(progress = "None")]
+    pub fn find_all_blocks(
+        &self,
+        py: Python,
+        sidewalks: bool,
+        progress: Option<PyObject>,
+    ) -> PyResult<String> {
         self.inner
-            .find_all_blocks(sidewalks)
+            .find_all_blocks_with_progress(sidewalks, &mut py_progress(py, progress))
             .map_err(err_to_py_runtime)
     }
 
@@ -351,33 +999,10 @@ impl PyStreetNetwork {
     ///
     /// Updates the roads and intersections connected to this way based on the new tags.
     pub fn overwrite_osm_tags_for_way(&mut self, id: i64, tags: &str) -> PyResult<()> {
-        let id = osm::WayID(id);
         let tags: Tags = serde_json::from_str(tags)
             .map_err(|e| err_to_py_value(format!("Failed to parse tags: {}", e)))?;
-
-        let mut intersections = BTreeSet::new();
-        for road in self.inner.roads.values_mut() {
-            if road.from_osm_way(id) {
-                road.lane_specs_ltr = osm2streets::get_lane_specs_ltr(&tags, &self.inner.config);
-                intersections.extend(road.endpoints());
-
-                if let Ok(p) = Placement::parse(&tags) {
-                    road.reference_line_placement = p;
-                }
-
-                road.update_center_line(self.inner.config.driving_side);
-            }
-        }
-        for i in intersections {
-            self.inner.update_i(i);
-        }
-
-        if let Some(way) = self.ways.get_mut(&id) {
-            way.tags = tags;
-        } else {
-            return Err(err_to_py_value(format!("Unknown way ID {}", id)));
-        }
-        Ok(())
+        streets_reader::ways::overwrite_osm_tags_for_way(&mut self.inner, osm::WayID(id), tags)
+            .map_err(err_to_py_value)
     }
 
     /// Collapses a short road by merging it into its neighboring road segments.
@@ -405,6 +1030,19 @@ impl PyStreetNetwork {
         Ok(())
     }
 
+    /// Moves an intersection to a new position, dragging the ends of connected roads along.
+    ///
+    /// - `intersection`: ID of the intersection to move.
+    /// - `lon` and `lat`: The new WGS84 position.
+    ///
+    /// Raises an error, leaving everything unchanged, if a road would become degenerate.
+    pub fn move_intersection(&mut self, intersection: usize, lon: f64, lat: f64) -> PyResult<()> {
+        let pt = LonLat::new(lon, lat).to_pt(&self.inner.gps_bounds);
+        self.inner
+            .move_intersection(IntersectionID(intersection), pt)
+            .map_err(err_to_py_runtime)
+    }
+
     /// Zips a sidepath (e.g., a bike lane or sidewalk) alongside a main road.
     ///
     /// - `road`: ID of the road with the sidepath to be zipped.
@@ -423,6 +1061,198 @@ pub struct PyDebugStreets {
     inner: DebugStreets,
 }
 
+impl PyStreetNetwork {
+    /// Imports a network. Shared by the constructor and `from_source`.
+    fn import(
+        py: Python,
+        osm_input: &[u8],
+        clip_pts_geojson: &str,
+        input: &str,
+        progress: Option<PyObject>,
+    ) -> PyResult<Self> {
+        SETUP_LOGGER.call_once(|| env_logger::init());
+
+        let input: ImportOptions = serde_json::from_str(input)
+            .map_err(|e| err_to_py_value(format!("Failed to parse input: {}", e)))?;
+
+        // Parse clip points if provided
+        let clip_pts = if clip_pts_geojson.is_empty() {
+            None
+        } else {
+            let mut list = LonLat::parse_geojson_polygons(clip_pts_geojson.to_string())
+                .map_err(|e| err_to_py_value(format!("{}", e)))?;
+            if list.len() != 1 {
+                return Err(err_to_py_value(
+                    "clip_pts_geojson must contain exactly one polygon",
+                ));
+            }
+            Some(list.pop().unwrap().0)
+        };
+
+        let mut cfg = MapConfig::default();
+        cfg.inferred_sidewalks = input.inferred_sidewalks;
+        cfg.inferred_kerbs = input.inferred_kerbs;
+        cfg.date_time = input.date_time;
+        cfg.reference_line_simplification = Distance::meters(input.reference_line_simplification);
+        cfg.degenerate_smoothing_max_deviation =
+            Distance::meters(input.degenerate_smoothing_max_deviation);
+        cfg.short_road_threshold = Distance::meters(input.short_road_threshold);
+        cfg.do_not_collapse = input.do_not_collapse.into_iter().map(osm::WayID).collect();
+        cfg.highway_filter.include = input.include_highways.into_iter().collect();
+        cfg.highway_filter.exclude = input.exclude_highways.into_iter().collect();
+        cfg.override_driving_side = match input.override_driving_side.as_str() {
+            "" => None,
+            "Left" => Some(DrivingSide::Left),
+            "Right" => Some(DrivingSide::Right),
+            x => {
+                return Err(err_to_py_value(format!("Unknown driving side: {x}")));
+            }
+        };
+        cfg.dead_end_cap = match input.dead_end_cap.as_str() {
+            "" | "square" => DeadEndCap::Square,
+            "rounded" => DeadEndCap::Rounded,
+            "turning_circles" => DeadEndCap::TurningCircles,
+            x => {
+                return Err(err_to_py_value(format!("Unknown dead_end_cap: {x}")));
+            }
+        };
+        cfg.intersection_geometry = parse_intersection_geometry(&input.intersection_geometry)?;
+        for (node, value) in &input.intersection_geometry_overrides {
+            let Ok(node) = node.parse::<i64>() else {
+                return Err(err_to_py_value(format!("Unknown OSM node ID: {node}")));
+            };
+            cfg.intersection_geometry_overrides
+                .insert(osm::NodeID(node), parse_intersection_geometry(value)?);
+        }
+        cfg.indoor_paths = match input.indoor_paths.as_str() {
+            "" => IndoorPaths::Include,
+            "exclude" => IndoorPaths::Exclude,
+            "separate" => IndoorPaths::Separate,
+            x => return Err(err_to_py_value(format!("Unknown indoor_paths: {x}"))),
+        };
+        cfg.block_barriers = input.block_barriers;
+        cfg.keep_original = input.keep_original;
+        cfg.preserve_road_names = input.preserve_road_names;
+        let frontage_roads = match input.frontage_roads.as_str() {
+            "" => None,
+            "detect" => Some(Transformation::DetectFrontageRoads),
+            "merge" => Some(Transformation::MergeFrontageRoads),
+            x => {
+                return Err(err_to_py_value(format!("Unknown frontage_roads: {x}")));
+            }
+        };
+
+        let mut progress = py_progress(py, progress);
+
+        let mut timer = Timer::throwaway();
+        let (mut street_network, mut doc) = streets_reader::osm_to_street_network_with_progress(
+            osm_input,
+            clip_pts,
+            cfg,
+            &mut timer,
+            &mut progress,
+        )
+        .map_err(err_to_py_runtime)?;
+
+        let skipped = std::mem::take(&mut doc.skipped);
+        let index = doc.into_osm_index(&street_network, input.drop_osm_document);
+        street_network.set_osm_index(index);
+
+        let mut transformations = Transformation::standard_for_clipped_areas();
+        if input.dual_carriageway_experiment {
+            transformations.retain(|t| !matches!(t, Transformation::CollapseShortRoads));
+            transformations.push(Transformation::MergeDualCarriageways);
+        }
+        if input.sidepath_zipping_experiment {
+            transformations.push(Transformation::ZipSidepaths);
+            transformations.push(Transformation::CollapseDegenerateIntersections);
+        }
+        if let Some(transformation) = frontage_roads {
+            transformations.push(transformation);
+            transformations.push(Transformation::CollapseDegenerateIntersections);
+        }
+        if input.remove_unbuilt_roads {
+            transformations.insert(0, Transformation::RemoveUnbuiltRoads);
+        }
+        if input.smooth_road_geometry {
+            transformations.push(Transformation::SmoothRoadGeometry);
+        }
+        if input.blend_placement_offsets {
+            transformations.push(Transformation::BlendPlacementOffsets);
+        }
+        if input.mark_lane_transitions {
+            transformations.push(Transformation::MarkLaneTransitions);
+        }
+        if input.link_separate_ways {
+            transformations.push(Transformation::LinkSeparateWays);
+        }
+
+        if input.debug_each_step {
+            street_network
+                .apply_transformations_stepwise_debugging_with_progress(
+                    transformations,
+                    &mut timer,
+                    &mut progress,
+                )
+                .map_err(err_to_py_runtime)?;
+        } else {
+            street_network
+                .apply_transformations_with_progress(transformations, &mut timer, &mut progress)
+                .map_err(err_to_py_runtime)?;
+        }
+
+        Ok(Self {
+            inner: street_network,
+            skipped,
+        })
+    }
+
+    /// Wraps part of this network. Its OSM index only covers that part.
+    fn with_network(&self, inner: StreetNetwork) -> Self {
+        Self {
+            inner,
+            skipped: Default::default(),
+        }
+    }
+
+    fn osm_ways(&self) -> impl Iterator<Item = (osm::WayID, &streets_reader::osm_reader::Way)> {
+        self.inner
+            .osm_index()
+            .into_iter()
+            .flat_map(|index| index.ways())
+    }
+
+    fn parse_filter(
+        &self,
+        bbox: Option<Vec<f64>>,
+        polygon_geojson: Option<String>,
+    ) -> PyResult<Filter> {
+        if let Some(geojson) = polygon_geojson {
+            let mut list = LonLat::parse_geojson_polygons(geojson).map_err(err_to_py_runtime)?;
+            if list.len() != 1 {
+                return Err(err_to_py_runtime(
+                    "polygon_geojson doesn't contain exactly one polygon",
+                ));
+            }
+            return Filter::within_gps(&self.inner.gps_bounds, list.pop().unwrap().0)
+                .map_err(err_to_py_runtime);
+        }
+        if let Some(bbox) = bbox {
+            if bbox.len() != 4 {
+                return Err(err_to_py_runtime(
+                    "bbox needs [min_lon, min_lat, max_lon, max_lat]",
+                ));
+            }
+            return Ok(Filter::bbox_from_gps(
+                &self.inner.gps_bounds,
+                LonLat::new(bbox[0], bbox[1]),
+                LonLat::new(bbox[2], bbox[3]),
+            ));
+        }
+        Ok(Filter::All)
+    }
+}
+
 #[pymethods]
 impl PyDebugStreets {
     pub fn get_label(&self) -> String {
@@ -432,6 +1262,12 @@ impl PyDebugStreets {
     pub fn to_debug_geojson(&self) -> Option<String> {
         self.inner.to_debug_geojson()
     }
+
+    /// Returns GeoJSON showing the roads and intersections added, modified, or removed since the
+    /// previous step, or `None` if nothing changed.
+    pub fn to_diff_geojson(&self) -> Option<String> {
+        self.inner.to_diff_geojson()
+    }
 }
 
 // Module setup
@@ -442,14 +1278,98 @@ fn osm2streets_python(_py: Python, m: &PyModule) -> PyResult<()> {
     Ok(())
 }
 
+fn parse_intersection_geometry(value: &str) -> PyResult<IntersectionGeometry> {
+    match value {
+        "" | "corner_trim" => Ok(IntersectionGeometry::CornerTrim),
+        "convex_hull" => Ok(IntersectionGeometry::ConvexHull),
+        "mapped_area" => Ok(IntersectionGeometry::MappedArea),
+        "buffer_union_fallback" => Ok(IntersectionGeometry::BufferUnionFallback),
+        x => Err(err_to_py_value(format!(
+            "Unknown intersection_geometry: {x}"
+        ))),
+    }
+}
+
 /// Converts any error implementing `std::fmt::Display` into a `PyRuntimeError`.
 /// Used for unexpected runtime failures.
 fn err_to_py_runtime<E: std::fmt::Display>(err: E) -> PyErr {
     pyo3::exceptions::PyRuntimeError::new_err(err.to_string())
 }
 
+/// Parses a routing profile: "pedestrian", "wheelchair", "bicycle", "motor", or "rail".
+fn parse_profile(value: &str) -> PyResult<Profile> {
+    match value {
+        "pedestrian" => Ok(Profile::Pedestrian),
+        "wheelchair" => Ok(Profile::Wheelchair),
+        "bicycle" => Ok(Profile::Bicycle),
+        "motor" => Ok(Profile::Motor),
+        "rail" => Ok(Profile::Rail),
+        x => Err(err_to_py_value(format!("Unknown profile: {x}"))),
+    }
+}
+
+/// Reads OSM input from a path, URL, or binary file-like object. See
+/// `PyStreetNetwork::from_source`.
+fn read_source(py: Python, source: PyObject) -> PyResult<Vec<u8>> {
+    if let Ok(url) = source.extract::<&str>(py) {
+        if url.starts_with("http://") || url.starts_with("https://") {
+            let response = py
+                .import("urllib.request")?
+                .call_method1("urlopen", (url,))?
+                .to_object(py);
+            return read_chunks(py, &response);
+        }
+    }
+    if let Ok(path) = source.extract::<PathBuf>(py) {
+        return std::fs::read(&path)
+            .map_err(|err| err_to_py_value(format!("Can't read {}: {err}", path.display())));
+    }
+    if source.as_ref(py).hasattr("read")? {
+        return read_chunks(py, &source);
+    }
+    Err(err_to_py_value(
+        "source must be a path, a URL, or a file-like object",
+    ))
+}
+
+/// Calls `read` on a file-like object until it's exhausted.
+fn read_chunks(py: Python, file: &PyObject) -> PyResult<Vec<u8>> {
+    const CHUNK_SIZE: usize = 1 << 20;
+    let mut result = Vec::new();
+    loop {
+        let chunk = file.call_method1(py, "read", (CHUNK_SIZE,))?;
+        let chunk: &[u8] = chunk
+            .extract(py)
+            .map_err(|_| err_to_py_value("file-like objects must be opened in binary mode"))?;
+        if chunk.is_empty() {
+            return Ok(result);
+        }
+        result.extend_from_slice(chunk);
+    }
+}
+
 /// Converts any error implementing `std::fmt::Display` into a `PyValueError`.
 /// Used for invalid inputs or incorrect arguments passed by the user.
 fn err_to_py_value<E: std::fmt::Display>(err: E) -> PyErr {
     pyo3::exceptions::PyValueError::new_err(err.to_string())
 }
+
+/// Calls a Python callable with `(stage, fraction)`. If it returns `False`, cancels the operation.
+fn py_progress(py: Python<'_>, callback: Option<PyObject>) -> Progress<'_> {
+    let Some(f) = callback else {
+        return Progress::none();
+    };
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    Progress::new(
+        move |stage, fraction| match f.call1(py, (stage, fraction)) {
+            Ok(result) => {
+                if let Ok(false) = result.extract::<bool>(py) {
+                    cancel.cancel();
+                }
+            }
+            Err(err) => err.print(py),
+        },
+    )
+    .with_cancellation(token)
+}
@@ -0,0 +1,189 @@
+use abstutil::Tags;
+use anyhow::Result;
+use geojson::Feature;
+use geom::{Distance, Pt2D};
+use serde::{Deserialize, Serialize};
+
+use crate::render::serialize_features;
+use crate::{Filter, LaneType, StreetNetwork};
+
+/// Ramps steeper than this (about 1:12) are too steep for many wheelchair users
+const MAX_INCLINE_PERCENT: f64 = 8.0;
+
+/// The height of a kerb where a path meets a road, from a `kerb` tag on a node. See
+/// <https://wiki.openstreetmap.org/wiki/Key:kerb>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KerbKind {
+    Raised,
+    Rolled,
+    Lowered,
+    Flush,
+    /// No kerb at all
+    None,
+}
+
+impl KerbKind {
+    /// Parses `kerb` or `barrier=kerb` on a node. A kerb without a height is assumed to be raised.
+    pub fn parse(tags: &Tags) -> Option<Self> {
+        match tags.get("kerb").map(|x| x.as_str()) {
+            Some("raised") | Some("yes") => Some(Self::Raised),
+            Some("rolled") => Some(Self::Rolled),
+            Some("lowered") => Some(Self::Lowered),
+            Some("flush") => Some(Self::Flush),
+            Some("no") => Some(Self::None),
+            Some(_) => None,
+            None => tags.is("barrier", "kerb").then_some(Self::Raised),
+        }
+    }
+
+    /// Can a wheelchair cross this kerb?
+    pub fn is_accessible(self) -> bool {
+        matches!(self, Self::Lowered | Self::Flush | Self::None)
+    }
+}
+
+impl StreetNetwork {
+    /// Marks places that are hard to use in a wheelchair: kerbs without a curb cut, steps without
+    /// a ramp, sidewalks narrower than `min_sidewalk_width`, and paths that are rough or steep.
+    /// Each feature has a `barrier` property and a `description`.
+    pub fn to_accessibility_geojson(
+        &self,
+        filter: &Filter,
+        min_sidewalk_width: Distance,
+    ) -> Result<String> {
+        let mut features = Vec::new();
+
+        for (pt, kind) in &self.kerbs {
+            if kind.is_accessible() || !filter.contains_pt(self, *pt) {
+                continue;
+            }
+            let mut f = Feature::from(pt.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("barrier", "kerb");
+            f.set_property("description", format!("{kind:?} kerb without a curb cut"));
+            features.push(f);
+        }
+
+        for road in filter.roads(self) {
+            if road.is_steps() && !road.is_wheelchair_accessible() {
+                let mut f = Feature::from(road.center_line.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("barrier", "steps");
+                f.set_property("description", "Steps without a wheelchair ramp");
+                f.set_property("road", road.id.0);
+                features.push(f);
+            }
+
+            for (idx, (lane, center)) in road
+                .lane_specs_ltr
+                .iter()
+                .zip(road.get_lane_center_lines())
+                .enumerate()
+            {
+                if !matches!(
+                    lane.lt,
+                    LaneType::Sidewalk | LaneType::Footway | LaneType::SharedUse
+                ) || lane.is_virtual
+                {
+                    continue;
+                }
+                let mut problems = Vec::new();
+                if lane.width < min_sidewalk_width {
+                    problems.push(("narrow", format!("Only {} wide", lane.width)));
+                }
+                let attributes = road.path_attributes.get(&idx);
+                let surface = attributes
+                    .and_then(|x| x.surface.clone())
+                    .or_else(|| road.lane_surfaces.get(&idx).map(|x| x.to_string()))
+                    .or_else(|| {
+                        road.is_footway()
+                            .then(|| road.surface.as_ref().map(|x| x.to_string()))
+                            .flatten()
+                    });
+                let smoothness = attributes.and_then(|x| x.smoothness.clone());
+                if is_rough(surface.as_deref(), smoothness.as_deref()) {
+                    problems.push((
+                        "rough_surface",
+                        format!(
+                            "Surface {}, smoothness {}",
+                            surface.as_deref().unwrap_or("unknown"),
+                            smoothness.as_deref().unwrap_or("unknown")
+                        ),
+                    ));
+                }
+                if let Some(percent) = attributes
+                    .and_then(|x| x.incline.as_ref())
+                    .and_then(|x| crate::units::parse_incline(x))
+                {
+                    if percent.abs() > MAX_INCLINE_PERCENT {
+                        problems.push(("steep", format!("{percent}% incline")));
+                    }
+                }
+
+                for (barrier, description) in problems {
+                    let mut f = Feature::from(center.to_geojson(Some(&self.gps_bounds)));
+                    f.set_property("barrier", barrier);
+                    f.set_property("description", description);
+                    f.set_property("road", road.id.0);
+                    f.set_property("index", idx);
+                    features.push(f);
+                }
+            }
+        }
+
+        serialize_features(features)
+    }
+}
+
+impl Filter {
+    fn contains_pt(&self, streets: &StreetNetwork, pt: Pt2D) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::Within(polygon) => polygon.contains_pt(pt),
+            Filter::BBox(bounds) => bounds.contains(pt),
+            Filter::Filtered(..) => self
+                .roads(streets)
+                .any(|road| road.center_line.project_pt(pt).dist_to(pt) < road.total_width()),
+        }
+    }
+}
+
+/// Surfaces and smoothness values that're hard going in a wheelchair
+fn is_rough(surface: Option<&str>, smoothness: Option<&str>) -> bool {
+    matches!(
+        smoothness,
+        Some("bad" | "very_bad" | "horrible" | "very_horrible" | "impassable")
+    ) || matches!(
+        surface,
+        Some(
+            "gravel"
+                | "fine_gravel"
+                | "pebblestone"
+                | "sand"
+                | "grass"
+                | "dirt"
+                | "earth"
+                | "ground"
+                | "mud"
+                | "unhewn_cobblestone"
+                | "cobblestone"
+                | "woodchips"
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_barriers() {
+        assert!(is_rough(Some("gravel"), None));
+        assert!(is_rough(Some("asphalt"), Some("bad")));
+        assert!(!is_rough(Some("paving_stones"), Some("good")));
+
+        let mut tags = Tags::empty();
+        tags.insert("barrier", "kerb");
+        assert_eq!(Some(KerbKind::Raised), KerbKind::parse(&tags));
+        tags.insert("kerb", "lowered");
+        assert!(KerbKind::parse(&tags).unwrap().is_accessible());
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{IntersectionID, RoadID, StreetNetwork};
+
+/// One change made by a transformation, explaining what happened to some roads and
+/// intersections. Answers questions like "why did this road disappear?"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// The name of the transformation, like "collapse short roads". Empty for changes made
+    /// outside of a transformation.
+    pub transformation: String,
+    /// The roads affected. They may not exist anymore.
+    pub roads: Vec<RoadID>,
+    /// The intersections affected. They may not exist anymore.
+    pub intersections: Vec<IntersectionID>,
+    pub message: String,
+}
+
+impl StreetNetwork {
+    /// Records a change in `audit_log`, attributed to the transformation currently running.
+    pub(crate) fn audit<I: Into<String>>(
+        &mut self,
+        roads: Vec<RoadID>,
+        intersections: Vec<IntersectionID>,
+        message: I,
+    ) {
+        self.audit_log.push(AuditEntry {
+            transformation: self.current_transformation.unwrap_or("").to_string(),
+            roads,
+            intersections,
+            message: message.into(),
+        });
+    }
+
+    /// Every entry in the audit log mentioning a road, in the order the changes happened
+    pub fn audit_log_for_road(&self, r: RoadID) -> Vec<&AuditEntry> {
+        self.audit_log
+            .iter()
+            .filter(|entry| entry.roads.contains(&r))
+            .collect()
+    }
+
+    /// Every entry in the audit log mentioning an intersection, in the order the changes happened
+    pub fn audit_log_for_intersection(&self, i: IntersectionID) -> Vec<&AuditEntry> {
+        self.audit_log
+            .iter()
+            .filter(|entry| entry.intersections.contains(&i))
+            .collect()
+    }
+
+    /// Returns the audit log as a JSON array. If a road or intersection is specified, only
+    /// includes entries mentioning it.
+    pub fn audit_log_json(
+        &self,
+        road: Option<RoadID>,
+        intersection: Option<IntersectionID>,
+    ) -> Result<String> {
+        let list: Vec<&AuditEntry> = self
+            .audit_log
+            .iter()
+            .filter(|entry| road.map(|r| entry.roads.contains(&r)).unwrap_or(true))
+            .filter(|entry| {
+                intersection
+                    .map(|i| entry.intersections.contains(&i))
+                    .unwrap_or(true)
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Timer;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road, TestIntersection};
+    use crate::{IntersectionKind, Transformation};
+
+    #[test]
+    fn test_transformations_write_audit_log() {
+        let mut streets = StreetNetwork::blank();
+        let intersections: Vec<TestIntersection> = [
+            (0.0, 0.0, IntersectionKind::MapEdge),
+            (100.0, 0.0, IntersectionKind::Connection),
+            (200.0, 0.0, IntersectionKind::Intersection),
+            (300.0, 0.0, IntersectionKind::MapEdge),
+            (0.0, 500.0, IntersectionKind::MapEdge),
+            (100.0, 500.0, IntersectionKind::MapEdge),
+        ]
+        .into_iter()
+        .map(|(x, y, kind)| add_intersection(&mut streets, x, y, kind))
+        .collect();
+        // r1 and r2 meet at a degenerate intersection, r3 is disused, and r4 is off by itself
+        let residential = &["highway=residential"];
+        let r1 = add_road(
+            &mut streets,
+            intersections[0],
+            intersections[1],
+            residential,
+        );
+        let r2 = add_road(
+            &mut streets,
+            intersections[1],
+            intersections[2],
+            residential,
+        );
+        let r3 = add_road(
+            &mut streets,
+            intersections[2],
+            intersections[3],
+            &["highway=residential", "disused=yes"],
+        );
+        let r4 = add_road(
+            &mut streets,
+            intersections[4],
+            intersections[5],
+            residential,
+        );
+
+        // Changes outside of a transformation aren't attributed to one
+        streets.audit(vec![r1], Vec::new(), "Checked by hand");
+
+        streets.apply_transformations(
+            vec![
+                Transformation::RemoveUnbuiltRoads,
+                Transformation::RemoveDisconnectedRoads,
+                Transformation::CollapseDegenerateIntersections,
+            ],
+            &mut Timer::throwaway(),
+        );
+
+        let entries = streets.audit_log_for_road(r3);
+        assert_eq!(1, entries.len());
+        assert_eq!("remove unbuilt roads", entries[0].transformation);
+        assert!(!streets.roads.contains_key(&r3));
+
+        let entries = streets.audit_log_for_road(r4);
+        assert_eq!(1, entries.len());
+        assert_eq!("remove disconnected roads", entries[0].transformation);
+
+        let entries = streets.audit_log_for_intersection(intersections[1].0);
+        assert_eq!(1, entries.len());
+        assert_eq!(
+            "collapse degenerate intersections",
+            entries[0].transformation
+        );
+        assert!(entries[0].roads.contains(&r1));
+        assert!(entries[0].roads.contains(&r2));
+
+        let entries = streets.audit_log_for_road(r1);
+        assert_eq!(2, entries.len());
+        assert_eq!("", entries[0].transformation);
+        assert_eq!("Checked by hand", entries[0].message);
+    }
+
+    #[test]
+    fn test_audit_log_json() {
+        let mut streets = StreetNetwork::blank();
+        let (r1, r2) = (RoadID(1), RoadID(2));
+        let i = IntersectionID(3);
+        streets.audit(vec![r1], vec![i], "first");
+        streets.audit(vec![r1, r2], Vec::new(), "second");
+
+        let messages = |road, intersection| -> Vec<String> {
+            let list: Vec<AuditEntry> =
+                serde_json::from_str(&streets.audit_log_json(road, intersection).unwrap()).unwrap();
+            list.into_iter().map(|entry| entry.message).collect()
+        };
+        assert_eq!(vec!["first", "second"], messages(None, None));
+        assert_eq!(vec!["second"], messages(Some(r2), None));
+        assert_eq!(vec!["first"], messages(Some(r1), Some(i)));
+        assert!(messages(Some(r2), Some(i)).is_empty());
+    }
+}
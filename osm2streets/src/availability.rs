@@ -0,0 +1,133 @@
+use abstutil::Tags;
+use serde::{Deserialize, Serialize};
+
+/// Access keys whose `:conditional` variants are recorded. See
+/// <https://wiki.openstreetmap.org/wiki/Conditional_restrictions>.
+const CONDITIONAL_KEYS: [&str; 7] = [
+    "access",
+    "vehicle",
+    "motor_vehicle",
+    "motorcar",
+    "hgv",
+    "bicycle",
+    "foot",
+];
+
+/// When a road can be used. Most roads are always available, but some are only open part of the
+/// year, cross water, or have access restrictions depending on time or conditions. osm2streets
+/// doesn't evaluate these; consumers like routing can decide when to exclude these roads, and
+/// renderers can show them differently.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Availability {
+    /// The `seasonal` tag, like `yes` or `winter`, if the road is only usable part of the year.
+    /// See <https://wiki.openstreetmap.org/wiki/Key:seasonal>.
+    pub seasonal: Option<String>,
+    /// The road crosses water without a bridge. See <https://wiki.openstreetmap.org/wiki/Key:ford>.
+    pub ford: bool,
+    pub conditional_access: Vec<ConditionalAccess>,
+}
+
+/// One restriction from a tag like `access:conditional=no @ (Nov-Mar)`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConditionalAccess {
+    /// The key without `:conditional`, like `access` or `hgv`
+    pub key: String,
+    /// Like `no` or `destination`
+    pub value: String,
+    /// Like `Nov-Mar` or `snow`, without parentheses
+    pub condition: String,
+}
+
+impl Availability {
+    pub fn parse(tags: &Tags) -> Self {
+        let mut conditional_access = Vec::new();
+        for key in CONDITIONAL_KEYS {
+            if let Some(value) = tags.get(&format!("{key}:conditional")) {
+                conditional_access.extend(parse_conditional(key, value));
+            }
+        }
+
+        Self {
+            seasonal: tags.get("seasonal").filter(|x| x.as_str() != "no").cloned(),
+            ford: tags.contains_key("ford") && !tags.is("ford", "no"),
+            conditional_access,
+        }
+    }
+
+    /// True if nothing limits when the road can be used
+    pub fn is_unconditional(&self) -> bool {
+        self.seasonal.is_none() && !self.ford && self.conditional_access.is_empty()
+    }
+
+    /// Summarizes each limit, like `seasonal=winter` or `access=no @ (Nov-Mar)`
+    pub fn describe(&self) -> Vec<String> {
+        let mut list = Vec::new();
+        if let Some(ref seasonal) = self.seasonal {
+            list.push(format!("seasonal={seasonal}"));
+        }
+        if self.ford {
+            list.push("ford".to_string());
+        }
+        for x in &self.conditional_access {
+            list.push(format!("{}={} @ ({})", x.key, x.value, x.condition));
+        }
+        list
+    }
+}
+
+/// Parses a value like `no @ (Nov-Mar); destination @ (snow)`. Semicolons inside the parentheses
+/// don't separate restrictions.
+fn parse_conditional(key: &str, input: &str) -> Vec<ConditionalAccess> {
+    let mut pieces = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth == 0 => {
+                pieces.push(&input[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    pieces.push(&input[start..]);
+
+    let mut result = Vec::new();
+    for piece in pieces {
+        let Some((value, condition)) = piece.split_once('@') else {
+            warn!("Weird {key}:conditional={input}");
+            continue;
+        };
+        let condition = condition.trim();
+        let condition = condition
+            .strip_prefix('(')
+            .and_then(|x| x.strip_suffix(')'))
+            .unwrap_or(condition);
+        result.push(ConditionalAccess {
+            key: key.to_string(),
+            value: value.trim().to_string(),
+            condition: condition.trim().to_string(),
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_conditional() {
+        let parsed = parse_conditional("access", "no @ (Nov-Mar; Sa); destination @ snow");
+        assert_eq!(
+            vec![("no", "Nov-Mar; Sa"), ("destination", "snow")],
+            parsed
+                .iter()
+                .map(|x| (x.value.as_str(), x.condition.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert!(parse_conditional("access", "no").is_empty());
+    }
+}
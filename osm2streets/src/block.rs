@@ -1,13 +1,13 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use abstutil::wraparound_get;
 use anyhow::Result;
 use geojson::Feature;
-use geom::{Polygon, Ring};
+use geom::{Distance, Polygon, Ring};
 
 use crate::{
-    Direction, IntersectionID, IntersectionKind, LaneType, RoadID, RoadSideID, SideOfRoad,
-    StreetNetwork,
+    Direction, IntersectionID, IntersectionKind, LaneType, Progress, RoadID, RoadSideID,
+    SideOfRoad, StreetNetwork,
 };
 
 /// A "tight" cycle of roads and intersections, with a polygon capturing the negative space inside.
@@ -16,9 +16,10 @@ pub struct Block {
     /// First != last, they're not repeated
     pub boundary: Vec<RoadSideID>,
     pub polygon: Polygon,
-    /// Not counting the boundary (described by steps)
-    pub member_roads: HashSet<RoadID>,
-    pub member_intersections: HashSet<IntersectionID>,
+    /// Not counting the boundary (described by steps). Sorted, so anything rendered from them has
+    /// a stable order.
+    pub member_roads: BTreeSet<RoadID>,
+    pub member_intersections: BTreeSet<IntersectionID>,
 }
 
 #[derive(Debug)]
@@ -50,8 +51,8 @@ impl StreetNetwork {
         let (boundary, start_intersection) = walk_around(self, start, sidewalks)?;
         let polygon = trace_polygon(self, &boundary, start_intersection)?;
 
-        let mut member_roads = HashSet::new();
-        let mut member_intersections = HashSet::new();
+        let mut member_roads = BTreeSet::new();
+        let mut member_intersections = BTreeSet::new();
         if sidewalks {
             // Look for roads inside the polygon geometrically
             // TODO Slow; could cache an rtree
@@ -84,10 +85,41 @@ impl StreetNetwork {
 
     // TODO Messy API again
     pub fn find_all_blocks(&self, sidewalks: bool) -> Result<String> {
+        self.find_all_blocks_with_progress(sidewalks, &mut Progress::none())
+    }
+
+    /// Like `find_all_blocks`, but reports progress and stops early if cancelled.
+    pub fn find_all_blocks_with_progress(
+        &self,
+        sidewalks: bool,
+        progress: &mut Progress,
+    ) -> Result<String> {
+        let blocks = self.find_blocks(sidewalks, progress)?;
+        let neighborhoods = self.group_blocks_into_neighborhoods(&blocks);
+
+        let mut features = Vec::new();
+        for (block, neighborhood) in blocks.into_iter().zip(neighborhoods) {
+            let mut f = Feature::from(block.polygon.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("type", "block");
+            f.set_property("kind", format!("{:?}", block.kind));
+            f.set_property("boundary", serde_json::to_value(&block.boundary)?);
+            f.set_property("perimeter", block.perimeter().inner_meters());
+            f.set_property("area", block.polygon.area());
+            f.set_property("neighborhood", neighborhood);
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+
+    /// Finds every block, returning them instead of GeoJSON. Each road side belongs to at most one
+    /// block.
+    pub fn find_blocks(&self, sidewalks: bool, progress: &mut Progress) -> Result<Vec<Block>> {
         let mut visited_roads: HashSet<RoadSideID> = HashSet::new();
         let mut blocks = Vec::new();
 
-        for r in self.roads.keys() {
+        for (idx, r) in self.roads.keys().enumerate() {
+            progress.check_cancelled("finding blocks")?;
+            progress.report_iter("finding blocks", idx, self.roads.len());
             if sidewalks && !self.roads[r].is_footway() {
                 continue;
             }
@@ -99,23 +131,158 @@ impl StreetNetwork {
                 }
                 if let Ok(block) = self.find_block(road_side, sidewalks) {
                     visited_roads.extend(block.boundary.clone());
-                    blocks.push(block);
+                    blocks.extend(self.split_block_at_barriers(block, sidewalks));
                 }
             }
         }
+        Ok(blocks)
+    }
 
-        let mut features = Vec::new();
-        for block in blocks {
-            let mut f = Feature::from(block.polygon.to_geojson(Some(&self.gps_bounds)));
-            f.set_property("type", "block");
-            f.set_property("kind", format!("{:?}", block.kind));
-            features.push(f);
+    /// Splits a block where waterways or railways in `block_barriers` cut all the way through it.
+    /// Each piece keeps the road sides and members inside it. Without any barriers, the block is
+    /// returned unchanged.
+    fn split_block_at_barriers(&self, block: Block, sidewalks: bool) -> Vec<Block> {
+        let mut pieces = vec![block.polygon.clone()];
+        for barrier in &self.block_barriers {
+            let barrier_bounds = barrier.get_bounds();
+            let cut = barrier.make_polygons(BARRIER_WIDTH);
+            let mut next = Vec::new();
+            for piece in pieces {
+                if !crate::render::overlaps(&piece.get_bounds(), &barrier_bounds) {
+                    next.push(piece);
+                    continue;
+                }
+                // Barriers that just clip a corner or end inside the block don't split it
+                match piece.difference(&cut) {
+                    Ok(split) if split.len() > 1 => next.extend(split),
+                    _ => next.push(piece),
+                }
+            }
+            pieces = next;
         }
-        serialize_features(features)
+        if pieces.len() == 1 {
+            return vec![block];
+        }
+
+        pieces
+            .into_iter()
+            .map(|polygon| {
+                // Check a point just past the edge of each road side
+                let boundary: Vec<RoadSideID> = block
+                    .boundary
+                    .iter()
+                    .filter(|side| {
+                        let road = &self.roads[&side.road];
+                        let (pt, angle) = road
+                            .center_line
+                            .must_dist_along(road.center_line.length() / 2.0);
+                        let rotate = if side.side == SideOfRoad::Left {
+                            -90.0
+                        } else {
+                            90.0
+                        };
+                        polygon.contains_pt(pt.project_away(
+                            road.half_width() + BARRIER_WIDTH / 2.0,
+                            angle.rotate_degs(rotate),
+                        ))
+                    })
+                    .cloned()
+                    .collect();
+                let member_roads: BTreeSet<RoadID> = block
+                    .member_roads
+                    .iter()
+                    .filter(|r| polygon.contains_pt(self.roads[r].center_line.middle()))
+                    .cloned()
+                    .collect();
+                let member_intersections: BTreeSet<IntersectionID> = block
+                    .member_intersections
+                    .iter()
+                    .filter(|i| polygon.contains_pt(self.intersections[i].polygon.center()))
+                    .cloned()
+                    .collect();
+                let kind = if sidewalks {
+                    classify_bundle(self, &polygon, &member_roads, &member_intersections)
+                } else {
+                    classify_block(self, &boundary)
+                };
+                Block {
+                    kind,
+                    boundary,
+                    polygon,
+                    member_roads,
+                    member_intersections,
+                }
+            })
+            .collect()
+    }
+
+    /// Groups blocks that're only separated by local streets or paths into neighborhoods,
+    /// returning the neighborhood of each block. Neighborhoods are numbered from 0.
+    pub fn group_blocks_into_neighborhoods(&self, blocks: &[Block]) -> Vec<usize> {
+        let mut block_per_side = HashMap::new();
+        for (idx, block) in blocks.iter().enumerate() {
+            for side in &block.boundary {
+                block_per_side.insert(*side, idx);
+            }
+        }
+
+        // Union-find, merging blocks on opposite sides of a local street
+        let mut parent: Vec<usize> = (0..blocks.len()).collect();
+        fn root(parent: &mut [usize], mut idx: usize) -> usize {
+            while parent[idx] != idx {
+                parent[idx] = parent[parent[idx]];
+                idx = parent[idx];
+            }
+            idx
+        }
+        for (idx, block) in blocks.iter().enumerate() {
+            for side in &block.boundary {
+                let road = &self.roads[&side.road];
+                if road.is_driveable() && !is_local_street(&road.highway_type) {
+                    continue;
+                }
+                let across = RoadSideID {
+                    road: side.road,
+                    side: side.side.opposite(),
+                };
+                if let Some(other) = block_per_side.get(&across) {
+                    let (a, b) = (root(&mut parent, idx), root(&mut parent, *other));
+                    parent[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let mut numbering = HashMap::new();
+        (0..blocks.len())
+            .map(|idx| {
+                let r = root(&mut parent, idx);
+                let next = numbering.len();
+                *numbering.entry(r).or_insert(next)
+            })
+            .collect()
     }
 }
 
+/// Barriers are thickened this much to cut blocks apart
+const BARRIER_WIDTH: Distance = Distance::const_meters(1.0);
+
+fn is_local_street(highway_type: &str) -> bool {
+    matches!(
+        highway_type,
+        "residential" | "living_street" | "unclassified" | "service"
+    )
+}
+
 impl Block {
+    /// The length of the polygon's outline
+    pub fn perimeter(&self) -> Distance {
+        self.polygon
+            .get_outer_ring()
+            .points()
+            .windows(2)
+            .fold(Distance::ZERO, |sum, pair| sum + pair[0].dist_to(pair[1]))
+    }
+
     pub fn render_polygon(&self, streets: &StreetNetwork) -> Result<String> {
         let mut features = Vec::new();
 
@@ -308,8 +475,8 @@ fn classify_block(streets: &StreetNetwork, boundary: &Vec<RoadSideID>) -> BlockK
 fn classify_bundle(
     streets: &StreetNetwork,
     polygon: &Polygon,
-    member_roads: &HashSet<RoadID>,
-    member_intersections: &HashSet<IntersectionID>,
+    member_roads: &BTreeSet<RoadID>,
+    member_intersections: &BTreeSet<IntersectionID>,
 ) -> BlockKind {
     // See how many "major" named roads are inside
     if true {
@@ -378,6 +545,155 @@ fn serialize_features(features: Vec<Feature>) -> Result<String> {
         features,
         foreign_members: None,
     });
-    let output = serde_json::to_string_pretty(&gj)?;
+    let output = serde_json::to_string(&gj)?;
     Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{LonLat, PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{osm, IntersectionControl, Road};
+
+    fn rectangle(width: f64, height: f64) -> Polygon {
+        Ring::must_new(vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(width, 0.0),
+            Pt2D::new(width, height),
+            Pt2D::new(0.0, height),
+            Pt2D::new(0.0, 0.0),
+        ])
+        .into_polygon()
+    }
+
+    fn block(boundary: Vec<RoadSideID>) -> Block {
+        Block {
+            kind: BlockKind::Unknown,
+            boundary,
+            polygon: rectangle(10.0, 20.0),
+            member_roads: BTreeSet::new(),
+            member_intersections: BTreeSet::new(),
+        }
+    }
+
+    // A grid of `n` by `n` intersections 100m apart, connected by roads of this type. Returns the
+    // roads.
+    fn grid(streets: &mut StreetNetwork, n: usize, highway: &str) -> Vec<RoadID> {
+        let mut ids = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let pt = Pt2D::new(100.0 * x as f64, 100.0 * y as f64);
+                let id = streets.insert_intersection(
+                    Vec::new(),
+                    pt,
+                    IntersectionKind::Intersection,
+                    IntersectionControl::Uncontrolled,
+                );
+                ids.push((id, pt));
+            }
+        }
+        let mut roads = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let here = ids[y * n + x];
+                let mut neighbors = Vec::new();
+                if x + 1 < n {
+                    neighbors.push(ids[y * n + x + 1]);
+                }
+                if y + 1 < n {
+                    neighbors.push(ids[(y + 1) * n + x]);
+                }
+                for there in neighbors {
+                    let mut tags = Tags::empty();
+                    tags.insert("highway", highway);
+                    let id = streets.next_road_id();
+                    let road = Road::new(
+                        id,
+                        vec![osm::WayID(id.0 as i64)],
+                        here.0,
+                        there.0,
+                        PolyLine::must_new(vec![here.1, there.1]),
+                        tags,
+                        &streets.config,
+                    );
+                    streets.insert_road(road);
+                    roads.push(id);
+                }
+            }
+        }
+        roads
+    }
+
+    #[test]
+    fn test_perimeter() {
+        assert_eq!(Distance::meters(60.0), block(Vec::new()).perimeter());
+    }
+
+    #[test]
+    fn test_group_blocks_into_neighborhoods() {
+        let mut streets = StreetNetwork::blank();
+        let local = grid(&mut streets, 2, "residential")[0];
+        let main = {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "primary");
+            let i1 = streets.insert_intersection(
+                Vec::new(),
+                Pt2D::new(0.0, 500.0),
+                IntersectionKind::MapEdge,
+                IntersectionControl::Uncontrolled,
+            );
+            let i2 = streets.insert_intersection(
+                Vec::new(),
+                Pt2D::new(100.0, 500.0),
+                IntersectionKind::MapEdge,
+                IntersectionControl::Uncontrolled,
+            );
+            let id = streets.next_road_id();
+            streets.insert_road(Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                i1,
+                i2,
+                PolyLine::must_new(vec![Pt2D::new(0.0, 500.0), Pt2D::new(100.0, 500.0)]),
+                tags,
+                &streets.config,
+            ));
+            id
+        };
+        let side = |road, side| RoadSideID { road, side };
+
+        // Blocks on both sides of a local street join, but not across a main road
+        let blocks = vec![
+            block(vec![side(local, SideOfRoad::Left)]),
+            block(vec![side(main, SideOfRoad::Left)]),
+            block(vec![side(local, SideOfRoad::Right)]),
+            block(vec![side(main, SideOfRoad::Right)]),
+        ];
+        assert_eq!(
+            vec![0, 1, 0, 2],
+            streets.group_blocks_into_neighborhoods(&blocks)
+        );
+    }
+
+    #[test]
+    fn test_find_all_blocks() {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        grid(&mut streets, 3, "residential");
+        let geojson: serde_json::Value =
+            serde_json::from_str(&streets.find_all_blocks(false).unwrap()).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        assert!(!features.is_empty());
+        for f in features {
+            let props = &f["properties"];
+            assert!(!props["boundary"].as_array().unwrap().is_empty());
+            assert!(props["perimeter"].as_f64().unwrap() > 0.0);
+            assert!(props["area"].as_f64().unwrap() > 0.0);
+            // Only local streets separate the blocks
+            assert_eq!(0, props["neighborhood"]);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use anyhow::Result;
+use geom::{Distance, Speed};
+use serde::Serialize;
+
+use crate::{Direction, Filter, LaneType, Road, RoadID, StreetNetwork};
+
+/// Rough estimates of how much traffic a road can carry, for seeding travel demand models. These
+/// loosely follow the Highway Capacity Manual, using only what's known from OSM.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RoadCapacity {
+    pub road: RoadID,
+    /// Relative to the road's direction. None if no general purpose driving lanes go this way.
+    pub forward: Option<DirectedCapacity>,
+    pub backward: Option<DirectedCapacity>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DirectedCapacity {
+    /// General purpose driving lanes in this direction
+    pub lanes: usize,
+    /// Passenger cars per hour, across all lanes
+    pub vehicles_per_hour: f64,
+    pub free_flow_speed: Speed,
+}
+
+impl Road {
+    pub fn capacity(&self) -> RoadCapacity {
+        let directed = |dir: Direction| {
+            let lanes: Vec<Distance> = self
+                .lane_specs_ltr
+                .iter()
+                .filter(|lane| lane.lt == LaneType::Driving && lane.dir == dir && !lane.is_virtual)
+                .map(|lane| lane.width)
+                .collect();
+            if lanes.is_empty() {
+                return None;
+            }
+            let avg_width =
+                lanes.iter().fold(Distance::ZERO, |sum, w| sum + *w) / lanes.len() as f64;
+            Some(estimate(
+                &self.highway_type,
+                lanes.len(),
+                avg_width,
+                self.speed_limit,
+            ))
+        };
+
+        RoadCapacity {
+            road: self.id,
+            forward: directed(Direction::Forward),
+            backward: directed(Direction::Backward),
+        }
+    }
+}
+
+impl StreetNetwork {
+    /// Returns a JSON array with the estimated capacity of each road. See `RoadCapacity`.
+    pub fn to_road_capacity_json(&self, filter: &Filter) -> Result<String> {
+        let list: Vec<serde_json::Value> = filter
+            .roads(self)
+            .map(|road| {
+                let capacity = road.capacity();
+                serde_json::json!({
+                    "road": capacity.road.0,
+                    "forward": capacity.forward.as_ref().map(directed_json),
+                    "backward": capacity.backward.as_ref().map(directed_json),
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+}
+
+pub(crate) fn directed_json(capacity: &DirectedCapacity) -> serde_json::Value {
+    serde_json::json!({
+        "lanes": capacity.lanes,
+        "vehicles_per_hour": capacity.vehicles_per_hour.round(),
+        "free_flow_speed_kmph": (capacity.free_flow_speed.inner_meters_per_second() * 3.6).round(),
+    })
+}
+
+fn estimate(
+    highway_type: &str,
+    lanes: usize,
+    avg_width: Distance,
+    speed_limit: Option<Speed>,
+) -> DirectedCapacity {
+    let highway_type = highway_type.trim_end_matches("_link");
+    let freeway = matches!(highway_type, "motorway" | "trunk");
+
+    // Without a speed limit, guess from the road class
+    let posted_kmph = speed_limit
+        .map(|speed| speed.inner_meters_per_second() * 3.6)
+        .unwrap_or(match highway_type {
+            "motorway" => 110.0,
+            "trunk" => 90.0,
+            "primary" => 60.0,
+            "secondary" | "tertiary" => 50.0,
+            "residential" | "unclassified" => 30.0,
+            "living_street" => 10.0,
+            _ => 20.0,
+        });
+    // Free-flow speeds on freeways tend to be a little above the limit. Narrow lanes slow
+    // everybody down.
+    let mut ffs_kmph = if freeway {
+        posted_kmph + 8.0
+    } else {
+        posted_kmph
+    };
+    let width = avg_width.inner_meters();
+    if width < 3.0 {
+        ffs_kmph -= 10.6;
+    } else if width < 3.3 {
+        ffs_kmph -= 3.1;
+    }
+    let ffs_kmph = ffs_kmph.max(5.0);
+
+    let per_lane = if freeway {
+        // Basic freeway segments, capped at 2400 pc/h/ln
+        (2200.0 + 6.2 * (ffs_kmph.min(113.0) - 80.0)).clamp(1900.0, 2400.0)
+    } else {
+        // A saturation flow of 1900 pc/h/ln, adjusted for lane width, and about half of that
+        // is usable with intersection control
+        let green_ratio = match highway_type {
+            "primary" | "secondary" => 0.5,
+            "tertiary" => 0.45,
+            _ => 0.4,
+        };
+        let width_factor = (1.0 + (width - 3.6) / 9.0).clamp(0.7, 1.1);
+        1900.0 * width_factor * green_ratio
+    };
+
+    DirectedCapacity {
+        lanes,
+        vehicles_per_hour: per_lane * lanes as f64,
+        free_flow_speed: Speed::km_per_hour(ffs_kmph),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        // A wide motorway hits the cap
+        let motorway = estimate(
+            "motorway",
+            3,
+            Distance::meters(3.7),
+            Some(Speed::km_per_hour(120.0)),
+        );
+        assert_eq!(7200.0, motorway.vehicles_per_hour);
+
+        // Narrow lanes lower the free-flow speed
+        let wide = estimate("residential", 1, Distance::meters(3.5), None);
+        let narrow = estimate("residential", 1, Distance::meters(2.8), None);
+        assert!(narrow.free_flow_speed < wide.free_flow_speed);
+        assert!(narrow.vehicles_per_hour < wide.vehicles_per_hour);
+    }
+}
@@ -0,0 +1,405 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use geom::Distance;
+use serde_json::{json, Value};
+
+use crate::{
+    Direction, IntersectionControl, IntersectionID, IntersectionKind, LaneType, Road, RoadID,
+    StreetNetwork, TurnDirection,
+};
+
+/// A sequence of driveable roads sharing the same name, walked from one end to the other. Each
+/// side of a dual carriageway winds up as a separate corridor.
+pub struct Corridor {
+    pub name: String,
+    /// In order along the corridor
+    pub roads: Vec<RoadID>,
+    /// In order along the corridor, including both ends
+    pub intersections: Vec<CorridorIntersection>,
+}
+
+/// A summary of one intersection along a `Corridor`.
+pub struct CorridorIntersection {
+    pub id: IntersectionID,
+    pub kind: IntersectionKind,
+    pub control: IntersectionControl,
+    /// The distance along the corridor from the previous intersection. None for the first one.
+    pub spacing: Option<Distance>,
+    /// The distance along the corridor from the previous signalized intersection, if there is one.
+    pub signal_spacing: Option<Distance>,
+    /// Every road leading into this intersection, including the ones on the corridor
+    pub approaches: Vec<Approach>,
+}
+
+/// The lanes on one road that lead into an intersection.
+pub struct Approach {
+    pub road: RoadID,
+    pub on_corridor: bool,
+    /// Driving and bus lanes pointing towards the intersection, plus shared center turn lanes,
+    /// which lead into it from both directions
+    pub lanes: usize,
+    /// Of those lanes, how many are shared center turn lanes or explicitly tagged with turns other
+    /// than going straight
+    pub turn_lanes: usize,
+}
+
+impl StreetNetwork {
+    /// Walks every named corridor and summarizes each intersection along it.
+    pub fn find_corridors(&self) -> Vec<Corridor> {
+        let mut roads_per_name: BTreeMap<&str, BTreeSet<RoadID>> = BTreeMap::new();
+        for road in self.roads.values() {
+            if !road.is_driveable() {
+                continue;
+            }
+            if let Some(ref name) = road.name {
+                roads_per_name
+                    .entry(name.as_str())
+                    .or_insert_with(BTreeSet::new)
+                    .insert(road.id);
+            }
+        }
+
+        let mut corridors = Vec::new();
+        for (name, members) in roads_per_name {
+            for (roads, intersections) in walk_corridors(self, &members) {
+                corridors.push(self.summarize_corridor(name, roads, intersections));
+            }
+        }
+        corridors
+    }
+
+    fn summarize_corridor(
+        &self,
+        name: &str,
+        roads: Vec<RoadID>,
+        intersections: Vec<IntersectionID>,
+    ) -> Corridor {
+        let mut summaries = Vec::new();
+        let mut dist_since_last = None;
+        let mut dist_since_last_signal = None;
+        for (idx, i) in intersections.into_iter().enumerate() {
+            // The previous road on the corridor leads here
+            if idx > 0 {
+                let len = self.roads[&roads[idx - 1]].untrimmed_length();
+                dist_since_last = Some(len);
+                dist_since_last_signal = dist_since_last_signal.map(|d| d + len);
+            }
+
+            let intersection = &self.intersections[&i];
+            let approaches = self
+                .roads_per_intersection(i)
+                .into_iter()
+                .filter_map(|road| {
+                    let approach = approach_lanes(road, i);
+                    if approach.lanes == 0 {
+                        return None;
+                    }
+                    Some(Approach {
+                        on_corridor: roads.contains(&road.id),
+                        ..approach
+                    })
+                })
+                .collect();
+
+            summaries.push(CorridorIntersection {
+                id: i,
+                kind: intersection.kind,
+                control: intersection.control,
+                spacing: dist_since_last,
+                signal_spacing: dist_since_last_signal,
+                approaches,
+            });
+
+            if intersection.control == IntersectionControl::Signalled {
+                dist_since_last_signal = Some(Distance::ZERO);
+            }
+        }
+        Corridor {
+            name: name.to_string(),
+            roads,
+            intersections: summaries,
+        }
+    }
+
+    /// Summarizes every named corridor as JSON.
+    pub fn to_corridors_json(&self) -> Result<String> {
+        let mut output = Vec::new();
+        for corridor in self.find_corridors() {
+            let intersections: Vec<Value> = corridor
+                .intersections
+                .iter()
+                .map(|summary| {
+                    json!({
+                        "id": summary.id.0,
+                        "osm_node_ids": self.intersections[&summary.id].osm_ids.iter().map(|id| id.0).collect::<Vec<_>>(),
+                        "intersection_kind": format!("{:?}", summary.kind),
+                        "control": format!("{:?}", summary.control),
+                        "spacing_meters": summary.spacing.map(|d| d.inner_meters()),
+                        "signal_spacing_meters": summary.signal_spacing.map(|d| d.inner_meters()),
+                        "approaches": summary.approaches.iter().map(|a| json!({
+                            "road": a.road.0,
+                            "on_corridor": a.on_corridor,
+                            "lanes": a.lanes,
+                            "turn_lanes": a.turn_lanes,
+                        })).collect::<Vec<_>>(),
+                    })
+                })
+                .collect();
+            output.push(json!({
+                "name": corridor.name,
+                "roads": corridor.roads.iter().map(|r| r.0).collect::<Vec<_>>(),
+                "intersections": intersections,
+            }));
+        }
+        Ok(serde_json::to_string(&output)?)
+    }
+
+    /// Summarizes every named corridor as CSV, with one row per intersection along each corridor.
+    pub fn to_corridors_csv(&self) -> String {
+        let mut out = String::from(
+            "corridor,position,intersection,intersection_kind,control,spacing_meters,signal_spacing_meters,approaches,approach_lanes,turn_lanes\n",
+        );
+        for corridor in self.find_corridors() {
+            // Names might contain commas or quotes
+            let name = format!("\"{}\"", corridor.name.replace('"', "\"\""));
+            for (idx, summary) in corridor.intersections.iter().enumerate() {
+                out.push_str(&format!(
+                    "{},{},{},{:?},{:?},{},{},{},{},{}\n",
+                    name,
+                    idx,
+                    summary.id.0,
+                    summary.kind,
+                    summary.control,
+                    summary
+                        .spacing
+                        .map(|d| format!("{:.1}", d.inner_meters()))
+                        .unwrap_or_default(),
+                    summary
+                        .signal_spacing
+                        .map(|d| format!("{:.1}", d.inner_meters()))
+                        .unwrap_or_default(),
+                    summary.approaches.len(),
+                    summary.approaches.iter().map(|a| a.lanes).sum::<usize>(),
+                    summary
+                        .approaches
+                        .iter()
+                        .map(|a| a.turn_lanes)
+                        .sum::<usize>(),
+                ));
+            }
+        }
+        out
+    }
+}
+
+/// Splits roads sharing a name into chains. Returns the roads and intersections in order for each
+/// chain.
+fn walk_corridors(
+    streets: &StreetNetwork,
+    members: &BTreeSet<RoadID>,
+) -> Vec<(Vec<RoadID>, Vec<IntersectionID>)> {
+    let mut roads_per_intersection: BTreeMap<IntersectionID, Vec<RoadID>> = BTreeMap::new();
+    for r in members {
+        for i in streets.roads[r].endpoints() {
+            roads_per_intersection
+                .entry(i)
+                .or_insert_with(Vec::new)
+                .push(*r);
+        }
+    }
+
+    // Prefer starting from the ends of a corridor. Anything left over afterwards is part of a
+    // loop, so start anywhere.
+    let mut starts: Vec<IntersectionID> = roads_per_intersection
+        .iter()
+        .filter(|(_, roads)| roads.len() == 1)
+        .map(|(i, _)| *i)
+        .collect();
+    starts.extend(roads_per_intersection.keys().cloned());
+
+    let mut visited = BTreeSet::new();
+    let mut results = Vec::new();
+    for start in starts {
+        let mut roads = Vec::new();
+        let mut intersections = vec![start];
+        let mut current = start;
+        while let Some(next) = roads_per_intersection[&current]
+            .iter()
+            .find(|r| !visited.contains(*r))
+            .cloned()
+        {
+            visited.insert(next);
+            roads.push(next);
+            current = streets.roads[&next].other_side(current);
+            intersections.push(current);
+        }
+        if !roads.is_empty() {
+            results.push((roads, intersections));
+        }
+    }
+    results
+}
+
+/// Describes the lanes of a road leading into intersection `i`. `on_corridor` is left false.
+fn approach_lanes(road: &Road, i: IntersectionID) -> Approach {
+    let incoming_dir = if road.dst_i == i {
+        Direction::Forward
+    } else {
+        Direction::Backward
+    };
+
+    let mut lanes = 0;
+    let mut turn_lanes = 0;
+    for lane in &road.lane_specs_ltr {
+        if lane.lt == LaneType::SharedLeftTurn {
+            lanes += 1;
+            turn_lanes += 1;
+            continue;
+        }
+        if !matches!(lane.lt, LaneType::Driving | LaneType::Bus) || lane.dir != incoming_dir {
+            continue;
+        }
+        lanes += 1;
+        if !lane.allowed_turns.is_empty() && lane.allowed_turns != TurnDirection::Through {
+            turn_lanes += 1;
+        }
+    }
+
+    Approach {
+        road: road.id,
+        on_corridor: false,
+        lanes,
+        turn_lanes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+
+    /// Main Street runs east through a signal and then an uncontrolled intersection, 100m apart.
+    /// A side road meets it at the signal, and a cul-de-sac with another name at the second one.
+    fn network() -> (StreetNetwork, Vec<IntersectionID>, Vec<RoadID>) {
+        let mut streets = StreetNetwork::blank();
+        let intersections: Vec<_> = [
+            (0.0, 0.0, IntersectionKind::MapEdge),
+            (100.0, 0.0, IntersectionKind::Intersection),
+            (200.0, 0.0, IntersectionKind::Intersection),
+            (300.0, 0.0, IntersectionKind::MapEdge),
+            (100.0, 100.0, IntersectionKind::MapEdge),
+            (200.0, 100.0, IntersectionKind::MapEdge),
+        ]
+        .into_iter()
+        .map(|(x, y, kind)| add_intersection(&mut streets, x, y, kind))
+        .collect();
+        let main = &["highway=residential", "lanes=2", "name=Main Street"];
+        let roads = vec![
+            add_road(&mut streets, intersections[0], intersections[1], main),
+            add_road(&mut streets, intersections[1], intersections[2], main),
+            add_road(&mut streets, intersections[2], intersections[3], main),
+        ];
+        add_road(
+            &mut streets,
+            intersections[1],
+            intersections[4],
+            &["highway=residential", "lanes=2"],
+        );
+        add_road(
+            &mut streets,
+            intersections[2],
+            intersections[5],
+            &["highway=residential", "lanes=2", "name=Side Street"],
+        );
+        streets
+            .intersections
+            .get_mut(&intersections[1].0)
+            .unwrap()
+            .control = IntersectionControl::Signalled;
+        (
+            streets,
+            intersections.into_iter().map(|(i, _)| i).collect(),
+            roads,
+        )
+    }
+
+    #[test]
+    fn test_find_corridors() {
+        let (streets, intersections, roads) = network();
+        let corridors = streets.find_corridors();
+        assert_eq!(
+            vec!["Main Street", "Side Street"],
+            corridors
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let main = &corridors[0];
+        assert_eq!(roads, main.roads);
+        assert_eq!(
+            intersections[0..4].to_vec(),
+            main.intersections.iter().map(|i| i.id).collect::<Vec<_>>()
+        );
+        let meters = |d: Option<Distance>| d.map(|d| d.inner_meters().round());
+        assert_eq!(
+            vec![None, Some(100.0), Some(100.0), Some(100.0)],
+            main.intersections
+                .iter()
+                .map(|i| meters(i.spacing))
+                .collect::<Vec<_>>()
+        );
+        // Nothing is counted before the first signal
+        assert_eq!(
+            vec![None, None, Some(100.0), Some(200.0)],
+            main.intersections
+                .iter()
+                .map(|i| meters(i.signal_spacing))
+                .collect::<Vec<_>>()
+        );
+
+        // One lane leads into the signal from each of the three roads
+        let signal = &main.intersections[1];
+        assert_eq!(3, signal.approaches.len());
+        assert!(signal.approaches.iter().all(|a| a.lanes == 1));
+        assert_eq!(
+            2,
+            signal.approaches.iter().filter(|a| a.on_corridor).count()
+        );
+
+        // The cul-de-sac is a corridor by itself
+        assert_eq!(1, corridors[1].roads.len());
+        assert_eq!(2, corridors[1].intersections.len());
+    }
+
+    #[test]
+    fn test_corridors_csv() {
+        let (streets, intersections, _) = network();
+        let csv = streets.to_corridors_csv();
+        let rows: Vec<Vec<&str>> = csv.lines().map(|line| line.split(',').collect()).collect();
+        assert_eq!(
+            "corridor,position,intersection,intersection_kind,control,spacing_meters,signal_spacing_meters,approaches,approach_lanes,turn_lanes",
+            csv.lines().next().unwrap()
+        );
+        // A header, 4 intersections along Main Street, and 2 along Side Street
+        assert_eq!(7, rows.len());
+
+        let signal = &rows[2];
+        assert_eq!("\"Main Street\"", signal[0]);
+        assert_eq!("1", signal[1]);
+        assert_eq!(intersections[1].0.to_string(), signal[2]);
+        assert_eq!("Signalled", signal[4]);
+        assert_eq!("100.0", signal[5]);
+        assert_eq!("", signal[6]);
+        assert_eq!(["3", "3", "0"], signal[7..10]);
+
+        let end = &rows[4];
+        assert_eq!("3", end[1]);
+        assert_eq!("100.0", end[5]);
+        assert_eq!("200.0", end[6]);
+        assert_eq!(["1", "1", "0"], end[7..10]);
+
+        assert_eq!("\"Side Street\"", rows[5][0]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::f64::consts::PI;
+
+use anyhow::Result;
+use geom::LonLat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::StreetNetwork;
+
+/// WGS84 ellipsoid
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257223563;
+/// The mean radius of the Earth, for spherical projections
+const MEAN_RADIUS: f64 = 6_371_008.8;
+const UTM_SCALE: f64 = 0.9996;
+
+/// The coordinate reference system to express GeoJSON output in. All output starts as WGS84
+/// longitude and latitude.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Crs {
+    /// WGS84 longitude and latitude, EPSG:4326. The GeoJSON default.
+    LonLat,
+    /// Web Mercator in meters, EPSG:3857
+    WebMercator,
+    /// The UTM zone containing the center of the network, in meters
+    Utm,
+    /// An azimuthal equidistant projection centered on the network, in meters. Distances from the
+    /// center are true.
+    LocalAzimuthal,
+    /// The internal coordinates of the network, in meters. Y points down.
+    Map,
+}
+
+impl Crs {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "" | "lonlat" => Ok(Self::LonLat),
+            "web_mercator" => Ok(Self::WebMercator),
+            "utm" => Ok(Self::Utm),
+            "local_azimuthal" => Ok(Self::LocalAzimuthal),
+            "map" => Ok(Self::Map),
+            x => bail!("Unknown CRS {x}"),
+        }
+    }
+}
+
+impl StreetNetwork {
+    /// Transforms every coordinate in a GeoJSON string produced by this network into another
+    /// coordinate system. The top level gets a `crs` member naming the EPSG code, when there is
+    /// one, and a `proj4` member describing the projection.
+    pub fn reproject_geojson(&self, geojson: &str, crs: Crs) -> Result<String> {
+        let mut value: Value = serde_json::from_str(geojson)?;
+        let center = self.boundary_polygon.center().to_gps(&self.gps_bounds);
+        let (epsg, proj4) = describe(crs, center);
+
+        if crs != Crs::LonLat {
+            let project = |lon: f64, lat: f64| -> (f64, f64) {
+                match crs {
+                    Crs::LonLat => (lon, lat),
+                    Crs::WebMercator => web_mercator(lon, lat),
+                    Crs::Utm => utm(lon, lat, utm_zone(center)),
+                    Crs::LocalAzimuthal => azimuthal_equidistant(lon, lat, center),
+                    Crs::Map => {
+                        let pt = LonLat::new(lon, lat).to_pt(&self.gps_bounds);
+                        (pt.x(), pt.y())
+                    }
+                }
+            };
+            reproject_value(&mut value, &project);
+        }
+
+        if let Value::Object(ref mut map) = value {
+            if let Some(epsg) = epsg {
+                map.insert(
+                    "crs".to_string(),
+                    serde_json::json!({
+                        "type": "name",
+                        "properties": { "name": epsg },
+                    }),
+                );
+            }
+            map.insert("proj4".to_string(), proj4.into());
+        }
+        Ok(serde_json::to_string(&value)?)
+    }
+}
+
+/// Finds every `coordinates` array and transforms the positions in it. Bounding boxes aren't
+/// transformed, so they're removed.
+fn reproject_value(value: &mut Value, project: &dyn Fn(f64, f64) -> (f64, f64)) {
+    match value {
+        Value::Object(map) => {
+            map.remove("bbox");
+            for (key, child) in map.iter_mut() {
+                if key == "coordinates" {
+                    reproject_coordinates(child, project);
+                } else if key != "properties" {
+                    reproject_value(child, project);
+                }
+            }
+        }
+        Value::Array(list) => {
+            for child in list {
+                reproject_value(child, project);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn reproject_coordinates(value: &mut Value, project: &dyn Fn(f64, f64) -> (f64, f64)) {
+    let Value::Array(list) = value else {
+        return;
+    };
+    if let (Some(x), Some(y)) = (
+        list.first().and_then(|x| x.as_f64()),
+        list.get(1).and_then(|x| x.as_f64()),
+    ) {
+        let (x, y) = project(x, y);
+        list[0] = x.into();
+        list[1] = y.into();
+    } else {
+        for child in list {
+            reproject_coordinates(child, project);
+        }
+    }
+}
+
+fn describe(crs: Crs, center: LonLat) -> (Option<String>, String) {
+    match crs {
+        Crs::LonLat => (
+            Some("EPSG:4326".to_string()),
+            "+proj=longlat +datum=WGS84 +no_defs".to_string(),
+        ),
+        Crs::WebMercator => (
+            Some("EPSG:3857".to_string()),
+            "+proj=merc +a=6378137 +b=6378137 +lat_ts=0 +lon_0=0 +x_0=0 +y_0=0 +k=1 +units=m \
+             +nadgrids=@null +no_defs"
+                .to_string(),
+        ),
+        Crs::Utm => {
+            let (zone, north) = utm_zone(center);
+            let epsg = if north { 32600 + zone } else { 32700 + zone };
+            (
+                Some(format!("EPSG:{epsg}")),
+                format!(
+                    "+proj=utm +zone={zone}{} +datum=WGS84 +units=m +no_defs",
+                    if north { "" } else { " +south" }
+                ),
+            )
+        }
+        Crs::LocalAzimuthal => (
+            None,
+            format!(
+                "+proj=aeqd +lat_0={} +lon_0={} +x_0=0 +y_0=0 +R={MEAN_RADIUS} +units=m +no_defs",
+                center.y(),
+                center.x()
+            ),
+        ),
+        // Not a standard projection; y points down, and the origin is the corner of the bounds
+        Crs::Map => (None, String::new()),
+    }
+}
+
+fn web_mercator(lon: f64, lat: f64) -> (f64, f64) {
+    let x = WGS84_A * lon.to_radians();
+    let y = WGS84_A * (PI / 4.0 + lat.to_radians() / 2.0).tan().ln();
+    (x, y)
+}
+
+/// The zone number and whether it's in the northern hemisphere
+fn utm_zone(center: LonLat) -> (u32, bool) {
+    let zone = (((center.x() + 180.0) / 6.0).floor() as u32 + 1).clamp(1, 60);
+    (zone, center.y() >= 0.0)
+}
+
+/// The transverse Mercator series from Snyder, "Map Projections: A Working Manual"
+fn utm(lon: f64, lat: f64, (zone, north): (u32, bool)) -> (f64, f64) {
+    let e2 = WGS84_F * (2.0 - WGS84_F);
+    let e4 = e2 * e2;
+    let e6 = e4 * e2;
+    let ep2 = e2 / (1.0 - e2);
+
+    let phi = lat.to_radians();
+    let central_meridian = (zone as f64 - 1.0) * 6.0 - 180.0 + 3.0;
+    let n = WGS84_A / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+    let t = phi.tan().powi(2);
+    let c = ep2 * phi.cos().powi(2);
+    let a = phi.cos() * (lon - central_meridian).to_radians();
+    let m = WGS84_A
+        * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+            - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+            + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+            - (35.0 * e6 / 3072.0) * (6.0 * phi).sin());
+
+    let x = UTM_SCALE
+        * n
+        * (a + (1.0 - t + c) * a.powi(3) / 6.0
+            + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0)
+        + 500_000.0;
+    let mut y = UTM_SCALE
+        * (m + n
+            * phi.tan()
+            * (a * a / 2.0
+                + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+    if !north {
+        y += 10_000_000.0;
+    }
+    (x, y)
+}
+
+/// The spherical azimuthal equidistant projection
+fn azimuthal_equidistant(lon: f64, lat: f64, center: LonLat) -> (f64, f64) {
+    let (phi, phi1) = (lat.to_radians(), center.y().to_radians());
+    let dlambda = (lon - center.x()).to_radians();
+    let cos_c = phi1.sin() * phi.sin() + phi1.cos() * phi.cos() * dlambda.cos();
+    let c = cos_c.clamp(-1.0, 1.0).acos();
+    let k = if c.abs() < 1e-12 { 1.0 } else { c / c.sin() };
+    let x = MEAN_RADIUS * k * phi.cos() * dlambda.sin();
+    let y = MEAN_RADIUS * k * (phi1.cos() * phi.sin() - phi1.sin() * phi.cos() * dlambda.cos());
+    (x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_projections() {
+        let close = |(x1, y1): (f64, f64), (x2, y2): (f64, f64)| {
+            (x1 - x2).abs() < 0.01 && (y1 - y2).abs() < 0.01
+        };
+
+        assert!(close((0.0, 0.0), web_mercator(0.0, 0.0)));
+        assert!(close((WGS84_A * PI, 0.0), web_mercator(180.0, 0.0)));
+
+        // On the central meridian of zone 10, at the equator
+        let zone = utm_zone(LonLat::new(-122.3, 47.6));
+        assert_eq!((10, true), zone);
+        assert!(close((500_000.0, 0.0), utm(-123.0, 0.0, zone)));
+
+        let center = LonLat::new(-122.3, 47.6);
+        assert!(close(
+            (0.0, 0.0),
+            azimuthal_equidistant(-122.3, 47.6, center)
+        ));
+        let (x, y) = azimuthal_equidistant(-122.3, 48.6, center);
+        assert!(x.abs() < 0.01);
+        assert!((y - MEAN_RADIUS * PI / 180.0).abs() < 0.01);
+    }
+}
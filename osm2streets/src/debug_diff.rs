@@ -0,0 +1,137 @@
+use std::collections::BTreeSet;
+
+use geom::Polygon;
+
+use crate::{IntersectionID, RoadID, StreetNetwork};
+
+/// What changed between two consecutive debug steps.
+#[derive(Clone, Debug, Default)]
+pub struct StepDiff {
+    pub roads_added: BTreeSet<RoadID>,
+    pub roads_modified: BTreeSet<RoadID>,
+    /// The roads that no longer exist, with their last polygon, so they can still be drawn
+    pub roads_removed: Vec<(RoadID, Polygon)>,
+    pub intersections_added: BTreeSet<IntersectionID>,
+    pub intersections_modified: BTreeSet<IntersectionID>,
+    pub intersections_removed: Vec<(IntersectionID, Polygon)>,
+}
+
+impl StepDiff {
+    pub fn new(before: &StreetNetwork, after: &StreetNetwork) -> Self {
+        let mut diff = Self::default();
+
+        for (id, road) in &after.roads {
+            match before.roads.get(id) {
+                None => {
+                    diff.roads_added.insert(*id);
+                }
+                Some(old) if old != road => {
+                    diff.roads_modified.insert(*id);
+                }
+                Some(_) => {}
+            }
+        }
+        for (id, road) in &before.roads {
+            if !after.roads.contains_key(id) {
+                diff.roads_removed
+                    .push((*id, road.center_line.make_polygons(road.total_width())));
+            }
+        }
+
+        for (id, intersection) in &after.intersections {
+            match before.intersections.get(id) {
+                None => {
+                    diff.intersections_added.insert(*id);
+                }
+                Some(old) if old != intersection => {
+                    diff.intersections_modified.insert(*id);
+                }
+                Some(_) => {}
+            }
+        }
+        for (id, intersection) in &before.intersections {
+            if !after.intersections.contains_key(id) {
+                diff.intersections_removed
+                    .push((*id, intersection.polygon.clone()));
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roads_added.is_empty()
+            && self.roads_modified.is_empty()
+            && self.roads_removed.is_empty()
+            && self.intersections_added.is_empty()
+            && self.intersections_modified.is_empty()
+            && self.intersections_removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Pt2D;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_lone_road, add_road};
+    use crate::{IntersectionControl, IntersectionKind};
+
+    #[test]
+    fn test_step_diff() {
+        let mut before = StreetNetwork::blank();
+        let i0 = add_intersection(&mut before, 0.0, 0.0, IntersectionKind::MapEdge);
+        let i1 = add_intersection(&mut before, 100.0, 0.0, IntersectionKind::Connection);
+        let i2 = add_intersection(&mut before, 200.0, 0.0, IntersectionKind::MapEdge);
+        let r0 = add_road(&mut before, i0, i1, &["highway=residential"]);
+        let r1 = add_road(&mut before, i1, i2, &["highway=residential"]);
+
+        let mut after = before.clone();
+        assert!(StepDiff::new(&before, &after).is_empty());
+
+        after.roads.get_mut(&r0).unwrap().layer = 1;
+        let removed_road = after.roads.remove(&r1).unwrap();
+        after.intersections.get_mut(&i1.0).unwrap().control = IntersectionControl::Signalled;
+        after.intersections.remove(&i2.0);
+        let r2 = add_lone_road(
+            &mut after,
+            vec![Pt2D::new(0.0, 100.0), Pt2D::new(100.0, 100.0)],
+            &["highway=residential"],
+        );
+        let new_intersections = BTreeSet::from([after.roads[&r2].src_i, after.roads[&r2].dst_i]);
+
+        let diff = StepDiff::new(&before, &after);
+        assert!(!diff.is_empty());
+        assert_eq!(BTreeSet::from([r2]), diff.roads_added);
+        assert_eq!(BTreeSet::from([r0]), diff.roads_modified);
+        assert_eq!(1, diff.roads_removed.len());
+        assert_eq!(r1, diff.roads_removed[0].0);
+        // The removed road is drawn where it used to be
+        assert!(diff.roads_removed[0]
+            .1
+            .get_bounds()
+            .contains(removed_road.center_line.middle()));
+        assert_eq!(new_intersections, diff.intersections_added);
+        assert_eq!(BTreeSet::from([i1.0]), diff.intersections_modified);
+        assert_eq!(1, diff.intersections_removed.len());
+        assert_eq!(i2.0, diff.intersections_removed[0].0);
+        assert_eq!(
+            before.intersections[&i2.0].polygon,
+            diff.intersections_removed[0].1
+        );
+
+        // Going backwards swaps what's added and removed
+        let reverse = StepDiff::new(&after, &before);
+        assert_eq!(BTreeSet::from([r1]), reverse.roads_added);
+        assert_eq!(BTreeSet::from([r0]), reverse.roads_modified);
+        assert_eq!(
+            vec![r2],
+            reverse
+                .roads_removed
+                .iter()
+                .map(|(r, _)| *r)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(BTreeSet::from([i2.0]), reverse.intersections_added);
+    }
+}
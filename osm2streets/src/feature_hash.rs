@@ -0,0 +1,104 @@
+use anyhow::Result;
+use serde_json::Value;
+
+/// Adds a `hash` property to every feature in the output of any GeoJSON method, including
+/// features nested in an object of FeatureCollections. The hash covers the geometry and the other
+/// properties, so a cache or frontend can skip re-uploading features that didn't change after an
+/// edit. Hashes are stable across runs and platforms, but not across versions of osm2streets.
+pub fn add_feature_hashes(geojson: &str) -> Result<String> {
+    let mut value: Value = serde_json::from_str(geojson)?;
+    hash_features(&mut value)?;
+    Ok(serde_json::to_string(&value)?)
+}
+
+fn hash_features(value: &mut Value) -> Result<()> {
+    match value {
+        Value::Object(map) => {
+            if map.get("type").and_then(|x| x.as_str()) == Some("Feature") {
+                let properties = map
+                    .entry("properties")
+                    .or_insert_with(|| Value::Object(Default::default()));
+                if let Value::Object(properties) = properties {
+                    properties.remove("hash");
+                }
+                let hash = {
+                    let mut hasher = Fnv1a::new();
+                    // Map keys are sorted, so this is canonical
+                    hasher.write(serde_json::to_string(&map.get("geometry"))?.as_bytes());
+                    hasher.write(serde_json::to_string(&map["properties"])?.as_bytes());
+                    hasher.finish()
+                };
+                if let Some(Value::Object(properties)) = map.get_mut("properties") {
+                    properties.insert("hash".to_string(), format!("{hash:016x}").into());
+                }
+            } else {
+                for child in map.values_mut() {
+                    hash_features(child)?;
+                }
+            }
+        }
+        Value::Array(list) => {
+            for child in list {
+                hash_features(child)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 64-bit FNV-1a. std's `DefaultHasher` isn't guaranteed to be stable between Rust releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feature_hashes() {
+        let feature = |x: f64, kind: &str| {
+            serde_json::json!({
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [x, 1.0] },
+                    "properties": { "type": kind },
+                }],
+            })
+            .to_string()
+        };
+        let hash = |input: String| -> String {
+            let output: Value = serde_json::from_str(&add_feature_hashes(&input).unwrap()).unwrap();
+            output["features"][0]["properties"]["hash"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let original = hash(feature(0.0, "Driving"));
+        assert_eq!(original, hash(feature(0.0, "Driving")));
+        assert_ne!(original, hash(feature(0.5, "Driving")));
+        assert_ne!(original, hash(feature(0.0, "Biking")));
+
+        // Hashing output again doesn't change it
+        let hashed = add_feature_hashes(&feature(0.0, "Driving")).unwrap();
+        assert_eq!(hashed, add_feature_hashes(&hashed).unwrap());
+    }
+}
@@ -0,0 +1,94 @@
+use abstutil::Tags;
+use anyhow::Result;
+use geojson::Feature;
+use geom::{Distance, PolyLine, Pt2D, Speed};
+use serde::{Deserialize, Serialize};
+
+use crate::render::serialize_features;
+use crate::{osm, IntersectionID, StreetNetwork};
+
+/// How far a ferry's end can be from an intersection and still connect to it. Ferry routes
+/// often end at a terminal a short walk from the road network.
+const MAX_TERMINAL_DIST: Distance = Distance::const_meters(50.0);
+/// When `duration` isn't tagged
+const TYPICAL_FERRY_SPEED_KMPH: f64 = 20.0;
+
+/// A `route=ferry` way. These connect the road network across water, but aren't `Road`s, so
+/// they don't get lanes or intersection geometry.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ferry {
+    pub osm_way: osm::WayID,
+    pub route: PolyLine,
+    pub name: Option<String>,
+    /// Tagged to carry cars, with `motor_vehicle=yes` or `motorcar=yes`
+    pub carries_vehicles: bool,
+    /// How many seconds the crossing takes, from the `duration` tag
+    pub tagged_duration: Option<f64>,
+}
+
+impl Ferry {
+    pub fn new(osm_way: osm::WayID, route: PolyLine, tags: &Tags) -> Self {
+        Self {
+            osm_way,
+            route,
+            name: tags.get("name").cloned(),
+            carries_vehicles: tags.is("motor_vehicle", "yes") || tags.is("motorcar", "yes"),
+            tagged_duration: tags
+                .get("duration")
+                .and_then(|x| crate::units::parse_duration(x)),
+        }
+    }
+
+    /// How long the crossing takes, using the tagged duration if possible
+    pub fn duration_seconds(&self) -> f64 {
+        self.tagged_duration.unwrap_or_else(|| {
+            self.route.length().inner_meters()
+                / Speed::km_per_hour(TYPICAL_FERRY_SPEED_KMPH).inner_meters_per_second()
+        })
+    }
+}
+
+impl StreetNetwork {
+    /// For each ferry, finds the intersections it connects, if both ends are near one.
+    pub fn ferry_connections(&self) -> Vec<(usize, IntersectionID, IntersectionID)> {
+        let closest = |pt: Pt2D| {
+            self.intersections
+                .values()
+                .map(|i| (i.id, i.polygon.center().dist_to(pt)))
+                .filter(|(_, dist)| *dist <= MAX_TERMINAL_DIST)
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .map(|(id, _)| id)
+        };
+        let mut results = Vec::new();
+        for (idx, ferry) in self.ferries.iter().enumerate() {
+            if let (Some(i1), Some(i2)) = (
+                closest(ferry.route.first_pt()),
+                closest(ferry.route.last_pt()),
+            ) {
+                if i1 != i2 {
+                    results.push((idx, i1, i2));
+                }
+            }
+        }
+        results
+    }
+
+    /// Shows every ferry route as a line, with the intersections it connects.
+    pub fn to_ferries_geojson(&self) -> Result<String> {
+        let connections = self.ferry_connections();
+        let mut features = Vec::new();
+        for (idx, ferry) in self.ferries.iter().enumerate() {
+            let mut f = Feature::from(ferry.route.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("osm_way_id", ferry.osm_way.0);
+            f.set_property("name", ferry.name.clone());
+            f.set_property("carries_vehicles", ferry.carries_vehicles);
+            f.set_property("duration_seconds", ferry.duration_seconds().round());
+            if let Some((_, i1, i2)) = connections.iter().find(|(x, _, _)| *x == idx) {
+                f.set_property("src_i", i1.0);
+                f.set_property("dst_i", i2.0);
+            }
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
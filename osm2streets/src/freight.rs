@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use abstutil::Tags;
+use anyhow::Result;
+use geojson::Feature;
+use geom::Distance;
+use petgraph::unionfind::UnionFind;
+use serde::{Deserialize, Serialize};
+
+use crate::render::serialize_features;
+use crate::{IntersectionID, StreetNetwork, Structure};
+
+/// Legal and physical limits on heavy goods vehicles using a road
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FreightRestrictions {
+    /// From `maxheight`, or `maxheight:physical` if it's lower
+    pub max_height: Option<Distance>,
+    /// In metric tonnes, from `maxweight`
+    pub max_weight: Option<f64>,
+    /// In metric tonnes, from `maxaxleload`
+    pub max_axle_load: Option<f64>,
+    /// From `hgv=no`
+    pub hgv_banned: bool,
+}
+
+/// The size of a truck, to check against `FreightRestrictions`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Truck {
+    pub height: Distance,
+    /// In metric tonnes
+    pub weight: f64,
+    /// In metric tonnes
+    pub axle_load: f64,
+}
+
+impl FreightRestrictions {
+    pub fn parse(tags: &Tags) -> Self {
+        let distance = |key: &str| tags.get(key).and_then(|x| crate::units::parse_distance(x));
+        let weight = |key: &str| tags.get(key).and_then(|x| crate::units::parse_weight(x));
+        Self {
+            max_height: match (distance("maxheight"), distance("maxheight:physical")) {
+                (Some(legal), Some(physical)) => Some(legal.min(physical)),
+                (legal, physical) => legal.or(physical),
+            },
+            max_weight: weight("maxweight"),
+            max_axle_load: weight("maxaxleload"),
+            hgv_banned: tags.is("hgv", "no"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// If the truck can't use the road, describes why
+    pub fn blocks(&self, truck: &Truck) -> Option<String> {
+        if self.hgv_banned {
+            return Some("hgv=no".to_string());
+        }
+        if let Some(height) = self.max_height {
+            if truck.height > height {
+                return Some(format!("max height {height}"));
+            }
+        }
+        if let Some(weight) = self.max_weight {
+            if truck.weight > weight {
+                return Some(format!("max weight {weight}t"));
+            }
+        }
+        if let Some(load) = self.max_axle_load {
+            if truck.axle_load > load {
+                return Some(format!("max axle load {load}t"));
+            }
+        }
+        None
+    }
+}
+
+impl StreetNetwork {
+    /// Finds roads that a truck can't use, where the roads it can use don't connect the two
+    /// ends some other way. These split the truck network, like a low bridge or weight-limited
+    /// bridge with no alternative. Each feature has the `reason` and whether it's a `structure`.
+    pub fn to_freight_barriers_geojson(&self, truck: &Truck) -> Result<String> {
+        let index: BTreeMap<IntersectionID, usize> = self
+            .intersections
+            .keys()
+            .enumerate()
+            .map(|(idx, i)| (*i, idx))
+            .collect();
+        let mut components = UnionFind::new(index.len());
+        let mut blocked = Vec::new();
+        for road in self.roads.values() {
+            if !road.is_driveable() {
+                continue;
+            }
+            match road.freight.blocks(truck) {
+                Some(reason) => blocked.push((road, reason)),
+                None => {
+                    components.union(index[&road.src_i], index[&road.dst_i]);
+                }
+            }
+        }
+
+        let mut features = Vec::new();
+        for (road, reason) in blocked {
+            if components.equiv(index[&road.src_i], index[&road.dst_i]) {
+                continue;
+            }
+            let mut f = Feature::from(road.center_line.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("road", road.id.0);
+            f.set_property("reason", reason);
+            f.set_property(
+                "structure",
+                match road.structure {
+                    Some(Structure::Bridge) => "bridge",
+                    Some(Structure::Tunnel) => "tunnel",
+                    None => "",
+                },
+            );
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freight_restrictions() {
+        let mut tags = Tags::empty();
+        tags.insert("maxheight", "14'");
+        tags.insert("maxheight:physical", "4.5");
+        tags.insert("maxweight", "7.5");
+        let restrictions = FreightRestrictions::parse(&tags);
+        assert_eq!(Some(Distance::feet(14.0)), restrictions.max_height);
+
+        let mut truck = Truck {
+            height: Distance::meters(4.0),
+            weight: 7.0,
+            axle_load: 2.0,
+        };
+        assert_eq!(None, restrictions.blocks(&truck));
+        truck.weight = 10.0;
+        assert!(restrictions.blocks(&truck).is_some());
+
+        tags.insert("maxheight", "default");
+        tags.insert("hgv", "no");
+        let restrictions = FreightRestrictions::parse(&tags);
+        assert_eq!(Some(Distance::meters(4.5)), restrictions.max_height);
+        assert!(restrictions.hgv_banned);
+    }
+}
@@ -0,0 +1,156 @@
+//! Conversions between osm2streets geometry and the `geo` ecosystem, so Rust users can run
+//! algorithms like buffering and overlay on the output. Every conversion takes an optional
+//! `GPSBounds`, like `to_geojson`. With it, coordinates are longitude and latitude. Without it,
+//! they're in the network's local space, in meters.
+
+use anyhow::Result;
+use geom::{GPSBounds, LonLat, PolyLine, Polygon, Pt2D, Ring};
+
+use crate::{Intersection, Road, StreetNetwork, Surface};
+
+pub fn pt_to_geo(pt: Pt2D, gps_bounds: Option<&GPSBounds>) -> geo::Point {
+    match gps_bounds {
+        Some(gps_bounds) => {
+            let gps = pt.to_gps(gps_bounds);
+            geo::Point::new(gps.x(), gps.y())
+        }
+        None => geo::Point::new(pt.x(), pt.y()),
+    }
+}
+
+pub fn pt_from_geo(pt: geo::Point, gps_bounds: Option<&GPSBounds>) -> Pt2D {
+    match gps_bounds {
+        Some(gps_bounds) => LonLat::new(pt.x(), pt.y()).to_pt(gps_bounds),
+        None => Pt2D::new(pt.x(), pt.y()),
+    }
+}
+
+pub fn polyline_to_geo(pl: &PolyLine, gps_bounds: Option<&GPSBounds>) -> geo::LineString {
+    pts_to_geo(pl.points(), gps_bounds)
+}
+
+pub fn polyline_from_geo(ls: &geo::LineString, gps_bounds: Option<&GPSBounds>) -> Result<PolyLine> {
+    PolyLine::new(pts_from_geo(ls, gps_bounds))
+}
+
+pub fn polygon_to_geo(polygon: &Polygon, gps_bounds: Option<&GPSBounds>) -> geo::Polygon {
+    let local: geo::Polygon = polygon.clone().into();
+    match gps_bounds {
+        Some(gps_bounds) => local_polygon_to_gps(&local, gps_bounds),
+        None => local,
+    }
+}
+
+/// Fails if the outline or a hole has repeated points or fewer than 3 points
+pub fn polygon_from_geo(polygon: &geo::Polygon, gps_bounds: Option<&GPSBounds>) -> Result<Polygon> {
+    let outer = Ring::deduping_new(pts_from_geo(polygon.exterior(), gps_bounds))?;
+    let mut holes = Vec::new();
+    for hole in polygon.interiors() {
+        holes.push(Ring::deduping_new(pts_from_geo(hole, gps_bounds))?);
+    }
+    Ok(Polygon::with_holes(outer, holes))
+}
+
+fn pts_to_geo(pts: &[Pt2D], gps_bounds: Option<&GPSBounds>) -> geo::LineString {
+    pts.iter()
+        .map(|pt| pt_to_geo(*pt, gps_bounds).0)
+        .collect::<Vec<geo::Coord>>()
+        .into()
+}
+
+fn pts_from_geo(ls: &geo::LineString, gps_bounds: Option<&GPSBounds>) -> Vec<Pt2D> {
+    ls.points().map(|pt| pt_from_geo(pt, gps_bounds)).collect()
+}
+
+fn local_polygon_to_gps(polygon: &geo::Polygon, gps_bounds: &GPSBounds) -> geo::Polygon {
+    geo::Polygon::new(
+        ring_to_gps(polygon.exterior(), gps_bounds),
+        polygon
+            .interiors()
+            .iter()
+            .map(|hole| ring_to_gps(hole, gps_bounds))
+            .collect(),
+    )
+}
+
+fn ring_to_gps(ls: &geo::LineString, gps_bounds: &GPSBounds) -> geo::LineString {
+    ls.points()
+        .map(|pt| pt_to_geo(Pt2D::new(pt.x(), pt.y()), Some(gps_bounds)).0)
+        .collect::<Vec<geo::Coord>>()
+        .into()
+}
+
+impl Road {
+    pub fn center_line_geo(&self, gps_bounds: Option<&GPSBounds>) -> geo::LineString {
+        polyline_to_geo(&self.center_line, gps_bounds)
+    }
+
+    pub fn reference_line_geo(&self, gps_bounds: Option<&GPSBounds>) -> geo::LineString {
+        polyline_to_geo(&self.reference_line, gps_bounds)
+    }
+
+    /// The area covered by all of the road's lanes
+    pub fn polygon_geo(&self, gps_bounds: Option<&GPSBounds>) -> geo::Polygon {
+        polygon_to_geo(
+            &self.center_line.make_polygons(self.total_width()),
+            gps_bounds,
+        )
+    }
+
+    /// One polygon per lane, from left to right
+    pub fn lane_polygons_geo(&self, gps_bounds: Option<&GPSBounds>) -> Vec<geo::Polygon> {
+        self.get_lane_center_lines()
+            .into_iter()
+            .zip(self.lane_specs_ltr.iter())
+            .map(|(pl, lane)| polygon_to_geo(&pl.make_polygons(lane.width), gps_bounds))
+            .collect()
+    }
+}
+
+impl Intersection {
+    pub fn polygon_geo(&self, gps_bounds: Option<&GPSBounds>) -> geo::Polygon {
+        polygon_to_geo(&self.polygon, gps_bounds)
+    }
+}
+
+impl Surface {
+    /// `area` is already a `geo::Polygon` in local space; this can also convert it to GPS.
+    pub fn area_geo(&self, gps_bounds: Option<&GPSBounds>) -> geo::Polygon {
+        match gps_bounds {
+            Some(gps_bounds) => local_polygon_to_gps(&self.area, gps_bounds),
+            None => self.area.clone(),
+        }
+    }
+}
+
+impl StreetNetwork {
+    pub fn boundary_polygon_geo(&self, gps_bounds: Option<&GPSBounds>) -> geo::Polygon {
+        polygon_to_geo(&self.boundary_polygon, gps_bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let square = Ring::must_new(vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 0.0),
+            Pt2D::new(10.0, 10.0),
+            Pt2D::new(0.0, 10.0),
+            Pt2D::new(0.0, 0.0),
+        ])
+        .into_polygon();
+        let round_trip = polygon_from_geo(&polygon_to_geo(&square, None), None).unwrap();
+        assert_eq!(
+            square.get_outer_ring().points(),
+            round_trip.get_outer_ring().points()
+        );
+
+        let pl = PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(5.0, 5.0)]);
+        let round_trip = polyline_from_geo(&polyline_to_geo(&pl, None), None).unwrap();
+        assert_eq!(pl, round_trip);
+    }
+}
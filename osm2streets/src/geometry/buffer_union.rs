@@ -0,0 +1,237 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use geo::{Area, BooleanOps, Intersects};
+use geom::{Distance, Polygon, Pt2D, Ring};
+
+use super::{mapped_area, Results};
+use crate::{InputRoad, RoadID, TrimAlgorithm};
+
+/// If `attempt` failed or made a polygon crossing itself, replaces it with `buffer_union`. If that
+/// fails too, the original attempt is kept, so the fallback never makes things worse.
+pub(crate) fn fall_back_if_broken(
+    attempt: Result<Results>,
+    algorithm: TrimAlgorithm,
+    blank: Results,
+    roads: BTreeMap<RoadID, InputRoad>,
+) -> Result<Results> {
+    let problem = match attempt {
+        Ok(ref results) => {
+            if !self_intersects(&results.intersection_polygon) {
+                return attempt;
+            }
+            format!("{:?} made a polygon crossing itself", results.algorithm)
+        }
+        Err(ref err) => format!("{algorithm:?} failed: {err}"),
+    };
+    match buffer_union(blank, roads) {
+        Ok(mut results) => {
+            results.algorithm = TrimAlgorithm::BufferUnion;
+            results.reason = problem;
+            Ok(results)
+        }
+        Err(err) => {
+            let mut results = attempt?;
+            results
+                .reason
+                .push_str(&format!(", and buffering failed too: {err}"));
+            Ok(results)
+        }
+    }
+}
+
+/// Buffers the end of every road and unions the pieces, then trims roads back to where they leave
+/// the union. The result isn't pretty, but unlike the corner-based approach, it doesn't depend on
+/// road edges colliding sensibly, so it works on pathological input.
+pub(crate) fn buffer_union(
+    results: Results,
+    roads: BTreeMap<RoadID, InputRoad>,
+) -> Result<Results> {
+    // The ends of every road should overlap near the center, and the widest road covers the
+    // most space
+    let reach = roads.values().fold(Distance::meters(1.0), |reach, road| {
+        reach.max(road.half_width())
+    });
+
+    let mut union: Option<geo::MultiPolygon> = None;
+    for road in roads.values() {
+        let pl = road.center_line_pointed_at(results.intersection_id);
+        let len = pl.length();
+        // Roads with no length have no end to buffer
+        let Ok(end) = pl.maybe_exact_slice(len - reach.min(len), len) else {
+            continue;
+        };
+        let buffer: geo::Polygon = end.make_polygons(road.total_width).into();
+        let buffer = geo::MultiPolygon(vec![buffer]);
+        union = Some(match union {
+            Some(union) => union.union(&buffer),
+            None => buffer,
+        });
+    }
+
+    // If the pieces somehow didn't all overlap, use the biggest
+    let Some(biggest) = union.and_then(|union| {
+        union
+            .0
+            .into_iter()
+            .max_by(|a, b| a.unsigned_area().total_cmp(&b.unsigned_area()))
+    }) else {
+        bail!("Buffering road ends produced nothing");
+    };
+    // Fill in any holes
+    let pts: Vec<Pt2D> = biggest
+        .exterior()
+        .coords()
+        .map(|c| Pt2D::new(c.x, c.y))
+        .collect();
+    let area = Ring::deduping_new(pts)?.into_polygon();
+
+    mapped_area::trim_to_area(results, roads, &area)
+}
+
+/// True if any two non-adjacent edges of the polygon's outline cross
+pub(crate) fn self_intersects(polygon: &Polygon) -> bool {
+    let pts = polygon.get_outer_ring().points();
+    let lines: Vec<geo::Line> = pts
+        .windows(2)
+        .map(|pair| geo::Line::new((pair[0].x(), pair[0].y()), (pair[1].x(), pair[1].y())))
+        .collect();
+    for i in 0..lines.len() {
+        for j in (i + 2)..lines.len() {
+            // The first and last lines share the ring's start
+            if i == 0 && j == lines.len() - 1 {
+                continue;
+            }
+            if lines[i].intersects(&lines[j]) {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::PolyLine;
+
+    use super::*;
+    use crate::IntersectionID;
+
+    fn blank() -> Results {
+        Results {
+            intersection_id: IntersectionID(1),
+            intersection_polygon: Polygon::dummy(),
+            algorithm: TrimAlgorithm::GeneralCase,
+            reason: String::new(),
+            trimmed_center_pts: BTreeMap::new(),
+            trim_starts: BTreeMap::new(),
+            trim_ends: BTreeMap::new(),
+            debug: Vec::new(),
+        }
+    }
+
+    /// Three roads meeting at (50, 50), from the west, east, and north
+    fn roads() -> BTreeMap<RoadID, InputRoad> {
+        let mut roads = BTreeMap::new();
+        for (id, pt) in [(0, (0.0, 50.0)), (1, (100.0, 50.0)), (2, (50.0, 0.0))] {
+            roads.insert(
+                RoadID(id),
+                InputRoad {
+                    id: RoadID(id),
+                    src_i: IntersectionID(10 + id),
+                    dst_i: IntersectionID(1),
+                    center_line: PolyLine::must_new(vec![
+                        Pt2D::new(pt.0, pt.1),
+                        Pt2D::new(50.0, 50.0),
+                    ]),
+                    total_width: Distance::meters(5.0),
+                    highway_type: "residential".to_string(),
+                },
+            );
+        }
+        roads
+    }
+
+    fn square() -> Polygon {
+        Ring::must_new(vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 0.0),
+            Pt2D::new(10.0, 10.0),
+            Pt2D::new(0.0, 10.0),
+            Pt2D::new(0.0, 0.0),
+        ])
+        .into_polygon()
+    }
+
+    fn bowtie() -> Polygon {
+        Ring::must_new(vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 10.0),
+            Pt2D::new(10.0, 0.0),
+            Pt2D::new(0.0, 10.0),
+            Pt2D::new(0.0, 0.0),
+        ])
+        .into_polygon()
+    }
+
+    fn with_polygon(polygon: Polygon) -> Results {
+        let mut results = blank();
+        results.intersection_polygon = polygon;
+        results
+    }
+
+    #[test]
+    fn test_buffer_union() {
+        let results = buffer_union(blank(), roads()).unwrap();
+        assert!(results
+            .intersection_polygon
+            .contains_pt(Pt2D::new(50.0, 50.0)));
+        assert!(!self_intersects(&results.intersection_polygon));
+        for pl in results.trimmed_center_pts.values() {
+            assert!(pl.length() < Distance::meters(50.0));
+            assert!(pl.length() > Distance::meters(40.0));
+        }
+    }
+
+    #[test]
+    fn test_fall_back_if_broken() {
+        let algorithm = TrimAlgorithm::GeneralCase;
+
+        // A good polygon is kept
+        let results =
+            fall_back_if_broken(Ok(with_polygon(square())), algorithm, blank(), roads()).unwrap();
+        assert_eq!(results.algorithm, TrimAlgorithm::GeneralCase);
+        assert_eq!(results.intersection_polygon, square());
+
+        // A polygon crossing itself or an error are replaced
+        let results =
+            fall_back_if_broken(Ok(with_polygon(bowtie())), algorithm, blank(), roads()).unwrap();
+        assert_eq!(results.algorithm, TrimAlgorithm::BufferUnion);
+        assert_eq!(results.reason, "GeneralCase made a polygon crossing itself");
+        let results =
+            fall_back_if_broken(Err(anyhow!("broken")), algorithm, blank(), roads()).unwrap();
+        assert_eq!(results.algorithm, TrimAlgorithm::BufferUnion);
+        assert_eq!(results.reason, "GeneralCase failed: broken");
+
+        // If buffering fails too, the original result stands
+        let results = fall_back_if_broken(
+            Ok(with_polygon(bowtie())),
+            algorithm,
+            blank(),
+            BTreeMap::new(),
+        )
+        .unwrap();
+        assert_eq!(results.algorithm, TrimAlgorithm::GeneralCase);
+        assert_eq!(results.intersection_polygon, bowtie());
+        assert!(
+            fall_back_if_broken(Err(anyhow!("broken")), algorithm, blank(), BTreeMap::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_self_intersects() {
+        assert!(!self_intersects(&square()));
+        assert!(self_intersects(&bowtie()));
+    }
+}
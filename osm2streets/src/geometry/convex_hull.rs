@@ -0,0 +1,74 @@
+use anyhow::Result;
+use geom::{Polygon, Pt2D, Ring};
+
+/// Replaces a polygon with its convex hull.
+pub(crate) fn convex_hull(polygon: &Polygon) -> Result<Polygon> {
+    let mut pts = hull(polygon.get_outer_ring().points().clone());
+    pts.push(pts[0]);
+    Ok(Ring::deduping_new(pts)?.into_polygon())
+}
+
+/// Andrew's monotone chain algorithm. Returns the hull in counter-clockwise order (with Y pointing
+/// up), without repeating the first point.
+fn hull(mut pts: Vec<Pt2D>) -> Vec<Pt2D> {
+    pts.sort_by(|a, b| a.x().total_cmp(&b.x()).then(a.y().total_cmp(&b.y())));
+    pts.dedup();
+    if pts.len() < 3 {
+        return pts;
+    }
+
+    fn cross(o: Pt2D, a: Pt2D, b: Pt2D) -> f64 {
+        (a.x() - o.x()) * (b.y() - o.y()) - (a.y() - o.y()) * (b.x() - o.x())
+    }
+
+    let mut lower: Vec<Pt2D> = Vec::new();
+    for pt in &pts {
+        while lower.len() >= 2 && cross(lower[lower.len() - 2], lower[lower.len() - 1], *pt) <= 0.0
+        {
+            lower.pop();
+        }
+        lower.push(*pt);
+    }
+    let mut upper: Vec<Pt2D> = Vec::new();
+    for pt in pts.iter().rev() {
+        while upper.len() >= 2 && cross(upper[upper.len() - 2], upper[upper.len() - 1], *pt) <= 0.0
+        {
+            upper.pop();
+        }
+        upper.push(*pt);
+    }
+
+    // The last point of each half is the first point of the other
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    lower
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hull() {
+        let pts = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 0.0),
+            // Inside
+            Pt2D::new(5.0, 2.0),
+            Pt2D::new(10.0, 10.0),
+            // A notch, making the original polygon concave
+            Pt2D::new(5.0, 5.0),
+            Pt2D::new(0.0, 10.0),
+        ];
+        assert_eq!(
+            vec![
+                Pt2D::new(0.0, 0.0),
+                Pt2D::new(10.0, 0.0),
+                Pt2D::new(10.0, 10.0),
+                Pt2D::new(0.0, 10.0),
+            ],
+            hull(pts)
+        );
+    }
+}
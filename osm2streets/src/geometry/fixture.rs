@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use geom::{Polygon, Pt2D};
+use serde::{Deserialize, Serialize};
+
+use super::{intersection_polygon_with_strategy, DeadEnd, InputRoad, Results};
+use crate::utils::{deserialize_btreemap, serialize_btreemap};
+use crate::{IntersectionGeometry, IntersectionID, IntersectionKind, RoadID};
+
+/// Everything `intersection_polygon` needs to calculate one intersection, standalone from the
+/// rest of the `StreetNetwork`. When the calculation fails, including in the middle of a
+/// transformation, this is saved, so the failure can be shared and replayed as a regression test.
+/// See `tests/geometry_fixtures`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeometryFixture {
+    pub intersection_id: IntersectionID,
+    pub intersection_kind: IntersectionKind,
+    /// Ordered clockwise
+    pub input_roads: Vec<InputRoad>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub trim_roads_for_merging: BTreeMap<(RoadID, bool), Pt2D>,
+    /// The shape of the intersection, if it's a dead-end
+    #[serde(default)]
+    pub dead_end: DeadEnd,
+    #[serde(default)]
+    pub strategy: IntersectionGeometry,
+    /// The `area:highway` polygon containing the intersection, if any
+    #[serde(default)]
+    pub mapped_area: Option<Polygon>,
+    /// The error produced when this was captured
+    pub error: String,
+}
+
+impl GeometryFixture {
+    /// Runs `intersection_polygon` again on the captured input
+    pub fn replay(&self) -> Result<Results> {
+        self.clone().calculate()
+    }
+
+    /// Like `replay`, but consumes the input instead of copying it
+    pub(crate) fn calculate(self) -> Result<Results> {
+        intersection_polygon_with_strategy(
+            self.intersection_id,
+            self.intersection_kind,
+            self.input_roads,
+            &self.trim_roads_for_merging,
+            self.dead_end,
+            self.strategy,
+            self.mapped_area.as_ref(),
+        )
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(input: &str) -> Result<Self> {
+        Ok(serde_json::from_str(input)?)
+    }
+}
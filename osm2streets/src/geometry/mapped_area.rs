@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use geom::{PolyLine, Polygon, Pt2D};
+
+use super::Results;
+use crate::{InputRoad, IntersectionID, RoadID};
+
+/// Finds the `area:highway` polygon mapping a junction, given the points where each of its roads
+/// ends. The area must contain all of them. Areas mapping the carriageway of one road often
+/// contain the junction too, but they extend along the road, so the smallest area is used.
+pub(crate) fn find_mapped_area<'a>(
+    areas: &'a [Polygon],
+    road_ends: &[Pt2D],
+) -> Option<&'a Polygon> {
+    if road_ends.is_empty() {
+        return None;
+    }
+    areas
+        .iter()
+        .filter(|area| road_ends.iter().all(|pt| area.contains_pt(*pt)))
+        .min_by(|a, b| a.area().total_cmp(&b.area()))
+}
+
+/// Uses an area mapped in OSM as the intersection polygon, trimming each road back to where it
+/// leaves that area.
+pub(crate) fn trim_to_area(
+    mut results: Results,
+    roads: BTreeMap<RoadID, InputRoad>,
+    area: &Polygon,
+) -> Result<Results> {
+    let outline = PolyLine::unchecked_new(area.get_outer_ring().points().clone());
+
+    for road in roads.into_values() {
+        match trim_to_outline(&road, results.intersection_id, &outline) {
+            Some(trimmed) => {
+                results.trimmed_center_pts.insert(road.id, trimmed);
+            }
+            // If the road never leaves the area, don't trim it at all
+            None => {
+                results.debug.push((
+                    road.center_line.middle(),
+                    format!("{} doesn't leave the mapped area", road.id),
+                ));
+                results
+                    .trimmed_center_pts
+                    .insert(road.id, road.center_line.clone());
+            }
+        }
+    }
+
+    results.intersection_polygon = area.clone();
+    Ok(results)
+}
+
+/// Trims the end of an untrimmed road at intersection `i` back to where it first crosses
+/// `outline`, going away from the intersection. Returns `None` if it never crosses.
+pub(crate) fn trim_to_outline(
+    road: &InputRoad,
+    i: IntersectionID,
+    outline: &PolyLine,
+) -> Option<PolyLine> {
+    // Start at the intersection, so the first hit is where the road leaves the area
+    let away = road.center_line_pointed_at(i).reversed();
+    let (pt, _) = away.intersection(outline)?;
+    let (dist, _) = away.dist_along_of_point(pt)?;
+    let trimmed = away.maybe_exact_slice(dist, away.length()).ok()?;
+    // Match the original orientation
+    if road.dst_i == i {
+        Some(trimmed.reversed())
+    } else {
+        Some(trimmed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{Distance, Ring};
+
+    use super::*;
+    use crate::geometry::TrimAlgorithm;
+
+    fn square(x1: f64, y1: f64, x2: f64, y2: f64) -> Polygon {
+        Ring::must_new(vec![
+            Pt2D::new(x1, y1),
+            Pt2D::new(x2, y1),
+            Pt2D::new(x2, y2),
+            Pt2D::new(x1, y2),
+            Pt2D::new(x1, y1),
+        ])
+        .into_polygon()
+    }
+
+    #[test]
+    fn test_find_mapped_area() {
+        let junction = square(40.0, 40.0, 60.0, 60.0);
+        // The carriageway of a road passing through the junction
+        let carriageway = square(0.0, 45.0, 100.0, 55.0);
+        let elsewhere = square(200.0, 200.0, 210.0, 210.0);
+        let areas = vec![carriageway, junction.clone(), elsewhere];
+
+        let center = Pt2D::new(50.0, 50.0);
+        assert_eq!(find_mapped_area(&areas, &[center, center]), Some(&junction));
+        // Every road has to end in the area
+        assert_eq!(
+            find_mapped_area(&areas, &[center, Pt2D::new(150.0, 150.0)]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_trim_to_area() {
+        let i = IntersectionID(1);
+        let road = |id: usize, src_i: usize, dst_i: usize, pts: Vec<Pt2D>| InputRoad {
+            id: RoadID(id),
+            src_i: IntersectionID(src_i),
+            dst_i: IntersectionID(dst_i),
+            center_line: PolyLine::must_new(pts),
+            total_width: Distance::meters(5.0),
+            highway_type: "residential".to_string(),
+        };
+        let mut roads = BTreeMap::new();
+        // One road ends at the intersection and the other starts there
+        roads.insert(
+            RoadID(0),
+            road(0, 0, 1, vec![Pt2D::new(0.0, 50.0), Pt2D::new(50.0, 50.0)]),
+        );
+        roads.insert(
+            RoadID(1),
+            road(1, 1, 2, vec![Pt2D::new(50.0, 50.0), Pt2D::new(50.0, 100.0)]),
+        );
+        let results = Results {
+            intersection_id: i,
+            intersection_polygon: Polygon::dummy(),
+            algorithm: TrimAlgorithm::MappedArea,
+            reason: String::new(),
+            trimmed_center_pts: BTreeMap::new(),
+            trim_starts: BTreeMap::new(),
+            trim_ends: BTreeMap::new(),
+            debug: Vec::new(),
+        };
+
+        let area = square(40.0, 40.0, 60.0, 60.0);
+        let results = trim_to_area(results, roads, &area).unwrap();
+        assert_eq!(results.intersection_polygon, area);
+
+        let trimmed = &results.trimmed_center_pts[&RoadID(0)];
+        assert_eq!(trimmed.first_pt(), Pt2D::new(0.0, 50.0));
+        assert_eq!(trimmed.last_pt(), Pt2D::new(40.0, 50.0));
+        let trimmed = &results.trimmed_center_pts[&RoadID(1)];
+        assert_eq!(trimmed.first_pt(), Pt2D::new(50.0, 60.0));
+        assert_eq!(trimmed.last_pt(), Pt2D::new(50.0, 100.0));
+    }
+}
@@ -8,24 +8,33 @@
 //!
 //! I wrote a novella about this: <https://a-b-street.github.io/docs/tech/map/geometry/index.html>
 
+mod buffer_union;
+mod convex_hull;
 mod degenerate;
+mod fixture;
 mod general_case;
+mod mapped_area;
 mod on_off_ramp;
 mod pretrimmed;
 mod terminus;
 
-use std::collections::BTreeMap;
+pub use self::fixture::GeometryFixture;
+pub(crate) use self::mapped_area::find_mapped_area;
+pub use self::terminus::DeadEnd;
+
+use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::Result;
 use geom::{Distance, PolyLine, Polygon, Pt2D, Ring};
+use serde::{Deserialize, Serialize};
 
 use crate::road::RoadEdge;
-use crate::{IntersectionID, IntersectionKind, RoadID, StopLine};
+use crate::{IntersectionGeometry, IntersectionID, IntersectionKind, RoadID, StopLine};
 
 // For anyone considering removing this indirection in the future: it's used to recalculate one or
 // two intersections at a time in A/B Street's edit mode. Within just this repo, it does seem
 // redundant.
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InputRoad {
     pub id: RoadID,
     pub src_i: IntersectionID,
@@ -62,31 +71,85 @@ impl InputRoad {
                 dir: crate::Direction::Forward,
                 width: self.total_width,
                 allowed_turns: Default::default(),
+                is_virtual: false,
+                shoulder_access: None,
+                max_speed: None,
+                bus_hours: None,
                 lane: None,
             }],
             // Mostly dummy values, except for what selfEdge::calculate needs
             osm_ids: Vec::new(),
-            highway_type: String::new(),
+            osm_start_node: None,
+            highway_type: crate::InternedString::new(""),
             name: None,
+            alt_names: Vec::new(),
             internal_junction_road: false,
             layer: 0,
+            structure: None,
+            lifecycle: crate::Lifecycle::Existing,
+            availability: Default::default(),
+            width_constraint: None,
+            freight: Default::default(),
             speed_limit: None,
             reference_line: PolyLine::dummy(),
-            reference_line_placement: osm2lanes::Placement::Transition,
+            reference_line_placement: crate::Placement::Transition,
+            placement_blend: [None, None],
             trim_start: Distance::ZERO,
             trim_end: Distance::ZERO,
+            trim_provenance: [None, None],
             turn_restrictions: Vec::new(),
+            turn_restriction_relations: BTreeMap::new(),
             complicated_turn_restrictions: Vec::new(),
+            parking_capacity: [None, None],
+            parking_exclusions: Vec::new(),
+            surface: None,
+            lane_surfaces: BTreeMap::new(),
+            path_attributes: BTreeMap::new(),
+            lane_changes: BTreeMap::new(),
+            lane_features: Vec::new(),
+            separately_mapped: Vec::new(),
+            steps: None,
+            service_lanes: BTreeSet::new(),
+            frontage_road: false,
+            parallel_to: BTreeSet::new(),
             stop_line_start: StopLine::dummy(),
             stop_line_end: StopLine::dummy(),
         }
     }
 }
 
+/// Which algorithm trimmed one end of a road
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrimAlgorithm {
+    MappedArea,
+    Terminus,
+    Degenerate,
+    Pretrimmed,
+    OnOffRamp,
+    GeneralCase,
+    /// The fallback for `IntersectionGeometry::BufferUnionFallback`
+    BufferUnion,
+    /// The intersection geometry couldn't be calculated, so the previous trim was kept
+    Failed,
+}
+
+/// Records how one end of a road was trimmed, to help debug geometry problems.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrimProvenance {
+    pub algorithm: TrimAlgorithm,
+    /// Why the algorithm was chosen, or the error if it failed
+    pub reason: String,
+    /// The trim distance calculated
+    pub trim: Distance,
+}
+
 #[derive(Clone)]
 pub struct Results {
     pub intersection_id: IntersectionID,
     pub intersection_polygon: Polygon,
+    /// Which algorithm produced these results, and why it was chosen
+    pub algorithm: TrimAlgorithm,
+    pub reason: String,
     /// The only transformation to `center_line` passed in must be to trim it (reducing the length)
     /// or to lengthen the first/last line. `trim_starts` and `trim_ends` are calculated from this,
     /// and the caller deliberately can't see `trimmed_center_pts`.
@@ -100,11 +163,35 @@ pub struct Results {
 /// Trims back all roads connected to the intersection, and generates a polygon for the
 /// intersection. The trimmed roads should meet this polygon at a right angle. The input is assumed
 /// to be untrimmed (based on the original reference geometry), and the roads must be ordered clockwise.
+/// To choose the algorithm or the shape of dead-ends, fill out a `GeometryFixture` and `replay` it.
 pub fn intersection_polygon(
     intersection_id: IntersectionID,
     intersection_kind: IntersectionKind,
     input_roads: Vec<InputRoad>,
     trim_roads_for_merging: &BTreeMap<(RoadID, bool), Pt2D>,
+) -> Result<Results> {
+    intersection_polygon_with_strategy(
+        intersection_id,
+        intersection_kind,
+        input_roads,
+        trim_roads_for_merging,
+        DeadEnd::Square,
+        IntersectionGeometry::CornerTrim,
+        None,
+    )
+}
+
+/// Like `intersection_polygon`, but with a choice of algorithm. `dead_end` is only used when
+/// there's one road. `mapped_area` is only used by `IntersectionGeometry::MappedArea`; without it,
+/// that falls back to `CornerTrim`.
+pub(crate) fn intersection_polygon_with_strategy(
+    intersection_id: IntersectionID,
+    intersection_kind: IntersectionKind,
+    input_roads: Vec<InputRoad>,
+    trim_roads_for_merging: &BTreeMap<(RoadID, bool), Pt2D>,
+    dead_end: DeadEnd,
+    strategy: IntersectionGeometry,
+    mapped_area: Option<&Polygon>,
 ) -> Result<Results> {
     // TODO Possibly take this as input in the first place
     let mut roads: BTreeMap<RoadID, InputRoad> = BTreeMap::new();
@@ -121,6 +208,8 @@ pub fn intersection_polygon(
         trimmed_center_pts: BTreeMap::new(),
         trim_starts: BTreeMap::new(),
         trim_ends: BTreeMap::new(),
+        algorithm: TrimAlgorithm::GeneralCase,
+        reason: String::new(),
     };
 
     // TODO Hack! Transformation::CollapseDegenerateIntersections triggers this, because we try to
@@ -132,25 +221,85 @@ pub fn intersection_polygon(
 
     let mut untrimmed_roads = roads.clone();
 
-    let mut results = if roads.len() == 1 {
+    let mapped_area = mapped_area.filter(|_| {
+        strategy == IntersectionGeometry::MappedArea
+            && intersection_kind != IntersectionKind::MapEdge
+    });
+    let (algorithm, reason) = if mapped_area.is_some() {
+        (TrimAlgorithm::MappedArea, "inside a mapped area:highway")
+    } else if roads.len() == 1 {
+        (TrimAlgorithm::Terminus, "only one road")
+    } else if roads.len() == 2 {
+        (TrimAlgorithm::Degenerate, "only two roads")
+    } else if !trim_roads_for_merging.is_empty() {
+        (
+            TrimAlgorithm::Pretrimmed,
+            "roads were trimmed before merging dual carriageways",
+        )
+    } else {
+        (TrimAlgorithm::GeneralCase, "no special case applies")
+    };
+    // Only the fallback needs to start over
+    let blank = (strategy == IntersectionGeometry::BufferUnionFallback).then(|| results.clone());
+    let attempt = if let Some(area) = mapped_area {
+        mapped_area::trim_to_area(results, roads, area)
+    } else if roads.len() == 1 {
         terminus::terminus(
             results,
             roads.into_values().next().unwrap(),
             intersection_kind,
+            dead_end,
         )
     } else if roads.len() == 2 {
         let mut iter = roads.into_values();
         degenerate::degenerate(results, iter.next().unwrap(), iter.next().unwrap())
     } else if !trim_roads_for_merging.is_empty() {
         pretrimmed::pretrimmed_geometry(results, roads, sorted_roads, trim_roads_for_merging)
-    } else if let Some(result) =
+    } else if let Some(mut result) =
         on_off_ramp::on_off_ramp(results.clone(), roads.clone(), &sorted_roads)
     {
+        result.algorithm = TrimAlgorithm::OnOffRamp;
+        result.reason = "a thin road merges into a thick one".to_string();
         Ok(result)
     } else {
         general_case::trim_to_corners(results, roads, sorted_roads)
+    }
+    .map(|mut results| {
+        if results.reason.is_empty() {
+            results.algorithm = algorithm;
+            results.reason = reason.to_string();
+        }
+        results
+    });
+
+    let mut results = if let Some(blank) = blank {
+        buffer_union::fall_back_if_broken(attempt, algorithm, blank, untrimmed_roads.clone())
+    } else {
+        attempt
     }?;
 
+    if strategy == IntersectionGeometry::ConvexHull {
+        results.intersection_polygon = convex_hull::convex_hull(&results.intersection_polygon)?;
+        // The hull covers more than the original polygon, so trim roads back to its edge
+        let outline = PolyLine::unchecked_new(
+            results
+                .intersection_polygon
+                .get_outer_ring()
+                .points()
+                .clone(),
+        );
+        for (r, pl) in &mut results.trimmed_center_pts {
+            if let Some(trimmed) =
+                mapped_area::trim_to_outline(&untrimmed_roads[r], intersection_id, &outline)
+            {
+                if trimmed.length() < pl.length() {
+                    *pl = trimmed;
+                }
+            }
+        }
+        results.reason.push_str(", then a convex hull");
+    }
+
     // We've filled out trimmed_center_pts, now calculate trim_starts and trim_ends
     for (r, pl) in &results.trimmed_center_pts {
         // Normally this'll be positive, indicating trim. If it's negative, the algorithm extended
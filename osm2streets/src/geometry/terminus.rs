@@ -1,40 +1,49 @@
 use anyhow::Result;
-use geom::{Distance, Ring};
+use geom::{Angle, Circle, Distance, PolyLine, Pt2D, Ring};
+use serde::{Deserialize, Serialize};
 
 use super::Results;
 use crate::{InputRoad, IntersectionKind};
 
-/// For dead-ends and map edges, just use a piece of the road as the intersection.
+/// How many points approximate the semicircle of a rounded dead-end
+const ROUNDED_CAP_STEPS: usize = 8;
+
+/// The shape of the intersection at the end of a dead-end road
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum DeadEnd {
+    /// Just use a piece of the road
+    #[default]
+    Square,
+    /// A piece of the road, plus a semicircle past the end
+    Rounded,
+    /// A circular bulb with this diameter, centered on the end of the road. It's never narrower
+    /// than the road.
+    TurningCircle(Distance),
+}
+
+/// For dead-ends, just use a piece of the road as the intersection, optionally rounded off. Map
+/// edges are handled separately.
 pub(crate) fn terminus(
     mut results: Results,
     road: InputRoad,
     kind: IntersectionKind,
+    dead_end: DeadEnd,
 ) -> Result<Results> {
     // Point at the intersection, to simplify logic below
     let mut center = road.center_line_pointed_at(results.intersection_id);
 
-    let intersection_len = if kind == IntersectionKind::MapEdge {
-        // Arbitrarily require the rest of the road to be at least this long, before trimming
-        let min_road_len = 3.0 * road.total_width;
-
-        // If the road is too short, extend it. Depending on the boundary, MapEdges can become way
-        // too small otherwise.
-        //
-        // 1) This kind of makes sense for a MapEdge, but is weird to do for a Terminus.
-        // 2) We might've trimmed the other side of this road already or not. If we haven't, we might
-        //    trim too much later and still wind up with something too short.
-        if center.length() < min_road_len {
-            center = center.extend_to_length(min_road_len);
-        }
+    if kind == IntersectionKind::MapEdge {
+        return map_edge(results, road, center);
+    }
+    if let DeadEnd::TurningCircle(diameter) = dead_end {
+        return turning_circle(results, road, center, diameter);
+    }
 
+    // Make the intersection roughly square if possible
+    let intersection_len = if center.length() > road.total_width + Distance::meters(1.0) {
         road.total_width
     } else {
-        // Make the intersection roughly square if possible
-        if center.length() > road.total_width + Distance::meters(1.0) {
-            road.total_width
-        } else {
-            0.4 * center.length()
-        }
+        0.4 * center.length()
     };
 
     // Before trimming, remember the left and right endpoint.
@@ -44,10 +53,21 @@ pub(crate) fn terminus(
         center.shift_right(road.half_width())?.last_pt(),
     ];
 
+    let untrimmed_end = center.last_pt();
+
     // Trim
     center = center.maybe_exact_slice(Distance::ZERO, center.length() - intersection_len)?;
 
     // Make the square polygon
+    if dead_end == DeadEnd::Rounded {
+        let arc = semicircle(
+            untrimmed_end,
+            endpts[0],
+            center.last_line().angle(),
+            road.half_width(),
+        );
+        endpts.splice(1..1, arc);
+    }
     endpts.push(center.shift_right(road.half_width())?.last_pt());
     endpts.push(center.shift_left(road.half_width())?.last_pt());
     endpts.push(endpts[0]);
@@ -62,3 +82,173 @@ pub(crate) fn terminus(
     results.trimmed_center_pts.insert(road.id, center);
     Ok(results)
 }
+
+/// The road continues past the map boundary, so don't trim it at all. The intersection is just a
+/// thin cap beyond the end of the road, marking where it leaves the map.
+fn map_edge(mut results: Results, road: InputRoad, center: PolyLine) -> Result<Results> {
+    let depth = Distance::meters(1.0);
+    let angle = center.last_line().angle();
+
+    let left = center.shift_left(road.half_width())?.last_pt();
+    let right = center.shift_right(road.half_width())?.last_pt();
+    results.intersection_polygon = Ring::deduping_new(vec![
+        left,
+        right,
+        right.project_away(depth, angle),
+        left.project_away(depth, angle),
+        left,
+    ])?
+    .into_polygon();
+
+    // Fix orientation if needed
+    let center = if road.src_i == results.intersection_id {
+        center.reversed()
+    } else {
+        center
+    };
+    results.trimmed_center_pts.insert(road.id, center);
+    Ok(results)
+}
+
+/// The intersection is a circle around the end of the road, and the road is trimmed back to
+/// exactly where its edges meet the circle.
+fn turning_circle(
+    mut results: Results,
+    road: InputRoad,
+    center: PolyLine,
+    diameter: Distance,
+) -> Result<Results> {
+    // The road's corners must land on the circle
+    let mut radius = diameter / 2.0;
+    if radius < road.half_width() * 1.5 {
+        radius = road.half_width() * 1.5;
+    }
+    let trim = Distance::meters(
+        (radius.inner_meters().powi(2) - road.half_width().inner_meters().powi(2)).sqrt(),
+    );
+    if center.length() <= trim + Distance::meters(1.0) {
+        // There's no room for a bulb
+        return terminus(results, road, IntersectionKind::Terminus, DeadEnd::Rounded);
+    }
+
+    results.intersection_polygon = Circle::new(center.last_pt(), radius).to_polygon();
+    let center = center.maybe_exact_slice(Distance::ZERO, center.length() - trim)?;
+
+    // Fix orientation if needed
+    let center = if road.src_i == results.intersection_id {
+        center.reversed()
+    } else {
+        center
+    };
+    results.trimmed_center_pts.insert(road.id, center);
+    Ok(results)
+}
+
+/// Points along a semicircle around `end`, starting just after `left` and ending just before the
+/// point opposite it, bulging out in the direction of `angle`.
+fn semicircle(end: Pt2D, left: Pt2D, angle: Angle, radius: Distance) -> Vec<Pt2D> {
+    let start = end.angle_to(left);
+    // Sweep through the direction the road is pointing
+    let sign = if start.rotate_degs(90.0).approx_eq(angle, 1.0) {
+        1.0
+    } else {
+        -1.0
+    };
+    (1..ROUNDED_CAP_STEPS)
+        .map(|step| {
+            let degrees = sign * 180.0 * (step as f64) / (ROUNDED_CAP_STEPS as f64);
+            end.project_away(radius, start.rotate_degs(degrees))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use geom::Polygon;
+
+    use super::*;
+    use crate::{IntersectionID, RoadID, TrimAlgorithm};
+
+    /// Calculates the dead-end at the east end of a road 10m wide
+    fn dead_end(length: f64, dead_end: DeadEnd) -> Results {
+        let results = Results {
+            intersection_id: IntersectionID(1),
+            intersection_polygon: Polygon::dummy(),
+            algorithm: TrimAlgorithm::Terminus,
+            reason: String::new(),
+            trimmed_center_pts: BTreeMap::new(),
+            trim_starts: BTreeMap::new(),
+            trim_ends: BTreeMap::new(),
+            debug: Vec::new(),
+        };
+        let road = InputRoad {
+            id: RoadID(0),
+            src_i: IntersectionID(0),
+            dst_i: IntersectionID(1),
+            center_line: PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(length, 0.0)]),
+            total_width: Distance::meters(10.0),
+            highway_type: "residential".to_string(),
+        };
+        terminus(results, road, IntersectionKind::Terminus, dead_end).unwrap()
+    }
+
+    fn trimmed_length(results: &Results) -> f64 {
+        results.trimmed_center_pts[&RoadID(0)]
+            .length()
+            .inner_meters()
+    }
+
+    #[test]
+    fn test_square_and_rounded() {
+        let square = dead_end(100.0, DeadEnd::Square);
+        assert!((trimmed_length(&square) - 90.0).abs() < 0.01);
+        assert!(square
+            .intersection_polygon
+            .contains_pt(Pt2D::new(95.0, 0.0)));
+        assert!(!square
+            .intersection_polygon
+            .contains_pt(Pt2D::new(103.0, 0.0)));
+
+        // The road is trimmed the same, but the cap bulges out by half the road's width
+        let rounded = dead_end(100.0, DeadEnd::Rounded);
+        assert!((trimmed_length(&rounded) - 90.0).abs() < 0.01);
+        assert!(rounded
+            .intersection_polygon
+            .contains_pt(Pt2D::new(95.0, 0.0)));
+        assert!(rounded
+            .intersection_polygon
+            .contains_pt(Pt2D::new(103.0, 0.0)));
+        assert!(!rounded
+            .intersection_polygon
+            .contains_pt(Pt2D::new(106.0, 0.0)));
+        // The corners past the end are cut off
+        assert!(!rounded
+            .intersection_polygon
+            .contains_pt(Pt2D::new(104.5, 4.5)));
+    }
+
+    #[test]
+    fn test_turning_circle() {
+        // The road's corners meet a circle with a 10m radius
+        let results = dead_end(100.0, DeadEnd::TurningCircle(Distance::meters(20.0)));
+        let trim = (10.0_f64.powi(2) - 5.0_f64.powi(2)).sqrt();
+        assert!((trimmed_length(&results) - (100.0 - trim)).abs() < 0.01);
+        for pt in [Pt2D::new(108.0, 0.0), Pt2D::new(100.0, 8.0)] {
+            assert!(results.intersection_polygon.contains_pt(pt));
+        }
+        assert!(!results
+            .intersection_polygon
+            .contains_pt(Pt2D::new(111.0, 0.0)));
+
+        // A small circle still covers the road
+        let results = dead_end(100.0, DeadEnd::TurningCircle(Distance::meters(2.0)));
+        let trim = (7.5_f64.powi(2) - 5.0_f64.powi(2)).sqrt();
+        assert!((trimmed_length(&results) - (100.0 - trim)).abs() < 0.01);
+
+        // Without room for a bulb, the end is rounded instead
+        let results = dead_end(5.0, DeadEnd::TurningCircle(Distance::meters(20.0)));
+        assert!((trimmed_length(&results) - 3.0).abs() < 0.01);
+    }
+}
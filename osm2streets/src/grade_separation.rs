@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, PolyLine, Pt2D};
+
+use crate::{Road, RoadID, StreetNetwork, Structure};
+
+/// A place where two roads cross without connecting, like a bridge over another road.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GradeSeparation {
+    pub pt: Pt2D,
+    /// The road on top. When the layers are equal, a bridge is on top of anything else, and
+    /// anything else is on top of a tunnel.
+    pub upper: RoadID,
+    pub lower: RoadID,
+    /// The difference between the `layer` of both roads. Zero with no bridge or tunnel involved
+    /// usually means a `layer` tag is missing, or the roads should really connect.
+    pub layer_delta: isize,
+    /// The height available under the upper road, from a `maxheight:physical` or `maxheight`
+    /// tag on the lower road
+    pub clearance: Option<Distance>,
+}
+
+impl GradeSeparation {
+    /// Both roads are at the same layer and neither is a bridge or tunnel, so this crossing is
+    /// probably a tagging mistake.
+    pub fn is_suspicious(&self, streets: &StreetNetwork) -> bool {
+        self.layer_delta == 0
+            && streets.roads[&self.upper].structure.is_none()
+            && streets.roads[&self.lower].structure.is_none()
+    }
+}
+
+impl StreetNetwork {
+    /// Finds everywhere two roads cross without sharing an intersection. Two roads crossing more
+    /// than once produce one result per crossing.
+    pub fn find_grade_separations(&self) -> Vec<GradeSeparation> {
+        let index = self.spatial_index();
+        let mut results = Vec::new();
+        for road1 in self.roads.values() {
+            for r2 in index.roads(&road1.center_line.get_bounds()) {
+                // Only look at each pair once
+                if r2 <= road1.id {
+                    continue;
+                }
+                let road2 = &self.roads[&r2];
+                if road1
+                    .endpoints()
+                    .iter()
+                    .any(|i| road2.endpoints().contains(i))
+                {
+                    continue;
+                }
+                let pts = crossings(&road1.center_line, &road2.center_line);
+                if pts.is_empty() {
+                    continue;
+                }
+
+                let rank = |structure| match structure {
+                    Some(Structure::Bridge) => 1,
+                    None => 0,
+                    Some(Structure::Tunnel) => -1,
+                };
+                let (upper, lower) = if (road1.layer, rank(road1.structure))
+                    >= (road2.layer, rank(road2.structure))
+                {
+                    (road1, road2)
+                } else {
+                    (road2, road1)
+                };
+                let clearance = self.clearance(lower);
+                for pt in pts {
+                    results.push(GradeSeparation {
+                        pt,
+                        upper: upper.id,
+                        lower: lower.id,
+                        layer_delta: upper.layer - lower.layer,
+                        clearance,
+                    });
+                }
+            }
+        }
+        results
+    }
+
+    /// The `maxheight:physical` or `maxheight` of any OSM way a road came from
+    fn clearance(&self, road: &Road) -> Option<Distance> {
+        let index = self.osm_index()?;
+        road.osm_ids.iter().find_map(|id| {
+            let tags = &index.way(*id)?.tags;
+            tags.get("maxheight:physical")
+                .or_else(|| tags.get("maxheight"))
+                .and_then(|x| crate::units::parse_distance(x))
+        })
+    }
+}
+
+/// Every point where two lines cross
+fn crossings(pl1: &PolyLine, pl2: &PolyLine) -> Vec<Pt2D> {
+    let mut pts: Vec<Pt2D> = Vec::new();
+    for line1 in pl1.lines() {
+        for line2 in pl2.lines() {
+            if let Some(pt) = line1.intersection(&line2) {
+                // A crossing exactly at a vertex is found on both segments touching it
+                if !pts
+                    .iter()
+                    .any(|other| other.dist_to(pt) < Distance::meters(0.1))
+                {
+                    pts.push(pt);
+                }
+            }
+        }
+    }
+    pts
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use abstutil::Tags;
+
+    use super::*;
+    use crate::osm_index::Way;
+    use crate::test_utils::add_lone_road;
+    use crate::OsmIndex;
+
+    #[test]
+    fn test_find_grade_separations() {
+        let mut streets = StreetNetwork::blank();
+        // A road winding under a bridge twice
+        let lower = add_lone_road(
+            &mut streets,
+            vec![
+                Pt2D::new(0.0, 0.0),
+                Pt2D::new(100.0, 100.0),
+                Pt2D::new(200.0, 0.0),
+            ],
+            &["highway=residential"],
+        );
+        let upper = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 50.0), Pt2D::new(200.0, 50.0)],
+            &["highway=residential"],
+        );
+        {
+            let road = streets.roads.get_mut(&upper).unwrap();
+            road.layer = 1;
+            road.structure = Some(Structure::Bridge);
+        }
+        let way = streets.roads[&lower].osm_ids[0];
+        let mut tags = Tags::empty();
+        tags.insert("maxheight", "4.5");
+        streets.set_osm_index(OsmIndex::new(
+            BTreeMap::new(),
+            BTreeMap::from([(
+                way,
+                Way {
+                    nodes: Vec::new(),
+                    pts: Vec::new(),
+                    tags,
+                    version: None,
+                },
+            )]),
+            BTreeMap::new(),
+        ));
+
+        let results = streets.find_grade_separations();
+        assert_eq!(results.len(), 2);
+        for result in &results {
+            assert_eq!(result.upper, upper);
+            assert_eq!(result.lower, lower);
+            assert_eq!(result.layer_delta, 1);
+            assert_eq!(result.clearance, Some(Distance::meters(4.5)));
+            assert!(!result.is_suspicious(&streets));
+        }
+        assert!(results[0].pt.dist_to(results[1].pt) > Distance::meters(50.0));
+    }
+}
@@ -0,0 +1,281 @@
+//! Matches public transit stops from a GTFS feed to the road network.
+
+use anyhow::Result;
+use geojson::Feature;
+use geom::{Distance, LonLat};
+
+use crate::render::serialize_features;
+use crate::{LaneID, LaneType, Road, RoadID, SideOfRoad, StreetNetwork};
+
+/// Stops further than this from any road are left unmatched
+const MAX_SNAP_DISTANCE: Distance = Distance::const_meters(30.0);
+
+/// One stop from a GTFS `stops.txt`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GtfsStop {
+    pub stop_id: String,
+    pub stop_name: String,
+    pub pos: LonLat,
+}
+
+/// Where a GTFS stop is along the road network.
+#[derive(Clone, Debug)]
+pub struct StopMatch {
+    pub stop: GtfsStop,
+    /// None if the stop isn't near any road
+    pub road: Option<RoadID>,
+    pub side: Option<SideOfRoad>,
+    /// The lane closest to the stop that buses can use, on its side of the road. If there's none,
+    /// the closest driving lane.
+    pub lane: Option<LaneID>,
+    /// Measured along the road's `center_line`
+    pub dist_along: Distance,
+    /// How far the stop is from the road's `center_line`
+    pub dist_away: Distance,
+    /// False if the road has no lanes that buses can use, either because there are only driving
+    /// lanes and the road is tagged like `bus=no` or `psv=no`, or because there are no driving
+    /// or bus lanes at all. These stops are likely mismatched or mapped on the wrong road.
+    pub bus_access: bool,
+}
+
+/// Parses the stops (not stations or entrances) from the contents of a GTFS `stops.txt` file.
+pub fn parse_gtfs_stops(contents: &str) -> Result<Vec<GtfsStop>> {
+    let mut lines = contents.lines();
+    let header = split_csv_line(lines.next().unwrap_or(""));
+    let column = |name: &str| -> Result<usize> {
+        header
+            .iter()
+            .position(|x| x.trim_start_matches('\u{feff}') == name)
+            .ok_or_else(|| anyhow!("stops.txt is missing the {name} column"))
+    };
+    let stop_id = column("stop_id")?;
+    let stop_lat = column("stop_lat")?;
+    let stop_lon = column("stop_lon")?;
+    let stop_name = column("stop_name").ok();
+    let location_type = column("location_type").ok();
+
+    let mut stops = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = split_csv_line(line);
+        let get = |idx: usize| row.get(idx).map(|x| x.as_str()).unwrap_or("");
+        if let Some(idx) = location_type {
+            if !matches!(get(idx), "" | "0") {
+                continue;
+            }
+        }
+        let (Ok(lon), Ok(lat)) = (get(stop_lon).parse::<f64>(), get(stop_lat).parse::<f64>())
+        else {
+            bail!("Stop {} has a bad position", get(stop_id));
+        };
+        stops.push(GtfsStop {
+            stop_id: get(stop_id).to_string(),
+            stop_name: stop_name.map(get).unwrap_or("").to_string(),
+            pos: LonLat::new(lon, lat),
+        });
+    }
+    Ok(stops)
+}
+
+impl StreetNetwork {
+    /// Snaps each stop to the closest road that vehicles use, and the lane on that side.
+    pub fn match_gtfs_stops(&self, stops: Vec<GtfsStop>) -> Vec<StopMatch> {
+        stops
+            .into_iter()
+            .map(|stop| {
+                let pt = stop.pos.to_pt(&self.gps_bounds);
+                let mut result = StopMatch {
+                    stop,
+                    road: None,
+                    side: None,
+                    lane: None,
+                    dist_along: Distance::ZERO,
+                    dist_away: Distance::ZERO,
+                    bus_access: false,
+                };
+
+                let mut best: Option<(RoadID, Distance, Distance)> = None;
+                for road in self.roads.values() {
+                    if road.is_footway() || road.is_light_rail() {
+                        continue;
+                    }
+                    let snapped = road.center_line.project_pt(pt);
+                    let dist_away = snapped.dist_to(pt);
+                    if dist_away > MAX_SNAP_DISTANCE
+                        || best.map(|(_, _, d)| dist_away >= d).unwrap_or(false)
+                    {
+                        continue;
+                    }
+                    if let Some((dist, _)) = road.center_line.dist_along_of_point(snapped) {
+                        best = Some((road.id, dist, dist_away));
+                    }
+                }
+                let Some((r, dist_along, dist_away)) = best else {
+                    return result;
+                };
+
+                let road = &self.roads[&r];
+                let side = match (
+                    road.center_line.shift_left(Distance::meters(1.0)),
+                    road.center_line.shift_right(Distance::meters(1.0)),
+                ) {
+                    (Ok(left), Ok(right)) => {
+                        if left.project_pt(pt).dist_to(pt) < right.project_pt(pt).dist_to(pt) {
+                            SideOfRoad::Left
+                        } else {
+                            SideOfRoad::Right
+                        }
+                    }
+                    _ => SideOfRoad::Right,
+                };
+                let buses_allowed = self.buses_allowed(road);
+                let bus_can_use = |lt: LaneType| {
+                    lt == LaneType::Bus || (lt == LaneType::Driving && buses_allowed)
+                };
+                let mut indices: Vec<usize> = (0..road.lane_specs_ltr.len()).collect();
+                if side == SideOfRoad::Right {
+                    indices.reverse();
+                }
+                let bus_lane = indices
+                    .iter()
+                    .find(|idx| bus_can_use(road.lane_specs_ltr[**idx].lt))
+                    .cloned();
+                let lane = bus_lane
+                    .or_else(|| {
+                        indices
+                            .into_iter()
+                            .find(|idx| road.lane_specs_ltr[*idx].lt == LaneType::Driving)
+                    })
+                    .map(|index| LaneID { road: r, index });
+
+                result.road = Some(r);
+                result.side = Some(side);
+                result.lane = lane;
+                result.dist_along = dist_along;
+                result.dist_away = dist_away;
+                result.bus_access = bus_lane.is_some();
+                result
+            })
+            .collect()
+    }
+
+    /// Checks the tags of the road's OSM way for access restrictions on buses. If the OSM objects
+    /// weren't kept, assume buses are allowed.
+    fn buses_allowed(&self, road: &Road) -> bool {
+        let Some(way) = road
+            .osm_ids
+            .first()
+            .and_then(|id| self.osm_index().and_then(|index| index.way(*id)))
+        else {
+            return true;
+        };
+        let tags = &way.tags;
+        for key in ["bus", "psv"] {
+            if tags.is_any(key, vec!["yes", "designated", "permissive"]) {
+                return true;
+            }
+            if tags.is_any(key, vec!["no", "private"]) {
+                return false;
+            }
+        }
+        !tags.is_any("access", vec!["no", "private"])
+    }
+
+    /// Describes matched stops as CSV, with one row per stop.
+    pub fn gtfs_matches_to_csv(&self, matches: &[StopMatch]) -> String {
+        let mut out = String::from(
+            "stop_id,stop_name,lon,lat,road,lane,side,dist_along_meters,dist_away_meters,bus_access\n",
+        );
+        for m in matches {
+            out.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{}\n",
+                csv_escape(&m.stop.stop_id),
+                csv_escape(&m.stop.stop_name),
+                m.stop.pos.x(),
+                m.stop.pos.y(),
+                m.road.map(|r| r.0.to_string()).unwrap_or_default(),
+                m.lane.map(|l| l.index.to_string()).unwrap_or_default(),
+                m.side.map(|s| format!("{s:?}")).unwrap_or_default(),
+                format!("{:.1}", m.dist_along.inner_meters()),
+                format!("{:.1}", m.dist_away.inner_meters()),
+                m.bus_access,
+            ));
+        }
+        out
+    }
+
+    /// Shows each stop as a point, with properties describing the match.
+    pub fn gtfs_matches_to_geojson(&self, matches: &[StopMatch]) -> Result<String> {
+        let mut features = Vec::new();
+        for m in matches {
+            let mut f = Feature::from(
+                m.stop
+                    .pos
+                    .to_pt(&self.gps_bounds)
+                    .to_geojson(Some(&self.gps_bounds)),
+            );
+            f.set_property("stop_id", m.stop.stop_id.clone());
+            f.set_property("stop_name", m.stop.stop_name.clone());
+            f.set_property("road", m.road.map(|r| r.0));
+            f.set_property("lane", m.lane.map(|l| l.index));
+            f.set_property("side", m.side.map(|s| format!("{s:?}")));
+            f.set_property("dist_along_meters", m.dist_along.inner_meters());
+            f.set_property("dist_away_meters", m.dist_away.inner_meters());
+            f.set_property("bus_access", m.bus_access);
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
+
+/// Splits one line of CSV, handling quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => {
+                quoted = !quoted;
+            }
+            ',' if !quoted => {
+                fields.push(std::mem::take(&mut current));
+            }
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains(',') || value.contains('"') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(
+            split_csv_line("1,\"Main St, at 5th\",47.6,-122.3\r"),
+            vec!["1", "Main St, at 5th", "47.6", "-122.3"]
+        );
+        assert_eq!(
+            split_csv_line("2,\"The \"\"Loop\"\"\",,"),
+            vec!["2", "The \"Loop\"", "", ""]
+        );
+    }
+}
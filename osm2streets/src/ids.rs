@@ -2,10 +2,8 @@ use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use osm2lanes::LaneSpec;
-
 use crate::utils::{deserialize_usize, serialize_usize};
-use crate::{Road, StreetNetwork};
+use crate::{osm, Direction, LaneSpec, LaneType, Road, StreetNetwork};
 
 /// Opaque and non-contiguous
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -56,6 +54,67 @@ impl fmt::Display for LaneID {
     }
 }
 
+/// Identifies a lane in a way that survives re-importing, unlike `LaneID`. Editing tags that
+/// don't affect the lane, like adding a bike lane elsewhere on the road, keeps the same key.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LaneKey {
+    /// The first OSM way making up the road
+    pub way: osm::WayID,
+    /// The OSM node where the road began when it was split from the way, distinguishing the
+    /// pieces. None if the road began at the map boundary. See `Road::osm_start_node`.
+    pub start_node: Option<osm::NodeID>,
+    pub dir: Direction,
+    pub lt: LaneType,
+    /// Counts lanes with the same type and direction, starting from the left of traffic moving in
+    /// that direction
+    pub ordinal: usize,
+}
+
+impl fmt::Display for LaneKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/", self.way.0)?;
+        if let Some(node) = self.start_node {
+            write!(f, "{}", node.0)?;
+        }
+        write!(f, "/{:?}/{:?}/{}", self.dir, self.lt, self.ordinal)
+    }
+}
+
+impl StreetNetwork {
+    /// None if the road doesn't come from OSM.
+    pub fn lane_key(&self, id: LaneID) -> Option<LaneKey> {
+        let road = &self.roads[&id.road];
+        let lane = &road.lane_specs_ltr[id.index];
+        Some(LaneKey {
+            way: *road.osm_ids.first()?,
+            start_node: road.osm_start_node,
+            dir: lane.dir,
+            lt: lane.lt,
+            ordinal: lane_ordinal(&road.lane_specs_ltr, id.index),
+        })
+    }
+
+    /// Finds the lane currently matching a key, if it still exists.
+    pub fn find_lane_by_key(&self, key: &LaneKey) -> Option<LaneID> {
+        self.roads
+            .values()
+            .filter(|r| r.osm_ids.first() == Some(&key.way))
+            .flat_map(|r| (0..r.lane_specs_ltr.len()).map(|index| LaneID { road: r.id, index }))
+            .find(|id| self.lane_key(*id).as_ref() == Some(key))
+    }
+}
+
+fn lane_ordinal(lanes_ltr: &[LaneSpec], idx: usize) -> usize {
+    let lane = &lanes_ltr[idx];
+    let same = |l: &&LaneSpec| l.lt == lane.lt && l.dir == lane.dir;
+    // Backward lanes are listed from the right of their traffic
+    if lane.dir == Direction::Forward {
+        lanes_ltr[0..idx].iter().filter(same).count()
+    } else {
+        lanes_ltr[idx + 1..].iter().filter(same).count()
+    }
+}
+
 /// It's sometimes useful to track both a road's ID and endpoints together. Use this sparingly.
 #[derive(Clone)]
 pub struct RoadWithEndpoints {
@@ -148,3 +207,18 @@ impl RoadSideID {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lane_ordinal() {
+        let lanes = LaneSpec::create_for_test("spddddbs", "vvvv^^^^");
+        let ordinals: Vec<usize> = (0..lanes.len())
+            .map(|idx| lane_ordinal(&lanes, idx))
+            .collect();
+        // Backward lanes count from the middle of the road out, and so do forward ones here
+        assert_eq!(ordinals, vec![0, 0, 1, 0, 0, 1, 0, 0]);
+    }
+}
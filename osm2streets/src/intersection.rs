@@ -1,12 +1,15 @@
 use std::collections::BTreeMap;
 
-use geom::{Circle, Distance, Polygon, Pt2D};
+use abstutil::Tags;
+use geom::{Angle, Circle, Distance, Polygon, Pt2D};
 use serde::{Deserialize, Serialize};
 
 use osm2lanes::osm;
 
 use crate::utils::{deserialize_btreemap, serialize_btreemap};
-use crate::{DrivingSide, IntersectionID, RoadID, RoadSideID, SideOfRoad, StreetNetwork};
+use crate::{
+    DrivingSide, IntersectionID, RoadID, RoadSideID, SideOfRoad, StreetNetwork, TurnDirection,
+};
 use TrafficConflict::*;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -16,6 +19,10 @@ pub struct Intersection {
     /// nodes (when an out-of-bounds intersection connected to multiple roads is clipped). One
     /// intersection may have multiple OSM nodes (when the intersection is consolidated).
     pub osm_ids: Vec<osm::NodeID>,
+    /// The OSM tags of all `osm_ids`. When nodes disagree about a tag, the values are joined with
+    /// `;`.
+    #[serde(default = "Tags::empty")]
+    pub osm_tags: Tags,
 
     /// This will be a placeholder circle until `update_geometry` runs or if errors occur.
     ///
@@ -27,9 +34,18 @@ pub struct Intersection {
     /// All roads connected to this intersection. They may be incoming or outgoing relative to this
     /// intersection. They're ordered clockwise around the intersection.
     pub roads: Vec<RoadID>,
+    /// Sorted by the road entering the intersection, then the road leaving, so the order doesn't
+    /// depend on how the roads happen to be ordered around the intersection
     pub movements: Vec<Movement>,
 
     pub crossing: Option<Crossing>,
+    /// Signs and signals controlling traffic through this intersection
+    #[serde(default)]
+    pub traffic_control_devices: Vec<TrafficControlDevice>,
+    /// The diameter of the turning circle at this dead-end, if one is mapped. See
+    /// <https://wiki.openstreetmap.org/wiki/Tag:highway%3Dturning_circle>.
+    #[serde(default)]
+    pub turning_circle: Option<Distance>,
 
     // true if src_i matches this intersection (or the deleted/consolidated one, whatever)
     // TODO Store start/end trim distance on _every_ road
@@ -90,6 +106,25 @@ pub enum IntersectionControl {
     Construction,
 }
 
+/// A sign, signal, or crossing controlling traffic through an intersection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrafficControlDevice {
+    pub kind: TrafficControlKind,
+    /// Where the device is mapped. This may be at the intersection or along one of its roads.
+    pub pt: Pt2D,
+    pub osm_node: Option<osm::NodeID>,
+    /// The incoming roads that the device applies to. Empty means every approach.
+    pub approaches: Vec<RoadID>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum TrafficControlKind {
+    TrafficSignals,
+    Stop,
+    GiveWay,
+    Crossing,
+}
+
 /// When an Intersection is a pedestrian (and/or bike) crossing, represents details.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Crossing {
@@ -112,11 +147,72 @@ pub enum CrossingKind {
 /// The path that some group of adjacent lanes of traffic can take through an intersection.
 pub type Movement = (RoadID, RoadID);
 
+/// A road crossing the map boundary. Traffic on it continues off-map, so this is useful for
+/// stitching together adjacent imports or loading travel demand at the boundary.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapEdgeStub {
+    /// The `MapEdge` intersection
+    pub intersection: IntersectionID,
+    pub road: RoadID,
+    /// Where the road meets the boundary
+    pub point: Pt2D,
+    /// Pointing from the road towards the boundary, off the map
+    pub angle: Angle,
+    /// Can traffic enter the map here?
+    pub inbound: bool,
+    /// Can traffic leave the map here?
+    pub outbound: bool,
+}
+
+/// One road around an intersection, with the angles between it and its neighbours
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClockwiseRoad {
+    pub road: RoadID,
+    /// The compass bearing in degrees of the road leaving the intersection, measured at its end
+    /// there. 0 is north, increasing clockwise.
+    pub bearing: f64,
+    /// The angle in degrees from this road clockwise to the next road around the intersection.
+    /// For the last road, that's the first.
+    pub gap_to_next: f64,
+}
+
 impl Intersection {
     pub fn is_map_edge(&self) -> bool {
         self.kind == IntersectionKind::MapEdge
     }
 
+    /// The name of the junction, from `junction:name`, or `name` on a node tagged `junction=*`.
+    /// Other named nodes, like bus stops, don't name the junction.
+    pub fn name(&self) -> Option<&String> {
+        self.osm_tags.get("junction:name").or_else(|| {
+            if self.osm_tags.contains_key("junction") {
+                self.osm_tags.get("name")
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Adds the tags of another OSM node that's part of this intersection. Values not already
+    /// present are appended, separated by `;`.
+    pub fn merge_osm_tags(&mut self, tags: &Tags) {
+        for (key, value) in tags.inner() {
+            let merged = match self.osm_tags.get(key) {
+                Some(existing) => {
+                    let mut values: Vec<&str> = existing.split(';').collect();
+                    for x in value.split(';') {
+                        if !values.contains(&x) {
+                            values.push(x);
+                        }
+                    }
+                    values.join(";")
+                }
+                None => value.clone(),
+            };
+            self.osm_tags.insert(key, merged);
+        }
+    }
+
     pub fn describe(&self) -> String {
         let osm_ids = self
             .osm_ids
@@ -204,6 +300,7 @@ impl StreetNetwork {
             Intersection {
                 id,
                 osm_ids,
+                osm_tags: Tags::empty(),
                 // Initially make a small circle around the original point
                 polygon: Circle::new(point, Distance::meters(3.0)).to_polygon(),
                 kind: t,
@@ -212,9 +309,12 @@ impl StreetNetwork {
                 roads: Vec::new(),
                 movements: Vec::new(),
                 crossing: None,
+                traffic_control_devices: Vec::new(),
+                turning_circle: None,
                 trim_roads_for_merging: BTreeMap::new(),
             },
         );
+        self.geometry_changed();
         id
     }
 
@@ -222,10 +322,49 @@ impl StreetNetwork {
     //
     // TODO This doesn't handle trim_roads_for_merging
     pub fn sort_roads(&mut self, i: IntersectionID) {
-        let intersection = self.intersections.get_mut(&i).unwrap();
-        if intersection.roads.len() < 2 {
+        if self.intersections[&i].roads.len() < 2 {
             return; // Already sorted.
         }
+        let roads = self.clockwise_roads(i);
+        self.intersections.get_mut(&i).unwrap().roads = roads;
+    }
+
+    /// Describes the roads of an intersection in their clockwise order, with the direction each
+    /// one leaves in and the angular gaps between them.
+    pub fn clockwise_road_angles(&self, i: IntersectionID) -> Vec<ClockwiseRoad> {
+        let bearings: Vec<f64> = self.intersections[&i]
+            .roads
+            .iter()
+            .map(|r| {
+                let road = &self.roads[r];
+                let angle = if road.src_i == i {
+                    road.center_line.first_line().angle()
+                } else {
+                    road.center_line.reversed().first_line().angle()
+                };
+                compass_bearing(angle)
+            })
+            .collect();
+        self.intersections[&i]
+            .roads
+            .iter()
+            .zip(bearings.iter())
+            .zip(clockwise_gaps(&bearings))
+            .map(|((road, bearing), gap_to_next)| ClockwiseRoad {
+                road: *road,
+                bearing: *bearing,
+                gap_to_next,
+            })
+            .collect()
+    }
+
+    /// Calculates the clockwise order of an intersection's roads from their geometry. This
+    /// should match `Intersection::roads`, unless roads were edited without calling `sort_roads`.
+    pub fn clockwise_roads(&self, i: IntersectionID) -> Vec<RoadID> {
+        let intersection = &self.intersections[&i];
+        if intersection.roads.len() < 2 {
+            return intersection.roads.clone();
+        }
 
         // (ID, polyline pointing to the intersection, sorting point that's filled out later)
         let mut road_centers = Vec::new();
@@ -272,7 +411,7 @@ impl StreetNetwork {
                 .normalized_degrees() as i64
         });
 
-        intersection.roads = road_centers.into_iter().map(|(r, _, _)| r).collect();
+        road_centers.into_iter().map(|(r, _, _)| r).collect()
     }
 
     /// Updates an intersection's derived properties -- geometry (and attached roads) and
@@ -283,6 +422,8 @@ impl StreetNetwork {
     }
 
     /// The kind and movements of a `MapEdge` are handled independently, so this method skips them.
+    /// A `MapEdge` never has movements; use `map_edge_stubs` to find where traffic can enter or
+    /// leave the map.
     pub(crate) fn update_movements(&mut self, i: IntersectionID) {
         if self.intersections[&i].kind == IntersectionKind::MapEdge {
             return;
@@ -294,6 +435,34 @@ impl StreetNetwork {
         intersection.kind = kind;
     }
 
+    /// Lists every road that continues past the map boundary.
+    pub fn map_edge_stubs(&self) -> Vec<MapEdgeStub> {
+        let mut stubs = Vec::new();
+        for i in self.intersections.values() {
+            if !i.is_map_edge() {
+                continue;
+            }
+            for road in self.roads_per_intersection(i.id) {
+                // Point at the boundary
+                let pl = if road.dst_i == i.id {
+                    road.center_line.clone()
+                } else {
+                    road.center_line.reversed()
+                };
+                stubs.push(MapEdgeStub {
+                    intersection: i.id,
+                    road: road.id,
+                    point: pl.last_pt(),
+                    angle: pl.last_line().angle(),
+                    inbound: road.can_drive_out_of_end(road.other_side(i.id))
+                        || !road.is_driveable(),
+                    outbound: road.can_drive_out_of_end(i.id) || !road.is_driveable(),
+                });
+            }
+        }
+        stubs
+    }
+
     fn calculate_movements_and_kind(&self, i: IntersectionID) -> (Vec<Movement>, IntersectionKind) {
         let roads: Vec<_> = self
             .roads_per_intersection(i)
@@ -335,7 +504,8 @@ impl StreetNetwork {
                 // ordered with the "insides" touching and
                 // the angle between them is small enough.
 
-                // Check for any turn restrictions.
+                // Check for any turn restrictions. Collapsing the 'via' road of a complicated
+                // restriction turns it into a simple restriction.
                 if src_road.allowed_to_turn_to(dst_road.id) {
                     connections.push((s, d));
                 }
@@ -361,11 +531,14 @@ impl StreetNetwork {
             }
         }
 
+        let mut movements: Vec<Movement> = connections
+            .iter()
+            .map(|(s, d)| (roads[*s].id, roads[*d].id))
+            .collect();
+        movements.sort();
+
         (
-            connections
-                .iter()
-                .map(|(s, d)| (roads[*s].id, roads[*d].id))
-                .collect(),
+            movements,
             match worst_conflict {
                 Uncontested => IntersectionKind::Connection,
                 // TODO check for give way signs or count lanes to detect Intersections:
@@ -374,11 +547,75 @@ impl StreetNetwork {
             },
         )
     }
+
+    /// The angle in degrees of turning from one road onto another through an intersection, with
+    /// 0 straight ahead and positive to the right.
+    pub(crate) fn movement_angle(&self, from: RoadID, to: RoadID, i: IntersectionID) -> f64 {
+        let from = &self.roads[&from];
+        let to = &self.roads[&to];
+        let incoming = if from.dst_i == i {
+            from.center_line.last_line().angle()
+        } else {
+            from.center_line.reversed().last_line().angle()
+        };
+        let outgoing = if to.src_i == i {
+            to.center_line.first_line().angle()
+        } else {
+            to.center_line.reversed().first_line().angle()
+        };
+        (outgoing.normalized_degrees() - incoming.normalized_degrees() + 540.0) % 360.0 - 180.0
+    }
+}
+
+/// Map coordinates have Y pointing down, so angles already increase clockwise, but start east.
+fn compass_bearing(angle: Angle) -> f64 {
+    (angle.normalized_degrees() + 90.0) % 360.0
+}
+
+/// The clockwise angle from each bearing to the next, wrapping around. A lone road has the whole
+/// circle.
+fn clockwise_gaps(bearings: &[f64]) -> Vec<f64> {
+    if bearings.len() == 1 {
+        return vec![360.0];
+    }
+    bearings
+        .iter()
+        .zip(bearings.iter().cycle().skip(1))
+        .map(|(a, b)| (b - a).rem_euclid(360.0))
+        .collect()
+}
+
+/// Classifies a movement by its angle from `movement_angle`.
+pub(crate) fn turn_direction_for_angle(angle: f64) -> TurnDirection {
+    let magnitude = angle.abs();
+    if magnitude <= 20.0 {
+        TurnDirection::Through
+    } else if magnitude >= 170.0 {
+        TurnDirection::Reverse
+    } else if angle > 0.0 {
+        if magnitude < 60.0 {
+            TurnDirection::SlightRight
+        } else if magnitude < 135.0 {
+            TurnDirection::Right
+        } else {
+            TurnDirection::SharpRight
+        }
+    } else if magnitude < 60.0 {
+        TurnDirection::SlightLeft
+    } else if magnitude < 135.0 {
+        TurnDirection::Left
+    } else {
+        TurnDirection::SharpLeft
+    }
 }
 
 /// Calculate how two turns through an intersection conflict. Turns are identified by the clockwise
 /// index of their (src, dst) roads.
-fn calc_conflict(a: &(usize, usize), b: &(usize, usize), side: DrivingSide) -> TrafficConflict {
+pub(crate) fn calc_conflict(
+    a: &(usize, usize),
+    b: &(usize, usize),
+    side: DrivingSide,
+) -> TrafficConflict {
     // If the traffic starts and ends at the same place in the same direction...
     if a.0 == b.0 && a.1 == b.1 {
         return Uncontested;
@@ -448,10 +685,85 @@ fn is_between(num: usize, range: &(usize, usize)) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use geom::LonLat;
+
     use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::{DebugStreets, Filter, StepDiff};
 
     #[test]
     fn test_crossing_kind_order() {
         assert!(CrossingKind::Signalized > CrossingKind::Marked);
     }
+
+    #[test]
+    fn test_clockwise_gaps() {
+        assert_eq!(90.0, compass_bearing(Angle::degrees(0.0)));
+        assert_eq!(0.0, compass_bearing(Angle::degrees(-90.0)));
+        assert_eq!(vec![90.0, 180.0, 90.0], clockwise_gaps(&[0.0, 90.0, 270.0]));
+        assert_eq!(vec![20.0, 340.0], clockwise_gaps(&[350.0, 10.0]));
+        assert_eq!(vec![360.0], clockwise_gaps(&[45.0]));
+    }
+
+    fn property(feature: &serde_json::Value, key: &str) -> serde_json::Value {
+        feature["properties"][key].clone()
+    }
+
+    #[test]
+    fn test_stable_order() {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        let center = add_intersection(&mut streets, 100.0, 100.0, IntersectionKind::Connection);
+        // Create the arms out of clockwise order, so the road IDs don't match it
+        for (x, y) in [(100.0, 200.0), (200.0, 100.0), (100.0, 0.0), (0.0, 100.0)] {
+            let edge = add_intersection(&mut streets, x, y, IntersectionKind::MapEdge);
+            add_road(&mut streets, center, edge, &["highway=residential"]);
+        }
+
+        let movements = streets.intersections[&center.0].movements.clone();
+        assert_eq!(12, movements.len());
+        let mut sorted = movements.clone();
+        sorted.sort();
+        assert_eq!(sorted, movements);
+
+        let geojson: serde_json::Value =
+            serde_json::from_str(&streets.to_movements_geojson(&Filter::All).unwrap()).unwrap();
+        let rendered: Vec<Movement> = geojson["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| {
+                (
+                    RoadID(property(f, "from_road").as_u64().unwrap() as usize),
+                    RoadID(property(f, "to_road").as_u64().unwrap() as usize),
+                )
+            })
+            .collect();
+        assert_eq!(movements, rendered);
+
+        // Debug labels are sorted, keeping the order of repeated labels
+        let step = DebugStreets {
+            label: "step".to_string(),
+            streets: streets.clone(),
+            points: vec![
+                (Pt2D::new(1.0, 0.0), "b".to_string()),
+                (Pt2D::new(2.0, 0.0), "a".to_string()),
+                (Pt2D::new(3.0, 0.0), "b".to_string()),
+            ],
+            polylines: Vec::new(),
+            diff: StepDiff::default(),
+        };
+        let geojson: serde_json::Value =
+            serde_json::from_str(&step.to_debug_geojson().unwrap()).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        let labels: Vec<_> = features.iter().map(|f| property(f, "label")).collect();
+        assert_eq!(vec!["a", "b", "b"], labels);
+        let x = |idx: usize| {
+            features[idx]["geometry"]["coordinates"][0]
+                .as_f64()
+                .unwrap()
+        };
+        assert!(x(1) < x(2));
+    }
 }
@@ -0,0 +1,247 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use geom::Polygon;
+
+use crate::{IntersectionID, Movement, RoadID, StreetNetwork};
+
+/// A large junction made of several intersections connected by short internal roads, tagged
+/// `junction=intersection`. The pieces are kept separate in the `StreetNetwork` when they aren't
+/// collapsed, but consumers can treat the complex as one node.
+#[derive(Clone, Debug)]
+pub struct JunctionComplex {
+    pub intersections: BTreeSet<IntersectionID>,
+    /// The `junction=intersection` roads inside the complex
+    pub internal_roads: BTreeSet<RoadID>,
+    /// The roads leading into or out of the complex, in no particular order
+    pub external_roads: Vec<RoadID>,
+    /// The convex hull of all intersections and internal roads
+    pub polygon: Polygon,
+    /// Pairs of external roads, where it's possible to drive from the first to the second through
+    /// the complex, following the movements of each intersection
+    pub movements: Vec<Movement>,
+}
+
+impl StreetNetwork {
+    /// Groups intersections connected by `junction=intersection` roads.
+    pub fn find_junction_complexes(&self) -> Vec<JunctionComplex> {
+        let mut visited: BTreeSet<RoadID> = BTreeSet::new();
+        let mut results = Vec::new();
+        for road in self.roads.values() {
+            if !road.internal_junction_road || visited.contains(&road.id) {
+                continue;
+            }
+
+            // Flood from this road to all connected internal roads
+            let mut internal_roads = BTreeSet::new();
+            let mut intersections = BTreeSet::new();
+            let mut queue = VecDeque::from([road.id]);
+            while let Some(r) = queue.pop_front() {
+                if !internal_roads.insert(r) {
+                    continue;
+                }
+                visited.insert(r);
+                for i in self.roads[&r].endpoints() {
+                    intersections.insert(i);
+                    for next in &self.intersections[&i].roads {
+                        if self.roads[next].internal_junction_road {
+                            queue.push_back(*next);
+                        }
+                    }
+                }
+            }
+
+            let mut external_roads = Vec::new();
+            for i in &intersections {
+                for r in &self.intersections[i].roads {
+                    if !internal_roads.contains(r) && !external_roads.contains(r) {
+                        external_roads.push(*r);
+                    }
+                }
+            }
+
+            let mut polygons: Vec<Polygon> = intersections
+                .iter()
+                .map(|i| self.intersections[i].polygon.clone())
+                .collect();
+            for r in &internal_roads {
+                let road = &self.roads[r];
+                polygons.push(road.center_line.make_polygons(road.total_width()));
+            }
+
+            let movements =
+                self.complex_movements(&intersections, &internal_roads, &external_roads);
+            results.push(JunctionComplex {
+                intersections,
+                internal_roads,
+                external_roads,
+                polygon: Polygon::convex_hull(polygons),
+                movements,
+            });
+        }
+        results
+    }
+
+    /// Follows movements from each external road through internal roads, until reaching another
+    /// external road.
+    fn complex_movements(
+        &self,
+        intersections: &BTreeSet<IntersectionID>,
+        internal_roads: &BTreeSet<RoadID>,
+        external_roads: &[RoadID],
+    ) -> Vec<Movement> {
+        let mut movements = Vec::new();
+        for from in external_roads {
+            let mut seen: BTreeSet<(RoadID, IntersectionID)> = BTreeSet::new();
+            let mut queue = VecDeque::new();
+            // An external road could touch the complex at both ends
+            for i in self.roads[from].endpoints() {
+                if intersections.contains(&i) {
+                    queue.push_back((*from, i));
+                }
+            }
+
+            while let Some((current, i)) = queue.pop_front() {
+                if !seen.insert((current, i)) {
+                    continue;
+                }
+                for (src, dst) in &self.intersections[&i].movements {
+                    if *src != current {
+                        continue;
+                    }
+                    if internal_roads.contains(dst) {
+                        queue.push_back((*dst, self.roads[dst].other_side(i)));
+                    } else if dst != from && !movements.contains(&(*from, *dst)) {
+                        movements.push((*from, *dst));
+                    }
+                }
+            }
+        }
+        movements.sort();
+        movements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionKind, Road};
+
+    //           north
+    //             |
+    // west ----- i1 =m= i2 ----- east
+    //                    |
+    //                  south
+    //
+    // m is tagged `junction=intersection`. Returns the roads west, north, m, east, south.
+    fn network(oneway_m: bool) -> (StreetNetwork, [RoadID; 5]) {
+        let mut streets = StreetNetwork::blank();
+        let pts = [
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, -100.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(110.0, 0.0),
+            Pt2D::new(210.0, 0.0),
+            Pt2D::new(110.0, 100.0),
+        ];
+        let intersections: Vec<IntersectionID> = pts
+            .iter()
+            .enumerate()
+            .map(|(idx, pt)| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    if idx == 2 || idx == 3 {
+                        IntersectionKind::Intersection
+                    } else {
+                        IntersectionKind::MapEdge
+                    },
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        let mut add_road = |i1: usize, i2: usize, internal: bool| {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "primary");
+            if internal {
+                tags.insert("junction", "intersection");
+                if oneway_m {
+                    tags.insert("oneway", "yes");
+                }
+            }
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                intersections[i1],
+                intersections[i2],
+                PolyLine::must_new(vec![pts[i1], pts[i2]]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+            id
+        };
+        let roads = [
+            add_road(0, 2, false),
+            add_road(1, 2, false),
+            add_road(2, 3, true),
+            add_road(3, 4, false),
+            add_road(3, 5, false),
+        ];
+        (streets, roads)
+    }
+
+    #[test]
+    fn test_find_junction_complexes() {
+        let (streets, [west, north, m, east, south]) = network(false);
+        let complexes = streets.find_junction_complexes();
+        assert_eq!(1, complexes.len());
+        let complex = &complexes[0];
+        assert_eq!(BTreeSet::from([m]), complex.internal_roads);
+        assert_eq!(
+            BTreeSet::from([streets.roads[&m].src_i, streets.roads[&m].dst_i]),
+            complex.intersections
+        );
+        let mut external = complex.external_roads.clone();
+        external.sort();
+        assert_eq!(vec![west, north, east, south], external);
+        assert!(complex
+            .polygon
+            .contains_pt(streets.roads[&m].center_line.middle()));
+
+        // Every external road reaches every other one, through m if needed
+        let mut expected = Vec::new();
+        for from in [west, north, east, south] {
+            for to in [west, north, east, south] {
+                if from != to {
+                    expected.push((from, to));
+                }
+            }
+        }
+        expected.sort();
+        assert_eq!(expected, complex.movements);
+
+        // Nothing is a complex without internal roads
+        let mut streets = streets;
+        streets.roads.get_mut(&m).unwrap().internal_junction_road = false;
+        assert!(streets.find_junction_complexes().is_empty());
+    }
+
+    #[test]
+    fn test_complex_movements_follow_oneways() {
+        let (streets, [west, north, _, east, south]) = network(true);
+        let movements = &streets.find_junction_complexes()[0].movements;
+        // m only goes east, so nothing from east or south reaches west or north
+        for from in [west, north] {
+            for to in [east, south] {
+                assert!(movements.contains(&(from, to)));
+                assert!(!movements.contains(&(to, from)));
+            }
+        }
+        assert!(movements.contains(&(west, north)));
+        assert!(movements.contains(&(east, south)));
+    }
+}
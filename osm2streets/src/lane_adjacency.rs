@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+
+use abstutil::Tags;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::render::marking::LongitudinalLine;
+use crate::{Direction, Filter, LaneID, LaneSpec, LaneType, Road, StreetNetwork};
+
+/// A `change:lanes` value, restricting lane changes out of one lane. Left and right are relative
+/// to the lane's direction of travel. See <https://wiki.openstreetmap.org/wiki/Key:change>.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaneChange {
+    Yes,
+    No,
+    NotLeft,
+    NotRight,
+}
+
+impl LaneChange {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "yes" => Some(Self::Yes),
+            "no" => Some(Self::No),
+            "not_left" | "only_right" => Some(Self::NotLeft),
+            "not_right" | "only_left" => Some(Self::NotRight),
+            _ => None,
+        }
+    }
+
+    fn allows(self, to_the_left: bool) -> bool {
+        match self {
+            Self::Yes => true,
+            Self::No => false,
+            Self::NotLeft => !to_the_left,
+            Self::NotRight => to_the_left,
+        }
+    }
+}
+
+/// Two lanes next to each other on a road, and whether vehicles may change between them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LaneAdjacency {
+    /// The lane on the left, in the order of `lane_specs_ltr`
+    pub left: LaneID,
+    pub right: LaneID,
+    pub left_to_right: bool,
+    pub right_to_left: bool,
+}
+
+impl Road {
+    /// Describes each pair of neighboring lanes, ignoring virtual lanes. Changing lanes is only
+    /// allowed between lanes of the same type for moving vehicles, across the dashed side of the
+    /// line painted between them. Lanes separated by a buffer aren't neighbors.
+    pub fn lane_adjacency(&self) -> Vec<LaneAdjacency> {
+        let (road, original) = self.without_virtual_lanes();
+        let mut results = Vec::new();
+        for (idx, pair) in road.lane_specs_ltr.windows(2).enumerate() {
+            let compatible = pair[0].lt == pair[1].lt && pair[0].lt.is_for_moving_vehicles();
+            let (left_to_right, right_to_left) = match road.line_between_lanes(idx) {
+                Some(LongitudinalLine::Lane {
+                    merge_left,
+                    merge_right,
+                }) if compatible => (merge_right, merge_left),
+                _ => (false, false),
+            };
+            results.push(LaneAdjacency {
+                left: LaneID {
+                    road: self.id,
+                    index: original[idx],
+                },
+                right: LaneID {
+                    road: self.id,
+                    index: original[idx + 1],
+                },
+                left_to_right,
+                right_to_left,
+            });
+        }
+        results
+    }
+
+    /// Whether `change:lanes` allows traffic in a lane to move to its left or right, relative to
+    /// the lane's direction of travel
+    pub(crate) fn may_change_lanes(&self, lane: usize, to_the_left: bool) -> bool {
+        self.lane_changes
+            .get(&lane)
+            .map(|change| change.allows(to_the_left))
+            .unwrap_or(true)
+    }
+}
+
+impl StreetNetwork {
+    /// Returns a JSON array describing each pair of neighboring lanes and whether vehicles may
+    /// change between them. See `Road::lane_adjacency`.
+    pub fn to_lane_adjacency_json(&self, filter: &Filter) -> Result<String> {
+        let list: Vec<LaneAdjacency> = filter
+            .roads(self)
+            .flat_map(|road| road.lane_adjacency())
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+}
+
+/// Parses `change`, `change:lanes`, and the `:forward` and `:backward` variations, returning the
+/// restriction for each lane, keyed by index into `lane_specs_ltr`. Only the lanes counted by
+/// `lanes` -- driving and bus lanes -- are described by these tags.
+pub(crate) fn parse_lane_changes(
+    tags: &Tags,
+    lane_specs_ltr: &[LaneSpec],
+) -> BTreeMap<usize, LaneChange> {
+    let counted = |dir: Direction| -> Vec<usize> {
+        lane_specs_ltr
+            .iter()
+            .enumerate()
+            .filter(|(_, lane)| {
+                lane.dir == dir && matches!(lane.lt, LaneType::Driving | LaneType::Bus)
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    };
+    // Per-lane values are listed from the left of each direction of travel
+    let forwards = counted(Direction::Forward);
+    let mut backwards = counted(Direction::Backward);
+    backwards.reverse();
+
+    let mut results = BTreeMap::new();
+    for (lanes, suffix) in [(forwards, "forward"), (backwards, "backward")] {
+        let per_lane = tags.get(&format!("change:lanes:{suffix}")).or_else(|| {
+            // Without a direction, this only describes oneways
+            let oneway = if suffix == "forward" {
+                tags.is("oneway", "yes")
+            } else {
+                tags.is("oneway", "-1")
+            };
+            if oneway {
+                tags.get("change:lanes")
+            } else {
+                None
+            }
+        });
+        let whole_road = tags
+            .get(&format!("change:{suffix}"))
+            .or_else(|| tags.get("change"))
+            .and_then(|x| LaneChange::parse(x));
+
+        if let Some(per_lane) = per_lane {
+            let values: Vec<&str> = per_lane.split('|').collect();
+            if values.len() != lanes.len() {
+                warn!(
+                    "change:lanes describes {} lanes, but there are {}",
+                    values.len(),
+                    lanes.len()
+                );
+                continue;
+            }
+            for (idx, value) in lanes.into_iter().zip(values) {
+                if let Some(change) = LaneChange::parse(value) {
+                    results.insert(idx, change);
+                }
+            }
+        } else if let Some(change) = whole_road {
+            for idx in lanes {
+                results.insert(idx, change);
+            }
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use enumset::EnumSet;
+    use geom::Distance;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_lane_changes() {
+        let lane = |lt, dir| LaneSpec {
+            lt,
+            dir,
+            width: Distance::meters(3.0),
+            allowed_turns: EnumSet::new(),
+            is_virtual: false,
+            shoulder_access: None,
+            max_speed: None,
+            bus_hours: None,
+            lane: None,
+        };
+        let lanes = vec![
+            lane(LaneType::Sidewalk, Direction::Backward),
+            lane(LaneType::Driving, Direction::Backward),
+            lane(LaneType::Driving, Direction::Backward),
+            lane(LaneType::Driving, Direction::Forward),
+            lane(LaneType::Bus, Direction::Forward),
+            lane(LaneType::Biking, Direction::Forward),
+        ];
+
+        let mut tags = Tags::empty();
+        tags.insert("change:lanes:forward", "not_right|no");
+        tags.insert("change:backward", "no");
+        let changes = parse_lane_changes(&tags, &lanes);
+        assert_eq!(
+            vec![
+                (1, LaneChange::No),
+                (2, LaneChange::No),
+                (3, LaneChange::NotRight),
+                (4, LaneChange::No)
+            ],
+            changes.into_iter().collect::<Vec<_>>()
+        );
+
+        // The number of lanes doesn't match, so this is ignored
+        let mut tags = Tags::empty();
+        tags.insert("change:lanes:forward", "no");
+        assert!(parse_lane_changes(&tags, &lanes).is_empty());
+    }
+
+    #[test]
+    fn test_lane_adjacency() {
+        let mut streets = StreetNetwork::blank();
+        let mut tags = Tags::empty();
+        tags.insert("highway", "primary");
+        tags.insert("lanes", "4");
+        tags.insert("lanes:forward", "3");
+        tags.insert("lanes:backward", "1");
+        tags.insert("sidewalk", "no");
+        tags.insert("change:lanes:forward", "yes|not_right|yes");
+        let road = Road::new(
+            streets.next_road_id(),
+            vec![crate::osm::WayID(1)],
+            crate::IntersectionID(0),
+            crate::IntersectionID(1),
+            geom::PolyLine::must_new(vec![geom::Pt2D::new(0.0, 0.0), geom::Pt2D::new(100.0, 0.0)]),
+            tags,
+            &streets.config,
+        );
+        let lts: Vec<(LaneType, Direction)> =
+            road.lane_specs_ltr.iter().map(|l| (l.lt, l.dir)).collect();
+        assert_eq!(
+            vec![
+                (LaneType::Driving, Direction::Backward),
+                (LaneType::Driving, Direction::Forward),
+                (LaneType::Driving, Direction::Forward),
+                (LaneType::Driving, Direction::Forward),
+            ],
+            lts
+        );
+
+        let allowed: Vec<(bool, bool)> = road
+            .lane_adjacency()
+            .into_iter()
+            .map(|x| (x.left_to_right, x.right_to_left))
+            .collect();
+        // Opposite directions, then a dashed line, then a line the middle lane can't cross
+        assert_eq!(vec![(false, false), (true, true), (false, true)], allowed);
+    }
+}
@@ -0,0 +1,358 @@
+use geom::{Distance, PolyLine, Polygon, Ring};
+use serde::Serialize;
+
+use crate::{
+    Direction, DrivingSide, IntersectionID, IntersectionKind, LaneSpec, LaneType, Road, RoadID,
+    StreetNetwork, TurnDirection,
+};
+
+/// How far before the end of a dropped lane to start tapering it
+const TAPER_LENGTH: Distance = Distance::const_meters(30.0);
+
+/// Where a road gains or drops a lane mid-block. Both roads meet at a `Connection` intersection.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LaneTransition {
+    pub intersection: IntersectionID,
+    /// Traffic flows from this road...
+    pub from: RoadID,
+    /// ... to this road
+    pub to: RoadID,
+    /// Indices into `from`'s `lane_specs_ltr` of lanes that end here, with the direction that
+    /// traffic in them must merge (`MergeLeft` or `MergeRight`)
+    #[serde(serialize_with = "serialize_dropped_lanes")]
+    pub dropped_lanes: Vec<(usize, TurnDirection)>,
+    /// Indices into `to`'s `lane_specs_ltr` of lanes that begin here
+    pub added_lanes: Vec<usize>,
+}
+
+/// Turns are written as their OSM tag value, like `merge_left`
+fn serialize_dropped_lanes<S: serde::Serializer>(
+    lanes: &[(usize, TurnDirection)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(lanes.iter().map(|(idx, turn)| (*idx, turn.tag_value())))
+}
+
+/// A lane added alongside a motorway or trunk road for traffic joining from an on-ramp, or
+/// leaving by an off-ramp. It doesn't run the full length of the road; it tapers at the other end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RampLane {
+    pub road: RoadID,
+    /// Index into the road's `lane_specs_ltr`
+    pub lane: usize,
+    pub kind: RampLaneKind,
+    /// The `_link` road joining or leaving
+    pub ramp: RoadID,
+    /// Where the ramp meets the road
+    pub intersection: IntersectionID,
+    /// Where the lane narrows to nothing, at the other end of the road
+    pub taper_intersection: IntersectionID,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RampLaneKind {
+    /// Begins at an on-ramp, and merges into the main road
+    Acceleration,
+    /// Splits from the main road, and continues onto an off-ramp
+    Deceleration,
+}
+
+impl StreetNetwork {
+    /// Finds everywhere the number of lanes in one direction changes between two roads.
+    pub fn find_lane_transitions(&self) -> Vec<LaneTransition> {
+        let mut results = Vec::new();
+        for i in self.intersections.values() {
+            if i.kind != IntersectionKind::Connection {
+                continue;
+            }
+            let roads = self.roads_per_intersection(i.id);
+            if roads.len() != 2 || !roads.iter().all(|r| r.is_driveable()) {
+                continue;
+            }
+            for (from, to) in [(roads[0], roads[1]), (roads[1], roads[0])] {
+                if let Some(transition) = self.lane_transition(i.id, from, to) {
+                    results.push(transition);
+                }
+            }
+        }
+        results
+    }
+
+    fn lane_transition(&self, i: IntersectionID, from: &Road, to: &Road) -> Option<LaneTransition> {
+        let from_lanes = self.lanes_towards(from, i);
+        let to_lanes = self.lanes_away(to, i);
+        if from_lanes.len() == to_lanes.len() || from_lanes.is_empty() || to_lanes.is_empty() {
+            return None;
+        }
+
+        // The outermost lane usually ends or begins, merging towards the center of the road
+        let merge = self.merge_towards_center();
+        let mut dropped_lanes = Vec::new();
+        let mut added_lanes = Vec::new();
+        if from_lanes.len() > to_lanes.len() {
+            for idx in &from_lanes[0..from_lanes.len() - to_lanes.len()] {
+                dropped_lanes.push((*idx, merge));
+            }
+        } else {
+            added_lanes.extend(&to_lanes[0..to_lanes.len() - from_lanes.len()]);
+        }
+
+        Some(LaneTransition {
+            intersection: i,
+            from: from.id,
+            to: to.id,
+            dropped_lanes,
+            added_lanes,
+        })
+    }
+
+    /// Finds acceleration and deceleration lanes where a `_link` road joins or leaves a motorway
+    /// or trunk road. A lane only counts if the main road gains it at the ramp and drops it at the
+    /// next intersection, or the other way around; lanes running onwards are ordinary lanes.
+    pub fn find_ramp_lanes(&self) -> Vec<RampLane> {
+        let transitions = self.find_lane_transitions();
+        let mut results = Vec::new();
+        for i in self.intersections.values() {
+            let roads = self.roads_per_intersection(i.id);
+            if roads.len() != 3 || !roads.iter().all(|r| r.is_driveable()) {
+                continue;
+            }
+            let (ramps, main): (Vec<&Road>, Vec<&Road>) = roads
+                .into_iter()
+                .partition(|r| r.highway_type.ends_with("_link"));
+            if ramps.len() != 1
+                || !main
+                    .iter()
+                    .all(|r| matches!(r.highway_type.as_str(), "motorway" | "trunk"))
+            {
+                continue;
+            }
+            let ramp = ramps[0];
+            let on_ramp = match (
+                self.lanes_towards(ramp, i.id).is_empty(),
+                self.lanes_away(ramp, i.id).is_empty(),
+            ) {
+                (false, true) => true,
+                (true, false) => false,
+                // A two-way link isn't a ramp
+                _ => continue,
+            };
+
+            for (from, to) in [(main[0], main[1]), (main[1], main[0])] {
+                let from_lanes = self.lanes_towards(from, i.id);
+                let to_lanes = self.lanes_away(to, i.id);
+                if from_lanes.is_empty() || to_lanes.is_empty() {
+                    continue;
+                }
+
+                let (road, lanes, kind) = if on_ramp && to_lanes.len() > from_lanes.len() {
+                    (
+                        to,
+                        &to_lanes[0..to_lanes.len() - from_lanes.len()],
+                        RampLaneKind::Acceleration,
+                    )
+                } else if !on_ramp && from_lanes.len() > to_lanes.len() {
+                    (
+                        from,
+                        &from_lanes[0..from_lanes.len() - to_lanes.len()],
+                        RampLaneKind::Deceleration,
+                    )
+                } else {
+                    continue;
+                };
+                let taper_intersection = road.other_side(i.id);
+
+                for idx in lanes {
+                    let tapers = transitions.iter().any(|t| {
+                        t.intersection == taper_intersection
+                            && match kind {
+                                RampLaneKind::Acceleration => {
+                                    t.from == road.id
+                                        && t.dropped_lanes.iter().any(|(x, _)| x == idx)
+                                }
+                                RampLaneKind::Deceleration => {
+                                    t.to == road.id && t.added_lanes.contains(idx)
+                                }
+                            }
+                    });
+                    if tapers {
+                        results.push(RampLane {
+                            road: road.id,
+                            lane: *idx,
+                            kind,
+                            ramp: ramp.id,
+                            intersection: i.id,
+                            taper_intersection,
+                        });
+                    }
+                }
+            }
+        }
+        results
+    }
+
+    /// Lanes ending at a transition must merge into the next lane over. Mark this as their only
+    /// allowed turn, unless the lane already has turns tagged. Deceleration lanes merge the other
+    /// way, onto their off-ramp.
+    pub(crate) fn mark_lane_transitions(&mut self) {
+        for transition in self.find_lane_transitions() {
+            let road = self.roads.get_mut(&transition.from).unwrap();
+            for (idx, merge) in transition.dropped_lanes {
+                let lane = &mut road.lane_specs_ltr[idx];
+                if lane.allowed_turns.is_empty() {
+                    lane.allowed_turns.insert(merge);
+                }
+            }
+        }
+
+        let merge_outwards = opposite_merge(self.merge_towards_center());
+        for ramp_lane in self.find_ramp_lanes() {
+            if ramp_lane.kind != RampLaneKind::Deceleration {
+                continue;
+            }
+            let lane =
+                &mut self.roads.get_mut(&ramp_lane.road).unwrap().lane_specs_ltr[ramp_lane.lane];
+            if lane.allowed_turns.is_empty() {
+                lane.allowed_turns.insert(merge_outwards);
+            }
+        }
+    }
+
+    /// The area where a dropped lane narrows to nothing, at the end of its road.
+    pub(crate) fn lane_taper_polygon(
+        &self,
+        transition: &LaneTransition,
+        idx: usize,
+        merge: TurnDirection,
+    ) -> Option<Polygon> {
+        self.taper_polygon(transition.from, idx, transition.intersection, merge)
+    }
+
+    /// The area where a deceleration lane widens out of the main road. Acceleration lanes are
+    /// dropped at a lane transition, so `lane_taper_polygon` covers them.
+    pub(crate) fn ramp_lane_taper_polygon(&self, ramp_lane: &RampLane) -> Option<Polygon> {
+        if ramp_lane.kind != RampLaneKind::Deceleration {
+            return None;
+        }
+        // Looking back from the start of the lane, the main road is on the other side
+        self.taper_polygon(
+            ramp_lane.road,
+            ramp_lane.lane,
+            ramp_lane.taper_intersection,
+            opposite_merge(self.merge_towards_center()),
+        )
+    }
+
+    /// Narrows a lane to a point on one side, over the last stretch before an end of its road.
+    fn taper_polygon(
+        &self,
+        road: RoadID,
+        idx: usize,
+        at: IntersectionID,
+        merge: TurnDirection,
+    ) -> Option<Polygon> {
+        let road = &self.roads[&road];
+        let lane = &road.lane_specs_ltr[idx];
+        let mut center = road.get_lane_center_lines().remove(idx);
+        if road.src_i == at {
+            center = center.reversed();
+        }
+        let len = center.length();
+        let taper: PolyLine = center
+            .maybe_exact_slice(len - TAPER_LENGTH.min(len / 2.0), len)
+            .ok()?;
+
+        let half_width = lane.width / 2.0;
+        let start_left = taper.shift_left(half_width).ok()?.first_pt();
+        let start_right = taper.shift_right(half_width).ok()?.first_pt();
+        // The lane shrinks from the side it's merging away from
+        let end = if merge == TurnDirection::MergeLeft {
+            taper.shift_left(half_width).ok()?.last_pt()
+        } else {
+            taper.shift_right(half_width).ok()?.last_pt()
+        };
+        Ring::deduping_new(vec![start_left, start_right, end, start_left])
+            .ok()
+            .map(|ring| ring.into_polygon())
+    }
+}
+
+impl StreetNetwork {
+    /// Indices of a road's lanes carrying traffic towards an intersection, outermost first
+    fn lanes_towards(&self, road: &Road, i: IntersectionID) -> Vec<usize> {
+        let dir = if road.dst_i == i {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+        outermost_first(&road.lane_specs_ltr, dir, self.config.driving_side)
+    }
+
+    /// Indices of a road's lanes carrying traffic away from an intersection, outermost first
+    fn lanes_away(&self, road: &Road, i: IntersectionID) -> Vec<usize> {
+        let dir = if road.src_i == i {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+        outermost_first(&road.lane_specs_ltr, dir, self.config.driving_side)
+    }
+
+    fn merge_towards_center(&self) -> TurnDirection {
+        if self.config.driving_side == DrivingSide::Right {
+            TurnDirection::MergeLeft
+        } else {
+            TurnDirection::MergeRight
+        }
+    }
+}
+
+fn opposite_merge(merge: TurnDirection) -> TurnDirection {
+    if merge == TurnDirection::MergeLeft {
+        TurnDirection::MergeRight
+    } else {
+        TurnDirection::MergeLeft
+    }
+}
+
+/// Returns indices of the driving and bus lanes pointing in a direction, starting with the
+/// outermost (closest to the kerb).
+fn outermost_first(
+    lanes_ltr: &[LaneSpec],
+    dir: Direction,
+    driving_side: DrivingSide,
+) -> Vec<usize> {
+    let mut lanes: Vec<usize> = lanes_ltr
+        .iter()
+        .enumerate()
+        .filter(|(_, lane)| matches!(lane.lt, LaneType::Driving | LaneType::Bus) && lane.dir == dir)
+        .map(|(idx, _)| idx)
+        .collect();
+    // In LTR order, the outermost lane of forward traffic on a right-handed road is last
+    if (dir == Direction::Forward) == (driving_side == DrivingSide::Right) {
+        lanes.reverse();
+    }
+    lanes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outermost_first() {
+        let lanes = LaneSpec::create_for_test("sdddds", "vvv^^^");
+        assert_eq!(
+            outermost_first(&lanes, Direction::Forward, DrivingSide::Right),
+            vec![4, 3]
+        );
+        assert_eq!(
+            outermost_first(&lanes, Direction::Backward, DrivingSide::Right),
+            vec![1, 2]
+        );
+        assert_eq!(
+            outermost_first(&lanes, Direction::Forward, DrivingSide::Left),
+            vec![3, 4]
+        );
+    }
+}
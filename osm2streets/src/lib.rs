@@ -4,41 +4,124 @@ extern crate anyhow;
 extern crate log;
 
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
 use geom::{GPSBounds, PolyLine, Polygon, Pt2D};
 
+use self::spatial_index::SpatialIndexCache;
 use self::utils::{deserialize_btreemap, serialize_btreemap};
 
-pub use self::geometry::{intersection_polygon, InputRoad};
+pub use self::accessibility::KerbKind;
+pub use self::audit::AuditEntry;
+pub use self::availability::{Availability, ConditionalAccess};
+pub use self::block::{Block, BlockKind};
+pub use self::capacity::{DirectedCapacity, RoadCapacity};
+pub use self::corridor::{Approach, Corridor, CorridorIntersection};
+pub use self::crs::Crs;
+pub use self::debug_diff::StepDiff;
+pub use self::feature_hash::add_feature_hashes;
+pub use self::ferry::Ferry;
+pub use self::freight::{FreightRestrictions, Truck};
+pub use self::geometry::{
+    intersection_polygon, DeadEnd, GeometryFixture, InputRoad, TrimAlgorithm, TrimProvenance,
+};
+pub use self::grade_separation::GradeSeparation;
+#[cfg(feature = "gtfs")]
+pub use self::gtfs::{parse_gtfs_stops, GtfsStop, StopMatch};
 pub(crate) use self::ids::RoadWithEndpoints;
-pub use self::ids::{CommonEndpoint, IntersectionID, LaneID, RoadID, RoadSideID, SideOfRoad};
+pub use self::ids::{
+    CommonEndpoint, IntersectionID, LaneID, LaneKey, RoadID, RoadSideID, SideOfRoad,
+};
 pub use self::intersection::{
-    Crossing, CrossingKind, Intersection, IntersectionControl, IntersectionKind, Movement,
-    TrafficConflict,
+    ClockwiseRoad, Crossing, CrossingKind, Intersection, IntersectionControl, IntersectionKind,
+    MapEdgeStub, Movement, TrafficConflict, TrafficControlDevice, TrafficControlKind,
 };
+pub use self::junction_complex::JunctionComplex;
+pub use self::lane_adjacency::{LaneAdjacency, LaneChange};
+pub use self::lane_transition::{LaneTransition, RampLane, RampLaneKind};
+pub use self::linear_ref::LinearEvent;
 pub use self::operations::zip_sidepath::Sidepath;
-pub use self::render::Filter;
-pub use self::road::{Road, StopLine, TrafficInterruption};
-pub use self::transform::Transformation;
-pub use self::types::NamePerLanguage;
+pub use self::operations::{EditCommand, LaneEdit, TagChange};
+pub use self::osm_index::OsmIndex;
+pub use self::parking::{ExclusionReason, ParkingExclusion, ParkingLane};
+pub use self::path_attributes::PathAttributes;
+pub use self::pathfinding::{Budget, Profile, ReachableRoad};
+pub use self::progress::{CancellationToken, Progress};
+pub use self::qa::{IssueKind, TaggingIssue};
+#[cfg(feature = "gzip")]
+pub use self::render::gzip;
+#[cfg(feature = "raster")]
+pub use self::render::RasterSnapshot;
+pub use self::render::{buffer_with_chevrons, Filter, Surface, SurfaceMaterial};
+pub use self::road::{
+    LaneFeature, LaneFeatureKind, Lifecycle, Road, StopLine, Structure, TrafficInterruption,
+};
+pub use self::road_widths::RoadWidths;
+pub use self::separate_ways::{SeparateKind, SeparateStatus, SeparatelyMapped};
+pub use self::simplify::{simplify_preserving_topology, smooth_corners};
+pub use self::steps::{Incline, Steps};
+pub use self::summary::NetworkSummary;
+pub use self::transform::{CollapseCandidate, CollapseReason, NetworkTransform, Transformation};
+pub use self::turn_costs::MovementAttributes;
+pub use self::turn_restrictions::{StaleReason, StaleTurnRestriction};
+pub use self::types::{InternedString, NamePerLanguage};
+pub use self::validate::InvariantViolation;
 
-// Re-export osm2lanes types for an easier refactor. TODO Stop doing this.
+// Lane types are only defined in osm2lanes. Re-export all of them, so users of osm2streets don't
+// need to depend on osm2lanes directly, and the two can't drift apart.
 pub use osm2lanes::{
-    get_lane_specs_ltr, osm, BufferType, Direction, DrivingSide, LaneSpec, LaneType, MapConfig,
-    ParkingType, Placement, NORMAL_LANE_THICKNESS, SIDEWALK_THICKNESS,
+    get_lane_specs_ltr, osm, units, BufferType, DeadEndCap, Direction, DrivingSide, HighwayFilter,
+    IndoorPaths, IntersectionGeometry, LaneSpec, LaneType, LtrLaneNum, MapConfig, ParkingType,
+    Placement, RoadPosition, ShoulderAccess, TrafficClass, TurnDirection, NORMAL_LANE_THICKNESS,
+    SIDEWALK_THICKNESS,
 };
 
+mod accessibility;
+mod audit;
+mod availability;
 mod block;
+mod capacity;
+mod corridor;
+mod crs;
+mod debug_diff;
+mod feature_hash;
+mod ferry;
+mod freight;
+#[cfg(feature = "geo-interop")]
+pub mod geo_interop;
 mod geometry;
+mod grade_separation;
+#[cfg(feature = "gtfs")]
+mod gtfs;
 mod ids;
 mod intersection;
+mod junction_complex;
+mod lane_adjacency;
+mod lane_transition;
+mod linear_ref;
 mod operations;
+pub mod osm_index;
+mod parking;
+mod path_attributes;
 mod pathfinding;
+mod progress;
+mod qa;
 mod render;
+mod right_of_way;
 mod road;
+mod road_widths;
+mod separate_ways;
+mod simplify;
+mod spatial_index;
+mod steps;
+mod summary;
+#[cfg(test)]
+mod test_utils;
 mod transform;
+mod turn_costs;
+mod turn_restrictions;
 mod types;
 pub mod utils;
 mod validate;
@@ -55,6 +138,27 @@ pub struct StreetNetwork {
         deserialize_with = "deserialize_btreemap"
     )]
     pub intersections: BTreeMap<IntersectionID, Intersection>,
+    /// Pedestrian refuges and channelization islands mapped as areas, like
+    /// `area:highway=traffic_island`. These are spatially indexed along with roads, so call
+    /// `geometry_changed` after editing them.
+    #[serde(default)]
+    pub traffic_islands: Vec<Polygon>,
+    /// Other `area:highway` polygons, used by `IntersectionGeometry::MappedArea`
+    #[serde(default)]
+    pub highway_areas: Vec<Polygon>,
+    /// Paths inside buildings, kept out of the network by `IndoorPaths::Separate`
+    #[serde(default)]
+    pub indoor_paths: Vec<(osm::WayID, PolyLine)>,
+    /// `route=ferry` ways, connecting the network across water
+    #[serde(default)]
+    pub ferries: Vec<Ferry>,
+    /// Waterways and railways that aren't roads, used to split blocks when
+    /// `MapConfig::block_barriers` is set
+    #[serde(default)]
+    pub block_barriers: Vec<PolyLine>,
+    /// Nodes tagged with `kerb` or `barrier=kerb`
+    #[serde(default)]
+    pub kerbs: Vec<(Pt2D, KerbKind)>,
 
     pub boundary_polygon: Polygon,
     pub gps_bounds: GPSBounds,
@@ -62,9 +166,44 @@ pub struct StreetNetwork {
 
     #[serde(skip_serializing, skip_deserializing)]
     pub debug_steps: Vec<DebugStreets>,
+    /// The input to every intersection whose geometry couldn't be calculated at any point,
+    /// including in the middle of transformations, so the failure can be reproduced in isolation.
+    /// These are kept even if the intersection is fixed or removed later. Only the latest failure
+    /// per intersection is kept.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub geometry_failures: BTreeMap<IntersectionID, GeometryFixture>,
+    /// Turn restrictions removed after transformations, because the roads they refer to are gone
+    /// or no longer connect
+    #[serde(skip_serializing, skip_deserializing)]
+    pub stale_turn_restrictions: Vec<StaleTurnRestriction>,
+    /// A copy of the network before any transformations, if `MapConfig::keep_original` is set.
+    /// Use `original()` to get it.
+    #[serde(skip_serializing, skip_deserializing)]
+    original: Option<Box<StreetNetwork>>,
+    /// The OSM objects the network was imported from, shared between copies. Use `osm_index()`
+    /// to get it.
+    #[serde(skip_serializing, skip_deserializing)]
+    osm_index: Option<Arc<OsmIndex>>,
+    /// Explains what each transformation changed, and why
+    #[serde(default)]
+    pub audit_log: Vec<AuditEntry>,
 
     intersection_id_counter: usize,
     road_id_counter: usize,
+
+    // Intersections whose roads changed, waiting to be updated. The value is true if the roads
+    // also need to be sorted again.
+    #[serde(skip_serializing, skip_deserializing)]
+    pending_updates: BTreeMap<IntersectionID, bool>,
+    // How many nested `batch_edits` calls are running
+    #[serde(skip_serializing, skip_deserializing)]
+    batch_depth: usize,
+    // The name of the transformation running, for the audit log
+    #[serde(skip_serializing, skip_deserializing)]
+    current_transformation: Option<&'static str>,
+    // Roads and intersections by bounds, for `Filter::Within` and `Filter::BBox`
+    #[serde(skip_serializing, skip_deserializing)]
+    spatial_index: SpatialIndexCache,
 }
 
 #[derive(Clone, Debug)]
@@ -77,6 +216,8 @@ pub struct DebugStreets {
     pub points: Vec<(Pt2D, String)>,
     /// Extra labelled polylines to debug
     pub polylines: Vec<(PolyLine, String)>,
+    /// What changed since the previous step. Empty for the first step.
+    pub diff: StepDiff,
 }
 
 impl StreetNetwork {
@@ -84,42 +225,110 @@ impl StreetNetwork {
         Self {
             roads: BTreeMap::new(),
             intersections: BTreeMap::new(),
+            traffic_islands: Vec::new(),
+            highway_areas: Vec::new(),
+            indoor_paths: Vec::new(),
+            ferries: Vec::new(),
+            block_barriers: Vec::new(),
+            kerbs: Vec::new(),
             // Some nonsense thing
             boundary_polygon: Polygon::rectangle(1.0, 1.0),
             gps_bounds: GPSBounds::new(),
             config: MapConfig::default(),
 
             debug_steps: Vec::new(),
+            geometry_failures: BTreeMap::new(),
+            stale_turn_restrictions: Vec::new(),
+            original: None,
+            osm_index: None,
+            audit_log: Vec::new(),
 
             intersection_id_counter: 0,
             road_id_counter: 0,
+
+            pending_updates: BTreeMap::new(),
+            batch_depth: 0,
+            current_transformation: None,
+            spatial_index: SpatialIndexCache::default(),
         }
     }
 
+    /// Adds a road. Roads it's `parallel_to` point back at it.
     pub fn insert_road(&mut self, road: Road) {
         let endpts = road.endpoints();
         let id = road.id;
+        for r in &road.parallel_to {
+            if let Some(other) = self.roads.get_mut(r) {
+                other.parallel_to.insert(id);
+            }
+        }
         self.roads.insert(road.id, road);
+        self.geometry_changed();
         for i in endpts {
             self.intersections.get_mut(&i).unwrap().roads.push(id);
-            self.sort_roads(i);
-            self.update_i(i);
+            self.clear_placement_blend(i);
+            self.intersection_roads_changed(i, true);
         }
     }
 
+    /// Removes a road, and any references to it from `parallel_to` of other roads. The returned
+    /// road keeps its own `parallel_to`, so inserting it again restores them.
     pub fn remove_road(&mut self, id: RoadID) -> Road {
+        for r in self.roads[&id].parallel_to.clone() {
+            if let Some(other) = self.roads.get_mut(&r) {
+                other.parallel_to.remove(&id);
+            }
+        }
         for i in self.roads[&id].endpoints() {
+            self.clear_placement_blend(i);
             self.intersections
                 .get_mut(&i)
                 .unwrap()
                 .roads
                 .retain(|r| *r != id);
             // Since the roads are already sorted, removing doesn't break the sort.
-            self.update_i(i);
+            self.intersection_roads_changed(i, false);
         }
+        self.geometry_changed();
         self.roads.remove(&id).unwrap()
     }
 
+    /// Every change to the roads connected to an intersection must call this. It sorts the roads
+    /// (if `needs_sort`) and updates the intersection's geometry and movements -- immediately, or
+    /// at the end of the outermost `batch_edits`.
+    pub(crate) fn intersection_roads_changed(&mut self, i: IntersectionID, needs_sort: bool) {
+        *self.pending_updates.entry(i).or_insert(false) |= needs_sort;
+        if self.batch_depth == 0 {
+            self.flush_pending_updates();
+        }
+    }
+
+    /// Runs a sequence of edits, like inserting and removing many roads, and only updates the
+    /// affected intersections once at the end. In between, intersection geometry and movements
+    /// may be stale.
+    pub fn batch_edits<T>(&mut self, edits: impl FnOnce(&mut Self) -> T) -> T {
+        self.batch_depth += 1;
+        let result = edits(self);
+        self.batch_depth -= 1;
+        if self.batch_depth == 0 {
+            self.flush_pending_updates();
+        }
+        result
+    }
+
+    fn flush_pending_updates(&mut self) {
+        for (i, needs_sort) in std::mem::take(&mut self.pending_updates) {
+            // The intersection may have been removed since
+            if !self.intersections.contains_key(&i) {
+                continue;
+            }
+            if needs_sort {
+                self.sort_roads(i);
+            }
+            self.update_i(i);
+        }
+    }
+
     pub fn retain_roads<F: Fn(&Road) -> bool>(&mut self, f: F) {
         let mut remove = Vec::new();
         for r in self.roads.values() {
@@ -134,6 +343,7 @@ impl StreetNetwork {
 
     pub fn remove_intersection(&mut self, id: IntersectionID) {
         let i = self.intersections.remove(&id).unwrap();
+        self.geometry_changed();
         if !i.roads.is_empty() {
             panic!("Can't remove_intersection({id}), it has roads still connected");
         }
@@ -149,48 +359,88 @@ impl StreetNetwork {
     }
 
     pub(crate) fn start_debug_step<I: Into<String>>(&mut self, label: I) {
+        let diff = self
+            .debug_steps
+            .last()
+            .map(|prev| StepDiff::new(&prev.streets, self))
+            .unwrap_or_default();
         let copy = DebugStreets {
             label: label.into(),
-            streets: StreetNetwork {
-                roads: self.roads.clone(),
-                intersections: self.intersections.clone(),
-                boundary_polygon: self.boundary_polygon.clone(),
-                gps_bounds: self.gps_bounds.clone(),
-                config: self.config.clone(),
-                debug_steps: Vec::new(),
-                intersection_id_counter: self.intersection_id_counter,
-                road_id_counter: self.road_id_counter,
-            },
+            streets: self.copy_without_debugging(),
             points: Vec::new(),
             polylines: Vec::new(),
+            diff,
         };
         self.debug_steps.push(copy);
     }
 
+    /// Copies everything except debugging info and the original network.
+    fn copy_without_debugging(&self) -> StreetNetwork {
+        StreetNetwork {
+            roads: self.roads.clone(),
+            intersections: self.intersections.clone(),
+            traffic_islands: self.traffic_islands.clone(),
+            highway_areas: self.highway_areas.clone(),
+            indoor_paths: self.indoor_paths.clone(),
+            ferries: self.ferries.clone(),
+            block_barriers: self.block_barriers.clone(),
+            kerbs: self.kerbs.clone(),
+            boundary_polygon: self.boundary_polygon.clone(),
+            gps_bounds: self.gps_bounds.clone(),
+            config: self.config.clone(),
+            debug_steps: Vec::new(),
+            geometry_failures: BTreeMap::new(),
+            stale_turn_restrictions: Vec::new(),
+            original: None,
+            osm_index: self.osm_index.clone(),
+            audit_log: self.audit_log.clone(),
+            intersection_id_counter: self.intersection_id_counter,
+            road_id_counter: self.road_id_counter,
+            pending_updates: BTreeMap::new(),
+            batch_depth: 0,
+            current_transformation: None,
+            spatial_index: SpatialIndexCache::default(),
+        }
+    }
+
+    /// The network as it was right after import, before any transformations. Only kept if
+    /// `MapConfig::keep_original` is set.
+    pub fn original(&self) -> Option<&StreetNetwork> {
+        self.original.as_deref()
+    }
+
+    /// Before the first transformation, save a copy if `MapConfig::keep_original` is set.
+    pub(crate) fn maybe_keep_original(&mut self) {
+        if self.config.keep_original && self.original.is_none() {
+            self.original = Some(Box::new(self.copy_without_debugging()));
+        }
+    }
+
     /// Only start a new debug step if there's at least one already (indicating that debugging is
-    /// enabled).
-    pub(crate) fn maybe_start_debug_step<I: Into<String>>(&mut self, label: I) {
+    /// enabled). Custom transformations can use this and the `debug_*` methods below to show
+    /// their work in stepwise debugging.
+    pub fn maybe_start_debug_step<I: Into<String>>(&mut self, label: I) {
         if self.debug_steps.is_empty() {
             return;
         }
         self.start_debug_step(label);
     }
 
-    pub(crate) fn debug_intersection<I: Into<String>>(&mut self, i: IntersectionID, label: I) {
+    pub fn debug_intersection<I: Into<String>>(&mut self, i: IntersectionID, label: I) {
         if let Some(step) = self.debug_steps.last_mut() {
             step.points
                 .push((self.intersections[&i].polygon.center(), label.into()));
         }
     }
 
-    pub(crate) fn debug_road<I: Into<String>>(&mut self, r: RoadID, label: I) {
+    pub fn debug_road<I: Into<String>>(&mut self, r: RoadID, label: I) {
         if let Some(step) = self.debug_steps.last_mut() {
             step.polylines
                 .push((self.roads[&r].center_line.clone(), label.into()));
         }
     }
 
-    pub(crate) fn debug_point<I: Into<String>>(&mut self, pt: Pt2D, label: I) {
+    pub fn debug_point<I: Into<String>>(&mut self, pt: Pt2D, label: I) {
         if let Some(step) = self.debug_steps.last_mut() {
             step.points.push((pt, label.into()));
         }
@@ -0,0 +1,297 @@
+use anyhow::Result;
+use geojson::Feature;
+use geom::{Angle, Bounds, Distance, LonLat, Pt2D};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::render::serialize_features;
+use crate::{Corridor, RoadID, StreetNetwork};
+
+/// Point or interval data located along a road, like a speed survey or a crash.
+#[derive(Clone, Debug)]
+pub struct LinearEvent {
+    pub road: RoadID,
+    /// Measured along the road's `center_line`
+    pub start: Distance,
+    /// For interval events, where the interval ends. None for point events.
+    pub end: Option<Distance>,
+    /// Arbitrary data to export with the event
+    pub properties: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct RawEvent {
+    road: Option<usize>,
+    start: Option<f64>,
+    end: Option<f64>,
+    lon: Option<f64>,
+    lat: Option<f64>,
+    #[serde(default)]
+    properties: Map<String, Value>,
+}
+
+/// GPS positions further than this from any road aren't snapped
+const MAX_SNAP_DISTANCE: Distance = Distance::const_meters(50.0);
+
+impl StreetNetwork {
+    /// Finds the position at some distance along a road's `center_line`, and the angle of the
+    /// road there.
+    pub fn pt_along_road(&self, road: RoadID, dist: Distance) -> Option<(Pt2D, Angle)> {
+        self.roads.get(&road)?.center_line.dist_along(dist).ok()
+    }
+
+    /// Snaps a point to the nearest road `center_line`, returning the distance along it. Points
+    /// further than `max_dist` from every road aren't snapped.
+    pub fn locate_pt(&self, pt: Pt2D, max_dist: Distance) -> Option<(RoadID, Distance)> {
+        let mut bounds = Bounds::new();
+        bounds.update(pt);
+        bounds.min_x -= max_dist.inner_meters();
+        bounds.min_y -= max_dist.inner_meters();
+        bounds.max_x += max_dist.inner_meters();
+        bounds.max_y += max_dist.inner_meters();
+
+        let mut best: Option<(RoadID, Distance, Distance)> = None;
+        for r in self.spatial_index().roads(&bounds) {
+            let road = &self.roads[&r];
+            let snapped = road.center_line.project_pt(pt);
+            let dist_away = snapped.dist_to(pt);
+            if dist_away > max_dist || best.map(|(_, _, d)| dist_away >= d).unwrap_or(false) {
+                continue;
+            }
+            if let Some((dist, _)) = road.center_line.dist_along_of_point(snapped) {
+                best = Some((road.id, dist, dist_away));
+            }
+        }
+        best.map(|(road, dist, _)| (road, dist))
+    }
+
+    /// Parses a JSON list of events. Each event has either a `road` ID and `start` (and
+    /// optionally `end`) in meters, or a `lon` and `lat` snapped to the nearest road, plus any
+    /// `properties`.
+    pub fn parse_linear_events(&self, json: &str) -> Result<Vec<LinearEvent>> {
+        let mut events = Vec::new();
+        for raw in serde_json::from_str::<Vec<RawEvent>>(json)? {
+            let (road, start) = match (raw.road, raw.start, raw.lon, raw.lat) {
+                (Some(road), Some(start), _, _) => (RoadID(road), Distance::meters(start)),
+                (_, _, Some(lon), Some(lat)) => {
+                    let pt = LonLat::new(lon, lat).to_pt(&self.gps_bounds);
+                    match self.locate_pt(pt, MAX_SNAP_DISTANCE) {
+                        Some(pair) => pair,
+                        None => {
+                            warn!("Event at {lon}, {lat} isn't near any road; skipping");
+                            continue;
+                        }
+                    }
+                }
+                _ => bail!("An event needs either road and start, or lon and lat"),
+            };
+            if !self.roads.contains_key(&road) {
+                bail!("Event refers to unknown {road}");
+            }
+            events.push(LinearEvent {
+                road,
+                start,
+                end: raw.end.map(Distance::meters),
+                properties: raw.properties,
+            });
+        }
+        Ok(events)
+    }
+
+    /// Exports events with their geometry: points for point events, and the slice of the road's
+    /// `center_line` for interval events.
+    pub fn linear_events_to_geojson(&self, events: &[LinearEvent]) -> Result<String> {
+        let mut features = Vec::new();
+        for event in events {
+            let road = &self.roads[&event.road];
+            let mut f = match event.end {
+                Some(end) => {
+                    let (start, end) = if event.start <= end {
+                        (event.start, end)
+                    } else {
+                        (end, event.start)
+                    };
+                    let Ok(slice) = road.center_line.maybe_exact_slice(start, end) else {
+                        warn!("Interval event on {} is outside the road", event.road);
+                        continue;
+                    };
+                    Feature::from(slice.to_geojson(Some(&self.gps_bounds)))
+                }
+                None => {
+                    let Ok((pt, _)) = road.center_line.dist_along(event.start) else {
+                        warn!("Point event on {} is outside the road", event.road);
+                        continue;
+                    };
+                    Feature::from(pt.to_geojson(Some(&self.gps_bounds)))
+                }
+            };
+            for (key, value) in &event.properties {
+                f.set_property(key, value.clone());
+            }
+            f.set_property("road", event.road.0);
+            f.set_property("start", event.start.inner_meters());
+            if let Some(end) = event.end {
+                f.set_property("end", end.inner_meters());
+            }
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
+
+impl Corridor {
+    /// Converts a position on one of the corridor's roads to the distance from the start of the
+    /// corridor. Intersections along the way count towards the distance.
+    pub fn chainage_of(
+        &self,
+        streets: &StreetNetwork,
+        road: RoadID,
+        dist: Distance,
+    ) -> Option<Distance> {
+        let mut so_far = Distance::ZERO;
+        for (idx, r) in self.roads.iter().enumerate() {
+            let road_obj = &streets.roads[r];
+            if *r == road {
+                let forwards = road_obj.src_i == self.intersections[idx].id;
+                return Some(if forwards {
+                    so_far + road_obj.trim_start + dist
+                } else {
+                    so_far + road_obj.trim_end + (road_obj.center_line.length() - dist)
+                });
+            }
+            so_far += road_obj.untrimmed_length();
+        }
+        None
+    }
+
+    /// Converts a distance from the start of the corridor to a position on one of its roads.
+    /// Positions inside an intersection snap to the end of the nearest road.
+    pub fn position_at_chainage(
+        &self,
+        streets: &StreetNetwork,
+        chainage: Distance,
+    ) -> Option<(RoadID, Distance)> {
+        let mut so_far = Distance::ZERO;
+        for (idx, r) in self.roads.iter().enumerate() {
+            let road = &streets.roads[r];
+            let untrimmed = road.untrimmed_length();
+            if chainage <= so_far + untrimmed {
+                let len = road.center_line.length();
+                let forwards = road.src_i == self.intersections[idx].id;
+                let dist = if forwards {
+                    chainage - so_far - road.trim_start
+                } else {
+                    len - (chainage - so_far - road.trim_end)
+                };
+                return Some((road.id, dist.max(Distance::ZERO).min(len)));
+            }
+            so_far += untrimmed;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    /// Main Street runs east through three intersections. The second road points backwards, and
+    /// an unnamed side road leaves from the middle.
+    fn network() -> (StreetNetwork, [RoadID; 2]) {
+        let mut streets = StreetNetwork::blank();
+        let i0 = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::MapEdge);
+        let i1 = add_intersection(&mut streets, 100.0, 0.0, IntersectionKind::Intersection);
+        let i2 = add_intersection(&mut streets, 250.0, 0.0, IntersectionKind::MapEdge);
+        let i3 = add_intersection(&mut streets, 100.0, 100.0, IntersectionKind::MapEdge);
+        let main = &["highway=residential", "name=Main Street"];
+        let r1 = add_road(&mut streets, i0, i1, main);
+        let r2 = add_road(&mut streets, i2, i1, main);
+        add_road(&mut streets, i1, i3, &["highway=residential"]);
+        (streets, [r1, r2])
+    }
+
+    fn close(d1: Distance, d2: Distance) -> bool {
+        (d1 - d2).inner_meters().abs() < 0.01
+    }
+
+    #[test]
+    fn test_chainage_roundtrip() {
+        let (streets, [r1, r2]) = network();
+        let corridors = streets.find_corridors();
+        assert_eq!(1, corridors.len());
+        let corridor = &corridors[0];
+        assert_eq!(vec![r1, r2], corridor.roads);
+
+        for r in [r1, r2] {
+            let len = streets.roads[&r].center_line.length();
+            for dist in [Distance::ZERO, len / 3.0, len] {
+                let chainage = corridor.chainage_of(&streets, r, dist).unwrap();
+                let (road, back) = corridor.position_at_chainage(&streets, chainage).unwrap();
+                assert_eq!(r, road);
+                assert!(close(dist, back), "{dist} on {r} came back as {back}");
+            }
+        }
+
+        // The second road points backwards, so its end is closer to the start of the corridor
+        let road = &streets.roads[&r2];
+        assert!(close(
+            streets.roads[&r1].untrimmed_length() + road.trim_end,
+            corridor
+                .chainage_of(&streets, r2, road.center_line.length())
+                .unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_chainage_out_of_range() {
+        let (streets, [r1, r2]) = network();
+        let corridors = streets.find_corridors();
+        let corridor = &corridors[0];
+        let total = streets.roads[&r1].untrimmed_length() + streets.roads[&r2].untrimmed_length();
+
+        // The ends of the corridor snap to the ends of the trimmed roads
+        for (chainage, r) in [(Distance::ZERO, r1), (total, r2)] {
+            let (road, dist) = corridor.position_at_chainage(&streets, chainage).unwrap();
+            assert_eq!(r, road);
+            assert!(close(Distance::ZERO, dist));
+        }
+        assert_eq!(
+            None,
+            corridor.position_at_chainage(&streets, total + Distance::meters(1.0))
+        );
+
+        // The side road isn't part of the corridor
+        let side = *streets.roads.keys().max().unwrap();
+        assert_eq!(None, corridor.chainage_of(&streets, side, Distance::ZERO));
+    }
+
+    #[test]
+    fn test_locate_pt() {
+        let (streets, [r1, r2]) = network();
+        let (road, dist) = streets
+            .locate_pt(Pt2D::new(30.0, 5.0), Distance::meters(10.0))
+            .unwrap();
+        assert_eq!(r1, road);
+        assert!(close(
+            Distance::meters(30.0) - streets.roads[&r1].trim_start,
+            dist
+        ));
+
+        // The second road starts at its far end
+        let (road, dist) = streets
+            .locate_pt(Pt2D::new(200.0, -5.0), Distance::meters(10.0))
+            .unwrap();
+        assert_eq!(r2, road);
+        assert!(close(
+            Distance::meters(50.0) - streets.roads[&r2].trim_start,
+            dist
+        ));
+
+        assert_eq!(
+            None,
+            streets.locate_pt(Pt2D::new(30.0, 50.0), Distance::meters(10.0))
+        );
+    }
+}
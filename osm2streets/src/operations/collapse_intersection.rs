@@ -2,6 +2,7 @@ use std::collections::BTreeSet;
 
 use geom::{PolyLine, Pt2D};
 
+use crate::simplify::smooth_junction;
 use crate::{IntersectionID, RoadID, StreetNetwork};
 
 impl StreetNetwork {
@@ -31,53 +32,90 @@ impl StreetNetwork {
             }
         }
 
-        // We could be more careful merging highway_type, layer, name, and other attributes, but in
-        // practice, it doesn't matter for the short segments we're merging.
-        // TODO Here's an example where it'd be great to defer recalculating movements and
-        // geometry until insert_road() below.
-        let mut keep_road = self.remove_road(keep_r);
-        let destroy_road = self.remove_road(destroy_r);
-        self.intersections.remove(&i).unwrap();
+        if self.config.preserve_road_names
+            && self.roads[&keep_r].name != self.roads[&destroy_r].name
+        {
+            info!("Not collapsing degenerate {i}, because the road names differ");
+            return;
+        }
 
-        // Remember the merge
-        keep_road.osm_ids.extend(destroy_road.osm_ids);
+        // We could be more careful merging highway_type and layer, but in practice, it doesn't
+        // matter for the short segments we're merging.
 
-        // There are 4 cases, easy to understand on paper. Preserve the original direction of
-        // keep_r. Work with points, not PolyLine::extend. We want to RDP simplify before
-        // finalizing.
-        let mut new_pts;
-        let (new_src_i, new_dst_i) = if keep_road.dst_i == destroy_road.src_i {
-            new_pts = keep_road.reference_line.clone().into_points();
-            new_pts.extend(destroy_road.reference_line.into_points());
-            (keep_road.src_i, destroy_road.dst_i)
-        } else if keep_road.dst_i == destroy_road.dst_i {
-            new_pts = keep_road.reference_line.clone().into_points();
-            new_pts.extend(destroy_road.reference_line.reversed().into_points());
-            (keep_road.src_i, destroy_road.src_i)
-        } else if keep_road.src_i == destroy_road.src_i {
-            new_pts = destroy_road.reference_line.into_points();
-            new_pts.reverse();
-            new_pts.extend(keep_road.reference_line.clone().into_points());
-            (destroy_road.dst_i, keep_road.dst_i)
-        } else if keep_road.src_i == destroy_road.dst_i {
-            new_pts = destroy_road.reference_line.into_points();
-            new_pts.extend(keep_road.reference_line.clone().into_points());
-            (destroy_road.src_i, keep_road.dst_i)
+        // Remember where the two roads meet, to smooth later
+        let junction = if self.roads[&keep_r].dst_i == i {
+            self.roads[&keep_r].reference_line.last_pt()
         } else {
-            unreachable!()
+            self.roads[&keep_r].reference_line.first_pt()
         };
-        // Sanity check
-        assert!(i != new_src_i && i != new_dst_i);
-        // Simplify curves and dedupe points. The epsilon was tuned for only one location that was
-        // breaking
-        let epsilon = 1.0;
-        keep_road.reference_line = PolyLine::must_new(Pt2D::simplify_rdp(new_pts, epsilon));
-        keep_road.update_center_line(self.config.driving_side);
 
-        // Keep the same ID, but fix the endpoints
-        keep_road.src_i = new_src_i;
-        keep_road.dst_i = new_dst_i;
-        self.insert_road(keep_road);
+        // Only recalculate movements and geometry once, after the merged road is inserted
+        self.batch_edits(|streets| {
+            let mut keep_road = streets.remove_road(keep_r);
+            let destroy_road = streets.remove_road(destroy_r);
+            streets.intersections.remove(&i).unwrap();
+
+            // Remember the merge
+            if let Some(name) = keep_road.merge_names(&destroy_road) {
+                streets.audit(
+                    vec![keep_r, destroy_r],
+                    vec![i],
+                    format!(
+                        "Name conflict merging {destroy_r} into {keep_r}: kept {}, and {name} \
+                         became an alternate name",
+                        keep_road.name.as_ref().unwrap()
+                    ),
+                );
+            }
+            keep_road.osm_ids.extend(destroy_road.osm_ids);
+            // Inserting the merged road points these back at it
+            keep_road.parallel_to.extend(destroy_road.parallel_to);
+            keep_road.parallel_to.remove(&destroy_r);
+            keep_road
+                .parking_exclusions
+                .extend(destroy_road.parking_exclusions);
+
+            // There are 4 cases, easy to understand on paper. Preserve the original direction of
+            // keep_r. Work with points, not PolyLine::extend. We want to RDP simplify before
+            // finalizing.
+            let mut new_pts;
+            let (new_src_i, new_dst_i) = if keep_road.dst_i == destroy_road.src_i {
+                new_pts = keep_road.reference_line.clone().into_points();
+                new_pts.extend(destroy_road.reference_line.into_points());
+                (keep_road.src_i, destroy_road.dst_i)
+            } else if keep_road.dst_i == destroy_road.dst_i {
+                new_pts = keep_road.reference_line.clone().into_points();
+                new_pts.extend(destroy_road.reference_line.reversed().into_points());
+                (keep_road.src_i, destroy_road.src_i)
+            } else if keep_road.src_i == destroy_road.src_i {
+                new_pts = destroy_road.reference_line.into_points();
+                new_pts.reverse();
+                new_pts.extend(keep_road.reference_line.clone().into_points());
+                (destroy_road.dst_i, keep_road.dst_i)
+            } else if keep_road.src_i == destroy_road.dst_i {
+                new_pts = destroy_road.reference_line.into_points();
+                new_pts.extend(keep_road.reference_line.clone().into_points());
+                (destroy_road.src_i, keep_road.dst_i)
+            } else {
+                unreachable!()
+            };
+            // Sanity check
+            assert!(i != new_src_i && i != new_dst_i);
+            // Simplify curves and dedupe points. The epsilon was tuned for only one location that
+            // was breaking
+            let epsilon = 1.0;
+            keep_road.reference_line = PolyLine::must_new(smooth_junction(
+                Pt2D::simplify_rdp(new_pts, epsilon),
+                junction,
+                streets.config.degenerate_smoothing_max_deviation,
+            ));
+            keep_road.update_center_line(streets.config.driving_side);
+
+            // Keep the same ID, but fix the endpoints
+            keep_road.src_i = new_src_i;
+            keep_road.dst_i = new_dst_i;
+            streets.insert_road(keep_road);
+        });
 
         // We may need to fix up turn restrictions. destroy_r becomes keep_r.
         fix_turn_restrictions_near.retain(|r| self.roads.contains_key(r));
@@ -3,10 +3,28 @@ use std::collections::BTreeMap;
 use anyhow::Result;
 
 use crate::{
-    Crossing, IntersectionControl, IntersectionKind, RestrictionType, RoadID, StreetNetwork,
+    Crossing, InternedString, IntersectionControl, IntersectionKind, RestrictionType, RoadID,
+    StreetNetwork,
 };
 
 impl StreetNetwork {
+    /// If this road is named and no other road connected to either end has the same name,
+    /// collapsing it would lose the name.
+    pub(crate) fn name_lost_by_collapsing(&self, r: RoadID) -> Option<InternedString> {
+        let road = &self.roads[&r];
+        let name = road.name.clone()?;
+        let kept = road.endpoints().into_iter().any(|i| {
+            self.roads_per_intersection(i)
+                .into_iter()
+                .any(|other| other.id != r && other.has_name(&name))
+        });
+        if kept {
+            None
+        } else {
+            Some(name)
+        }
+    }
+
     /// Collapses a road, merging the two intersections together. This may also delete other roads
     /// connected two the merged intersection, if they become a loop on that intersection.
     pub fn collapse_short_road(&mut self, short_r: RoadID) -> Result<()> {
@@ -27,6 +45,12 @@ impl StreetNetwork {
             bail!("{short_r} touches a map edge");
         }
 
+        if self.config.preserve_road_names {
+            if let Some(name) = self.name_lost_by_collapsing(short_r) {
+                bail!("{short_r} is the only road named {name} here");
+            }
+        }
+
         // A previous call to this method on nearby roads could produce loop roads. If we later try
         // to collapse this, all we need to do is remove it.
         if keep_i == destroy_i {
@@ -78,8 +102,23 @@ impl StreetNetwork {
             keep_intersection.crossing =
                 merge_crossings(keep_intersection.crossing.take(), destroy_i.crossing.take());
 
+            // Devices only controlling the short road are now inside the intersection
+            keep_intersection
+                .traffic_control_devices
+                .append(&mut destroy_i.traffic_control_devices);
+            keep_intersection
+                .traffic_control_devices
+                .retain_mut(|device| {
+                    if device.approaches.is_empty() {
+                        return true;
+                    }
+                    device.approaches.retain(|r| *r != short_r);
+                    !device.approaches.is_empty()
+                });
+
             // Remember the merge
             keep_intersection.osm_ids.extend(destroy_i.osm_ids);
+            keep_intersection.merge_osm_tags(&destroy_i.osm_tags);
         }
 
         // Fix the endpoint of all roads connected to destroy_i.
@@ -105,8 +144,7 @@ impl StreetNetwork {
         }
 
         // We just connected a bunch of things to keep_i. Fix ordering and movements.
-        self.sort_roads(keep_i);
-        self.update_i(keep_i);
+        self.intersection_roads_changed(keep_i, true);
 
         // TODO Fix up turn restrictions. Many cases:
         // [ ] road we're deleting has simple restrictions
@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use geom::Pt2D;
+
+use crate::{GeometryFixture, IntersectionID, LaneEdit, RoadID, StreetNetwork};
+
+/// One edit to a `StreetNetwork`, for applying many at once with `apply_commands`.
+#[derive(Clone, Debug)]
+pub enum EditCommand {
+    CollapseShortRoad(RoadID),
+    CollapseIntersection(IntersectionID),
+    MoveIntersection(IntersectionID, Pt2D),
+    SplitRoad(RoadID, Pt2D),
+    EditLanes(RoadID, LaneEdit),
+}
+
+impl EditCommand {
+    /// Every intersection this command could change, or an error if the command refers to
+    /// something that doesn't exist.
+    fn neighborhood(&self, streets: &StreetNetwork) -> Result<BTreeSet<IntersectionID>> {
+        let mut result = BTreeSet::new();
+        match self {
+            EditCommand::CollapseShortRoad(r)
+            | EditCommand::SplitRoad(r, _)
+            | EditCommand::EditLanes(r, _) => {
+                let Some(road) = streets.roads.get(r) else {
+                    bail!("{r} doesn't exist");
+                };
+                result.extend(road.endpoints());
+            }
+            EditCommand::CollapseIntersection(i) | EditCommand::MoveIntersection(i, _) => {
+                if !streets.intersections.contains_key(i) {
+                    bail!("{i} doesn't exist");
+                }
+                result.insert(*i);
+            }
+        }
+        // The geometry of neighboring intersections changes too
+        for i in result.clone() {
+            for road in streets.roads_per_intersection(i) {
+                result.extend(road.endpoints());
+            }
+        }
+        Ok(result)
+    }
+
+    fn apply(&self, streets: &mut StreetNetwork) -> Result<()> {
+        match self {
+            EditCommand::CollapseShortRoad(r) => streets.collapse_short_road(*r),
+            EditCommand::CollapseIntersection(i) => {
+                if streets.intersections[i].roads.len() != 2 {
+                    bail!("Can't collapse {i}; it doesn't have exactly two roads");
+                }
+                streets.collapse_intersection(*i);
+                Ok(())
+            }
+            EditCommand::MoveIntersection(i, pt) => streets.move_intersection(*i, *pt),
+            EditCommand::SplitRoad(r, pt) => streets.split_road(*r, *pt).map(|_| ()),
+            EditCommand::EditLanes(r, edit) => streets.edit_lanes(*r, edit),
+        }
+    }
+}
+
+impl StreetNetwork {
+    /// Applies many edits, returning the result of each in order.
+    ///
+    /// The commands are grouped into waves of independent edits, whose neighborhoods don't
+    /// overlap. Within each wave, the edits themselves run one at a time, but intersection
+    /// geometry is only recalculated once at the end, in parallel. A command overlapping an
+    /// earlier one waits for a later wave, so it sees the result of that earlier edit.
+    pub fn apply_commands(&mut self, commands: Vec<EditCommand>) -> Vec<Result<()>> {
+        let mut results: Vec<Option<Result<()>>> = commands.iter().map(|_| None).collect();
+        let mut remaining: Vec<usize> = (0..commands.len()).collect();
+
+        while !remaining.is_empty() {
+            // Plan the wave based on the current state of the network
+            let mut claimed = BTreeSet::new();
+            let mut wave = Vec::new();
+            let mut deferred = Vec::new();
+            for idx in remaining {
+                match commands[idx].neighborhood(self) {
+                    Ok(neighborhood) => {
+                        if neighborhood.is_disjoint(&claimed) {
+                            wave.push(idx);
+                        } else {
+                            deferred.push(idx);
+                        }
+                        // Even deferred commands claim their neighborhood, so later commands
+                        // don't skip ahead of them
+                        claimed.extend(neighborhood);
+                    }
+                    Err(err) => {
+                        results[idx] = Some(Err(err));
+                    }
+                }
+            }
+            remaining = deferred;
+
+            self.batch_depth += 1;
+            for idx in wave {
+                results[idx] = Some(commands[idx].apply(self));
+            }
+            self.batch_depth -= 1;
+            if self.batch_depth == 0 {
+                self.flush_pending_updates_in_parallel();
+            }
+        }
+
+        results.into_iter().map(|x| x.unwrap()).collect()
+    }
+
+    /// Like `flush_pending_updates`, but calculates intersection geometry in parallel
+    fn flush_pending_updates_in_parallel(&mut self) {
+        let mut fixtures = Vec::new();
+        for (i, needs_sort) in std::mem::take(&mut self.pending_updates) {
+            // The intersection may have been removed since
+            if !self.intersections.contains_key(&i) {
+                continue;
+            }
+            if needs_sort {
+                self.sort_roads(i);
+            }
+            fixtures.push(self.geometry_fixture(i));
+        }
+
+        let ids: Vec<IntersectionID> = fixtures.iter().map(|f| f.intersection_id).collect();
+        let results = calculate_all(fixtures);
+        for (i, result) in ids.iter().zip(results) {
+            self.apply_geometry(*i, result);
+        }
+        for i in ids {
+            self.update_movements(i);
+        }
+    }
+}
+
+/// Spawning threads costs more than calculating a few intersections, so each thread should get at
+/// least this many
+#[cfg(not(target_arch = "wasm32"))]
+const MIN_FIXTURES_PER_THREAD: usize = 16;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn calculate_all(fixtures: Vec<GeometryFixture>) -> Vec<Result<crate::geometry::Results>> {
+    let threads = std::thread::available_parallelism()
+        .map(|x| x.get())
+        .unwrap_or(1)
+        .min(fixtures.len() / MIN_FIXTURES_PER_THREAD);
+    if threads <= 1 {
+        return fixtures.into_iter().map(|f| f.calculate()).collect();
+    }
+    calculate_in_threads(fixtures, threads)
+}
+
+/// Splits the fixtures into one chunk per thread, keeping the results in the original order
+#[cfg(not(target_arch = "wasm32"))]
+fn calculate_in_threads(
+    fixtures: Vec<GeometryFixture>,
+    threads: usize,
+) -> Vec<Result<crate::geometry::Results>> {
+    let chunk_size = ((fixtures.len() + threads - 1) / threads).max(1);
+    let mut chunks: Vec<Vec<GeometryFixture>> = Vec::new();
+    let mut fixtures = fixtures.into_iter().peekable();
+    while fixtures.peek().is_some() {
+        chunks.push(fixtures.by_ref().take(chunk_size).collect());
+    }
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
+                scope.spawn(move || chunk.into_iter().map(|f| f.calculate()).collect::<Vec<_>>())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// There are no threads in WebAssembly
+#[cfg(target_arch = "wasm32")]
+fn calculate_all(fixtures: Vec<GeometryFixture>) -> Vec<Result<crate::geometry::Results>> {
+    fixtures.into_iter().map(|f| f.calculate()).collect()
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    /// A grid of `n` by `n` intersections
+    fn grid(n: usize) -> StreetNetwork {
+        let mut streets = StreetNetwork::blank();
+        let mut ids = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                ids.push(add_intersection(
+                    &mut streets,
+                    100.0 * x as f64,
+                    100.0 * y as f64,
+                    IntersectionKind::Intersection,
+                ));
+            }
+        }
+        let residential = &["highway=residential"];
+        for y in 0..n {
+            for x in 0..n {
+                if x + 1 < n {
+                    add_road(
+                        &mut streets,
+                        ids[y * n + x],
+                        ids[y * n + x + 1],
+                        residential,
+                    );
+                }
+                if y + 1 < n {
+                    add_road(
+                        &mut streets,
+                        ids[y * n + x],
+                        ids[(y + 1) * n + x],
+                        residential,
+                    );
+                }
+            }
+        }
+        streets
+    }
+
+    #[test]
+    fn test_threads_match_sequential() {
+        let streets = grid(5);
+        let fixtures: Vec<GeometryFixture> = streets
+            .intersections
+            .keys()
+            .map(|i| streets.geometry_fixture(*i))
+            .collect();
+
+        let sequential: Vec<_> = fixtures
+            .clone()
+            .into_iter()
+            .map(|f| f.calculate().unwrap())
+            .collect();
+        // An uneven split, so the last chunk is smaller
+        let threaded = calculate_in_threads(fixtures, 4);
+        assert_eq!(sequential.len(), threaded.len());
+        for (expected, actual) in sequential.into_iter().zip(threaded) {
+            let actual = actual.unwrap();
+            assert_eq!(expected.intersection_id, actual.intersection_id);
+            assert_eq!(expected.intersection_polygon, actual.intersection_polygon);
+            assert_eq!(expected.algorithm, actual.algorithm);
+            assert_eq!(expected.trim_starts, actual.trim_starts);
+            assert_eq!(expected.trim_ends, actual.trim_ends);
+        }
+    }
+
+    #[test]
+    fn test_small_batches_match_sequential() {
+        let mut streets = grid(3);
+        let roads: Vec<RoadID> = streets.roads.keys().take(2).cloned().collect();
+        let mut expected = streets.clone();
+        for r in &roads {
+            expected.edit_lanes(*r, &LaneEdit::Delete(0)).unwrap();
+        }
+
+        let results = streets.apply_commands(
+            roads
+                .iter()
+                .map(|r| EditCommand::EditLanes(*r, LaneEdit::Delete(0)))
+                .collect(),
+        );
+        assert!(results.iter().all(|x| x.is_ok()));
+        for (id, i) in &expected.intersections {
+            assert_eq!(i.polygon, streets.intersections[id].polygon);
+        }
+        for (id, r) in &expected.roads {
+            assert_eq!(r.trim_start, streets.roads[id].trim_start);
+            assert_eq!(r.trim_end, streets.roads[id].trim_end);
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use geom::Distance;
+use serde::{Deserialize, Serialize};
+
+use osm2lanes::RoadLayout;
+
+use crate::{Direction, DrivingSide, LaneSpec, LaneType, Road, RoadID, StreetNetwork};
+
+/// One change to the lanes of a single road. Lanes are referred to by index into
+/// `lane_specs_ltr`, as of before the edit. The road's total width never changes; the physical
+/// lanes on either side of the edited lane shrink or grow, in proportion to their widths, to make
+/// room or fill the gap.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LaneEdit {
+    /// Insert a new lane, so it winds up at `index`. Inserting at `lane_specs_ltr.len()` adds a
+    /// lane on the right. If `width` isn't specified, the new lane copies the width of a lane next
+    /// to it, preferring one of the same type.
+    Insert {
+        index: usize,
+        lt: LaneType,
+        dir: Direction,
+        width: Option<Distance>,
+    },
+    /// Insert a zero-width lane that only describes access, like contraflow cycling. See
+    /// `LaneSpec::is_virtual`.
+    InsertVirtual {
+        index: usize,
+        lt: LaneType,
+        dir: Direction,
+    },
+    /// The deleted lane's width goes to its neighbours.
+    Delete(usize),
+    /// The difference in width is taken from or given to the lane's neighbours, so a road with
+    /// only one physical lane can't change width.
+    ChangeWidth(usize, Distance),
+    FlipDirection(usize),
+    /// Changes the type of a lane, keeping its width, so the road's total width doesn't change.
+    /// Converting parking to a bike lane is one use.
+    ChangeType(usize, LaneType),
+}
+
+impl Road {
+    /// Changes the lanes of this road, then regenerates the center line, since the lanes may have
+    /// moved relative to the reference line. Anything referring to lanes by index, like
+    /// `lane_surfaces`, is updated. If the neighbouring lanes are too narrow to keep the total
+    /// width, nothing changes and this fails.
+    /// The intersections on either end aren't updated; use `StreetNetwork::edit_lanes` for that.
+    pub fn edit_lanes(&mut self, edit: &LaneEdit, driving_side: DrivingSide) -> Result<()> {
+        let id = self.id;
+        let num_lanes = self.lane_specs_ltr.len();
+        let check = |idx: usize| {
+            if idx >= num_lanes {
+                bail!("{id} only has {num_lanes} lanes, not {}", idx + 1);
+            }
+            Ok(())
+        };
+
+        match edit {
+            LaneEdit::Insert {
+                index,
+                lt,
+                dir,
+                width,
+            } => {
+                if *index > num_lanes {
+                    bail!("Can't insert a lane at {index}; {id} has {num_lanes}");
+                }
+                let width = match width {
+                    Some(width) => *width,
+                    None => self
+                        .neighbouring_lane_width(*index, *lt)
+                        .unwrap_or_else(|| {
+                            LaneSpec::typical_lane_widths(*lt, &self.highway_type)[0].0
+                        }),
+                };
+                if width <= Distance::ZERO {
+                    bail!("A new lane must have a positive width");
+                }
+                self.absorb_width(self.neighbouring_lanes(*index, *index), -width)?;
+                self.lane_specs_ltr.insert(
+                    *index,
+                    LaneSpec {
+                        lt: *lt,
+                        dir: *dir,
+                        width,
+                        allowed_turns: Default::default(),
+                        is_virtual: false,
+                        shoulder_access: None,
+                        max_speed: None,
+                        bus_hours: None,
+                        lane: None,
+                    },
+                );
+                let index = *index;
+                self.remap_lane_indices(|idx| Some(if idx >= index { idx + 1 } else { idx }));
+            }
+            LaneEdit::InsertVirtual { index, lt, dir } => {
+                if *index > num_lanes {
+                    bail!("Can't insert a lane at {index}; {id} has {num_lanes}");
+                }
+                self.lane_specs_ltr.insert(
+                    *index,
+                    LaneSpec {
+                        lt: *lt,
+                        dir: *dir,
+                        width: Distance::ZERO,
+                        allowed_turns: Default::default(),
+                        is_virtual: true,
+                        shoulder_access: None,
+                        max_speed: None,
+                        bus_hours: None,
+                        lane: None,
+                    },
+                );
+                let index = *index;
+                self.remap_lane_indices(|idx| Some(if idx >= index { idx + 1 } else { idx }));
+            }
+            LaneEdit::Delete(index) => {
+                check(*index)?;
+                if self
+                    .lane_specs_ltr
+                    .iter()
+                    .enumerate()
+                    .all(|(idx, lane)| idx == *index || lane.is_virtual)
+                {
+                    bail!("Can't delete the only physical lane of {id}");
+                }
+                let width = self.lane_specs_ltr[*index].width;
+                if !self.lane_specs_ltr[*index].is_virtual {
+                    self.absorb_width(self.neighbouring_lanes(*index, *index + 1), width)?;
+                }
+                self.lane_specs_ltr.remove(*index);
+                let index = *index;
+                self.remap_lane_indices(|idx| match idx.cmp(&index) {
+                    std::cmp::Ordering::Less => Some(idx),
+                    std::cmp::Ordering::Equal => None,
+                    std::cmp::Ordering::Greater => Some(idx - 1),
+                });
+            }
+            LaneEdit::ChangeWidth(index, width) => {
+                check(*index)?;
+                if *width <= Distance::ZERO {
+                    bail!("A lane must have a positive width");
+                }
+                if self.lane_specs_ltr[*index].is_virtual {
+                    bail!("Virtual lanes don't have a width");
+                }
+                let delta = self.lane_specs_ltr[*index].width - *width;
+                self.absorb_width(self.neighbouring_lanes(*index, *index + 1), delta)?;
+                self.lane_specs_ltr[*index].width = *width;
+            }
+            LaneEdit::FlipDirection(index) => {
+                check(*index)?;
+                let lane = &mut self.lane_specs_ltr[*index];
+                lane.dir = lane.dir.opposite();
+                // Turn restrictions made sense for the old direction
+                lane.allowed_turns = Default::default();
+            }
+            LaneEdit::ChangeType(index, lt) => {
+                check(*index)?;
+                let lane = &mut self.lane_specs_ltr[*index];
+                lane.lt = *lt;
+                lane.allowed_turns = Default::default();
+            }
+        }
+
+        self.update_center_line(driving_side);
+        Ok(())
+    }
+
+    /// The width of a physical lane on either side of `index`, preferring one with the same type.
+    fn neighbouring_lane_width(&self, index: usize, lt: LaneType) -> Option<Distance> {
+        let neighbours: Vec<&LaneSpec> = [index.checked_sub(1), Some(index)]
+            .into_iter()
+            .flatten()
+            .filter_map(|idx| self.lane_specs_ltr.get(idx))
+            .filter(|lane| !lane.is_virtual)
+            .collect();
+        neighbours
+            .iter()
+            .find(|lane| lane.lt == lt)
+            .or_else(|| neighbours.first())
+            .map(|lane| lane.width)
+    }
+
+    /// The closest physical lane before `before` and the closest at or after `after`
+    fn neighbouring_lanes(&self, before: usize, after: usize) -> Vec<usize> {
+        let is_physical = |idx: &usize| !self.lane_specs_ltr[*idx].is_virtual;
+        let left = (0..before).rev().find(is_physical);
+        let right = (after..self.lane_specs_ltr.len()).find(is_physical);
+        left.into_iter().chain(right).collect()
+    }
+
+    /// Adds `delta`, which may be negative, to the width of these lanes, in proportion to their
+    /// current widths. Nothing changes if any lane would wind up without a positive width.
+    fn absorb_width(&mut self, lanes: Vec<usize>, delta: Distance) -> Result<()> {
+        if lanes.is_empty() {
+            bail!("{} has no other lanes to keep its width", self.id);
+        }
+        let available: Distance = lanes
+            .iter()
+            .map(|idx| self.lane_specs_ltr[*idx].width)
+            .sum();
+        if available + delta <= Distance::ZERO {
+            bail!(
+                "The lanes of {} next to the edit are only {available} wide, not enough to keep \
+                 its width",
+                self.id
+            );
+        }
+        for idx in lanes {
+            let lane = &mut self.lane_specs_ltr[idx];
+            lane.width = lane.width + delta * (lane.width / available);
+        }
+        Ok(())
+    }
+
+    /// Changes the index of anything attached to a lane. Returning `None` removes it.
+    pub(crate) fn remap_lane_indices<F: Fn(usize) -> Option<usize>>(&mut self, remap: F) {
+        self.lane_surfaces = std::mem::take(&mut self.lane_surfaces)
+            .into_iter()
+            .filter_map(|(idx, surface)| Some((remap(idx)?, surface)))
+            .collect();
+        self.path_attributes = std::mem::take(&mut self.path_attributes)
+            .into_iter()
+            .filter_map(|(idx, attributes)| Some((remap(idx)?, attributes)))
+            .collect();
+        self.lane_changes = std::mem::take(&mut self.lane_changes)
+            .into_iter()
+            .filter_map(|(idx, change)| Some((remap(idx)?, change)))
+            .collect();
+        self.service_lanes = std::mem::take(&mut self.service_lanes)
+            .into_iter()
+            .filter_map(&remap)
+            .collect();
+        self.lane_features = std::mem::take(&mut self.lane_features)
+            .into_iter()
+            .filter_map(|mut feature| {
+                feature.lane = remap(feature.lane)?;
+                Some(feature)
+            })
+            .collect();
+    }
+}
+
+impl StreetNetwork {
+    /// Changes the lanes of one road, then updates the geometry of the intersections on both ends.
+    pub fn edit_lanes(&mut self, r: RoadID, edit: &LaneEdit) -> Result<()> {
+        let driving_side = self.config.driving_side;
+        let Some(road) = self.roads.get_mut(&r) else {
+            bail!("{r} doesn't exist");
+        };
+        road.edit_lanes(edit, driving_side)?;
+        for i in road.endpoints() {
+            self.refresh_placement_blend(i);
+            self.update_i(i);
+        }
+        Ok(())
+    }
+}
+
+/// One OSM tag that a lane edit would change.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TagChange {
+    pub key: String,
+    /// None if the tag would be added
+    pub old: Option<String>,
+    /// None if the tag would be removed
+    pub new: Option<String>,
+}
+
+impl StreetNetwork {
+    /// Describes how the OSM tags of a road would change after a lane edit, without applying it.
+    /// Both the current and edited lanes are described with `RoadLayout::to_tags`, so only the
+    /// tags that differ are returned. Widths aren't described by those tags, so changing a lane's
+    /// width produces no changes.
+    pub fn preview_lane_edit_tags(&self, r: RoadID, edit: &LaneEdit) -> Result<Vec<TagChange>> {
+        let Some(road) = self.roads.get(&r) else {
+            bail!("{r} doesn't exist");
+        };
+        let mut edited = road.clone();
+        edited.edit_lanes(edit, self.config.driving_side)?;
+
+        let describe = |road: &Road| {
+            RoadLayout {
+                highway: road.highway_type.to_string(),
+                lanes: road.lane_specs_ltr.clone(),
+                placement: road.reference_line_placement,
+            }
+            .to_tags()
+        };
+        let before = describe(road);
+        let after = describe(&edited);
+
+        let keys: BTreeSet<&String> = before.inner().keys().chain(after.inner().keys()).collect();
+        let mut changes = Vec::new();
+        for key in keys {
+            let old = before.get(key).cloned();
+            let new = after.get(key).cloned();
+            if old != new {
+                changes.push(TagChange {
+                    key: key.clone(),
+                    old,
+                    new,
+                });
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Takes a `LaneEdit` as JSON, and returns `preview_lane_edit_tags` as a JSON array.
+    pub fn preview_lane_edit_tags_json(&self, r: RoadID, edit_json: &str) -> Result<String> {
+        let edit: LaneEdit = serde_json::from_str(edit_json)?;
+        Ok(serde_json::to_string(
+            &self.preview_lane_edit_tags(r, &edit)?,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use abstutil::Tags;
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{osm, IntersectionID, LaneChange, MapConfig};
+
+    #[test]
+    fn test_insert_and_delete_remap_lanes() {
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        let mut road = Road::new(
+            RoadID(0),
+            vec![osm::WayID(1)],
+            IntersectionID(0),
+            IntersectionID(1),
+            PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            tags,
+            &MapConfig::default(),
+        );
+        assert!(road.lane_specs_ltr.len() >= 2);
+        road.lane_specs_ltr[0].width = Distance::meters(2.7);
+        road.lane_surfaces.insert(0, "gravel".into());
+        road.lane_surfaces.insert(1, "asphalt".into());
+        road.lane_changes.insert(1, LaneChange::No);
+        road.service_lanes.insert(1);
+
+        let total_width = road.total_width();
+        let close_enough = |a: Distance, b: Distance| (a - b).inner_meters().abs() < 0.01;
+
+        // Without a width, the new lane copies its neighbour. The neighbours shrink to make room.
+        let lt = road.lane_specs_ltr[0].lt;
+        road.edit_lanes(
+            &LaneEdit::Insert {
+                index: 1,
+                lt,
+                dir: Direction::Forward,
+                width: None,
+            },
+            DrivingSide::Right,
+        )
+        .unwrap();
+        assert_eq!(road.lane_specs_ltr[1].width, Distance::meters(2.7));
+        assert!(close_enough(road.total_width(), total_width));
+        assert_eq!(
+            road.lane_surfaces,
+            BTreeMap::from([(0, "gravel".into()), (2, "asphalt".into())])
+        );
+        assert_eq!(road.lane_changes, BTreeMap::from([(2, LaneChange::No)]));
+        assert_eq!(road.service_lanes, BTreeSet::from([2]));
+
+        // Deleting a lane drops what was attached to it and shifts the rest
+        road.edit_lanes(&LaneEdit::Delete(0), DrivingSide::Right)
+            .unwrap();
+        assert!(close_enough(road.total_width(), total_width));
+        assert_eq!(road.lane_surfaces, BTreeMap::from([(1, "asphalt".into())]));
+        assert_eq!(road.lane_changes, BTreeMap::from([(1, LaneChange::No)]));
+        assert_eq!(road.service_lanes, BTreeSet::from([1]));
+
+        // Widening a lane narrows its neighbours, and fails if they can't make room
+        let before = road.lane_specs_ltr.clone();
+        road.edit_lanes(
+            &LaneEdit::ChangeWidth(1, total_width + Distance::meters(1.0)),
+            DrivingSide::Right,
+        )
+        .unwrap_err();
+        assert_eq!(road.lane_specs_ltr, before);
+        let width = road.lane_specs_ltr[1].width + Distance::meters(0.5);
+        road.edit_lanes(&LaneEdit::ChangeWidth(1, width), DrivingSide::Right)
+            .unwrap();
+        assert_eq!(road.lane_specs_ltr[1].width, width);
+        assert!(close_enough(road.total_width(), total_width));
+    }
+}
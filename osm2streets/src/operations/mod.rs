@@ -2,5 +2,13 @@
 
 mod collapse_intersection;
 mod collapse_short_road;
+mod commands;
+mod edit_lanes;
+mod move_intersection;
+mod split_road;
+mod subset;
 mod update_geometry;
 pub mod zip_sidepath;
+
+pub use commands::EditCommand;
+pub use edit_lanes::{LaneEdit, TagChange};
@@ -0,0 +1,113 @@
+use anyhow::Result;
+use geom::{PolyLine, Pt2D};
+
+use crate::{IntersectionID, StreetNetwork};
+
+impl StreetNetwork {
+    /// Moves an intersection to a new point, dragging the ends of all connected roads with it, then
+    /// recalculates the geometry and movements of the intersection and its neighbors. If any road
+    /// would become degenerate, nothing is changed and an error is returned.
+    pub fn move_intersection(&mut self, i: IntersectionID, new_pt: Pt2D) -> Result<()> {
+        if !self.intersections.contains_key(&i) {
+            bail!("{i} doesn't exist");
+        }
+
+        // Calculate all of the new reference lines first, so a failure doesn't leave things half
+        // moved
+        let mut new_reference_lines = Vec::new();
+        for road in self.roads_per_intersection(i) {
+            let mut pts = road.reference_line.clone().into_points();
+            if road.src_i == i {
+                pts[0] = new_pt;
+            }
+            if road.dst_i == i {
+                *pts.last_mut().unwrap() = new_pt;
+            }
+            match PolyLine::new(pts) {
+                Ok(pl) => new_reference_lines.push((road.id, pl)),
+                Err(err) => bail!("Moving {i} would break {}: {err}", road.id),
+            }
+        }
+
+        let mut neighbors = Vec::new();
+        for (r, pl) in new_reference_lines {
+            let road = self.roads.get_mut(&r).unwrap();
+            road.reference_line = pl;
+            road.update_center_line(self.config.driving_side);
+            neighbors.push(road.other_side(i));
+        }
+
+        // The angles of roads changed at both ends, so re-sort everything
+        self.batch_edits(|streets| {
+            streets.intersection_roads_changed(i, true);
+            for neighbor in neighbors {
+                streets.intersection_roads_changed(neighbor, true);
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Distance;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    #[test]
+    fn test_move_intersection() {
+        let mut streets = StreetNetwork::blank();
+        let mut intersection = |x, y, kind| add_intersection(&mut streets, x, y, kind);
+        let center = intersection(100.0, 100.0, IntersectionKind::Intersection);
+        let west = intersection(0.0, 100.0, IntersectionKind::MapEdge);
+        let east = intersection(200.0, 100.0, IntersectionKind::MapEdge);
+        let south = intersection(100.0, 200.0, IntersectionKind::MapEdge);
+        // Roads pointing both into and out of the intersection
+        let roads = [
+            add_road(&mut streets, west, center, &["highway=residential"]),
+            add_road(&mut streets, center, east, &["highway=residential"]),
+            add_road(&mut streets, center, south, &["highway=residential"]),
+        ];
+        let i = center.0;
+
+        let new_pt = Pt2D::new(100.0, 80.0);
+        streets.move_intersection(i, new_pt).unwrap();
+        for (r, far_end) in roads.iter().zip([west.1, east.1, south.1]) {
+            let road = &streets.roads[r];
+            let (near, far) = if road.src_i == i {
+                (
+                    road.reference_line.first_pt(),
+                    road.reference_line.last_pt(),
+                )
+            } else {
+                (
+                    road.reference_line.last_pt(),
+                    road.reference_line.first_pt(),
+                )
+            };
+            assert_eq!(new_pt, near);
+            assert_eq!(far_end, far);
+
+            // The trimmed center line ends near the new position too
+            let trimmed_end = if road.src_i == i {
+                road.center_line.first_pt()
+            } else {
+                road.center_line.last_pt()
+            };
+            assert!(trimmed_end.dist_to(new_pt) < Distance::meters(10.0));
+        }
+        let polygon = &streets.intersections[&i].polygon;
+        assert!(polygon.contains_pt(new_pt));
+        assert!(!polygon.contains_pt(center.1));
+        assert_eq!(3, streets.intersections[&i].roads.len());
+
+        // Moving onto the far end of a road would make it degenerate, so nothing changes
+        assert!(streets.move_intersection(i, west.1).is_err());
+        assert_eq!(new_pt, streets.roads[&roads[0]].reference_line.last_pt());
+        assert!(streets
+            .move_intersection(IntersectionID(999), new_pt)
+            .is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use anyhow::Result;
+use geom::{Distance, Pt2D};
+
+use crate::{
+    IntersectionControl, IntersectionID, IntersectionKind, Road, RoadID, StaleReason,
+    StaleTurnRestriction, StopLine, StreetNetwork,
+};
+
+impl StreetNetwork {
+    /// Splits a road in two at the point along its `reference_line` closest to `pt`, creating a
+    /// new intersection there. Returns the new intersection. The first piece keeps the original
+    /// road's `src_i` and the second its `dst_i`.
+    ///
+    /// Turn restrictions from or to the road move to the piece at the right end. Restrictions via
+    /// the road can't be expressed anymore, so they're removed and recorded in
+    /// `stale_turn_restrictions`.
+    pub fn split_road(&mut self, r: RoadID, pt: Pt2D) -> Result<IntersectionID> {
+        let (dist, split_pt) = {
+            let road = &self.roads[&r];
+            let split_pt = road.reference_line.project_pt(pt);
+            let Some((dist, _)) = road.reference_line.dist_along_of_point(split_pt) else {
+                bail!("Can't find {pt} along {r}");
+            };
+            if dist <= Distance::ZERO || dist >= road.reference_line.length() {
+                bail!("Splitting {r} at {pt} would leave an empty piece");
+            }
+            (dist, split_pt)
+        };
+        // Needed later to fix up center_line-relative distances
+        let center_dist = {
+            let road = &self.roads[&r];
+            road.center_line
+                .dist_along_of_point(road.center_line.project_pt(split_pt))
+                .map(|(d, _)| d)
+                .unwrap_or(dist)
+        };
+
+        let new_i = self.insert_intersection(
+            Vec::new(),
+            split_pt,
+            IntersectionKind::Connection,
+            IntersectionControl::Uncontrolled,
+        );
+
+        let road = self.remove_road(r);
+        let id1 = self.next_road_id();
+        let id2 = self.next_road_id();
+
+        let mut first = road.clone();
+        first.id = id1;
+        first.dst_i = new_i;
+        first.reference_line = road.reference_line.exact_slice(Distance::ZERO, dist);
+        first.stop_line_end = StopLine::dummy();
+
+        let mut second = road.clone();
+        second.id = id2;
+        second.src_i = new_i;
+        second.osm_start_node = None;
+        second.reference_line = road
+            .reference_line
+            .exact_slice(dist, road.reference_line.length());
+        second.stop_line_start = StopLine::dummy();
+        // Stop lines are relative to the reference_line
+        let stop_line = &mut second.stop_line_end;
+        stop_line.vehicle_distance = stop_line.vehicle_distance.map(|d| d - dist);
+        stop_line.bike_distance = stop_line.bike_distance.map(|d| d - dist);
+
+        for piece in [&mut first, &mut second] {
+            piece.trim_start = Distance::ZERO;
+            piece.trim_end = Distance::ZERO;
+            piece.trim_provenance = [None, None];
+            piece.update_center_line(self.config.driving_side);
+            // Restrictions leading out of the road apply at the end touching the destination
+            let keep_i = if piece.id == id1 {
+                road.src_i
+            } else {
+                road.dst_i
+            };
+            piece.turn_restrictions.retain(|(_, to)| {
+                self.roads
+                    .get(to)
+                    .map(|x| x.src_i == keep_i || x.dst_i == keep_i)
+                    .unwrap_or(false)
+            });
+            let keep_targets: Vec<RoadID> =
+                piece.turn_restrictions.iter().map(|(_, to)| *to).collect();
+            piece
+                .turn_restriction_relations
+                .retain(|to, _| keep_targets.contains(to));
+            piece.complicated_turn_restrictions.retain(|(via, _)| {
+                self.roads
+                    .get(via)
+                    .map(|x| x.src_i == keep_i || x.dst_i == keep_i)
+                    .unwrap_or(false)
+            });
+        }
+        first.parking_exclusions.retain(|(pt, _)| {
+            road.reference_line
+                .dist_along_of_point(road.reference_line.project_pt(*pt))
+                .map(|(d, _)| d < dist)
+                .unwrap_or(true)
+        });
+        second
+            .parking_exclusions
+            .retain(|x| !first.parking_exclusions.contains(x));
+        first.lane_features.retain(|x| x.dist < center_dist);
+        second.lane_features.retain(|x| x.dist >= center_dist);
+        for feature in &mut second.lane_features {
+            feature.dist = feature.dist - center_dist;
+        }
+
+        // Other roads with turn restrictions to this one need to point at the right piece
+        for other in self.roads.values_mut() {
+            let replacement = if other.src_i == road.src_i || other.dst_i == road.src_i {
+                id1
+            } else if other.src_i == road.dst_i || other.dst_i == road.dst_i {
+                id2
+            } else {
+                continue;
+            };
+            for (_, to) in &mut other.turn_restrictions {
+                if *to == r {
+                    *to = replacement;
+                }
+            }
+            if let Some(relation) = other.turn_restriction_relations.remove(&r) {
+                other
+                    .turn_restriction_relations
+                    .insert(replacement, relation);
+            }
+        }
+        self.split_complicated_turn_restrictions(&road, id1, id2);
+
+        self.insert_road(first);
+        self.insert_road(second);
+
+        Ok(new_i)
+    }
+
+    /// After splitting `road` into `id1` and `id2`, restrictions to it go to the piece touching
+    /// the 'via' road. Restrictions via it are removed.
+    fn split_complicated_turn_restrictions(&mut self, road: &Road, id1: RoadID, id2: RoadID) {
+        let mut stale = Vec::new();
+        let froms: Vec<RoadID> = self.roads.keys().cloned().collect();
+        for from in froms {
+            let mut restrictions = std::mem::take(
+                &mut self
+                    .roads
+                    .get_mut(&from)
+                    .unwrap()
+                    .complicated_turn_restrictions,
+            );
+            restrictions.retain_mut(|(via, to)| {
+                if *via == road.id {
+                    stale.push(StaleTurnRestriction {
+                        from,
+                        via: Some(*via),
+                        to: *to,
+                        restriction: None,
+                        relation: None,
+                        problem: StaleReason::SplitVia,
+                    });
+                    return false;
+                }
+                if *to == road.id {
+                    if let Some(via_road) = self.roads.get(via) {
+                        if via_road.src_i == road.src_i || via_road.dst_i == road.src_i {
+                            *to = id1;
+                        } else if via_road.src_i == road.dst_i || via_road.dst_i == road.dst_i {
+                            *to = id2;
+                        }
+                    }
+                }
+                true
+            });
+            self.roads
+                .get_mut(&from)
+                .unwrap()
+                .complicated_turn_restrictions = restrictions;
+        }
+
+        for x in &stale {
+            warn!("Removing turn restriction via split road: {:?}", x);
+        }
+        self.stale_turn_restrictions.extend(stale);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+
+    #[test]
+    fn test_split_complicated_turn_restrictions() {
+        //   i4
+        //   |  g
+        //   i0 --- r --- i1
+        //                |  v
+        //                i2 --- f --- i3
+        let mut streets = StreetNetwork::blank();
+        let i0 = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::Intersection);
+        let i1 = add_intersection(&mut streets, 100.0, 0.0, IntersectionKind::Intersection);
+        let i2 = add_intersection(&mut streets, 100.0, 100.0, IntersectionKind::Intersection);
+        let i3 = add_intersection(&mut streets, 200.0, 100.0, IntersectionKind::Intersection);
+        let i4 = add_intersection(&mut streets, 0.0, -100.0, IntersectionKind::Intersection);
+        let r = add_road(&mut streets, i0, i1, &["highway=residential"]);
+        let v = add_road(&mut streets, i1, i2, &["highway=residential"]);
+        let f = add_road(&mut streets, i3, i2, &["highway=residential"]);
+        let g = add_road(&mut streets, i4, i0, &["highway=residential"]);
+        streets
+            .roads
+            .get_mut(&r)
+            .unwrap()
+            .complicated_turn_restrictions
+            .push((v, f));
+        streets
+            .roads
+            .get_mut(&f)
+            .unwrap()
+            .complicated_turn_restrictions
+            .push((v, r));
+        streets
+            .roads
+            .get_mut(&g)
+            .unwrap()
+            .complicated_turn_restrictions
+            .push((r, v));
+
+        let new_i = streets.split_road(r, Pt2D::new(50.0, 0.0)).unwrap();
+        let pieces = streets.intersections[&new_i].roads.clone();
+        let first = *pieces
+            .iter()
+            .find(|x| streets.roads[x].src_i == i0.0)
+            .unwrap();
+        let second = *pieces
+            .iter()
+            .find(|x| streets.roads[x].dst_i == i1.0)
+            .unwrap();
+
+        // Restrictions from and to the road stay at the end touching 'via'
+        assert!(streets.roads[&first]
+            .complicated_turn_restrictions
+            .is_empty());
+        assert_eq!(
+            vec![(v, f)],
+            streets.roads[&second].complicated_turn_restrictions
+        );
+        assert_eq!(
+            vec![(v, second)],
+            streets.roads[&f].complicated_turn_restrictions
+        );
+
+        // The restriction via the road is reported
+        assert!(streets.roads[&g].complicated_turn_restrictions.is_empty());
+        assert_eq!(
+            vec![StaleTurnRestriction {
+                from: g,
+                via: Some(r),
+                to: v,
+                restriction: None,
+                relation: None,
+                problem: StaleReason::SplitVia,
+            }],
+            streets.stale_turn_restrictions
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+
+use crate::{Filter, IntersectionID, IntersectionKind, RoadID, StreetNetwork};
+
+impl StreetNetwork {
+    /// Copies the roads matching the filter into a standalone `StreetNetwork`, useful for sharing
+    /// a small piece of a map as a test case or bug report. Intersections keep only the roads in
+    /// the subset; where that leaves a dead-end that used to continue, it becomes a map edge.
+    ///
+    /// For `Filter::Within`, the polygon becomes the new boundary. Otherwise the boundary stays
+    /// the same. Debug steps and the original network aren't copied, and the OSM index only keeps
+    /// the objects the subset was built from.
+    pub fn subset(&self, filter: &Filter) -> Result<StreetNetwork> {
+        let keep_roads: BTreeSet<RoadID> = filter.roads(self).map(|r| r.id).collect();
+        if keep_roads.is_empty() {
+            bail!("The filter doesn't match any roads");
+        }
+
+        let mut streets = self.clone();
+        streets.debug_steps.clear();
+        streets.original = None;
+        if let Filter::Within(ref polygon) = filter {
+            streets.boundary_polygon = polygon.clone();
+        }
+
+        streets.batch_edits(|streets| {
+            let mut cut_intersections: BTreeSet<IntersectionID> = BTreeSet::new();
+            let remove_roads: Vec<RoadID> = streets
+                .roads
+                .keys()
+                .filter(|r| !keep_roads.contains(r))
+                .cloned()
+                .collect();
+            for r in remove_roads {
+                let road = streets.remove_road(r);
+                cut_intersections.extend(road.endpoints());
+            }
+
+            for i in cut_intersections {
+                let intersection = streets.intersections.get_mut(&i).unwrap();
+                match intersection.roads.len() {
+                    0 => {
+                        streets.remove_intersection(i);
+                    }
+                    1 => {
+                        intersection.kind = IntersectionKind::MapEdge;
+                    }
+                    _ => {}
+                }
+            }
+
+            // Don't point at roads that no longer exist
+            for road in streets.roads.values_mut() {
+                road.turn_restrictions
+                    .retain(|(_, to)| keep_roads.contains(to));
+                road.turn_restriction_relations
+                    .retain(|to, _| keep_roads.contains(to));
+                road.complicated_turn_restrictions
+                    .retain(|(via, to)| keep_roads.contains(via) && keep_roads.contains(to));
+            }
+        });
+
+        if let Some(ref index) = self.osm_index {
+            streets.osm_index = Some(Arc::new(index.only_for(&streets)));
+        }
+
+        Ok(streets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::osm::{NodeID, OsmID, RelationID, WayID};
+    use crate::osm_index::{Node, Relation, Way};
+    use crate::{IntersectionControl, OsmIndex, RestrictionType, Road};
+
+    /// Two separate roads, made from ways 0 and 1, with nodes 0 to 3 at their ends
+    fn network() -> StreetNetwork {
+        let mut streets = StreetNetwork::blank();
+        for (way, y) in [(0, 0.0), (1, 500.0)] {
+            let mut endpoints = Vec::new();
+            for (idx, x) in [0.0, 100.0].into_iter().enumerate() {
+                endpoints.push(streets.insert_intersection(
+                    vec![NodeID(2 * way + idx as i64)],
+                    Pt2D::new(x, y),
+                    IntersectionKind::MapEdge,
+                    IntersectionControl::Uncontrolled,
+                ));
+            }
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![WayID(way)],
+                endpoints[0],
+                endpoints[1],
+                PolyLine::must_new(vec![Pt2D::new(0.0, y), Pt2D::new(100.0, y)]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+        }
+        streets
+    }
+
+    #[test]
+    fn test_subset_narrows_osm_index() {
+        let mut streets = network();
+        let nodes = (0..4)
+            .map(|id| {
+                let node = Node {
+                    pt: Pt2D::new(0.0, 0.0),
+                    tags: Tags::empty(),
+                    version: None,
+                };
+                (NodeID(id), node)
+            })
+            .collect();
+        let ways = (0..2)
+            .map(|id| {
+                let way = Way {
+                    nodes: Vec::new(),
+                    pts: Vec::new(),
+                    tags: Tags::empty(),
+                    version: None,
+                };
+                (WayID(id), way)
+            })
+            .collect();
+        let relations = (0..2)
+            .map(|id| {
+                let relation = Relation {
+                    tags: Tags::empty(),
+                    members: vec![("".to_string(), OsmID::Way(WayID(id)))],
+                    version: None,
+                };
+                (RelationID(id), relation)
+            })
+            .collect();
+        streets.set_osm_index(OsmIndex::new(nodes, ways, relations));
+
+        let first = *streets.roads.keys().next().unwrap();
+        let mut subset = streets
+            .subset(&Filter::Filtered(BTreeSet::from([first]), BTreeSet::new()))
+            .unwrap();
+        let index = subset.osm_index().unwrap();
+        let ways: Vec<WayID> = index.ways().map(|(id, _)| id).collect();
+        assert_eq!(vec![WayID(0)], ways);
+        assert!(index.node(NodeID(1)).is_some());
+        assert!(index.node(NodeID(2)).is_none());
+        assert_eq!(1, subset.relations_for_way(WayID(0)).len());
+        assert!(index.relation(RelationID(1)).is_none());
+
+        // Editing the subset doesn't change the original
+        let mut tags = Tags::empty();
+        tags.insert("highway", "service");
+        subset
+            .osm_index_mut()
+            .unwrap()
+            .way_mut(WayID(0))
+            .unwrap()
+            .tags = tags;
+        let original = streets.osm_index().unwrap().way(WayID(0)).unwrap();
+        assert!(!original.tags.contains_key("highway"));
+        assert_eq!(2, streets.osm_index().unwrap().ways().count());
+    }
+
+    //            i4
+    //            |  d
+    // i0 -a- i1 -b- i2 -c- i3
+    //
+    // Only b is named. Returns the roads in order.
+    fn chain() -> (StreetNetwork, Vec<RoadID>) {
+        let mut streets = StreetNetwork::blank();
+        let pts = [
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(200.0, 0.0),
+            Pt2D::new(300.0, 0.0),
+            Pt2D::new(100.0, -100.0),
+        ];
+        let intersections: Vec<IntersectionID> = pts
+            .iter()
+            .map(|pt| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    IntersectionKind::Intersection,
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        let mut roads = Vec::new();
+        for (i1, i2) in [(0, 1), (1, 2), (2, 3), (1, 4)] {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            if (i1, i2) == (1, 2) {
+                tags.insert("name", "High Street");
+            }
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![WayID(id.0 as i64)],
+                intersections[i1],
+                intersections[i2],
+                PolyLine::must_new(vec![pts[i1], pts[i2]]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+            roads.push(id);
+        }
+        (streets, roads)
+    }
+
+    #[test]
+    fn test_around_street() {
+        let (streets, roads) = chain();
+        let b = &streets.roads[&roads[1]];
+        let Filter::Filtered(found, intersections) =
+            Filter::around_street(&streets, "High Street", 0).unwrap()
+        else {
+            panic!("around_street should list roads");
+        };
+        assert_eq!(BTreeSet::from([b.id]), found);
+        assert_eq!(BTreeSet::from([b.src_i, b.dst_i]), intersections);
+
+        let Filter::Filtered(found, intersections) =
+            Filter::around_street(&streets, "High Street", 1).unwrap()
+        else {
+            panic!("around_street should list roads");
+        };
+        assert_eq!(roads.iter().cloned().collect::<BTreeSet<_>>(), found);
+        assert_eq!(5, intersections.len());
+
+        assert!(Filter::around_street(&streets, "Low Street", 1).is_err());
+    }
+
+    #[test]
+    fn test_subset() {
+        let (mut streets, roads) = chain();
+        // One restriction stays inside the subset, and one points outside of it
+        streets
+            .roads
+            .get_mut(&roads[1])
+            .unwrap()
+            .turn_restrictions
+            .push((RestrictionType::BanTurns, roads[2]));
+        streets
+            .roads
+            .get_mut(&roads[0])
+            .unwrap()
+            .turn_restrictions
+            .push((RestrictionType::BanTurns, roads[1]));
+
+        let keep = BTreeSet::from([roads[0], roads[1]]);
+        let subset = streets
+            .subset(&Filter::Filtered(keep.clone(), BTreeSet::new()))
+            .unwrap();
+        assert_eq!(keep, subset.roads.keys().cloned().collect());
+        assert_eq!(3, subset.intersections.len());
+        // i2 used to continue, so it's now a map edge. i1 still joins two roads.
+        let b = &subset.roads[&roads[1]];
+        assert_eq!(
+            IntersectionKind::MapEdge,
+            subset.intersections[&b.dst_i].kind
+        );
+        assert_ne!(
+            IntersectionKind::MapEdge,
+            subset.intersections[&b.src_i].kind
+        );
+        assert!(b.turn_restrictions.is_empty());
+        assert_eq!(
+            vec![(RestrictionType::BanTurns, roads[1])],
+            subset.roads[&roads[0]].turn_restrictions
+        );
+        subset.check_invariants();
+        // The original is untouched
+        assert_eq!(4, streets.roads.len());
+
+        assert!(streets
+            .subset(&Filter::Filtered(BTreeSet::new(), BTreeSet::new()))
+            .is_err());
+    }
+}
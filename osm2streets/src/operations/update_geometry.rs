@@ -1,11 +1,23 @@
-use geom::{Circle, Distance};
+use anyhow::Result;
+use geom::{Circle, Distance, Pt2D};
 
-use crate::{IntersectionID, Road, StreetNetwork};
+use crate::geometry::{find_mapped_area, Results};
+use crate::{
+    DeadEnd, DeadEndCap, GeometryFixture, IntersectionGeometry, IntersectionID, Road,
+    StreetNetwork, TrimAlgorithm, TrimProvenance,
+};
 
 impl StreetNetwork {
     /// Recalculates trim distances and intersection geometry. This is idempotent; it doesn't use
     /// any results from the previous call.
     pub(crate) fn update_geometry(&mut self, id: IntersectionID) {
+        let results = self.geometry_fixture(id).calculate();
+        self.apply_geometry(id, results);
+    }
+
+    /// Everything needed to calculate the geometry of one intersection, independent of the rest
+    /// of the network. This is the input to the calculation, and it's only kept if that fails.
+    pub(crate) fn geometry_fixture(&self, id: IntersectionID) -> GeometryFixture {
         let i = &self.intersections[&id];
 
         // Update the polygon and set trim distances for roads
@@ -14,23 +26,98 @@ impl StreetNetwork {
             .iter()
             .map(|r| self.roads[r].to_input_road(self.config.driving_side))
             .collect::<Vec<_>>();
-        match crate::intersection_polygon(i.id, i.kind, input_roads, &i.trim_roads_for_merging) {
+        let dead_end = match (self.config.dead_end_cap, i.turning_circle) {
+            (DeadEndCap::Square, _) => DeadEnd::Square,
+            (DeadEndCap::Rounded, _) | (DeadEndCap::TurningCircles, None) => DeadEnd::Rounded,
+            (DeadEndCap::TurningCircles, Some(diameter)) => DeadEnd::TurningCircle(diameter),
+        };
+        let strategy = i
+            .osm_ids
+            .iter()
+            .find_map(|id| self.config.intersection_geometry_overrides.get(id))
+            .copied()
+            .unwrap_or(self.config.intersection_geometry);
+        let mapped_area = if strategy == IntersectionGeometry::MappedArea {
+            let road_ends: Vec<Pt2D> = input_roads
+                .iter()
+                .map(|road| road.center_line_pointed_at(id).last_pt())
+                .collect();
+            find_mapped_area(&self.highway_areas, &road_ends).cloned()
+        } else {
+            None
+        };
+        GeometryFixture {
+            intersection_id: i.id,
+            intersection_kind: i.kind,
+            input_roads,
+            trim_roads_for_merging: i.trim_roads_for_merging.clone(),
+            dead_end,
+            strategy,
+            mapped_area,
+            error: String::new(),
+        }
+    }
+
+    /// Stores the result of calculating `geometry_fixture`, then re-trims the intersection's
+    /// roads.
+    pub(crate) fn apply_geometry(&mut self, id: IntersectionID, results: Result<Results>) {
+        self.geometry_changed();
+        match results {
             Ok(results) => {
                 self.intersections.get_mut(&id).unwrap().polygon = results.intersection_polygon;
 
-                for (r, dist) in results.trim_starts {
-                    self.roads.get_mut(&r).unwrap().trim_start = dist;
+                let provenance = |trim| {
+                    Some(TrimProvenance {
+                        algorithm: results.algorithm,
+                        reason: results.reason.clone(),
+                        trim,
+                    })
+                };
+                for (r, dist) in &results.trim_starts {
+                    let road = self.roads.get_mut(r).unwrap();
+                    road.trim_start = *dist;
+                    road.trim_provenance[0] = provenance(*dist);
                 }
-                for (r, dist) in results.trim_ends {
-                    self.roads.get_mut(&r).unwrap().trim_end = dist;
+                for (r, dist) in &results.trim_ends {
+                    let road = self.roads.get_mut(r).unwrap();
+                    road.trim_end = *dist;
+                    road.trim_provenance[1] = provenance(*dist);
                 }
                 for (pt, label) in results.debug {
                     self.debug_point(pt, label);
                 }
             }
             Err(err) => {
-                error!("Can't make intersection geometry for {}: {}", i.id, err);
+                // Capture the input before changing anything. Keep it even if a later
+                // calculation succeeds, so failures in the middle of a transformation are kept.
+                let error = match self.current_transformation {
+                    Some(transformation) => format!("During {transformation}: {err}"),
+                    None => err.to_string(),
+                };
+                let fixture = GeometryFixture {
+                    error,
+                    ..self.geometry_fixture(id)
+                };
+                self.geometry_failures.insert(id, fixture);
 
+                let i = &self.intersections[&id];
+                error!("Can't make intersection geometry for {}: {}", i.id, err);
+                for r in &i.roads {
+                    let road = self.roads.get_mut(r).unwrap();
+                    let failed = |trim| {
+                        Some(TrimProvenance {
+                            algorithm: TrimAlgorithm::Failed,
+                            reason: err.to_string(),
+                            trim,
+                        })
+                    };
+                    if road.src_i == id {
+                        road.trim_provenance[0] = failed(road.trim_start);
+                    }
+                    if road.dst_i == id {
+                        road.trim_provenance[1] = failed(road.trim_end);
+                    }
+                }
                 let r = i.roads[0];
                 // Don't trim lines back at all
                 let road = &self.roads[&r];
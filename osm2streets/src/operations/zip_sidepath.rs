@@ -1,6 +1,11 @@
-use geom::{Distance, PolyLine};
+use std::collections::BTreeMap;
 
-use crate::{BufferType, Direction, IntersectionID, LaneSpec, LaneType, RoadID, StreetNetwork};
+use geom::{Distance, PolyLine, Pt2D};
+
+use crate::{
+    BufferType, Direction, ExclusionReason, IntersectionID, LaneFeature, LaneFeatureKind, LaneSpec,
+    LaneType, Road, RoadID, SeparateKind, SeparateStatus, SideOfRoad, StreetNetwork,
+};
 
 // We're only pattern matching on one type of parallel sidepath right now. This represents a single
 // Road that's parallel to one or more main_roads.
@@ -16,31 +21,77 @@ use crate::{BufferType, Direction, IntersectionID, LaneSpec, LaneType, RoadID, S
 //
 // S is the sidepath segment. X are intersections. M are main roads -- note there are two matching
 // up to this one sidepath. The '-'s are short connector roads between the two.
+//
+// The sidepath may also only run along part of the main road, with no connector at one end. Then
+// the main road nearest that loose end gets split, and only the overlapping piece is zipped.
 pub struct Sidepath {
     sidepath: RoadID,
     sidepath_center: PolyLine,
     main_road_src_i: IntersectionID,
     main_road_dst_i: IntersectionID,
     main_roads: Vec<RoadID>,
+    // The last of main_roads should be split at this point before zipping
+    split_at: Option<Pt2D>,
+    // A frontage road, not a cycleway or footway
+    frontage: bool,
 }
 
+const SHORT_ROAD_THRESHOLD: Distance = Distance::const_meters(10.0);
+// How far a loose end of a sidepath can be from the main road
+const MAX_SIDEPATH_DIST: Distance = Distance::const_meters(15.0);
+// Frontage roads are usually farther from the main road, so the side streets connecting them are
+// longer
+const FRONTAGE_CONNECTOR_THRESHOLD: Distance = Distance::const_meters(40.0);
+
 impl Sidepath {
     pub fn new(streets: &StreetNetwork, start: RoadID) -> Option<Self> {
-        const SHORT_ROAD_THRESHOLD: Distance = Distance::const_meters(10.0);
+        Self::find(streets, start, SHORT_ROAD_THRESHOLD)
+    }
 
+    /// Matches a frontage road parallel to a main road, as detected by the
+    /// `DetectFrontageRoads` transformation. Zipping it inserts its lanes into the main road as
+    /// `service_lanes`.
+    pub fn new_frontage(streets: &StreetNetwork, start: RoadID) -> Option<Self> {
+        let mut result = Self::find(streets, start, FRONTAGE_CONNECTOR_THRESHOLD)?;
+        let parallel_to = &streets.roads[&start].parallel_to;
+        if !result.main_roads.iter().any(|r| parallel_to.contains(r)) {
+            return None;
+        }
+        result.frontage = true;
+        Some(result)
+    }
+
+    fn find(streets: &StreetNetwork, start: RoadID, connector_threshold: Distance) -> Option<Self> {
         let sidepath_road = &streets.roads[&start];
 
         // Look at other roads connected to both endpoints. One of them should be "very short."
         let mut main_road_endpoints = Vec::new();
+        let mut connectors = Vec::new();
+        let mut loose_end = None;
         for i in sidepath_road.endpoints() {
             let mut connector_candidates = Vec::new();
             for road in streets.roads_per_intersection(i) {
-                if road.untrimmed_length() < SHORT_ROAD_THRESHOLD {
+                if road.untrimmed_length() < connector_threshold {
                     connector_candidates.push(road.id);
                 }
             }
             if connector_candidates.len() == 1 {
                 main_road_endpoints.push(streets.roads[&connector_candidates[0]].other_side(i));
+                connectors.push(connector_candidates[0]);
+            } else if connector_candidates.is_empty() {
+                loose_end = Some(i);
+            }
+        }
+
+        if main_road_endpoints.len() == 1 {
+            if let Some(i) = loose_end {
+                return Self::new_partial(
+                    streets,
+                    sidepath_road,
+                    main_road_endpoints[0],
+                    connectors[0],
+                    i,
+                );
             }
         }
 
@@ -56,22 +107,107 @@ impl Sidepath {
         // Find all main road segments "parallel to" this sidepath, by pathfinding between the
         // main road intersections. We don't care about the order, but simple_path does. In
         // case it's one-way for driving, try both.
-        if let Some(path) = streets
-            .simple_path(main_road_src_i, main_road_dst_i, &[LaneType::Driving])
-            .or_else(|| streets.simple_path(main_road_dst_i, main_road_src_i, &[LaneType::Driving]))
-        {
+        if let Some(path) = driving_path(streets, main_road_src_i, main_road_dst_i) {
             return Some(Self {
                 sidepath: sidepath_road.id,
                 sidepath_center: sidepath_road.center_line.clone(),
                 main_road_src_i,
                 main_road_dst_i,
-                main_roads: path.into_iter().map(|(r, _)| r).collect(),
+                main_roads: path,
+                split_at: None,
+                frontage: false,
             });
         }
 
         None
     }
 
+    fn new_partial(
+        streets: &StreetNetwork,
+        sidepath_road: &Road,
+        main_road_src_i: IntersectionID,
+        connector: RoadID,
+        loose_end: IntersectionID,
+    ) -> Option<Self> {
+        let loose_pt = if sidepath_road.src_i == loose_end {
+            sidepath_road.reference_line.first_pt()
+        } else {
+            sidepath_road.reference_line.last_pt()
+        };
+
+        // Find the nearby main road closest to the loose end
+        let mut best: Option<(RoadID, Distance)> = None;
+        for r in streets.find_nearby_roads(connector, 3) {
+            let road = &streets.roads[&r];
+            if r == sidepath_road.id || r == connector || !road.is_driveable() {
+                continue;
+            }
+            let dist = road.center_line.project_pt(loose_pt).dist_to(loose_pt);
+            if dist < MAX_SIDEPATH_DIST && best.map(|(_, d)| dist < d).unwrap_or(true) {
+                best = Some((r, dist));
+            }
+        }
+        let (split_r, _) = best?;
+        let split_road = &streets.roads[&split_r];
+
+        // If the loose end is near one end of the main road anyway, don't split it
+        let projected = split_road.center_line.project_pt(loose_pt);
+        for i in split_road.endpoints() {
+            let endpt = if split_road.src_i == i {
+                split_road.center_line.first_pt()
+            } else {
+                split_road.center_line.last_pt()
+            };
+            if endpt.dist_to(projected) < SHORT_ROAD_THRESHOLD {
+                let main_roads = driving_path(streets, main_road_src_i, i)?;
+                if main_roads.is_empty() {
+                    return None;
+                }
+                return Some(Self {
+                    sidepath: sidepath_road.id,
+                    sidepath_center: sidepath_road.center_line.clone(),
+                    main_road_src_i,
+                    main_road_dst_i: i,
+                    main_roads,
+                    split_at: None,
+                    frontage: false,
+                });
+            }
+        }
+
+        // Get to the nearer end of the road to split, without crossing it
+        let mut best_path: Option<(Vec<RoadID>, IntersectionID, Distance)> = None;
+        for i in split_road.endpoints() {
+            if let Some(path) = driving_path(streets, main_road_src_i, i) {
+                if path.contains(&split_r) {
+                    continue;
+                }
+                let length = path.iter().fold(Distance::ZERO, |sum, r| {
+                    sum + streets.roads[r].untrimmed_length()
+                });
+                if best_path
+                    .as_ref()
+                    .map(|(_, _, best)| length < *best)
+                    .unwrap_or(true)
+                {
+                    best_path = Some((path, split_road.other_side(i), length));
+                }
+            }
+        }
+        let (mut main_roads, main_road_dst_i, _) = best_path?;
+        main_roads.push(split_r);
+
+        Some(Self {
+            sidepath: sidepath_road.id,
+            sidepath_center: sidepath_road.center_line.clone(),
+            main_road_src_i,
+            main_road_dst_i,
+            main_roads,
+            split_at: Some(loose_pt),
+            frontage: false,
+        })
+    }
+
     pub fn debug(&self, streets: &mut StreetNetwork, label: String) {
         streets.debug_road(self.sidepath, format!("sidepath {label}"));
         streets.debug_intersection(self.main_road_src_i, format!("src_i of {label}"));
@@ -81,11 +217,80 @@ impl Sidepath {
         }
     }
 
-    pub fn zip(self, streets: &mut StreetNetwork) {
+    pub fn zip(mut self, streets: &mut StreetNetwork) {
         assert!(streets.roads.contains_key(&self.sidepath));
 
+        // Only zip the piece of the last main road overlapping the sidepath
+        if let Some(pt) = self.split_at {
+            let split_r = self.main_roads.pop().unwrap();
+            let far_i = self.main_road_dst_i;
+            match streets.split_road(split_r, pt) {
+                Ok(new_i) => {
+                    // Keep the piece that doesn't lead to the far end
+                    let Some(piece) = streets
+                        .roads_per_intersection(new_i)
+                        .into_iter()
+                        .find(|r| r.other_side(new_i) != far_i)
+                        .map(|r| r.id)
+                    else {
+                        warn!(
+                            "Not zipping partial sidepath {}: {split_r} is a loop",
+                            self.sidepath
+                        );
+                        return;
+                    };
+                    self.main_roads.push(piece);
+                    self.main_road_dst_i = new_i;
+                }
+                Err(err) => {
+                    warn!("Not zipping partial sidepath {}: {err}", self.sidepath);
+                    return;
+                }
+            }
+        }
+
         // Remove the sidepath, but remember the lanes it contained
-        let mut sidepath_lanes = streets.remove_road(self.sidepath).lane_specs_ltr;
+        let sidepath_road = streets.remove_road(self.sidepath);
+        let sidepath_kind = if sidepath_road.is_cycleway() {
+            SeparateKind::Cycleway
+        } else {
+            SeparateKind::Sidewalk
+        };
+        let mut sidepath_lanes = sidepath_road.lane_specs_ltr;
+        let mut audit_roads = vec![self.sidepath];
+        audit_roads.extend(self.main_roads.clone());
+        streets.audit(
+            audit_roads,
+            Vec::new(),
+            format!(
+                "Zipped {} {} into {:?}, which it runs alongside",
+                if self.frontage {
+                    "frontage road"
+                } else {
+                    "sidepath"
+                },
+                self.sidepath,
+                self.main_roads
+            ),
+        );
+
+        // Crossings and bus stops along the sidepath get attached to the closest main road
+        let mut points_per_road: BTreeMap<RoadID, Vec<(Pt2D, LaneFeatureKind)>> = BTreeMap::new();
+        for (pt, reason) in sidepath_road.parking_exclusions {
+            let kind = match reason {
+                ExclusionReason::Crossing => LaneFeatureKind::Crossing,
+                ExclusionReason::BusStop => LaneFeatureKind::BusStop,
+                _ => continue,
+            };
+            let closest = self.main_roads.iter().min_by(|r1, r2| {
+                let d1 = streets.roads[r1].center_line.project_pt(pt).dist_to(pt);
+                let d2 = streets.roads[r2].center_line.project_pt(pt).dist_to(pt);
+                d1.partial_cmp(&d2).unwrap()
+            });
+            if let Some(r) = closest {
+                points_per_road.entry(*r).or_default().push((pt, kind));
+            }
+        }
 
         // TODO Preserve osm_ids
 
@@ -109,6 +314,10 @@ impl Sidepath {
             dir: Direction::Forward,
             width: LaneSpec::typical_lane_width(LaneType::Buffer(BufferType::Planters)),
             allowed_turns: Default::default(),
+            is_virtual: false,
+            shoulder_access: None,
+            max_speed: None,
+            bus_hours: None,
             lane: None,
         };
 
@@ -185,8 +394,83 @@ impl Sidepath {
                 insert_lanes.insert(0, buffer_lane);
             }
 
+            // Where the sidepath lanes themselves wind up, not counting the buffer
+            let num_inserted = insert_lanes.len();
+            let sidepath_indices = if snap_to_left {
+                insert_idx..insert_idx + num_inserted - 1
+            } else {
+                insert_idx + 1..insert_idx + num_inserted
+            };
+
             let main_road = streets.roads.get_mut(&r).unwrap();
             splice_in(&mut main_road.lane_specs_ltr, insert_idx, insert_lanes);
+
+            // The separate way that the main road's tags mentioned is now part of it
+            if !self.frontage {
+                let side = if snap_to_left {
+                    SideOfRoad::Left
+                } else {
+                    SideOfRoad::Right
+                };
+                for expected in &mut main_road.separately_mapped {
+                    if expected.kind == sidepath_kind && expected.side == side {
+                        expected.status = SeparateStatus::Zipped;
+                    }
+                }
+            }
+
+            main_road.remap_lane_indices(|idx| {
+                Some(if idx >= insert_idx {
+                    idx + num_inserted
+                } else {
+                    idx
+                })
+            });
+            if self.frontage {
+                main_road.service_lanes.extend(sidepath_indices.clone());
+            }
+
+            // Remember the sidepath's surface, if it differs
+            if let Some(ref surface) = sidepath_road.surface {
+                if main_road.surface.as_ref() != Some(surface) {
+                    for idx in sidepath_indices.clone() {
+                        main_road.lane_surfaces.insert(idx, surface.clone());
+                    }
+                }
+            }
+
+            // The sidepath's own tags describe its lanes
+            if let Some(attributes) = sidepath_road.path_attributes.values().next() {
+                for idx in sidepath_indices.clone() {
+                    if matches!(
+                        main_road.lane_specs_ltr[idx].lt,
+                        LaneType::Sidewalk
+                            | LaneType::Footway
+                            | LaneType::SharedUse
+                            | LaneType::Biking
+                    ) {
+                        main_road.path_attributes.insert(idx, attributes.clone());
+                    }
+                }
+            }
+
+            // Attach crossings and bus stops to the sidepath lane closest to the main road
+            if sidepath_indices.is_empty() {
+                continue;
+            }
+            let lane = if snap_to_left {
+                sidepath_indices.end - 1
+            } else {
+                sidepath_indices.start
+            };
+            for (pt, kind) in points_per_road.remove(&r).unwrap_or_default() {
+                let snapped = main_road.center_line.project_pt(pt);
+                if let Some((dist, _)) = main_road.center_line.dist_along_of_point(snapped) {
+                    main_road
+                        .lane_features
+                        .push(LaneFeature { lane, dist, kind });
+                }
+            }
         }
 
         // After this transformation, we should run CollapseDegenerateIntersections to handle the
@@ -194,9 +478,131 @@ impl Sidepath {
     }
 }
 
+// The roads along a driving path between two intersections, in either direction
+fn driving_path(
+    streets: &StreetNetwork,
+    from: IntersectionID,
+    to: IntersectionID,
+) -> Option<Vec<RoadID>> {
+    streets
+        .simple_path(from, to, &[LaneType::Driving])
+        .or_else(|| streets.simple_path(to, from, &[LaneType::Driving]))
+        .map(|path| path.into_iter().map(|(r, _)| r).collect())
+}
+
 // Insert all of `insert` at `idx` in `target`
 fn splice_in<T>(target: &mut Vec<T>, idx: usize, insert: Vec<T>) {
     let tail = target.split_off(idx);
     target.extend(insert);
     target.extend(tail);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    fn has_lane(road: &Road, lt: LaneType) -> bool {
+        road.lane_specs_ltr.iter().any(|lane| lane.lt == lt)
+    }
+
+    #[test]
+    fn test_partial_sidepath() {
+        // The cycleway only runs along the first 120m of the main road, with a connector at
+        // the start
+        let mut streets = StreetNetwork::blank();
+        let main_start = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::Intersection);
+        let main_end = add_intersection(&mut streets, 200.0, 0.0, IntersectionKind::Intersection);
+        let path_start = add_intersection(&mut streets, 0.0, 8.0, IntersectionKind::Intersection);
+        let loose_end = add_intersection(&mut streets, 120.0, 8.0, IntersectionKind::Intersection);
+        let main_road = add_road(&mut streets, main_start, main_end, &["highway=residential"]);
+        add_road(&mut streets, main_start, path_start, &["highway=cycleway"]);
+        let sidepath = add_road(&mut streets, path_start, loose_end, &["highway=cycleway"]);
+
+        let zip = Sidepath::new(&streets, sidepath).unwrap();
+        assert_eq!(vec![main_road], zip.main_roads);
+        assert_eq!(main_end.0, zip.main_road_dst_i);
+        assert_eq!(Some(Pt2D::new(120.0, 8.0)), zip.split_at);
+        zip.zip(&mut streets);
+
+        // The main road was split, and only the piece alongside the cycleway gained its lanes
+        assert!(!streets.roads.contains_key(&sidepath));
+        assert!(!streets.roads.contains_key(&main_road));
+        let pieces: Vec<&Road> = streets
+            .roads
+            .values()
+            .filter(|r| r.is_driveable())
+            .collect();
+        assert_eq!(2, pieces.len());
+        for piece in pieces {
+            assert_eq!(
+                piece.src_i == main_start.0,
+                has_lane(piece, LaneType::Biking)
+            );
+        }
+    }
+
+    #[test]
+    fn test_frontage_road() {
+        // A frontage road 30m from the main road, with side streets connecting them at both ends
+        let mut streets = StreetNetwork::blank();
+        let main_start = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::Intersection);
+        let main_end = add_intersection(&mut streets, 200.0, 0.0, IntersectionKind::Intersection);
+        let frontage_start =
+            add_intersection(&mut streets, 0.0, 30.0, IntersectionKind::Intersection);
+        let frontage_end =
+            add_intersection(&mut streets, 200.0, 30.0, IntersectionKind::Intersection);
+        let main_road = add_road(&mut streets, main_start, main_end, &["highway=primary"]);
+        add_road(
+            &mut streets,
+            main_start,
+            frontage_start,
+            &["highway=residential"],
+        );
+        add_road(
+            &mut streets,
+            main_end,
+            frontage_end,
+            &["highway=residential"],
+        );
+        let frontage = add_road(
+            &mut streets,
+            frontage_start,
+            frontage_end,
+            &["highway=service"],
+        );
+
+        // The connectors are too long for a sidepath, and it isn't a frontage road until detected
+        assert!(Sidepath::new(&streets, frontage).is_none());
+        assert!(Sidepath::new_frontage(&streets, frontage).is_none());
+
+        streets
+            .roads
+            .get_mut(&frontage)
+            .unwrap()
+            .parallel_to
+            .insert(main_road);
+        streets
+            .roads
+            .get_mut(&main_road)
+            .unwrap()
+            .parallel_to
+            .insert(frontage);
+        let zip = Sidepath::new_frontage(&streets, frontage).unwrap();
+        assert_eq!(vec![main_road], zip.main_roads);
+        assert!(zip.frontage);
+
+        let lanes_before = streets.roads[&main_road].lane_specs_ltr.len();
+        zip.zip(&mut streets);
+        let road = &streets.roads[&main_road];
+        assert!(!streets.roads.contains_key(&frontage));
+        assert!(road.lane_specs_ltr.len() > lanes_before);
+        assert!(!road.service_lanes.is_empty());
+        assert!(road
+            .service_lanes
+            .iter()
+            .any(|idx| road.lane_specs_ltr[*idx].lt == LaneType::Driving));
+        assert!(road.parallel_to.is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use abstutil::Tags;
+use anyhow::Result;
+use geom::Pt2D;
+use serde_json::{json, Value};
+
+use crate::osm::{NodeID, OsmID, RelationID, WayID};
+use crate::{IntersectionID, StreetNetwork};
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub pt: Pt2D,
+    pub tags: Tags,
+    pub version: Option<i32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Way {
+    // Duplicates geometry, because it's convenient
+    pub nodes: Vec<NodeID>,
+    pub pts: Vec<Pt2D>,
+    pub tags: Tags,
+    pub version: Option<i32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Relation {
+    pub tags: Tags,
+    /// Role, member
+    pub members: Vec<(String, OsmID)>,
+    pub version: Option<i32>,
+}
+
+/// The OSM objects a network was imported from, kept after import to look up the original tags
+/// and relations of roads and intersections. Each object is shared, so copying the index or
+/// taking part of it doesn't copy the objects.
+#[derive(Clone, Debug, Default)]
+pub struct OsmIndex {
+    nodes: BTreeMap<NodeID, Arc<Node>>,
+    ways: BTreeMap<WayID, Arc<Way>>,
+    relations: BTreeMap<RelationID, Arc<Relation>>,
+    /// For every member, the relations containing it
+    relations_by_member: BTreeMap<OsmID, Vec<RelationID>>,
+}
+
+impl OsmIndex {
+    pub fn new(
+        nodes: BTreeMap<NodeID, Node>,
+        ways: BTreeMap<WayID, Way>,
+        relations: BTreeMap<RelationID, Relation>,
+    ) -> Self {
+        Self::from_shared(
+            nodes.into_iter().map(|(id, x)| (id, Arc::new(x))).collect(),
+            ways.into_iter().map(|(id, x)| (id, Arc::new(x))).collect(),
+            relations
+                .into_iter()
+                .map(|(id, x)| (id, Arc::new(x)))
+                .collect(),
+        )
+    }
+
+    fn from_shared(
+        nodes: BTreeMap<NodeID, Arc<Node>>,
+        ways: BTreeMap<WayID, Arc<Way>>,
+        relations: BTreeMap<RelationID, Arc<Relation>>,
+    ) -> Self {
+        let mut relations_by_member: BTreeMap<OsmID, Vec<RelationID>> = BTreeMap::new();
+        for (id, relation) in &relations {
+            for (_, member) in &relation.members {
+                let list = relations_by_member.entry(*member).or_default();
+                if !list.contains(id) {
+                    list.push(*id);
+                }
+            }
+        }
+        Self {
+            nodes,
+            ways,
+            relations,
+            relations_by_member,
+        }
+    }
+
+    /// Only the ways that became roads in `streets`, the nodes that became its intersections, and
+    /// the relations referencing those ways. The objects are shared with this index.
+    pub fn only_for(&self, streets: &StreetNetwork) -> Self {
+        let mut nodes = BTreeMap::new();
+        for i in streets.intersections.values() {
+            for id in &i.osm_ids {
+                if let Some(node) = self.nodes.get(id) {
+                    nodes.insert(*id, node.clone());
+                }
+            }
+        }
+        let mut ways = BTreeMap::new();
+        let mut relations = BTreeMap::new();
+        for road in streets.roads.values() {
+            for id in &road.osm_ids {
+                if let Some(way) = self.ways.get(id) {
+                    ways.insert(*id, way.clone());
+                    for relation in self
+                        .relations_by_member
+                        .get(&OsmID::Way(*id))
+                        .into_iter()
+                        .flatten()
+                    {
+                        relations.insert(*relation, self.relations[relation].clone());
+                    }
+                }
+            }
+        }
+        Self::from_shared(nodes, ways, relations)
+    }
+
+    pub fn node(&self, id: NodeID) -> Option<&Node> {
+        self.nodes.get(&id).map(|x| x.as_ref())
+    }
+
+    pub fn way(&self, id: WayID) -> Option<&Way> {
+        self.ways.get(&id).map(|x| x.as_ref())
+    }
+
+    /// Changing a way doesn't change any roads made from it. If other copies of the index share
+    /// the way, only this copy changes.
+    pub fn way_mut(&mut self, id: WayID) -> Option<&mut Way> {
+        self.ways.get_mut(&id).map(Arc::make_mut)
+    }
+
+    pub fn relation(&self, id: RelationID) -> Option<&Relation> {
+        self.relations.get(&id).map(|x| x.as_ref())
+    }
+
+    pub fn ways(&self) -> impl Iterator<Item = (WayID, &Way)> {
+        self.ways.iter().map(|(id, way)| (*id, way.as_ref()))
+    }
+
+    /// Every relation with this member, in ID order
+    pub fn relations_referencing(&self, member: OsmID) -> Vec<(RelationID, &Relation)> {
+        self.relations_by_member
+            .get(&member)
+            .into_iter()
+            .flatten()
+            .map(|id| (*id, self.relations[id].as_ref()))
+            .collect()
+    }
+}
+
+impl StreetNetwork {
+    /// The OSM objects this network was imported from, if the importer kept them
+    pub fn osm_index(&self) -> Option<&OsmIndex> {
+        self.osm_index.as_deref()
+    }
+
+    /// Copies of the network, like debug steps, share the index until one of them modifies it.
+    /// Then only the lookup tables are copied, not the objects.
+    pub fn osm_index_mut(&mut self) -> Option<&mut OsmIndex> {
+        self.osm_index.as_mut().map(Arc::make_mut)
+    }
+
+    pub fn set_osm_index(&mut self, index: OsmIndex) {
+        self.osm_index = Some(Arc::new(index));
+    }
+
+    /// The OSM tags of every node an intersection was built from. Nodes missing from the index
+    /// are skipped.
+    pub fn osm_tags_for_intersection(&self, i: IntersectionID) -> Vec<(NodeID, &Tags)> {
+        let Some(index) = self.osm_index() else {
+            return Vec::new();
+        };
+        self.intersections[&i]
+            .osm_ids
+            .iter()
+            .filter_map(|id| index.node(*id).map(|node| (*id, &node.tags)))
+            .collect()
+    }
+
+    /// Every relation referencing a way, like turn restrictions and routes
+    pub fn relations_for_way(&self, id: WayID) -> Vec<(RelationID, &Relation)> {
+        self.osm_index()
+            .map(|index| index.relations_referencing(OsmID::Way(id)))
+            .unwrap_or_default()
+    }
+
+    /// Describes `osm_tags_for_intersection` as a JSON array of `{ node, tags }`
+    pub fn osm_tags_for_intersection_json(&self, i: IntersectionID) -> Result<String> {
+        let list: Vec<Value> = self
+            .osm_tags_for_intersection(i)
+            .into_iter()
+            .map(|(id, tags)| json!({ "node": id.0, "tags": tags }))
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+
+    /// Describes `relations_for_way` as a JSON array of `{ id, tags, members }`. Each member is
+    /// `{ type, ref, role }`.
+    pub fn relations_for_way_json(&self, id: WayID) -> Result<String> {
+        let list: Vec<Value> = self
+            .relations_for_way(id)
+            .into_iter()
+            .map(|(id, relation)| {
+                let members: Vec<Value> = relation
+                    .members
+                    .iter()
+                    .map(|(role, member)| {
+                        let (kind, id) = match member {
+                            OsmID::Node(x) => ("node", x.0),
+                            OsmID::Way(x) => ("way", x.0),
+                            OsmID::Relation(x) => ("relation", x.0),
+                        };
+                        json!({ "type": kind, "ref": id, "role": role })
+                    })
+                    .collect();
+                json!({ "id": id.0, "tags": relation.tags, "members": members })
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relations_referencing() {
+        let relation = |members: Vec<OsmID>| Relation {
+            tags: Tags::empty(),
+            members: members.into_iter().map(|id| ("".to_string(), id)).collect(),
+            version: None,
+        };
+        let mut relations = BTreeMap::new();
+        relations.insert(
+            RelationID(1),
+            relation(vec![OsmID::Way(WayID(10)), OsmID::Way(WayID(11))]),
+        );
+        relations.insert(
+            RelationID(2),
+            relation(vec![OsmID::Way(WayID(10)), OsmID::Way(WayID(10))]),
+        );
+        let index = OsmIndex::new(BTreeMap::new(), BTreeMap::new(), relations);
+
+        let ids = |member| -> Vec<RelationID> {
+            index
+                .relations_referencing(member)
+                .into_iter()
+                .map(|(id, _)| id)
+                .collect()
+        };
+        assert_eq!(
+            vec![RelationID(1), RelationID(2)],
+            ids(OsmID::Way(WayID(10)))
+        );
+        assert_eq!(vec![RelationID(1)], ids(OsmID::Way(WayID(11))));
+        assert!(ids(OsmID::Node(NodeID(10))).is_empty());
+    }
+}
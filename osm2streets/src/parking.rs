@@ -0,0 +1,349 @@
+use std::collections::BTreeMap;
+
+use abstutil::Tags;
+use geom::{Distance, Polygon};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    IntersectionID, IntersectionKind, LaneID, LaneType, ParkingType, Road, RoadID, SideOfRoad,
+    StreetNetwork,
+};
+
+/// Cars can't park this close to a junction with another street
+const INTERSECTION_CLEARANCE: Distance = Distance::const_meters(5.0);
+/// Leave a little room beside driveways to turn in and out
+const DRIVEWAY_CLEARANCE: Distance = Distance::const_meters(1.0);
+/// Keep pedestrians visible to approaching drivers
+const CROSSING_CLEARANCE: Distance = Distance::const_meters(5.0);
+/// Buses need room to pull in and out of the stop
+const BUS_STOP_CLEARANCE: Distance = Distance::const_meters(15.0);
+
+/// The parking along one lane of a road.
+#[derive(Clone, Debug)]
+pub struct ParkingLane {
+    pub id: LaneID,
+    pub side: SideOfRoad,
+    pub parking_type: ParkingType,
+    /// Intervals along the lane, in the direction of the road, where nobody may park
+    pub exclusions: Vec<ParkingExclusion>,
+    /// Ordered in the direction of the road
+    pub spots: Vec<Polygon>,
+    /// The capacity tagged in OSM, apportioned to this road if the OSM way was split. If this is
+    /// set, the spots are sized to fit this many into the free space; otherwise the number is
+    /// estimated from the length of the lane and the parking orientation. Either way, `spots` may
+    /// have fewer entries, because spots too short to draw are skipped.
+    pub tagged_capacity: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParkingExclusion {
+    pub start: Distance,
+    pub end: Distance,
+    pub reason: ExclusionReason,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExclusionReason {
+    Intersection,
+    Driveway,
+    Crossing,
+    BusStop,
+}
+
+impl ExclusionReason {
+    /// How far to keep clear, on each side of a point or from the end of a road
+    pub fn clearance(self) -> Distance {
+        match self {
+            ExclusionReason::Intersection => INTERSECTION_CLEARANCE,
+            ExclusionReason::Driveway => DRIVEWAY_CLEARANCE,
+            ExclusionReason::Crossing => CROSSING_CLEARANCE,
+            ExclusionReason::BusStop => BUS_STOP_CLEARANCE,
+        }
+    }
+}
+
+impl ParkingLane {
+    pub fn capacity(&self) -> usize {
+        self.spots.len()
+    }
+}
+
+impl StreetNetwork {
+    /// Divides every parking lane into individual spots.
+    pub fn find_parking(&self) -> Vec<ParkingLane> {
+        let tagged_capacity = self.apportion_parking_capacity();
+
+        let mut results = Vec::new();
+        for road in self.roads.values() {
+            let lane_centers = road.get_lane_center_lines();
+            for (idx, (lane, center)) in road.lane_specs_ltr.iter().zip(lane_centers).enumerate() {
+                let LaneType::Parking(parking_type) = lane.lt else {
+                    continue;
+                };
+                let side = if road.lane_specs_ltr[0..idx]
+                    .iter()
+                    .any(|l| l.lt.is_for_moving_vehicles())
+                {
+                    SideOfRoad::Right
+                } else {
+                    SideOfRoad::Left
+                };
+                let tagged_capacity = tagged_capacity
+                    .get(&(road.id, side))
+                    .cloned()
+                    .unwrap_or(None);
+
+                let len = center.length();
+                let mut exclusions = Vec::new();
+                if let Some(reason) = self.parking_clearance(road, road.src_i) {
+                    exclusions.push(ParkingExclusion {
+                        start: Distance::ZERO,
+                        end: reason.clearance().min(len),
+                        reason,
+                    });
+                }
+                if let Some(reason) = self.parking_clearance(road, road.dst_i) {
+                    exclusions.push(ParkingExclusion {
+                        start: (len - reason.clearance()).max(Distance::ZERO),
+                        end: len,
+                        reason,
+                    });
+                }
+                for (pt, reason) in &road.parking_exclusions {
+                    if let Some((dist, _)) = center.dist_along_of_point(center.project_pt(*pt)) {
+                        exclusions.push(ParkingExclusion {
+                            start: (dist - reason.clearance()).max(Distance::ZERO),
+                            end: (dist + reason.clearance()).min(len),
+                            reason: *reason,
+                        });
+                    }
+                }
+                let intervals = available_intervals(len, &exclusions);
+
+                let spot_length = match parking_type {
+                    ParkingType::Parallel => self.config.parallel_street_parking_spot_length,
+                    ParkingType::Perpendicular => self.config.vehicle_width_for_parking_spots,
+                    // The spots are angled at 45 degrees, so each one takes more of the kerb
+                    ParkingType::Diagonal => {
+                        self.config.vehicle_width_for_parking_spots * std::f64::consts::SQRT_2
+                    }
+                };
+                let counts = match tagged_capacity {
+                    Some(n) => allocate_spots(&intervals, n),
+                    None => intervals
+                        .iter()
+                        .map(|(start, end)| ((*end - *start) / spot_length).floor() as usize)
+                        .collect(),
+                };
+
+                let mut spots = Vec::new();
+                for ((start, end), num_spots) in intervals.into_iter().zip(counts) {
+                    if num_spots == 0 {
+                        continue;
+                    }
+                    // Tagged spots fill the space exactly; estimated ones are centered in it
+                    let length = if tagged_capacity.is_some() {
+                        (end - start) / (num_spots as f64)
+                    } else {
+                        spot_length
+                    };
+                    let mut dist = start + ((end - start) - length * (num_spots as f64)) / 2.0;
+                    for _ in 0..num_spots {
+                        if let Ok(slice) = center.maybe_exact_slice(dist, dist + length) {
+                            spots.push(slice.make_polygons(lane.width));
+                        }
+                        dist += length;
+                    }
+                }
+
+                results.push(ParkingLane {
+                    id: LaneID {
+                        road: road.id,
+                        index: idx,
+                    },
+                    side,
+                    parking_type,
+                    exclusions,
+                    spots,
+                    tagged_capacity,
+                });
+            }
+        }
+        results
+    }
+
+    /// Why the end of a road at an intersection must be kept clear of parked cars, if it must.
+    fn parking_clearance(&self, road: &Road, i: IntersectionID) -> Option<ExclusionReason> {
+        let intersection = &self.intersections[&i];
+        if intersection.crossing.is_some() {
+            return Some(ExclusionReason::Crossing);
+        }
+        if matches!(
+            intersection.kind,
+            IntersectionKind::MapEdge | IntersectionKind::Terminus | IntersectionKind::Connection
+        ) {
+            return None;
+        }
+        let others: Vec<&Road> = intersection
+            .roads
+            .iter()
+            .filter(|r| **r != road.id)
+            .map(|r| &self.roads[r])
+            .filter(|r| r.is_driveable())
+            .collect();
+        if others.is_empty() {
+            None
+        } else if others.iter().all(|r| r.is_service()) {
+            Some(ExclusionReason::Driveway)
+        } else {
+            Some(ExclusionReason::Intersection)
+        }
+    }
+
+    /// `capacity` is tagged on an entire OSM way, but the way may be split into many roads.
+    /// Divide it between them by length.
+    fn apportion_parking_capacity(&self) -> BTreeMap<(RoadID, SideOfRoad), Option<usize>> {
+        let mut total_length: BTreeMap<_, Distance> = BTreeMap::new();
+        for road in self.roads.values() {
+            if let Some(way) = road.osm_ids.first() {
+                *total_length.entry(*way).or_insert(Distance::ZERO) += road.untrimmed_length();
+            }
+        }
+
+        let mut results = BTreeMap::new();
+        for road in self.roads.values() {
+            let Some(way) = road.osm_ids.first() else {
+                continue;
+            };
+            let fraction = if total_length[way] > Distance::ZERO {
+                road.untrimmed_length() / total_length[way]
+            } else {
+                1.0
+            };
+            for (side, capacity) in [
+                (SideOfRoad::Left, road.parking_capacity[0]),
+                (SideOfRoad::Right, road.parking_capacity[1]),
+            ] {
+                results.insert(
+                    (road.id, side),
+                    capacity.map(|n| ((n as f64) * fraction).round() as usize),
+                );
+            }
+        }
+        results
+    }
+}
+
+/// The intervals along a lane of length `len` not covered by any exclusion.
+fn available_intervals(
+    len: Distance,
+    exclusions: &[ParkingExclusion],
+) -> Vec<(Distance, Distance)> {
+    let mut sorted: Vec<&ParkingExclusion> = exclusions.iter().collect();
+    sorted.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
+
+    let mut results = Vec::new();
+    let mut dist = Distance::ZERO;
+    for exclusion in sorted {
+        if exclusion.start > dist {
+            results.push((dist, exclusion.start));
+        }
+        dist = dist.max(exclusion.end);
+    }
+    if dist < len {
+        results.push((dist, len));
+    }
+    results
+}
+
+/// Divides `num_spots` between the intervals by length, using the largest remainder method so the
+/// total is exact.
+fn allocate_spots(intervals: &[(Distance, Distance)], num_spots: usize) -> Vec<usize> {
+    let lengths: Vec<Distance> = intervals.iter().map(|(start, end)| *end - *start).collect();
+    let total: Distance = lengths.iter().cloned().sum();
+    if total == Distance::ZERO {
+        return vec![0; intervals.len()];
+    }
+
+    let exact: Vec<f64> = lengths
+        .iter()
+        .map(|len| (num_spots as f64) * (*len / total))
+        .collect();
+    let mut counts: Vec<usize> = exact.iter().map(|x| x.floor() as usize).collect();
+    let mut by_remainder: Vec<usize> = (0..exact.len()).collect();
+    by_remainder.sort_by(|a, b| {
+        (exact[*b] - exact[*b].floor())
+            .partial_cmp(&(exact[*a] - exact[*a].floor()))
+            .unwrap()
+    });
+    let remaining = num_spots - counts.iter().sum::<usize>();
+    for idx in by_remainder.into_iter().take(remaining) {
+        counts[idx] += 1;
+    }
+    counts
+}
+
+/// Parses the number of parking spaces on the left and right side of a way. See
+/// <https://wiki.openstreetmap.org/wiki/Street_parking>.
+pub(crate) fn parse_parking_capacity(tags: &Tags) -> [Option<usize>; 2] {
+    ["left", "right"].map(|side| {
+        [
+            format!("parking:{side}:capacity"),
+            "parking:both:capacity".to_string(),
+            format!("parking:lane:{side}:capacity"),
+            "parking:lane:both:capacity".to_string(),
+        ]
+        .into_iter()
+        .find_map(|key| tags.get(&key).and_then(|x| x.parse::<usize>().ok()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tags_from_pairs;
+
+    #[test]
+    fn test_allocate_spots() {
+        let m = Distance::meters;
+        let len = m(50.0);
+        let exclusions = vec![
+            ParkingExclusion {
+                start: m(0.0),
+                end: m(5.0),
+                reason: ExclusionReason::Intersection,
+            },
+            ParkingExclusion {
+                start: m(15.0),
+                end: m(35.0),
+                reason: ExclusionReason::BusStop,
+            },
+            ParkingExclusion {
+                start: m(20.0),
+                end: m(25.0),
+                reason: ExclusionReason::Crossing,
+            },
+        ];
+        let intervals = available_intervals(len, &exclusions);
+        assert_eq!(intervals, vec![(m(5.0), m(15.0)), (m(35.0), m(50.0))]);
+        assert_eq!(allocate_spots(&intervals, 4), vec![2, 2]);
+        assert_eq!(allocate_spots(&intervals, 5), vec![2, 3]);
+        assert_eq!(allocate_spots(&intervals, 0), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_parse_parking_capacity() {
+        for (input, expected) in [
+            (vec!["parking:both:capacity=10"], [Some(10), Some(10)]),
+            (
+                vec!["parking:both:capacity=10", "parking:left:capacity=3"],
+                [Some(3), Some(10)],
+            ),
+            (vec!["parking:lane:right:capacity=4"], [None, Some(4)]),
+            (vec!["parking:left:capacity=lots"], [None, None]),
+        ] {
+            let tags = tags_from_pairs(&input);
+            assert_eq!(parse_parking_capacity(&tags), expected, "for {:?}", input);
+        }
+    }
+}
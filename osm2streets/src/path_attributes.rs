@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+use abstutil::Tags;
+use serde::{Deserialize, Serialize};
+
+use crate::{osm, LaneSpec, LaneType};
+
+/// Comfort and accessibility details about one sidewalk or cycle lane. Sidewalks and cycle lanes
+/// tagged on a road use keys like `sidewalk:left:surface` or `cycleway:both:smoothness`, while
+/// separate footways and cycleways use plain `surface` and such.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PathAttributes {
+    /// The raw `incline` value, like `up`, `down`, or `5%`, relative to the OSM way's direction
+    pub incline: Option<String>,
+    pub surface: Option<String>,
+    pub smoothness: Option<String>,
+    pub lit: Option<bool>,
+}
+
+impl PathAttributes {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Finds attributes for every sidewalk, footway, and cycle lane. Lanes without any are omitted.
+pub(crate) fn parse_path_attributes(
+    tags: &Tags,
+    lanes: &[LaneSpec],
+) -> BTreeMap<usize, PathAttributes> {
+    let separate_path = tags.is_any(
+        osm::HIGHWAY,
+        vec![
+            "footway",
+            "cycleway",
+            "path",
+            "pedestrian",
+            "steps",
+            "bridleway",
+        ],
+    );
+    let mut results = BTreeMap::new();
+    for (idx, lane) in lanes.iter().enumerate() {
+        let prefix = match lane.lt {
+            LaneType::Sidewalk | LaneType::Footway | LaneType::SharedUse => "sidewalk",
+            LaneType::Biking => "cycleway",
+            _ => continue,
+        };
+        // Which half of the road is the lane in?
+        let side = if (idx as f64 + 0.5) < lanes.len() as f64 / 2.0 {
+            "left"
+        } else {
+            "right"
+        };
+        let get = |key: &str| -> Option<String> {
+            let mut keys = vec![
+                format!("{prefix}:{side}:{key}"),
+                format!("{prefix}:both:{key}"),
+                format!("{prefix}:{key}"),
+            ];
+            // Lighting usually covers the whole street
+            if separate_path || key == "lit" {
+                keys.push(key.to_string());
+            }
+            keys.into_iter().find_map(|key| tags.get(&key).cloned())
+        };
+
+        let attributes = PathAttributes {
+            incline: get("incline"),
+            surface: get("surface"),
+            smoothness: get("smoothness"),
+            lit: get("lit").map(|x| x != "no"),
+        };
+        if !attributes.is_empty() {
+            results.insert(idx, attributes);
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path_attributes() {
+        let lanes = LaneSpec::create_for_test("sdds", "vv^^");
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        tags.insert("surface", "asphalt");
+        tags.insert("lit", "yes");
+        tags.insert("sidewalk:left:surface", "paving_stones");
+        tags.insert("sidewalk:both:smoothness", "good");
+
+        let results = parse_path_attributes(&tags, &lanes);
+        assert_eq!(
+            Some(&PathAttributes {
+                incline: None,
+                surface: Some("paving_stones".to_string()),
+                smoothness: Some("good".to_string()),
+                lit: Some(true),
+            }),
+            results.get(&0)
+        );
+        // The road's surface doesn't describe the sidewalk
+        assert_eq!(None, results[&3].surface);
+        assert!(!results.contains_key(&1));
+    }
+}
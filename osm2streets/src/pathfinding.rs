@@ -1,9 +1,43 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
-use geom::Distance;
+use anyhow::Result;
+use geojson::Feature;
+use geom::{Distance, Duration, PolyLine, Speed};
 use petgraph::graphmap::DiGraphMap;
 
-use crate::{Direction, IntersectionID, LaneType, RoadID, StreetNetwork};
+use crate::render::serialize_features;
+use crate::{
+    Direction, IntersectionID, LaneSpec, LaneType, Road, RoadID, StreetNetwork, TrafficClass,
+};
+
+/// How far to search in `reachable_within`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Budget {
+    Distance(Distance),
+    Time(Duration),
+}
+
+/// Who is traveling in `reachable_within`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Pedestrian,
+    /// Like pedestrians, but avoiding steps without a ramp
+    Wheelchair,
+    Bicycle,
+    Motor,
+    Rail,
+}
+
+/// A road at least partly reachable from the start of `reachable_within`. Costs are in meters
+/// or seconds, depending on the `Budget`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReachableRoad {
+    pub road: RoadID,
+    /// The direction the road is first entered
+    pub dir: Direction,
+    pub cost_at_start: f64,
+    pub cost_at_end: f64,
+}
 
 // A/B Street's map_model has lots of pathfinding support at both a road segment and lane level.
 // This is a delibrately simple subset of functionality for now.
@@ -91,4 +125,410 @@ impl StreetNetwork {
 
         found
     }
+
+    /// Finds every road reachable from an intersection by one class of traffic, within a distance
+    /// or time budget. Roads only partly reachable before running out of budget are included.
+    /// Motor vehicles respect the movements allowed through each intersection; pedestrians can
+    /// walk along sidewalks in either direction, and wheelchairs avoid steps. Ferries carry
+    /// everybody except trains, but only carry motor vehicles if they're tagged to. Times use
+    /// typical speeds for walking, wheelchairs, and cycling, and the estimated free-flow speed for
+    /// other traffic.
+    pub fn reachable_within(
+        &self,
+        start: IntersectionID,
+        budget: Budget,
+        profile: Profile,
+    ) -> Result<Vec<ReachableRoad>> {
+        if !self.intersections.contains_key(&start) {
+            bail!("{start} doesn't exist");
+        }
+        let max_cost = match budget {
+            Budget::Distance(dist) => dist.inner_meters(),
+            Budget::Time(duration) => duration.inner_seconds(),
+        };
+        let cost = |road: &Road, dir: Direction| -> f64 {
+            let length = road.untrimmed_length();
+            match budget {
+                Budget::Distance(_) => length.inner_meters(),
+                Budget::Time(_) => {
+                    let speed = match profile {
+                        Profile::Pedestrian => Speed::meters_per_second(1.34),
+                        Profile::Wheelchair => Speed::meters_per_second(1.0),
+                        Profile::Bicycle => Speed::km_per_hour(16.0),
+                        Profile::Motor | Profile::Rail => {
+                            let capacity = road.capacity();
+                            let directed = if dir == Direction::Forward {
+                                capacity.forward
+                            } else {
+                                capacity.backward
+                            };
+                            directed
+                                .map(|x| x.free_flow_speed)
+                                .or(road.speed_limit)
+                                .unwrap_or_else(|| Speed::km_per_hour(30.0))
+                        }
+                    };
+                    length.inner_meters() / speed.inner_meters_per_second()
+                }
+            }
+        };
+
+        // Nodes are a road crossed in some direction, with None for the start
+        let mut graph: DiGraphMap<Option<(RoadID, Direction)>, f64> = DiGraphMap::new();
+        graph.add_node(None);
+        let leaving = |i: IntersectionID| -> Vec<(RoadID, Direction)> {
+            self.roads_per_intersection(i)
+                .into_iter()
+                .filter_map(|road| {
+                    let dir = if road.src_i == i {
+                        Direction::Forward
+                    } else {
+                        Direction::Backward
+                    };
+                    can_travel(road, dir, profile).then_some((road.id, dir))
+                })
+                .collect()
+        };
+        for (r, dir) in leaving(start) {
+            graph.add_edge(None, Some((r, dir)), cost(&self.roads[&r], dir));
+        }
+        for road in self.roads.values() {
+            for dir in [Direction::Forward, Direction::Backward] {
+                if !can_travel(road, dir, profile) {
+                    continue;
+                }
+                let i = if dir == Direction::Forward {
+                    road.dst_i
+                } else {
+                    road.src_i
+                };
+                let movements = &self.intersections[&i].movements;
+                let options = leaving(i);
+                // Only turn around at dead-ends
+                for (next, next_dir) in options.iter().cloned() {
+                    if next == road.id && options.len() > 1 {
+                        continue;
+                    }
+                    if profile == Profile::Motor && !movements.contains(&(road.id, next)) {
+                        continue;
+                    }
+                    graph.add_edge(
+                        Some((road.id, dir)),
+                        Some((next, next_dir)),
+                        cost(&self.roads[&next], next_dir),
+                    );
+                }
+            }
+        }
+
+        // Ferries connect everything arriving at one terminal to everything leaving the other
+        let arriving = |i: IntersectionID| -> Vec<Option<(RoadID, Direction)>> {
+            let mut nodes: Vec<Option<(RoadID, Direction)>> = self
+                .roads_per_intersection(i)
+                .into_iter()
+                .filter_map(|road| {
+                    let dir = if road.dst_i == i {
+                        Direction::Forward
+                    } else {
+                        Direction::Backward
+                    };
+                    can_travel(road, dir, profile).then_some(Some((road.id, dir)))
+                })
+                .collect();
+            if i == start {
+                nodes.push(None);
+            }
+            nodes
+        };
+        for (idx, i1, i2) in self.ferry_connections() {
+            let ferry = &self.ferries[idx];
+            if profile == Profile::Rail || (profile == Profile::Motor && !ferry.carries_vehicles) {
+                continue;
+            }
+            let crossing = match budget {
+                Budget::Distance(_) => ferry.route.length().inner_meters(),
+                Budget::Time(_) => ferry.duration_seconds(),
+            };
+            for (from, to) in [(i1, i2), (i2, i1)] {
+                for node in arriving(from) {
+                    for (next, next_dir) in leaving(to) {
+                        let total = crossing + cost(&self.roads[&next], next_dir);
+                        // Don't replace a cheaper edge found some other way
+                        let existing = graph.edge_weight(node, Some((next, next_dir))).cloned();
+                        if existing.map(|x| total < x).unwrap_or(true) {
+                            graph.add_edge(node, Some((next, next_dir)), total);
+                        }
+                    }
+                }
+            }
+        }
+
+        let costs = petgraph::algo::dijkstra(&graph, None, None, |(_, _, cost)| *cost);
+        let mut best: BTreeMap<RoadID, ReachableRoad> = BTreeMap::new();
+        for (node, cost_at_end) in costs {
+            let Some((r, dir)) = node else {
+                continue;
+            };
+            let cost_at_start = cost_at_end - cost(&self.roads[&r], dir);
+            if cost_at_start >= max_cost {
+                continue;
+            }
+            if best
+                .get(&r)
+                .map(|x| cost_at_start < x.cost_at_start)
+                .unwrap_or(true)
+            {
+                best.insert(
+                    r,
+                    ReachableRoad {
+                        road: r,
+                        dir,
+                        cost_at_start,
+                        cost_at_end,
+                    },
+                );
+            }
+        }
+        Ok(best.into_values().collect())
+    }
+
+    /// Renders `reachable_within` as GeoJSON, with each road cut off where the budget runs out.
+    pub fn reachable_within_geojson(
+        &self,
+        start: IntersectionID,
+        budget: Budget,
+        profile: Profile,
+    ) -> Result<String> {
+        let max_cost = match budget {
+            Budget::Distance(dist) => dist.inner_meters(),
+            Budget::Time(duration) => duration.inner_seconds(),
+        };
+        let mut features = Vec::new();
+        for reached in self.reachable_within(start, budget, profile)? {
+            let road = &self.roads[&reached.road];
+            let partial = reached.cost_at_end > max_cost;
+            // Too short to draw
+            let Some(pl) = road.reached_line(&reached, max_cost) else {
+                continue;
+            };
+            let mut f = Feature::from(
+                pl.make_polygons(road.total_width())
+                    .to_geojson(Some(&self.gps_bounds)),
+            );
+            f.set_property("road", reached.road.0);
+            f.set_property("direction", format!("{:?}", reached.dir));
+            f.set_property("cost_at_start", reached.cost_at_start);
+            f.set_property("cost_at_end", reached.cost_at_end);
+            f.set_property("partial", partial);
+            // Describe the sidewalk or cycle lane used
+            let attributes = match profile {
+                Profile::Pedestrian | Profile::Wheelchair => {
+                    road.path_attributes.iter().find(|(idx, _)| {
+                        matches!(
+                            road.lane_specs_ltr[**idx].lt,
+                            LaneType::Sidewalk | LaneType::Footway | LaneType::SharedUse
+                        )
+                    })
+                }
+                Profile::Bicycle => road.path_attributes.iter().find(|(idx, _)| {
+                    let lane = &road.lane_specs_ltr[**idx];
+                    lane.dir == reached.dir
+                        && matches!(lane.lt, LaneType::Biking | LaneType::SharedUse)
+                }),
+                Profile::Motor | Profile::Rail => None,
+            };
+            if let Some((_, attributes)) = attributes {
+                f.set_property("path_attributes", serde_json::to_value(attributes)?);
+            }
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
+
+impl Road {
+    /// The part of the trimmed center line reached before the budget runs out. Costs cover the
+    /// untrimmed road, including the parts inside intersections, so the distance reached is
+    /// measured from the untrimmed end. Returns `None` if the budget runs out before the trimmed
+    /// line starts.
+    fn reached_line(&self, reached: &ReachableRoad, max_cost: f64) -> Option<PolyLine> {
+        let pl = &self.center_line;
+        if reached.cost_at_end <= max_cost {
+            return Some(pl.clone());
+        }
+        let fraction =
+            (max_cost - reached.cost_at_start) / (reached.cost_at_end - reached.cost_at_start);
+        let trim = if reached.dir == Direction::Forward {
+            self.trim_start
+        } else {
+            self.trim_end
+        };
+        let length = (self.untrimmed_length() * fraction - trim).min(pl.length());
+        if length <= Distance::ZERO {
+            return None;
+        }
+        if reached.dir == Direction::Forward {
+            pl.maybe_exact_slice(Distance::ZERO, length)
+        } else {
+            pl.maybe_exact_slice(pl.length() - length, pl.length())
+        }
+        .ok()
+    }
+}
+
+/// Can this class of traffic travel along the road in this direction?
+fn can_travel(road: &Road, dir: Direction, profile: Profile) -> bool {
+    if profile == Profile::Wheelchair && !road.is_wheelchair_accessible() {
+        return false;
+    }
+    road.lane_specs_ltr.iter().any(|lane| {
+        // Direction doesn't matter for walking
+        (matches!(profile, Profile::Pedestrian | Profile::Wheelchair) || lane.dir == dir)
+            && profile.can_use_lane(road, lane)
+    })
+}
+
+impl Profile {
+    /// Can this class of traffic use the lane, in its direction of travel?
+    pub(crate) fn can_use_lane(self, road: &Road, lane: &LaneSpec) -> bool {
+        let Some(class) = lane.lt.traffic_class() else {
+            return false;
+        };
+        match self {
+            Profile::Pedestrian | Profile::Wheelchair => {
+                class == TrafficClass::Pedestrian || lane.lt == LaneType::SharedUse
+            }
+            // Cyclists can usually ride in general traffic, just not on motorways
+            Profile::Bicycle => {
+                class == TrafficClass::Bicycle
+                    || (lane.lt == LaneType::Driving
+                        && !matches!(
+                            road.highway_type.as_str(),
+                            "motorway" | "motorway_link" | "trunk" | "trunk_link"
+                        ))
+            }
+            Profile::Motor => lane.lt == LaneType::Driving,
+            Profile::Rail => class == TrafficClass::Rail,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    //          i4
+    //          |  r4 (motorway)
+    //          i3
+    //          |  r3
+    // i0 -r1- i1 -r2- i2
+    //
+    // Every road is 100m long.
+    fn network() -> (StreetNetwork, IntersectionID, Vec<RoadID>) {
+        let mut streets = StreetNetwork::blank();
+        let mut intersection =
+            |x, y| add_intersection(&mut streets, x, y, IntersectionKind::Intersection);
+        let i0 = intersection(0.0, 0.0);
+        let i1 = intersection(100.0, 0.0);
+        let i2 = intersection(200.0, 0.0);
+        let i3 = intersection(100.0, 100.0);
+        let i4 = intersection(100.0, 200.0);
+        let residential = &["highway=residential", "sidewalk=both"];
+        let roads = vec![
+            add_road(&mut streets, i0, i1, residential),
+            add_road(&mut streets, i1, i2, residential),
+            add_road(&mut streets, i1, i3, residential),
+            add_road(&mut streets, i3, i4, &["highway=motorway"]),
+        ];
+        (streets, i0.0, roads)
+    }
+
+    fn reached(streets: &StreetNetwork, start: IntersectionID, profile: Profile) -> Vec<RoadID> {
+        streets
+            .reachable_within(start, Budget::Distance(Distance::meters(250.0)), profile)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.road)
+            .collect()
+    }
+
+    #[test]
+    fn test_motor_movements() {
+        let (mut streets, start, roads) = network();
+        assert_eq!(roads, reached(&streets, start, Profile::Motor));
+
+        // Without the turn, any detour to r3 is over budget
+        let i1 = streets.roads[&roads[0]].dst_i;
+        streets
+            .intersections
+            .get_mut(&i1)
+            .unwrap()
+            .movements
+            .retain(|x| *x != (roads[0], roads[2]));
+        assert_eq!(
+            vec![roads[0], roads[1]],
+            reached(&streets, start, Profile::Motor)
+        );
+        // Walking ignores movements
+        assert_eq!(
+            vec![roads[0], roads[1], roads[2]],
+            reached(&streets, start, Profile::Pedestrian)
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let (streets, start, roads) = network();
+        // Nobody walks or cycles on the motorway
+        for profile in [Profile::Pedestrian, Profile::Bicycle] {
+            assert_eq!(roads[0..3].to_vec(), reached(&streets, start, profile));
+        }
+        assert!(reached(&streets, start, Profile::Rail).is_empty());
+
+        let costs = streets
+            .reachable_within(
+                start,
+                Budget::Distance(Distance::meters(250.0)),
+                Profile::Bicycle,
+            )
+            .unwrap();
+        assert_eq!(0.0, costs[0].cost_at_start);
+        assert!((costs[2].cost_at_start - 100.0).abs() < 0.01);
+        assert!((costs[2].cost_at_end - 200.0).abs() < 0.01);
+
+        // Walking is slower than cycling
+        let time = Budget::Time(Duration::seconds(60.0));
+        let walk = streets
+            .reachable_within(start, time, Profile::Pedestrian)
+            .unwrap();
+        let cycle = streets
+            .reachable_within(start, time, Profile::Bicycle)
+            .unwrap();
+        assert!(walk[0].cost_at_end > cycle[0].cost_at_end);
+    }
+
+    #[test]
+    fn test_reached_line() {
+        let (streets, start, roads) = network();
+        let road = &streets.roads[&roads[0]];
+        let reached = streets
+            .reachable_within(
+                start,
+                Budget::Distance(Distance::meters(60.0)),
+                Profile::Pedestrian,
+            )
+            .unwrap();
+        assert_eq!(1, reached.len());
+
+        // The distance reached counts from the untrimmed start of the road
+        let pl = road.reached_line(&reached[0], 60.0).unwrap();
+        assert_eq!(road.center_line.first_pt(), pl.first_pt());
+        let expected = Distance::meters(60.0) - road.trim_start;
+        assert!((pl.length() - expected).inner_meters().abs() < 0.01);
+
+        // If the budget runs out inside the intersection, nothing is drawn
+        assert!(road.reached_line(&reached[0], 0.1).is_none());
+    }
 }
@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+
+/// Reports how far along a long-running operation like importing is, so callers can show a
+/// progress bar. The callback receives the name of the current stage and the fraction of that
+/// stage completed, from 0 to 1.
+pub struct Progress<'a> {
+    callback: Option<Box<dyn FnMut(&str, f64) + 'a>>,
+    // The last stage and percent reported, to avoid flooding the callback
+    last: Option<(String, usize)>,
+    cancel: Option<CancellationToken>,
+}
+
+/// Lets a caller stop a long-running operation early. Clones share the same state, so one copy
+/// can be handed to the operation and another cancelled from a callback or a different thread.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(callback: impl FnMut(&str, f64) + 'a) -> Self {
+        Self {
+            callback: Some(Box::new(callback)),
+            last: None,
+            cancel: None,
+        }
+    }
+
+    /// Doesn't report anything.
+    pub fn none() -> Self {
+        Self {
+            callback: None,
+            last: None,
+            cancel: None,
+        }
+    }
+
+    /// Makes `check_cancelled` fail once the token is cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Long-running operations call this periodically. If the operation has been cancelled, it
+    /// returns an error, and the operation should stop. Anything modified in-place so far is left
+    /// in a valid but partial state.
+    pub fn check_cancelled(&self, stage: &str) -> Result<()> {
+        if self
+            .cancel
+            .as_ref()
+            .map(|token| token.is_cancelled())
+            .unwrap_or(false)
+        {
+            bail!("Cancelled during {stage}");
+        }
+        Ok(())
+    }
+
+    /// Reports progress through a stage. Calls that don't change the whole percent complete are
+    /// skipped, so it's fine to call this for every item in a big loop.
+    pub fn report(&mut self, stage: &str, fraction: f64) {
+        let Some(ref mut callback) = self.callback else {
+            return;
+        };
+        let fraction = fraction.clamp(0.0, 1.0);
+        let percent = (fraction * 100.0) as usize;
+        if let Some((ref last_stage, last_percent)) = self.last {
+            if last_stage == stage && last_percent == percent {
+                return;
+            }
+        }
+        self.last = Some((stage.to_string(), percent));
+        callback(stage, fraction);
+    }
+
+    /// Reports progress through the `idx`th of `total` items in a stage.
+    pub fn report_iter(&mut self, stage: &str, idx: usize, total: usize) {
+        if total == 0 {
+            self.report(stage, 1.0);
+        } else {
+            self.report(stage, idx as f64 / total as f64);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_skips_repeats() {
+        let mut calls = Vec::new();
+        {
+            let mut progress = Progress::new(|stage, fraction| {
+                calls.push((stage.to_string(), fraction));
+            });
+            for idx in 0..1000 {
+                progress.report_iter("nodes", idx, 1000);
+            }
+            progress.report("ways", 0.0);
+        }
+        assert_eq!(calls.len(), 101);
+        assert_eq!(calls.last().unwrap(), &("ways".to_string(), 0.0));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let token = CancellationToken::new();
+        let progress = Progress::none().with_cancellation(token.clone());
+        assert!(progress.check_cancelled("nodes").is_ok());
+        token.cancel();
+        assert!(progress.check_cancelled("nodes").is_err());
+    }
+}
@@ -0,0 +1,320 @@
+//! Finds problems in the OSM source data, so mappers can fix them.
+
+use abstutil::Tags;
+use anyhow::Result;
+use geojson::Feature;
+use geom::Pt2D;
+
+use crate::render::serialize_features;
+use crate::{
+    osm, Direction, IntersectionID, IntersectionKind, LaneType, Road, StreetNetwork, TurnDirection,
+};
+
+/// Highway types that usually have sidewalks in cities, and so should have them tagged
+const SIDEWALK_HIGHWAYS: [&str; 6] = [
+    "primary",
+    "secondary",
+    "tertiary",
+    "residential",
+    "unclassified",
+    "living_street",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IssueKind {
+    /// A lane is tagged with a turn that no road at the intersection allows
+    ImpossibleTurnLane,
+    /// Sidewalks were inferred, because the road doesn't say whether it has them
+    MissingSidewalkTags,
+    /// Tags contradict each other
+    ConflictingTags,
+    /// A sidewalk or cycleway is tagged as mapped separately, but it couldn't be found
+    UnmatchedSeparateWay,
+}
+
+/// A problem with the tags on one OSM way.
+#[derive(Clone, Debug)]
+pub struct TaggingIssue {
+    pub way: osm::WayID,
+    /// Where to look
+    pub pt: Pt2D,
+    pub kind: IssueKind,
+    pub description: String,
+}
+
+impl StreetNetwork {
+    /// Finds lanes whose tagged turns aren't possible at the intersection they lead to. Roads
+    /// clipped at the map boundary or ending at a dead-end are skipped there, since the turns
+    /// lead somewhere this network doesn't cover.
+    pub fn find_impossible_turn_lanes(&self) -> Vec<TaggingIssue> {
+        let mut issues = Vec::new();
+        for road in self.roads.values() {
+            if road.osm_ids.is_empty() {
+                continue;
+            }
+            for (dir, i) in [
+                (Direction::Forward, road.dst_i),
+                (Direction::Backward, road.src_i),
+            ] {
+                if matches!(
+                    self.intersections[&i].kind,
+                    IntersectionKind::MapEdge | IntersectionKind::Terminus
+                ) {
+                    continue;
+                }
+                let turn_angles = self.turn_angles_from(road, i);
+                for lane in &road.lane_specs_ltr {
+                    // Center turn lanes are mostly used to reach driveways along the road
+                    if lane.dir != dir || lane.lt == LaneType::SharedLeftTurn {
+                        continue;
+                    }
+                    for turn in lane.allowed_turns {
+                        if turn.is_merge() {
+                            continue;
+                        }
+                        if !turn_angles
+                            .iter()
+                            .any(|angle| turn_matches_angle(turn, *angle))
+                        {
+                            issues.push(TaggingIssue {
+                                way: road.osm_ids[0],
+                                pt: self.intersections[&i].polygon.center(),
+                                kind: IssueKind::ImpossibleTurnLane,
+                                description: format!(
+                                    "A lane is tagged {}, but there's nowhere to go that way",
+                                    turn.tag_value()
+                                ),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        issues
+    }
+
+    /// The angles in degrees of every movement from a road through an intersection, with 0
+    /// straight ahead and positive to the right.
+    fn turn_angles_from(&self, from: &Road, i: IntersectionID) -> Vec<f64> {
+        self.intersections[&i]
+            .movements
+            .iter()
+            .filter(|(r1, _)| *r1 == from.id)
+            .map(|(_, r2)| self.movement_angle(from.id, *r2, i))
+            .collect()
+    }
+
+    /// Checks the tags of OSM ways that became roads for contradictions and for missing
+    /// information that had to be guessed.
+    pub fn find_tagging_issues<'a>(
+        &self,
+        ways: impl Iterator<Item = (osm::WayID, &'a Tags)>,
+    ) -> Vec<TaggingIssue> {
+        let mut issues = Vec::new();
+        for (way, tags) in ways {
+            let Some(road) = self
+                .roads
+                .values()
+                .find(|r| r.osm_ids.first() == Some(&way))
+            else {
+                continue;
+            };
+            let pt = road.center_line.middle();
+            for (kind, description) in check_tags(tags, self.config.inferred_sidewalks) {
+                issues.push(TaggingIssue {
+                    way,
+                    pt,
+                    kind,
+                    description,
+                });
+            }
+        }
+        issues
+    }
+
+    /// Packages issues as a MapRoulette challenge: a GeoJSON FeatureCollection with one task per
+    /// issue. Each feature has an `@id` property identifying the way to edit.
+    pub fn to_maproulette_geojson(&self, issues: &[TaggingIssue]) -> Result<String> {
+        let mut features = Vec::new();
+        for issue in issues {
+            let mut f = Feature::from(issue.pt.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("@id", format!("way/{}", issue.way.0));
+            f.set_property("issue", format!("{:?}", issue.kind));
+            f.set_property("description", issue.description.clone());
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
+
+fn turn_matches_angle(turn: TurnDirection, angle: f64) -> bool {
+    match turn {
+        TurnDirection::Through => angle.abs() < 60.0,
+        TurnDirection::Left | TurnDirection::SlightLeft | TurnDirection::SharpLeft => angle < -20.0,
+        TurnDirection::Right | TurnDirection::SlightRight | TurnDirection::SharpRight => {
+            angle > 20.0
+        }
+        // U-turns might happen on the same road, and merges aren't about intersections
+        TurnDirection::Reverse | TurnDirection::MergeLeft | TurnDirection::MergeRight => true,
+    }
+}
+
+fn check_tags(tags: &Tags, inferred_sidewalks: bool) -> Vec<(IssueKind, String)> {
+    let mut issues = Vec::new();
+    let count = |key: &str| tags.get(key).and_then(|x| x.parse::<usize>().ok());
+
+    if let (Some(total), Some(fwd), Some(back)) = (
+        count("lanes"),
+        count("lanes:forward"),
+        count("lanes:backward"),
+    ) {
+        let both_ways = count("lanes:both_ways").unwrap_or(0);
+        if fwd + back + both_ways != total {
+            issues.push((
+                IssueKind::ConflictingTags,
+                format!(
+                    "lanes={total}, but lanes:forward={fwd} and lanes:backward={back} add up to {}",
+                    fwd + back + both_ways
+                ),
+            ));
+        }
+    }
+
+    if tags.is("oneway", "yes") {
+        if let Some(back) = count("lanes:backward") {
+            if back > 0 {
+                issues.push((
+                    IssueKind::ConflictingTags,
+                    format!("oneway=yes, but lanes:backward={back}"),
+                ));
+            }
+        }
+    }
+
+    for (turn_key, lanes_key) in [
+        ("turn:lanes", "lanes"),
+        ("turn:lanes:forward", "lanes:forward"),
+        ("turn:lanes:backward", "lanes:backward"),
+    ] {
+        if let (Some(turns), Some(lanes)) = (tags.get(turn_key), count(lanes_key)) {
+            let num_turns = turns.split('|').count();
+            // turn:lanes without a direction only describes all lanes on oneways
+            let relevant = turn_key != "turn:lanes" || tags.is("oneway", "yes");
+            if relevant && num_turns != lanes {
+                issues.push((
+                    IssueKind::ConflictingTags,
+                    format!("{turn_key} describes {num_turns} lanes, but {lanes_key}={lanes}"),
+                ));
+            }
+        }
+    }
+
+    if inferred_sidewalks
+        && tags.is_any(osm::HIGHWAY, SIDEWALK_HIGHWAYS.to_vec())
+        && !tags.inner().keys().any(|k| k.starts_with("sidewalk"))
+    {
+        issues.push((
+            IssueKind::MissingSidewalkTags,
+            "Sidewalks aren't tagged, so they were guessed".to_string(),
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::tags_from_pairs;
+
+    #[test]
+    fn test_check_tags() {
+        for (input, expected) in [
+            (
+                vec!["highway=residential", "sidewalk=both"],
+                Vec::<IssueKind>::new(),
+            ),
+            (
+                vec!["highway=residential"],
+                vec![IssueKind::MissingSidewalkTags],
+            ),
+            (
+                vec![
+                    "highway=primary",
+                    "sidewalk=no",
+                    "lanes=4",
+                    "lanes:forward=2",
+                    "lanes:backward=1",
+                ],
+                vec![IssueKind::ConflictingTags],
+            ),
+            (
+                vec![
+                    "highway=primary",
+                    "sidewalk=no",
+                    "oneway=yes",
+                    "lanes=2",
+                    "turn:lanes=left|through|right",
+                ],
+                vec![IssueKind::ConflictingTags],
+            ),
+        ] {
+            let tags = tags_from_pairs(&input);
+            let actual: Vec<IssueKind> = check_tags(&tags, true)
+                .into_iter()
+                .map(|(kind, _)| kind)
+                .collect();
+            assert_eq!(actual, expected, "for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_no_impossible_turns_at_map_edge() {
+        use geom::PolyLine;
+
+        use crate::IntersectionControl;
+
+        // A oneway road from a dead-end, clipped at the map boundary
+        let mut streets = StreetNetwork::blank();
+        let pt1 = Pt2D::new(0.0, 0.0);
+        let pt2 = Pt2D::new(100.0, 0.0);
+        let dead_end = streets.insert_intersection(
+            vec![osm::NodeID(1)],
+            pt1,
+            IntersectionKind::Terminus,
+            IntersectionControl::Uncontrolled,
+        );
+        let map_edge = streets.insert_intersection(
+            Vec::new(),
+            pt2,
+            IntersectionKind::MapEdge,
+            IntersectionControl::Uncontrolled,
+        );
+        let mut tags = Tags::empty();
+        for (k, v) in [
+            ("highway", "primary"),
+            ("oneway", "yes"),
+            ("lanes", "1"),
+            ("turn:lanes", "left"),
+        ] {
+            tags.insert(k, v);
+        }
+        let id = streets.next_road_id();
+        let road = Road::new(
+            id,
+            vec![osm::WayID(1)],
+            dead_end,
+            map_edge,
+            PolyLine::must_new(vec![pt1, pt2]),
+            tags,
+            &streets.config,
+        );
+        streets.insert_road(road);
+
+        assert_eq!(
+            streets.intersections[&map_edge].kind,
+            IntersectionKind::MapEdge
+        );
+        assert!(streets.find_impossible_turn_lanes().is_empty());
+    }
+}
@@ -0,0 +1,57 @@
+use geom::{Distance, PolyLine, Polygon};
+
+/// Roughly how far apart chevrons are along the line
+const CHEVRON_SPACING: Distance = Distance::const_meters(50.0);
+
+/// Buffers a line to 1.5 times `width`, then cuts out chevrons pointing along the line to show its
+/// direction. Chevrons that can't be cut out are skipped, so this always produces something.
+pub fn buffer_with_chevrons(polyline: &PolyLine, width: Distance) -> Polygon {
+    let mut polygon = polyline.make_polygons(1.5 * width);
+
+    let num_chevrons = (polyline.length() / CHEVRON_SPACING).floor().max(1.0) as usize;
+    for i in 1..=num_chevrons {
+        // Spread chevrons uniformly along the line
+        let Ok((top_pt, angle)) =
+            polyline.dist_along((i as f64 / (num_chevrons as f64 + 1.0)) * polyline.length())
+        else {
+            continue;
+        };
+        let Ok(chevron) = PolyLine::new(vec![
+            top_pt.project_away(width / 2.0, angle.rotate_degs(135.0)),
+            top_pt,
+            top_pt.project_away(width / 2.0, angle.rotate_degs(-135.0)),
+        ]) else {
+            continue;
+        };
+        let chevron = chevron.make_polygons(width * 0.2);
+
+        // The difference can fail on unusual geometry, or split the polygon into pieces. Keep the
+        // biggest piece.
+        if let Some(piece) = polygon.difference(&chevron).ok().and_then(|pieces| {
+            pieces
+                .into_iter()
+                .max_by(|a, b| a.area().total_cmp(&b.area()))
+        }) {
+            polygon = piece;
+        }
+    }
+    polygon
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Pt2D;
+
+    use super::*;
+
+    #[test]
+    fn test_buffer_with_chevrons() {
+        let width = Distance::meters(10.0);
+        for length in [5.0, 120.0] {
+            let polyline = PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(length, 0.0)]);
+            let plain = polyline.make_polygons(1.5 * width);
+            let carved = buffer_with_chevrons(&polyline, width);
+            assert!(carved.area() < plain.area());
+        }
+    }
+}
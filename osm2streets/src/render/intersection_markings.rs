@@ -1,10 +1,14 @@
 use anyhow::Result;
+use geo::Intersects;
 use geojson::Feature;
 use geom::{Distance, Line, PolyLine, Polygon, Pt2D, Ring};
 
 use super::{serialize_features, Filter};
 use crate::road::RoadEdge;
-use crate::{CrossingKind, Intersection, LaneType, Road, StreetNetwork};
+use crate::{BufferType, CrossingKind, Intersection, LaneType, Road, StreetNetwork};
+
+/// How wide to draw a traffic island implied by a crossing, when the road has no physical median
+const IMPLIED_ISLAND_WIDTH: Distance = Distance::const_meters(1.5);
 
 impl StreetNetwork {
     pub fn to_intersection_markings_geojson(&self, filter: &Filter) -> Result<String> {
@@ -16,6 +20,13 @@ impl StreetNetwork {
                 features.push(f);
             }
 
+            for (polygon, source) in find_traffic_islands(self, intersection) {
+                let mut f = Feature::from(polygon.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("type", "traffic island");
+                f.set_property("source", source);
+                features.push(f);
+            }
+
             if let Some(ref crossing) = intersection.crossing {
                 match crossing.kind {
                     CrossingKind::Signalized | CrossingKind::Marked => {
@@ -133,6 +144,108 @@ fn make_sidewalk_corners(streets: &StreetNetwork, intersection: &Intersection) -
     results
 }
 
+/// Finds pedestrian refuges and channelization islands in an intersection. Islands mapped as
+/// areas are used if they touch the intersection. Otherwise, if a crossing is tagged with an
+/// island, one is drawn across the crossing, along the road's physical median or between the
+/// opposite directions of traffic.
+fn find_traffic_islands(
+    streets: &StreetNetwork,
+    intersection: &Intersection,
+) -> Vec<(Polygon, &'static str)> {
+    let intersection_polygon: geo::Polygon = intersection.polygon.clone().into();
+    let mapped: Vec<(Polygon, &'static str)> = streets
+        .spatial_index()
+        .traffic_islands(&intersection.polygon.get_bounds())
+        .into_iter()
+        .map(|idx| &streets.traffic_islands[idx])
+        .filter(|island| {
+            let island: geo::Polygon = (*island).clone().into();
+            island.intersects(&intersection_polygon)
+        })
+        .map(|island| (island.clone(), "mapped"))
+        .collect();
+    if !mapped.is_empty() {
+        return mapped;
+    }
+
+    if !intersection
+        .crossing
+        .as_ref()
+        .map(|c| c.has_island)
+        .unwrap_or(false)
+    {
+        return Vec::new();
+    }
+    let mut medians = Vec::new();
+    let mut width = None;
+    for r in &intersection.roads {
+        let road = &streets.roads[r];
+        if !road.is_driveable() {
+            continue;
+        }
+        if let Some((pl, median_width)) = median_pointed_at(road, intersection) {
+            medians.push((road, pl.last_pt()));
+            width = Some(width.unwrap_or(median_width).min(median_width));
+        }
+    }
+    let (Some(((_, pt1), (_, pt2))), Some(width)) = (farthest_pair(medians), width) else {
+        return Vec::new();
+    };
+    match Line::new(pt1, pt2) {
+        Ok(line) => vec![(line.make_polygons(width), "implied")],
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Returns the line along the middle of a road's median, pointed at the intersection, and the
+/// median's width. A physical median is preferred; otherwise it's the line between opposite
+/// directions of traffic.
+fn median_pointed_at(road: &Road, intersection: &Intersection) -> Option<(PolyLine, Distance)> {
+    let (road, _) = road.without_virtual_lanes();
+    let lanes = &road.lane_specs_ltr;
+    let mut left_edge = Distance::ZERO;
+    let mut physical = None;
+    let mut between_directions = None;
+    for (idx, lane) in lanes.iter().enumerate() {
+        let interior = idx != 0 && idx != lanes.len() - 1;
+        if interior
+            && physical.is_none()
+            && matches!(
+                lane.lt,
+                LaneType::Buffer(
+                    BufferType::Curb
+                        | BufferType::Planters
+                        | BufferType::JerseyBarrier
+                        | BufferType::Verge
+                )
+            )
+        {
+            physical = Some((left_edge + lane.width / 2.0, lane.width));
+        }
+        if let Some(next) = lanes.get(idx + 1) {
+            if between_directions.is_none()
+                && lane.lt.is_for_moving_vehicles()
+                && next.lt.is_for_moving_vehicles()
+                && lane.dir != next.dir
+            {
+                between_directions = Some((left_edge + lane.width, IMPLIED_ISLAND_WIDTH));
+            }
+        }
+        left_edge += lane.width;
+    }
+
+    let (offset, width) = physical.or(between_directions)?;
+    let pl = road
+        .center_line
+        .shift_from_center(road.total_width(), offset)
+        .ok()?;
+    if road.dst_i == intersection.id {
+        Some((pl, width))
+    } else {
+        Some((pl.reversed(), width))
+    }
+}
+
 // TODO The alternate approach would be to preserve the original OSM way representing the crossing,
 // even as the intersection polygon gets built
 fn get_crossing_line_and_min_width(
@@ -230,3 +343,132 @@ fn perp_line(l: Line, length: Distance) -> Line {
     let pt2 = l.shift_left(length / 2.0).pt1();
     Line::must_new(pt1, pt2)
 }
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+
+    use super::*;
+    use crate::{osm, Crossing, IntersectionControl, IntersectionID, IntersectionKind, RoadID};
+
+    fn square(x: f64, y: f64, radius: f64) -> Polygon {
+        Ring::must_new(vec![
+            Pt2D::new(x - radius, y - radius),
+            Pt2D::new(x + radius, y - radius),
+            Pt2D::new(x + radius, y + radius),
+            Pt2D::new(x - radius, y + radius),
+            Pt2D::new(x - radius, y - radius),
+        ])
+        .into_polygon()
+    }
+
+    // Two two-way roads crossing at (100, 0), optionally with mapped islands. Returns the
+    // intersection and the roads west and east of it.
+    fn network(islands: Vec<Polygon>) -> (StreetNetwork, IntersectionID, [RoadID; 2]) {
+        let mut streets = StreetNetwork::blank();
+        streets.traffic_islands = islands;
+        let center = streets.insert_intersection(
+            Vec::new(),
+            Pt2D::new(100.0, 0.0),
+            IntersectionKind::Intersection,
+            IntersectionControl::Uncontrolled,
+        );
+        let mut roads = Vec::new();
+        for (x, y) in [(0.0, 0.0), (200.0, 0.0), (100.0, -100.0), (100.0, 100.0)] {
+            let pt = Pt2D::new(x, y);
+            let edge = streets.insert_intersection(
+                Vec::new(),
+                pt,
+                IntersectionKind::MapEdge,
+                IntersectionControl::Uncontrolled,
+            );
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            tags.insert("lanes", "2");
+            tags.insert("sidewalk", "no");
+            let id = streets.next_road_id();
+            // Point roads both into and out of the intersection
+            let (src_i, dst_i, pts) = if roads.len() % 2 == 0 {
+                (edge, center, vec![pt, Pt2D::new(100.0, 0.0)])
+            } else {
+                (center, edge, vec![Pt2D::new(100.0, 0.0), pt])
+            };
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                src_i,
+                dst_i,
+                PolyLine::must_new(pts),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+            roads.push(id);
+        }
+        (streets, center, [roads[0], roads[1]])
+    }
+
+    #[test]
+    fn test_median_pointed_at() {
+        let (mut streets, i, [west, east]) = network(Vec::new());
+        let center = Pt2D::new(100.0, 0.0);
+        // Roads on both sides point at the intersection
+        for r in [west, east] {
+            let (pl, width) =
+                median_pointed_at(&streets.roads[&r], &streets.intersections[&i]).unwrap();
+            assert_eq!(IMPLIED_ISLAND_WIDTH, width);
+            assert!(pl.last_pt().dist_to(center) < pl.first_pt().dist_to(center));
+        }
+
+        // A physical median is used instead
+        let road = streets.roads.get_mut(&west).unwrap();
+        let idx = road
+            .lane_specs_ltr
+            .windows(2)
+            .position(|pair| pair[0].dir != pair[1].dir)
+            .unwrap()
+            + 1;
+        let mut median = road.lane_specs_ltr[idx].clone();
+        median.lt = LaneType::Buffer(BufferType::Curb);
+        median.width = Distance::meters(2.0);
+        road.lane_specs_ltr.insert(idx, median);
+        let (_, width) =
+            median_pointed_at(&streets.roads[&west], &streets.intersections[&i]).unwrap();
+        assert_eq!(Distance::meters(2.0), width);
+
+        // A oneway road has nothing between opposite directions
+        let road = streets.roads.get_mut(&east).unwrap();
+        let dir = road.lane_specs_ltr[0].dir;
+        for lane in &mut road.lane_specs_ltr {
+            lane.dir = dir;
+        }
+        assert!(median_pointed_at(&streets.roads[&east], &streets.intersections[&i]).is_none());
+    }
+
+    #[test]
+    fn test_find_traffic_islands() {
+        let (mut streets, i, _) = network(Vec::new());
+        assert!(find_traffic_islands(&streets, &streets.intersections[&i]).is_empty());
+
+        // A crossing with an island implies one between the directions of traffic
+        streets.intersections.get_mut(&i).unwrap().crossing = Some(Crossing {
+            kind: CrossingKind::Marked,
+            has_island: true,
+        });
+        let islands = find_traffic_islands(&streets, &streets.intersections[&i]);
+        assert_eq!(1, islands.len());
+        assert_eq!("implied", islands[0].1);
+
+        // Mapped islands touching the intersection win, and others are ignored
+        let mapped = square(100.0, 0.0, 1.0);
+        let (mut streets, i, _) = network(vec![square(500.0, 500.0, 1.0), mapped.clone()]);
+        streets.intersections.get_mut(&i).unwrap().crossing = Some(Crossing {
+            kind: CrossingKind::Marked,
+            has_island: true,
+        });
+        assert_eq!(
+            vec![(mapped, "mapped")],
+            find_traffic_islands(&streets, &streets.intersections[&i])
+        );
+    }
+}
@@ -1,10 +1,13 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use anyhow::Result;
 use geojson::Feature;
 use geom::{ArrowCap, Distance, Line, PolyLine, Polygon};
 
 use super::{serialize_features, Filter};
 use crate::{
-    BufferType, Direction, DrivingSide, LaneSpec, LaneType, ParkingType, Road, StreetNetwork,
+    BufferType, Direction, DrivingSide, LaneID, LaneSpec, LaneType, ParkingExclusion, ParkingType,
+    Road, RoadID, StreetNetwork,
 };
 
 impl StreetNetwork {
@@ -13,8 +16,14 @@ impl StreetNetwork {
         // TODO Split this up!
         let gps_bounds = Some(&self.gps_bounds);
         let mut features = Vec::new();
+        let parking_exclusions: BTreeMap<LaneID, Vec<ParkingExclusion>> = self
+            .find_parking()
+            .into_iter()
+            .map(|parking| (parking.id, parking.exclusions))
+            .collect();
 
         for road in filter.roads(self) {
+            let (road, original_idx) = road.without_virtual_lanes();
             // Always oriented in the direction of the road
             let mut lane_centers = road.get_lane_center_lines();
 
@@ -56,7 +65,7 @@ impl StreetNetwork {
 
             // Stop line distances are relative to the direction of the road, not the lane!
             for (lane, center) in road.lane_specs_ltr.iter().zip(lane_centers.iter()) {
-                for (polygon, kind) in draw_stop_lines(lane, center, road) {
+                for (polygon, kind) in draw_stop_lines(lane, center, &road) {
                     let mut f = Feature::from(polygon.to_geojson(gps_bounds));
                     f.set_property("type", kind);
                     features.push(f);
@@ -142,8 +151,13 @@ impl StreetNetwork {
                 }
             }
 
-            for (lane, center) in road.lane_specs_ltr.iter().zip(lane_centers.iter()) {
-                let polygons = match lane.lt {
+            for (idx, (lane, center)) in road
+                .lane_specs_ltr
+                .iter()
+                .zip(lane_centers.iter())
+                .enumerate()
+            {
+                let mut polygons = match lane.lt {
                     LaneType::Parking(ParkingType::Parallel) => {
                         draw_parallel_parking_lines(lane, center, self)
                     }
@@ -155,6 +169,24 @@ impl StreetNetwork {
                     }
                     _ => continue,
                 };
+                // Don't draw spots where nobody may park
+                if let Some(exclusions) = parking_exclusions.get(&LaneID {
+                    road: road.id,
+                    index: original_idx[idx],
+                }) {
+                    polygons.retain(|polygon| {
+                        let Some((mut dist, _)) =
+                            center.dist_along_of_point(center.project_pt(polygon.center()))
+                        else {
+                            return true;
+                        };
+                        // Exclusions are measured in the direction of the road
+                        if lane.dir == Direction::Backward {
+                            dist = center.length() - dist;
+                        }
+                        !exclusions.iter().any(|x| x.start <= dist && dist <= x.end)
+                    });
+                }
                 for polygon in polygons {
                     let mut f = Feature::from(polygon.to_geojson(gps_bounds));
                     f.set_property("type", "parking hatch");
@@ -185,6 +217,34 @@ impl StreetNetwork {
             }
         }
 
+        // Show where dropped lanes narrow before ending
+        let roads: BTreeSet<RoadID> = filter.roads(self).map(|r| r.id).collect();
+        for transition in self.find_lane_transitions() {
+            if !roads.contains(&transition.from) {
+                continue;
+            }
+            for (idx, merge) in &transition.dropped_lanes {
+                if let Some(polygon) = self.lane_taper_polygon(&transition, *idx, *merge) {
+                    let mut f = Feature::from(polygon.to_geojson(gps_bounds));
+                    f.set_property("type", "lane taper");
+                    f.set_property("layer", self.roads[&transition.from].layer);
+                    features.push(f);
+                }
+            }
+        }
+
+        for ramp_lane in self.find_ramp_lanes() {
+            if !roads.contains(&ramp_lane.road) {
+                continue;
+            }
+            if let Some(polygon) = self.ramp_lane_taper_polygon(&ramp_lane) {
+                let mut f = Feature::from(polygon.to_geojson(gps_bounds));
+                f.set_property("type", "lane taper");
+                f.set_property("layer", self.roads[&ramp_lane.road].layer);
+                features.push(f);
+            }
+        }
+
         serialize_features(features)
     }
 }
@@ -14,7 +14,7 @@ use enumset::EnumSet;
 // We use geom and stay in map space. Output is done in latlon.
 use geom::{Angle, Line, PolyLine, Polygon, Pt2D};
 
-use osm2lanes::{LaneType, TrafficClass, TurnDirection};
+use crate::{LaneType, TrafficClass, TurnDirection};
 
 /// A marking painted on the road surface to direct traffic.
 pub enum RoadMarking {
@@ -41,7 +41,8 @@ pub enum LongitudinalLine {
         overtake_left: bool,
         overtake_right: bool,
     },
-    /// A line separating lanes of traffic travelling in the same direction.
+    /// A line separating lanes of traffic travelling in the same direction. `merge_left` means
+    /// traffic may cross it moving left, in the order of `lane_specs_ltr`.
     Lane { merge_left: bool, merge_right: bool },
     /// A line at the edge of a lane that is also the edge of the road.
     Edge,
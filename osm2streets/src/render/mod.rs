@@ -1,36 +1,123 @@
+mod chevrons;
 mod intersection_markings;
 mod lane_markings;
-mod marking;
+pub(crate) mod marking;
+mod mode_layers;
+mod osm_xml;
 mod output;
 mod paint;
+#[cfg(feature = "raster")]
+mod raster;
+mod svg;
+
+pub use self::chevrons::buffer_with_chevrons;
+pub use self::output::{Surface, SurfaceMaterial};
+#[cfg(feature = "raster")]
+pub use self::raster::RasterSnapshot;
 
 use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::Result;
+use geo::Intersects;
 use geojson::Feature;
-use geom::{ArrowCap, Distance, Line, Polygon};
+use geom::{ArrowCap, Bounds, Distance, GPSBounds, Line, LonLat, PolyLine, Polygon, Ring};
 use serde_json::Value;
 
+use crate::capacity::directed_json;
+use crate::intersection::turn_direction_for_angle;
 use crate::{
-    DebugStreets, Direction, DrivingSide, Intersection, IntersectionID, LaneID, Movement, Road,
-    RoadID, StreetNetwork,
+    BufferType, DebugStreets, Direction, DrivingSide, Intersection, IntersectionID, LaneID,
+    LaneType, Movement, RestrictionType, Road, RoadID, StreetNetwork, TrafficClass,
 };
 
 /// Specifies what roads and intersections to render.
 pub enum Filter {
     All,
     Filtered(BTreeSet<RoadID>, BTreeSet<IntersectionID>),
+    /// Everything touching this polygon, in map space
+    Within(Polygon),
+    /// Everything touching this rectangle, in map space. Cheaper than `Within`.
+    BBox(Bounds),
 }
 
 impl Filter {
-    fn roads<'a>(&'a self, streets: &'a StreetNetwork) -> Box<dyn Iterator<Item = &Road> + 'a> {
+    /// Everything touching a rectangle, described by two opposite GPS corners.
+    pub fn bbox_from_gps(gps_bounds: &GPSBounds, corner1: LonLat, corner2: LonLat) -> Self {
+        let mut bounds = Bounds::new();
+        bounds.update(corner1.to_pt(gps_bounds));
+        bounds.update(corner2.to_pt(gps_bounds));
+        Filter::BBox(bounds)
+    }
+
+    /// Everything touching a polygon, described in GPS.
+    pub fn within_gps(gps_bounds: &GPSBounds, pts: Vec<LonLat>) -> Result<Self> {
+        Ok(Filter::Within(
+            Ring::deduping_new(gps_bounds.convert(&pts))?.into_polygon(),
+        ))
+    }
+
+    /// Every road with this name, plus the roads up to `hops` intersections away from them, and
+    /// their intersections.
+    pub fn around_street(streets: &StreetNetwork, name: &str, hops: usize) -> Result<Self> {
+        let mut roads: BTreeSet<RoadID> = streets
+            .roads
+            .values()
+            .filter(|r| r.name.as_ref().map(|x| x.as_str()) == Some(name))
+            .map(|r| r.id)
+            .collect();
+        if roads.is_empty() {
+            bail!("No road is named {name}");
+        }
+        for _ in 0..hops {
+            let mut next = roads.clone();
+            for r in &roads {
+                for i in streets.roads[r].endpoints() {
+                    next.extend(streets.intersections[&i].roads.iter().cloned());
+                }
+            }
+            roads = next;
+        }
+        let intersections = roads
+            .iter()
+            .flat_map(|r| streets.roads[r].endpoints())
+            .collect();
+        Ok(Filter::Filtered(roads, intersections))
+    }
+
+    /// Matching roads, always in order of ID, so output is stable
+    pub(crate) fn roads<'a>(
+        &'a self,
+        streets: &'a StreetNetwork,
+    ) -> Box<dyn Iterator<Item = &Road> + 'a> {
         match self {
             Filter::All => Box::new(streets.roads.values()),
             Filter::Filtered(ref roads, _) => Box::new(roads.iter().map(|r| &streets.roads[r])),
+            Filter::Within(ref polygon) => {
+                let candidates = streets.spatial_index().roads(&polygon.get_bounds());
+                let polygon: geo::Polygon = polygon.clone().into();
+                Box::new(
+                    candidates
+                        .into_iter()
+                        .filter_map(move |r| streets.roads.get(&r))
+                        .filter(move |r| {
+                            let road_polygon: geo::Polygon =
+                                r.center_line.make_polygons(r.total_width()).into();
+                            road_polygon.intersects(&polygon)
+                        }),
+                )
+            }
+            Filter::BBox(ref bounds) => Box::new(
+                streets
+                    .spatial_index()
+                    .roads(bounds)
+                    .into_iter()
+                    .filter_map(move |r| streets.roads.get(&r)),
+            ),
         }
     }
 
-    fn intersections<'a>(
+    /// Matching intersections, always in order of ID, so output is stable
+    pub(crate) fn intersections<'a>(
         &'a self,
         streets: &'a StreetNetwork,
     ) -> Box<dyn Iterator<Item = &Intersection> + 'a> {
@@ -39,10 +126,34 @@ impl Filter {
             Filter::Filtered(_, ref intersections) => {
                 Box::new(intersections.iter().map(|i| &streets.intersections[i]))
             }
+            Filter::Within(ref polygon) => {
+                let candidates = streets.spatial_index().intersections(&polygon.get_bounds());
+                let polygon: geo::Polygon = polygon.clone().into();
+                Box::new(
+                    candidates
+                        .into_iter()
+                        .filter_map(move |i| streets.intersections.get(&i))
+                        .filter(move |i| {
+                            let i_polygon: geo::Polygon = i.polygon.clone().into();
+                            i_polygon.intersects(&polygon)
+                        }),
+                )
+            }
+            Filter::BBox(ref bounds) => Box::new(
+                streets
+                    .spatial_index()
+                    .intersections(bounds)
+                    .into_iter()
+                    .filter_map(move |i| streets.intersections.get(&i)),
+            ),
         }
     }
 }
 
+pub(crate) fn overlaps(b1: &Bounds, b2: &Bounds) -> bool {
+    b1.min_x <= b2.max_x && b2.min_x <= b1.max_x && b1.min_y <= b2.max_y && b2.min_y <= b1.max_y
+}
+
 impl StreetNetwork {
     /// Generates a plain GeoJSON rendering with one polygon per road and intersection.
     pub fn to_geojson(&self, filter: &Filter) -> Result<String> {
@@ -64,6 +175,11 @@ impl StreetNetwork {
             f.set_property("src_i", road.src_i.0);
             f.set_property("dst_i", road.dst_i.0);
             f.set_property("layer", road.layer);
+            let widths = road.widths();
+            f.set_property("carriageway_width", widths.carriageway.inner_meters());
+            f.set_property("curb_to_curb_width", widths.curb_to_curb.inner_meters());
+            f.set_property("right_of_way_width", widths.right_of_way.inner_meters());
+            f.set_property("general_lanes", widths.general_lanes);
             features.push(f);
         }
 
@@ -79,6 +195,15 @@ impl StreetNetwork {
             f.set_property("intersection_kind", format!("{:?}", intersection.kind));
             f.set_property("control", format!("{:?}", intersection.control));
             f.set_property("crossing", serde_json::to_value(&intersection.crossing)?);
+            if let Some(name) = intersection.name() {
+                f.set_property("name", name.clone());
+            }
+            if let Some(signals) = intersection.osm_tags.get("traffic_signals") {
+                f.set_property("traffic_signals", signals.clone());
+            }
+            if let Some(dir) = intersection.osm_tags.get("traffic_signals:direction") {
+                f.set_property("traffic_signals_direction", dir.clone());
+            }
             f.set_property(
                 "movements",
                 Value::Array(
@@ -103,13 +228,18 @@ impl StreetNetwork {
             features,
             foreign_members: Some(foreign_members),
         });
-        let output = serde_json::to_string_pretty(&gj)?;
+        let output = serde_json::to_string(&gj)?;
         Ok(output)
     }
 
     /// Generates a polygon per lane, with a property indicating type.
     pub fn to_lane_polygons_geojson(&self, filter: &Filter) -> Result<String> {
         let mut features = Vec::new();
+        let ramp_lanes: BTreeMap<(RoadID, usize), String> = self
+            .find_ramp_lanes()
+            .into_iter()
+            .map(|x| ((x.road, x.lane), format!("{:?}", x.kind)))
+            .collect();
 
         for road in filter.roads(self) {
             for (idx, (lane, pl)) in road
@@ -118,6 +248,9 @@ impl StreetNetwork {
                 .zip(road.get_lane_center_lines().into_iter())
                 .enumerate()
             {
+                if lane.is_virtual {
+                    continue;
+                }
                 let mut f = Feature::from(
                     pl.make_polygons(lane.width)
                         .to_geojson(Some(&self.gps_bounds)),
@@ -125,8 +258,18 @@ impl StreetNetwork {
                 f.set_property("type", format!("{:?}", lane.lt));
                 f.set_property("road", road.id.0);
                 f.set_property("layer", road.layer);
-                f.set_property("speed_limit", format!("{:?}", road.speed_limit));
+                f.set_property(
+                    "speed_limit_kmph",
+                    road.speed_limit
+                        .map(|speed| speed.inner_meters_per_second() * 3.6),
+                );
                 f.set_property("index", idx);
+                if let Some(key) = self.lane_key(LaneID {
+                    road: road.id,
+                    index: idx,
+                }) {
+                    f.set_property("key", key.to_string());
+                }
                 f.set_property("width", lane.width.inner_meters());
                 f.set_property("direction", format!("{:?}", lane.dir));
                 f.set_property(
@@ -145,6 +288,101 @@ impl StreetNetwork {
                 if let Some(ref muv) = lane.lane {
                     f.set_property("muv", serde_json::to_value(muv)?);
                 }
+                f.set_property("lifecycle", road.lifecycle.as_str());
+                if let Some(ref steps) = road.steps {
+                    f.set_property("steps", serde_json::to_value(steps)?);
+                }
+                if !road.availability.is_unconditional() {
+                    f.set_property(
+                        "availability",
+                        Value::Array(
+                            road.availability
+                                .describe()
+                                .into_iter()
+                                .map(Value::from)
+                                .collect(),
+                        ),
+                    );
+                }
+                if let Some(width) = road.width_constraint {
+                    f.set_property("width_constraint", width.inner_meters());
+                }
+                if let Some(height) = road.freight.max_height {
+                    f.set_property("max_height", height.inner_meters());
+                }
+                if let Some(weight) = road.freight.max_weight {
+                    f.set_property("max_weight", weight);
+                }
+                if let Some(load) = road.freight.max_axle_load {
+                    f.set_property("max_axle_load", load);
+                }
+                if road.freight.hgv_banned {
+                    f.set_property("hgv_banned", true);
+                }
+                if let Some(kind) = ramp_lanes.get(&(road.id, idx)) {
+                    f.set_property("ramp_lane", kind.clone());
+                }
+                if let Some(speed) = lane.max_speed {
+                    f.set_property(
+                        "lane_speed_limit_kmph",
+                        speed.inner_meters_per_second() * 3.6,
+                    );
+                }
+                if let Some(ref hours) = lane.bus_hours {
+                    f.set_property("bus_hours", hours.clone());
+                }
+                if let Some(access) = lane.shoulder_access {
+                    f.set_property("shoulder_vehicles", access.vehicles);
+                    f.set_property("shoulder_emergency", access.emergency);
+                }
+                let widths = road.widths();
+                f.set_property("road_carriageway_width", widths.carriageway.inner_meters());
+                f.set_property(
+                    "road_curb_to_curb_width",
+                    widths.curb_to_curb.inner_meters(),
+                );
+                f.set_property(
+                    "road_right_of_way_width",
+                    widths.right_of_way.inner_meters(),
+                );
+                f.set_property("road_general_lanes", widths.general_lanes);
+                let capacity = road.capacity();
+                if let Some(ref forward) = capacity.forward {
+                    f.set_property("road_capacity_forward", directed_json(forward));
+                }
+                if let Some(ref backward) = capacity.backward {
+                    f.set_property("road_capacity_backward", directed_json(backward));
+                }
+                let path_attributes = road.path_attributes.get(&idx);
+                if let Some(surface) =
+                    path_attributes.and_then(|x| x.surface.clone()).or_else(|| {
+                        road.lane_surfaces
+                            .get(&idx)
+                            .or(road.surface.as_ref())
+                            .map(|x| x.to_string())
+                    })
+                {
+                    f.set_property("surface", surface);
+                }
+                if let Some(attributes) = path_attributes {
+                    f.set_property("incline", attributes.incline.clone());
+                    f.set_property("smoothness", attributes.smoothness.clone());
+                    f.set_property("lit", attributes.lit);
+                }
+                let lane_features: Vec<Value> = road
+                    .lane_features
+                    .iter()
+                    .filter(|x| x.lane == idx)
+                    .map(|x| {
+                        serde_json::json!({
+                            "kind": format!("{:?}", x.kind),
+                            "dist": x.dist.inner_meters(),
+                        })
+                    })
+                    .collect();
+                if !lane_features.is_empty() {
+                    f.set_property("features", Value::Array(lane_features));
+                }
                 features.push(f);
             }
         }
@@ -152,13 +390,18 @@ impl StreetNetwork {
         serialize_features(features)
     }
 
-    /// For an intersection, show the clockwise ordering of roads around it
+    /// For an intersection, show the clockwise ordering of roads around it. Each point has the
+    /// `road`, its `bearing`, and the `gap_to_next` road, from `clockwise_road_angles`.
     pub fn debug_clockwise_ordering_geojson(&self, filter: &Filter) -> Result<String> {
         let mut features = Vec::new();
 
         for intersection in filter.intersections(self) {
-            for (idx, r) in intersection.roads.iter().enumerate() {
-                let road = &self.roads[r];
+            for (idx, angles) in self
+                .clockwise_road_angles(intersection.id)
+                .into_iter()
+                .enumerate()
+            {
+                let road = &self.roads[&angles.road];
                 let pt = if road.src_i == intersection.id {
                     road.center_line.first_pt()
                 } else {
@@ -169,13 +412,317 @@ impl StreetNetwork {
                     "label",
                     format!("{} / {}", idx + 1, intersection.roads.len()),
                 );
+                f.set_property("road", road.id.0);
+                f.set_property("bearing", angles.bearing);
+                f.set_property("gap_to_next", angles.gap_to_next);
+                features.push(f);
+            }
+        }
+
+        serialize_features(features)
+    }
+
+    /// For each end of a road, shows how it was trimmed back from the intersection. See
+    /// `Road::trim_provenance`.
+    pub fn debug_trim_provenance_geojson(&self, filter: &Filter) -> Result<String> {
+        let mut features = Vec::new();
+        for road in filter.roads(self) {
+            for (provenance, pt, i) in [
+                (
+                    &road.trim_provenance[0],
+                    road.center_line.first_pt(),
+                    road.src_i,
+                ),
+                (
+                    &road.trim_provenance[1],
+                    road.center_line.last_pt(),
+                    road.dst_i,
+                ),
+            ] {
+                let Some(provenance) = provenance else {
+                    continue;
+                };
+                let mut f = Feature::from(pt.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("road", road.id.0);
+                f.set_property("intersection", i.0);
+                f.set_property("algorithm", format!("{:?}", provenance.algorithm));
+                f.set_property("reason", provenance.reason.clone());
+                f.set_property("trim", provenance.trim.inner_meters());
+                f.set_property(
+                    "label",
+                    format!("{:?}, trimmed {}", provenance.algorithm, provenance.trim),
+                );
+                features.push(f);
+            }
+        }
+        serialize_features(features)
+    }
+
+    /// Shows every road leaving the map, with an arrow pointing off-map and properties describing
+    /// which way traffic can flow.
+    pub fn to_map_edges_geojson(&self) -> Result<String> {
+        let mut features = Vec::new();
+        for stub in self.map_edge_stubs() {
+            let arrow = PolyLine::must_new(vec![
+                stub.point,
+                stub.point.project_away(Distance::meters(10.0), stub.angle),
+            ]);
+            let mut f = Feature::from(arrow.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("intersection", stub.intersection.0);
+            f.set_property("road", stub.road.0);
+            f.set_property("inbound", stub.inbound);
+            f.set_property("outbound", stub.outbound);
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+
+    /// Shows the paths inside buildings kept separately by `IndoorPaths::Separate`, as lines.
+    pub fn to_indoor_paths_geojson(&self) -> Result<String> {
+        let mut features = Vec::new();
+        for (id, pl) in &self.indoor_paths {
+            let mut f = Feature::from(pl.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("osm_way_id", id.0);
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+
+    /// Shows every individual parking spot, and the places along parking lanes where parking
+    /// isn't allowed.
+    pub fn to_parking_geojson(&self) -> Result<String> {
+        let mut features = Vec::new();
+        for parking in self.find_parking() {
+            for spot in &parking.spots {
+                let mut f = Feature::from(spot.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("type", "spot");
+                f.set_property("road", parking.id.road.0);
+                f.set_property("lane", parking.id.index);
+                f.set_property("side", format!("{:?}", parking.side));
+                f.set_property("parking", format!("{:?}", parking.parking_type));
+                f.set_property("capacity", parking.capacity());
+                f.set_property("tagged_capacity", parking.tagged_capacity);
+                features.push(f);
+            }
+
+            let road = &self.roads[&parking.id.road];
+            let center = road.get_lane_center_lines().remove(parking.id.index);
+            let width = road.lane_specs_ltr[parking.id.index].width;
+            for exclusion in &parking.exclusions {
+                if let Ok(slice) = center.maybe_exact_slice(exclusion.start, exclusion.end) {
+                    let mut f = Feature::from(
+                        slice
+                            .make_polygons(width)
+                            .to_geojson(Some(&self.gps_bounds)),
+                    );
+                    f.set_property("type", "exclusion");
+                    f.set_property("road", parking.id.road.0);
+                    f.set_property("lane", parking.id.index);
+                    f.set_property("reason", format!("{:?}", exclusion.reason));
+                    features.push(f);
+                }
+            }
+        }
+        serialize_features(features)
+    }
+
+    /// Generates an arrow per movement through each intersection. Movements banned by turn
+    /// restrictions are included, but not allowed.
+    pub fn to_movements_geojson(&self, filter: &Filter) -> Result<String> {
+        let mut features = Vec::new();
+        for intersection in filter.intersections(self) {
+            let i = intersection.id;
+            let mut movements = intersection.movements.clone();
+            for r in &intersection.roads {
+                for (rt, to) in &self.roads[r].turn_restrictions {
+                    if *rt == RestrictionType::BanTurns
+                        && intersection.roads.contains(to)
+                        && !movements.contains(&(*r, *to))
+                    {
+                        movements.push((*r, *to));
+                    }
+                }
+            }
+            movements.sort();
+
+            let attributes = self.movement_attributes(i);
+            for ((from, to), polygon) in movements_for_intersection(self, i, &movements) {
+                let from_road = &self.roads[&from];
+                let mut f = Feature::from(polygon.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("type", "movement");
+                f.set_property("intersection", i.0);
+                f.set_property("from_road", from.0);
+                f.set_property("to_road", to.0);
+                f.set_property(
+                    "turn_direction",
+                    format!(
+                        "{:?}",
+                        turn_direction_for_angle(self.movement_angle(from, to, i))
+                    ),
+                );
+                f.set_property(
+                    "allowed_classes",
+                    Value::Array(
+                        self.movement_traffic_classes(from, to, i)
+                            .into_iter()
+                            .map(|class| format!("{class:?}").into())
+                            .collect(),
+                    ),
+                );
+                f.set_property("allowed", intersection.movements.contains(&(from, to)));
+                f.set_property(
+                    "restriction_relation",
+                    from_road.turn_restriction_relations.get(&to).map(|id| id.0),
+                );
+                f.set_property(
+                    "approach_control",
+                    self.approach_control(i, from).map(|x| format!("{x:?}")),
+                );
+                if let Some(attributes) = attributes.iter().find(|x| x.from == from && x.to == to) {
+                    f.set_property("conflicting_movements", attributes.conflicting_movements);
+                    f.set_property("crosses_traffic", attributes.crosses_traffic);
+                }
+                features.push(f);
+            }
+        }
+        serialize_features(features)
+    }
+
+    /// Generates a point per sign, signal, or crossing controlling an intersection.
+    pub fn to_traffic_control_geojson(&self, filter: &Filter) -> Result<String> {
+        let mut features = Vec::new();
+        for intersection in filter.intersections(self) {
+            for device in &intersection.traffic_control_devices {
+                let mut f = Feature::from(device.pt.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("type", "traffic_control");
+                f.set_property("kind", format!("{:?}", device.kind));
+                f.set_property("intersection", intersection.id.0);
+                f.set_property("osm_node_id", device.osm_node.map(|id| id.0));
+                f.set_property(
+                    "approaches",
+                    Value::Array(device.approaches.iter().map(|r| r.0.into()).collect()),
+                );
                 features.push(f);
             }
         }
+        serialize_features(features)
+    }
 
+    /// Renders every place where two roads cross without connecting, as points. See
+    /// `find_grade_separations`.
+    pub fn to_grade_separations_geojson(&self, filter: &Filter) -> Result<String> {
+        let roads: BTreeSet<RoadID> = filter.roads(self).map(|r| r.id).collect();
+        let mut features = Vec::new();
+        for grade_separation in self.find_grade_separations() {
+            if !roads.contains(&grade_separation.upper) && !roads.contains(&grade_separation.lower)
+            {
+                continue;
+            }
+            let upper = &self.roads[&grade_separation.upper];
+            let lower = &self.roads[&grade_separation.lower];
+            let mut f = Feature::from(grade_separation.pt.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("type", "grade_separation");
+            f.set_property("upper_road", upper.id.0);
+            f.set_property("lower_road", lower.id.0);
+            f.set_property("layer_delta", grade_separation.layer_delta);
+            f.set_property(
+                "clearance",
+                grade_separation.clearance.map(|x| x.inner_meters()),
+            );
+            f.set_property(
+                "upper_structure",
+                upper.structure.map(|x| format!("{:?}", x)),
+            );
+            f.set_property(
+                "lower_structure",
+                lower.structure.map(|x| format!("{:?}", x)),
+            );
+            f.set_property("suspicious", grade_separation.is_suspicious(self));
+            features.push(f);
+        }
         serialize_features(features)
     }
 
+    /// Shows each group of intersections connected by `junction=intersection` roads as one
+    /// polygon, with the member intersections and roads, and the through-movements between
+    /// external roads.
+    pub fn to_junction_complexes_geojson(&self, filter: &Filter) -> Result<String> {
+        let intersections: BTreeSet<IntersectionID> =
+            filter.intersections(self).map(|i| i.id).collect();
+        let mut features = Vec::new();
+        for complex in self.find_junction_complexes() {
+            if complex.intersections.is_disjoint(&intersections) {
+                continue;
+            }
+            let mut f = Feature::from(complex.polygon.to_geojson(Some(&self.gps_bounds)));
+            f.set_property("type", "junction_complex");
+            f.set_property(
+                "intersections",
+                Value::Array(complex.intersections.iter().map(|i| i.0.into()).collect()),
+            );
+            f.set_property(
+                "internal_roads",
+                Value::Array(complex.internal_roads.iter().map(|r| r.0.into()).collect()),
+            );
+            f.set_property(
+                "external_roads",
+                Value::Array(complex.external_roads.iter().map(|r| r.0.into()).collect()),
+            );
+            f.set_property(
+                "movements",
+                Value::Array(
+                    complex
+                        .movements
+                        .iter()
+                        .map(|(from, to)| Value::Array(vec![from.0.into(), to.0.into()]))
+                        .collect(),
+                ),
+            );
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+
+    /// The classes of traffic with lanes leading into and out of a movement.
+    fn movement_traffic_classes(
+        &self,
+        from: RoadID,
+        to: RoadID,
+        i: IntersectionID,
+    ) -> Vec<TrafficClass> {
+        let from = &self.roads[&from];
+        let to = &self.roads[&to];
+        let incoming_dir = if from.dst_i == i {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+        let outgoing_dir = if to.src_i == i {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+        let outgoing: Vec<TrafficClass> = to
+            .lane_specs_ltr
+            .iter()
+            .filter(|lane| lane.dir == outgoing_dir)
+            .filter_map(|lane| lane.lt.traffic_class())
+            .collect();
+
+        let mut classes = Vec::new();
+        for lane in &from.lane_specs_ltr {
+            if lane.dir != incoming_dir {
+                continue;
+            }
+            if let Some(class) = lane.lt.traffic_class() {
+                if outgoing.contains(&class) && !classes.contains(&class) {
+                    classes.push(class);
+                }
+            }
+        }
+        classes
+    }
+
     pub fn debug_movements_from_lane_geojson(&self, id: LaneID) -> Result<String> {
         let road = &self.roads[&id.road];
         let i = if road.lane_specs_ltr[id.index].dir == Direction::Forward {
@@ -185,7 +732,9 @@ impl StreetNetwork {
         };
 
         let mut features = Vec::new();
-        for ((from, _), polygon) in movements_for_intersection(self, i) {
+        for ((from, _), polygon) in
+            movements_for_intersection(self, i, &self.intersections[&i].movements)
+        {
             if from == road.id {
                 features.push(Feature::from(polygon.to_geojson(Some(&self.gps_bounds))));
             }
@@ -195,15 +744,21 @@ impl StreetNetwork {
 }
 
 impl DebugStreets {
-    /// None if there's nothing labelled
+    /// Points, then polylines, each sorted by label. Things with the same label keep the order
+    /// they were labelled in. None if there's nothing labelled
     pub fn to_debug_geojson(&self) -> Option<String> {
+        let mut points: Vec<_> = self.points.iter().collect();
+        points.sort_by(|a, b| a.1.cmp(&b.1));
+        let mut polylines: Vec<_> = self.polylines.iter().collect();
+        polylines.sort_by(|a, b| a.1.cmp(&b.1));
+
         let mut features = Vec::new();
-        for (pt, label) in &self.points {
+        for (pt, label) in points {
             let mut f = Feature::from(pt.to_geojson(Some(&self.streets.gps_bounds)));
             f.set_property("label", label.to_string());
             features.push(f);
         }
-        for (pl, label) in &self.polylines {
+        for (pl, label) in polylines {
             let mut f = Feature::from(pl.to_geojson(Some(&self.streets.gps_bounds)));
             f.set_property("label", label.to_string());
             features.push(f);
@@ -213,11 +768,63 @@ impl DebugStreets {
         }
         Some(serialize_features(features).unwrap())
     }
+
+    /// Shows what changed since the previous step. Each feature has a `change` property, `added`,
+    /// `modified`, or `removed`, and an `object`, `road` or `intersection`. Removed objects are
+    /// drawn where they were in the previous step. None if nothing changed.
+    pub fn to_diff_geojson(&self) -> Option<String> {
+        let streets = &self.streets;
+        let diff = &self.diff;
+        let mut features = Vec::new();
+        let mut push = |polygon: &Polygon, object: &str, id: usize, change: &str| {
+            let mut f = Feature::from(polygon.to_geojson(Some(&streets.gps_bounds)));
+            f.set_property("object", object);
+            f.set_property("id", id);
+            f.set_property("change", change);
+            features.push(f);
+        };
+
+        for (ids, change) in [
+            (&diff.roads_added, "added"),
+            (&diff.roads_modified, "modified"),
+        ] {
+            for r in ids {
+                let road = &streets.roads[r];
+                let polygon = road.center_line.make_polygons(road.total_width());
+                push(&polygon, "road", r.0, change);
+            }
+        }
+        for (r, polygon) in &diff.roads_removed {
+            push(polygon, "road", r.0, "removed");
+        }
+        for (ids, change) in [
+            (&diff.intersections_added, "added"),
+            (&diff.intersections_modified, "modified"),
+        ] {
+            for i in ids {
+                push(
+                    &streets.intersections[i].polygon,
+                    "intersection",
+                    i.0,
+                    change,
+                );
+            }
+        }
+        for (i, polygon) in &diff.intersections_removed {
+            push(polygon, "intersection", i.0, "removed");
+        }
+
+        if features.is_empty() {
+            return None;
+        }
+        Some(serialize_features(features).unwrap())
+    }
 }
 
 fn movements_for_intersection(
     streets: &StreetNetwork,
     i: IntersectionID,
+    movements: &[Movement],
 ) -> Vec<(Movement, Polygon)> {
     // Each movement is represented as an arrow from the end of one road to the beginning of
     // another. To stop arrows overlapping, arrows to/from bidirectional roads are offset from
@@ -259,7 +866,7 @@ fn movements_for_intersection(
         .collect();
 
     let mut result = Vec::new();
-    for (a, b) in &streets.intersections[&i].movements {
+    for (a, b) in movements {
         if a != b {
             if let Ok(line) = Line::new(road_points[a].0, road_points[b].1) {
                 result.push((
@@ -273,12 +880,40 @@ fn movements_for_intersection(
     result
 }
 
-fn serialize_features(features: Vec<Feature>) -> Result<String> {
+/// Compresses the output of any of the `to_*` methods. Useful to shrink big payloads, like
+/// passing a whole city's lane polygons across the WASM boundary.
+/// The RGB color of each lane type, matching the web app
+fn lane_color(lt: LaneType) -> (u8, u8, u8) {
+    match lt {
+        LaneType::Driving | LaneType::SharedLeftTurn => (0x00, 0x00, 0x00),
+        LaneType::Parking(_) => (0x33, 0x33, 0x33),
+        LaneType::Sidewalk | LaneType::Shoulder => (0xCC, 0xCC, 0xCC),
+        LaneType::Biking => (0x0F, 0x7D, 0x4B),
+        LaneType::Bus => (0xBE, 0x4A, 0x4C),
+        LaneType::Construction => (0xFF, 0x6D, 0x00),
+        LaneType::LightRail => (0x84, 0x42, 0x04),
+        LaneType::Footway => (0xDD, 0xDD, 0xE8),
+        LaneType::SharedUse => (0xDE, 0xD6, 0x8A),
+        LaneType::Buffer(BufferType::Curb) => (0xFF, 0xFF, 0xFF),
+        LaneType::Buffer(_) => (0x55, 0x55, 0x55),
+    }
+}
+
+#[cfg(feature = "gzip")]
+pub fn gzip(output: &str) -> Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(output.as_bytes())?;
+    Ok(encoder.finish()?)
+}
+
+pub(crate) fn serialize_features(features: Vec<Feature>) -> Result<String> {
     let gj = geojson::GeoJson::from(geojson::FeatureCollection {
         bbox: None,
         features,
         foreign_members: None,
     });
-    let output = serde_json::to_string_pretty(&gj)?;
+    let output = serde_json::to_string(&gj)?;
     Ok(output)
 }
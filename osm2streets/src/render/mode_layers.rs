@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use geojson::{Feature, FeatureCollection};
+use serde_json::{Map, Value};
+
+use super::Filter;
+use crate::{IntersectionID, Profile, StreetNetwork};
+
+impl StreetNetwork {
+    /// Splits the network into one layer per mode of travel, so a frontend can toggle them
+    /// independently. Returns a JSON object with `motor`, `cycling`, `walking`, and `rail`
+    /// FeatureCollections. Each layer has a polygon per lane usable by that mode and a polygon per
+    /// intersection connecting those lanes. Lanes have `road` and `index` properties and
+    /// intersections have `intersection`, matching the IDs in other outputs.
+    pub fn to_mode_layers_geojson(&self, filter: &Filter) -> Result<String> {
+        let mut layers = Map::new();
+        for (name, profile) in [
+            ("motor", Profile::Motor),
+            ("cycling", Profile::Bicycle),
+            ("walking", Profile::Pedestrian),
+            ("rail", Profile::Rail),
+        ] {
+            let mut features = Vec::new();
+            let mut intersections: BTreeSet<IntersectionID> = BTreeSet::new();
+            for road in filter.roads(self) {
+                for (idx, (lane, pl)) in road
+                    .lane_specs_ltr
+                    .iter()
+                    .zip(road.get_lane_center_lines().into_iter())
+                    .enumerate()
+                {
+                    if lane.is_virtual || !profile.can_use_lane(road, lane) {
+                        continue;
+                    }
+                    let mut f = Feature::from(
+                        pl.make_polygons(lane.width)
+                            .to_geojson(Some(&self.gps_bounds)),
+                    );
+                    f.set_property("layer", name);
+                    f.set_property("road", road.id.0);
+                    f.set_property("index", idx);
+                    f.set_property("type", format!("{:?}", lane.lt));
+                    f.set_property("direction", format!("{:?}", lane.dir));
+                    features.push(f);
+                    intersections.extend(road.endpoints());
+                }
+            }
+
+            for i in intersections {
+                let intersection = &self.intersections[&i];
+                let mut f = Feature::from(intersection.polygon.to_geojson(Some(&self.gps_bounds)));
+                f.set_property("layer", name);
+                f.set_property("intersection", i.0);
+                f.set_property("intersection_kind", format!("{:?}", intersection.kind));
+                features.push(f);
+            }
+
+            layers.insert(
+                name.to_string(),
+                serde_json::to_value(FeatureCollection {
+                    bbox: None,
+                    features,
+                    foreign_members: None,
+                })?,
+            );
+        }
+        Ok(serde_json::to_string(&Value::Object(layers))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{LonLat, PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionKind, Road};
+
+    #[test]
+    fn test_mode_layers() {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        let pts = [
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(100.0, 100.0),
+        ];
+        let intersections: Vec<IntersectionID> = pts
+            .iter()
+            .map(|pt| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    IntersectionKind::MapEdge,
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        // A street with sidewalks and bike lanes, continuing as a footway
+        let mut street = Tags::empty();
+        street.insert("highway", "residential");
+        street.insert("sidewalk", "both");
+        street.insert("cycleway:both", "lane");
+        let mut footway = Tags::empty();
+        footway.insert("highway", "footway");
+        for (idx, tags) in [street, footway].into_iter().enumerate() {
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                intersections[idx],
+                intersections[idx + 1],
+                PolyLine::must_new(vec![pts[idx], pts[idx + 1]]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+        }
+
+        let layers: Value =
+            serde_json::from_str(&streets.to_mode_layers_geojson(&Filter::All).unwrap()).unwrap();
+        let lane_types = |layer: &str| -> Vec<String> {
+            let mut types: Vec<String> = layers[layer]["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|f| f["properties"]["road"].is_u64())
+                .map(|f| {
+                    assert_eq!(layer, f["properties"]["layer"]);
+                    f["properties"]["type"].as_str().unwrap().to_string()
+                })
+                .collect();
+            types.sort();
+            types
+        };
+        let intersections_in = |layer: &str| -> Vec<usize> {
+            layers[layer]["features"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|f| f["properties"]["intersection"].as_u64())
+                .map(|i| i as usize)
+                .collect()
+        };
+
+        assert_eq!(vec!["Driving", "Driving"], lane_types("motor"));
+        assert_eq!(
+            vec!["Biking", "Biking", "Driving", "Driving"],
+            lane_types("cycling")
+        );
+        assert_eq!(
+            vec!["Footway", "Sidewalk", "Sidewalk"],
+            lane_types("walking")
+        );
+        assert!(lane_types("rail").is_empty());
+
+        // Only intersections connecting a lane in the layer are included, once each
+        assert_eq!(
+            vec![intersections[0].0, intersections[1].0],
+            intersections_in("motor")
+        );
+        assert_eq!(
+            intersections.iter().map(|i| i.0).collect::<Vec<_>>(),
+            intersections_in("walking")
+        );
+        assert!(intersections_in("rail").is_empty());
+    }
+}
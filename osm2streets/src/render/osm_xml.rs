@@ -0,0 +1,286 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use abstutil::Tags;
+use geom::Pt2D;
+use itertools::Itertools;
+use osm2lanes::RoadLayout;
+
+use super::Filter;
+use crate::{IntersectionControl, IntersectionID, Road, StreetNetwork, Structure};
+
+impl StreetNetwork {
+    /// Writes the simplified network as synthetic OSM XML, so tools that only understand OSM can
+    /// use the simplified topology. Each intersection becomes one node, shared by every way
+    /// touching it, and each road becomes one way, with tags describing its lanes. All IDs are
+    /// new and negative; the original OSM IDs are kept in `osm2streets:*` tags.
+    pub fn to_osm_xml(&self, filter: &Filter) -> String {
+        let mut next_id = -1;
+        let mut new_id = || {
+            let id = next_id;
+            next_id -= 1;
+            id
+        };
+
+        let mut nodes = String::new();
+        let mut ways = String::new();
+        let mut intersection_nodes: BTreeMap<IntersectionID, i64> = BTreeMap::new();
+        for road in filter.roads(self) {
+            let mut node_ids = Vec::new();
+            for (idx, pt) in road.reference_line.points().iter().enumerate() {
+                let endpoint = if idx == 0 {
+                    Some(road.src_i)
+                } else if idx == road.reference_line.points().len() - 1 {
+                    Some(road.dst_i)
+                } else {
+                    None
+                };
+
+                if let Some(i) = endpoint {
+                    if let Some(id) = intersection_nodes.get(&i) {
+                        node_ids.push(*id);
+                        continue;
+                    }
+                    let id = new_id();
+                    intersection_nodes.insert(i, id);
+                    node_ids.push(id);
+                    let intersection = &self.intersections[&i];
+                    self.write_node(
+                        &mut nodes,
+                        id,
+                        intersection.polygon.center(),
+                        &intersection_tags(self, i),
+                    );
+                } else {
+                    let id = new_id();
+                    node_ids.push(id);
+                    self.write_node(&mut nodes, id, *pt, &Tags::empty());
+                }
+            }
+
+            writeln!(ways, "  <way id=\"{}\" version=\"1\">", new_id()).unwrap();
+            for id in node_ids {
+                writeln!(ways, "    <nd ref=\"{id}\"/>").unwrap();
+            }
+            write_tags(&mut ways, &road_tags(road));
+            writeln!(ways, "  </way>").unwrap();
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm version=\"0.6\" \
+             generator=\"osm2streets\">\n{nodes}{ways}</osm>\n"
+        )
+    }
+
+    fn write_node(&self, out: &mut String, id: i64, pt: Pt2D, tags: &Tags) {
+        let gps = pt.to_gps(&self.gps_bounds);
+        if tags.inner().is_empty() {
+            writeln!(
+                out,
+                "  <node id=\"{id}\" version=\"1\" lat=\"{:.7}\" lon=\"{:.7}\"/>",
+                gps.y(),
+                gps.x()
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                out,
+                "  <node id=\"{id}\" version=\"1\" lat=\"{:.7}\" lon=\"{:.7}\">",
+                gps.y(),
+                gps.x()
+            )
+            .unwrap();
+            write_tags(out, tags);
+            writeln!(out, "  </node>").unwrap();
+        }
+    }
+}
+
+/// Describes the road's lanes with the usual tags, plus everything else known about it
+fn road_tags(road: &Road) -> Tags {
+    let mut tags = RoadLayout {
+        highway: road.highway_type.to_string(),
+        lanes: road.lane_specs_ltr.clone(),
+        placement: road.reference_line_placement,
+    }
+    .to_tags();
+    if let Some(ref name) = road.name {
+        tags.insert("name", name.to_string());
+    }
+    if !road.alt_names.is_empty() {
+        let alt_names: Vec<String> = road.alt_names.iter().map(|x| x.to_string()).collect();
+        tags.insert("alt_name", alt_names.join(";"));
+    }
+    if road.layer != 0 {
+        tags.insert("layer", road.layer.to_string());
+    }
+    match road.structure {
+        Some(Structure::Bridge) => {
+            tags.insert("bridge", "yes");
+        }
+        Some(Structure::Tunnel) => {
+            tags.insert("tunnel", "yes");
+        }
+        None => {}
+    }
+    if let Some(speed) = road.speed_limit {
+        tags.insert(
+            "maxspeed",
+            format!("{}", (speed.inner_meters_per_second() * 3.6).round()),
+        );
+    }
+    if let Some(ref surface) = road.surface {
+        tags.insert("surface", surface.to_string());
+    }
+    if let Some(height) = road.freight.max_height {
+        tags.insert("maxheight", format!("{:.2}", height.inner_meters()));
+    }
+    if let Some(weight) = road.freight.max_weight {
+        tags.insert("maxweight", weight.to_string());
+    }
+    if let Some(load) = road.freight.max_axle_load {
+        tags.insert("maxaxleload", load.to_string());
+    }
+    if road.freight.hgv_banned {
+        tags.insert("hgv", "no");
+    }
+    tags.insert("osm2streets:road", road.id.0.to_string());
+    if !road.osm_ids.is_empty() {
+        tags.insert(
+            "osm2streets:ways",
+            road.osm_ids.iter().map(|id| id.0).join(";"),
+        );
+    }
+    tags
+}
+
+fn intersection_tags(streets: &StreetNetwork, i: IntersectionID) -> Tags {
+    let intersection = &streets.intersections[&i];
+    let mut tags = Tags::empty();
+    if intersection.control == IntersectionControl::Signalled {
+        tags.insert("highway", "traffic_signals");
+    }
+    tags.insert("osm2streets:intersection", i.0.to_string());
+    if !intersection.osm_ids.is_empty() {
+        tags.insert(
+            "osm2streets:nodes",
+            intersection.osm_ids.iter().map(|id| id.0).join(";"),
+        );
+    }
+    tags
+}
+
+fn write_tags(out: &mut String, tags: &Tags) {
+    for (k, v) in tags.inner() {
+        writeln!(out, "    <tag k=\"{}\" v=\"{}\"/>", escape(k), escape(v)).unwrap();
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use geom::LonLat;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_lone_road, add_road};
+    use crate::IntersectionKind;
+
+    /// A signalled intersection joining two roads, plus a separate bent road
+    fn network() -> (StreetNetwork, Vec<crate::RoadID>) {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        let i0 = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::MapEdge);
+        let i1 = add_intersection(&mut streets, 100.0, 0.0, IntersectionKind::Intersection);
+        let i2 = add_intersection(&mut streets, 200.0, 0.0, IntersectionKind::MapEdge);
+        streets.intersections.get_mut(&i1.0).unwrap().control = IntersectionControl::Signalled;
+
+        let tags = ["highway=residential", "lanes=2", "name=Main & Co"];
+        let roads = vec![
+            add_road(&mut streets, i0, i1, &tags),
+            add_road(&mut streets, i1, i2, &tags),
+            add_lone_road(
+                &mut streets,
+                vec![
+                    Pt2D::new(0.0, 100.0),
+                    Pt2D::new(50.0, 150.0),
+                    Pt2D::new(100.0, 100.0),
+                ],
+                &["highway=service"],
+            ),
+        ];
+        (streets, roads)
+    }
+
+    fn count(xml: &str, pattern: &str) -> usize {
+        xml.matches(pattern).count()
+    }
+
+    #[test]
+    fn test_osm_xml() {
+        let (streets, roads) = network();
+        let xml = streets.to_osm_xml(&Filter::All);
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm "));
+        assert!(xml.ends_with("</osm>\n"));
+
+        // One node per intersection, plus the bend in the last road
+        assert_eq!(6, count(&xml, "<node "));
+        assert_eq!(3, count(&xml, "<way "));
+        assert_eq!(7, count(&xml, "<nd ref="));
+        // The first two roads share the node for the intersection between them
+        assert_eq!(2, count(&xml, "<nd ref=\"-2\"/>"));
+
+        // Only the intersection node is tagged as a signal
+        assert_eq!(1, count(&xml, "<tag k=\"highway\" v=\"traffic_signals\"/>"));
+        // The bend has no tags
+        let bend = xml
+            .lines()
+            .find(|line| line.starts_with("  <node id=\"-7\""))
+            .unwrap();
+        assert!(bend.ends_with("/>"), "{bend}");
+
+        assert_eq!(2, count(&xml, "<tag k=\"name\" v=\"Main &amp; Co\"/>"));
+        assert_eq!(
+            1,
+            count(
+                &xml,
+                &format!("<tag k=\"osm2streets:ways\" v=\"{}\"/>", roads[2].0)
+            )
+        );
+        assert_eq!(
+            1,
+            count(
+                &xml,
+                &format!("<tag k=\"osm2streets:road\" v=\"{}\"/>", roads[0].0)
+            )
+        );
+    }
+
+    #[test]
+    fn test_osm_xml_filtered() {
+        let (streets, roads) = network();
+        let filter = Filter::Filtered(BTreeSet::from([roads[0]]), BTreeSet::new());
+        let xml = streets.to_osm_xml(&filter);
+        assert_eq!(2, count(&xml, "<node "));
+        assert_eq!(1, count(&xml, "<way "));
+        assert_eq!(0, count(&xml, "highway\" v=\"service"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;",
+            escape("<a href=\"x\">Tom & Jerry's</a>")
+        );
+    }
+}
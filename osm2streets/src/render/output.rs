@@ -1,13 +1,59 @@
+use anyhow::Result;
+use enumset::EnumSet;
 use itertools::Itertools;
 
 use geo::MapCoordsInPlace;
+use geojson::Feature;
 use geom::{Distance, Line, Pt2D};
 
-use osm2lanes::{RoadPosition, TrafficClass};
-
 use crate::render::marking::{LongitudinalLine, RoadMarking, Transverse};
 use crate::render::paint::PaintArea;
-use crate::{BufferType, Direction, LaneType, Placement, StreetNetwork, TrafficInterruption};
+use crate::render::serialize_features;
+use crate::{
+    BufferType, Direction, LaneType, Placement, Road, RoadPosition, StreetNetwork, TrafficClass,
+    TrafficInterruption,
+};
+
+impl Road {
+    /// The line painted between lane `idx` and the next lane to its right. Lanes going the same
+    /// way are separated by a dashed line, or a solid line where `Road::lane_changes` forbids
+    /// crossing it.
+    pub(crate) fn line_between_lanes(&self, idx: usize) -> Option<LongitudinalLine> {
+        let guess_overtaking = match self.highway_type.as_str() {
+            "motorway" | "trunk" | "primary" => false,
+            _ => true,
+        };
+        let left = &self.lane_specs_ltr[idx];
+        let right = &self.lane_specs_ltr[idx + 1];
+        let between_lanes = || {
+            if left.dir != right.dir {
+                LongitudinalLine::dividing(guess_overtaking, guess_overtaking)
+            } else {
+                // Moving right in ltr order is to the left of backward traffic
+                LongitudinalLine::lane(
+                    self.may_change_lanes(idx + 1, right.dir == Direction::Forward),
+                    self.may_change_lanes(idx, left.dir == Direction::Backward),
+                )
+            }
+        };
+        Some(match (left.lt.traffic_class(), right.lt.traffic_class()) {
+            (Some(TrafficClass::Motor), Some(TrafficClass::Motor)) => between_lanes(),
+            (Some(TrafficClass::Motor), Some(TrafficClass::Bicycle))
+            | (Some(TrafficClass::Bicycle), Some(TrafficClass::Motor)) => {
+                // AU specifies the use of an "edge line" in this case...
+                LongitudinalLine::lane(false, false)
+            }
+            (Some(TrafficClass::Motor), _) | (_, Some(TrafficClass::Motor)) => {
+                LongitudinalLine::edge()
+            }
+            (Some(TrafficClass::Bicycle), Some(TrafficClass::Bicycle)) => between_lanes(),
+            (Some(TrafficClass::Bicycle), _) | (_, Some(TrafficClass::Bicycle)) => {
+                LongitudinalLine::edge()
+            }
+            _ => return None,
+        })
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Surface {
@@ -22,6 +68,7 @@ impl StreetNetwork {
 
         // Add polygons for road surfaces.
         for road in self.roads.values() {
+            let (road, _) = road.without_virtual_lanes();
             // Generate an area for each contiguous group of footpath, bike path and road lanes.
             let center_offset = road.total_width() / 2.0;
             let mut processed_width = Distance::ZERO;
@@ -73,6 +120,17 @@ impl StreetNetwork {
         output
     }
 
+    /// Returns `calculate_surfaces` as GeoJSON, with the `material` of each polygon.
+    pub fn to_surfaces_geojson(&self) -> Result<String> {
+        let mut features = Vec::new();
+        for surface in self.calculate_surfaces() {
+            let mut f = Feature::from(geojson::Geometry::from(&surface.area));
+            f.set_property("material", surface.material.to_str());
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+
     // TODO calculate_designations -> Vec<Designation> {...} // travel areas, parking, etc.
 
     /// Generate markings, described semantically.
@@ -80,12 +138,9 @@ impl StreetNetwork {
         let mut markings = Vec::new();
 
         for road in self.roads.values() {
+            let (road, _) = road.without_virtual_lanes();
             // Always oriented in the direction of the road
             let mut lane_centers = road.get_lane_center_lines();
-            let guess_overtaking = match road.highway_type.as_str() {
-                "motorway" | "trunk" | "primary" => false,
-                _ => true,
-            };
 
             // Add the left road edge.
             if let Some(first_lane) = road.lane_specs_ltr.first() {
@@ -104,37 +159,10 @@ impl StreetNetwork {
             }
             // Add longitudinal markings between lanes.
             for (idx, pair) in road.lane_specs_ltr.windows(2).enumerate() {
+                let Some(kind) = road.line_between_lanes(idx) else {
+                    continue;
+                };
                 if let Ok(separation) = lane_centers[idx].shift_right(pair[0].width / 2.0) {
-                    let kind = match (pair[0].lt.traffic_class(), pair[1].lt.traffic_class()) {
-                        (Some(TrafficClass::Motor), Some(TrafficClass::Motor)) => {
-                            if pair[0].dir != pair[1].dir {
-                                LongitudinalLine::dividing(guess_overtaking, guess_overtaking)
-                            } else {
-                                LongitudinalLine::lane(true, true)
-                            }
-                        }
-                        (Some(TrafficClass::Motor), Some(TrafficClass::Bicycle))
-                        | (Some(TrafficClass::Bicycle), Some(TrafficClass::Motor)) => {
-                            // AU specifies the use of an "edge line" in this case...
-                            LongitudinalLine::lane(false, false)
-                        }
-                        (Some(TrafficClass::Motor), _) | (_, Some(TrafficClass::Motor)) => {
-                            LongitudinalLine::edge()
-                        }
-                        (Some(TrafficClass::Bicycle), Some(TrafficClass::Bicycle)) => {
-                            if pair[0].dir != pair[1].dir {
-                                LongitudinalLine::dividing(guess_overtaking, guess_overtaking)
-                            } else {
-                                LongitudinalLine::lane(true, true)
-                            }
-                        }
-                        (Some(TrafficClass::Bicycle), _) | (_, Some(TrafficClass::Bicycle)) => {
-                            LongitudinalLine::edge()
-                        }
-                        _ => {
-                            continue;
-                        }
-                    };
                     markings.push(RoadMarking::longitudinal(
                         separation,
                         kind,
@@ -261,18 +289,68 @@ impl StreetNetwork {
                     continue;
                 }
 
-                // Add arrows along the lane, starting at the end.
+                // Add arrows along the lane, starting at the stop line (or the end). Only the arrow
+                // nearest the stop line shows the allowed turns, like on real roads; the others
+                // just show the direction of travel.
                 let step_size = Distance::meters(20.0);
                 let buffer_ends = Distance::meters(5.0);
-                for (pt, rev_angle) in center.reversed().step_along(step_size, buffer_ends) {
+                // Stop lines are measured along the untrimmed reference_line, but the lane centers
+                // are trimmed. Find the distance back from the end of the lane.
+                let stop_line_dist = if lane.dir == Direction::Forward {
+                    road.stop_line_end
+                        .vehicle_distance
+                        .map(|dist| road.reference_line.length() - road.trim_end - dist)
+                } else {
+                    road.stop_line_start
+                        .vehicle_distance
+                        .map(|dist| dist - road.trim_start)
+                };
+                let reversed = center.reversed();
+                let upstream = match stop_line_dist {
+                    Some(dist) if dist > Distance::ZERO && dist < reversed.length() => reversed
+                        .maybe_exact_slice(dist, reversed.length())
+                        .unwrap_or(reversed),
+                    _ => reversed,
+                };
+                for (idx, (pt, rev_angle)) in upstream
+                    .step_along(step_size, buffer_ends)
+                    .into_iter()
+                    .enumerate()
+                {
                     markings.push(RoadMarking::turn_arrow(
                         pt,
                         rev_angle.opposite(),
-                        lane.allowed_turns.clone(),
+                        if idx == 0 {
+                            lane.allowed_turns
+                        } else {
+                            EnumSet::empty()
+                        },
                     ))
                 }
             }
 
+            // Center turn lanes have pairs of arrows facing opposite ways.
+            for (lane, center) in road.lane_specs_ltr.iter().zip(lane_centers.iter()) {
+                if lane.lt != LaneType::SharedLeftTurn {
+                    continue;
+                }
+                let step_size = Distance::meters(30.0);
+                let buffer_ends = Distance::meters(10.0);
+                let gap = Distance::meters(1.5);
+                for (pt, angle) in center.step_along(step_size, buffer_ends) {
+                    markings.push(RoadMarking::turn_arrow(
+                        pt.project_away(gap, angle.opposite()),
+                        angle,
+                        lane.allowed_turns,
+                    ));
+                    markings.push(RoadMarking::turn_arrow(
+                        pt.project_away(gap, angle),
+                        angle.opposite(),
+                        lane.allowed_turns,
+                    ));
+                }
+            }
+
             // Add markings for painted buffers.
             for (lane, center) in road.lane_specs_ltr.iter().zip(lane_centers.iter()) {
                 if let LaneType::Buffer(buffer) = lane.lt {
@@ -293,7 +371,10 @@ impl StreetNetwork {
 
     pub fn calculate_paint_areas(&self) -> Vec<PaintArea> {
         let markings = self.calculate_markings();
-        let mut areas: Vec<_> = markings.iter().flat_map(RoadMarking::paint).collect();
+        let mut areas: Vec<_> = markings
+            .iter()
+            .flat_map(|marking| marking.paint(self.config.driving_side))
+            .collect();
 
         // Translate from map coords back to lonlat before returning.
         for paint in areas.iter_mut() {
@@ -3,7 +3,7 @@ use crate::render::{marking, marking::RoadMarking};
 // We use geom and stay in map space. Output is done in latlon.
 use geom::{Angle, Distance, Line, PolyLine, Polygon, Pt2D, Ring};
 
-use osm2lanes::TrafficClass;
+use crate::{DrivingSide, TrafficClass, TurnDirection};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct PaintArea {
@@ -49,11 +49,12 @@ trait Paint<T> {
 }
 
 impl RoadMarking {
-    pub fn paint(&self) -> Vec<PaintArea> {
+    /// Some symbols, like U-turn arrows, are drawn differently depending on the driving side.
+    pub fn paint(&self, driving_side: DrivingSide) -> Vec<PaintArea> {
         match self {
             RoadMarking::Longitudinal(g, m) => m.paint(g),
             RoadMarking::Transverse(g, m) => m.paint(g),
-            RoadMarking::Symbol(g0, g1, m) => m.paint(&(*g0, *g1)),
+            RoadMarking::Symbol(g0, g1, m) => m.paint(&(*g0, *g1, driving_side)),
             RoadMarking::Area(g, m) => m.paint(g),
         }
     }
@@ -194,8 +195,8 @@ impl Paint<Line> for marking::Transverse {
     }
 }
 
-impl Paint<(Pt2D, Angle)> for marking::Symbol {
-    fn paint(&self, &(pt, a): &(Pt2D, Angle)) -> Vec<PaintArea> {
+impl Paint<(Pt2D, Angle, DrivingSide)> for marking::Symbol {
+    fn paint(&self, &(pt, a, driving_side): &(Pt2D, Angle, DrivingSide)) -> Vec<PaintArea> {
         match self {
             marking::Symbol::TurnArrow(directions) => {
                 if directions.is_empty() {
@@ -219,9 +220,14 @@ impl Paint<(Pt2D, Angle)> for marking::Symbol {
                 } else {
                     directions
                         .iter()
-                        .map(|dir| {
-                            PaintArea::white(angled_arrow(pt, a, dir.turn_angle(), !dir.is_merge()))
+                        .filter_map(|dir| {
+                            if dir == TurnDirection::Reverse {
+                                u_turn_arrow(pt, a, driving_side)
+                            } else {
+                                Some(angled_arrow(pt, a, dir.turn_angle(), !dir.is_merge()))
+                            }
                         })
+                        .map(PaintArea::white)
                         .collect()
                 }
             }
@@ -247,6 +253,32 @@ fn angled_arrow(pt: Pt2D, base_angle: Angle, turn_angle: Angle, kinked: bool) ->
         .into_outer_ring()
 }
 
+/// Shares the stem with any other arrows in the glyph, then hooks back around, towards oncoming
+/// traffic.
+fn u_turn_arrow(pt: Pt2D, base_angle: Angle, driving_side: DrivingSide) -> Option<Ring> {
+    let arrow_len = Distance::meters(2.0);
+    let hook_width = Distance::meters(0.8);
+    let thickness = LINE_WIDTH_THICK;
+
+    let hook_angle = match driving_side {
+        DrivingSide::Right => base_angle.rotate_degs(-90.0),
+        DrivingSide::Left => base_angle.rotate_degs(90.0),
+    };
+    let top = pt.project_away(arrow_len / 4.0, base_angle);
+    let other_top = top.project_away(hook_width, hook_angle);
+    let points = vec![
+        pt.project_away(arrow_len / 2.0, base_angle.opposite()),
+        pt,
+        top,
+        other_top,
+        other_top.project_away(arrow_len / 2.0, base_angle.opposite()),
+    ];
+    PolyLine::new(points).ok().map(|pl| {
+        pl.make_arrow(thickness, geom::ArrowCap::Triangle)
+            .into_outer_ring()
+    })
+}
+
 impl Paint<Polygon> for marking::Area {
     fn paint(&self, geometry: &Polygon) -> Vec<PaintArea> {
         vec![PaintArea::white(geometry.get_outer_ring().clone())]
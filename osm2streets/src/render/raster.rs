@@ -0,0 +1,221 @@
+use anyhow::Result;
+use geom::{Bounds, Polygon, Pt2D};
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Transform};
+
+use super::paint::PaintColor;
+use super::{lane_color, Filter};
+use crate::StreetNetwork;
+
+/// Refuse to allocate images larger than this many pixels on either side
+const MAX_PIXELS: f64 = 10_000.0;
+
+/// A PNG image of part of the network, with a world file to geo-reference it.
+pub struct RasterSnapshot {
+    pub png: Vec<u8>,
+    /// The contents of a world file (usually saved as `.pgw`), placing the image in WGS84. The
+    /// image is in map space, which is only approximately linear in longitude and latitude, so
+    /// this is close but not exact for large areas.
+    pub world_file: String,
+}
+
+impl StreetNetwork {
+    /// Draws intersections, lanes, and markings touching the filter to a PNG.
+    pub fn to_png(&self, filter: &Filter, meters_per_pixel: f64) -> Result<RasterSnapshot> {
+        if meters_per_pixel <= 0.0 {
+            bail!("meters_per_pixel must be positive, not {meters_per_pixel}");
+        }
+
+        let mut shapes: Vec<(Polygon, Color)> = Vec::new();
+        for i in filter.intersections(self) {
+            shapes.push((i.polygon.clone(), Color::from_rgba8(102, 102, 102, 255)));
+        }
+        for road in filter.roads(self) {
+            for (lane, pl) in road
+                .lane_specs_ltr
+                .iter()
+                .zip(road.get_lane_center_lines().into_iter())
+            {
+                if lane.is_virtual {
+                    continue;
+                }
+                let (r, g, b) = lane_color(lane.lt);
+                shapes.push((
+                    pl.make_polygons(lane.width),
+                    Color::from_rgba8(r, g, b, 255),
+                ));
+            }
+        }
+        if shapes.is_empty() {
+            bail!("Nothing to draw");
+        }
+
+        let mut bounds = Bounds::new();
+        for (polygon, _) in &shapes {
+            bounds.union(polygon.get_bounds());
+        }
+        let width = (bounds.width() / meters_per_pixel).ceil();
+        let height = (bounds.height() / meters_per_pixel).ceil();
+        if width > MAX_PIXELS || height > MAX_PIXELS {
+            bail!("A {width}x{height} image is too large; increase meters_per_pixel");
+        }
+        let mut pixmap = Pixmap::new(width.max(1.0) as u32, height.max(1.0) as u32)
+            .ok_or_else(|| anyhow!("Can't create a {width}x{height} image"))?;
+        pixmap.fill(Color::WHITE);
+
+        let to_pixels = |pt: Pt2D| {
+            (
+                ((pt.x() - bounds.min_x) / meters_per_pixel) as f32,
+                ((pt.y() - bounds.min_y) / meters_per_pixel) as f32,
+            )
+        };
+        let mut fill = |pts: Vec<Pt2D>, color: Color| {
+            let mut pb = PathBuilder::new();
+            for (idx, pt) in pts.into_iter().enumerate() {
+                let (x, y) = to_pixels(pt);
+                if idx == 0 {
+                    pb.move_to(x, y);
+                } else {
+                    pb.line_to(x, y);
+                }
+            }
+            pb.close();
+            if let Some(path) = pb.finish() {
+                let mut paint = Paint::default();
+                paint.set_color(color);
+                paint.anti_alias = true;
+                pixmap.fill_path(
+                    &path,
+                    &paint,
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        };
+
+        for (polygon, color) in shapes {
+            fill(polygon.into_outer_ring().into_points(), color);
+        }
+        // Markings aren't filtered, but anything outside the image is clipped anyway
+        for paint in self
+            .calculate_markings()
+            .iter()
+            .flat_map(|marking| marking.paint(self.config.driving_side))
+        {
+            let color = match paint.color {
+                PaintColor::White => Color::WHITE,
+                PaintColor::Yellow => Color::from_rgba8(255, 204, 0, 255),
+            };
+            let pts = paint
+                .area
+                .exterior()
+                .coords()
+                .map(|c| Pt2D::new(c.x, c.y))
+                .collect();
+            fill(pts, color);
+        }
+
+        let png = pixmap.encode_png()?;
+
+        // World files describe the center of the top-left pixel
+        let top_left = Pt2D::new(bounds.min_x, bounds.min_y).to_gps(&self.gps_bounds);
+        let bottom_right = Pt2D::new(bounds.max_x, bounds.max_y).to_gps(&self.gps_bounds);
+        let x_per_pixel = (bottom_right.x() - top_left.x()) / (bounds.width() / meters_per_pixel);
+        let y_per_pixel = (bottom_right.y() - top_left.y()) / (bounds.height() / meters_per_pixel);
+        let world_file = format!(
+            "{}\n0.0\n0.0\n{}\n{}\n{}\n",
+            x_per_pixel,
+            y_per_pixel,
+            top_left.x() + x_per_pixel / 2.0,
+            top_left.y() + y_per_pixel / 2.0
+        );
+
+        Ok(RasterSnapshot { png, world_file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{LonLat, PolyLine};
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionKind, Road};
+
+    /// Two roads meeting at a corner, leaving the bottom-left of the image empty
+    fn corner() -> StreetNetwork {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        let pts = [
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(100.0, 100.0),
+        ];
+        let intersections: Vec<_> = pts
+            .iter()
+            .enumerate()
+            .map(|(idx, pt)| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    if idx == 1 {
+                        IntersectionKind::Connection
+                    } else {
+                        IntersectionKind::MapEdge
+                    },
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        for idx in 0..2 {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            tags.insert("lanes", "2");
+            tags.insert("sidewalk", "no");
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                intersections[idx],
+                intersections[idx + 1],
+                PolyLine::must_new(vec![pts[idx], pts[idx + 1]]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+        }
+        streets
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let streets = corner();
+        let snapshot = streets.to_png(&Filter::All, 0.5).unwrap();
+        // The same network always draws the same image
+        assert_eq!(snapshot.png, streets.to_png(&Filter::All, 0.5).unwrap().png);
+        assert_eq!(6, snapshot.world_file.lines().count());
+
+        let pixmap = Pixmap::decode_png(&snapshot.png).unwrap();
+        // 100m of road plus its width, at 2 pixels per meter
+        for size in [pixmap.width(), pixmap.height()] {
+            assert!(size > 200 && size < 240, "{size}");
+        }
+        let rgb = |x, y| {
+            let c = pixmap.pixel(x, y).unwrap();
+            (c.red(), c.green(), c.blue())
+        };
+        assert_eq!((255, 255, 255), rgb(0, pixmap.height() - 1));
+        let driving = lane_color(crate::LaneType::Driving);
+        assert!((0..pixmap.width())
+            .flat_map(|x| (0..pixmap.height()).map(move |y| (x, y)))
+            .any(|(x, y)| rgb(x, y) == driving));
+    }
+
+    #[test]
+    fn test_bad_scale() {
+        let streets = corner();
+        assert!(streets.to_png(&Filter::All, 0.0).is_err());
+        assert!(streets.to_png(&Filter::All, 0.001).is_err());
+    }
+}
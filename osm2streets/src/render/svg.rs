@@ -0,0 +1,238 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use geom::{Bounds, Pt2D};
+
+use super::paint::PaintColor;
+use super::{lane_color, Filter};
+use crate::StreetNetwork;
+
+/// Space around the drawn elements, in meters
+const MARGIN: f64 = 5.0;
+const LABEL_SIZE: f64 = 3.0;
+
+impl StreetNetwork {
+    /// Draws intersections, lanes colored by type, markings, and road names touching the filter as
+    /// an SVG. One unit is one meter in map space.
+    pub fn to_svg(&self, filter: &Filter) -> Result<String> {
+        let mut bounds = Bounds::new();
+        let mut body = String::new();
+
+        writeln!(body, "  <g id=\"intersections\" fill=\"#666666\">")?;
+        for i in filter.intersections(self) {
+            bounds.union(i.polygon.get_bounds());
+            writeln!(
+                body,
+                "    <path id=\"i{}\" d=\"{}\"/>",
+                i.id.0,
+                path(i.polygon.get_outer_ring().points())
+            )?;
+        }
+        writeln!(body, "  </g>")?;
+
+        let mut labels = String::new();
+        writeln!(body, "  <g id=\"lanes\">")?;
+        for road in filter.roads(self) {
+            for (idx, (lane, pl)) in road
+                .lane_specs_ltr
+                .iter()
+                .zip(road.get_lane_center_lines().into_iter())
+                .enumerate()
+            {
+                if lane.is_virtual {
+                    continue;
+                }
+                let polygon = pl.make_polygons(lane.width);
+                bounds.union(polygon.get_bounds());
+                let (r, g, b) = lane_color(lane.lt);
+                writeln!(
+                    body,
+                    "    <path id=\"r{}-{idx}\" class=\"{:?}\" fill=\"#{r:02X}{g:02X}{b:02X}\" \
+                     d=\"{}\"/>",
+                    road.id.0,
+                    lane.lt,
+                    path(polygon.get_outer_ring().points())
+                )?;
+            }
+
+            if let Some(ref name) = road.name {
+                let pt = road.center_line.middle();
+                let mut angle = road.center_line.overall_angle().normalized_degrees();
+                // Keep text upright
+                if angle > 90.0 && angle < 270.0 {
+                    angle -= 180.0;
+                }
+                writeln!(
+                    labels,
+                    "    <text x=\"{:.2}\" y=\"{:.2}\" transform=\"rotate({angle:.1} {:.2} \
+                     {:.2})\">{}</text>",
+                    pt.x(),
+                    pt.y(),
+                    pt.x(),
+                    pt.y(),
+                    escape(name)
+                )?;
+            }
+        }
+        writeln!(body, "  </g>")?;
+
+        if bounds.min_x > bounds.max_x {
+            bail!("Nothing to draw");
+        }
+
+        writeln!(body, "  <g id=\"markings\">")?;
+        for paint in self
+            .calculate_markings()
+            .iter()
+            .flat_map(|marking| marking.paint(self.config.driving_side))
+        {
+            let pts: Vec<Pt2D> = paint
+                .area
+                .exterior()
+                .coords()
+                .map(|c| Pt2D::new(c.x, c.y))
+                .collect();
+            if !pts.iter().any(|pt| bounds.contains(*pt)) {
+                continue;
+            }
+            let color = match paint.color {
+                PaintColor::White => "#FFFFFF",
+                PaintColor::Yellow => "#FFCC00",
+            };
+            writeln!(body, "    <path fill=\"{color}\" d=\"{}\"/>", path(&pts))?;
+        }
+        writeln!(body, "  </g>")?;
+
+        writeln!(
+            body,
+            "  <g id=\"steps\" stroke=\"#333333\" stroke-width=\"0.1\">"
+        )?;
+        for road in filter.roads(self) {
+            for (pt1, pt2) in road.stair_treads() {
+                writeln!(
+                    body,
+                    "    <line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\"/>",
+                    pt1.x(),
+                    pt1.y(),
+                    pt2.x(),
+                    pt2.y()
+                )?;
+            }
+        }
+        writeln!(body, "  </g>")?;
+
+        writeln!(
+            body,
+            "  <g id=\"labels\" font-family=\"sans-serif\" font-size=\"{LABEL_SIZE}\" \
+             text-anchor=\"middle\" dominant-baseline=\"middle\" fill=\"#000000\" \
+             stroke=\"#FFFFFF\" stroke-width=\"0.3\" paint-order=\"stroke\">"
+        )?;
+        body.push_str(&labels);
+        writeln!(body, "  </g>")?;
+
+        Ok(format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.2} {:.2} {:.2} {:.2}\">\n{body}</svg>\n",
+            bounds.min_x - MARGIN,
+            bounds.min_y - MARGIN,
+            bounds.width() + 2.0 * MARGIN,
+            bounds.height() + 2.0 * MARGIN,
+        ))
+    }
+}
+
+fn path(pts: &[Pt2D]) -> String {
+    let mut d = String::new();
+    for (idx, pt) in pts.iter().enumerate() {
+        let cmd = if idx == 0 { 'M' } else { 'L' };
+        write!(d, "{cmd}{:.2},{:.2} ", pt.x(), pt.y()).unwrap();
+    }
+    d.push('Z');
+    d
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    /// One named road between two map edges
+    fn network() -> (StreetNetwork, crate::RoadID) {
+        let mut streets = StreetNetwork::blank();
+        let i1 = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::MapEdge);
+        let i2 = add_intersection(&mut streets, 100.0, 0.0, IntersectionKind::MapEdge);
+        let r = add_road(
+            &mut streets,
+            i1,
+            i2,
+            &["highway=residential", "lanes=2", "name=Tom & Jerry Lane"],
+        );
+        (streets, r)
+    }
+
+    /// Parses `min_x min_y width height` from the `viewBox` attribute
+    fn view_box(svg: &str) -> Vec<f64> {
+        let start = svg.find("viewBox=\"").unwrap() + "viewBox=\"".len();
+        let end = start + svg[start..].find('"').unwrap();
+        svg[start..end]
+            .split(' ')
+            .map(|x| x.parse().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_svg() {
+        let (streets, r) = network();
+        let svg = streets.to_svg(&Filter::All).unwrap();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" "));
+        assert!(svg.ends_with("</svg>\n"));
+
+        let lanes = streets.roads[&r]
+            .lane_specs_ltr
+            .iter()
+            .filter(|lane| !lane.is_virtual)
+            .count();
+        assert_eq!(2, svg.matches("<path id=\"i").count());
+        assert_eq!(lanes, svg.matches("<path id=\"r").count());
+        assert_eq!(1, svg.matches("<text ").count());
+        assert!(svg.contains(">Tom &amp; Jerry Lane</text>"));
+
+        // The intersections stick out 3m past the ends of the road, then there's the margin
+        let view_box = view_box(&svg);
+        assert_eq!(4, view_box.len());
+        assert!((view_box[0] - (-3.0 - MARGIN)).abs() < 0.1, "{view_box:?}");
+        assert!(
+            (view_box[2] - (106.0 + 2.0 * MARGIN)).abs() < 0.2,
+            "{view_box:?}"
+        );
+        let width = streets.roads[&r].total_width().inner_meters();
+        assert!(view_box[3] >= width + 2.0 * MARGIN - 0.1, "{view_box:?}");
+    }
+
+    #[test]
+    fn test_nothing_to_draw() {
+        let (streets, _) = network();
+        let filter = Filter::Filtered(BTreeSet::new(), BTreeSet::new());
+        assert!(streets.to_svg(&filter).is_err());
+    }
+
+    #[test]
+    fn test_filtered_svg() {
+        let (streets, r) = network();
+        let filter = Filter::Filtered(BTreeSet::from([r]), BTreeSet::new());
+        let svg = streets.to_svg(&filter).unwrap();
+        assert_eq!(0, svg.matches("<path id=\"i").count());
+        assert_eq!(1, svg.matches("<text ").count());
+        // Only the road counts towards the bounds
+        assert!(view_box(&svg)[0] > -3.0 - MARGIN + 0.5);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use geo::{Area, BooleanOps};
+use geojson::{Feature, Geometry};
+use geom::{GPSBounds, Pt2D};
+use serde_json::Value;
+
+use crate::render::serialize_features;
+use crate::{Filter, IntersectionID, RoadID, StreetNetwork};
+
+impl StreetNetwork {
+    /// Shows the full right-of-way of each street: the union of the full width of its roads,
+    /// including sidewalks, verges, and zipped sidepaths, and the intersections along it. Roads
+    /// sharing a name form one street, so an intersection may be part of two streets. Unnamed
+    /// roads are each their own street.
+    pub fn to_right_of_way_geojson(&self, filter: &Filter) -> Result<String> {
+        let mut streets: BTreeMap<(Option<String>, Option<RoadID>), Vec<RoadID>> = BTreeMap::new();
+        for road in filter.roads(self) {
+            let key = match road.name {
+                Some(ref name) => (Some(name.to_string()), None),
+                None => (None, Some(road.id)),
+            };
+            streets.entry(key).or_default().push(road.id);
+        }
+
+        let mut features = Vec::new();
+        for ((name, _), roads) in streets {
+            let intersections: BTreeSet<IntersectionID> = roads
+                .iter()
+                .flat_map(|r| self.roads[r].endpoints())
+                .collect();
+
+            let mut union = geo::MultiPolygon::<f64>(Vec::new());
+            for r in &roads {
+                let road = &self.roads[r];
+                let polygon: geo::Polygon =
+                    road.center_line.make_polygons(road.total_width()).into();
+                union = union.union(&geo::MultiPolygon(vec![polygon]));
+            }
+            for i in &intersections {
+                let polygon: geo::Polygon = self.intersections[i].polygon.clone().into();
+                union = union.union(&geo::MultiPolygon(vec![polygon]));
+            }
+
+            let mut f = Feature::from(multipolygon_to_geojson(&union, &self.gps_bounds));
+            f.set_property("type", "right_of_way");
+            f.set_property("name", name);
+            f.set_property(
+                "roads",
+                Value::Array(roads.iter().map(|r| r.0.into()).collect()),
+            );
+            f.set_property(
+                "intersections",
+                Value::Array(intersections.iter().map(|i| i.0.into()).collect()),
+            );
+            f.set_property("area_square_meters", union.unsigned_area());
+            features.push(f);
+        }
+        serialize_features(features)
+    }
+}
+
+fn multipolygon_to_geojson(mp: &geo::MultiPolygon, gps_bounds: &GPSBounds) -> Geometry {
+    let ring = |ls: &geo::LineString| -> Vec<Vec<f64>> {
+        ls.coords()
+            .map(|c| {
+                let gps = Pt2D::new(c.x, c.y).to_gps(gps_bounds);
+                vec![gps.x(), gps.y()]
+            })
+            .collect()
+    };
+    Geometry::new(geojson::Value::MultiPolygon(
+        mp.0.iter()
+            .map(|polygon| {
+                std::iter::once(ring(polygon.exterior()))
+                    .chain(polygon.interiors().iter().map(ring))
+                    .collect()
+            })
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::LonLat;
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionKind, Road};
+
+    #[test]
+    fn test_right_of_way() {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        let pts: Vec<Pt2D> = (0..4).map(|x| Pt2D::new(100.0 * x as f64, 0.0)).collect();
+        let intersections: Vec<IntersectionID> = pts
+            .iter()
+            .map(|pt| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    IntersectionKind::Intersection,
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        // Two roads named Main Street, then an unnamed one
+        let mut roads = Vec::new();
+        for (idx, name) in [Some("Main Street"), Some("Main Street"), None]
+            .into_iter()
+            .enumerate()
+        {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            if let Some(name) = name {
+                tags.insert("name", name);
+            }
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                intersections[idx],
+                intersections[idx + 1],
+                geom::PolyLine::must_new(vec![pts[idx], pts[idx + 1]]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+            roads.push(id);
+        }
+
+        let geojson: Value =
+            serde_json::from_str(&streets.to_right_of_way_geojson(&Filter::All).unwrap()).unwrap();
+        let features = geojson["features"].as_array().unwrap();
+        assert_eq!(2, features.len());
+        let ids = |f: &Value, key: &str| -> Vec<usize> {
+            f["properties"][key]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|x| x.as_u64().unwrap() as usize)
+                .collect()
+        };
+
+        // Unnamed roads sort first
+        let unnamed = &features[0];
+        assert!(unnamed["properties"]["name"].is_null());
+        assert_eq!(vec![roads[2].0], ids(unnamed, "roads"));
+        assert_eq!(
+            vec![intersections[2].0, intersections[3].0],
+            ids(unnamed, "intersections")
+        );
+
+        let main = &features[1];
+        assert_eq!("Main Street", main["properties"]["name"]);
+        assert_eq!(vec![roads[0].0, roads[1].0], ids(main, "roads"));
+        assert_eq!(
+            vec![intersections[0].0, intersections[1].0, intersections[2].0],
+            ids(main, "intersections")
+        );
+        assert_eq!("MultiPolygon", main["geometry"]["type"]);
+
+        // The union covers both roads at full width
+        let width = streets.roads[&roads[0]].total_width().inner_meters();
+        let area = |f: &Value| f["properties"]["area_square_meters"].as_f64().unwrap();
+        assert!(area(main) >= 200.0 * width * 0.99);
+        assert!(area(main) > area(unnamed));
+    }
+}
@@ -1,23 +1,42 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Tags;
-use geom::{Angle, Distance, PolyLine, Speed};
+use geom::{Angle, Bounds, Distance, LonLat, PolyLine, Pt2D, Speed};
 
-use osm2lanes::{osm, RoadPosition};
+use osm2lanes::osm;
 
+use crate::lane_adjacency::parse_lane_changes;
+use crate::parking::parse_parking_capacity;
+use crate::path_attributes::parse_path_attributes;
+use crate::separate_ways::parse_separately_mapped;
+use crate::steps::parse_steps;
 use crate::{
-    get_lane_specs_ltr, CommonEndpoint, Direction, DrivingSide, InputRoad, IntersectionID,
-    LaneSpec, LaneType, MapConfig, Placement, RestrictionType, RoadID, RoadWithEndpoints,
-    StreetNetwork,
+    get_lane_specs_ltr, Availability, CommonEndpoint, Direction, DrivingSide, ExclusionReason,
+    FreightRestrictions, InputRoad, InternedString, IntersectionID, LaneChange, LaneSpec, LaneType,
+    MapConfig, PathAttributes, Placement, RestrictionType, RoadID, RoadPosition, RoadWithEndpoints,
+    Steps, StreetNetwork, TrimProvenance,
 };
 
+/// How far from an intersection `Road::placement_blend` eases the center line
+const PLACEMENT_BLEND_DISTANCE: Distance = Distance::const_meters(10.0);
+/// The spacing of points along the eased part of a center line
+const PLACEMENT_BLEND_STEP: Distance = Distance::const_meters(1.0);
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Road {
     pub id: RoadID,
     /// The original OSM ways making up this road. One road may consist of multiple ways (when an
     /// intersection is collapsed).
     pub osm_ids: Vec<osm::WayID>,
+    /// The OSM node where this road began when it was split from its OSM way. None if it began at
+    /// the map boundary or was split from another road. This isn't updated when roads are merged,
+    /// so along with the first of `osm_ids`, it identifies the road across re-imports.
+    #[serde(default)]
+    pub osm_start_node: Option<osm::NodeID>,
 
     pub src_i: IntersectionID,
     pub dst_i: IntersectionID,
@@ -26,15 +45,37 @@ pub struct Road {
     /// <https://wiki.openstreetmap.org/wiki/Key:highway>.
     ///
     /// Note for railways, this is actually the `railway` tag instead.
-    pub highway_type: String,
+    pub highway_type: InternedString,
     /// The name of the road in the default OSM-specified language
-    pub name: Option<String>,
+    pub name: Option<InternedString>,
+    /// Other names for the road, from `alt_name`, plus the names of roads merged into this one
+    #[serde(default)]
+    pub alt_names: Vec<InternedString>,
     /// This road exists only for graph connectivity. It's physically part of a complex
     /// intersection. A transformation will likely collapse it.
     pub internal_junction_road: bool,
     /// The vertical layer of the road, with 0 the default and negative values lower down. See
     /// <https://wiki.openstreetmap.org/wiki/Key:layer>.
     pub layer: isize,
+    /// Whether the road is a bridge or tunnel. See <https://wiki.openstreetmap.org/wiki/Key:bridge>
+    /// and <https://wiki.openstreetmap.org/wiki/Key:tunnel>.
+    #[serde(default)]
+    pub structure: Option<Structure>,
+    /// Whether the road exists yet, or anymore. See
+    /// <https://wiki.openstreetmap.org/wiki/Lifecycle_prefix>.
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+    /// Seasonal closures, fords, and conditional access restrictions
+    #[serde(default)]
+    pub availability: Availability,
+    /// How wide the carriageway physically is at a pinch point, like a narrow historic bridge. Any
+    /// lanes that wouldn't fit have been narrowed proportionally. See
+    /// <https://wiki.openstreetmap.org/wiki/Key:maxwidth:physical>.
+    #[serde(default)]
+    pub width_constraint: Option<Distance>,
+    /// Limits on the height and weight of vehicles, and bans on trucks
+    #[serde(default)]
+    pub freight: FreightRestrictions,
     /// The max legal speed limit, if specified. See
     /// <https://wiki.openstreetmap.org/wiki/Key:maxspeed>.
     pub speed_limit: Option<Speed>,
@@ -44,6 +85,12 @@ pub struct Road {
     /// tag and might be nonsense for the first/last segment.
     pub reference_line: PolyLine,
     pub reference_line_placement: Placement,
+    /// Where this road meets another continuing it with a different placement, the shift from
+    /// `reference_line` to `center_line` at the start and end is eased to this, so both center
+    /// lines meet without a kink. Set by `Transformation::BlendPlacementOffsets`, and undone when
+    /// another road is connected or disconnected there.
+    #[serde(default)]
+    pub placement_blend: [Option<Distance>; 2],
     /// The physical center of all the lanes, including sidewalks (at
     /// RoadPosition::FullWidthCenter). This will differ from `reference_line`, incorporating
     /// `reference_line_placement`, `trim_start`, `trim_end`, etc.
@@ -52,17 +99,122 @@ pub struct Road {
     /// extend the first line.
     pub trim_start: Distance,
     pub trim_end: Distance,
+    /// How `trim_start` and `trim_end` were calculated, for debugging. None until the
+    /// intersection's geometry is first calculated.
+    #[serde(default)]
+    pub trim_provenance: [Option<TrimProvenance>; 2],
 
     pub turn_restrictions: Vec<(RestrictionType, RoadID)>,
+    /// The OSM relation that each of `turn_restrictions` came from, keyed by the road the
+    /// restriction applies to.
+    #[serde(default)]
+    pub turn_restriction_relations: BTreeMap<RoadID, osm::RelationID>,
     /// (via, to). For turn restrictions where 'via' is an entire road. Only BanTurns.
     pub complicated_turn_restrictions: Vec<(RoadID, RoadID)>,
 
     pub lane_specs_ltr: Vec<LaneSpec>,
+    /// The number of parking spaces tagged on the left and right side of the original OSM way, if
+    /// any. See <https://wiki.openstreetmap.org/wiki/Street_parking>.
+    #[serde(default)]
+    pub parking_capacity: [Option<usize>; 2],
+    /// Places along the road, like mid-block crossings and bus stops, where nobody may park
+    #[serde(default)]
+    pub parking_exclusions: Vec<(Pt2D, ExclusionReason)>,
+    /// The OSM `surface` tag, if specified
+    #[serde(default)]
+    pub surface: Option<InternedString>,
+    /// The surface of individual lanes, keyed by index into `lane_specs_ltr`, when it differs from
+    /// the road's `surface`. This happens when a sidepath is zipped into the road.
+    #[serde(default)]
+    pub lane_surfaces: BTreeMap<usize, InternedString>,
+    /// Incline, surface, smoothness, and lighting of sidewalks and cycle lanes, keyed by index
+    /// into `lane_specs_ltr`
+    #[serde(default)]
+    pub path_attributes: BTreeMap<usize, PathAttributes>,
+    /// Sidewalks and cycleways that the tags say are mapped as separate ways
+    #[serde(default)]
+    pub separately_mapped: Vec<SeparatelyMapped>,
+    /// Restrictions on changing lanes out of specific lanes, keyed by index into `lane_specs_ltr`,
+    /// from `change:lanes` tags. Lanes without an entry allow changing in either direction.
+    #[serde(default)]
+    pub lane_changes: BTreeMap<usize, LaneChange>,
+    /// Crossings and bus stops along specific lanes. These come from a sidepath zipped into the
+    /// road.
+    #[serde(default)]
+    pub lane_features: Vec<LaneFeature>,
+    /// Lanes, keyed by index into `lane_specs_ltr`, that came from a frontage road merged into this
+    /// road. They're physically separate, usually only used for local access.
+    #[serde(default)]
+    pub service_lanes: BTreeSet<usize>,
+    /// Set for `highway=steps`
+    #[serde(default)]
+    pub steps: Option<Steps>,
+    /// Tagged as a frontage road. See <https://wiki.openstreetmap.org/wiki/Key:frontage_road>.
+    #[serde(default)]
+    pub frontage_road: bool,
+    /// Roads running alongside this one, like a frontage road and the main road it serves. Both
+    /// roads point to each other.
+    #[serde(default)]
+    pub parallel_to: BTreeSet<RoadID>,
 
     pub stop_line_start: StopLine,
     pub stop_line_end: StopLine,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Structure {
+    Bridge,
+    Tunnel,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Lifecycle {
+    #[default]
+    Existing,
+    UnderConstruction,
+    Proposed,
+    Disused,
+}
+
+impl Lifecycle {
+    pub fn parse(tags: &Tags) -> Self {
+        if tags.is(osm::HIGHWAY, "construction") {
+            Lifecycle::UnderConstruction
+        } else if tags.is(osm::HIGHWAY, "proposed") {
+            Lifecycle::Proposed
+        } else if tags.is("disused", "yes") || tags.contains_key("disused:highway") {
+            Lifecycle::Disused
+        } else {
+            Lifecycle::Existing
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Lifecycle::Existing => "existing",
+            Lifecycle::UnderConstruction => "under_construction",
+            Lifecycle::Proposed => "proposed",
+            Lifecycle::Disused => "disused",
+        }
+    }
+}
+
+/// Something at one point along a lane.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LaneFeature {
+    /// Index into `lane_specs_ltr`
+    pub lane: usize,
+    /// Relative to the road's `center_line`
+    pub dist: Distance,
+    pub kind: LaneFeatureKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaneFeatureKind {
+    Crossing,
+    BusStop,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct StopLine {
     /// Relative to the road's reference_line. Stop lines at the start of the road will have low
@@ -106,7 +258,7 @@ impl Road {
         osm_tags: Tags,
         config: &MapConfig,
     ) -> Self {
-        let lane_specs_ltr = get_lane_specs_ltr(&osm_tags, config);
+        let mut lane_specs_ltr = get_lane_specs_ltr(&osm_tags, config);
 
         let layer = if let Some(layer) = osm_tags.get("layer") {
             match layer.parse::<f64>() {
@@ -121,6 +273,19 @@ impl Road {
             0
         };
 
+        let structure = if osm_tags.contains_key("bridge") && !osm_tags.is("bridge", "no") {
+            Some(Structure::Bridge)
+        } else if osm_tags.contains_key("tunnel") && !osm_tags.is("tunnel", "no") {
+            Some(Structure::Tunnel)
+        } else {
+            None
+        };
+
+        let width_constraint = parse_width_constraint(&osm_tags, structure, &lane_specs_ltr);
+        if let Some(width) = width_constraint {
+            narrow_to_fit(&mut lane_specs_ltr, width);
+        }
+
         let speed_limit = osm_tags
             .get("maxspeed")
             .and_then(|x| parse_maxspeed(x.as_ref()));
@@ -134,25 +299,54 @@ impl Road {
         let mut result = Self {
             id,
             osm_ids,
+            osm_start_node: None,
             src_i,
             dst_i,
             highway_type: osm_tags
                 .get(osm::HIGHWAY)
                 .or_else(|| osm_tags.get("railway"))
-                .cloned()
+                .map(|x| InternedString::new(x))
                 .expect("Can't create a Road without the highway or railway tag"),
-            name: osm_tags.get("name").cloned(),
+            name: osm_tags.get("name").map(|x| InternedString::new(x)),
+            alt_names: osm_tags
+                .get("alt_name")
+                .map(|x| {
+                    x.split(';')
+                        .map(|x| InternedString::new(x.trim()))
+                        .collect()
+                })
+                .unwrap_or_default(),
             internal_junction_road: osm_tags.is("junction", "intersection"),
             layer,
+            structure,
+            lifecycle: Lifecycle::parse(&osm_tags),
+            availability: Availability::parse(&osm_tags),
+            width_constraint,
+            freight: FreightRestrictions::parse(&osm_tags),
             speed_limit,
             reference_line,
             reference_line_placement: placement,
+            placement_blend: [None, None],
             center_line: PolyLine::dummy(),
             trim_start: Distance::ZERO,
             trim_end: Distance::ZERO,
+            trim_provenance: [None, None],
             turn_restrictions: Vec::new(),
+            turn_restriction_relations: BTreeMap::new(),
             complicated_turn_restrictions: Vec::new(),
+            lane_changes: parse_lane_changes(&osm_tags, &lane_specs_ltr),
+            path_attributes: parse_path_attributes(&osm_tags, &lane_specs_ltr),
             lane_specs_ltr,
+            parking_capacity: parse_parking_capacity(&osm_tags),
+            parking_exclusions: Vec::new(),
+            surface: osm_tags.get("surface").map(|x| InternedString::new(x)),
+            lane_surfaces: BTreeMap::new(),
+            lane_features: Vec::new(),
+            separately_mapped: parse_separately_mapped(&osm_tags),
+            steps: parse_steps(&osm_tags),
+            service_lanes: BTreeSet::new(),
+            frontage_road: osm_tags.is("frontage_road", "yes"),
+            parallel_to: BTreeSet::new(),
             stop_line_start: StopLine::dummy(),
             stop_line_end: StopLine::dummy(),
         };
@@ -161,6 +355,89 @@ impl Road {
         result
     }
 
+    /// Re-derives everything parsed from OSM tags, exactly like `new`. The lanes may change, so
+    /// `lane_surfaces`, `lane_features`, and `service_lanes` from sidepaths and frontage roads
+    /// merged into this road no longer match them, and are cleared. Geometry and connections are
+    /// kept; the caller must update the center line and intersections.
+    pub fn update_from_tags(&mut self, tags: &Tags, config: &MapConfig) {
+        // No `..`, so a new field has to be sorted into one of these groups
+        let Road {
+            id: _,
+            osm_ids: _,
+            osm_start_node: _,
+            src_i: _,
+            dst_i: _,
+            highway_type,
+            name,
+            alt_names,
+            internal_junction_road,
+            layer,
+            structure,
+            lifecycle,
+            availability,
+            width_constraint,
+            freight,
+            speed_limit,
+            reference_line: _,
+            reference_line_placement,
+            placement_blend: _,
+            center_line: _,
+            trim_start: _,
+            trim_end: _,
+            trim_provenance: _,
+            turn_restrictions: _,
+            turn_restriction_relations: _,
+            complicated_turn_restrictions: _,
+            lane_specs_ltr,
+            parking_capacity,
+            parking_exclusions: _,
+            surface,
+            lane_surfaces,
+            path_attributes,
+            separately_mapped,
+            lane_changes,
+            lane_features,
+            service_lanes,
+            steps,
+            frontage_road,
+            parallel_to: _,
+            stop_line_start: _,
+            stop_line_end: _,
+        } = Road::new(
+            self.id,
+            self.osm_ids.clone(),
+            self.src_i,
+            self.dst_i,
+            self.reference_line.clone(),
+            tags.clone(),
+            config,
+        );
+
+        self.highway_type = highway_type;
+        self.name = name;
+        self.alt_names = alt_names;
+        self.internal_junction_road = internal_junction_road;
+        self.layer = layer;
+        self.structure = structure;
+        self.lifecycle = lifecycle;
+        self.availability = availability;
+        self.width_constraint = width_constraint;
+        self.freight = freight;
+        self.speed_limit = speed_limit;
+        self.reference_line_placement = reference_line_placement;
+        self.lane_specs_ltr = lane_specs_ltr;
+        self.parking_capacity = parking_capacity;
+        self.surface = surface;
+        self.lane_surfaces = lane_surfaces;
+        self.path_attributes = path_attributes;
+        self.separately_mapped = separately_mapped;
+        self.lane_changes = lane_changes;
+        self.lane_features = lane_features;
+        self.service_lanes = service_lanes;
+        self.steps = steps;
+        self.frontage_road = frontage_road;
+    }
+
     /// Resets the center_line using reference_line and reference_line_placement. Does
     /// not apply trim.
     pub fn update_center_line(&mut self, driving_side: DrivingSide) {
@@ -169,6 +446,21 @@ impl Road {
 
     /// Calculates the center_line from reference_line, reference_line_placement
     pub fn get_untrimmed_center_line(&self, driving_side: DrivingSide) -> PolyLine {
+        let shift = self.center_line_shift(driving_side);
+        let result = if self.placement_blend == [None, None] {
+            self.reference_line.shift_either_direction(shift)
+        } else {
+            shift_with_blended_ends(&self.reference_line, shift, self.placement_blend)
+        };
+        result.unwrap_or_else(|_| {
+            warn!("resulting center_line is degenerate!");
+            self.reference_line.clone()
+        })
+    }
+
+    /// How far `reference_line` is shifted to produce the center line, before any
+    /// `placement_blend`. Positive is to the right.
+    pub fn center_line_shift(&self, driving_side: DrivingSide) -> Distance {
         let ref_position = match self.reference_line_placement {
             Placement::Consistent(p) => p,
             Placement::Varying(p, _) => {
@@ -183,13 +475,32 @@ impl Road {
         };
         let ref_offset = self.left_edge_offset_of(ref_position, driving_side);
         let target_offset = self.left_edge_offset_of(RoadPosition::FullWidthCenter, driving_side);
+        target_offset - ref_offset
+    }
 
-        self.reference_line
-            .shift_either_direction(target_offset - ref_offset)
-            .unwrap_or_else(|_| {
-                warn!("resulting center_line is degenerate!");
-                self.reference_line.clone()
-            })
+    /// Like `center_line_shift`, but at some distance along `reference_line`, interpolating a
+    /// `Placement::Varying` and easing the ends with `placement_blend` the same way as
+    /// `shift_with_blended_ends`.
+    fn center_line_shift_at(&self, dist: Distance, driving_side: DrivingSide) -> Distance {
+        let len = self.reference_line.length();
+        let shift = match self.reference_line_placement {
+            Placement::Varying(start, end) if len > Distance::ZERO => {
+                let target = self.left_edge_offset_of(RoadPosition::FullWidthCenter, driving_side);
+                let start = target - self.left_edge_offset_of(start, driving_side);
+                let end = target - self.left_edge_offset_of(end, driving_side);
+                start + (end - start) * (dist / len)
+            }
+            _ => self.center_line_shift(driving_side),
+        };
+
+        let blend = PLACEMENT_BLEND_DISTANCE.min(len / 2.0);
+        match self.placement_blend {
+            [Some(target), _] if dist < blend => target + (shift - target) * (dist / blend),
+            [_, Some(target)] if len - dist < blend => {
+                target + (shift - target) * ((len - dist) / blend)
+            }
+            _ => shift,
+        }
     }
 
     pub fn is_light_rail(&self) -> bool {
@@ -198,6 +509,32 @@ impl Road {
             .all(|spec| spec.lt == LaneType::LightRail)
     }
 
+    /// True if this road is called `name`, or has it as an alternate name
+    pub fn has_name(&self, name: &InternedString) -> bool {
+        self.name.as_ref() == Some(name) || self.alt_names.contains(name)
+    }
+
+    /// When merging `other` into this road, keep this road's name, but remember the other road's
+    /// names as alternates. Returns the other road's name if it differs.
+    pub(crate) fn merge_names(&mut self, other: &Road) -> Option<InternedString> {
+        let mut conflict = None;
+        match (&self.name, &other.name) {
+            (None, Some(name)) => {
+                self.name = Some(name.clone());
+            }
+            (Some(name), Some(other_name)) if name != other_name => {
+                conflict = Some(other_name.clone());
+            }
+            _ => {}
+        }
+        for name in conflict.iter().chain(other.alt_names.iter()) {
+            if !self.has_name(name) {
+                self.alt_names.push(name.clone());
+            }
+        }
+        conflict
+    }
+
     pub fn is_service(&self) -> bool {
         self.highway_type == "service"
     }
@@ -225,6 +562,12 @@ impl Road {
         self.lane_specs_ltr.len() == 1 && self.lane_specs_ltr[0].lt.is_walkable()
     }
 
+    /// Describes the lanes compactly, like `s1.5 dv3.0 d^3.0 s1.5`. See
+    /// `LaneSpec::cross_section_sketch`.
+    pub fn cross_section_sketch(&self) -> String {
+        LaneSpec::cross_section_sketch(&self.lane_specs_ltr)
+    }
+
     pub fn oneway_for_driving(&self) -> Option<Direction> {
         LaneSpec::oneway_for_driving(&self.lane_specs_ltr)
     }
@@ -307,6 +650,21 @@ impl Road {
         self.total_width() / 2.0
     }
 
+    /// Rendering ignores virtual lanes. Returns this road without them, along with the original
+    /// index of each remaining lane. Virtual lanes have no width, so no other lane moves.
+    pub(crate) fn without_virtual_lanes(&self) -> (Cow<Road>, Vec<usize>) {
+        let original: Vec<usize> = (0..self.lane_specs_ltr.len())
+            .filter(|idx| !self.lane_specs_ltr[*idx].is_virtual)
+            .collect();
+        if original.len() == self.lane_specs_ltr.len() {
+            return (Cow::Borrowed(self), original);
+        }
+        let mut road = self.clone();
+        road.lane_specs_ltr.retain(|lane| !lane.is_virtual);
+        road.remap_lane_indices(|idx| original.iter().position(|x| *x == idx));
+        (Cow::Owned(road), original)
+    }
+
     /// Calculates the distance from the left edge to the placement.
     pub fn left_edge_offset_of(
         &self,
@@ -475,7 +833,7 @@ impl Road {
             // Always pass in the untrimmed center
             center_line: self.get_untrimmed_center_line(driving_side),
             total_width: self.total_width(),
-            highway_type: self.highway_type.clone(),
+            highway_type: self.highway_type.to_string(),
         }
     }
 
@@ -526,6 +884,28 @@ impl Road {
         Some(pl)
     }
 
+    /// Describes the lanes at some distance along `reference_line`. Each lane has its offset
+    /// from `reference_line`, measured from the left edge to the right edge, with negative values
+    /// to the left. Returns None if the distance is outside the trimmed road, in an intersection.
+    pub fn cross_section_at(
+        &self,
+        dist: Distance,
+        driving_side: DrivingSide,
+    ) -> Option<Vec<(LaneSpec, (Distance, Distance))>> {
+        if dist < self.trim_start || dist > self.reference_line.length() - self.trim_end {
+            return None;
+        }
+
+        // The lanes are centered on the center line
+        let mut left_edge = self.center_line_shift_at(dist, driving_side) - self.half_width();
+        let mut result = Vec::new();
+        for lane in &self.lane_specs_ltr {
+            result.push((lane.clone(), (left_edge, left_edge + lane.width)));
+            left_edge += lane.width;
+        }
+        Some(result)
+    }
+
     pub fn from_osm_way(&self, way: osm::WayID) -> bool {
         self.osm_ids.iter().any(|id| *id == way)
     }
@@ -551,6 +931,50 @@ impl StreetNetwork {
         self.road_id_counter += 1;
         id
     }
+
+    /// Finds the road covering a point and describes its lanes there, as in
+    /// `Road::cross_section_at`. When roads overlap, the highest layer wins.
+    pub fn cross_section_at_point(
+        &self,
+        pt: Pt2D,
+    ) -> Option<(RoadID, Vec<(LaneSpec, (Distance, Distance))>)> {
+        let mut bounds = Bounds::new();
+        bounds.update(pt);
+        let road = self
+            .spatial_index()
+            .roads(&bounds)
+            .into_iter()
+            .map(|r| &self.roads[&r])
+            .filter(|r| r.center_line.make_polygons(r.total_width()).contains_pt(pt))
+            .max_by_key(|r| r.layer)?;
+        let (dist, _) = road
+            .reference_line
+            .dist_along_of_point(road.reference_line.project_pt(pt))?;
+        let lanes = road.cross_section_at(dist, self.config.driving_side)?;
+        Some((road.id, lanes))
+    }
+
+    /// Describes the lanes of the road at a GPS position as JSON, or `null` if no road is there.
+    pub fn cross_section_at_point_json(&self, pt: LonLat) -> Result<String> {
+        let result =
+            self.cross_section_at_point(pt.to_pt(&self.gps_bounds))
+                .map(|(road, lanes)| {
+                    serde_json::json!({
+                        "road": road.0,
+                        "lanes": lanes
+                            .into_iter()
+                            .map(|(lane, (left, right))| {
+                                serde_json::json!({
+                                    "lane": lane,
+                                    "left_offset": left.inner_meters(),
+                                    "right_offset": right.inner_meters(),
+                                })
+                            })
+                            .collect::<Vec<_>>(),
+                    })
+                });
+        Ok(serde_json::to_string(&result)?)
+    }
 }
 
 /// The edge of a road, pointed into some intersection
@@ -601,23 +1025,120 @@ impl RoadEdge {
     }
 }
 
-fn parse_maxspeed(maxspeed: &str) -> Option<Speed> {
-    if let Ok(kmph) = maxspeed.parse::<f64>() {
-        Some(Speed::km_per_hour(kmph))
-    } else if let Some(mph) = maxspeed
-        .strip_suffix(" mph")
-        .and_then(|x| x.parse::<f64>().ok())
+// The physical width of the carriageway, if it's constrained. maxwidth:physical already describes
+// the carriageway. The width of a bridge or tunnel covers sidewalks too, so those are subtracted.
+// Only physical constraints narrow the road. maxwidth alone is a legal limit on vehicles, and width
+// on ordinary roads is too often approximate.
+fn parse_width_constraint(
+    tags: &Tags,
+    structure: Option<Structure>,
+    lanes: &[LaneSpec],
+) -> Option<Distance> {
+    if let Some(width) = tags
+        .get("maxwidth:physical")
+        .and_then(|x| crate::units::parse_distance(x))
     {
-        Some(Speed::miles_per_hour(mph))
-    } else {
-        // TODO Fallback to https://github.com/westnordost/osm-legal-default-speeds
-        None
+        return Some(width);
+    }
+    if structure.is_some() {
+        let width = tags
+            .get("width")
+            .and_then(|x| crate::units::parse_distance(x))?;
+        let walkable = lanes
+            .iter()
+            .filter(|lane| lane.lt.is_walkable())
+            .fold(Distance::ZERO, |sum, lane| sum + lane.width);
+        if width <= walkable {
+            warn!("width={width} doesn't leave room for anything besides sidewalks");
+            return None;
+        }
+        return Some(width - walkable);
+    }
+    None
+}
+
+// Shrink everything except sidewalks and other walkable lanes, so the carriageway fits within
+// `width`
+fn narrow_to_fit(lanes: &mut [LaneSpec], width: Distance) {
+    let carriageway = lanes
+        .iter()
+        .filter(|lane| !lane.lt.is_walkable())
+        .fold(Distance::ZERO, |sum, lane| sum + lane.width);
+    if carriageway <= width || carriageway == Distance::ZERO {
+        return;
+    }
+    let ratio = width / carriageway;
+    for lane in lanes {
+        if !lane.lt.is_walkable() {
+            lane.width = lane.width * ratio;
+        }
     }
 }
 
+// Shift a line, except the shift gradually changes to a different value at the start and/or end,
+// over PLACEMENT_BLEND_DISTANCE (or half the line, if it's shorter)
+fn shift_with_blended_ends(
+    pl: &PolyLine,
+    shift: Distance,
+    [start, end]: [Option<Distance>; 2],
+) -> Result<PolyLine> {
+    let len = pl.length();
+    let blend = PLACEMENT_BLEND_DISTANCE.min(len / 2.0);
+
+    // Samples points along the original line, shifting each one by an interpolated amount
+    let sample = |from: Distance, target: Distance, reverse: bool| -> Result<Vec<Pt2D>> {
+        let mut pts = Vec::new();
+        let mut dist = Distance::ZERO;
+        while dist < blend {
+            let along = if reverse { len - dist } else { dist };
+            let (pt, angle) = pl.dist_along(along)?;
+            let amount = target + (from - target) * (dist / blend);
+            pts.push(if amount >= Distance::ZERO {
+                pt.project_away(amount, angle.rotate_degs(90.0))
+            } else {
+                pt.project_away(-amount, angle.rotate_degs(-90.0))
+            });
+            dist += PLACEMENT_BLEND_STEP;
+        }
+        Ok(pts)
+    };
+
+    // Distances are measured along the original line, so slice it before shifting the middle
+    let mut pts = Vec::new();
+    let mut middle_start = Distance::ZERO;
+    let mut middle_end = len;
+    if let Some(target) = start {
+        pts.extend(sample(shift, target, false)?);
+        middle_start = blend;
+    }
+    if end.is_some() {
+        middle_end = len - blend;
+    }
+    if middle_start < middle_end {
+        pts.extend(
+            pl.maybe_exact_slice(middle_start, middle_end)?
+                .shift_either_direction(shift)?
+                .into_points(),
+        );
+    }
+    if let Some(target) = end {
+        let mut end_pts = sample(shift, target, true)?;
+        end_pts.reverse();
+        pts.extend(end_pts);
+    }
+    PolyLine::deduping_new(pts)
+}
+
+fn parse_maxspeed(maxspeed: &str) -> Option<Speed> {
+    // TODO Fallback to https://github.com/westnordost/osm-legal-default-speeds
+    crate::units::parse_speed(maxspeed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::add_lone_road;
+    use crate::LtrLaneNum;
 
     #[test]
     fn test_parse_maxspeed() {
@@ -629,4 +1150,272 @@ mod tests {
         );
         assert_eq!(None, parse_maxspeed("30.5 mysteryunits"));
     }
+
+    #[test]
+    fn test_shift_with_blended_ends() {
+        // An L shape, so the shifted line is shorter than the original
+        let pl = PolyLine::must_new(vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(50.0, 0.0),
+            Pt2D::new(50.0, 50.0),
+        ]);
+        let shift = Distance::meters(5.0);
+        let shifted = pl.shift_either_direction(shift).unwrap();
+
+        // Blending to the same shift changes nothing
+        let same = shift_with_blended_ends(&pl, shift, [Some(shift), Some(shift)]).unwrap();
+        assert!(same.first_pt().dist_to(shifted.first_pt()) < Distance::meters(0.01));
+        assert!(same.last_pt().dist_to(shifted.last_pt()) < Distance::meters(0.01));
+        assert!((same.length() - shifted.length()).inner_meters().abs() < 0.1);
+
+        // Only the start meets the original line
+        let blended = shift_with_blended_ends(&pl, shift, [Some(Distance::ZERO), None]).unwrap();
+        assert!(blended.first_pt().dist_to(pl.first_pt()) < Distance::meters(0.01));
+        assert!(blended.last_pt().dist_to(shifted.last_pt()) < Distance::meters(0.01));
+    }
+
+    #[test]
+    fn test_cross_section_follows_placement() {
+        let config = MapConfig::default();
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        tags.insert("lanes", "2");
+        let mut road = Road::new(
+            RoadID(0),
+            vec![osm::WayID(1)],
+            IntersectionID(0),
+            IntersectionID(1),
+            PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            tags,
+            &config,
+        );
+        let side = config.driving_side;
+        let left_edge = |road: &Road, dist: f64| -> f64 {
+            let lanes = road.cross_section_at(Distance::meters(dist), side).unwrap();
+            (lanes[0].1).0.inner_meters()
+        };
+        let close = |a: f64, b: Distance| (a - b.inner_meters()).abs() < 0.01;
+        let center = road.left_edge_offset_of(RoadPosition::Center, side);
+        let right_edge = RoadPosition::RightOf(LtrLaneNum::Forward(1));
+        let right = road.left_edge_offset_of(right_edge, side);
+        assert!(center < right);
+
+        // The reference line moves from the center of the road to its right edge
+        road.reference_line_placement = Placement::Varying(RoadPosition::Center, right_edge);
+        assert!(close(left_edge(&road, 0.0), -center));
+        assert!(close(left_edge(&road, 50.0), -(center + right) / 2.0));
+        assert!(close(left_edge(&road, 100.0), -right));
+
+        // The start eases onto the reference line over the blend distance
+        road.reference_line_placement = Placement::Consistent(RoadPosition::Center);
+        road.placement_blend = [Some(Distance::ZERO), None];
+        assert!(close(left_edge(&road, 0.0), -road.half_width()));
+        assert!(close(left_edge(&road, 50.0), -center));
+        assert!(close(left_edge(&road, 100.0), -center));
+    }
+
+    #[test]
+    fn test_cross_section_at_point() {
+        let mut streets = StreetNetwork::blank();
+        let tags = ["highway=residential", "lanes=2"];
+        add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)],
+            &tags,
+        );
+        let r2 = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 50.0), Pt2D::new(100.0, 50.0)],
+            &tags,
+        );
+        let bridge = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(50.0, -50.0), Pt2D::new(50.0, 100.0)],
+            &["highway=residential", "lanes=2", "bridge=yes", "layer=1"],
+        );
+
+        let (r, lanes) = streets
+            .cross_section_at_point(Pt2D::new(20.0, 50.0))
+            .unwrap();
+        assert_eq!(r2, r);
+        assert_eq!(streets.roads[&r2].lane_specs_ltr.len(), lanes.len());
+        // The bridge is on top where the roads cross
+        assert_eq!(
+            bridge,
+            streets
+                .cross_section_at_point(Pt2D::new(50.0, 50.0))
+                .unwrap()
+                .0
+        );
+        assert!(streets
+            .cross_section_at_point(Pt2D::new(20.0, 25.0))
+            .is_none());
+    }
+
+    #[test]
+    fn test_merge_names() {
+        let config = MapConfig::default();
+        let road = |id: usize, name: Option<&str>, alt_name: Option<&str>| {
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            if let Some(name) = name {
+                tags.insert("name", name);
+            }
+            if let Some(alt_name) = alt_name {
+                tags.insert("alt_name", alt_name);
+            }
+            Road::new(
+                RoadID(id),
+                vec![osm::WayID(id as i64)],
+                IntersectionID(0),
+                IntersectionID(1),
+                PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+                tags,
+                &config,
+            )
+        };
+        let names =
+            |road: &Road| -> Vec<String> { road.alt_names.iter().map(|x| x.to_string()).collect() };
+
+        // The other name is kept as an alternate, along with its alternates
+        let mut main = road(0, Some("Main Street"), None);
+        let other = road(1, Some("High Street"), Some("Old Road;Main Street"));
+        assert_eq!(
+            Some(InternedString::new("High Street")),
+            main.merge_names(&other)
+        );
+        assert_eq!(Some(InternedString::new("Main Street")), main.name);
+        assert_eq!(vec!["High Street", "Old Road"], names(&main));
+
+        // An unnamed road takes the other name, without a conflict
+        let mut unnamed = road(2, None, None);
+        assert_eq!(None, unnamed.merge_names(&other));
+        assert_eq!(Some(InternedString::new("High Street")), unnamed.name);
+        assert_eq!(vec!["Old Road", "Main Street"], names(&unnamed));
+    }
+
+    fn carriageway_and_total_width(tags: Tags) -> (Distance, Distance) {
+        let road = Road::new(
+            RoadID(0),
+            vec![osm::WayID(1)],
+            IntersectionID(0),
+            IntersectionID(1),
+            PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            tags,
+            &MapConfig::default(),
+        );
+        let carriageway = road
+            .lane_specs_ltr
+            .iter()
+            .filter(|lane| !lane.lt.is_walkable())
+            .fold(Distance::ZERO, |sum, lane| sum + lane.width);
+        (carriageway, road.total_width())
+    }
+
+    #[test]
+    fn test_width_constraints() {
+        let close_enough = |a: Distance, b: Distance| (a - b).inner_meters().abs() < 0.01;
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        tags.insert("sidewalk", "both");
+        let (base_carriageway, base_total) = carriageway_and_total_width(tags.clone());
+        let sidewalks = base_total - base_carriageway;
+        assert!(base_carriageway > Distance::meters(4.0));
+
+        // maxwidth:physical limits the carriageway. Sidewalks keep their width.
+        let mut pinch = tags.clone();
+        pinch.insert("maxwidth:physical", "4");
+        let (carriageway, total) = carriageway_and_total_width(pinch);
+        assert!(close_enough(carriageway, Distance::meters(4.0)));
+        assert!(close_enough(total, Distance::meters(4.0) + sidewalks));
+
+        // The width of a bridge includes its sidewalks
+        let mut bridge = tags.clone();
+        bridge.insert("bridge", "yes");
+        bridge.insert("width", "7");
+        let (carriageway, total) = carriageway_and_total_width(bridge);
+        assert!(close_enough(total, Distance::meters(7.0)));
+        assert!(close_enough(carriageway, Distance::meters(7.0) - sidewalks));
+
+        // A bridge wide enough for everything isn't changed
+        let mut bridge = tags.clone();
+        bridge.insert("bridge", "yes");
+        bridge.insert("width", "30");
+        assert!(close_enough(
+            carriageway_and_total_width(bridge).1,
+            base_total
+        ));
+
+        // width on an ordinary road doesn't narrow anything
+        let mut road = tags.clone();
+        road.insert("width", "3");
+        assert!(close_enough(
+            carriageway_and_total_width(road).1,
+            base_total
+        ));
+    }
+
+    #[test]
+    fn test_update_from_tags() {
+        let config = MapConfig::default();
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        tags.insert("name", "Main Street");
+        tags.insert("maxspeed", "30 mph");
+        tags.insert("surface", "asphalt");
+        tags.insert("sidewalk", "both");
+        tags.insert("sidewalk:both:surface", "paving_stones");
+        let mut road = Road::new(
+            RoadID(0),
+            vec![osm::WayID(1)],
+            IntersectionID(0),
+            IntersectionID(1),
+            PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            tags,
+            &config,
+        );
+        let sidewalks = |road: &Road| -> Vec<usize> {
+            (0..road.lane_specs_ltr.len())
+                .filter(|idx| road.lane_specs_ltr[*idx].lt == LaneType::Sidewalk)
+                .collect()
+        };
+        assert_eq!(2, sidewalks(&road).len());
+        // As if a sidepath was zipped into the last lane
+        let last = road.lane_specs_ltr.len() - 1;
+        road.lane_surfaces.insert(last, "gravel".into());
+        road.service_lanes.insert(last);
+        road.lane_features.push(LaneFeature {
+            lane: last,
+            dist: Distance::meters(50.0),
+            kind: LaneFeatureKind::BusStop,
+        });
+
+        // Removing a sidewalk shifts the lane indices, so the attributes have to follow
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        tags.insert("name", "High Street");
+        tags.insert("sidewalk", "right");
+        tags.insert("sidewalk:right:surface", "gravel");
+        road.update_from_tags(&tags, &config);
+        let sidewalks = sidewalks(&road);
+        assert_eq!(1, sidewalks.len());
+        assert_eq!(
+            sidewalks,
+            road.path_attributes.keys().cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some("gravel".to_string()),
+            road.path_attributes[&sidewalks[0]].surface
+        );
+
+        // The lanes merged in before don't match the new ones
+        assert!(road.lane_surfaces.is_empty());
+        assert!(road.service_lanes.is_empty());
+        assert!(road.lane_features.is_empty());
+
+        // Everything else from the tags follows them too
+        assert_eq!(Some(InternedString::new("High Street")), road.name);
+        assert_eq!(None, road.speed_limit);
+        assert_eq!(None, road.surface);
+    }
 }
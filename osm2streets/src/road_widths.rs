@@ -0,0 +1,206 @@
+use anyhow::Result;
+use geom::Distance;
+use serde::Serialize;
+
+use crate::{Filter, LaneType, Road, RoadID, StreetNetwork};
+
+/// Widths across a road, derived from its lanes.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RoadWidths {
+    pub road: RoadID,
+    /// The lanes vehicles travel along: driving, bus, bike, and shared turn lanes
+    pub carriageway: Distance,
+    /// From the first to the last lane on the roadway, including parking and painted buffers in
+    /// between, but not sidewalks or verges outside the curbs
+    pub curb_to_curb: Distance,
+    /// Everything, including sidewalks
+    pub right_of_way: Distance,
+    /// The number of general purpose driving lanes in both directions
+    pub general_lanes: usize,
+}
+
+impl Road {
+    pub fn widths(&self) -> RoadWidths {
+        let carriageway = self
+            .lane_specs_ltr
+            .iter()
+            .filter(|lane| lane.lt.is_for_moving_vehicles() || lane.lt == LaneType::SharedLeftTurn)
+            .fold(Distance::ZERO, |sum, lane| sum + lane.width);
+
+        let roadway: Vec<usize> = self
+            .lane_specs_ltr
+            .iter()
+            .enumerate()
+            .filter(|(_, lane)| lane.lt.is_roadway())
+            .map(|(idx, _)| idx)
+            .collect();
+        let curb_to_curb = match (roadway.first(), roadway.last()) {
+            (Some(first), Some(last)) => self.lane_specs_ltr[*first..=*last]
+                .iter()
+                .fold(Distance::ZERO, |sum, lane| sum + lane.width),
+            _ => Distance::ZERO,
+        };
+
+        RoadWidths {
+            road: self.id,
+            carriageway,
+            curb_to_curb,
+            right_of_way: self.total_width(),
+            general_lanes: self
+                .lane_specs_ltr
+                .iter()
+                .filter(|lane| lane.lt == LaneType::Driving)
+                .count(),
+        }
+    }
+}
+
+impl StreetNetwork {
+    /// Returns a JSON array with the widths of each road, in meters. See `RoadWidths`.
+    pub fn to_road_widths_json(&self, filter: &Filter) -> Result<String> {
+        let list: Vec<serde_json::Value> = filter
+            .roads(self)
+            .map(|road| {
+                let widths = road.widths();
+                serde_json::json!({
+                    "road": widths.road.0,
+                    "carriageway": widths.carriageway.inner_meters(),
+                    "curb_to_curb": widths.curb_to_curb.inner_meters(),
+                    "right_of_way": widths.right_of_way.inner_meters(),
+                    "general_lanes": widths.general_lanes,
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{LonLat, PolyLine, Pt2D};
+
+    use super::*;
+    use crate::test_utils::add_lone_road;
+    use crate::{osm, BufferType, IntersectionID, MapConfig};
+
+    fn road(kv: &[(&str, &str)]) -> Road {
+        let mut tags = Tags::empty();
+        for (k, v) in kv {
+            tags.insert(*k, *v);
+        }
+        Road::new(
+            RoadID(0),
+            vec![osm::WayID(0)],
+            IntersectionID(0),
+            IntersectionID(1),
+            PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            tags,
+            &MapConfig::default(),
+        )
+    }
+
+    fn sum_widths(road: &Road, include: impl Fn(LaneType) -> bool) -> f64 {
+        road.lane_specs_ltr
+            .iter()
+            .filter(|lane| include(lane.lt))
+            .map(|lane| lane.width.inner_meters())
+            .sum()
+    }
+
+    #[test]
+    fn test_widths() {
+        let road = road(&[
+            ("highway", "residential"),
+            ("lanes", "2"),
+            ("sidewalk", "both"),
+            ("parking:lane:both", "parallel"),
+        ]);
+        let widths = road.widths();
+        assert_eq!(2, widths.general_lanes);
+        assert!(
+            (widths.carriageway.inner_meters() - sum_widths(&road, |lt| lt == LaneType::Driving))
+                .abs()
+                < 0.01
+        );
+        // Parking is between the curbs, sidewalks aren't
+        let outside_curbs = |lt: LaneType| {
+            matches!(
+                lt,
+                LaneType::Sidewalk | LaneType::Buffer(BufferType::Curb | BufferType::Verge)
+            )
+        };
+        assert!(
+            (widths.curb_to_curb.inner_meters() - sum_widths(&road, |lt| !outside_curbs(lt))).abs()
+                < 0.01
+        );
+        assert_eq!(road.total_width(), widths.right_of_way);
+        assert!(widths.carriageway < widths.curb_to_curb);
+        assert!(widths.curb_to_curb < widths.right_of_way);
+
+        // A footway has no roadway at all
+        let widths = self::road(&[("highway", "footway")]).widths();
+        assert_eq!(0, widths.general_lanes);
+        assert_eq!(Distance::ZERO, widths.carriageway);
+        assert_eq!(Distance::ZERO, widths.curb_to_curb);
+        assert!(widths.right_of_way > Distance::ZERO);
+    }
+
+    /// A single two-lane residential road
+    fn network() -> (StreetNetwork, RoadID) {
+        let mut streets = StreetNetwork::blank();
+        streets.gps_bounds.update(LonLat::new(-122.3, 47.6));
+        streets.gps_bounds.update(LonLat::new(-122.2, 47.7));
+        let id = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)],
+            &["highway=residential", "lanes=2"],
+        );
+        (streets, id)
+    }
+
+    #[test]
+    fn test_road_widths_json() {
+        let (streets, id) = network();
+        let json: serde_json::Value =
+            serde_json::from_str(&streets.to_road_widths_json(&Filter::All).unwrap()).unwrap();
+        let widths = streets.roads[&id].widths();
+        assert_eq!(
+            serde_json::json!([{
+                "road": id.0,
+                "carriageway": widths.carriageway.inner_meters(),
+                "curb_to_curb": widths.curb_to_curb.inner_meters(),
+                "right_of_way": widths.right_of_way.inner_meters(),
+                "general_lanes": 2,
+            }]),
+            json
+        );
+    }
+
+    #[test]
+    fn test_road_geojson_widths() {
+        let (streets, id) = network();
+        let geojson: serde_json::Value =
+            serde_json::from_str(&streets.to_geojson(&Filter::All).unwrap()).unwrap();
+        let road = geojson["features"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["properties"]["type"] == "road")
+            .unwrap();
+        let widths = streets.roads[&id].widths();
+        assert_eq!(
+            serde_json::json!(widths.carriageway.inner_meters()),
+            road["properties"]["carriageway_width"]
+        );
+        assert_eq!(
+            serde_json::json!(widths.curb_to_curb.inner_meters()),
+            road["properties"]["curb_to_curb_width"]
+        );
+        assert_eq!(
+            serde_json::json!(widths.right_of_way.inner_meters()),
+            road["properties"]["right_of_way_width"]
+        );
+        assert_eq!(2, road["properties"]["general_lanes"]);
+    }
+}
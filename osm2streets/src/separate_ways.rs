@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+
+use abstutil::Tags;
+use geom::{Distance, Pt2D};
+use serde::{Deserialize, Serialize};
+
+use crate::{IssueKind, RoadID, SideOfRoad, StreetNetwork, TaggingIssue};
+
+/// How far the middle of a separate way can be from the edge of the road it belongs to
+const MAX_SEPARATE_DIST: Distance = Distance::const_meters(15.0);
+/// How many degrees a separate way can diverge from the road and still count as running alongside
+const MAX_ANGLE_DIFF: f64 = 30.0;
+
+/// Infrastructure that a road's tags say is mapped as a separate OSM way, like `sidewalk=separate`
+/// or `cycleway:right=separate`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SeparatelyMapped {
+    pub kind: SeparateKind,
+    /// Left and right are relative to the road's direction
+    pub side: SideOfRoad,
+    pub status: SeparateStatus,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeparateKind {
+    Sidewalk,
+    Cycleway,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SeparateStatus {
+    /// `StreetNetwork::link_separately_mapped` hasn't looked yet
+    Unlinked,
+    /// Nothing was found alongside
+    Unmatched,
+    /// This road runs alongside
+    Matched(RoadID),
+    /// The separate way was zipped into this road
+    Zipped,
+}
+
+impl SeparateKind {
+    fn key(self) -> &'static str {
+        match self {
+            Self::Sidewalk => "sidewalk",
+            Self::Cycleway => "cycleway",
+        }
+    }
+}
+
+/// Finds `sidewalk=separate`, `cycleway:left=separate`, and similar tags.
+pub(crate) fn parse_separately_mapped(tags: &Tags) -> Vec<SeparatelyMapped> {
+    let mut results = Vec::new();
+    for kind in [SeparateKind::Sidewalk, SeparateKind::Cycleway] {
+        let key = kind.key();
+        for side in [SideOfRoad::Left, SideOfRoad::Right] {
+            let side_key = match side {
+                SideOfRoad::Left => "left",
+                SideOfRoad::Right => "right",
+            };
+            if tags.is(key, "separate")
+                || tags.is(&format!("{key}:both"), "separate")
+                || tags.is(&format!("{key}:{side_key}"), "separate")
+            {
+                results.push(SeparatelyMapped {
+                    kind,
+                    side,
+                    status: SeparateStatus::Unlinked,
+                });
+            }
+        }
+    }
+    results
+}
+
+impl StreetNetwork {
+    /// For every road expecting a separately mapped sidewalk or cycleway, look for a footway or
+    /// cycleway running alongside it on that side.
+    pub fn link_separately_mapped(&mut self) {
+        let candidates: BTreeMap<RoadID, (SeparateKind, Pt2D)> = self
+            .roads
+            .values()
+            .filter_map(|road| {
+                let kind = if road.is_cycleway() {
+                    SeparateKind::Cycleway
+                } else if road.is_footway() {
+                    SeparateKind::Sidewalk
+                } else {
+                    return None;
+                };
+                Some((road.id, (kind, road.center_line.middle())))
+            })
+            .collect();
+        let index = self.spatial_index();
+
+        let mut matches = Vec::new();
+        for road in self.roads.values() {
+            for (idx, expected) in road.separately_mapped.iter().enumerate() {
+                if expected.status == SeparateStatus::Zipped {
+                    continue;
+                }
+                let max_dist = road.half_width() + MAX_SEPARATE_DIST;
+                let mut bounds = road.center_line.get_bounds();
+                bounds.min_x -= max_dist.inner_meters();
+                bounds.min_y -= max_dist.inner_meters();
+                bounds.max_x += max_dist.inner_meters();
+                bounds.max_y += max_dist.inner_meters();
+
+                let mut best: Option<(RoadID, Distance)> = None;
+                for candidate in index.roads(&bounds) {
+                    let Some((kind, pt)) = candidates.get(&candidate) else {
+                        continue;
+                    };
+                    if *kind != expected.kind {
+                        continue;
+                    }
+                    let projected = road.center_line.project_pt(*pt);
+                    let dist = projected.dist_to(*pt);
+                    if dist > max_dist {
+                        continue;
+                    }
+                    let Some((_, road_angle)) = road.center_line.dist_along_of_point(projected)
+                    else {
+                        continue;
+                    };
+
+                    // Which side of the road is it on? Angles increase clockwise, so the right is
+                    // between 0 and 180 degrees from the road's direction.
+                    let rotation = (projected.angle_to(*pt).normalized_degrees()
+                        - road_angle.normalized_degrees())
+                    .rem_euclid(360.0);
+                    let side = if rotation < 180.0 {
+                        SideOfRoad::Right
+                    } else {
+                        SideOfRoad::Left
+                    };
+                    if side != expected.side {
+                        continue;
+                    }
+
+                    // Is it roughly parallel, in either direction?
+                    let candidate_angle = self.roads[&candidate].center_line.overall_angle();
+                    let diff = (candidate_angle.normalized_degrees()
+                        - road_angle.normalized_degrees())
+                    .rem_euclid(180.0);
+                    if diff.min(180.0 - diff) > MAX_ANGLE_DIFF {
+                        continue;
+                    }
+
+                    if best.map(|(_, d)| dist < d).unwrap_or(true) {
+                        best = Some((candidate, dist));
+                    }
+                }
+
+                let status = match best {
+                    Some((r, _)) => SeparateStatus::Matched(r),
+                    None => SeparateStatus::Unmatched,
+                };
+                matches.push((road.id, idx, status));
+            }
+        }
+
+        for (r, idx, status) in matches {
+            self.roads.get_mut(&r).unwrap().separately_mapped[idx].status = status;
+        }
+    }
+
+    /// Finds roads tagged with separately mapped sidewalks or cycleways that couldn't be found.
+    /// Nothing is reported until `link_separately_mapped` runs.
+    pub fn find_unmatched_separate_ways(&self) -> Vec<TaggingIssue> {
+        let mut issues = Vec::new();
+        for road in self.roads.values() {
+            if road.osm_ids.is_empty() {
+                continue;
+            }
+            for expected in &road.separately_mapped {
+                let missing = match expected.status {
+                    SeparateStatus::Unmatched => true,
+                    SeparateStatus::Matched(r) => !self.roads.contains_key(&r),
+                    SeparateStatus::Unlinked | SeparateStatus::Zipped => false,
+                };
+                if missing {
+                    issues.push(TaggingIssue {
+                        way: road.osm_ids[0],
+                        pt: road.center_line.middle(),
+                        kind: IssueKind::UnmatchedSeparateWay,
+                        description: format!(
+                            "The {:?} side is tagged {}=separate, but no separate way was found \
+                             alongside",
+                            expected.side,
+                            expected.kind.key()
+                        ),
+                    });
+                }
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::PolyLine;
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionKind, Road};
+
+    #[test]
+    fn test_parse_separately_mapped() {
+        let mut tags = Tags::empty();
+        tags.insert("sidewalk", "separate");
+        tags.insert("cycleway:right", "separate");
+        tags.insert("cycleway:left", "lane");
+        let parsed: Vec<(SeparateKind, SideOfRoad)> = parse_separately_mapped(&tags)
+            .into_iter()
+            .map(|x| (x.kind, x.side))
+            .collect();
+        assert_eq!(
+            vec![
+                (SeparateKind::Sidewalk, SideOfRoad::Left),
+                (SeparateKind::Sidewalk, SideOfRoad::Right),
+                (SeparateKind::Cycleway, SideOfRoad::Right),
+            ],
+            parsed
+        );
+    }
+
+    #[test]
+    fn test_link_separately_mapped() {
+        let mut streets = StreetNetwork::blank();
+        let mut add_road = |y1: f64, y2: f64, tags: Vec<(&str, &str)>| {
+            let i1 = streets.insert_intersection(
+                Vec::new(),
+                Pt2D::new(0.0, y1),
+                IntersectionKind::MapEdge,
+                IntersectionControl::Uncontrolled,
+            );
+            let i2 = streets.insert_intersection(
+                Vec::new(),
+                Pt2D::new(100.0, y2),
+                IntersectionKind::MapEdge,
+                IntersectionControl::Uncontrolled,
+            );
+            let mut osm_tags = Tags::empty();
+            for (k, v) in tags {
+                osm_tags.insert(k, v);
+            }
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                i1,
+                i2,
+                PolyLine::must_new(vec![Pt2D::new(0.0, y1), Pt2D::new(100.0, y2)]),
+                osm_tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+            id
+        };
+        // Heading east, so the right side has greater y
+        let main = add_road(
+            0.0,
+            0.0,
+            vec![
+                ("highway", "residential"),
+                ("sidewalk:right", "separate"),
+                ("cycleway:left", "separate"),
+            ],
+        );
+        let right = add_road(10.0, 10.0, vec![("highway", "footway")]);
+        // On the wrong side
+        add_road(-10.0, -10.0, vec![("highway", "footway")]);
+        // Too far away
+        add_road(-100.0, -100.0, vec![("highway", "cycleway")]);
+
+        assert!(streets.find_unmatched_separate_ways().is_empty());
+        streets.link_separately_mapped();
+        let statuses: Vec<(SeparateKind, SeparateStatus)> = streets.roads[&main]
+            .separately_mapped
+            .iter()
+            .map(|x| (x.kind, x.status))
+            .collect();
+        assert_eq!(
+            vec![
+                (SeparateKind::Sidewalk, SeparateStatus::Matched(right)),
+                (SeparateKind::Cycleway, SeparateStatus::Unmatched),
+            ],
+            statuses
+        );
+        assert_eq!(1, streets.find_unmatched_separate_ways().len());
+    }
+}
@@ -0,0 +1,282 @@
+use geom::{Distance, Pt2D};
+
+/// Simplifies a line with the Douglas-Peucker algorithm, removing points that stray less than
+/// `tolerance` from the result. The first and last points never move, and if the original line
+/// doesn't cross itself, neither does the result.
+pub fn simplify_preserving_topology(pts: Vec<Pt2D>, tolerance: Distance) -> Vec<Pt2D> {
+    if pts.len() <= 2 || tolerance <= Distance::ZERO {
+        return pts;
+    }
+    let tolerance = tolerance.inner_meters();
+
+    let mut keep = vec![false; pts.len()];
+    keep[0] = true;
+    keep[pts.len() - 1] = true;
+    let mut stack = vec![(0, pts.len() - 1)];
+    while let Some((start, end)) = stack.pop() {
+        if let Some((idx, dist)) = farthest_point(&pts, start, end) {
+            if dist > tolerance {
+                keep[idx] = true;
+                stack.push((start, idx));
+                stack.push((idx, end));
+            }
+        }
+    }
+
+    // Dropping points can make the line cross itself. Restore the farthest point of any segment
+    // that crosses another, until nothing does. This terminates, because in the worst case, every
+    // point is restored.
+    loop {
+        let kept: Vec<usize> = (0..pts.len()).filter(|idx| keep[*idx]).collect();
+        let mut restored = false;
+        for (seg1, pair1) in kept.windows(2).enumerate() {
+            // Only simplified segments can be fixed
+            if pair1[1] == pair1[0] + 1 {
+                continue;
+            }
+            let crosses = kept.windows(2).enumerate().any(|(seg2, pair2)| {
+                seg1.abs_diff(seg2) > 1
+                    && segments_cross(pts[pair1[0]], pts[pair1[1]], pts[pair2[0]], pts[pair2[1]])
+            });
+            if crosses {
+                let (idx, _) = farthest_point(&pts, pair1[0], pair1[1]).unwrap();
+                keep[idx] = true;
+                restored = true;
+            }
+        }
+        if !restored {
+            break;
+        }
+    }
+
+    pts.into_iter()
+        .zip(keep)
+        .filter_map(|(pt, keep)| if keep { Some(pt) } else { None })
+        .collect()
+}
+
+// The point strictly between start and end farthest from the segment between them, and that
+// distance
+fn farthest_point(pts: &[Pt2D], start: usize, end: usize) -> Option<(usize, f64)> {
+    let mut best = None;
+    for idx in start + 1..end {
+        let dist = dist_to_segment(pts[idx], pts[start], pts[end]);
+        if best.map(|(_, d)| dist > d).unwrap_or(true) {
+            best = Some((idx, dist));
+        }
+    }
+    best
+}
+
+fn dist_to_segment(pt: Pt2D, a: Pt2D, b: Pt2D) -> f64 {
+    let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+    let len_squared = dx * dx + dy * dy;
+    let t = if len_squared == 0.0 {
+        0.0
+    } else {
+        (((pt.x() - a.x()) * dx + (pt.y() - a.y()) * dy) / len_squared).clamp(0.0, 1.0)
+    };
+    let (x, y) = (a.x() + t * dx, a.y() + t * dy);
+    ((pt.x() - x).powi(2) + (pt.y() - y).powi(2)).sqrt()
+}
+
+// Do the segments a1-a2 and b1-b2 touch or cross? Segments sharing an endpoint (like the two ends
+// of a loop) don't count.
+fn segments_cross(a1: Pt2D, a2: Pt2D, b1: Pt2D, b2: Pt2D) -> bool {
+    if a1 == b1 || a1 == b2 || a2 == b1 || a2 == b2 {
+        return false;
+    }
+    let orientation = |p: Pt2D, q: Pt2D, r: Pt2D| {
+        let value = (q.x() - p.x()) * (r.y() - p.y()) - (q.y() - p.y()) * (r.x() - p.x());
+        if value > 0.0 {
+            1
+        } else if value < 0.0 {
+            -1
+        } else {
+            0
+        }
+    };
+    let on_segment = |p: Pt2D, q: Pt2D, r: Pt2D| {
+        r.x() >= p.x().min(q.x())
+            && r.x() <= p.x().max(q.x())
+            && r.y() >= p.y().min(q.y())
+            && r.y() <= p.y().max(q.y())
+    };
+
+    let o1 = orientation(a1, a2, b1);
+    let o2 = orientation(a1, a2, b2);
+    let o3 = orientation(b1, b2, a1);
+    let o4 = orientation(b1, b2, a2);
+    if o1 != o2 && o3 != o4 {
+        return true;
+    }
+    (o1 == 0 && on_segment(a1, a2, b1))
+        || (o2 == 0 && on_segment(a1, a2, b2))
+        || (o3 == 0 && on_segment(b1, b2, a1))
+        || (o4 == 0 && on_segment(b1, b2, a2))
+}
+
+/// Smooths every sharp corner along a line into a curve, straying at most `max_deviation` from the
+/// original corner. The first and last points never move. Useful for roads digitized with long
+/// straight segments and sharp corners.
+pub fn smooth_corners(pts: Vec<Pt2D>, max_deviation: Distance) -> Vec<Pt2D> {
+    if pts.len() <= 2 {
+        return pts;
+    }
+    let corners = pts[1..pts.len() - 1].to_vec();
+    let mut result = pts;
+    for corner in corners {
+        result = smooth_junction(result, corner, max_deviation);
+    }
+    result
+}
+
+/// If the line kinks sharply at `junction`, replace that point with a quadratic Bézier curve. The
+/// curve's control point is the junction itself, and the deviation from it is capped.
+pub(crate) fn smooth_junction(
+    mut pts: Vec<Pt2D>,
+    junction: Pt2D,
+    max_deviation: Distance,
+) -> Vec<Pt2D> {
+    // Below this, don't bother
+    let min_turn_degrees = 10.0;
+    let num_samples = 6;
+
+    if max_deviation <= Distance::ZERO {
+        return pts;
+    }
+    // RDP might've removed the junction point, if it wasn't much of a kink
+    let idx = match pts
+        .iter()
+        .position(|pt| pt.to_hashable() == junction.to_hashable())
+    {
+        Some(idx) if idx > 0 && idx < pts.len() - 1 => idx,
+        _ => {
+            return pts;
+        }
+    };
+    let before = pts[idx - 1];
+    let after = pts[idx + 1];
+
+    let back_angle = junction.angle_to(before);
+    let fwd_angle = junction.angle_to(after);
+    let diff = (fwd_angle.normalized_degrees() - back_angle.normalized_degrees()).rem_euclid(360.0);
+    // How far the direction of travel changes at the junction
+    let turn_degrees = 180.0 - diff.min(360.0 - diff);
+    if turn_degrees < min_turn_degrees {
+        return pts;
+    }
+
+    // The curve starts and ends this far from the junction. The midpoint of the curve is
+    // 0.5 * dist * sin(turn / 2) away from the junction, so solve for the max deviation, but don't
+    // consume more than half of either neighboring segment.
+    let dist = (2.0 * max_deviation / (turn_degrees / 2.0).to_radians().sin())
+        .min(0.5 * junction.dist_to(before))
+        .min(0.5 * junction.dist_to(after));
+    if dist <= Distance::ZERO {
+        return pts;
+    }
+    let start = junction.project_away(dist, back_angle);
+    let end = junction.project_away(dist, fwd_angle);
+
+    let mut curve = Vec::new();
+    for step in 0..=num_samples {
+        let t = (step as f64) / (num_samples as f64);
+        let a = (1.0 - t) * (1.0 - t);
+        let b = 2.0 * (1.0 - t) * t;
+        let c = t * t;
+        curve.push(Pt2D::new(
+            a * start.x() + b * junction.x() + c * end.x(),
+            a * start.y() + b * junction.y() + c * end.y(),
+        ));
+    }
+    pts.splice(idx..=idx, curve);
+    pts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_endpoints_and_drops_noise() {
+        let pts = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 0.1),
+            Pt2D::new(20.0, -0.1),
+            Pt2D::new(30.0, 0.0),
+        ];
+        let result = simplify_preserving_topology(pts, Distance::meters(1.0));
+        assert_eq!(result, vec![Pt2D::new(0.0, 0.0), Pt2D::new(30.0, 0.0)]);
+    }
+
+    #[test]
+    fn test_no_new_self_intersections() {
+        // A small bump, then a long loop returning to just under the bump. Plain Douglas-Peucker
+        // flattens the bump, so the end of the line crosses the start.
+        let pts = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(10.0, 3.0),
+            Pt2D::new(20.0, 0.0),
+            Pt2D::new(500.0, 0.0),
+            Pt2D::new(500.0, -20.0),
+            Pt2D::new(10.0, -20.0),
+            Pt2D::new(10.0, 1.0),
+        ];
+        let result = simplify_preserving_topology(pts.clone(), Distance::meters(5.0));
+        assert_eq!(result[0], pts[0]);
+        assert_eq!(result.last(), pts.last());
+        assert!(result.contains(&Pt2D::new(10.0, 3.0)));
+        for (idx1, pair1) in result.windows(2).enumerate() {
+            for (idx2, pair2) in result.windows(2).enumerate() {
+                if idx1.abs_diff(idx2) > 1 {
+                    assert!(!segments_cross(pair1[0], pair1[1], pair2[0], pair2[1]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_smooth_junction() {
+        let junction = Pt2D::new(100.0, 0.0);
+        let pts = vec![Pt2D::new(0.0, 0.0), junction, Pt2D::new(100.0, 100.0)];
+        let max_deviation = Distance::meters(1.0);
+
+        let smoothed = smooth_junction(pts.clone(), junction, max_deviation);
+        assert!(smoothed.len() > pts.len());
+        assert_eq!(smoothed[0], pts[0]);
+        assert_eq!(smoothed.last(), pts.last());
+        for pt in &smoothed[1..smoothed.len() - 1] {
+            assert!(pt.dist_to(junction) <= Distance::meters(3.0));
+            // The curve stays inside the corner
+            assert!(pt.x() <= 100.0 + 1e-6 && pt.y() >= -1e-6);
+        }
+
+        // Straight lines are left alone
+        let straight = vec![Pt2D::new(0.0, 0.0), junction, Pt2D::new(200.0, 0.0)];
+        assert_eq!(
+            smooth_junction(straight.clone(), junction, max_deviation),
+            straight
+        );
+
+        // So is everything, when disabled
+        assert_eq!(smooth_junction(pts.clone(), junction, Distance::ZERO), pts);
+    }
+
+    #[test]
+    fn test_smooth_corners() {
+        let pts = vec![
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(100.0, 100.0),
+            Pt2D::new(200.0, 100.0),
+        ];
+        let smoothed = smooth_corners(pts.clone(), Distance::meters(1.0));
+        assert!(smoothed.len() > pts.len());
+        assert_eq!(smoothed[0], pts[0]);
+        assert_eq!(smoothed.last(), pts.last());
+        // Both corners got cut
+        assert!(!smoothed.contains(&pts[1]));
+        assert!(!smoothed.contains(&pts[2]));
+    }
+}
@@ -0,0 +1,238 @@
+use std::sync::{Arc, Mutex};
+
+use geom::Bounds;
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, AABB};
+
+use crate::{IntersectionID, Road, RoadID, StreetNetwork};
+
+type Entry<T> = GeomWithData<Rectangle<[f64; 2]>, T>;
+
+/// An R-tree over the bounds of every road, intersection, and traffic island, for quickly finding
+/// everything near some area.
+pub(crate) struct SpatialIndex {
+    roads: RTree<Entry<RoadID>>,
+    intersections: RTree<Entry<IntersectionID>>,
+    /// Indices into `StreetNetwork::traffic_islands`
+    traffic_islands: RTree<Entry<usize>>,
+}
+
+impl SpatialIndex {
+    fn new(streets: &StreetNetwork) -> Self {
+        Self {
+            roads: RTree::bulk_load(
+                streets
+                    .roads
+                    .values()
+                    .map(|r| GeomWithData::new(to_rectangle(&road_bounds(r)), r.id))
+                    .collect(),
+            ),
+            intersections: RTree::bulk_load(
+                streets
+                    .intersections
+                    .values()
+                    .map(|i| GeomWithData::new(to_rectangle(&i.polygon.get_bounds()), i.id))
+                    .collect(),
+            ),
+            traffic_islands: RTree::bulk_load(
+                streets
+                    .traffic_islands
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, p)| GeomWithData::new(to_rectangle(&p.get_bounds()), idx))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Roads whose bounds overlap these bounds, in order of ID
+    pub fn roads(&self, bounds: &Bounds) -> Vec<RoadID> {
+        let mut ids: Vec<RoadID> = self
+            .roads
+            .locate_in_envelope_intersecting(&to_envelope(bounds))
+            .map(|entry| entry.data)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Intersections whose bounds overlap these bounds, in order of ID
+    pub fn intersections(&self, bounds: &Bounds) -> Vec<IntersectionID> {
+        let mut ids: Vec<IntersectionID> = self
+            .intersections
+            .locate_in_envelope_intersecting(&to_envelope(bounds))
+            .map(|entry| entry.data)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Indices of the traffic islands whose bounds overlap these bounds, in order
+    pub fn traffic_islands(&self, bounds: &Bounds) -> Vec<usize> {
+        let mut indices: Vec<usize> = self
+            .traffic_islands
+            .locate_in_envelope_intersecting(&to_envelope(bounds))
+            .map(|entry| entry.data)
+            .collect();
+        indices.sort();
+        indices
+    }
+}
+
+/// Lazily builds a `SpatialIndex` the first time it's needed, and again once the network has
+/// changed. The index is keyed by a `Stamp`: the methods editing the network bump `generation`,
+/// and adding or removing roads, intersections, or traffic islands directly through the public
+/// fields changes the counts. Copies of the network start with an empty cache.
+#[derive(Default)]
+pub(crate) struct SpatialIndexCache {
+    generation: u64,
+    cached: Mutex<Option<(Stamp, Arc<SpatialIndex>)>>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    generation: u64,
+    roads: usize,
+    intersections: usize,
+    traffic_islands: usize,
+    road_id_counter: usize,
+    intersection_id_counter: usize,
+}
+
+impl Clone for SpatialIndexCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl std::fmt::Debug for SpatialIndexCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SpatialIndexCache")
+    }
+}
+
+impl StreetNetwork {
+    /// The spatial index for the current network, built if needed
+    pub(crate) fn spatial_index(&self) -> Arc<SpatialIndex> {
+        let stamp = Stamp {
+            generation: self.spatial_index.generation,
+            roads: self.roads.len(),
+            intersections: self.intersections.len(),
+            traffic_islands: self.traffic_islands.len(),
+            road_id_counter: self.road_id_counter,
+            intersection_id_counter: self.intersection_id_counter,
+        };
+        let mut cached = self.spatial_index.cached.lock().unwrap();
+        if let Some((prev, ref index)) = *cached {
+            if prev == stamp {
+                return index.clone();
+            }
+        }
+        let index = Arc::new(SpatialIndex::new(self));
+        *cached = Some((stamp, index.clone()));
+        index
+    }
+
+    /// Call after changing the geometry of roads, intersections, or traffic islands directly
+    /// through their fields, so queries like `Filter::Within` see the change. The methods editing
+    /// the network already do this.
+    pub fn geometry_changed(&mut self) {
+        self.spatial_index.generation += 1;
+    }
+}
+
+/// The bounds of a road's center line, grown to cover its full width
+pub(crate) fn road_bounds(road: &Road) -> Bounds {
+    let mut bounds = road.center_line.get_bounds();
+    let half_width = road.half_width().inner_meters();
+    bounds.min_x -= half_width;
+    bounds.min_y -= half_width;
+    bounds.max_x += half_width;
+    bounds.max_y += half_width;
+    bounds
+}
+
+fn to_rectangle(bounds: &Bounds) -> Rectangle<[f64; 2]> {
+    Rectangle::from_corners([bounds.min_x, bounds.min_y], [bounds.max_x, bounds.max_y])
+}
+
+fn to_envelope(bounds: &Bounds) -> AABB<[f64; 2]> {
+    AABB::from_corners([bounds.min_x, bounds.min_y], [bounds.max_x, bounds.max_y])
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::test_utils::add_lone_road;
+    use crate::Filter;
+
+    #[test]
+    fn test_bbox_sees_new_roads() {
+        let mut streets = StreetNetwork::blank();
+        let r1 = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)],
+            &["highway=residential"],
+        );
+        let mut bounds = Bounds::new();
+        bounds.update(Pt2D::new(-10.0, -10.0));
+        bounds.update(Pt2D::new(500.0, 10.0));
+        let filter = Filter::BBox(bounds);
+        let roads = |streets: &StreetNetwork| -> Vec<RoadID> {
+            filter.roads(streets).map(|r| r.id).collect()
+        };
+        assert_eq!(roads(&streets), vec![r1]);
+
+        // The index is cached, but inserting a road clears it. The second new road is outside the
+        // bounds.
+        let r2 = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(300.0, 0.0), Pt2D::new(400.0, 0.0)],
+            &["highway=residential"],
+        );
+        add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 300.0), Pt2D::new(100.0, 300.0)],
+            &["highway=residential"],
+        );
+        assert_eq!(roads(&streets), vec![r1, r2]);
+
+        streets.remove_road(r1);
+        assert_eq!(roads(&streets), vec![r2]);
+    }
+
+    #[test]
+    fn test_bbox_sees_direct_edits() {
+        let mut streets = StreetNetwork::blank();
+        let r1 = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)],
+            &["highway=residential"],
+        );
+        let r2 = add_lone_road(
+            &mut streets,
+            vec![Pt2D::new(0.0, 50.0), Pt2D::new(100.0, 50.0)],
+            &["highway=residential"],
+        );
+        let mut bounds = Bounds::new();
+        bounds.update(Pt2D::new(-10.0, -10.0));
+        bounds.update(Pt2D::new(110.0, 10.0));
+        let filter = Filter::BBox(bounds);
+        let roads = |streets: &StreetNetwork| -> Vec<RoadID> {
+            filter.roads(streets).map(|r| r.id).collect()
+        };
+        assert_eq!(roads(&streets), vec![r1]);
+
+        // Moving a road through the public fields needs `geometry_changed`
+        let road = streets.roads.get_mut(&r2).unwrap();
+        road.center_line = PolyLine::must_new(vec![Pt2D::new(0.0, 5.0), Pt2D::new(100.0, 5.0)]);
+        streets.geometry_changed();
+        assert_eq!(roads(&streets), vec![r1, r2]);
+
+        // Removing one directly is noticed without it
+        streets.roads.remove(&r1);
+        assert_eq!(roads(&streets), vec![r2]);
+    }
+}
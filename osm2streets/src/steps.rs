@@ -0,0 +1,126 @@
+use abstutil::Tags;
+use geom::{Distance, Pt2D};
+use serde::{Deserialize, Serialize};
+
+use crate::{osm, Road};
+
+/// Typical depth of one step, used to draw stairs when the number of steps isn't tagged
+const TREAD_DEPTH: Distance = Distance::const_meters(0.3);
+
+/// Details about a `highway=steps` road. See <https://wiki.openstreetmap.org/wiki/Tag:highway=steps>.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Steps {
+    /// From `step_count`
+    pub step_count: Option<usize>,
+    /// Which way is up, relative to the road's direction. None if `incline` isn't tagged.
+    pub incline: Option<Incline>,
+    /// A ramp alongside the steps usable by wheelchairs, from `ramp:wheelchair`
+    pub wheelchair_ramp: bool,
+    /// A channel for pushing bikes, from `ramp:bicycle`
+    pub bicycle_ramp: bool,
+    /// From `handrail`, or `handrail:left`, `handrail:right`, or `handrail:center`
+    pub handrail: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Incline {
+    Up,
+    Down,
+}
+
+/// Parses tags describing steps. Returns None for anything other than `highway=steps`.
+pub(crate) fn parse_steps(tags: &Tags) -> Option<Steps> {
+    if !tags.is(osm::HIGHWAY, "steps") {
+        return None;
+    }
+    let incline = tags.get("incline").and_then(|value| {
+        // Percentages and degrees are also allowed; only their sign matters here
+        if value == "up" {
+            Some(Incline::Up)
+        } else if value == "down" {
+            Some(Incline::Down)
+        } else if value.starts_with('-') {
+            Some(Incline::Down)
+        } else if value.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(Incline::Up)
+        } else {
+            None
+        }
+    });
+    let yes = |key: &str| tags.is_any(key, vec!["yes", "automatic", "manual"]);
+    Some(Steps {
+        step_count: tags.get("step_count").and_then(|x| x.parse::<usize>().ok()),
+        incline,
+        wheelchair_ramp: yes("ramp:wheelchair"),
+        bicycle_ramp: yes("ramp:bicycle"),
+        handrail: [
+            "handrail",
+            "handrail:left",
+            "handrail:right",
+            "handrail:center",
+        ]
+        .into_iter()
+        .any(|key| tags.is(key, "yes")),
+    })
+}
+
+impl Road {
+    pub fn is_steps(&self) -> bool {
+        self.steps.is_some()
+    }
+
+    /// Steps without a wheelchair ramp can't be used by wheelchairs.
+    pub fn is_wheelchair_accessible(&self) -> bool {
+        match self.steps {
+            Some(ref steps) => steps.wheelchair_ramp,
+            None => true,
+        }
+    }
+
+    /// For drawing stairs, returns one line across the road per step.
+    pub(crate) fn stair_treads(&self) -> Vec<(Pt2D, Pt2D)> {
+        let Some(ref steps) = self.steps else {
+            return Vec::new();
+        };
+        let length = self.center_line.length();
+        let count = steps
+            .step_count
+            .unwrap_or_else(|| (length / TREAD_DEPTH).floor() as usize)
+            .max(1);
+        let half_width = self.half_width();
+
+        let mut treads = Vec::new();
+        for step in 1..count {
+            let (pt, angle) = self
+                .center_line
+                .must_dist_along(length * (step as f64) / (count as f64));
+            treads.push((
+                pt.project_away(half_width, angle.rotate_degs(-90.0)),
+                pt.project_away(half_width, angle.rotate_degs(90.0)),
+            ));
+        }
+        treads
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_steps() {
+        let mut tags = Tags::empty();
+        tags.insert("highway", "steps");
+        tags.insert("incline", "-20%");
+        tags.insert("step_count", "12");
+        tags.insert("handrail:right", "yes");
+        let steps = parse_steps(&tags).unwrap();
+        assert_eq!(Some(12), steps.step_count);
+        assert_eq!(Some(Incline::Down), steps.incline);
+        assert!(steps.handrail);
+        assert!(!steps.wheelchair_ramp);
+
+        tags.insert("highway", "footway");
+        assert_eq!(None, parse_steps(&tags));
+    }
+}
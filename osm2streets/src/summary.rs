@@ -0,0 +1,261 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::utils::serialize_btreemap;
+use crate::{LaneSpec, LaneType, Road, SeparateKind, SeparateStatus, SideOfRoad, StreetNetwork};
+
+/// Totals describing an entire `StreetNetwork`, meant for dashboards and quick comparisons.
+/// Lengths are measured along the untrimmed center lines of roads.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NetworkSummary {
+    pub num_roads: usize,
+    pub num_intersections: usize,
+    /// Total road length in kilometers, keyed by the OSM `highway` tag
+    pub km_by_highway: BTreeMap<String, f64>,
+    /// The length of each lane summed up in kilometers, keyed by lane type. Some lane types aren't
+    /// plain strings, so in JSON this is a list of pairs.
+    #[serde(serialize_with = "serialize_btreemap")]
+    pub lane_km_by_type: BTreeMap<LaneType, f64>,
+    /// Keyed by `IntersectionKind`
+    pub intersections_by_kind: BTreeMap<String, usize>,
+    /// Keyed by `IntersectionControl`
+    pub intersections_by_control: BTreeMap<String, usize>,
+    /// Of both sides of every driveable road, the percent of length with a sidewalk, either as
+    /// part of the road or mapped separately alongside
+    pub sidewalk_coverage_percent: f64,
+    /// Of every driveable road, the percent of length with a bike lane or separate cycleway on at
+    /// least one side
+    pub cycle_coverage_percent: f64,
+}
+
+impl StreetNetwork {
+    pub fn summarize(&self) -> NetworkSummary {
+        let mut km_by_highway = BTreeMap::new();
+        let mut lane_km_by_type = BTreeMap::new();
+        let mut driveable_km = 0.0;
+        let mut sidewalk_km = 0.0;
+        let mut cycle_km = 0.0;
+        for road in self.roads.values() {
+            let km = road.untrimmed_length().inner_meters() / 1000.0;
+            *km_by_highway
+                .entry(road.highway_type.to_string())
+                .or_insert(0.0) += km;
+            for lane in &road.lane_specs_ltr {
+                *lane_km_by_type.entry(lane.lt).or_insert(0.0) += km;
+            }
+
+            if !road.is_driveable() {
+                continue;
+            }
+            driveable_km += km;
+            for side in [SideOfRoad::Left, SideOfRoad::Right] {
+                if has_sidewalk(road, side) {
+                    sidewalk_km += km / 2.0;
+                }
+            }
+            if road
+                .lane_specs_ltr
+                .iter()
+                .any(|lane| lane.lt == LaneType::Biking)
+                || has_separate(road, SeparateKind::Cycleway, None)
+            {
+                cycle_km += km;
+            }
+        }
+
+        let mut intersections_by_kind = BTreeMap::new();
+        let mut intersections_by_control = BTreeMap::new();
+        for i in self.intersections.values() {
+            *intersections_by_kind
+                .entry(format!("{:?}", i.kind))
+                .or_insert(0) += 1;
+            *intersections_by_control
+                .entry(format!("{:?}", i.control))
+                .or_insert(0) += 1;
+        }
+
+        let percent = |x: f64| {
+            if driveable_km == 0.0 {
+                0.0
+            } else {
+                100.0 * x / driveable_km
+            }
+        };
+        NetworkSummary {
+            num_roads: self.roads.len(),
+            num_intersections: self.intersections.len(),
+            km_by_highway,
+            lane_km_by_type,
+            intersections_by_kind,
+            intersections_by_control,
+            sidewalk_coverage_percent: percent(sidewalk_km),
+            cycle_coverage_percent: percent(cycle_km),
+        }
+    }
+
+    /// Returns `summarize` as JSON.
+    pub fn to_summary_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&self.summarize())?)
+    }
+}
+
+/// Is there a sidewalk between the roadway and the edge of the road on this side?
+fn has_sidewalk(road: &Road, side: SideOfRoad) -> bool {
+    let is_sidewalk = |lanes: &mut dyn Iterator<Item = &LaneSpec>| {
+        lanes
+            .take_while(|lane| !lane.lt.is_roadway())
+            .any(|lane| lane.lt == LaneType::Sidewalk)
+    };
+    let on_road = match side {
+        SideOfRoad::Left => is_sidewalk(&mut road.lane_specs_ltr.iter()),
+        SideOfRoad::Right => is_sidewalk(&mut road.lane_specs_ltr.iter().rev()),
+    };
+    on_road || has_separate(road, SeparateKind::Sidewalk, Some(side))
+}
+
+/// Was a separately mapped way of this kind found alongside the road?
+fn has_separate(road: &Road, kind: SeparateKind, side: Option<SideOfRoad>) -> bool {
+    road.separately_mapped.iter().any(|x| {
+        x.kind == kind
+            && side.map(|side| x.side == side).unwrap_or(true)
+            && matches!(
+                x.status,
+                SeparateStatus::Matched(_) | SeparateStatus::Zipped
+            )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionID, IntersectionKind, SeparatelyMapped};
+
+    // Two 100m residential roads in a row, the first with parking, then a 50m footway
+    fn network() -> StreetNetwork {
+        let mut streets = StreetNetwork::blank();
+        let pts = [
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(200.0, 0.0),
+            Pt2D::new(200.0, 50.0),
+        ];
+        let intersections: Vec<IntersectionID> = pts
+            .iter()
+            .enumerate()
+            .map(|(idx, pt)| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    if idx == 0 || idx == 3 {
+                        IntersectionKind::MapEdge
+                    } else {
+                        IntersectionKind::Connection
+                    },
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        let ways: [&[(&str, &str)]; 3] = [
+            &[
+                ("highway", "residential"),
+                ("lanes", "2"),
+                ("sidewalk", "both"),
+                ("parking:lane:both", "parallel"),
+            ],
+            &[
+                ("highway", "residential"),
+                ("lanes", "2"),
+                ("sidewalk", "right"),
+                ("cycleway:right", "lane"),
+            ],
+            &[("highway", "footway")],
+        ];
+        for (idx, kv) in ways.into_iter().enumerate() {
+            let mut tags = Tags::empty();
+            for (k, v) in kv {
+                tags.insert(*k, *v);
+            }
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                intersections[idx],
+                intersections[idx + 1],
+                PolyLine::must_new(vec![pts[idx], pts[idx + 1]]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+        }
+        streets
+    }
+
+    fn close(x: f64, y: f64) -> bool {
+        (x - y).abs() < 0.001
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut streets = network();
+        let summary = streets.summarize();
+        assert_eq!(3, summary.num_roads);
+        assert_eq!(4, summary.num_intersections);
+        assert!(close(0.2, summary.km_by_highway["residential"]));
+        assert!(close(0.05, summary.km_by_highway["footway"]));
+        assert!(close(0.4, summary.lane_km_by_type[&LaneType::Driving]));
+        assert!(close(
+            0.2,
+            summary
+                .lane_km_by_type
+                .iter()
+                .filter(|(lt, _)| matches!(lt, LaneType::Parking(_)))
+                .map(|(_, km)| km)
+                .sum()
+        ));
+        assert_eq!(2, summary.intersections_by_kind["MapEdge"]);
+        assert_eq!(4, summary.intersections_by_control["Uncontrolled"]);
+        // Three of the four sides of driveable roads have sidewalks. The footway doesn't count.
+        assert!(close(75.0, summary.sidewalk_coverage_percent));
+        assert!(close(50.0, summary.cycle_coverage_percent));
+
+        // Separately mapped ways count once they're found
+        let first = *streets.roads.keys().next().unwrap();
+        for status in [SeparateStatus::Unmatched, SeparateStatus::Zipped] {
+            let road = streets.roads.get_mut(&first).unwrap();
+            road.separately_mapped = vec![SeparatelyMapped {
+                kind: SeparateKind::Cycleway,
+                side: SideOfRoad::Left,
+                status,
+            }];
+            let expected = if status == SeparateStatus::Zipped {
+                100.0
+            } else {
+                50.0
+            };
+            assert!(close(expected, streets.summarize().cycle_coverage_percent));
+        }
+
+        // Parking lane types aren't strings, but still serialize
+        let json: serde_json::Value =
+            serde_json::from_str(&streets.to_summary_json().unwrap()).unwrap();
+        assert_eq!(3, json["num_roads"]);
+        assert!(json["lane_km_by_type"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|pair| pair[0].get("Parking").is_some()));
+    }
+
+    #[test]
+    fn test_empty() {
+        let summary = StreetNetwork::blank().summarize();
+        assert_eq!(0, summary.num_roads);
+        assert_eq!(0.0, summary.sidewalk_coverage_percent);
+        assert_eq!(0.0, summary.cycle_coverage_percent);
+    }
+}
@@ -0,0 +1,83 @@
+//! Helpers for building small networks in unit tests.
+
+use abstutil::Tags;
+use geom::{PolyLine, Pt2D};
+
+use crate::{
+    osm, IntersectionControl, IntersectionID, IntersectionKind, Road, RoadID, StreetNetwork,
+};
+
+/// Intersections don't store a point, so tests keep it alongside the ID to build roads
+pub(crate) type TestIntersection = (IntersectionID, Pt2D);
+
+pub(crate) fn add_intersection(
+    streets: &mut StreetNetwork,
+    x: f64,
+    y: f64,
+    kind: IntersectionKind,
+) -> TestIntersection {
+    let pt = Pt2D::new(x, y);
+    let id = streets.insert_intersection(Vec::new(), pt, kind, IntersectionControl::Uncontrolled);
+    (id, pt)
+}
+
+/// Adds a straight road between two intersections, with tags written as `key=value`. The road
+/// comes from an OSM way with the same ID.
+pub(crate) fn add_road(
+    streets: &mut StreetNetwork,
+    (i1, pt1): TestIntersection,
+    (i2, pt2): TestIntersection,
+    tags: &[&str],
+) -> RoadID {
+    add_road_between(streets, i1, i2, PolyLine::must_new(vec![pt1, pt2]), tags)
+}
+
+/// Adds a road along some points, with a map edge at each end
+pub(crate) fn add_lone_road(streets: &mut StreetNetwork, pts: Vec<Pt2D>, tags: &[&str]) -> RoadID {
+    let i1 = streets.insert_intersection(
+        Vec::new(),
+        pts[0],
+        IntersectionKind::MapEdge,
+        IntersectionControl::Uncontrolled,
+    );
+    let i2 = streets.insert_intersection(
+        Vec::new(),
+        *pts.last().unwrap(),
+        IntersectionKind::MapEdge,
+        IntersectionControl::Uncontrolled,
+    );
+    add_road_between(streets, i1, i2, PolyLine::must_new(pts), tags)
+}
+
+fn add_road_between(
+    streets: &mut StreetNetwork,
+    i1: IntersectionID,
+    i2: IntersectionID,
+    reference_line: PolyLine,
+    tags: &[&str],
+) -> RoadID {
+    let id = streets.next_road_id();
+    let road = Road::new(
+        id,
+        vec![osm::WayID(id.0 as i64)],
+        i1,
+        i2,
+        reference_line,
+        tags_from_pairs(tags),
+        &streets.config,
+    );
+    streets.insert_road(road);
+    id
+}
+
+/// Builds tags from `key=value` strings, like `["highway=residential", "lanes=2"]`
+pub(crate) fn tags_from_pairs(pairs: &[&str]) -> Tags {
+    let mut tags = Tags::empty();
+    for pair in pairs {
+        let (key, value) = pair
+            .split_once('=')
+            .unwrap_or_else(|| panic!("{pair} isn't key=value"));
+        tags.insert(key, value);
+    }
+    tags
+}
@@ -0,0 +1,154 @@
+use crate::{IntersectionID, Placement, RoadID, RoadPosition, StreetNetwork};
+
+/// Roads with a `placement` other than the center have their center line shifted from the OSM way,
+/// so it doesn't meet the center line of the road continuing it, making a kink in the intersection.
+/// Where exactly two roads meet, ease both center lines towards the same point.
+pub fn blend_placement_offsets(streets: &mut StreetNetwork) {
+    streets.batch_edits(|streets| {
+        let intersections: Vec<IntersectionID> = streets.intersections.keys().cloned().collect();
+        for i in intersections {
+            for r in streets.blend_placement(i) {
+                for endpt in streets.roads[&r].endpoints() {
+                    streets.intersection_roads_changed(endpt, false);
+                }
+            }
+        }
+    });
+}
+
+impl StreetNetwork {
+    /// Eases the center lines of the two roads meeting at `i`, if they have different placements.
+    /// Returns the roads changed.
+    fn blend_placement(&mut self, i: IntersectionID) -> Vec<RoadID> {
+        let driving_side = self.config.driving_side;
+        let roads = &self.intersections[&i].roads;
+        if roads.len() != 2 {
+            return Vec::new();
+        }
+        let (r1, r2) = (roads[0], roads[1]);
+        let road1 = &self.roads[&r1];
+        let road2 = &self.roads[&r2];
+        if road1.src_i == road1.dst_i || road2.src_i == road2.dst_i {
+            return Vec::new();
+        }
+        // Roads centered on the OSM way already meet
+        let centered = Placement::Consistent(RoadPosition::Center);
+        if road1.reference_line_placement == centered && road2.reference_line_placement == centered
+        {
+            return Vec::new();
+        }
+
+        // Express both shifts relative to the direction from road1, through the intersection, and
+        // along road2
+        let into_i = road1.dst_i == i;
+        let out_of_i = road2.src_i == i;
+        let shift1 = road1.center_line_shift(driving_side);
+        let shift2 = road2.center_line_shift(driving_side);
+        let through1 = if into_i { shift1 } else { -shift1 };
+        let through2 = if out_of_i { shift2 } else { -shift2 };
+        if through1 == through2 {
+            return Vec::new();
+        }
+        let meet = (through1 + through2) / 2.0;
+
+        let road1 = self.roads.get_mut(&r1).unwrap();
+        road1.placement_blend[if into_i { 1 } else { 0 }] = Some(if into_i { meet } else { -meet });
+        road1.update_center_line(driving_side);
+
+        let road2 = self.roads.get_mut(&r2).unwrap();
+        road2.placement_blend[if out_of_i { 0 } else { 1 }] =
+            Some(if out_of_i { meet } else { -meet });
+        road2.update_center_line(driving_side);
+
+        vec![r1, r2]
+    }
+
+    /// Undoes any `Road::placement_blend` of the roads at the `i` end. Returns true if a road was
+    /// blended there. The caller must update the intersection.
+    pub(crate) fn clear_placement_blend(&mut self, i: IntersectionID) -> bool {
+        let driving_side = self.config.driving_side;
+        let mut any = false;
+        for r in self.intersections[&i].roads.clone() {
+            let road = self.roads.get_mut(&r).unwrap();
+            let mut changed = false;
+            for (idx, endpt) in [road.src_i, road.dst_i].into_iter().enumerate() {
+                if endpt == i && road.placement_blend[idx].is_some() {
+                    road.placement_blend[idx] = None;
+                    changed = true;
+                }
+            }
+            if changed {
+                road.update_center_line(driving_side);
+                any = true;
+            }
+        }
+        any
+    }
+
+    /// After the lanes or placement of roads at `i` change, blends their center lines again, if
+    /// they were blended before. The caller must update the intersection.
+    pub fn refresh_placement_blend(&mut self, i: IntersectionID) {
+        if self.clear_placement_blend(i) {
+            self.blend_placement(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Distance;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    const TAGS: [&str; 4] = [
+        "highway=residential",
+        "oneway=yes",
+        "lanes=2",
+        "sidewalk=no",
+    ];
+
+    fn meeting_gap(streets: &StreetNetwork, r1: RoadID, r2: RoadID) -> Distance {
+        let driving_side = streets.config.driving_side;
+        streets.roads[&r1]
+            .get_untrimmed_center_line(driving_side)
+            .last_pt()
+            .dist_to(
+                streets.roads[&r2]
+                    .get_untrimmed_center_line(driving_side)
+                    .first_pt(),
+            )
+    }
+
+    #[test]
+    fn test_blend_placement_offsets() {
+        let mut streets = StreetNetwork::blank();
+        let i0 = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::MapEdge);
+        let i1 = add_intersection(&mut streets, 100.0, 0.0, IntersectionKind::Connection);
+        let i2 = add_intersection(&mut streets, 200.0, 0.0, IntersectionKind::Connection);
+        let i3 = add_intersection(&mut streets, 300.0, 0.0, IntersectionKind::MapEdge);
+        let r1 = add_road(
+            &mut streets,
+            i0,
+            i1,
+            &[&TAGS[..], &["placement=left_of:1"]].concat(),
+        );
+        let r2 = add_road(&mut streets, i1, i2, &TAGS);
+        // Two centered roads meet at i2, so they're left alone
+        let r3 = add_road(&mut streets, i2, i3, &TAGS);
+        assert!(meeting_gap(&streets, r1, r2) > Distance::meters(1.0));
+
+        blend_placement_offsets(&mut streets);
+        assert!(meeting_gap(&streets, r1, r2) < Distance::meters(0.1));
+        assert_eq!([None, None], streets.roads[&r3].placement_blend);
+        assert!(streets.roads[&r2].placement_blend[0].is_some());
+        assert!(streets.roads[&r2].placement_blend[1].is_none());
+
+        // A third road at the blended intersection undoes the blend
+        let i4 = add_intersection(&mut streets, 100.0, 100.0, IntersectionKind::MapEdge);
+        add_road(&mut streets, i1, i4, &TAGS);
+        assert_eq!([None, None], streets.roads[&r1].placement_blend);
+        assert_eq!([None, None], streets.roads[&r2].placement_blend);
+    }
+}
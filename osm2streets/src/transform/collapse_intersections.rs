@@ -1,13 +1,19 @@
 use anyhow::Result;
+use geom::Distance;
 
-use crate::{IntersectionID, Placement, Road, StreetNetwork};
+use crate::{IntersectionID, LaneSpec, Placement, Road, StreetNetwork};
 
 /// Collapse degenerate intersections:
 /// - between two cycleways
 /// - when the lane specs, name, and layer match
 /// - when there's no crossing
+///
+/// If the lanes only differ in width, keep the intersection, but insert a short tapered section
+/// with widths between the two, so the road doesn't abruptly change width. If the lanes genuinely
+/// change, just keep the intersection to mark the transition.
 pub fn collapse(streets: &mut StreetNetwork) {
     let mut merge: Vec<IntersectionID> = Vec::new();
+    let mut tapers: Vec<IntersectionID> = Vec::new();
     for intersection in streets.intersections.values() {
         if intersection.crossing.is_some() {
             continue;
@@ -17,9 +23,17 @@ pub fn collapse(streets: &mut StreetNetwork) {
             continue;
         }
         match should_collapse(roads[0], roads[1]) {
-            Ok(()) => {
+            Ok(LaneMatch::Same) => {
                 merge.push(intersection.id);
             }
+            Ok(LaneMatch::DifferentWidths) => {
+                // If one road is short, it's already a transition between the widths
+                if roads[0].untrimmed_length() > MAX_TRANSITION_LENGTH
+                    && roads[1].untrimmed_length() > MAX_TRANSITION_LENGTH
+                {
+                    tapers.push(intersection.id);
+                }
+            }
             Err(err) => {
                 warn!(
                     "Not collapsing degenerate intersection {}: {}",
@@ -30,14 +44,42 @@ pub fn collapse(streets: &mut StreetNetwork) {
     }
 
     for i in merge {
+        let roads = streets.intersections[&i].roads.clone();
         streets.collapse_intersection(i);
+        // Loops are skipped
+        if !streets.intersections.contains_key(&i) {
+            streets.audit(
+                roads,
+                vec![i],
+                format!(
+                    "Removed degenerate {i}, joining its two roads with matching lanes and names"
+                ),
+            );
+        }
+    }
+
+    for i in tapers {
+        match insert_taper(streets, i) {
+            Ok(()) => {
+                let roads = streets.intersections[&i].roads.clone();
+                streets.audit(
+                    roads,
+                    vec![i],
+                    format!("Inserted a transition where lane widths change at {i}"),
+                );
+            }
+            Err(err) => {
+                warn!("Not tapering lane widths at {i}: {err}");
+            }
+        }
     }
 
     // It's possible we need to do this in a fixed-point until there are no changes, but meh.
     // Results look good so far.
 }
 
-fn should_collapse(road1: &Road, road2: &Road) -> Result<()> {
+/// If the roads can be joined, says whether their lane widths differ.
+fn should_collapse(road1: &Road, road2: &Road) -> Result<LaneMatch> {
     // Don't attempt to merge roads with these.
     if !road1.turn_restrictions.is_empty() || !road1.complicated_turn_restrictions.is_empty() {
         bail!("one road has turn restrictions");
@@ -55,9 +97,9 @@ fn should_collapse(road1: &Road, road2: &Road) -> Result<()> {
         bail!("oneway roads point at each other");
     }
 
-    if road1.lane_specs_ltr != road2.lane_specs_ltr {
+    let Some(lane_match) = match_lanes(road1, road2) else {
         bail!("lane specs don't match");
-    }
+    };
 
     if road1.name != road2.name {
         bail!("names don't match");
@@ -83,5 +125,217 @@ fn should_collapse(road1: &Road, road2: &Road) -> Result<()> {
         _ => bail!("one of the placements isn't consistent"),
     }
 
+    Ok(lane_match)
+}
+
+/// A road shorter than this whose lanes only differ in width from its neighbor is already a
+/// transition between the two widths.
+const MAX_TRANSITION_LENGTH: Distance = Distance::const_meters(15.0);
+/// The length of an inserted transition between two lane widths. This must be shorter than
+/// `MAX_TRANSITION_LENGTH`, so running this transformation again doesn't insert another.
+const TAPER_LENGTH: Distance = Distance::const_meters(5.0);
+
+/// Splits a short piece off the longer road at a degenerate intersection, next to it, and gives
+/// each lane of that piece a width halfway between the two roads.
+fn insert_taper(streets: &mut StreetNetwork, i: IntersectionID) -> Result<()> {
+    // Collapsing other intersections may have changed things
+    let roads = match streets.intersections.get(&i) {
+        Some(intersection) if intersection.roads.len() == 2 => intersection.roads.clone(),
+        _ => bail!("{i} isn't degenerate anymore"),
+    };
+    let (split_r, other_r) = if streets.roads[&roads[1]].untrimmed_length()
+        > streets.roads[&roads[0]].untrimmed_length()
+    {
+        (roads[1], roads[0])
+    } else {
+        (roads[0], roads[1])
+    };
+
+    let (pt, widths) = {
+        let split = &streets.roads[&split_r];
+        let other = &streets.roads[&other_r];
+        let pl = &split.reference_line;
+        let dist = if split.dst_i == i {
+            pl.length() - TAPER_LENGTH
+        } else {
+            TAPER_LENGTH
+        };
+        let (pt, _) = pl.dist_along(dist)?;
+        let widths: Vec<Distance> = split
+            .lane_specs_ltr
+            .iter()
+            .zip(oriented_lanes(split, other).iter())
+            .map(|(a, b)| (a.width + b.width) / 2.0)
+            .collect();
+        (pt, widths)
+    };
+
+    let new_i = streets.split_road(split_r, pt)?;
+    let Some(taper_r) = streets.intersections[&new_i]
+        .roads
+        .iter()
+        .find(|r| streets.roads[r].other_side(new_i) == i)
+        .cloned()
+    else {
+        bail!("Splitting {split_r} didn't leave a piece next to {i}");
+    };
+    // The taper changes the road's total width, which `LaneEdit::ChangeWidth` never does
+    let driving_side = streets.config.driving_side;
+    let road = streets.roads.get_mut(&taper_r).unwrap();
+    for (lane, width) in road.lane_specs_ltr.iter_mut().zip(widths) {
+        if !lane.is_virtual {
+            lane.width = width;
+        }
+    }
+    road.update_center_line(driving_side);
+    for i in road.endpoints() {
+        streets.update_i(i);
+    }
     Ok(())
 }
+
+#[derive(Debug, PartialEq)]
+enum LaneMatch {
+    Same,
+    /// Everything about every lane matches except some widths
+    DifferentWidths,
+}
+
+/// Compares the lanes of two roads joined at a degenerate intersection, returning `None` if they
+/// differ in more than width. If the roads point in opposite directions, the lanes of one are
+/// flipped first.
+fn match_lanes(road1: &Road, road2: &Road) -> Option<LaneMatch> {
+    compare_lanes(&road1.lane_specs_ltr, &oriented_lanes(road1, road2))
+}
+
+/// The lanes of `road2`, flipped if needed to point the same way as `road1`
+fn oriented_lanes(road1: &Road, road2: &Road) -> Vec<LaneSpec> {
+    let mut lanes = road2.lane_specs_ltr.clone();
+    if road1.src_i == road2.src_i || road1.dst_i == road2.dst_i {
+        lanes.reverse();
+        for lane in &mut lanes {
+            lane.dir = lane.dir.opposite();
+        }
+    }
+    lanes
+}
+
+fn compare_lanes(lanes1: &[LaneSpec], lanes2: &[LaneSpec]) -> Option<LaneMatch> {
+    if lanes1.len() != lanes2.len() {
+        return None;
+    }
+    let mut result = LaneMatch::Same;
+    for (a, b) in lanes1.iter().zip(lanes2.iter()) {
+        if a.width != b.width {
+            result = LaneMatch::DifferentWidths;
+        }
+        // Every other field must match
+        let same_width = LaneSpec {
+            width: b.width,
+            ..a.clone()
+        };
+        if same_width != *b {
+            return None;
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{PolyLine, Pt2D, Speed};
+
+    use super::*;
+    use crate::{osm, IntersectionControl, IntersectionKind};
+
+    #[test]
+    fn test_compare_lanes() {
+        let lanes = LaneSpec::create_for_test("sdds", "vv^^");
+        assert_eq!(compare_lanes(&lanes, &lanes), Some(LaneMatch::Same));
+
+        let mut wider = lanes.clone();
+        wider[1].width = wider[1].width + Distance::meters(0.5);
+        assert_eq!(
+            compare_lanes(&lanes, &wider),
+            Some(LaneMatch::DifferentWidths)
+        );
+
+        let extra_lane = LaneSpec::create_for_test("sddds", "vv^^^");
+        assert_eq!(compare_lanes(&lanes, &extra_lane), None);
+
+        let flipped = LaneSpec::create_for_test("sdds", "vvv^");
+        assert_eq!(compare_lanes(&lanes, &flipped), None);
+
+        let mut faster = lanes.clone();
+        faster[1].max_speed = Some(Speed::km_per_hour(50.0));
+        assert_eq!(compare_lanes(&lanes, &faster), None);
+    }
+
+    #[test]
+    fn test_insert_taper() {
+        let mut streets = StreetNetwork::blank();
+        let pts = [
+            Pt2D::new(0.0, 0.0),
+            Pt2D::new(100.0, 0.0),
+            Pt2D::new(200.0, 0.0),
+        ];
+        let intersections: Vec<IntersectionID> = pts
+            .iter()
+            .enumerate()
+            .map(|(idx, pt)| {
+                streets.insert_intersection(
+                    Vec::new(),
+                    *pt,
+                    if idx == 1 {
+                        IntersectionKind::Connection
+                    } else {
+                        IntersectionKind::MapEdge
+                    },
+                    IntersectionControl::Uncontrolled,
+                )
+            })
+            .collect();
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        for idx in 0..2 {
+            let id = streets.next_road_id();
+            let mut road = Road::new(
+                id,
+                vec![osm::WayID(1)],
+                intersections[idx],
+                intersections[idx + 1],
+                PolyLine::must_new(vec![pts[idx], pts[idx + 1]]),
+                tags.clone(),
+                &streets.config,
+            );
+            // The second road's lanes are all a meter wider
+            if idx == 1 {
+                for lane in &mut road.lane_specs_ltr {
+                    lane.width = lane.width + Distance::meters(1.0);
+                }
+                road.update_center_line(streets.config.driving_side);
+            }
+            streets.insert_road(road);
+        }
+        let narrow: Vec<Distance> = streets.roads_per_intersection(intersections[0])[0]
+            .lane_specs_ltr
+            .iter()
+            .map(|lane| lane.width)
+            .collect();
+
+        insert_taper(&mut streets, intersections[1]).unwrap();
+
+        assert_eq!(streets.roads.len(), 3);
+        let taper = streets
+            .roads
+            .values()
+            .find(|r| r.reference_line.length() < MAX_TRANSITION_LENGTH)
+            .unwrap();
+        assert!(taper.endpoints().contains(&intersections[1]));
+        for (lane, width) in taper.lane_specs_ltr.iter().zip(narrow) {
+            let expected = width + Distance::meters(0.5);
+            assert!((lane.width - expected).inner_meters().abs() < 0.001);
+        }
+    }
+}
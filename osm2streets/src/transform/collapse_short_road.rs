@@ -1,16 +1,128 @@
 use abstutil::Timer;
+use anyhow::Result;
+use geom::Distance;
+use serde::Serialize;
 
-use crate::StreetNetwork;
+use crate::{IntersectionKind, Road, RoadID, StreetNetwork};
 
-/// Collapse all roads marked with `junction=intersection`
-pub fn collapse_all_junction_roads(streets: &mut StreetNetwork, timer: &mut Timer) {
-    let mut queue = Vec::new();
-    for (id, road) in &streets.roads {
+/// A road that `Transformation::CollapseShortRoads` would collapse, for previewing before making
+/// any changes.
+#[derive(Clone, Debug, Serialize)]
+pub struct CollapseCandidate {
+    pub road: RoadID,
+    pub reasons: Vec<CollapseReason>,
+    /// The length of the trimmed center line
+    pub length: Distance,
+    /// If the road is listed in `MapConfig::do_not_collapse`, touches a map edge, or would lose its
+    /// name with `MapConfig::preserve_road_names`, describes why it won't actually be collapsed
+    pub blocked: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum CollapseReason {
+    /// Shorter than `MapConfig::short_road_threshold`
+    Length,
+    /// Tagged `junction=intersection`
+    JunctionTag,
+    /// The intersections on either end trim away all of the road
+    TrimOverlap,
+}
+
+impl StreetNetwork {
+    /// Finds every road that `Transformation::CollapseShortRoads` would try to collapse, without
+    /// changing anything.
+    pub fn preview_collapse_short_roads(&self) -> Vec<CollapseCandidate> {
+        let mut results = Vec::new();
+        for road in self.roads.values() {
+            let reasons = self.collapse_reasons(road);
+            if reasons.is_empty() {
+                continue;
+            }
+            results.push(CollapseCandidate {
+                road: road.id,
+                reasons,
+                length: road.center_line.length(),
+                blocked: self.collapse_blocked(road),
+            });
+        }
+        results
+    }
+
+    /// Why `Transformation::CollapseShortRoads` would collapse a road. Empty if it wouldn't.
+    fn collapse_reasons(&self, road: &Road) -> Vec<CollapseReason> {
+        let threshold = self.config.short_road_threshold;
+        let mut reasons = Vec::new();
+        if threshold > Distance::ZERO && road.center_line.length() < threshold {
+            reasons.push(CollapseReason::Length);
+        }
+        // Roads trimmed into oblivion are also marked as internal_junction_road, so tell the two
+        // cases apart
         if road.internal_junction_road {
-            queue.push(*id);
+            if road.trim_start + road.trim_end >= road.untrimmed_length() {
+                reasons.push(CollapseReason::TrimOverlap);
+            } else {
+                reasons.push(CollapseReason::JunctionTag);
+            }
+        }
+        reasons
+    }
+
+    /// Why a road with some `collapse_reasons` won't be collapsed after all
+    fn collapse_blocked(&self, road: &Road) -> Option<String> {
+        if road
+            .osm_ids
+            .iter()
+            .any(|x| self.config.do_not_collapse.contains(x))
+        {
+            Some("listed in do_not_collapse".to_string())
+        } else if road
+            .endpoints()
+            .into_iter()
+            .any(|i| self.intersections[&i].kind == IntersectionKind::MapEdge)
+        {
+            Some("touches a map edge".to_string())
+        } else if let Some(name) = self
+            .config
+            .preserve_road_names
+            .then(|| self.name_lost_by_collapsing(road.id))
+            .flatten()
+        {
+            Some(format!("the only road named {name} here"))
+        } else {
+            None
         }
     }
 
+    /// Returns `preview_collapse_short_roads` as a JSON array, with lengths in meters.
+    pub fn preview_collapse_short_roads_json(&self) -> Result<String> {
+        let list: Vec<serde_json::Value> = self
+            .preview_collapse_short_roads()
+            .into_iter()
+            .map(|candidate| {
+                serde_json::json!({
+                    "road": candidate.road,
+                    "reasons": candidate.reasons,
+                    "length": candidate.length.inner_meters(),
+                    "blocked": candidate.blocked,
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+}
+
+/// Collapse all roads marked with `junction=intersection`, or shorter than
+/// `MapConfig::short_road_threshold`. Roads that `preview_collapse_short_roads` reports as blocked
+/// are skipped.
+pub fn collapse_all_junction_roads(streets: &mut StreetNetwork, timer: &mut Timer) {
+    let threshold = streets.config.short_road_threshold;
+    let queue: Vec<RoadID> = streets
+        .roads
+        .values()
+        .filter(|road| !streets.collapse_reasons(road).is_empty())
+        .map(|road| road.id)
+        .collect();
+
     timer.start_iter("collapse short roads", queue.len());
     for (idx, id) in queue.into_iter().enumerate() {
         timer.next();
@@ -19,8 +131,142 @@ pub fn collapse_all_junction_roads(streets: &mut StreetNetwork, timer: &mut Time
         }
         streets.maybe_start_debug_step(format!("collapse road {idx}"));
         streets.debug_road(id, "collapse");
-        if let Err(err) = streets.collapse_short_road(id) {
-            warn!("Not collapsing short road / junction=intersection: {}", err);
+        let road = &streets.roads[&id];
+        let (keep_i, destroy_i) = (road.src_i, road.dst_i);
+        let reason = if road.internal_junction_road {
+            "it's tagged junction=intersection or trimmed away completely".to_string()
+        } else {
+            format!("its length {} < {}", road.center_line.length(), threshold)
+        };
+        // Earlier collapses may have changed this
+        if let Some(blocked) = streets.collapse_blocked(road) {
+            streets.audit(
+                vec![id],
+                Vec::new(),
+                format!("Didn't collapse {id}, even though {reason}: {blocked}"),
+            );
+            continue;
+        }
+        let lost_name = streets.name_lost_by_collapsing(id);
+        match streets.collapse_short_road(id) {
+            Ok(()) => {
+                streets.audit(
+                    vec![id],
+                    vec![keep_i, destroy_i],
+                    format!("Collapsed {id}, merging {destroy_i} into {keep_i}, because {reason}"),
+                );
+                if let Some(name) = lost_name {
+                    streets.audit(
+                        vec![id],
+                        vec![keep_i],
+                        format!("Name conflict: {name} was lost by collapsing {id}"),
+                    );
+                }
+            }
+            Err(err) => {
+                warn!("Not collapsing short road / junction=intersection: {}", err);
+                streets.audit(
+                    vec![id],
+                    Vec::new(),
+                    format!("Didn't collapse {id}, even though {reason}: {err}"),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Pt2D;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+
+    // A long road interrupted by a short one, with a side road at both ends of the short one.
+    // Returns the short road.
+    fn network(short_name: &str) -> (StreetNetwork, RoadID) {
+        let mut streets = StreetNetwork::blank();
+        let mut intersection = |x, y, kind| add_intersection(&mut streets, x, y, kind);
+        let i0 = intersection(0.0, 0.0, IntersectionKind::MapEdge);
+        let i1 = intersection(100.0, 0.0, IntersectionKind::Intersection);
+        let i2 = intersection(110.0, 0.0, IntersectionKind::Intersection);
+        let i3 = intersection(210.0, 0.0, IntersectionKind::MapEdge);
+        let i4 = intersection(100.0, 100.0, IntersectionKind::MapEdge);
+        let i5 = intersection(110.0, -100.0, IntersectionKind::MapEdge);
+        let main = ["highway=residential", "name=Main Street"];
+        let short_name_tag = format!("name={short_name}");
+        add_road(&mut streets, i0, i1, &main);
+        let short = add_road(
+            &mut streets,
+            i1,
+            i2,
+            &["highway=residential", short_name_tag.as_str()],
+        );
+        add_road(&mut streets, i2, i3, &main);
+        add_road(&mut streets, i1, i4, &["highway=residential"]);
+        add_road(&mut streets, i2, i5, &["highway=residential"]);
+        streets.config.short_road_threshold = Distance::meters(20.0);
+        (streets, short)
+    }
+
+    #[test]
+    fn test_preserve_road_names() {
+        let (mut streets, short) = network("Short Street");
+        streets.config.preserve_road_names = true;
+        let preview = streets.preview_collapse_short_roads();
+        assert_eq!(1, preview.len());
+        assert_eq!(short, preview[0].road);
+        assert_eq!(
+            Some("the only road named Short Street here".to_string()),
+            preview[0].blocked
+        );
+        collapse_all_junction_roads(&mut streets, &mut Timer::throwaway());
+        assert!(streets.roads.contains_key(&short));
+
+        streets.config.preserve_road_names = false;
+        assert_eq!(None, streets.preview_collapse_short_roads()[0].blocked);
+        collapse_all_junction_roads(&mut streets, &mut Timer::throwaway());
+        assert!(!streets.roads.contains_key(&short));
+
+        // A name shared with a neighbor isn't lost
+        let (mut streets, short) = network("Main Street");
+        streets.config.preserve_road_names = true;
+        assert_eq!(None, streets.preview_collapse_short_roads()[0].blocked);
+        collapse_all_junction_roads(&mut streets, &mut Timer::throwaway());
+        assert!(!streets.roads.contains_key(&short));
+    }
+
+    #[test]
+    fn test_preview_matches_collapse() {
+        for preserve_road_names in [false, true] {
+            let (mut streets, short) = network("Short Street");
+            streets.config.preserve_road_names = preserve_road_names;
+            // Another short road, at a map edge
+            let i = streets.roads[&short].dst_i;
+            let edge = add_intersection(&mut streets, 110.0, 10.0, IntersectionKind::MapEdge);
+            add_road(
+                &mut streets,
+                (i, Pt2D::new(110.0, 0.0)),
+                edge,
+                &["highway=residential"],
+            );
+
+            let expected: Vec<RoadID> = streets
+                .preview_collapse_short_roads()
+                .into_iter()
+                .filter(|candidate| candidate.blocked.is_none())
+                .map(|candidate| candidate.road)
+                .collect();
+            let before: Vec<RoadID> = streets.roads.keys().cloned().collect();
+            collapse_all_junction_roads(&mut streets, &mut Timer::throwaway());
+            let collapsed: Vec<RoadID> = before
+                .into_iter()
+                .filter(|r| !streets.roads.contains_key(r))
+                .collect();
+            assert_eq!(
+                expected, collapsed,
+                "preserve_road_names = {preserve_road_names}"
+            );
         }
     }
 }
@@ -181,7 +181,7 @@ impl DualCarriagewayPt1 {
                 if road.id == current.road {
                     continue;
                 }
-                if road.name == Some(road_name.to_string()) {
+                if road.name.as_deref() == Some(road_name) {
                     if road.oneway_for_driving().is_some() {
                         current = RoadWithEndpoints::new(road);
                         sequence.push(current.clone());
@@ -0,0 +1,225 @@
+use geom::Distance;
+
+use crate::{RoadID, Sidepath, StreetNetwork};
+
+// Frontage roads shorter than this are probably just driveways or parking aisles
+const MIN_FRONTAGE_LENGTH: Distance = Distance::const_meters(50.0);
+// How far apart the center lines of a frontage road and its main road may be
+const MAX_FRONTAGE_DIST: Distance = Distance::const_meters(40.0);
+// How much of a frontage road has to run alongside main roads
+const MIN_FRONTAGE_OVERLAP: f64 = 0.8;
+const NUM_SAMPLES: usize = 10;
+
+/// Find service roads and roads tagged `frontage_road=yes` running parallel to a main road, and
+/// record the relationship in `parallel_to` on both roads.
+pub fn detect(streets: &mut StreetNetwork) {
+    let mut pairs: Vec<(RoadID, RoadID)> = Vec::new();
+    for road in streets.roads.values() {
+        if !(road.frontage_road || road.is_service())
+            || !road.is_driveable()
+            || road.untrimmed_length() < MIN_FRONTAGE_LENGTH
+        {
+            continue;
+        }
+
+        let candidates: Vec<RoadID> = streets
+            .find_nearby_roads(road.id, 3)
+            .into_iter()
+            .filter(|r| {
+                let other = &streets.roads[r];
+                *r != road.id
+                    && other.is_driveable()
+                    && !other.is_service()
+                    && !other.frontage_road
+                    && other.layer == road.layer
+            })
+            .collect();
+        if candidates.is_empty() {
+            continue;
+        }
+
+        // Sample points along the road, and see which main road, if any, runs alongside each one
+        let mut matches = Vec::new();
+        for idx in 0..NUM_SAMPLES {
+            let dist = road.center_line.length() * ((idx as f64 + 0.5) / NUM_SAMPLES as f64);
+            let Ok((pt, angle)) = road.center_line.dist_along(dist) else {
+                continue;
+            };
+            let mut best: Option<(RoadID, Distance)> = None;
+            for r in &candidates {
+                let center_line = &streets.roads[r].center_line;
+                let projected = center_line.project_pt(pt);
+                let gap = projected.dist_to(pt);
+                if gap > MAX_FRONTAGE_DIST || best.map(|(_, d)| gap >= d).unwrap_or(false) {
+                    continue;
+                }
+                let Some((_, other_angle)) = center_line.dist_along_of_point(projected) else {
+                    continue;
+                };
+                // The frontage road may point either way
+                if angle.approx_eq(other_angle, 30.0)
+                    || angle.approx_eq(other_angle.opposite(), 30.0)
+                {
+                    best = Some((*r, gap));
+                }
+            }
+            if let Some((r, _)) = best {
+                matches.push(r);
+            }
+        }
+
+        if (matches.len() as f64) / (NUM_SAMPLES as f64) < MIN_FRONTAGE_OVERLAP {
+            continue;
+        }
+        matches.sort();
+        matches.dedup();
+        for r in matches {
+            pairs.push((road.id, r));
+        }
+    }
+
+    for (frontage, main) in pairs {
+        streets
+            .roads
+            .get_mut(&frontage)
+            .unwrap()
+            .parallel_to
+            .insert(main);
+        streets
+            .roads
+            .get_mut(&main)
+            .unwrap()
+            .parallel_to
+            .insert(frontage);
+    }
+}
+
+/// Detect frontage roads, then zip each one into the main road it runs alongside, as service
+/// lanes separated by a buffer.
+pub fn merge(streets: &mut StreetNetwork) {
+    detect(streets);
+
+    let candidates: Vec<RoadID> = streets
+        .roads
+        .values()
+        .filter(|r| (r.frontage_road || r.is_service()) && !r.parallel_to.is_empty())
+        .map(|r| r.id)
+        .collect();
+    for (idx, r) in candidates.into_iter().enumerate() {
+        if !streets.roads.contains_key(&r) {
+            continue;
+        }
+        if let Some(frontage) = Sidepath::new_frontage(streets, r) {
+            streets.maybe_start_debug_step(format!("merge frontage road {idx}"));
+            frontage.debug(streets, idx.to_string());
+            frontage.zip(streets);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use geom::Pt2D;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::{IntersectionKind, LaneType};
+
+    /// A main road, and a service road 30m away connected to it at both ends. Returns the main
+    /// road and the service road.
+    fn network() -> (StreetNetwork, RoadID, RoadID) {
+        let mut streets = StreetNetwork::blank();
+        let mut intersection =
+            |x, y| add_intersection(&mut streets, x, y, IntersectionKind::Intersection);
+        let main_start = intersection(0.0, 0.0);
+        let main_end = intersection(200.0, 0.0);
+        let frontage_start = intersection(0.0, 30.0);
+        let frontage_end = intersection(200.0, 30.0);
+        let main = add_road(&mut streets, main_start, main_end, &["highway=primary"]);
+        add_road(
+            &mut streets,
+            main_start,
+            frontage_start,
+            &["highway=residential"],
+        );
+        add_road(
+            &mut streets,
+            main_end,
+            frontage_end,
+            &["highway=residential"],
+        );
+        let frontage = add_road(
+            &mut streets,
+            frontage_start,
+            frontage_end,
+            &["highway=service"],
+        );
+        (streets, main, frontage)
+    }
+
+    #[test]
+    fn test_detect() {
+        let (mut streets, main, frontage) = network();
+        detect(&mut streets);
+        assert_eq!(BTreeSet::from([main]), streets.roads[&frontage].parallel_to);
+        assert_eq!(BTreeSet::from([frontage]), streets.roads[&main].parallel_to);
+        // Nothing else is a frontage road or its main road
+        for road in streets.roads.values() {
+            if road.id != main && road.id != frontage {
+                assert!(road.parallel_to.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let (mut streets, main, frontage) = network();
+        merge(&mut streets);
+        assert!(!streets.roads.contains_key(&frontage));
+        let road = &streets.roads[&main];
+        assert!(road.parallel_to.is_empty());
+        assert!(road
+            .service_lanes
+            .iter()
+            .any(|idx| road.lane_specs_ltr[*idx].lt == LaneType::Driving));
+    }
+
+    #[test]
+    fn test_edits_keep_parallel_to() {
+        let (mut streets, main, frontage) = network();
+        detect(&mut streets);
+
+        // Both pieces of a split main road run alongside the frontage road
+        let new_i = streets.split_road(main, Pt2D::new(100.0, 0.0)).unwrap();
+        let pieces: BTreeSet<RoadID> = streets.intersections[&new_i]
+            .roads
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(pieces, streets.roads[&frontage].parallel_to);
+        for r in &pieces {
+            assert_eq!(BTreeSet::from([frontage]), streets.roads[r].parallel_to);
+        }
+
+        // Merging them again leaves one
+        streets.collapse_intersection(new_i);
+        let merged: Vec<RoadID> = pieces
+            .into_iter()
+            .filter(|r| streets.roads.contains_key(r))
+            .collect();
+        assert_eq!(1, merged.len());
+        assert_eq!(
+            BTreeSet::from([merged[0]]),
+            streets.roads[&frontage].parallel_to
+        );
+        assert_eq!(
+            BTreeSet::from([frontage]),
+            streets.roads[&merged[0]].parallel_to
+        );
+
+        streets.remove_road(merged[0]);
+        assert!(streets.roads[&frontage].parallel_to.is_empty());
+    }
+}
@@ -1,20 +1,64 @@
 use abstutil::Timer;
+use anyhow::Result;
+use serde::Deserialize;
 
-use crate::StreetNetwork;
+use crate::{Progress, RoadID, StreetNetwork};
 
+mod blend_placement;
 mod collapse_intersections;
 mod collapse_short_road;
 mod dual_carriageways;
+mod frontage_roads;
 mod parallel_sidepaths;
 mod remove_disconnected;
+mod remove_unbuilt;
+mod smooth_geometry;
+
+pub use collapse_short_road::{CollapseCandidate, CollapseReason};
 
 /// An in-place transformation of a `StreetNetwork`.
+#[derive(Deserialize)]
 pub enum Transformation {
     ZipSidepaths,
     RemoveDisconnectedRoads,
     CollapseShortRoads,
     CollapseDegenerateIntersections,
     MergeDualCarriageways,
+    /// Lanes ending mid-block must merge into the next lane over, and deceleration lanes merge
+    /// onto their off-ramp. Set this as the lane's only allowed turn, unless it has turns tagged.
+    /// See `StreetNetwork::find_lane_transitions`.
+    MarkLaneTransitions,
+    /// Record frontage roads and the main roads they run alongside in `parallel_to`, but leave
+    /// them separate
+    DetectFrontageRoads,
+    /// Zip frontage roads into their main road as `service_lanes`
+    MergeFrontageRoads,
+    /// Smooth sharp corners along roads into curves, straying at most
+    /// `MapConfig::road_smoothing_max_deviation`
+    SmoothRoadGeometry,
+    /// Remove roads that are under construction, proposed, or disused
+    RemoveUnbuiltRoads,
+    /// Where two roads with different `placement` meet, ease their center lines to meet smoothly
+    BlendPlacementOffsets,
+    /// Find the separate ways matching tags like `sidewalk=separate`. See
+    /// `StreetNetwork::link_separately_mapped`.
+    LinkSeparateWays,
+    /// A transformation defined outside this crate
+    #[serde(skip)]
+    Custom(Box<dyn NetworkTransform>),
+}
+
+/// A transformation that library users can define and run in the same sequence as the built-in
+/// ones, with progress reporting and stepwise debugging.
+pub trait NetworkTransform: Send + Sync {
+    /// Labels the transformation in progress reports, timers, debug steps, and the audit log
+    fn name(&self) -> &'static str {
+        "custom transformation"
+    }
+
+    /// Modifies the network in place. Implementations should keep the network valid, like calling
+    /// `StreetNetwork::update_i` after changing roads around an intersection.
+    fn apply(&self, streets: &mut StreetNetwork);
 }
 
 impl Transformation {
@@ -35,11 +79,21 @@ impl Transformation {
             Transformation::CollapseShortRoads => "collapse short roads",
             Transformation::CollapseDegenerateIntersections => "collapse degenerate intersections",
             Transformation::MergeDualCarriageways => "merge dual carriageways",
+            Transformation::MarkLaneTransitions => "mark lane transitions",
+            Transformation::DetectFrontageRoads => "detect frontage roads",
+            Transformation::MergeFrontageRoads => "merge frontage roads",
+            Transformation::SmoothRoadGeometry => "smooth road geometry",
+            Transformation::RemoveUnbuiltRoads => "remove unbuilt roads",
+            Transformation::BlendPlacementOffsets => "blend placement offsets",
+            Transformation::LinkSeparateWays => "link separate ways",
+            Transformation::Custom(transform) => transform.name(),
         }
     }
 
     fn apply(&self, streets: &mut StreetNetwork, timer: &mut Timer) {
         timer.start(self.name());
+        streets.current_transformation = Some(self.name());
+        let roads_before: Vec<RoadID> = streets.roads.keys().cloned().collect();
         match self {
             Transformation::ZipSidepaths => {
                 parallel_sidepaths::zip_sidepaths(streets);
@@ -56,7 +110,39 @@ impl Transformation {
             Transformation::MergeDualCarriageways => {
                 dual_carriageways::merge(streets);
             }
+            Transformation::MarkLaneTransitions => {
+                streets.mark_lane_transitions();
+            }
+            Transformation::DetectFrontageRoads => {
+                frontage_roads::detect(streets);
+            }
+            Transformation::MergeFrontageRoads => {
+                frontage_roads::merge(streets);
+            }
+            Transformation::SmoothRoadGeometry => {
+                smooth_geometry::smooth_all_roads(streets);
+            }
+            Transformation::RemoveUnbuiltRoads => {
+                remove_unbuilt::remove_unbuilt_roads(streets);
+            }
+            Transformation::BlendPlacementOffsets => {
+                blend_placement::blend_placement_offsets(streets);
+            }
+            Transformation::LinkSeparateWays => {
+                streets.link_separately_mapped();
+            }
+            Transformation::Custom(transform) => {
+                transform.apply(streets);
+            }
         }
+        // Only removing roads can leave turn restrictions referring to roads that are gone or no
+        // longer connect
+        if roads_before.iter().any(|r| !streets.roads.contains_key(r)) {
+            streets.remove_stale_turn_restrictions();
+        }
+        streets.current_transformation = None;
+        // Transformations may edit road and intersection geometry directly
+        streets.geometry_changed();
         timer.stop(self.name());
     }
 }
@@ -67,11 +153,34 @@ impl StreetNetwork {
         transformations: Vec<Transformation>,
         timer: &mut Timer,
     ) {
+        // Can't fail without a CancellationToken
+        self.apply_transformations_with_progress(transformations, timer, &mut Progress::none())
+            .unwrap();
+    }
+
+    /// Like `apply_transformations`, but reports which transformation is running. If the
+    /// operation is cancelled, the transformations applied so far are kept.
+    pub fn apply_transformations_with_progress(
+        &mut self,
+        transformations: Vec<Transformation>,
+        timer: &mut Timer,
+        progress: &mut Progress,
+    ) -> Result<()> {
+        self.maybe_keep_original();
         timer.start("simplify StreetNetwork");
-        for transformation in transformations {
+        let total = transformations.len();
+        for (idx, transformation) in transformations.into_iter().enumerate() {
+            if let Err(err) = progress.check_cancelled(transformation.name()) {
+                // Balance the timer, so callers can keep using it
+                timer.stop("simplify StreetNetwork");
+                return Err(err);
+            }
+            progress.report_iter(transformation.name(), idx, total);
             transformation.apply(self, timer);
         }
+        progress.report("simplify StreetNetwork", 1.0);
         timer.stop("simplify StreetNetwork");
+        Ok(())
     }
 
     /// Apply a sequence of transformations, but also check invariants after every step. More
@@ -81,6 +190,7 @@ impl StreetNetwork {
         transformations: Vec<Transformation>,
         timer: &mut Timer,
     ) {
+        self.maybe_keep_original();
         timer.start("simplify StreetNetwork");
         for transformation in transformations {
             transformation.apply(self, timer);
@@ -96,15 +206,104 @@ impl StreetNetwork {
         transformations: Vec<Transformation>,
         timer: &mut Timer,
     ) {
+        // Can't fail without a CancellationToken
+        self.apply_transformations_stepwise_debugging_with_progress(
+            transformations,
+            timer,
+            &mut Progress::none(),
+        )
+        .unwrap();
+    }
+
+    /// Like `apply_transformations_stepwise_debugging`, but reports which transformation is
+    /// running. If the operation is cancelled, the transformations applied so far are kept.
+    pub fn apply_transformations_stepwise_debugging_with_progress(
+        &mut self,
+        transformations: Vec<Transformation>,
+        timer: &mut Timer,
+        progress: &mut Progress,
+    ) -> Result<()> {
         self.start_debug_step("original");
 
+        self.maybe_keep_original();
         timer.start("simplify StreetNetwork");
-        for transformation in transformations {
+        let total = transformations.len();
+        for (idx, transformation) in transformations.into_iter().enumerate() {
+            if let Err(err) = progress.check_cancelled(transformation.name()) {
+                // Balance the timer, so callers can keep using it
+                timer.stop("simplify StreetNetwork");
+                return Err(err);
+            }
+            progress.report_iter(transformation.name(), idx, total);
             transformation.apply(self, timer);
             // Do this after, so any internal debug steps done by the transformation itself show up
             // first
             self.start_debug_step(transformation.name());
         }
+        progress.report("simplify StreetNetwork", 1.0);
         timer.stop("simplify StreetNetwork");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct RenameEverything;
+
+    impl NetworkTransform for RenameEverything {
+        fn name(&self) -> &'static str {
+            "rename everything"
+        }
+
+        fn apply(&self, streets: &mut StreetNetwork) {
+            streets.config.country_code = "renamed".to_string();
+        }
+    }
+
+    #[test]
+    fn test_custom_transformation() {
+        let mut streets = StreetNetwork::blank();
+        streets.apply_transformations_stepwise_debugging(
+            vec![Transformation::Custom(Box::new(RenameEverything))],
+            &mut Timer::throwaway(),
+        );
+        assert_eq!("renamed", streets.config.country_code);
+        assert_eq!(
+            vec!["original", "rename everything"],
+            streets
+                .debug_steps
+                .iter()
+                .map(|step| step.label.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_cancel_stops_timer() {
+        let mut streets = StreetNetwork::blank();
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let mut progress = Progress::none().with_cancellation(token);
+        let mut timer = Timer::throwaway();
+        timer.start("outer");
+        assert!(streets
+            .apply_transformations_with_progress(
+                vec![Transformation::Custom(Box::new(RenameEverything))],
+                &mut timer,
+                &mut progress,
+            )
+            .is_err());
+        assert!(streets
+            .apply_transformations_stepwise_debugging_with_progress(
+                vec![Transformation::Custom(Box::new(RenameEverything))],
+                &mut timer,
+                &mut progress,
+            )
+            .is_err());
+        assert_ne!("renamed", streets.config.country_code);
+        // The inner phases were stopped, so this matches
+        timer.stop("outer");
     }
 }
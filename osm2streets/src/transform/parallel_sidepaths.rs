@@ -3,17 +3,24 @@ use crate::{Sidepath, StreetNetwork};
 /// Find sidepath segments that exist as separate objects, parallel to a main road. Zip (or "snap")
 /// them into the main road, inserting a buffer lane to represent the physical division.
 pub fn zip_sidepaths(streets: &mut StreetNetwork) {
-    let mut sidepaths = Vec::new();
+    let mut candidates = Vec::new();
     for r in streets.roads.values() {
         // TODO Or footpath
         if r.is_cycleway() {
-            sidepaths.extend(Sidepath::new(streets, r.id));
+            candidates.push(r.id);
         }
     }
 
-    for (idx, sidepath) in sidepaths.into_iter().enumerate() {
-        streets.maybe_start_debug_step(format!("snap sidepath {idx}"));
-        sidepath.debug(streets, idx.to_string());
-        sidepath.zip(streets);
+    // Zipping one sidepath may split a main road or add lanes to it, so match each sidepath only
+    // right before zipping it. This handles a main road with sidepaths on both sides.
+    for (idx, r) in candidates.into_iter().enumerate() {
+        if !streets.roads.contains_key(&r) {
+            continue;
+        }
+        if let Some(sidepath) = Sidepath::new(streets, r) {
+            streets.maybe_start_debug_step(format!("snap sidepath {idx}"));
+            sidepath.debug(streets, idx.to_string());
+            sidepath.zip(streets);
+        }
     }
 }
@@ -34,16 +34,39 @@ pub fn remove_disconnected_roads(streets: &mut StreetNetwork) {
 
     partitions.sort_by_key(|roads| roads.len());
     partitions.reverse();
+    let largest = partitions.first().map(|p| p.len()).unwrap_or(0);
     for p in partitions.iter().skip(1) {
         for id in p {
             info!("Removing {} because it's disconnected from most roads", id);
             streets.remove_road(*id);
+            streets.audit(
+                vec![*id],
+                Vec::new(),
+                format!(
+                    "Removed {id}, because it's in a group of {} roads disconnected from the \
+                     largest group of {largest}",
+                    p.len()
+                ),
+            );
         }
     }
 
     // Also remove cul-de-sacs here. TODO Support them properly, but for now, they mess up parking
     // hint matching (loop PolyLine) and pathfinding later.
-    streets.retain_roads(|r| r.src_i != r.dst_i);
+    let loops: Vec<RoadID> = streets
+        .roads
+        .values()
+        .filter(|r| r.src_i == r.dst_i)
+        .map(|r| r.id)
+        .collect();
+    for id in loops {
+        let i = streets.remove_road(id).src_i;
+        streets.audit(
+            vec![id],
+            vec![i],
+            format!("Removed {id}, because it starts and ends at {i}"),
+        );
+    }
 
     // Remove intersections without any roads
     streets.intersections.retain(|_, i| !i.roads.is_empty());
@@ -0,0 +1,23 @@
+use crate::{Lifecycle, RoadID, StreetNetwork};
+
+/// Roads under construction, proposed, or disused can't be used today. Some consumers, like
+/// routing, only care about the network as it exists now, so remove them.
+pub fn remove_unbuilt_roads(streets: &mut StreetNetwork) {
+    let unbuilt: Vec<(RoadID, Lifecycle)> = streets
+        .roads
+        .values()
+        .filter(|r| r.lifecycle != Lifecycle::Existing)
+        .map(|r| (r.id, r.lifecycle))
+        .collect();
+    for (id, lifecycle) in unbuilt {
+        streets.remove_road(id);
+        streets.audit(
+            vec![id],
+            Vec::new(),
+            format!("Removed {id}, because it's {lifecycle:?}"),
+        );
+    }
+
+    // Remove intersections without any roads
+    streets.intersections.retain(|_, i| !i.roads.is_empty());
+}
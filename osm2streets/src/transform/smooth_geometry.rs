@@ -0,0 +1,35 @@
+use geom::PolyLine;
+
+use crate::{smooth_corners, StreetNetwork};
+
+/// Some roads are mapped with long straight segments and sharp corners, making lane polygons kink.
+/// Smooth every corner into a curve.
+pub fn smooth_all_roads(streets: &mut StreetNetwork) {
+    let max_deviation = streets.config.road_smoothing_max_deviation;
+    let driving_side = streets.config.driving_side;
+
+    streets.batch_edits(|streets| {
+        let mut changed = Vec::new();
+        for road in streets.roads.values_mut() {
+            let pts = road.reference_line.clone().into_points();
+            let num_pts = pts.len();
+            let smoothed = smooth_corners(pts, max_deviation);
+            if smoothed.len() == num_pts {
+                continue;
+            }
+            match PolyLine::new(smoothed) {
+                Ok(pl) => {
+                    road.reference_line = pl;
+                    road.update_center_line(driving_side);
+                    changed.extend(road.endpoints());
+                }
+                Err(err) => {
+                    warn!("Not smoothing {}: {err}", road.id);
+                }
+            }
+        }
+        for i in changed {
+            streets.intersection_roads_changed(i, true);
+        }
+    });
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+
+use crate::intersection::{calc_conflict, turn_direction_for_angle};
+use crate::{
+    Filter, IntersectionControl, IntersectionID, RoadID, StreetNetwork, TrafficConflict,
+    TrafficControlKind, TurnDirection,
+};
+
+/// Describes one movement through an intersection, for routing engines to assign turn costs
+/// without working out the intersection geometry themselves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MovementAttributes {
+    pub intersection: IntersectionID,
+    pub from: RoadID,
+    pub to: RoadID,
+    /// In degrees, with 0 straight ahead and positive to the right
+    pub angle: f64,
+    pub turn_direction: TurnDirection,
+    /// The strictest sign or signal controlling traffic entering from `from`, if any
+    pub approach_control: Option<TrafficControlKind>,
+    /// The number of other movements through the intersection that cross or merge with this one
+    pub conflicting_movements: usize,
+    /// True if at least one other movement crosses the path of this one
+    pub crosses_traffic: bool,
+}
+
+impl StreetNetwork {
+    /// Describes every allowed movement through an intersection.
+    pub fn movement_attributes(&self, i: IntersectionID) -> Vec<MovementAttributes> {
+        let intersection = &self.intersections[&i];
+        // Roads are sorted clockwise, which is what calc_conflict needs
+        let idx = |r: RoadID| intersection.roads.iter().position(|x| *x == r).unwrap();
+        let indices: Vec<(usize, usize)> = intersection
+            .movements
+            .iter()
+            .map(|(from, to)| (idx(*from), idx(*to)))
+            .collect();
+
+        let mut results = Vec::new();
+        for (movement, (from, to)) in indices.iter().zip(intersection.movements.iter()) {
+            let mut conflicting_movements = 0;
+            let mut crosses_traffic = false;
+            for other in &indices {
+                match calc_conflict(movement, other, self.config.driving_side) {
+                    TrafficConflict::Cross => {
+                        conflicting_movements += 1;
+                        crosses_traffic = true;
+                    }
+                    TrafficConflict::Merge => {
+                        conflicting_movements += 1;
+                    }
+                    TrafficConflict::Uncontested | TrafficConflict::Diverge => {}
+                }
+            }
+
+            let angle = self.movement_angle(*from, *to, i);
+            results.push(MovementAttributes {
+                intersection: i,
+                from: *from,
+                to: *to,
+                angle,
+                turn_direction: turn_direction_for_angle(angle),
+                approach_control: self.approach_control(i, *from),
+                conflicting_movements,
+                crosses_traffic,
+            });
+        }
+        results
+    }
+
+    /// Returns `movement_attributes` for every intersection as a JSON array.
+    pub fn to_movement_attributes_json(&self, filter: &Filter) -> Result<String> {
+        let list: Vec<serde_json::Value> = filter
+            .intersections(self)
+            .flat_map(|i| self.movement_attributes(i.id))
+            .map(|attributes| {
+                serde_json::json!({
+                    "intersection": attributes.intersection.0,
+                    "from_road": attributes.from.0,
+                    "to_road": attributes.to.0,
+                    "angle": attributes.angle.round(),
+                    "turn_direction": format!("{:?}", attributes.turn_direction),
+                    "approach_control": attributes.approach_control.map(|x| format!("{x:?}")),
+                    "conflicting_movements": attributes.conflicting_movements,
+                    "crosses_traffic": attributes.crosses_traffic,
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+
+    /// Signals win over stop signs, which win over give way signs. Crossings don't control
+    /// vehicles on their own.
+    pub(crate) fn approach_control(
+        &self,
+        i: IntersectionID,
+        from: RoadID,
+    ) -> Option<TrafficControlKind> {
+        let intersection = &self.intersections[&i];
+        if intersection.control == IntersectionControl::Signalled {
+            return Some(TrafficControlKind::TrafficSignals);
+        }
+        let rank = |kind: TrafficControlKind| match kind {
+            TrafficControlKind::TrafficSignals => 3,
+            TrafficControlKind::Stop => 2,
+            TrafficControlKind::GiveWay => 1,
+            TrafficControlKind::Crossing => 0,
+        };
+        intersection
+            .traffic_control_devices
+            .iter()
+            .filter(|device| device.approaches.is_empty() || device.approaches.contains(&from))
+            .map(|device| device.kind)
+            .filter(|kind| *kind != TrafficControlKind::Crossing)
+            .max_by_key(|kind| rank(*kind))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use geom::{PolyLine, Pt2D};
+
+    use super::*;
+    use crate::{osm, IntersectionKind, Road, TrafficControlDevice};
+
+    // A four-way intersection of two-way streets. Returns the center, then the north, east, south,
+    // and west arms.
+    fn four_way() -> (StreetNetwork, IntersectionID, [RoadID; 4]) {
+        let mut streets = StreetNetwork::blank();
+        let center_pt = Pt2D::new(100.0, 100.0);
+        let center = streets.insert_intersection(
+            Vec::new(),
+            center_pt,
+            IntersectionKind::Intersection,
+            IntersectionControl::Uncontrolled,
+        );
+        // Y points down
+        let mut arms = Vec::new();
+        for (x, y) in [(100.0, 0.0), (200.0, 100.0), (100.0, 200.0), (0.0, 100.0)] {
+            let pt = Pt2D::new(x, y);
+            let edge = streets.insert_intersection(
+                Vec::new(),
+                pt,
+                IntersectionKind::MapEdge,
+                IntersectionControl::Uncontrolled,
+            );
+            let mut tags = Tags::empty();
+            tags.insert("highway", "residential");
+            let id = streets.next_road_id();
+            let road = Road::new(
+                id,
+                vec![osm::WayID(id.0 as i64)],
+                center,
+                edge,
+                PolyLine::must_new(vec![center_pt, pt]),
+                tags,
+                &streets.config,
+            );
+            streets.insert_road(road);
+            arms.push(id);
+        }
+        (streets, center, [arms[0], arms[1], arms[2], arms[3]])
+    }
+
+    fn find(list: &[MovementAttributes], from: RoadID, to: RoadID) -> &MovementAttributes {
+        list.iter().find(|m| m.from == from && m.to == to).unwrap()
+    }
+
+    #[test]
+    fn test_movement_attributes() {
+        let (streets, center, [north, east, south, west]) = four_way();
+        let list = streets.movement_attributes(center);
+        assert_eq!(12, list.len());
+
+        let through = find(&list, east, west);
+        assert_eq!(TurnDirection::Through, through.turn_direction);
+        assert!(through.angle.abs() < 1.0);
+        assert!(through.crosses_traffic);
+
+        // Heading west, south is to the left
+        let left = find(&list, east, south);
+        assert_eq!(TurnDirection::Left, left.turn_direction);
+        assert!((left.angle + 90.0).abs() < 1.0);
+        assert!(left.crosses_traffic);
+
+        let right = find(&list, east, north);
+        assert_eq!(TurnDirection::Right, right.turn_direction);
+        assert!((right.angle - 90.0).abs() < 1.0);
+        // Turning right on the right side of the road crosses less traffic than turning left
+        assert!(right.conflicting_movements < left.conflicting_movements);
+
+        assert!(list.iter().all(|m| m.approach_control.is_none()));
+    }
+
+    #[test]
+    fn test_approach_control() {
+        let (mut streets, center, [north, east, _, _]) = four_way();
+        let device = |kind, approaches| TrafficControlDevice {
+            kind,
+            pt: Pt2D::new(100.0, 100.0),
+            osm_node: None,
+            approaches,
+        };
+        let intersection = streets.intersections.get_mut(&center).unwrap();
+        intersection.traffic_control_devices = vec![
+            device(TrafficControlKind::GiveWay, Vec::new()),
+            device(TrafficControlKind::Stop, vec![east]),
+            device(TrafficControlKind::Crossing, vec![north]),
+        ];
+        // The stop sign is stricter than the give way sign covering every approach
+        assert_eq!(
+            Some(TrafficControlKind::Stop),
+            streets.approach_control(center, east)
+        );
+        assert_eq!(
+            Some(TrafficControlKind::GiveWay),
+            streets.approach_control(center, north)
+        );
+
+        streets.intersections.get_mut(&center).unwrap().control = IntersectionControl::Signalled;
+        assert_eq!(
+            Some(TrafficControlKind::TrafficSignals),
+            streets.approach_control(center, north)
+        );
+    }
+
+    #[test]
+    fn test_movement_attributes_json() {
+        let (streets, center, [_, east, _, west]) = four_way();
+        let list: Vec<serde_json::Value> =
+            serde_json::from_str(&streets.to_movement_attributes_json(&Filter::All).unwrap())
+                .unwrap();
+        assert_eq!(12, list.len());
+        let through = list
+            .iter()
+            .find(|m| m["from_road"] == east.0 && m["to_road"] == west.0)
+            .unwrap();
+        assert_eq!(center.0, through["intersection"]);
+        assert_eq!("Through", through["turn_direction"]);
+        assert_eq!(0.0, through["angle"].as_f64().unwrap().abs());
+        assert!(through["approach_control"].is_null());
+    }
+}
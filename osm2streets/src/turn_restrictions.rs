@@ -0,0 +1,202 @@
+use serde::Serialize;
+
+use osm2lanes::osm;
+
+use crate::{CommonEndpoint, RestrictionType, RoadID, StreetNetwork};
+
+/// A turn restriction that no longer makes sense after transforming the network, usually because
+/// a road it refers to was removed or merged away.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StaleTurnRestriction {
+    pub from: RoadID,
+    /// Only set for restrictions where 'via' is an entire road
+    pub via: Option<RoadID>,
+    pub to: RoadID,
+    /// Only set for simple restrictions
+    pub restriction: Option<RestrictionType>,
+    /// The OSM relation the restriction came from, if known
+    pub relation: Option<osm::RelationID>,
+    pub problem: StaleReason,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum StaleReason {
+    /// The 'to' road doesn't exist anymore
+    MissingTo,
+    /// The 'via' road doesn't exist anymore. Collapsing a road rewrites restrictions via it, so
+    /// it was removed some other way.
+    MissingVia,
+    /// All of the roads exist, but they're no longer connected in sequence
+    Disconnected,
+    /// The 'via' road was split in two, and a restriction can only go via one road
+    SplitVia,
+}
+
+impl StreetNetwork {
+    /// Finds all turn restrictions referring to roads that no longer exist or no longer connect.
+    pub fn find_stale_turn_restrictions(&self) -> Vec<StaleTurnRestriction> {
+        let mut stale = Vec::new();
+        for road in self.roads.values() {
+            for (rt, to) in &road.turn_restrictions {
+                let problem = if !self.roads.contains_key(to) {
+                    StaleReason::MissingTo
+                } else if !self.roads_meet(road.id, *to) {
+                    StaleReason::Disconnected
+                } else {
+                    continue;
+                };
+                stale.push(StaleTurnRestriction {
+                    from: road.id,
+                    via: None,
+                    to: *to,
+                    restriction: Some(*rt),
+                    relation: road.turn_restriction_relations.get(to).cloned(),
+                    problem,
+                });
+            }
+
+            for (via, to) in &road.complicated_turn_restrictions {
+                let problem = if !self.roads.contains_key(to) {
+                    StaleReason::MissingTo
+                } else if !self.roads.contains_key(via) {
+                    StaleReason::MissingVia
+                } else if !self.roads_meet(road.id, *via) || !self.roads_meet(*via, *to) {
+                    StaleReason::Disconnected
+                } else {
+                    continue;
+                };
+                stale.push(StaleTurnRestriction {
+                    from: road.id,
+                    via: Some(*via),
+                    to: *to,
+                    restriction: None,
+                    relation: None,
+                    problem,
+                });
+            }
+        }
+        stale
+    }
+
+    /// Removes every restriction found by `find_stale_turn_restrictions`, and records them in
+    /// `stale_turn_restrictions`. Movements are recalculated where anything changed. Only
+    /// removing roads can make restrictions stale, so transformations call this after doing so.
+    pub fn remove_stale_turn_restrictions(&mut self) {
+        let stale = self.find_stale_turn_restrictions();
+
+        let mut changed = Vec::new();
+        for x in &stale {
+            let road = self.roads.get_mut(&x.from).unwrap();
+            if let Some(via) = x.via {
+                road.complicated_turn_restrictions
+                    .retain(|pair| *pair != (via, x.to));
+            } else {
+                road.turn_restrictions.retain(|(_, to)| *to != x.to);
+                road.turn_restriction_relations.remove(&x.to);
+            }
+            changed.extend(road.endpoints());
+        }
+
+        for x in &stale {
+            warn!("Removing stale turn restriction: {:?}", x);
+        }
+        self.stale_turn_restrictions.extend(stale);
+
+        self.batch_edits(|streets| {
+            for i in changed {
+                streets.intersection_roads_changed(i, false);
+            }
+        });
+    }
+
+    /// True if two existing roads share at least one intersection
+    fn roads_meet(&self, r1: RoadID, r2: RoadID) -> bool {
+        let (Some(road1), Some(road2)) = (self.roads.get(&r1), self.roads.get(&r2)) else {
+            return false;
+        };
+        CommonEndpoint::new((road1.src_i, road1.dst_i), (road2.src_i, road2.dst_i))
+            != CommonEndpoint::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::{IntersectionID, IntersectionKind};
+
+    // A straight road split into three, with a side road at both ends of the short middle one.
+    // Returns the straight roads, the side road meeting the first one, and where they meet.
+    fn network() -> (StreetNetwork, [RoadID; 3], RoadID, IntersectionID) {
+        let mut streets = StreetNetwork::blank();
+        // Anything but a map edge, so roads can be collapsed
+        let i0 = add_intersection(&mut streets, 0.0, 0.0, IntersectionKind::Intersection);
+        let i1 = add_intersection(&mut streets, 100.0, 0.0, IntersectionKind::Intersection);
+        let i2 = add_intersection(&mut streets, 110.0, 0.0, IntersectionKind::Intersection);
+        let i3 = add_intersection(&mut streets, 210.0, 0.0, IntersectionKind::Intersection);
+        let r1 = add_road(&mut streets, i0, i1, &["highway=residential"]);
+        let via = add_road(&mut streets, i1, i2, &["highway=residential"]);
+        let r3 = add_road(&mut streets, i2, i3, &["highway=residential"]);
+        let i4 = add_intersection(&mut streets, 100.0, 100.0, IntersectionKind::Intersection);
+        let side = add_road(&mut streets, i1, i4, &["highway=residential"]);
+        let i5 = add_intersection(&mut streets, 110.0, -100.0, IntersectionKind::Intersection);
+        add_road(&mut streets, i2, i5, &["highway=residential"]);
+        (streets, [r1, via, r3], side, i1.0)
+    }
+
+    #[test]
+    fn test_collapsing_via_road() {
+        let (mut streets, [r1, via, r3], _, i1) = network();
+        streets
+            .roads
+            .get_mut(&r1)
+            .unwrap()
+            .complicated_turn_restrictions
+            .push((via, r3));
+
+        streets.collapse_short_road(via).unwrap();
+        // The restriction applies directly at the merged intersection
+        assert_eq!(
+            vec![(RestrictionType::BanTurns, r3)],
+            streets.roads[&r1].turn_restrictions
+        );
+        assert!(streets.roads[&r1].complicated_turn_restrictions.is_empty());
+        assert!(!streets.intersections[&i1].movements.contains(&(r1, r3)));
+        assert!(streets.find_stale_turn_restrictions().is_empty());
+    }
+
+    #[test]
+    fn test_removing_via_road() {
+        let (mut streets, [r1, via, r3], side, i1) = network();
+        {
+            let road = streets.roads.get_mut(&r1).unwrap();
+            road.turn_restrictions
+                .push((RestrictionType::BanTurns, via));
+            road.complicated_turn_restrictions.push((via, r3));
+            road.complicated_turn_restrictions.push((via, side));
+        }
+
+        // Removing the 'via' road without collapsing it doesn't ban turning onto the side road,
+        // even though it meets the 'from' road
+        streets.remove_road(via);
+        assert!(streets.intersections[&i1].movements.contains(&(r1, side)));
+        assert_eq!(
+            vec![
+                StaleReason::MissingTo,
+                StaleReason::MissingVia,
+                StaleReason::MissingVia,
+            ],
+            streets
+                .find_stale_turn_restrictions()
+                .into_iter()
+                .map(|x| x.problem)
+                .collect::<Vec<_>>()
+        );
+
+        streets.remove_stale_turn_restrictions();
+        assert!(streets.roads[&r1].turn_restrictions.is_empty());
+        assert!(streets.roads[&r1].complicated_turn_restrictions.is_empty());
+        assert_eq!(3, streets.stale_turn_restrictions.len());
+        assert!(streets.find_stale_turn_restrictions().is_empty());
+    }
+}
@@ -1,6 +1,9 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use abstutil::Tags;
 
@@ -48,3 +51,123 @@ impl NamePerLanguage {
         self.0.keys().flatten().collect()
     }
 }
+
+/// A string that's stored once, no matter how many copies exist. Many roads share the same
+/// highway type and name, so on city-scale imports, this saves lots of memory. The shared table
+/// is global, but strings no longer used anywhere are periodically dropped from it, so long-lived
+/// sessions importing many networks don't grow without bound.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InternedString(Arc<str>);
+
+static INTERNED_STRINGS: Mutex<InternTable> = Mutex::new(InternTable {
+    strings: BTreeSet::new(),
+    prune_at: MIN_PRUNE_AT,
+});
+
+/// Don't bother pruning small tables
+const MIN_PRUNE_AT: usize = 1024;
+
+struct InternTable {
+    strings: BTreeSet<Arc<str>>,
+    /// Drop unused strings once the table grows to this size
+    prune_at: usize,
+}
+
+impl InternedString {
+    pub fn new(value: &str) -> Self {
+        let mut table = INTERNED_STRINGS.lock().unwrap();
+        if let Some(existing) = table.strings.get(value) {
+            return Self(existing.clone());
+        }
+        if table.strings.len() >= table.prune_at {
+            // Strings only referenced by the table itself aren't used anymore
+            table.strings.retain(|x| Arc::strong_count(x) > 1);
+            table.prune_at = (2 * table.strings.len()).max(MIN_PRUNE_AT);
+        }
+        let value: Arc<str> = Arc::from(value);
+        table.strings.insert(value.clone());
+        Self(value)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for InternedString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for InternedString {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl PartialEq<str> for InternedString {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for InternedString {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl fmt::Display for InternedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Debug for InternedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl Serialize for InternedString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for InternedString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Ok(Self::new(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interned_strings_share_storage() {
+        let a = InternedString::new("residential");
+        let b = InternedString::from(String::from("residential").as_str());
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!(a, "residential");
+        assert_ne!(a, InternedString::new("primary"));
+    }
+
+    #[test]
+    fn test_unused_interned_strings_are_dropped() {
+        let unused = Arc::downgrade(&InternedString::new("test_unused_interned_strings").0);
+        // Other tests share the table, so keep interning until it's pruned
+        for i in 0..100_000 {
+            if unused.upgrade().is_none() {
+                break;
+            }
+            InternedString::new(&format!("test_unused_interned_strings {i}"));
+        }
+        assert!(unused.upgrade().is_none());
+    }
+}
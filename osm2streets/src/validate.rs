@@ -1,52 +1,319 @@
-use crate::StreetNetwork;
+use std::collections::BTreeSet;
+use std::fmt;
+
+use anyhow::Result;
+use geom::EPSILON_DIST;
+use serde::Serialize;
+
+use crate::{IntersectionID, RoadID, StreetNetwork};
+
+/// Something wrong with the structure of a `StreetNetwork`. These indicate bugs in osm2streets,
+/// not problems with the input data.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum InvariantViolation {
+    /// A road's endpoint doesn't exist, or doesn't list the road
+    DanglingRoad {
+        road: RoadID,
+        intersection: IntersectionID,
+    },
+    /// An intersection lists a road that doesn't exist, or doesn't point to the intersection
+    DanglingIntersectionRoad {
+        intersection: IntersectionID,
+        road: RoadID,
+    },
+    NoLanes(RoadID),
+    /// The road's center line has no length, so neither do any of its lanes
+    ZeroLengthRoad(RoadID),
+    NoRoads(IntersectionID),
+    /// A movement refers to a road not connected to the intersection
+    BadMovement {
+        intersection: IntersectionID,
+        from: RoadID,
+        to: RoadID,
+    },
+    /// The intersection's roads aren't in clockwise order
+    UnsortedRoads(IntersectionID),
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DanglingRoad { road, intersection } => {
+                write!(f, "{road} points to {intersection}, which doesn't list it")
+            }
+            Self::DanglingIntersectionRoad { intersection, road } => {
+                write!(
+                    f,
+                    "{intersection} contains {road}, which doesn't point to it"
+                )
+            }
+            Self::NoLanes(r) => write!(f, "{r} has no lanes"),
+            Self::ZeroLengthRoad(r) => write!(f, "{r} has no length"),
+            Self::NoRoads(i) => write!(f, "{i} has no roads"),
+            Self::BadMovement {
+                intersection,
+                from,
+                to,
+            } => write!(
+                f,
+                "{intersection} has a movement from {from} to {to}, but doesn't contain both"
+            ),
+            Self::UnsortedRoads(i) => write!(f, "{i} doesn't list its roads clockwise"),
+        }
+    }
+}
 
 impl StreetNetwork {
-    /// Validates various things are true about the StreetNetwork, panicking if not.
+    /// Validates various things are true about the StreetNetwork, panicking if not. Roads out of
+    /// clockwise order are only reported by `find_invariant_violations`, because some edits leave
+    /// sorting to later steps. So are roads with no length, which OSM data can produce.
     pub fn check_invariants(&self) {
-        for r in self.roads.values() {
-            for i in r.endpoints() {
-                let i = &self.intersections[&i];
-                assert!(
-                    i.roads.contains(&r.id),
-                    "{} doesn't list {}",
-                    i.describe(),
-                    r.describe()
-                );
-            }
-            assert!(
-                !r.lane_specs_ltr.is_empty(),
-                "{} has no lanes",
-                r.describe()
-            );
-        }
-
-        for i in self.intersections.values() {
-            assert!(!i.roads.is_empty(), "{} has no roads", i.describe());
-
-            for r in &i.roads {
-                let r = &self.roads[r];
-                assert!(
-                    r.src_i == i.id || r.dst_i == i.id,
-                    "{} contains {}, which doesn't point to it",
-                    i.describe(),
-                    r.describe()
-                );
-            }
-
-            for (r1, r2) in &i.movements {
-                assert!(
-                    i.roads.contains(r1),
-                    "{} has a movement for the wrong road {}",
-                    i.describe(),
-                    r1
-                );
-                assert!(
-                    i.roads.contains(r2),
-                    "{} has a movement for the wrong road {}",
-                    i.describe(),
-                    r2
-                );
+        for violation in self.find_invariant_violations() {
+            if !matches!(
+                violation,
+                InvariantViolation::UnsortedRoads(_) | InvariantViolation::ZeroLengthRoad(_)
+            ) {
+                panic!("{violation}");
+            }
+        }
+    }
+
+    /// Checks the entire network, returning every problem found.
+    pub fn find_invariant_violations(&self) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+        for r in self.roads.keys() {
+            self.check_road(*r, &mut violations);
+        }
+        for i in self.intersections.keys() {
+            self.check_intersection(*i, &mut violations);
+        }
+        violations
+    }
+
+    /// Only checks some intersections and every road connected to them. Use this after editing one
+    /// part of a large network.
+    pub fn find_invariant_violations_near(
+        &self,
+        intersections: &BTreeSet<IntersectionID>,
+    ) -> Vec<InvariantViolation> {
+        let mut violations = Vec::new();
+        let mut roads = BTreeSet::new();
+        for i in intersections {
+            if let Some(intersection) = self.intersections.get(i) {
+                roads.extend(intersection.roads.iter().cloned());
+            }
+        }
+        for r in roads {
+            if self.roads.contains_key(&r) {
+                self.check_road(r, &mut violations);
+            }
+        }
+        for i in intersections {
+            if self.intersections.contains_key(i) {
+                self.check_intersection(*i, &mut violations);
+            }
+        }
+        violations
+    }
+
+    /// Returns `find_invariant_violations` as a JSON array, each with a `description`.
+    pub fn to_invariant_violations_json(&self) -> Result<String> {
+        let list: Vec<serde_json::Value> = self
+            .find_invariant_violations()
+            .into_iter()
+            .map(|violation| {
+                serde_json::json!({
+                    "description": violation.to_string(),
+                    "violation": violation,
+                })
+            })
+            .collect();
+        Ok(serde_json::to_string(&list)?)
+    }
+
+    fn check_road(&self, r: RoadID, violations: &mut Vec<InvariantViolation>) {
+        let road = &self.roads[&r];
+        for i in road.endpoints() {
+            if !self
+                .intersections
+                .get(&i)
+                .map(|i| i.roads.contains(&r))
+                .unwrap_or(false)
+            {
+                violations.push(InvariantViolation::DanglingRoad {
+                    road: r,
+                    intersection: i,
+                });
             }
         }
+        if road.lane_specs_ltr.is_empty() {
+            violations.push(InvariantViolation::NoLanes(r));
+        }
+        if road.center_line.length() <= EPSILON_DIST {
+            violations.push(InvariantViolation::ZeroLengthRoad(r));
+        }
+    }
+
+    fn check_intersection(&self, i: IntersectionID, violations: &mut Vec<InvariantViolation>) {
+        let intersection = &self.intersections[&i];
+        if intersection.roads.is_empty() {
+            violations.push(InvariantViolation::NoRoads(i));
+        }
+
+        let mut all_roads_ok = true;
+        for r in &intersection.roads {
+            if !self
+                .roads
+                .get(r)
+                .map(|road| road.src_i == i || road.dst_i == i)
+                .unwrap_or(false)
+            {
+                violations.push(InvariantViolation::DanglingIntersectionRoad {
+                    intersection: i,
+                    road: *r,
+                });
+                all_roads_ok = false;
+            }
+        }
+
+        for (from, to) in &intersection.movements {
+            if !intersection.roads.contains(from) || !intersection.roads.contains(to) {
+                violations.push(InvariantViolation::BadMovement {
+                    intersection: i,
+                    from: *from,
+                    to: *to,
+                });
+            }
+        }
+
+        // Sorting needs every road to exist. The order may start with any road.
+        if all_roads_ok && intersection.roads.len() > 2 {
+            let expected = self.clockwise_roads(i);
+            let start = expected
+                .iter()
+                .position(|r| *r == intersection.roads[0])
+                .unwrap();
+            if !expected
+                .iter()
+                .cycle()
+                .skip(start)
+                .take(expected.len())
+                .eq(intersection.roads.iter())
+            {
+                violations.push(InvariantViolation::UnsortedRoads(i));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::PolyLine;
+
+    use super::*;
+    use crate::test_utils::{add_intersection, add_road};
+    use crate::IntersectionKind;
+
+    /// Two separate roads, each between two intersections
+    fn network() -> (StreetNetwork, Vec<IntersectionID>, Vec<RoadID>) {
+        let mut streets = StreetNetwork::blank();
+        let ends: Vec<_> = [(0.0, 0.0), (100.0, 0.0), (0.0, 500.0), (100.0, 500.0)]
+            .into_iter()
+            .map(|(x, y)| add_intersection(&mut streets, x, y, IntersectionKind::MapEdge))
+            .collect();
+        let intersections = ends.iter().map(|(i, _)| *i).collect();
+        let r1 = add_road(&mut streets, ends[0], ends[1], &["highway=residential"]);
+        let r2 = add_road(&mut streets, ends[2], ends[3], &["highway=residential"]);
+        (streets, intersections, vec![r1, r2])
+    }
+
+    #[test]
+    fn test_find_invariant_violations() {
+        let (mut streets, intersections, roads) = network();
+        assert_eq!(
+            Vec::<InvariantViolation>::new(),
+            streets.find_invariant_violations()
+        );
+
+        // Break each road differently
+        streets
+            .roads
+            .get_mut(&roads[0])
+            .unwrap()
+            .lane_specs_ltr
+            .clear();
+        streets
+            .intersections
+            .get_mut(&intersections[3])
+            .unwrap()
+            .movements
+            .push((roads[1], roads[0]));
+        assert_eq!(
+            vec![
+                InvariantViolation::NoLanes(roads[0]),
+                InvariantViolation::BadMovement {
+                    intersection: intersections[3],
+                    from: roads[1],
+                    to: roads[0],
+                },
+            ],
+            streets.find_invariant_violations()
+        );
+
+        // Only problems around the first road are found near it
+        let near: BTreeSet<IntersectionID> = [intersections[0]].into_iter().collect();
+        assert_eq!(
+            vec![InvariantViolation::NoLanes(roads[0])],
+            streets.find_invariant_violations_near(&near)
+        );
+        let near: BTreeSet<IntersectionID> = [intersections[2]].into_iter().collect();
+        assert_eq!(
+            Vec::<InvariantViolation>::new(),
+            streets.find_invariant_violations_near(&near)
+        );
+    }
+
+    #[test]
+    fn test_dangling_roads() {
+        let (mut streets, intersections, roads) = network();
+        streets
+            .intersections
+            .get_mut(&intersections[1])
+            .unwrap()
+            .roads
+            .clear();
+        streets
+            .intersections
+            .get_mut(&intersections[2])
+            .unwrap()
+            .roads
+            .push(roads[0]);
+        assert_eq!(
+            vec![
+                InvariantViolation::DanglingRoad {
+                    road: roads[0],
+                    intersection: intersections[1],
+                },
+                InvariantViolation::NoRoads(intersections[1]),
+                InvariantViolation::DanglingIntersectionRoad {
+                    intersection: intersections[2],
+                    road: roads[0],
+                },
+            ],
+            streets.find_invariant_violations()
+        );
+    }
+
+    #[test]
+    fn test_zero_length_road_is_only_reported() {
+        let (mut streets, _, roads) = network();
+        let road = streets.roads.get_mut(&roads[0]).unwrap();
+        let pt = road.center_line.first_pt();
+        road.center_line = PolyLine::unchecked_new(vec![pt, pt]);
+        assert_eq!(
+            vec![InvariantViolation::ZeroLengthRoad(roads[0])],
+            streets.find_invariant_violations()
+        );
+        streets.check_invariants();
     }
 }
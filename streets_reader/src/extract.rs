@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use abstutil::Tags;
-use geom::{HashablePt2D, Pt2D};
+use geom::{Distance, HashablePt2D, Polygon, Pt2D, Ring};
 use osm2streets::osm::{NodeID, OsmID, RelationID, WayID};
-use osm2streets::{osm, Crossing, CrossingKind, Direction, RestrictionType};
+use osm2streets::{
+    osm, Crossing, CrossingKind, Direction, IndoorPaths, KerbKind, RestrictionType,
+    TrafficControlKind,
+};
 
 use crate::osm_reader::{Node, Relation, Way};
 use crate::MapConfig;
@@ -14,8 +17,10 @@ pub struct OsmExtract {
     /// partly pre-split.
     pub roads: Vec<(WayID, Vec<Pt2D>, Tags)>,
     pub osm_node_ids: HashMap<HashablePt2D, NodeID>,
+    /// The tags of every tagged node, for intersections to keep
+    pub node_tags: HashMap<NodeID, Tags>,
     /// (ID, restriction type, from way ID, via node ID, to way ID)
-    pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID)>,
+    pub simple_turn_restrictions: Vec<(RelationID, RestrictionType, WayID, NodeID, WayID)>,
     /// (relation ID, from way ID, via way ID, to way ID)
     pub complicated_turn_restrictions: Vec<(RelationID, WayID, WayID, WayID)>,
 
@@ -23,6 +28,84 @@ pub struct OsmExtract {
     pub traffic_signals: HashMap<HashablePt2D, Option<Direction>>,
     pub cycleway_stop_lines: Vec<(HashablePt2D, Option<Direction>)>,
     pub crossings: HashMap<HashablePt2D, Crossing>,
+    /// Bus stops mapped as nodes of a road
+    pub bus_stops: Vec<HashablePt2D>,
+    /// Turning circles at the end of dead-ends, with their diameter if tagged
+    pub turning_circles: HashMap<HashablePt2D, Option<Distance>>,
+    pub kerbs: Vec<(Pt2D, KerbKind)>,
+    /// Traffic islands mapped as closed ways
+    pub traffic_islands: Vec<Polygon>,
+    /// Other closed ways tagged `area:highway`
+    pub highway_areas: Vec<Polygon>,
+    /// Paths inside buildings, with `IndoorPaths::Separate`
+    pub indoor_paths: Vec<(WayID, Vec<Pt2D>)>,
+    /// `route=ferry` ways
+    pub ferries: Vec<(WayID, Vec<Pt2D>, Tags)>,
+    /// Waterways and railways splitting blocks, with `MapConfig::block_barriers`
+    pub block_barriers: Vec<Vec<Pt2D>>,
+    /// Signs, signals, and crossings, with an optional direction they apply to
+    pub traffic_control_devices: Vec<(NodeID, HashablePt2D, TrafficControlKind, Option<Direction>)>,
+
+    /// Everything that was ignored while extracting
+    pub skipped: SkipReport,
+}
+
+/// Summarizes the OSM objects that didn't become part of the `StreetNetwork`, grouped by the
+/// reason they were skipped, so users can audit whether something they care about was dropped.
+#[derive(Clone, Debug, Default)]
+pub struct SkipReport {
+    pub reasons: BTreeMap<String, SkippedObjects>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SkippedObjects {
+    pub count: usize,
+    /// Up to `SkipReport::MAX_SAMPLES` of the skipped objects
+    pub samples: Vec<OsmID>,
+}
+
+impl SkipReport {
+    pub const MAX_SAMPLES: usize = 5;
+
+    pub fn record<S: Into<String>>(&mut self, reason: S, id: OsmID) {
+        let entry = self.reasons.entry(reason.into()).or_default();
+        entry.count += 1;
+        if entry.samples.len() < Self::MAX_SAMPLES {
+            entry.samples.push(id);
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        self.reasons.values().map(|x| x.count).sum()
+    }
+
+    /// Merges another report into this one
+    pub fn extend(&mut self, other: SkipReport) {
+        for (reason, objects) in other.reasons {
+            let entry = self.reasons.entry(reason).or_default();
+            entry.count += objects.count;
+            for id in objects.samples {
+                if entry.samples.len() < Self::MAX_SAMPLES {
+                    entry.samples.push(id);
+                }
+            }
+        }
+    }
+
+    /// Produces a JSON object keyed by reason, with `count` and `samples` for each.
+    pub fn to_json(&self) -> String {
+        let mut reasons = serde_json::Map::new();
+        for (reason, objects) in &self.reasons {
+            reasons.insert(
+                reason.clone(),
+                serde_json::json!({
+                    "count": objects.count,
+                    "samples": objects.samples.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+                }),
+            );
+        }
+        serde_json::Value::Object(reasons).to_string()
+    }
 }
 
 impl OsmExtract {
@@ -30,28 +113,82 @@ impl OsmExtract {
         Self {
             roads: Vec::new(),
             osm_node_ids: HashMap::new(),
+            node_tags: HashMap::new(),
             simple_turn_restrictions: Vec::new(),
             complicated_turn_restrictions: Vec::new(),
 
             traffic_signals: HashMap::new(),
             cycleway_stop_lines: Vec::new(),
             crossings: HashMap::new(),
+            bus_stops: Vec::new(),
+            turning_circles: HashMap::new(),
+            kerbs: Vec::new(),
+            traffic_islands: Vec::new(),
+            highway_areas: Vec::new(),
+            indoor_paths: Vec::new(),
+            ferries: Vec::new(),
+            block_barriers: Vec::new(),
+            traffic_control_devices: Vec::new(),
+
+            skipped: SkipReport::default(),
         }
     }
 
     pub fn handle_node(&mut self, id: NodeID, node: &Node) {
         self.osm_node_ids.insert(node.pt.to_hashable(), id);
+        if !node.tags.inner().is_empty() {
+            self.node_tags.insert(id, node.tags.clone());
+        }
 
         if node.tags.is(osm::HIGHWAY, "traffic_signals") {
             let dir = parse_dir(node.tags.get("traffic_signals:direction"));
             self.traffic_signals.insert(node.pt.to_hashable(), dir);
         }
 
+        let device = match node.tags.get(osm::HIGHWAY).map(|x| x.as_str()) {
+            Some("traffic_signals") => Some((
+                TrafficControlKind::TrafficSignals,
+                parse_dir(node.tags.get("traffic_signals:direction")),
+            )),
+            Some("stop") => Some((
+                TrafficControlKind::Stop,
+                parse_dir(node.tags.get("direction")),
+            )),
+            Some("give_way") => Some((
+                TrafficControlKind::GiveWay,
+                parse_dir(node.tags.get("direction")),
+            )),
+            Some("crossing") => Some((TrafficControlKind::Crossing, None)),
+            _ => None,
+        };
+        if let Some((kind, dir)) = device {
+            self.traffic_control_devices
+                .push((id, node.pt.to_hashable(), kind, dir));
+        }
+
         if node.tags.is("cycleway", "asl") {
             let dir = parse_dir(node.tags.get("direction"));
             self.cycleway_stop_lines.push((node.pt.to_hashable(), dir));
         }
 
+        if node.tags.is(osm::HIGHWAY, "bus_stop")
+            || (node.tags.is("public_transport", "stop_position") && node.tags.is("bus", "yes"))
+        {
+            self.bus_stops.push(node.pt.to_hashable());
+        }
+
+        if node.tags.is(osm::HIGHWAY, "turning_circle") {
+            let diameter = node
+                .tags
+                .get("diameter")
+                .and_then(|x| osm2streets::units::parse_distance(x));
+            self.turning_circles.insert(node.pt.to_hashable(), diameter);
+        }
+
+        if let Some(kind) = KerbKind::parse(&node.tags) {
+            self.kerbs.push((node.pt, kind));
+        }
+
         if node.tags.is("highway", "crossing") || node.tags.is("railway", "crossing") {
             let kind = match node.tags.get("crossing").map(|x| x.as_str()) {
                 Some("traffic_signals") => CrossingKind::Signalized,
@@ -74,7 +211,39 @@ impl OsmExtract {
     pub fn handle_way(&mut self, id: WayID, way: &Way, cfg: &MapConfig) -> bool {
         let tags = &way.tags;
 
+        if tags.is("area:highway", "traffic_island")
+            || (tags.is("traffic_calming", "island")
+                && !tags.contains_key(osm::HIGHWAY)
+                && way.nodes.first() == way.nodes.last())
+        {
+            match Ring::new(way.pts.clone()) {
+                Ok(ring) => self.traffic_islands.push(ring.into_polygon()),
+                Err(_) => self
+                    .skipped
+                    .record("traffic island isn't a valid area", OsmID::Way(id)),
+            }
+            return false;
+        }
+        if tags.contains_key("area:highway") {
+            match Ring::new(way.pts.clone()) {
+                Ok(ring) => self.highway_areas.push(ring.into_polygon()),
+                Err(_) => self
+                    .skipped
+                    .record("area:highway isn't a valid area", OsmID::Way(id)),
+            }
+            return false;
+        }
+
+        // Ferries aren't roads, but keep them to connect the network across water
+        if tags.is("route", "ferry") {
+            self.ferries.push((id, way.pts.clone(), tags.clone()));
+            return false;
+        }
+
         if tags.is("area", "yes") {
+            if tags.has_any(vec![osm::HIGHWAY, "railway"]) {
+                self.skipped.record("way is an area", OsmID::Way(id));
+            }
             return false;
         }
 
@@ -87,47 +256,63 @@ impl OsmExtract {
             self.roads.push((id, way.pts.clone(), tags.clone()));
             return true;
         }
+        if cfg.block_barriers && is_block_barrier(tags) {
+            self.block_barriers.push(way.pts.clone());
+            return false;
+        }
 
-        let highway = if let Some(x) = tags.get(osm::HIGHWAY) {
-            if x == "construction" {
-                // What exactly is under construction?
-                if let Some(x) = tags.get("construction") {
-                    x
-                } else {
+        // Disused roads don't have a highway tag. If they're wanted, pretend they do.
+        if !tags.contains_key(osm::HIGHWAY) {
+            if let Some(highway) = tags.get("disused:highway") {
+                if !cfg.highway_filter.allows("disused") {
+                    self.skipped
+                        .record(filtered_reason(cfg, "disused"), OsmID::Way(id));
                     return false;
                 }
-            } else {
+                if !cfg.highway_filter.allows(highway) {
+                    self.skipped
+                        .record(filtered_reason(cfg, highway), OsmID::Way(id));
+                    return false;
+                }
+                let mut tags = tags.clone();
+                tags.insert(osm::HIGHWAY, highway.clone());
+                self.roads.push((id, way.pts.clone(), tags));
+                return true;
+            }
+        }
+
+        let Some(highway) = tags.get(osm::HIGHWAY) else {
+            if let Some(railway) = tags.get("railway") {
+                self.skipped
+                    .record(format!("unhandled railway={railway}"), OsmID::Way(id));
+            }
+            return false;
+        };
+        // Roads under construction or proposed are filtered both by their lifecycle and by what
+        // they'll eventually be
+        let highway = if highway == "construction" || highway == "proposed" {
+            if !cfg.highway_filter.allows(highway) {
+                self.skipped
+                    .record(filtered_reason(cfg, highway), OsmID::Way(id));
+                return false;
+            }
+            // What exactly is under construction?
+            if let Some(x) = tags.get(highway) {
                 x
+            } else {
+                self.skipped.record(
+                    format!("highway={highway} without {highway}"),
+                    OsmID::Way(id),
+                );
+                return false;
             }
         } else {
-            return false;
+            highway
         };
 
-        if !vec![
-            "busway",
-            "cycleway",
-            "footway",
-            "living_street",
-            "motorway",
-            "motorway_link",
-            "path",
-            "pedestrian",
-            "primary",
-            "primary_link",
-            "residential",
-            "secondary",
-            "secondary_link",
-            "service",
-            "steps",
-            "tertiary",
-            "tertiary_link",
-            "track",
-            "trunk",
-            "trunk_link",
-            "unclassified",
-        ]
-        .contains(&highway.as_ref())
-        {
+        if !cfg.highway_filter.allows(highway) {
+            self.skipped
+                .record(filtered_reason(cfg, highway), OsmID::Way(id));
             return false;
         }
 
@@ -138,11 +323,27 @@ impl OsmExtract {
             if tags.is(osm::HIGHWAY, "footway")
                 && tags.is_any("footway", vec!["crossing", "sidewalk"])
             {
+                self.skipped.record(
+                    "separate sidewalk or crossing, with inferred_sidewalks",
+                    OsmID::Way(id),
+                );
                 return false;
             }
         }
 
+        if cfg.indoor_paths != IndoorPaths::Include && is_indoor_path(tags) {
+            if cfg.indoor_paths == IndoorPaths::Separate {
+                self.indoor_paths.push((id, way.pts.clone()));
+                self.skipped
+                    .record("indoor path, kept separately", OsmID::Way(id));
+            } else {
+                self.skipped.record("indoor path", OsmID::Way(id));
+            }
+            return false;
+        }
+
         if highway == "service" && tags.is("golf", "cartpath") {
+            self.skipped.record("golf cartpath", OsmID::Way(id));
             return false;
         }
 
@@ -153,6 +354,15 @@ impl OsmExtract {
     // Returns true if the relation was used (turn restrictions only)
     pub fn handle_relation(&mut self, id: RelationID, rel: &Relation) -> bool {
         if !rel.tags.is("type", "restriction") {
+            let kind = rel
+                .tags
+                .get("type")
+                .map(|x| x.as_str())
+                .unwrap_or("missing");
+            self.skipped.record(
+                format!("unhandled relation type={kind}"),
+                OsmID::Relation(id),
+            );
             return false;
         }
 
@@ -184,13 +394,17 @@ impl OsmExtract {
         if let Some(restriction) = rel.tags.get("restriction") {
             if let Some(rt) = RestrictionType::new(restriction) {
                 if let (Some(from), Some(via), Some(to)) = (from_way_id, via_node_id, to_way_id) {
-                    self.simple_turn_restrictions.push((rt, from, via, to));
+                    self.simple_turn_restrictions.push((id, rt, from, via, to));
                 } else if let (Some(from), Some(via), Some(to)) =
                     (from_way_id, via_way_id, to_way_id)
                 {
                     if rt == RestrictionType::BanTurns {
                         self.complicated_turn_restrictions.push((id, from, via, to));
                     } else {
+                        self.skipped.record(
+                            "unsupported turn restriction via a way",
+                            OsmID::Relation(id),
+                        );
                         warn!(
                             "Weird complicated turn restriction \"{}\" from {} to {} via {}: \
                              {}",
@@ -198,6 +412,11 @@ impl OsmExtract {
                         );
                     }
                 }
+            } else {
+                self.skipped.record(
+                    format!("unhandled restriction={restriction}"),
+                    OsmID::Relation(id),
+                );
             }
         }
 
@@ -205,6 +424,14 @@ impl OsmExtract {
     }
 }
 
+fn filtered_reason(cfg: &MapConfig, highway: &str) -> String {
+    if cfg.highway_filter.exclude.contains(highway) {
+        format!("excluded highway={highway}")
+    } else {
+        format!("unhandled highway={highway}")
+    }
+}
+
 fn parse_dir(x: Option<&String>) -> Option<Direction> {
     match x.map(|x| x.as_str()) {
         Some("forward") => Some(Direction::Forward),
@@ -212,3 +439,63 @@ fn parse_dir(x: Option<&String>) -> Option<Direction> {
         _ => None,
     }
 }
+
+/// See `IndoorPaths`. Only paths count; roads through buildings, like in parking garages, stay.
+fn is_indoor_path(tags: &Tags) -> bool {
+    if !tags.is_any(
+        osm::HIGHWAY,
+        vec![
+            "corridor",
+            "cycleway",
+            "footway",
+            "path",
+            "pedestrian",
+            "steps",
+        ],
+    ) {
+        return false;
+    }
+    (tags.contains_key("indoor") && !tags.is("indoor", "no"))
+        || tags.is(osm::HIGHWAY, "corridor")
+        || (tags.contains_key("level") && tags.is("covered", "yes"))
+}
+
+/// Rivers and railways physically divide blocks, unless they're underground
+fn is_block_barrier(tags: &Tags) -> bool {
+    if tags.contains_key("tunnel") && !tags.is("tunnel", "no") {
+        return false;
+    }
+    tags.is_any("waterway", vec!["river", "canal", "stream"])
+        || tags.is_any("railway", vec!["rail", "narrow_gauge", "subway"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_indoor_path() {
+        let mut tags = Tags::empty();
+        tags.insert("highway", "footway");
+        tags.insert("level", "1");
+        assert!(!is_indoor_path(&tags));
+        tags.insert("covered", "yes");
+        assert!(is_indoor_path(&tags));
+
+        tags.insert("highway", "service");
+        assert!(!is_indoor_path(&tags));
+    }
+
+    #[test]
+    fn test_is_block_barrier() {
+        let mut tags = Tags::empty();
+        tags.insert("waterway", "canal");
+        assert!(is_block_barrier(&tags));
+        tags.insert("tunnel", "culvert");
+        assert!(!is_block_barrier(&tags));
+
+        let mut tags = Tags::empty();
+        tags.insert("railway", "tram");
+        assert!(!is_block_barrier(&tags));
+    }
+}
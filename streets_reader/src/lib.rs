@@ -9,40 +9,120 @@ use country_boundaries::{CountryBoundaries, BOUNDARIES_ODBL_60X30};
 use geom::{GPSBounds, LonLat, Ring};
 
 use muv_osm::lanes::highway::driving_side;
-use osm2streets::{DrivingSide, MapConfig, StreetNetwork};
+use osm2streets::osm::OsmID;
+use osm2streets::{DrivingSide, MapConfig, Progress, StreetNetwork};
 use osm_reader::Document;
 
-pub use self::extract::OsmExtract;
+pub use self::extract::{OsmExtract, SkipReport};
 
 // TODO Clean up the public API of all of this
 pub mod extract;
 pub mod osm_reader;
 pub mod split_ways;
+pub mod ways;
 
 /// Create a `StreetNetwork` from the contents of an `.osm.xml` or `.pbf` file. If `clip_pts` is
 /// specified, use these as a boundary polygon. (Use `LonLat::read_geojson_polygon` or similar to
 /// produce these.)
 ///
 /// You probably want to do `StreetNetwork::apply_transformations` on the result to get a useful
-/// result.
+/// result. `Document::skipped` summarizes OSM objects that were ignored.
 pub fn osm_to_street_network(
     input_bytes: &[u8],
     clip_pts: Option<Vec<LonLat>>,
     cfg: MapConfig,
     timer: &mut Timer,
 ) -> Result<(StreetNetwork, Document)> {
-    let mut streets = StreetNetwork::blank();
-    // Note that DrivingSide is still incorrect. It'll be set in extract_osm, before Road::new
-    // happens in split_ways.
-    streets.config = cfg;
+    osm_to_street_network_with_progress(input_bytes, clip_pts, cfg, timer, &mut Progress::none())
+}
+
+/// Like `osm_to_street_network`, but reports progress through each stage of importing, and stops
+/// early with an error if the progress is cancelled.
+pub fn osm_to_street_network_with_progress(
+    input_bytes: &[u8],
+    clip_pts: Option<Vec<LonLat>>,
+    cfg: MapConfig,
+    timer: &mut Timer,
+    progress: &mut Progress,
+) -> Result<(StreetNetwork, Document)> {
+    let mut import = ChunkedImport::read(input_bytes, clip_pts, cfg, timer, progress)?;
+    import.extract(timer, progress)?;
+    import.split(timer, progress)?;
+    import.finish()
+}
 
-    let (extract, doc) = extract_osm(&mut streets, input_bytes, clip_pts, timer)?;
-    split_ways::split_up_roads(&mut streets, extract, timer);
+/// Imports in separate stages, so a caller can do something else in between, like a web worker
+/// yielding to the event loop. Call `read`, `extract`, `split`, and `finish` in order.
+pub struct ChunkedImport {
+    streets: StreetNetwork,
+    doc: Document,
+    extract: Option<OsmExtract>,
+    split: bool,
+}
 
-    // Cul-de-sacs aren't supported yet.
-    streets.retain_roads(|r| r.src_i != r.dst_i);
+impl ChunkedImport {
+    /// Parses the input, clips it, and detects the country.
+    pub fn read(
+        input_bytes: &[u8],
+        clip_pts: Option<Vec<LonLat>>,
+        cfg: MapConfig,
+        timer: &mut Timer,
+        progress: &mut Progress,
+    ) -> Result<Self> {
+        let mut streets = StreetNetwork::blank();
+        // Note that DrivingSide is still incorrect. It'll be set in read_osm, before Road::new
+        // happens in split_ways.
+        streets.config = cfg;
+        let doc = read_osm(&mut streets, input_bytes, clip_pts, timer, progress)?;
+        Ok(Self {
+            streets,
+            doc,
+            extract: None,
+            split: false,
+        })
+    }
 
-    Ok((streets, doc))
+    /// Processes every OSM node, way, and relation.
+    pub fn extract(&mut self, timer: &mut Timer, progress: &mut Progress) -> Result<()> {
+        if self.extract.is_some() || self.split {
+            bail!("Already extracted");
+        }
+        self.extract = Some(extract_osm(&self.streets, &mut self.doc, timer, progress)?);
+        Ok(())
+    }
+
+    /// Splits OSM ways into roads and intersections.
+    pub fn split(&mut self, timer: &mut Timer, progress: &mut Progress) -> Result<()> {
+        let Some(extract) = self.extract.take() else {
+            bail!("Call extract before split");
+        };
+        progress.check_cancelled("splitting OSM ways")?;
+        progress.report("splitting OSM ways", 0.0);
+        split_ways::split_up_roads(&mut self.streets, extract, timer);
+        progress.report("splitting OSM ways", 1.0);
+
+        // Cul-de-sacs aren't supported yet.
+        for road in self.streets.roads.values() {
+            if road.src_i == road.dst_i {
+                for id in &road.osm_ids {
+                    self.doc
+                        .skipped
+                        .record("loop road (unsupported)", OsmID::Way(*id));
+                }
+            }
+        }
+        self.streets.retain_roads(|r| r.src_i != r.dst_i);
+        self.split = true;
+        Ok(())
+    }
+
+    /// Returns the network, not yet transformed, and the OSM document.
+    pub fn finish(self) -> Result<(StreetNetwork, Document)> {
+        if !self.split {
+            bail!("Call split before finish");
+        }
+        Ok((self.streets, self.doc))
+    }
 }
 
 /// Set up country code and driving side, using an arbitrary point. This must be called after
@@ -76,12 +156,14 @@ pub fn detect_country_code(streets: &mut StreetNetwork) {
     };
 }
 
-fn extract_osm(
+fn read_osm(
     streets: &mut StreetNetwork,
     input_bytes: &[u8],
     clip_pts: Option<Vec<LonLat>>,
     timer: &mut Timer,
-) -> Result<(OsmExtract, Document)> {
+    progress: &mut Progress,
+) -> Result<Document> {
+    progress.report("reading OSM", 0.0);
     let mut doc = Document::read(
         input_bytes,
         clip_pts.as_ref().map(|pts| GPSBounds::from(pts.clone())),
@@ -104,34 +186,55 @@ fn extract_osm(
     }
 
     detect_country_code(streets);
+    Ok(doc)
+}
 
+fn extract_osm(
+    streets: &StreetNetwork,
+    doc: &mut Document,
+    timer: &mut Timer,
+    progress: &mut Progress,
+) -> Result<OsmExtract> {
     let mut out = OsmExtract::new();
 
     timer.start_iter("processing OSM nodes", doc.nodes.len());
-    for (id, node) in &doc.nodes {
+    for (idx, (id, node)) in doc.nodes.iter().enumerate() {
         timer.next();
+        progress.check_cancelled("processing OSM nodes")?;
+        progress.report_iter("processing OSM nodes", idx, doc.nodes.len());
         out.handle_node(*id, node);
     }
 
     timer.start_iter("processing OSM ways", doc.ways.len());
-    for (id, way) in &doc.ways {
+    for (idx, (id, way)) in doc.ways.iter().enumerate() {
         timer.next();
+        progress.check_cancelled("processing OSM ways")?;
+        progress.report_iter("processing OSM ways", idx, doc.ways.len());
         out.handle_way(*id, way, &streets.config);
     }
     timer.start_iter(
         "processing OSM ways split into pieces",
         doc.clipped_copied_ways.len(),
     );
-    for (id, way) in &doc.clipped_copied_ways {
+    for (idx, (id, way)) in doc.clipped_copied_ways.iter().enumerate() {
         timer.next();
+        progress.check_cancelled("processing OSM ways split into pieces")?;
+        progress.report_iter(
+            "processing OSM ways split into pieces",
+            idx,
+            doc.clipped_copied_ways.len(),
+        );
         out.handle_way(*id, way, &streets.config);
     }
 
     timer.start_iter("processing OSM relations", doc.relations.len());
-    for (id, rel) in &doc.relations {
+    for (idx, (id, rel)) in doc.relations.iter().enumerate() {
         timer.next();
+        progress.check_cancelled("processing OSM relations")?;
+        progress.report_iter("processing OSM relations", idx, doc.relations.len());
         out.handle_relation(*id, rel);
     }
+    doc.skipped.extend(std::mem::take(&mut out.skipped));
 
-    Ok((out, doc))
+    Ok(out)
 }
@@ -18,13 +18,26 @@ impl Document {
                 boundary_polygon.contains_pt(node.pt)
             });
 
-        // Remove ways that have no nodes within bounds.
+        // Remove ways that have no nodes within bounds. Remember the ones that could've become
+        // roads, for the SkipReport.
+        let before: Vec<_> = self
+            .ways
+            .iter()
+            .filter(|(_, way)| way.tags.has_any(vec![osm::HIGHWAY, "railway"]))
+            .map(|(id, _)| *id)
+            .collect();
         // TODO If there's a way that geometrically crosses the boundary but only has nodes outside
         // it, this'll remove it. Is that desirable?
         self.ways =
             timer.retain_parallelized("filter ways", std::mem::take(&mut self.ways), |way| {
                 way.nodes.iter().any(|node| self.nodes.contains_key(node))
             });
+        for id in before {
+            if !self.ways.contains_key(&id) {
+                self.skipped
+                    .record("way outside the clip boundary", OsmID::Way(id));
+            }
+        }
 
         // For line-string ways (not areas), clip them to the boundary. way.pts and way.nodes
         // become out-of-sync.
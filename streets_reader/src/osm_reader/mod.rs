@@ -1,10 +1,13 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
-use abstutil::Tags;
-use geom::{GPSBounds, Pt2D};
+use geom::GPSBounds;
 
 pub use self::multipolygon::glue_multipolygon;
-use osm2streets::osm::{NodeID, OsmID, RelationID, WayID};
+use osm2streets::osm::{NodeID, RelationID, WayID};
+pub use osm2streets::osm_index::{Node, Relation, Way};
+use osm2streets::{OsmIndex, StreetNetwork};
+
+use crate::extract::SkipReport;
 
 mod clip;
 mod multipolygon;
@@ -19,26 +22,38 @@ pub struct Document {
 
     /// These ways share a WayID, but each have different pts
     pub clipped_copied_ways: Vec<(WayID, Way)>,
-}
 
-pub struct Node {
-    pub pt: Pt2D,
-    pub tags: Tags,
-    pub version: Option<i32>,
+    /// Objects that were clipped or ignored while extracting a `StreetNetwork`
+    pub skipped: SkipReport,
 }
 
-#[derive(Clone)]
-pub struct Way {
-    // Duplicates geometry, because it's convenient
-    pub nodes: Vec<NodeID>,
-    pub pts: Vec<Pt2D>,
-    pub tags: Tags,
-    pub version: Option<i32>,
+impl Document {
+    /// Keeps only the ways that became roads, freeing everything else. Useful to save memory on
+    /// big imports, when the original OSM data is only needed to look up tags and geometry of
+    /// roads.
+    pub fn into_ways_for_roads(self, streets: &StreetNetwork) -> BTreeMap<WayID, Way> {
+        ways_for_roads(self.ways, streets)
+    }
+
+    /// Keeps the document for queries after import. If `only_roads` is set, keeps only the ways
+    /// that became roads, the nodes that became intersections, and the relations referencing
+    /// those ways, like `into_ways_for_roads`.
+    pub fn into_osm_index(self, streets: &StreetNetwork, only_roads: bool) -> OsmIndex {
+        let index = OsmIndex::new(self.nodes, self.ways, self.relations);
+        if only_roads {
+            index.only_for(streets)
+        } else {
+            index
+        }
+    }
 }
 
-pub struct Relation {
-    pub tags: Tags,
-    /// Role, member
-    pub members: Vec<(String, OsmID)>,
-    pub version: Option<i32>,
+fn ways_for_roads(mut ways: BTreeMap<WayID, Way>, streets: &StreetNetwork) -> BTreeMap<WayID, Way> {
+    let used: BTreeSet<WayID> = streets
+        .roads
+        .values()
+        .flat_map(|r| r.osm_ids.iter().cloned())
+        .collect();
+    ways.retain(|id, _| used.contains(id));
+    ways
 }
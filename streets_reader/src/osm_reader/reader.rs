@@ -8,6 +8,7 @@ use osm_reader::{Element, OsmID};
 use osm2streets::utils::prettyprint_usize;
 
 use super::{Document, Node, Relation, Way};
+use crate::extract::SkipReport;
 
 // References to missing objects are just filtered out.
 // Per https://wiki.openstreetmap.org/wiki/OSM_XML#Certainties_and_Uncertainties, we assume
@@ -31,6 +32,7 @@ impl Document {
             ways: BTreeMap::new(),
             relations: BTreeMap::new(),
             clipped_copied_ways: Vec::new(),
+            skipped: SkipReport::default(),
         };
 
         timer.start("scrape objects");
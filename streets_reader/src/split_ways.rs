@@ -1,10 +1,10 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
 
 use abstutil::Timer;
-use geom::{HashablePt2D, PolyLine, Pt2D};
+use geom::{Distance, HashablePt2D, PolyLine, Pt2D};
 use osm2streets::{
-    Direction, IntersectionControl, IntersectionID, IntersectionKind, Road, RoadID, StreetNetwork,
-    TrafficInterruption,
+    Direction, ExclusionReason, Ferry, IntersectionControl, IntersectionID, IntersectionKind,
+    MapConfig, Road, RoadID, StreetNetwork, TrafficControlDevice, TrafficInterruption,
 };
 
 use super::OsmExtract;
@@ -60,7 +60,15 @@ pub fn split_up_roads(
                         IntersectionControl::Signed
                     };
 
+                    let tags: Vec<_> = osm_ids
+                        .iter()
+                        .filter_map(|id| input.node_tags.get(id))
+                        .collect();
                     let id = streets.insert_intersection(osm_ids, *pt, kind, control);
+                    let intersection = streets.intersections.get_mut(&id).unwrap();
+                    for tags in tags {
+                        intersection.merge_osm_tags(tags);
+                    }
                     entry.insert(id);
                 }
             }
@@ -93,13 +101,15 @@ pub fn split_up_roads(
                     }
                 }
 
-                let untrimmed_center_line = simplify_linestring(std::mem::take(&mut pts));
+                let start_node = input.osm_node_ids.get(&pts[0].to_hashable()).cloned();
+                let untrimmed_center_line =
+                    simplify_linestring(std::mem::take(&mut pts), &streets.config);
                 match PolyLine::new(untrimmed_center_line) {
                     Ok(pl) => {
-                        streets.roads.insert(
-                            id,
-                            Road::new(id, vec![*osm_way_id], i1, *i2, pl, tags, &streets.config),
-                        );
+                        let mut road =
+                            Road::new(id, vec![*osm_way_id], i1, *i2, pl, tags, &streets.config);
+                        road.osm_start_node = start_node;
+                        streets.roads.insert(id, road);
                         streets.intersections.get_mut(&i1).unwrap().roads.push(id);
                         streets.intersections.get_mut(&i2).unwrap().roads.push(id);
                     }
@@ -119,13 +129,15 @@ pub fn split_up_roads(
         assert!(pts.len() == 1);
     }
 
+    dedupe_coincident_roads(streets, &mut pt_to_road);
+
     // Resolve simple turn restrictions (via a node)
     let mut restrictions = Vec::new();
     timer.start_iter(
         "resolve simple turn restrictions",
         input.simple_turn_restrictions.len(),
     );
-    for (restriction, from_osm, via_osm, to_osm) in input.simple_turn_restrictions {
+    for (rel_osm, restriction, from_osm, via_osm, to_osm) in input.simple_turn_restrictions {
         timer.next();
         // A via node might not be an intersection
         let via_id = if let Some(i) = streets
@@ -147,16 +159,13 @@ pub fn split_up_roads(
             roads.iter().find(|r| r.from_osm_way(from_osm)),
             roads.iter().find(|r| r.from_osm_way(to_osm)),
         ) {
-            restrictions.push((from.id, restriction, to.id));
+            restrictions.push((rel_osm, from.id, restriction, to.id));
         }
     }
-    for (from, rt, to) in restrictions {
-        streets
-            .roads
-            .get_mut(&from)
-            .unwrap()
-            .turn_restrictions
-            .push((rt, to));
+    for (rel_osm, from, rt, to) in restrictions {
+        let road = streets.roads.get_mut(&from).unwrap();
+        road.turn_restrictions.push((rt, to));
+        road.turn_restriction_relations.insert(to, rel_osm);
     }
 
     // Resolve complicated turn restrictions (via a way). TODO Only handle via ways immediately
@@ -295,6 +304,8 @@ pub fn split_up_roads(
         }
 
         if let Some(road) = pt_to_road.get(&pt).and_then(|r| streets.roads.get_mut(r)) {
+            road.parking_exclusions
+                .push((pt.to_pt2d(), ExclusionReason::Crossing));
             if let Some((dist, _)) = road.reference_line.dist_along_of_point(pt.to_pt2d()) {
                 // We don't know the direction. Arbitrarily snap to the start or end if it's within
                 // 30% of the length. If it's in the middle 40%, it might be a mid-block crossing?
@@ -310,6 +321,102 @@ pub fn split_up_roads(
         }
     }
 
+    for (pt, diameter) in input.turning_circles {
+        if let Some(i) = pt_to_intersection_id.get(&pt) {
+            streets.intersections.get_mut(i).unwrap().turning_circle =
+                Some(diameter.unwrap_or(DEFAULT_TURNING_CIRCLE_DIAMETER));
+        }
+    }
+
+    streets.kerbs = input.kerbs;
+    streets.traffic_islands = input.traffic_islands;
+    streets.highway_areas = input.highway_areas;
+    streets.ferries = input
+        .ferries
+        .into_iter()
+        .filter_map(|(id, pts, tags)| {
+            PolyLine::new(pts)
+                .ok()
+                .map(|route| Ferry::new(id, route, &tags))
+        })
+        .collect();
+    streets.indoor_paths = input
+        .indoor_paths
+        .into_iter()
+        .filter_map(|(id, pts)| PolyLine::new(pts).ok().map(|pl| (id, pl)))
+        .collect();
+    streets.block_barriers = input
+        .block_barriers
+        .into_iter()
+        .filter_map(|pts| PolyLine::new(pts).ok())
+        .collect();
+
+    // Bus stops mapped at intersections are handled like any other intersection
+    for pt in input.bus_stops {
+        if let Some(road) = pt_to_road.get(&pt).and_then(|r| streets.roads.get_mut(r)) {
+            road.parking_exclusions
+                .push((pt.to_pt2d(), ExclusionReason::BusStop));
+        }
+    }
+
+    timer.start_iter(
+        "match traffic control devices",
+        input.traffic_control_devices.len(),
+    );
+    for (node, pt, kind, dir) in input.traffic_control_devices {
+        timer.next();
+        let device = TrafficControlDevice {
+            kind,
+            pt: pt.to_pt2d(),
+            osm_node: Some(node),
+            approaches: Vec::new(),
+        };
+
+        // Devices at an intersection apply to every approach
+        if let Some(i) = pt_to_intersection_id.get(&pt) {
+            if let Some(i) = streets.intersections.get_mut(i) {
+                i.traffic_control_devices.push(device);
+            }
+            continue;
+        }
+
+        // Otherwise the device is along a road, facing one end
+        let Some(road) = pt_to_road.get(&pt).and_then(|r| streets.roads.get(r)) else {
+            continue;
+        };
+        let Some((dist, _)) = road.reference_line.dist_along_of_point(pt.to_pt2d()) else {
+            continue;
+        };
+        let len = road.reference_line.length();
+        let dir = match dir.or_else(|| road.oneway_for_driving()) {
+            Some(dir) => dir,
+            // Guess the closer end
+            None => {
+                if dist < len - dist {
+                    Direction::Backward
+                } else {
+                    Direction::Forward
+                }
+            }
+        };
+        let (i, dist_to_end) = if dir == Direction::Forward {
+            (road.dst_i, len - dist)
+        } else {
+            (road.src_i, dist)
+        };
+        // Mid-block crossings and signs far from an intersection don't control it
+        if dist_to_end > MAX_DEVICE_DIST_FROM_INTERSECTION {
+            continue;
+        }
+        let r = road.id;
+        if let Some(i) = streets.intersections.get_mut(&i) {
+            i.traffic_control_devices.push(TrafficControlDevice {
+                approaches: vec![r],
+                ..device
+            });
+        }
+    }
+
     let intersection_ids: Vec<_> = streets.intersections.keys().cloned().collect();
     timer.start_iter(
         "calculate intersection geometry and movements",
@@ -325,9 +432,177 @@ pub fn split_up_roads(
     pt_to_road
 }
 
+/// Most turning circles aren't tagged with a diameter. This is enough for a car to turn around.
+const DEFAULT_TURNING_CIRCLE_DIAMETER: Distance = Distance::const_meters(20.0);
+
+/// Signs, signals, and crossings along a road further than this from its end aren't considered
+/// part of the intersection.
+const MAX_DEVICE_DIST_FROM_INTERSECTION: Distance = Distance::const_meters(30.0);
+
+/// Sometimes two OSM ways share exactly the same nodes -- usually a mapping error, or something
+/// like a tram running along a street. Since ways are split at every shared node, any overlapping
+/// pieces become roads with the same endpoints and identical geometry. This confuses intersection
+/// geometry later, so merge duplicates with the same attributes. Separate the rest by layer, and
+/// detach the lifted road from the shared intersections, so it doesn't affect their geometry and
+/// movements.
+fn dedupe_coincident_roads(
+    streets: &mut StreetNetwork,
+    pt_to_road: &mut HashMap<HashablePt2D, RoadID>,
+) {
+    let mut roads_per_endpoints: BTreeMap<(IntersectionID, IntersectionID), Vec<RoadID>> =
+        BTreeMap::new();
+    for road in streets.roads.values() {
+        if road.src_i == road.dst_i {
+            continue;
+        }
+        let key = if road.src_i < road.dst_i {
+            (road.src_i, road.dst_i)
+        } else {
+            (road.dst_i, road.src_i)
+        };
+        roads_per_endpoints
+            .entry(key)
+            .or_insert_with(Vec::new)
+            .push(road.id);
+    }
+
+    let mut merged: HashMap<RoadID, RoadID> = HashMap::new();
+    let mut detach: Vec<RoadID> = Vec::new();
+    for group in roads_per_endpoints.into_values() {
+        for (idx, r1) in group.iter().enumerate() {
+            if merged.contains_key(r1) {
+                continue;
+            }
+            for r2 in &group[idx + 1..] {
+                if merged.contains_key(r2) {
+                    continue;
+                }
+                let road1 = &streets.roads[r1];
+                let road2 = &streets.roads[r2];
+                if !same_geometry(road1, road2) {
+                    continue;
+                }
+
+                if road1.highway_type == road2.highway_type
+                    && road1.layer == road2.layer
+                    && road1.name == road2.name
+                    && same_lanes(road1, road2)
+                {
+                    warn!(
+                        "{} and {} have identical geometry and attributes; merging them",
+                        road1.osm_ids[0], road2.osm_ids[0]
+                    );
+                    merged.insert(*r2, *r1);
+                } else if road1.layer == road2.layer {
+                    warn!(
+                        "{} and {} have identical geometry, but differ; placing them on separate layers",
+                        road1.osm_ids[0], road2.osm_ids[0]
+                    );
+                    // Keep the driveable road where it is
+                    let lift = if road1.is_driveable() { *r2 } else { *r1 };
+                    streets.roads.get_mut(&lift).unwrap().layer += 1;
+                    detach.push(lift);
+                }
+            }
+        }
+    }
+
+    for (remove, keep) in &merged {
+        let removed = streets.roads.remove(remove).unwrap();
+        for i in removed.endpoints() {
+            streets
+                .intersections
+                .get_mut(&i)
+                .unwrap()
+                .roads
+                .retain(|r| r != remove);
+        }
+        streets
+            .roads
+            .get_mut(keep)
+            .unwrap()
+            .osm_ids
+            .extend(removed.osm_ids);
+    }
+    for r in pt_to_road.values_mut() {
+        if let Some(keep) = merged.get(r) {
+            *r = *keep;
+        }
+    }
+
+    // A road can be lifted more than once
+    detach.sort();
+    detach.dedup();
+    for r in detach {
+        let road = &streets.roads[&r];
+        let ends = [
+            (road.src_i, road.reference_line.first_pt()),
+            (road.dst_i, road.reference_line.last_pt()),
+        ];
+        for (old_i, pt) in ends {
+            let kind = if streets.intersections[&old_i].kind == IntersectionKind::MapEdge {
+                IntersectionKind::MapEdge
+            } else {
+                IntersectionKind::Intersection
+            };
+            let new_i = streets.insert_intersection(
+                Vec::new(),
+                pt,
+                kind,
+                IntersectionControl::Uncontrolled,
+            );
+            streets
+                .intersections
+                .get_mut(&old_i)
+                .unwrap()
+                .roads
+                .retain(|x| *x != r);
+            streets.intersections.get_mut(&new_i).unwrap().roads.push(r);
+            let road = streets.roads.get_mut(&r).unwrap();
+            if road.src_i == old_i {
+                road.src_i = new_i;
+            } else {
+                road.dst_i = new_i;
+            }
+        }
+    }
+}
+
+fn same_geometry(road1: &Road, road2: &Road) -> bool {
+    let pts1 = road1.reference_line.points();
+    let mut pts2 = road2.reference_line.points().clone();
+    if road1.src_i != road2.src_i {
+        pts2.reverse();
+    }
+    pts1.len() == pts2.len()
+        && pts1
+            .iter()
+            .zip(pts2.iter())
+            .all(|(a, b)| a.to_hashable() == b.to_hashable())
+}
+
+fn same_lanes(road1: &Road, road2: &Road) -> bool {
+    if road1.lane_specs_ltr.len() != road2.lane_specs_ltr.len() {
+        return false;
+    }
+    if road1.src_i == road2.src_i {
+        return road1
+            .lane_specs_ltr
+            .iter()
+            .zip(road2.lane_specs_ltr.iter())
+            .all(|(a, b)| a.lt == b.lt && a.dir == b.dir);
+    }
+    // If the ways point opposite directions, the lanes should be mirrored
+    road1
+        .lane_specs_ltr
+        .iter()
+        .zip(road2.lane_specs_ltr.iter().rev())
+        .all(|(a, b)| a.lt == b.lt && a.dir == b.dir.opposite())
+}
+
 // TODO Consider doing this in PolyLine::new always. Also in extend() -- it attempts to dedupe
 // angles.
-fn simplify_linestring(pts: Vec<Pt2D>) -> Vec<Pt2D> {
+fn simplify_linestring(pts: Vec<Pt2D>, cfg: &MapConfig) -> Vec<Pt2D> {
     // Reduce the number of points along curves. They're wasteful, and when they're too close
     // together, actually break PolyLine shifting:
     // https://github.com/a-b-street/abstreet/issues/833
@@ -337,5 +612,83 @@ fn simplify_linestring(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     // got noticeably flattened. At 0.5, some intersetion polygons get a bit worse, but only in
     // places where they were already pretty broken.
     let epsilon = 0.5;
-    Pt2D::simplify_rdp(pts, epsilon)
+    let pts = Pt2D::simplify_rdp(pts, epsilon);
+
+    // Optionally simplify further, more carefully
+    osm2streets::simplify_preserving_topology(pts, cfg.reference_line_simplification)
+}
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use osm2streets::osm;
+
+    use super::*;
+
+    #[test]
+    fn test_dedupe_coincident_roads() {
+        let mut streets = StreetNetwork::blank();
+        let pt1 = Pt2D::new(0.0, 0.0);
+        let pt2 = Pt2D::new(100.0, 0.0);
+        let i1 = streets.insert_intersection(
+            Vec::new(),
+            pt1,
+            IntersectionKind::Intersection,
+            IntersectionControl::Uncontrolled,
+        );
+        let i2 = streets.insert_intersection(
+            Vec::new(),
+            pt2,
+            IntersectionKind::Intersection,
+            IntersectionControl::Uncontrolled,
+        );
+        let mut add_road = |way: i64, highway: &str, reverse: bool| {
+            let mut tags = Tags::empty();
+            tags.insert("highway", highway);
+            let id = streets.next_road_id();
+            let (src_i, dst_i, pts) = if reverse {
+                (i2, i1, vec![pt2, pt1])
+            } else {
+                (i1, i2, vec![pt1, pt2])
+            };
+            let road = Road::new(
+                id,
+                vec![osm::WayID(way)],
+                src_i,
+                dst_i,
+                PolyLine::must_new(pts),
+                tags,
+                &streets.config,
+            );
+            streets.roads.insert(id, road);
+            streets.intersections.get_mut(&i1).unwrap().roads.push(id);
+            streets.intersections.get_mut(&i2).unwrap().roads.push(id);
+            id
+        };
+        let road = add_road(1, "residential", false);
+        let duplicate = add_road(2, "residential", true);
+        let footway = add_road(3, "footway", false);
+
+        let mut pt_to_road = HashMap::new();
+        pt_to_road.insert(pt1.to_hashable(), duplicate);
+        dedupe_coincident_roads(&mut streets, &mut pt_to_road);
+
+        // The identical road is merged
+        assert!(!streets.roads.contains_key(&duplicate));
+        assert_eq!(
+            streets.roads[&road].osm_ids,
+            vec![osm::WayID(1), osm::WayID(2)]
+        );
+        assert_eq!(pt_to_road[&pt1.to_hashable()], road);
+
+        // The footway is lifted and no longer shares intersections with the road
+        let footway = &streets.roads[&footway];
+        assert_eq!(footway.layer, 1);
+        assert_eq!(streets.intersections[&i1].roads, vec![road]);
+        assert_eq!(streets.intersections[&i2].roads, vec![road]);
+        for i in footway.endpoints() {
+            assert!(i != i1 && i != i2);
+            assert_eq!(streets.intersections[&i].roads, vec![footway.id]);
+        }
+    }
 }
@@ -0,0 +1,121 @@
+//! Helpers for the bindings, which keep the original OSM ways in `StreetNetwork::osm_index` to
+//! inspect and edit them.
+
+use std::collections::BTreeSet;
+
+use abstutil::Tags;
+use anyhow::Result;
+use geom::{PolyLine, Polygon};
+use osm2streets::osm::WayID;
+use osm2streets::{buffer_with_chevrons, StreetNetwork};
+
+use crate::osm_reader::Way;
+
+/// Describes a way in OSM XML, including any tags changed by `overwrite_osm_tags_for_way`.
+pub fn way_to_xml(id: WayID, way: &Way) -> String {
+    let mut out = format!(r#"<way id="{}""#, id.0);
+    if let Some(version) = way.version {
+        out.push_str(&format!(r#" version="{version}""#));
+    }
+    out.push_str(">\n");
+    for node in &way.nodes {
+        out.push_str(&format!("  <nd ref=\"{}\"/>\n", node.0));
+    }
+    for (k, v) in way.tags.inner() {
+        out.push_str(&format!(
+            "  <tag k=\"{}\" v=\"{}\"/>\n",
+            escape_xml(k),
+            escape_xml(v)
+        ));
+    }
+    out.push_str("</way>");
+    out
+}
+
+/// Replaces all of a way's tags, then recalculates the lanes and geometry of every road made from
+/// it.
+pub fn overwrite_osm_tags_for_way(
+    streets: &mut StreetNetwork,
+    id: WayID,
+    tags: Tags,
+) -> Result<()> {
+    if streets
+        .osm_index()
+        .and_then(|index| index.way(id))
+        .is_none()
+    {
+        bail!("unknown way {id}");
+    }
+
+    let mut intersections = BTreeSet::new();
+    for road in streets.roads.values_mut() {
+        if road.from_osm_way(id) {
+            // TODO This could panic, for example if the user removes the highway tag
+            road.update_from_tags(&tags, &streets.config);
+            intersections.extend(road.endpoints());
+            road.update_center_line(streets.config.driving_side);
+        }
+    }
+    for i in intersections {
+        streets.refresh_placement_blend(i);
+        streets.update_i(i);
+    }
+
+    streets.osm_index_mut().unwrap().way_mut(id).unwrap().tags = tags;
+    Ok(())
+}
+
+/// A wide buffer around a way's original geometry, with chevrons cut out to show its direction.
+pub fn geometry_for_way(streets: &StreetNetwork, id: WayID) -> Result<Polygon> {
+    let Some(way) = streets.osm_index().and_then(|index| index.way(id)) else {
+        bail!("unknown way {id}");
+    };
+    // The lanes, and thus width, will be the same for every road belonging to the way
+    let Some(width) = streets
+        .roads
+        .values()
+        .find(|r| r.from_osm_way(id))
+        .map(|r| r.total_width())
+    else {
+        bail!("{id} isn't part of any road");
+    };
+    let polyline = PolyLine::new(way.pts.clone())?;
+    Ok(buffer_with_chevrons(&polyline, width))
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use osm2streets::osm::NodeID;
+
+    use super::*;
+
+    #[test]
+    fn test_way_to_xml() {
+        let mut tags = Tags::empty();
+        tags.insert("highway", "residential");
+        tags.insert("name", "Rose & \"Crown\" Lane");
+        let way = Way {
+            nodes: vec![NodeID(1), NodeID(2)],
+            pts: Vec::new(),
+            tags,
+            version: Some(3),
+        };
+        assert_eq!(
+            r#"<way id="42" version="3">
+  <nd ref="1"/>
+  <nd ref="2"/>
+  <tag k="highway" v="residential"/>
+  <tag k="name" v="Rose &amp; &quot;Crown&quot; Lane"/>
+</way>"#,
+            way_to_xml(WayID(42), &way)
+        );
+    }
+}
@@ -21,4 +21,27 @@ fn main() {
         writeln!(test_file, "}}").unwrap();
     }
     assert!(any, "Didn't find any tests");
+
+    // Geometry failures captured from real maps are optional
+    if let Ok(entries) = std::fs::read_dir("geometry_fixtures") {
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path.extension().map(|x| x != "json").unwrap_or(true) {
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .replace('-', "_");
+            let path = path.display().to_string();
+
+            writeln!(test_file, "#[test]").unwrap();
+            writeln!(test_file, "fn geometry_fixture_{name}() {{").unwrap();
+            writeln!(test_file, "  replay_geometry_fixture(\"{path}\").unwrap();").unwrap();
+            writeln!(test_file, "}}").unwrap();
+        }
+    }
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=geometry_fixtures");
 }
@@ -1,5 +1,6 @@
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::path::Path;
     use std::sync::Once;
 
@@ -7,13 +8,49 @@ mod tests {
     use anyhow::{bail, Result};
     use env_logger::{Builder, Env};
     use geom::LonLat;
+    use serde::Deserialize;
 
-    use osm2streets::{Filter, MapConfig, Transformation};
+    use osm2streets::{
+        DrivingSide, Filter, GeometryFixture, MapConfig, StreetNetwork, Transformation,
+    };
 
     static SETUP_LOGGER: Once = Once::new();
 
     include!(concat!(env!("OUT_DIR"), "/tests.rs"));
 
+    /// Optional settings for one test case, read from `config.json`
+    #[derive(Default, Deserialize)]
+    #[serde(default)]
+    struct TestConfig {
+        /// Defaults to `Transformation::standard_for_clipped_areas`
+        transformations: Option<Vec<Transformation>>,
+        driving_side: Option<DrivingSide>,
+        inferred_sidewalks: Option<bool>,
+        inferred_kerbs: Option<bool>,
+    }
+
+    impl TestConfig {
+        fn load(path: &str) -> Result<Self> {
+            let path = format!("{path}/config.json");
+            if !Path::new(&path).exists() {
+                return Ok(Self::default());
+            }
+            Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+        }
+
+        fn map_config(&self) -> MapConfig {
+            let mut cfg = MapConfig::default();
+            cfg.override_driving_side = self.driving_side;
+            if let Some(x) = self.inferred_sidewalks {
+                cfg.inferred_sidewalks = x;
+            }
+            if let Some(x) = self.inferred_kerbs {
+                cfg.inferred_kerbs = x;
+            }
+            cfg
+        }
+    }
+
     fn test(path: &str) -> Result<()> {
         SETUP_LOGGER
             .call_once(|| Builder::from_env(Env::default().default_filter_or("info")).init());
@@ -30,62 +67,123 @@ mod tests {
             None
         };
 
+        let config = TestConfig::load(path)?;
+
         let (mut street_network, _) = if Path::new(format!("{path}/input.osm").as_str()).exists() {
             streets_reader::osm_to_street_network(
                 &std::fs::read(format!("{path}/input.osm"))?,
                 clip_pts,
-                MapConfig::default(),
+                config.map_config(),
                 &mut timer,
             )?
         } else {
             streets_reader::osm_to_street_network(
                 &std::fs::read(format!("{path}/input.osm.pbf"))?,
                 clip_pts,
-                MapConfig::default(),
+                config.map_config(),
                 &mut timer,
             )?
         };
         street_network.check_invariants();
+
+        // Set OSM2STREETS_TEST_LANES=1 to diff-test the lanes parsed from each way, before any
+        // transformations merge them. Run this before and after changing the lane parser to see
+        // every way it affects.
+        if std::env::var("OSM2STREETS_TEST_LANES").is_ok() {
+            compare_goldenfile(path, "lanes", lanes_per_way(&street_network)?)?;
+        }
         street_network.apply_transformations_with_invariant_checks(
-            Transformation::standard_for_clipped_areas(),
+            config
+                .transformations
+                .unwrap_or_else(Transformation::standard_for_clipped_areas),
             &mut timer,
         );
 
-        // Read the output file before modifying it. If it doesn't exist, then we're creating a new
-        // test case.
-        let prior_geometry = std::fs::read_to_string(format!("{path}/geometry.json"))
-            .unwrap_or_else(|_| String::new());
-        std::fs::write(
-            format!("{path}/geometry.json"),
-            street_network.to_geojson(&Filter::All)?,
-        )?;
-        let current_geometry = std::fs::read_to_string(format!("{path}/geometry.json"))?;
-        if prior_geometry != current_geometry {
-            std::fs::write(format!("{path}/geometry.orig.json"), prior_geometry)?;
-            bail!(
-                "./{path}/geometry.json is different! If it is OK, commit it. Compare to
-                ./{path}/geometry.orig.json or use two versions of Street Explorer"
-            );
-        }
+        compare_goldenfile(path, "geometry", street_network.to_geojson(&Filter::All)?)?;
 
         // Manually enable to do diff-testing on blocks.
         if false {
-            let prior_blocks = std::fs::read_to_string(format!("{path}/blocks.json"))
-                .unwrap_or_else(|_| String::new());
-            std::fs::write(
-                format!("{path}/blocks.json"),
-                street_network.find_all_blocks(false)?,
+            compare_goldenfile(path, "blocks", street_network.find_all_blocks(false)?)?;
+        }
+
+        // Set OSM2STREETS_TEST_RENDERING=1 to also diff-test markings and surfaces
+        if std::env::var("OSM2STREETS_TEST_RENDERING").is_ok() {
+            compare_goldenfile(
+                path,
+                "lane_markings",
+                street_network.to_lane_markings_geojson(&Filter::All)?,
             )?;
-            let current_blocks = std::fs::read_to_string(format!("{path}/blocks.json"))?;
-            if prior_blocks != current_blocks {
-                std::fs::write(format!("{path}/blocks.orig.json"), prior_blocks)?;
-                bail!(
-                    "./{path}/blocks.json is different! If it is OK, commit it. Compare to
-                ./{path}/blocks.orig.json or use two versions of Street Explorer"
-                );
+            compare_goldenfile(
+                path,
+                "intersection_markings",
+                street_network.to_intersection_markings_geojson(&Filter::All)?,
+            )?;
+            compare_goldenfile(path, "surfaces", street_network.to_surfaces_geojson()?)?;
+        }
+
+        Ok(())
+    }
+
+    /// For every OSM way, the number of lanes, plus the type, direction, and width of each, as
+    /// `Road::cross_section_sketch`. Ways split into several roads list each distinct result.
+    fn lanes_per_way(streets: &StreetNetwork) -> Result<String> {
+        let mut per_way: BTreeMap<i64, Vec<(usize, String)>> = BTreeMap::new();
+        for road in streets.roads.values() {
+            let result = (road.lane_specs_ltr.len(), road.cross_section_sketch());
+            for way in &road.osm_ids {
+                let list = per_way.entry(way.0).or_insert_with(Vec::new);
+                if !list.contains(&result) {
+                    list.push(result.clone());
+                }
             }
         }
+        let json: serde_json::Map<String, serde_json::Value> = per_way
+            .into_iter()
+            .map(|(way, list)| {
+                let list = list
+                    .into_iter()
+                    .map(|(num_lanes, sketch)| {
+                        serde_json::json!({ "num_lanes": num_lanes, "lanes": sketch })
+                    })
+                    .collect();
+                (format!("https://www.openstreetmap.org/way/{way}"), list)
+            })
+            .collect();
+        Ok(serde_json::to_string(&json)?)
+    }
 
+    /// Writes `{path}/{name}.json`, failing if it was different before. If it doesn't exist, then
+    /// we're creating a new test case.
+    fn compare_goldenfile(path: &str, name: &str, output: String) -> Result<()> {
+        let prior = std::fs::read_to_string(format!("{path}/{name}.json"))
+            .unwrap_or_else(|_| String::new());
+        std::fs::write(format!("{path}/{name}.json"), pretty(output)?)?;
+        let current = std::fs::read_to_string(format!("{path}/{name}.json"))?;
+        if prior != current {
+            std::fs::write(format!("{path}/{name}.orig.json"), prior)?;
+            bail!(
+                "./{path}/{name}.json is different! If it is OK, commit it. Compare to
+                ./{path}/{name}.orig.json or use two versions of Street Explorer"
+            );
+        }
         Ok(())
     }
+
+    /// Replays a captured intersection geometry failure. It should succeed once the bug is fixed.
+    fn replay_geometry_fixture(path: &str) -> Result<()> {
+        let fixture = GeometryFixture::from_json(&std::fs::read_to_string(path)?)?;
+        if let Err(err) = fixture.replay() {
+            bail!(
+                "{path} still fails: {err}. It originally failed with: {}",
+                fixture.error
+            );
+        }
+        Ok(())
+    }
+
+    /// Output is compact, but goldenfiles are pretty-printed to make diffs readable
+    fn pretty(output: String) -> Result<String> {
+        let value: serde_json::Value = serde_json::from_str(&output)?;
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
 }